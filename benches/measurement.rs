@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Measures the cost of computing the launch digest of large guests.
+//!
+//! The VMSA benchmarks compare hashing the serialized page of every vCPU
+//! against hashing each distinct page once. The page benchmark measures the
+//! hashing throughput of guest memory; run it with and without the
+//! `parallel` feature to compare the sequential and parallel pipelines.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use sev::measurement::{
+    gctx::{LaunchDigest, PageUpdate, VmsaDigests},
+    vcpu_types::CpuType,
+    vmsa::{GuestFeatures, VMMType, VMSA},
+};
+
+const VCPUS: usize = 256;
+const GUEST_SIZE: usize = 16 << 20;
+
+fn vmsas(c: &mut Criterion) {
+    let vmsa: VMSA = VMSA::new(
+        0xffff_fff0,
+        CpuType::EpycV4,
+        VMMType::QEMU,
+        Some(VCPUS as u64),
+        GuestFeatures(0x1),
+    );
+
+    let mut group = c.benchmark_group("snp_vmsas");
+    group.throughput(Throughput::Elements(VCPUS as u64));
+
+    group.bench_function("per_vcpu", |b| {
+        b.iter(|| {
+            let mut digest: LaunchDigest = LaunchDigest::new();
+            for page in vmsa.pages(VCPUS).unwrap().iter() {
+                digest
+                    .update(black_box(&PageUpdate::Vmsa { page }))
+                    .unwrap();
+            }
+
+            digest.digest()
+        })
+    });
+
+    group.bench_function("deduplicated", |b| {
+        b.iter(|| {
+            let mut digest: LaunchDigest = LaunchDigest::new();
+            digest.vmsas(black_box(&vmsa), VCPUS).unwrap();
+
+            digest.digest()
+        })
+    });
+
+    let digests: VmsaDigests = VmsaDigests::new(&vmsa).unwrap();
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            let mut digest: LaunchDigest = LaunchDigest::new();
+            digest.vmsa_digests(black_box(&digests), VCPUS).unwrap();
+
+            digest.digest()
+        })
+    });
+
+    group.finish();
+}
+
+fn pages(c: &mut Criterion) {
+    let memory: Vec<u8> = (0..GUEST_SIZE).map(|i| i as u8).collect();
+
+    let mut group = c.benchmark_group("snp_pages");
+    group.throughput(Throughput::Bytes(GUEST_SIZE as u64));
+    group.sample_size(10);
+
+    group.bench_function("normal", |b| {
+        b.iter(|| {
+            let mut digest: LaunchDigest = LaunchDigest::new();
+            digest
+                .update(black_box(&PageUpdate::Normal {
+                    gpa: 0,
+                    data: &memory,
+                }))
+                .unwrap();
+
+            digest.digest()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, vmsas, pages);
+criterion_main!(benches);
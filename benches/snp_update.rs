@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compares encrypting guest memory with one SNP_LAUNCH_UPDATE per page
+//! against a coalesced [UpdateBatch].
+//!
+//! The VM is simulated, with a fixed cost per command standing in for the
+//! ioctl round trip, so the benchmark measures the command overhead which
+//! batching removes rather than the firmware's encryption throughput.
+
+use std::{
+    hint::black_box,
+    io,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use sev::launch::{
+    snp::*,
+    vmm::{EncryptOp, VmHandle},
+};
+
+const GUEST_SIZE: usize = 16 << 20;
+const COMMAND_COST: Duration = Duration::from_micros(5);
+
+/// A VM which spins for [COMMAND_COST] on every command.
+struct SimulatedVm;
+
+impl VmHandle for SimulatedVm {
+    fn encrypt_op(&mut self, _op: &mut EncryptOp) -> io::Result<()> {
+        let begin: Instant = Instant::now();
+        while begin.elapsed() < COMMAND_COST {
+            std::hint::spin_loop();
+        }
+
+        Ok(())
+    }
+
+    fn register_region(&mut self, _addr: u64, _size: u64) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn pages(memory: &[u8]) -> impl Iterator<Item = Update<'_>> {
+    let dp: VmplPerms = VmplPerms::empty();
+
+    memory
+        .chunks(PAGE_SIZE)
+        .enumerate()
+        .map(move |(gfn, page)| {
+            Update::new(gfn as u64, page, false, PageType::Normal, (dp, dp, dp))
+        })
+}
+
+fn launch_update(c: &mut Criterion) {
+    let buffer: Vec<u8> = vec![0; GUEST_SIZE + PAGE_SIZE];
+    let offset: usize = buffer.as_ptr().align_offset(PAGE_SIZE);
+    let memory: &[u8] = &buffer[offset..offset + GUEST_SIZE];
+
+    let mut launcher = Launcher::new(SimulatedVm, -1)
+        .unwrap()
+        .start(Start::default())
+        .unwrap();
+
+    let mut group = c.benchmark_group("snp_launch_update");
+    group.throughput(Throughput::Bytes(GUEST_SIZE as u64));
+    group.sample_size(10);
+
+    group.bench_function("per_page", |b| {
+        b.iter(|| {
+            for update in pages(memory) {
+                launcher.update_data(black_box(update)).unwrap();
+            }
+        })
+    });
+
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            let mut batch: UpdateBatch = UpdateBatch::new();
+            for update in pages(memory) {
+                batch.push(update).unwrap();
+            }
+
+            launcher.update_batch(black_box(&batch)).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, launch_update);
+criterion_main!(benches);
@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: Apache-2.0
+/*
+ * AMD SEV-SNP attestation report parsing and verification
+ *
+ * Built with the "capi" feature. See sev(3) for API documentation.
+ *
+ * Generated from src/capi.rs by cbindgen; do not edit.
+ */
+
+#ifndef _RUST_SEV_CAPI_H
+#define _RUST_SEV_CAPI_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * A pointer argument was NULL.
+ */
+#define SEV_CAPI_ERR_NULL -1
+
+/**
+ * A buffer was too short, or a report was not exactly one report long.
+ */
+#define SEV_CAPI_ERR_LENGTH -2
+
+/**
+ * An argument selecting a format, field or TCB was out of range.
+ */
+#define SEV_CAPI_ERR_ARGUMENT -3
+
+/**
+ * The report or a certificate could not be parsed.
+ */
+#define SEV_CAPI_ERR_PARSE -4
+
+/**
+ * The certificate chain or the report signature did not verify.
+ */
+#define SEV_CAPI_ERR_VERIFY -5
+
+/**
+ * Certificates are DER-encoded.
+ */
+#define SEV_CAPI_CERT_DER 0
+
+/**
+ * Certificates are PEM-encoded.
+ */
+#define SEV_CAPI_CERT_PEM 1
+
+/**
+ * The launch measurement (48 bytes).
+ */
+#define SEV_SNP_FIELD_MEASUREMENT 0
+
+/**
+ * The guest-provided report data (64 bytes).
+ */
+#define SEV_SNP_FIELD_REPORT_DATA 1
+
+/**
+ * The host data provided at launch (32 bytes).
+ */
+#define SEV_SNP_FIELD_HOST_DATA 2
+
+/**
+ * The family ID provided at launch (16 bytes).
+ */
+#define SEV_SNP_FIELD_FAMILY_ID 3
+
+/**
+ * The image ID provided at launch (16 bytes).
+ */
+#define SEV_SNP_FIELD_IMAGE_ID 4
+
+/**
+ * The digest of the ID key (48 bytes).
+ */
+#define SEV_SNP_FIELD_ID_KEY_DIGEST 5
+
+/**
+ * The digest of the author key (48 bytes).
+ */
+#define SEV_SNP_FIELD_AUTHOR_KEY_DIGEST 6
+
+/**
+ * The report ID of the guest (32 bytes).
+ */
+#define SEV_SNP_FIELD_REPORT_ID 7
+
+/**
+ * The report ID of the guest's migration agent (32 bytes).
+ */
+#define SEV_SNP_FIELD_REPORT_ID_MA 8
+
+/**
+ * The chip ID, zeroes if masked (64 bytes).
+ */
+#define SEV_SNP_FIELD_CHIP_ID 9
+
+/**
+ * The current TCB.
+ */
+#define SEV_SNP_TCB_CURRENT 0
+
+/**
+ * The TCB the VCEK signing the report was derived from.
+ */
+#define SEV_SNP_TCB_REPORTED 1
+
+/**
+ * The committed TCB.
+ */
+#define SEV_SNP_TCB_COMMITTED 2
+
+/**
+ * The current TCB when the guest was launched.
+ */
+#define SEV_SNP_TCB_LAUNCH 3
+
+/**
+ * An SEV-SNP attestation report parsed for C callers.
+ */
+typedef struct SevSnpReport SevSnpReport;
+
+/**
+ * The SVNs of a TCB version.
+ */
+typedef struct SevSnpTcb {
+  /**
+   * SVN of the PSP bootloader.
+   */
+  uint8_t bootloader;
+  /**
+   * SVN of the PSP operating system.
+   */
+  uint8_t tee;
+  /**
+   * SVN of the SNP firmware.
+   */
+  uint8_t snp;
+  /**
+   * Lowest patch level of all the cores.
+   */
+  uint8_t microcode;
+} SevSnpTcb;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parse the `len` bytes at `bytes` as an attestation report, storing a
+ * handle to it in `report`.
+ *
+ * # Safety
+ *
+ * The caller of this function is responsible for ensuring that the pointer arguments are
+ * valid.
+ */
+int sev_snp_report_parse(const uint8_t *bytes, size_t len, struct SevSnpReport **report);
+
+/**
+ * Release a report returned by [sev_snp_report_parse]. NULL is ignored.
+ *
+ * # Safety
+ *
+ * `report` must have been returned by [sev_snp_report_parse] and not already been released.
+ */
+void sev_snp_report_free(struct SevSnpReport *report);
+
+/**
+ * Verify that the ARK signs the ASK, the ASK signs the VCEK or VLEK, and
+ * the VCEK or VLEK signs `report`. The certificates are encoded in
+ * `format`.
+ *
+ * # Safety
+ *
+ * The caller of this function is responsible for ensuring that the pointer arguments are
+ * valid.
+ */
+int sev_snp_report_verify(const struct SevSnpReport *report,
+                          int format,
+                          const uint8_t *ark,
+                          size_t ark_len,
+                          const uint8_t *ask,
+                          size_t ask_len,
+                          const uint8_t *vek,
+                          size_t vek_len);
+
+/**
+ * The version of the report format, or 0 if `report` is NULL.
+ *
+ * # Safety
+ *
+ * `report` must be NULL or have been returned by [sev_snp_report_parse].
+ */
+uint32_t sev_snp_report_version(const struct SevSnpReport *report);
+
+/**
+ * The guest SVN, or 0 if `report` is NULL.
+ *
+ * # Safety
+ *
+ * `report` must be NULL or have been returned by [sev_snp_report_parse].
+ */
+uint32_t sev_snp_report_guest_svn(const struct SevSnpReport *report);
+
+/**
+ * The guest policy, or 0 if `report` is NULL.
+ *
+ * # Safety
+ *
+ * `report` must be NULL or have been returned by [sev_snp_report_parse].
+ */
+uint64_t sev_snp_report_policy(const struct SevSnpReport *report);
+
+/**
+ * The VMPL the report was requested at, or 0 if `report` is NULL.
+ *
+ * # Safety
+ *
+ * `report` must be NULL or have been returned by [sev_snp_report_parse].
+ */
+uint32_t sev_snp_report_vmpl(const struct SevSnpReport *report);
+
+/**
+ * Copy the report field selected by `field` (a `SEV_SNP_FIELD_*`) to the
+ * `len` bytes at `out`, returning the length of the field.
+ *
+ * # Safety
+ *
+ * The caller of this function is responsible for ensuring that the pointer arguments are
+ * valid.
+ */
+int sev_snp_report_field(const struct SevSnpReport *report, int field, uint8_t *out, size_t len);
+
+/**
+ * Store the TCB version selected by `tcb` (a `SEV_SNP_TCB_*`) in `out`.
+ *
+ * # Safety
+ *
+ * The caller of this function is responsible for ensuring that the pointer arguments are
+ * valid.
+ */
+int sev_snp_report_tcb(const struct SevSnpReport *report, int tcb, struct SevSnpTcb *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* _RUST_SEV_CAPI_H */
@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Python bindings to parse SEV-SNP attestation reports, fetch the
+//! certificates endorsing them from the AMD Key Distribution Server (KDS)
+//! and verify them, for attestation services written in Python.
+//!
+//! # Example:
+//! ```python
+//! import sev
+//!
+//! report = sev.AttestationReport.from_bytes(raw)
+//! ark, ask = sev.fetch_ca("milan")
+//! vcek = sev.fetch_vcek("milan", report)
+//! sev.verify_report(report, ark, ask, vcek)
+//! ```
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+
+use sev::{
+    certs::snp::{ca, Certificate, Chain, Verifiable},
+    firmware::{guest::AttestationReport as Report, host::TcbVersion as Tcb},
+};
+
+use std::convert::TryFrom;
+
+/// The SVNs of a TCB version.
+#[pyclass(frozen, get_all, module = "sev")]
+#[derive(Clone)]
+struct TcbVersion {
+    /// SVN of the PSP bootloader.
+    bootloader: u8,
+    /// SVN of the PSP operating system.
+    tee: u8,
+    /// SVN of the SNP firmware.
+    snp: u8,
+    /// Lowest patch level of all the cores.
+    microcode: u8,
+}
+
+impl From<Tcb> for TcbVersion {
+    fn from(tcb: Tcb) -> Self {
+        Self {
+            bootloader: tcb.bootloader,
+            tee: tcb.tee,
+            snp: tcb.snp,
+            microcode: tcb.microcode,
+        }
+    }
+}
+
+#[pymethods]
+impl TcbVersion {
+    fn __repr__(&self) -> String {
+        format!(
+            "TcbVersion(bootloader={}, tee={}, snp={}, microcode={})",
+            self.bootloader, self.tee, self.snp, self.microcode
+        )
+    }
+}
+
+/// An SEV-SNP attestation report.
+#[pyclass(frozen, module = "sev")]
+struct AttestationReport(Report);
+
+#[pymethods]
+impl AttestationReport {
+    /// Parse a report from its raw bytes.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        Report::try_from(bytes).map(Self).map_err(|_| {
+            PyValueError::new_err(format!(
+                "an attestation report is {} bytes, not {}",
+                Report::SIZE,
+                bytes.len()
+            ))
+        })
+    }
+
+    /// The raw bytes of the report.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.to_bytes())
+    }
+
+    /// Version of the report format.
+    #[getter]
+    fn version(&self) -> u32 {
+        self.0.version
+    }
+
+    /// Guest SVN.
+    #[getter]
+    fn guest_svn(&self) -> u32 {
+        self.0.guest_svn
+    }
+
+    /// Guest policy.
+    #[getter]
+    fn policy(&self) -> u64 {
+        self.0.policy.into()
+    }
+
+    /// VMPL the report was requested at.
+    #[getter]
+    fn vmpl(&self) -> u32 {
+        self.0.vmpl
+    }
+
+    /// Launch measurement.
+    #[getter]
+    fn measurement<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.measurement)
+    }
+
+    /// Guest-provided report data.
+    #[getter]
+    fn report_data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.report_data)
+    }
+
+    /// Host data provided at launch.
+    #[getter]
+    fn host_data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.host_data)
+    }
+
+    /// Digest of the ID key.
+    #[getter]
+    fn id_key_digest<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.id_key_digest)
+    }
+
+    /// Digest of the author key.
+    #[getter]
+    fn author_key_digest<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.author_key_digest)
+    }
+
+    /// Report ID of the guest.
+    #[getter]
+    fn report_id<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.report_id)
+    }
+
+    /// Chip ID, zeroes if masked.
+    #[getter]
+    fn chip_id<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.chip_id)
+    }
+
+    /// The current TCB.
+    #[getter]
+    fn current_tcb(&self) -> TcbVersion {
+        self.0.current_tcb.into()
+    }
+
+    /// The TCB the VCEK signing the report was derived from.
+    #[getter]
+    fn reported_tcb(&self) -> TcbVersion {
+        self.0.reported_tcb.into()
+    }
+
+    /// The committed TCB.
+    #[getter]
+    fn committed_tcb(&self) -> TcbVersion {
+        self.0.committed_tcb.into()
+    }
+
+    /// The current TCB when the guest was launched.
+    #[getter]
+    fn launch_tcb(&self) -> TcbVersion {
+        self.0.launch_tcb.into()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// Parse a PEM or DER-encoded certificate.
+fn certificate(bytes: &[u8]) -> PyResult<Certificate> {
+    let cert = if bytes.starts_with(b"-----BEGIN") {
+        Certificate::from_pem(bytes)
+    } else {
+        Certificate::from_der(bytes)
+    };
+
+    cert.map_err(|e| PyValueError::new_err(format!("invalid certificate: {e}")))
+}
+
+/// Parse the PEM or DER-encoded ARK, ASK and VCEK or VLEK.
+fn chain(ark: &[u8], ask: &[u8], vek: &[u8]) -> PyResult<Chain> {
+    Ok(Chain {
+        ca: ca::Chain {
+            ark: certificate(ark)?,
+            ask: certificate(ask)?,
+        },
+        vek: certificate(vek)?,
+    })
+}
+
+/// Verify that the ARK is self-signed, the ARK signs the ASK and the ASK
+/// signs the VCEK or VLEK, raising ValueError otherwise.
+#[pyfunction]
+fn verify_chain(ark: &[u8], ask: &[u8], vek: &[u8]) -> PyResult<()> {
+    chain(ark, ask, vek)?
+        .verify()
+        .map(|_| ())
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Verify the certificate chain and that the VCEK or VLEK signs `report`,
+/// raising ValueError otherwise.
+#[pyfunction]
+fn verify_report(
+    report: PyRef<'_, AttestationReport>,
+    ark: &[u8],
+    ask: &[u8],
+    vek: &[u8],
+) -> PyResult<()> {
+    (&chain(ark, ask, vek)?, &report.0)
+        .verify()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Requests to the AMD Key Distribution Server.
+#[cfg(feature = "kds")]
+mod kds {
+    use super::AttestationReport;
+
+    use pyo3::{
+        exceptions::{PyIOError, PyValueError},
+        prelude::*,
+        types::PyBytes,
+    };
+
+    use sev::{
+        firmware::host::{Identifier, PlatformId, KDS_URL},
+        Generation,
+    };
+
+    use std::{convert::TryFrom, io::Read};
+
+    /// Parse a processor generation, e.g. "milan" or "genoa".
+    fn generation(name: &str) -> PyResult<Generation> {
+        Generation::try_from(name.to_string())
+            .map_err(|_| PyValueError::new_err(format!("unknown processor generation {name}")))
+    }
+
+    /// The body of the response to a GET request of `url`.
+    fn get(url: &str) -> PyResult<Vec<u8>> {
+        let response = ureq::get(url)
+            .call()
+            .map_err(|e| PyIOError::new_err(format!("{url}: {e}")))?;
+
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body)?;
+
+        Ok(body)
+    }
+
+    /// Fetch the PEM-encoded ARK and ASK of `generation`, returned as an
+    /// (ark, ask) tuple.
+    #[pyfunction]
+    pub fn fetch_ca<'py>(
+        py: Python<'py>,
+        generation: &str,
+    ) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+        let url = format!(
+            "{KDS_URL}/vcek/v1/{}/cert_chain",
+            self::generation(generation)?.titlecase()
+        );
+        let body = py.allow_threads(|| get(&url))?;
+
+        // The KDS returns the ASK first, followed by the ARK.
+        const END: &[u8] = b"-----END CERTIFICATE-----";
+        let split = body
+            .windows(END.len())
+            .position(|w| w == END)
+            .map(|pos| pos + END.len())
+            .ok_or_else(|| PyValueError::new_err("the KDS returned no certificates"))?;
+
+        let (ask, ark) = body.split_at(split);
+        let start = ark
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(ark.len());
+
+        Ok((
+            PyBytes::new_bound(py, &ark[start..]),
+            PyBytes::new_bound(py, ask),
+        ))
+    }
+
+    /// Fetch the DER-encoded VCEK of `generation` which signs `report`,
+    /// derived from its chip ID and reported TCB.
+    #[pyfunction]
+    pub fn fetch_vcek<'py>(
+        py: Python<'py>,
+        generation: &str,
+        report: PyRef<'_, AttestationReport>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        if report.0.chip_id.iter().all(|b| *b == 0) {
+            return Err(PyValueError::new_err("the report's chip ID is masked"));
+        }
+
+        let url = PlatformId::from(Identifier(report.0.chip_id.to_vec()))
+            .vcek_url(0, self::generation(generation)?, &report.0.reported_tcb)
+            .ok_or_else(|| PyValueError::new_err("the report has no chip ID"))?;
+        let der = py.allow_threads(|| get(&url))?;
+
+        Ok(PyBytes::new_bound(py, &der))
+    }
+}
+
+/// The `sev` Python module.
+#[pymodule]
+#[pyo3(name = "sev")]
+fn sev_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<AttestationReport>()?;
+    m.add_class::<TcbVersion>()?;
+    m.add_function(wrap_pyfunction!(verify_chain, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_report, m)?)?;
+
+    #[cfg(feature = "kds")]
+    {
+        m.add_function(wrap_pyfunction!(kds::fetch_ca, m)?)?;
+        m.add_function(wrap_pyfunction!(kds::fetch_vcek, m)?)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+# SPDX-License-Identifier: Apache-2.0
+
+from pathlib import Path
+
+import pytest
+
+import sev
+
+ROOT = Path(__file__).resolve().parents[2]
+DATA = ROOT / "tests" / "certs_data"
+MILAN = ROOT / "src" / "certs" / "snp" / "builtin" / "milan"
+
+
+def raw_report():
+    return bytes.fromhex((DATA / "report_milan.hex").read_text())
+
+
+def certs():
+    return (
+        (MILAN / "ark.pem").read_bytes(),
+        (MILAN / "ask.pem").read_bytes(),
+        (DATA / "vcek_milan.der").read_bytes(),
+    )
+
+
+def test_parse():
+    raw = raw_report()
+    report = sev.AttestationReport.from_bytes(raw)
+
+    assert report.to_bytes() == raw
+    assert len(report.measurement) == 48
+    assert len(report.report_data) == 64
+    assert len(report.chip_id) == 64
+    assert report.reported_tcb.snp == report.to_bytes()[0x186]
+
+
+def test_parse_invalid_length():
+    with pytest.raises(ValueError):
+        sev.AttestationReport.from_bytes(raw_report()[1:])
+
+
+def test_verify_chain():
+    sev.verify_chain(*certs())
+
+
+def test_verify_report():
+    report = sev.AttestationReport.from_bytes(raw_report())
+    sev.verify_report(report, *certs())
+
+
+def test_verify_report_tampered():
+    raw = bytearray(raw_report())
+    raw[0x90] ^= 0xFF
+    report = sev.AttestationReport.from_bytes(bytes(raw))
+
+    with pytest.raises(ValueError):
+        sev.verify_report(report, *certs())
@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Allow-lists of known-good launch measurements.
+//!
+//! An [AllowList] records the measurements a guest image is expected to
+//! launch with, keyed by the product it runs on, the OVMF build and the
+//! kernel version it boots. An image usually has several measurements, one
+//! per vCPU count or VMM it is launched with. Each entry may additionally
+//! require a minimum TCB, for images only trusted on patched firmware.
+//!
+//! Allow-lists are stored as JSON documents with a
+//! [versioned envelope](crate::snapshot), so that they can be reviewed and
+//! updated by release pipelines, and are consulted by the appraisal when passed in
+//! the [AppraisalContext](super::AppraisalContext).
+//!
+//! # Example:
+//! ```ignore
+//! let mut list = AllowList::load("allowlist.json")?;
+//!
+//! let key = MeasurementKey::new("Milan", "edk2-stable202402", "6.8.0-31");
+//! list.allow(key, measurement);
+//! list.save("allowlist.json")?;
+//! ```
+
+use super::MinTcb;
+
+use crate::{
+    encoding::Measurement48, error::SnapshotError, firmware::host::TcbVersion, snapshot::Versioned,
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fmt::{self, Display},
+    io::{Read, Write},
+    path::Path,
+};
+
+/// What a set of measurements is known-good for
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct MeasurementKey {
+    /// The product the guest runs on, such as `Milan` or `Genoa`.
+    pub product: String,
+
+    /// The OVMF build the guest boots.
+    pub ovmf: String,
+
+    /// The kernel version the guest boots.
+    pub kernel: String,
+}
+
+impl MeasurementKey {
+    /// Key measurements by product, OVMF build and kernel version.
+    pub fn new(
+        product: impl Into<String>,
+        ovmf: impl Into<String>,
+        kernel: impl Into<String>,
+    ) -> Self {
+        Self {
+            product: product.into(),
+            ovmf: ovmf.into(),
+            kernel: kernel.into(),
+        }
+    }
+}
+
+impl Display for MeasurementKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "OVMF {} and kernel {} on {}",
+            self.ovmf, self.kernel, self.product
+        )
+    }
+}
+
+/// The known-good measurements of one guest image
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AllowListEntry {
+    /// What the measurements are known-good for.
+    pub key: MeasurementKey,
+
+    /// The known-good launch measurements.
+    pub measurements: Vec<Measurement48>,
+
+    /// The minimum TCB the measurements are only trusted with, if any.
+    #[serde(default)]
+    pub min_tcb: Option<MinTcb>,
+}
+
+impl AllowListEntry {
+    /// Whether the entry allows `measurement` with the reported `tcb`.
+    pub fn allows(&self, measurement: &Measurement48, tcb: &TcbVersion) -> bool {
+        self.measurements.contains(measurement)
+            && self.min_tcb.map_or(true, |min| min.is_met_by(tcb))
+    }
+}
+
+/// A store of known-good launch measurements
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AllowList {
+    /// The known-good measurements, one entry per key.
+    pub entries: Vec<AllowListEntry>,
+}
+
+impl Versioned for AllowList {
+    const KIND: [u8; 4] = *b"ALST";
+    const VERSION: u16 = 1;
+}
+
+impl AllowList {
+    /// An empty allow-list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read an allow-list from JSON.
+    pub fn from_reader(reader: impl Read) -> Result<Self, SnapshotError> {
+        Self::from_json_reader(reader)
+    }
+
+    /// Write the allow-list as JSON.
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), SnapshotError> {
+        self.to_json_writer(writer)
+    }
+
+    /// Read an allow-list from the JSON file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::load_json(path)
+    }
+
+    /// Write the allow-list as JSON to the file at `path`.
+    ///
+    /// The list is written next to `path` first and then renamed over it,
+    /// so that verifiers reading the file never see a partial list.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        self.save_json(path)
+    }
+
+    /// The entry for `key`, if any.
+    pub fn get(&self, key: &MeasurementKey) -> Option<&AllowListEntry> {
+        self.entries.iter().find(|entry| &entry.key == key)
+    }
+
+    /// Insert `entry`, returning the entry it replaces for the same key.
+    pub fn insert(&mut self, entry: AllowListEntry) -> Option<AllowListEntry> {
+        match self.entries.iter_mut().find(|e| e.key == entry.key) {
+            Some(existing) => Some(std::mem::replace(existing, entry)),
+            None => {
+                self.entries.push(entry);
+                None
+            }
+        }
+    }
+
+    /// Add `measurement` to the entry for `key`, creating it if needed.
+    ///
+    /// Returns whether the measurement was not allowed for `key` before.
+    pub fn allow(&mut self, key: MeasurementKey, measurement: impl Into<Measurement48>) -> bool {
+        let measurement = measurement.into();
+
+        let entry = match self.entries.iter().position(|e| e.key == key) {
+            Some(index) => &mut self.entries[index],
+            None => {
+                self.entries.push(AllowListEntry {
+                    key,
+                    measurements: vec![],
+                    min_tcb: None,
+                });
+                self.entries.last_mut().unwrap()
+            }
+        };
+
+        if entry.measurements.contains(&measurement) {
+            return false;
+        }
+
+        entry.measurements.push(measurement);
+        true
+    }
+
+    /// Remove the entry for `key`, if any.
+    pub fn remove(&mut self, key: &MeasurementKey) -> Option<AllowListEntry> {
+        let index = self.entries.iter().position(|e| &e.key == key)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// The entries for `product`.
+    pub fn product<'a>(&'a self, product: &'a str) -> impl Iterator<Item = &'a AllowListEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.key.product == product)
+    }
+
+    /// The first entry allowing `measurement` with the reported `tcb`,
+    /// restricted to `product` if given.
+    pub fn find(
+        &self,
+        product: Option<&str>,
+        measurement: &Measurement48,
+        tcb: &TcbVersion,
+    ) -> Option<&AllowListEntry> {
+        self.entries.iter().find(|entry| {
+            product.map_or(true, |p| entry.key.product == p) && entry.allows(measurement, tcb)
+        })
+    }
+}
@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Binding a TLS channel into the report data.
+//!
+//! In attested TLS, the guest proves that the TLS channel a relying party is
+//! talking over ends in the guest by binding the channel into the report
+//! data of its report. The channel is identified either by keying material
+//! exported from the session (RFC 5705, RFC 8446 section 7.5) with
+//! [TLS_EXPORTER_LABEL], which differs between sessions, or by the DER
+//! encoding of the certificate the guest authenticates the channel with.
+//!
+//! The report data starts with the relying party's nonce, if any, so that a
+//! [NonceManager](super::NonceManager) checks it as usual. The rest of the
+//! report data holds the start of the SHA-512 digest of the channel binding,
+//! prefixed with a label and the kind of binding:
+//!
+//! ```text
+//! report_data = nonce || SHA-512("AMD SEV-SNP TLS binding v1" || 0x00 || kind || material)[..64 - len(nonce)]
+//! ```
+//!
+//! where `kind` is 1 for exported keying material and 2 for a certificate.
+//!
+//! # Example:
+//! ```ignore
+//! // In the guest:
+//! let binding = TlsBinding::exporter(tls.export_keying_material(TLS_EXPORTER_LABEL, TLS_EXPORTER_LENGTH)?);
+//! let report = firmware.get_report(None, Some(binding.report_data(&nonce)?.into_inner()), None)?;
+//!
+//! // In the relying party, after verifying the report:
+//! let binding = TlsBinding::exporter(tls.export_keying_material(TLS_EXPORTER_LABEL, TLS_EXPORTER_LENGTH)?);
+//! binding.check(&report, &nonce)?;
+//! ```
+
+use crate::{
+    encoding::ReportData, error::BindingError, firmware::guest::AttestationReport,
+    util::digest::sha512,
+};
+
+/// The label to export the keying material bound into reports with.
+pub const TLS_EXPORTER_LABEL: &str = "EXPORTER-AMD-SEV-SNP-report-data";
+
+/// The length of the keying material to export, in bytes.
+pub const TLS_EXPORTER_LENGTH: usize = 32;
+
+/// The longest nonce bound next to a channel, in bytes, which leaves at least
+/// 32 bytes of the digest of the channel binding.
+pub const MAX_BOUND_NONCE_LENGTH: usize = 32;
+
+/// The label the channel binding is digested with.
+const DIGEST_LABEL: &[u8] = b"AMD SEV-SNP TLS binding v1\0";
+
+/// The TLS channel a guest binds into its reports
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TlsBinding {
+    /// Keying material exported from the TLS session.
+    Exporter(Vec<u8>),
+
+    /// The DER encoding of the certificate the guest authenticates the
+    /// channel with.
+    Certificate(Vec<u8>),
+}
+
+impl TlsBinding {
+    /// Bind keying material exported with [TLS_EXPORTER_LABEL].
+    pub fn exporter(material: impl Into<Vec<u8>>) -> Self {
+        Self::Exporter(material.into())
+    }
+
+    /// Bind the DER-encoded certificate of the guest's end of the channel.
+    pub fn certificate(der: impl Into<Vec<u8>>) -> Self {
+        Self::Certificate(der.into())
+    }
+
+    /// The report data which binds the channel, starting with `nonce`.
+    pub fn report_data(&self, nonce: &[u8]) -> Result<ReportData, BindingError> {
+        let (kind, material) = match self {
+            Self::Exporter(material) => (1u8, material),
+            Self::Certificate(der) => (2u8, der),
+        };
+
+        bind(DIGEST_LABEL, &[&[kind], material], nonce)
+    }
+
+    /// Check that `report` binds the channel, with its report data starting
+    /// with `nonce`.
+    ///
+    /// Only the report data is checked: verify the report's signature, and
+    /// the nonce's freshness, separately.
+    pub fn check(&self, report: &AttestationReport, nonce: &[u8]) -> Result<(), BindingError> {
+        check(&self.report_data(nonce)?, report)
+    }
+}
+
+/// The report data starting with `nonce` and followed by the start of the
+/// SHA-512 digest of `label` and `parts`.
+pub(super) fn bind(
+    label: &[u8],
+    parts: &[&[u8]],
+    nonce: &[u8],
+) -> Result<ReportData, BindingError> {
+    if nonce.len() > MAX_BOUND_NONCE_LENGTH {
+        return Err(BindingError::NonceTooLong {
+            max: MAX_BOUND_NONCE_LENGTH,
+            actual: nonce.len(),
+        });
+    }
+
+    let digest = sha512(&[&[label], parts].concat());
+
+    let mut report_data = ReportData::default();
+    let (start, rest) = report_data.0.split_at_mut(nonce.len());
+    start.copy_from_slice(nonce);
+    rest.copy_from_slice(&digest[..rest.len()]);
+
+    Ok(report_data)
+}
+
+/// Check that the report data of `report` is `expected`.
+pub(super) fn check(expected: &ReportData, report: &AttestationReport) -> Result<(), BindingError> {
+    // Compare without leaking where the report data first differs.
+    let diff = expected
+        .as_bytes()
+        .iter()
+        .zip(report.report_data.as_bytes().iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b));
+
+    match diff {
+        0 => Ok(()),
+        _ => Err(BindingError::Mismatch),
+    }
+}
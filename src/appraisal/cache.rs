@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Caching the verification of attestation reports.
+//!
+//! Gateways which attest a guest on every connection see the same report
+//! many times. A [VerificationCache] remembers that a report verified
+//! against a certificate chain, keyed by the SHA-384 digests of the whole
+//! report, signature included, and of the DER encodings of the ARK, ASK and
+//! VCEK (or VLEK), so that the certificate and report signatures are only
+//! checked again once the entry is older than the cache's time-to-live.
+//! Failed verifications are never cached.
+//!
+//! Hitting the cache only proves the report is unchanged since it verified:
+//! the report still has to be appraised, and its nonce checked, each time.
+//!
+//! # Example:
+//! ```ignore
+//! let cache = Arc::new(VerificationCache::new(Duration::from_secs(300)));
+//!
+//! // On every connection:
+//! cache.verify(&chain, &report)?;
+//! let appraisal = policy.appraise(&report, &context);
+//! ```
+
+use crate::{
+    certs::snp::{Chain, Verifiable},
+    error::VerificationError,
+    firmware::guest::AttestationReport,
+    util::digest::sha384,
+};
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+/// The number of verifications cached by default.
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+/// The digests of a report and of the chain it verified against.
+type Key = ([u8; 48], [u8; 48]);
+
+/// The metrics of a [VerificationCache]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// How many verifications the cache answered.
+    pub hits: u64,
+
+    /// How many verifications the cache had to run.
+    pub misses: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// When each report verified against each chain.
+    verified: HashMap<Key, SystemTime>,
+
+    stats: CacheStats,
+}
+
+/// Remembers which reports verified against which chains, for a
+/// time-to-live
+#[derive(Debug)]
+pub struct VerificationCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl VerificationCache {
+    /// Remember each verification for `ttl` after it ran.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            capacity: DEFAULT_CACHE_CAPACITY,
+            entries: Default::default(),
+        }
+    }
+
+    /// Remember up to `capacity` verifications rather than
+    /// [DEFAULT_CACHE_CAPACITY], forgetting the oldest first.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is 0.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "cache capacity must not be 0");
+        self.capacity = capacity;
+        self
+    }
+
+    /// How long verifications are remembered for after they ran.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The number of verifications remembered, expired or not.
+    pub fn len(&self) -> usize {
+        self.lock().verified.len()
+    }
+
+    /// Whether no verification is remembered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The hits and misses so far.
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Forget every verification, and the hits and misses so far.
+    pub fn clear(&self) {
+        *self.lock() = Entries::default();
+    }
+
+    /// Verify `report` against `chain`, unless the same report verified
+    /// against the same chain within the time-to-live.
+    pub fn verify(
+        &self,
+        chain: &Chain,
+        report: &AttestationReport,
+    ) -> Result<(), VerificationError> {
+        self.verify_at(chain, report, SystemTime::now())
+    }
+
+    /// Verify `report` against `chain` as [verify](Self::verify) does, at
+    /// `now`.
+    pub fn verify_at(
+        &self,
+        chain: &Chain,
+        report: &AttestationReport,
+        now: SystemTime,
+    ) -> Result<(), VerificationError> {
+        let key = (sha384(&[&report.to_bytes()]), chain_digest(chain)?);
+
+        {
+            let mut entries = self.lock();
+            let hit = entries
+                .verified
+                .get(&key)
+                .is_some_and(|verified_at| !self.expired(*verified_at, now));
+
+            if hit {
+                entries.stats.hits += 1;
+                return Ok(());
+            }
+
+            entries.stats.misses += 1;
+        }
+
+        // Verify without holding the lock, so that other reports need not
+        // wait for this one.
+        (chain, report).verify()?;
+
+        let mut entries = self.lock();
+        if entries.verified.len() >= self.capacity && !entries.verified.contains_key(&key) {
+            self.evict(&mut entries, now);
+        }
+        entries.verified.insert(key, now);
+
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn expired(&self, verified_at: SystemTime, now: SystemTime) -> bool {
+        // A verification which ran after `now` ran by a clock running ahead.
+        now.duration_since(verified_at)
+            .is_ok_and(|age| age > self.ttl)
+    }
+
+    /// Forget the expired verifications, or the oldest one if none expired.
+    fn evict(&self, entries: &mut Entries, now: SystemTime) {
+        entries
+            .verified
+            .retain(|_, verified_at| !self.expired(*verified_at, now));
+
+        if entries.verified.len() < self.capacity {
+            return;
+        }
+
+        let oldest = entries
+            .verified
+            .iter()
+            .min_by_key(|(_, verified_at)| **verified_at)
+            .map(|(key, _)| *key);
+
+        if let Some(oldest) = oldest {
+            entries.verified.remove(&oldest);
+        }
+    }
+}
+
+/// The digest of the DER encodings of the certificates of `chain`.
+fn chain_digest(chain: &Chain) -> Result<[u8; 48], VerificationError> {
+    let der = [&chain.ca.ark, &chain.ca.ask, &chain.vek]
+        .iter()
+        .map(|cert| cert.to_der())
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(|e| VerificationError::Crypto(e.into()))?;
+
+    // Prefix each certificate with its length, so that the boundaries
+    // between them are part of the digest.
+    let lengths: Vec<[u8; 8]> = der
+        .iter()
+        .map(|cert| (cert.len() as u64).to_be_bytes())
+        .collect();
+    let parts: Vec<&[u8]> = lengths
+        .iter()
+        .zip(&der)
+        .flat_map(|(length, cert)| [&length[..], &cert[..]])
+        .collect();
+
+    Ok(sha384(&parts))
+}
@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Analyzing the drift between the TCB versions of a report.
+//!
+//! A report carries four TCB versions: the current TCB the platform runs,
+//! the reported TCB the hypervisor chose to derive the VCEK from, the
+//! committed TCB the firmware can no longer be rolled back below, and the
+//! launch TCB which was current when the guest launched. On a platform
+//! without pending updates the four are equal. A [TcbAnalysis] compares
+//! them and lists how they drifted apart as [TcbFinding]s, e.g. for
+//! compliance tooling tracking how far a fleet lags behind its updates.
+//!
+//! The reported, committed and launch TCBs are never newer than the current
+//! TCB. A report in which one of them is still flags a platform which is
+//! not behaving as the firmware specification requires.
+//!
+//! # Example:
+//! ```ignore
+//! let analysis = TcbAnalysis::of(&report);
+//!
+//! for finding in &analysis.findings {
+//!     println!("{finding}");
+//! }
+//! ```
+
+use crate::firmware::{guest::AttestationReport, host::TcbVersion};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+};
+
+/// One of the TCB versions of a report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TcbSource {
+    /// The TCB the platform runs.
+    Current,
+
+    /// The TCB the VCEK is derived from.
+    Reported,
+
+    /// The TCB the firmware cannot be rolled back below.
+    Committed,
+
+    /// The TCB which was current when the guest launched.
+    Launch,
+}
+
+impl Display for TcbSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self {
+            Self::Current => "current",
+            Self::Reported => "reported",
+            Self::Committed => "committed",
+            Self::Launch => "launch",
+        };
+        write!(f, "{source}")
+    }
+}
+
+/// One of the components of a TCB version
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TcbComponent {
+    /// The PSP bootloader.
+    Bootloader,
+
+    /// The PSP operating system.
+    Tee,
+
+    /// The SNP firmware.
+    Snp,
+
+    /// The microcode.
+    Microcode,
+}
+
+impl Display for TcbComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let component = match self {
+            Self::Bootloader => "bootloader",
+            Self::Tee => "tee",
+            Self::Snp => "snp",
+            Self::Microcode => "microcode",
+        };
+        write!(f, "{component}")
+    }
+}
+
+/// How two TCB versions of a report drifted apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TcbDrift {
+    /// The platform runs newer firmware than it reports, e.g. as the
+    /// hypervisor waits for the VCEKs of the new TCB to be published.
+    NewerThanReported,
+
+    /// The platform runs firmware it has not committed to yet, so that it
+    /// may still be rolled back to the committed TCB.
+    NewerThanCommitted,
+
+    /// The guest launched before the last TCB update of the platform.
+    LaunchedBeforeUpdate,
+
+    /// The platform reports a TCB older than the one it can be rolled back
+    /// to.
+    ReportedBelowCommitted,
+
+    /// A TCB is newer than the current TCB in some component, which the
+    /// firmware never reports.
+    Inconsistent,
+}
+
+/// A drift between two TCB versions of a report
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TcbFinding {
+    /// How the TCB versions drifted apart.
+    pub drift: TcbDrift,
+
+    /// The TCB version expected not to be newer.
+    pub older: TcbSource,
+
+    /// The TCB version expected not to be older.
+    pub newer: TcbSource,
+
+    /// The components in which the TCB versions differ.
+    pub components: Vec<TcbComponent>,
+}
+
+impl Display for TcbFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.drift {
+            TcbDrift::NewerThanReported => write!(f, "running newer-than-reported firmware")?,
+            TcbDrift::NewerThanCommitted => write!(f, "running uncommitted firmware")?,
+            TcbDrift::LaunchedBeforeUpdate => write!(f, "launched before the last TCB update")?,
+            TcbDrift::ReportedBelowCommitted => {
+                write!(f, "reporting a TCB older than the committed TCB")?
+            }
+            TcbDrift::Inconsistent => write!(
+                f,
+                "the {} TCB is newer than the {} TCB",
+                self.older, self.newer
+            )?,
+        }
+
+        let components: Vec<String> = self.components.iter().map(|c| c.to_string()).collect();
+        write!(f, " ({})", components.join(", "))
+    }
+}
+
+/// The TCB versions of a report and how they drifted apart
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TcbAnalysis {
+    /// The TCB the platform runs.
+    pub current: TcbVersion,
+
+    /// The TCB the VCEK is derived from.
+    pub reported: TcbVersion,
+
+    /// The TCB the firmware cannot be rolled back below.
+    pub committed: TcbVersion,
+
+    /// The TCB which was current when the guest launched.
+    pub launch: TcbVersion,
+
+    /// How the TCB versions drifted apart, if they did.
+    pub findings: Vec<TcbFinding>,
+}
+
+impl TcbAnalysis {
+    /// Compare the TCB versions of `report`.
+    pub fn of(report: &AttestationReport) -> Self {
+        let mut analysis = Self {
+            current: report.current_tcb,
+            reported: report.reported_tcb,
+            committed: report.committed_tcb,
+            launch: report.launch_tcb,
+            findings: vec![],
+        };
+
+        for (drift, older) in [
+            (TcbDrift::NewerThanReported, TcbSource::Reported),
+            (TcbDrift::NewerThanCommitted, TcbSource::Committed),
+            (TcbDrift::LaunchedBeforeUpdate, TcbSource::Launch),
+        ] {
+            analysis.compare(drift, older, TcbSource::Current);
+        }
+
+        // Whether the reported TCB is newer than the committed TCB is of no
+        // concern, only whether it is older in any component.
+        let below = components(&analysis.reported, &analysis.committed, Ordering::Less);
+        if !below.is_empty() {
+            analysis.findings.push(TcbFinding {
+                drift: TcbDrift::ReportedBelowCommitted,
+                older: TcbSource::Reported,
+                newer: TcbSource::Committed,
+                components: below,
+            });
+        }
+
+        analysis
+    }
+
+    /// Whether the TCB versions are equal, the platform having no update
+    /// pending since the guest launched.
+    pub fn is_up_to_date(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Whether no TCB version is newer than the current TCB.
+    pub fn is_consistent(&self) -> bool {
+        !self
+            .findings
+            .iter()
+            .any(|finding| finding.drift == TcbDrift::Inconsistent)
+    }
+
+    /// The TCB version of the report `source` names.
+    pub fn tcb(&self, source: TcbSource) -> &TcbVersion {
+        match source {
+            TcbSource::Current => &self.current,
+            TcbSource::Reported => &self.reported,
+            TcbSource::Committed => &self.committed,
+            TcbSource::Launch => &self.launch,
+        }
+    }
+
+    /// Record how `older` drifted from `newer`: as `drift` if it is older,
+    /// and as inconsistent if it is newer in any component.
+    fn compare(&mut self, drift: TcbDrift, older: TcbSource, newer: TcbSource) {
+        let (older_tcb, newer_tcb) = (self.tcb(older), self.tcb(newer));
+
+        let (drift, components) = match older_tcb.partial_cmp(newer_tcb) {
+            Some(Ordering::Equal) => return,
+            Some(Ordering::Less) => (drift, components(older_tcb, newer_tcb, Ordering::Less)),
+            _ => (
+                TcbDrift::Inconsistent,
+                components(older_tcb, newer_tcb, Ordering::Greater),
+            ),
+        };
+
+        self.findings.push(TcbFinding {
+            drift,
+            older,
+            newer,
+            components,
+        });
+    }
+}
+
+/// The components in which `tcb` compares to `other` as `ordering`.
+fn components(tcb: &TcbVersion, other: &TcbVersion, ordering: Ordering) -> Vec<TcbComponent> {
+    [
+        (
+            TcbComponent::Bootloader,
+            tcb.bootloader.cmp(&other.bootloader),
+        ),
+        (TcbComponent::Tee, tcb.tee.cmp(&other.tee)),
+        (TcbComponent::Snp, tcb.snp.cmp(&other.snp)),
+        (TcbComponent::Microcode, tcb.microcode.cmp(&other.microcode)),
+    ]
+    .iter()
+    .filter(|(_, cmp)| *cmp == ordering)
+    .map(|(component, _)| *component)
+    .collect()
+}
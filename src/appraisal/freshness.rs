@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Issuing and checking the nonces bound into attestation reports.
+//!
+//! A relying party proves a report is fresh by sending the guest a random
+//! nonce, which the guest requests the report with as (the start of) its
+//! report data. A [NonceManager] issues these nonces, remembers when, and
+//! accepts each one once only, within a time-to-live. Nonces are forgotten
+//! only after twice their time-to-live, so that within this sliding window
+//! a replayed or late report is told apart from one with a forged nonce.
+//!
+//! # Example:
+//! ```ignore
+//! let nonces = NonceManager::new(Duration::from_secs(60));
+//!
+//! let nonce = nonces.issue()?;
+//! // Send `nonce.value` to the guest and receive its report.
+//!
+//! let nonce = nonces.validate(report.report_data)?;
+//! let context = AppraisalContext::new().nonce(nonce.value, nonce.issued_at);
+//! ```
+
+use super::Nonce;
+
+use crate::error::FreshnessError;
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime},
+};
+
+/// The length of the nonces issued by default, in bytes.
+pub const DEFAULT_NONCE_LENGTH: usize = 32;
+
+#[derive(Debug, Default)]
+struct Nonces {
+    /// When each nonce not yet used was issued.
+    outstanding: HashMap<Vec<u8>, SystemTime>,
+
+    /// When each nonce used was issued.
+    used: HashMap<Vec<u8>, SystemTime>,
+}
+
+/// Issues nonces and accepts each of them once within a time-to-live
+#[derive(Debug)]
+pub struct NonceManager {
+    ttl: Duration,
+    length: usize,
+    nonces: Mutex<Nonces>,
+}
+
+impl NonceManager {
+    /// Accept the nonces issued for `ttl` after issuing them.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            length: DEFAULT_NONCE_LENGTH,
+            nonces: Default::default(),
+        }
+    }
+
+    /// Issue nonces of `length` bytes rather than [DEFAULT_NONCE_LENGTH].
+    ///
+    /// # Panics
+    ///
+    /// If `length` is 0 or longer than the 64 bytes of report data.
+    pub fn length(mut self, length: usize) -> Self {
+        assert!(
+            (1..=64).contains(&length),
+            "nonce length {} is not within 1..=64",
+            length
+        );
+        self.length = length;
+        self
+    }
+
+    /// How long nonces are accepted for after issuing them.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The number of nonces issued and neither used nor forgotten yet.
+    pub fn outstanding(&self) -> usize {
+        self.lock().outstanding.len()
+    }
+
+    /// Issue a random nonce.
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    pub fn issue(&self) -> Result<Nonce, FreshnessError> {
+        let mut value = vec![0; self.length];
+        fill_random(&mut value)?;
+
+        self.register(value, SystemTime::now())
+    }
+
+    /// Register a nonce generated by the caller, issued at `issued_at`.
+    pub fn register(&self, value: Vec<u8>, issued_at: SystemTime) -> Result<Nonce, FreshnessError> {
+        if value.len() != self.length {
+            return Err(FreshnessError::InvalidLength {
+                expected: self.length,
+                actual: value.len(),
+            });
+        }
+
+        let mut nonces = self.lock();
+        self.purge(&mut nonces, issued_at);
+
+        if nonces.outstanding.contains_key(&value) || nonces.used.contains_key(&value) {
+            return Err(FreshnessError::Duplicate);
+        }
+
+        nonces.outstanding.insert(value.clone(), issued_at);
+
+        Ok(Nonce { value, issued_at })
+    }
+
+    /// Check that `report_data` starts with a nonce issued and not yet used
+    /// or expired, and mark that nonce used.
+    pub fn validate(&self, report_data: impl AsRef<[u8]>) -> Result<Nonce, FreshnessError> {
+        self.validate_at(report_data, SystemTime::now())
+    }
+
+    /// Check `report_data` as [validate](Self::validate) does, at `now`.
+    pub fn validate_at(
+        &self,
+        report_data: impl AsRef<[u8]>,
+        now: SystemTime,
+    ) -> Result<Nonce, FreshnessError> {
+        let report_data = report_data.as_ref();
+        let value = report_data
+            .get(..self.length)
+            .ok_or(FreshnessError::InvalidLength {
+                expected: self.length,
+                actual: report_data.len(),
+            })?;
+
+        let mut nonces = self.lock();
+
+        if nonces.used.contains_key(value) {
+            return Err(FreshnessError::Replayed);
+        }
+
+        let (value, issued_at) = nonces
+            .outstanding
+            .remove_entry(value)
+            .ok_or(FreshnessError::Unknown)?;
+
+        if self.expired(issued_at, now) {
+            self.purge(&mut nonces, now);
+            return Err(FreshnessError::Expired);
+        }
+
+        nonces.used.insert(value.clone(), issued_at);
+        self.purge(&mut nonces, now);
+
+        Ok(Nonce { value, issued_at })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Nonces> {
+        self.nonces.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn expired(&self, issued_at: SystemTime, now: SystemTime) -> bool {
+        older_than(issued_at, now, self.ttl)
+    }
+
+    /// Forget the nonces which fell out of the window by `now`.
+    fn purge(&self, nonces: &mut Nonces, now: SystemTime) {
+        let window = self.ttl.saturating_mul(2);
+
+        nonces
+            .outstanding
+            .retain(|_, issued_at| !older_than(*issued_at, now, window));
+        nonces
+            .used
+            .retain(|_, issued_at| !older_than(*issued_at, now, window));
+    }
+}
+
+fn older_than(issued_at: SystemTime, now: SystemTime, limit: Duration) -> bool {
+    // A nonce issued after `now` was issued by a clock running ahead.
+    now.duration_since(issued_at).is_ok_and(|age| age > limit)
+}
+
+#[cfg(feature = "openssl")]
+fn fill_random(buf: &mut [u8]) -> Result<(), FreshnessError> {
+    openssl::rand::rand_bytes(buf)
+        .map_err(|e| FreshnessError::Random(std::io::Error::new(std::io::ErrorKind::Other, e)))
+}
+
+#[cfg(feature = "crypto_nossl")]
+fn fill_random(buf: &mut [u8]) -> Result<(), FreshnessError> {
+    use rsa::rand_core::{OsRng, RngCore};
+
+    OsRng
+        .try_fill_bytes(buf)
+        .map_err(|e| FreshnessError::Random(std::io::Error::new(std::io::ErrorKind::Other, e)))
+}
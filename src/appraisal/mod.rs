@@ -0,0 +1,512 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Declarative appraisal of SEV-SNP attestation reports.
+//!
+//! Relying parties express what they accept as an [AppraisalPolicy]: the
+//! minimum SVN of each TCB component, the guest policy bits the guest must
+//! be launched with, the allowed launch measurements, the keys allowed to
+//! sign the report and how old the nonce bound into the report may be. The
+//! policy is a serializable document, so that it can be stored and reviewed
+//! next to the services enforcing it. Evaluating it against a report yields
+//! an [Appraisal] with the outcome and reason of each rule.
+//!
+//! Allowed measurements may also be kept in an [AllowList] shared between
+//! verifiers, keyed by product, OVMF build and kernel version.
+//!
+//! A [NonceManager] issues the nonces bound into the report data and checks
+//! that each report carries a current one, used once only. A [TlsBinding]
+//! binds the TLS channel a relying party talks to the guest over into the
+//! report data, and checks that a report binds the channel. A [KeyRelease]
+//! seals secrets to the guests whose reports verify, satisfy a policy and
+//! bind the key the secret is sealed to.
+//!
+//! A [TcbAnalysis] compares the current, reported, committed and launch TCB
+//! versions of a report, and lists how the platform's firmware drifted from
+//! what it reports and commits to, and from what the guest launched on.
+//!
+//! The appraisal only inspects the contents of the report. Verify the
+//! report's signature and certificate chain before trusting them, e.g.
+//! through a [VerificationCache] remembering the reports already verified.
+//!
+//! # Example:
+//! ```ignore
+//! let policy = AppraisalPolicy::from_reader(File::open("policy.json")?)?;
+//!
+//! let context = AppraisalContext::new().nonce(nonce, issued_at);
+//! let appraisal = policy.appraise(&report, &context);
+//!
+//! if !appraisal.passed() {
+//!     for failure in appraisal.failures() {
+//!         eprintln!("{failure}");
+//!     }
+//! }
+//! ```
+
+mod allowlist;
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+mod binding;
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+mod cache;
+mod drift;
+mod freshness;
+#[cfg(feature = "openssl")]
+mod release;
+
+pub use allowlist::*;
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+pub use binding::*;
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+pub use cache::*;
+pub use drift::*;
+pub use freshness::*;
+#[cfg(feature = "openssl")]
+pub use release::*;
+
+use crate::{
+    encoding::Measurement48,
+    firmware::{
+        guest::{AttestationReport, SigningKey},
+        host::TcbVersion,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fmt::{self, Display},
+    io::{Read, Result, Write},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// The minimum SVN of each component of the reported TCB
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MinTcb {
+    /// Minimum SVN of the PSP bootloader.
+    pub bootloader: u8,
+
+    /// Minimum SVN of the PSP operating system.
+    pub tee: u8,
+
+    /// Minimum SVN of the SNP firmware.
+    pub snp: u8,
+
+    /// Minimum microcode patch level.
+    pub microcode: u8,
+}
+
+impl MinTcb {
+    /// Whether every component of `tcb` meets its minimum SVN.
+    pub fn is_met_by(&self, tcb: &TcbVersion) -> bool {
+        tcb.bootloader >= self.bootloader
+            && tcb.tee >= self.tee
+            && tcb.snp >= self.snp
+            && tcb.microcode >= self.microcode
+    }
+}
+
+/// The guest policy bits a guest must be launched with
+///
+/// Each bit left unset is not checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyRequirements {
+    /// Whether debugging the guest must be allowed.
+    pub debug: Option<bool>,
+
+    /// Whether the host must be allowed to use SMT.
+    pub smt: Option<bool>,
+
+    /// Whether association with a migration agent must be allowed.
+    pub migrate_ma: Option<bool>,
+
+    /// Whether the guest must be restricted to a single socket.
+    pub single_socket: Option<bool>,
+}
+
+/// Rules an attestation report must satisfy
+///
+/// Each rule left unset is not evaluated.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppraisalPolicy {
+    /// The minimum SVN of each component of the reported TCB.
+    pub min_tcb: Option<MinTcb>,
+
+    /// The guest policy bits the guest must be launched with.
+    pub policy: Option<PolicyRequirements>,
+
+    /// The launch measurements allowed, if any are listed.
+    pub measurements: Vec<Measurement48>,
+
+    /// The keys allowed to sign the report, if any are listed.
+    pub signers: Vec<SigningKey>,
+
+    /// How long after the nonce in the report data was issued the report
+    /// is accepted, in seconds.
+    pub max_age_secs: Option<u64>,
+}
+
+/// The nonce a relying party expects to find in the report data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Nonce {
+    /// The nonce, which the report data must start with. An empty nonce
+    /// never passes, as every report data would start with it.
+    pub value: Vec<u8>,
+
+    /// When the relying party issued the nonce.
+    pub issued_at: SystemTime,
+}
+
+/// What a report is appraised against besides the policy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppraisalContext {
+    /// The nonce issued for the report, if any.
+    pub nonce: Option<Nonce>,
+
+    /// The time the report is appraised at.
+    pub now: SystemTime,
+
+    /// The allow-list of measurements to consult besides the policy, if any.
+    pub allow_list: Option<Arc<AllowList>>,
+
+    /// The product the guest runs on, which restricts the entries of the
+    /// allow-list consulted, if known.
+    pub product: Option<String>,
+}
+
+impl AppraisalContext {
+    /// Appraise a report now, without a nonce.
+    pub fn new() -> Self {
+        Self {
+            nonce: None,
+            now: SystemTime::now(),
+            allow_list: None,
+            product: None,
+        }
+    }
+
+    /// Expect the report data to start with `value`, issued at `issued_at`.
+    pub fn nonce(mut self, value: impl Into<Vec<u8>>, issued_at: SystemTime) -> Self {
+        self.nonce = Some(Nonce {
+            value: value.into(),
+            issued_at,
+        });
+        self
+    }
+
+    /// Also allow the measurements of `list`.
+    pub fn allow_list(mut self, list: Arc<AllowList>) -> Self {
+        self.allow_list = Some(list);
+        self
+    }
+
+    /// Only consult the allow-list entries for `product`.
+    pub fn product(mut self, product: impl Into<String>) -> Self {
+        self.product = Some(product.into());
+        self
+    }
+}
+
+impl Default for AppraisalContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A rule of an [AppraisalPolicy]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// The minimum TCB.
+    MinTcb,
+
+    /// The guest policy bits.
+    Policy,
+
+    /// The allowed measurements.
+    Measurement,
+
+    /// The allowed signers.
+    Signer,
+
+    /// The maximum report age.
+    Freshness,
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = match self {
+            Self::MinTcb => "min_tcb",
+            Self::Policy => "policy",
+            Self::Measurement => "measurement",
+            Self::Signer => "signer",
+            Self::Freshness => "freshness",
+        };
+        write!(f, "{rule}")
+    }
+}
+
+/// The outcome of evaluating one rule
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RuleResult {
+    /// The rule evaluated.
+    pub rule: Rule,
+
+    /// Whether the report satisfies the rule.
+    pub passed: bool,
+
+    /// Why the report does or does not satisfy the rule.
+    pub reason: String,
+}
+
+impl RuleResult {
+    fn new(rule: Rule, passed: bool, reason: String) -> Self {
+        Self {
+            rule,
+            passed,
+            reason,
+        }
+    }
+}
+
+impl Display for RuleResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.passed { "pass" } else { "fail" };
+        write!(f, "[{outcome}] {}: {}", self.rule, self.reason)
+    }
+}
+
+/// The outcome of appraising a report
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Appraisal {
+    /// The outcome of each rule of the policy, in evaluation order.
+    pub results: Vec<RuleResult>,
+}
+
+impl Appraisal {
+    /// Whether the report satisfies every rule.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    /// The rules the report does not satisfy.
+    pub fn failures(&self) -> impl Iterator<Item = &RuleResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+}
+
+impl AppraisalPolicy {
+    /// Read a policy from JSON.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Write the policy as JSON.
+    pub fn to_writer(&self, writer: impl Write) -> Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    /// Evaluate every rule of the policy against `report`.
+    pub fn appraise(&self, report: &AttestationReport, context: &AppraisalContext) -> Appraisal {
+        let mut results = vec![];
+
+        if let Some(min) = &self.min_tcb {
+            results.push(appraise_tcb(min, report));
+        }
+
+        if let Some(requirements) = &self.policy {
+            results.push(appraise_policy(requirements, report));
+        }
+
+        if !self.measurements.is_empty() || context.allow_list.is_some() {
+            results.push(self.appraise_measurement(report, context));
+        }
+
+        if !self.signers.is_empty() {
+            results.push(match report.key_info.signing_key() {
+                Ok(signer) if self.signers.contains(&signer) => {
+                    RuleResult::new(Rule::Signer, true, format!("signed by the {signer}"))
+                }
+                Ok(signer) => RuleResult::new(
+                    Rule::Signer,
+                    false,
+                    format!("signed by the {signer}, which is not allowed"),
+                ),
+                Err(raw) => RuleResult::new(
+                    Rule::Signer,
+                    false,
+                    format!("signing key {raw} is reserved"),
+                ),
+            });
+        }
+
+        if let Some(max_age) = self.max_age_secs {
+            results.push(appraise_freshness(
+                Duration::from_secs(max_age),
+                report,
+                context,
+            ));
+        }
+
+        Appraisal { results }
+    }
+
+    fn appraise_measurement(
+        &self,
+        report: &AttestationReport,
+        context: &AppraisalContext,
+    ) -> RuleResult {
+        let measurement = report.measurement;
+
+        if self.measurements.contains(&measurement) {
+            return RuleResult::new(
+                Rule::Measurement,
+                true,
+                format!("measurement {measurement} is allowed"),
+            );
+        }
+
+        if let Some(list) = &context.allow_list {
+            let product = context.product.as_deref();
+
+            if let Some(entry) = list.find(product, &measurement, &report.reported_tcb) {
+                return RuleResult::new(
+                    Rule::Measurement,
+                    true,
+                    format!("measurement {measurement} is allowed for {}", entry.key),
+                );
+            }
+
+            // The measurement may be known-good, but not on this firmware.
+            let newest = TcbVersion::new(u8::MAX, u8::MAX, u8::MAX, u8::MAX);
+            if let Some(entry) = list.find(product, &measurement, &newest) {
+                return RuleResult::new(
+                    Rule::Measurement,
+                    false,
+                    format!(
+                        "measurement {measurement} is allowed for {} only with a newer TCB",
+                        entry.key
+                    ),
+                );
+            }
+        }
+
+        RuleResult::new(
+            Rule::Measurement,
+            false,
+            format!("measurement {measurement} is not allowed"),
+        )
+    }
+}
+
+fn appraise_tcb(min: &MinTcb, report: &AttestationReport) -> RuleResult {
+    let tcb = report.reported_tcb;
+
+    let below: Vec<String> = [
+        ("bootloader", tcb.bootloader, min.bootloader),
+        ("tee", tcb.tee, min.tee),
+        ("snp", tcb.snp, min.snp),
+        ("microcode", tcb.microcode, min.microcode),
+    ]
+    .iter()
+    .filter(|(_, svn, min)| svn < min)
+    .map(|(name, svn, min)| format!("{name} SVN {svn} is below {min}"))
+    .collect();
+
+    if below.is_empty() {
+        RuleResult::new(
+            Rule::MinTcb,
+            true,
+            "every TCB component meets its minimum SVN".into(),
+        )
+    } else {
+        RuleResult::new(Rule::MinTcb, false, below.join(", "))
+    }
+}
+
+fn appraise_policy(requirements: &PolicyRequirements, report: &AttestationReport) -> RuleResult {
+    let policy = report.policy;
+
+    let mismatched: Vec<String> = [
+        ("debug", requirements.debug, policy.debug_allowed()),
+        ("smt", requirements.smt, policy.smt_allowed()),
+        (
+            "migrate_ma",
+            requirements.migrate_ma,
+            policy.migrate_ma_allowed(),
+        ),
+        (
+            "single_socket",
+            requirements.single_socket,
+            policy.single_socket_required(),
+        ),
+    ]
+    .iter()
+    .filter_map(|(name, required, bit)| {
+        let actual = *bit != 0;
+        match required {
+            Some(required) if *required != actual => {
+                Some(format!("{name} is {actual}, {required} required"))
+            }
+            _ => None,
+        }
+    })
+    .collect();
+
+    if mismatched.is_empty() {
+        RuleResult::new(
+            Rule::Policy,
+            true,
+            "the guest policy has the required bits".into(),
+        )
+    } else {
+        RuleResult::new(Rule::Policy, false, mismatched.join(", "))
+    }
+}
+
+fn appraise_freshness(
+    max_age: Duration,
+    report: &AttestationReport,
+    context: &AppraisalContext,
+) -> RuleResult {
+    let nonce = match &context.nonce {
+        Some(nonce) => nonce,
+        None => return RuleResult::new(Rule::Freshness, false, "no nonce was issued".into()),
+    };
+
+    if nonce.value.is_empty() {
+        return RuleResult::new(Rule::Freshness, false, "the nonce is empty".into());
+    }
+
+    if !report.report_data.as_bytes().starts_with(&nonce.value) {
+        return RuleResult::new(
+            Rule::Freshness,
+            false,
+            "the report data does not contain the nonce".into(),
+        );
+    }
+
+    match context.now.duration_since(nonce.issued_at) {
+        Ok(age) if age <= max_age => RuleResult::new(
+            Rule::Freshness,
+            true,
+            format!("the nonce is {}s old", age.as_secs()),
+        ),
+        Ok(age) => RuleResult::new(
+            Rule::Freshness,
+            false,
+            format!(
+                "the nonce is {}s old, more than {}s",
+                age.as_secs(),
+                max_age.as_secs()
+            ),
+        ),
+        Err(_) => RuleResult::new(
+            Rule::Freshness,
+            false,
+            "the nonce was issued in the future".into(),
+        ),
+    }
+}
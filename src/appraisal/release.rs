@@ -0,0 +1,325 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Releasing secrets to guests whose reports are verified and appraised.
+//!
+//! A guest asking for a secret, e.g. a disk encryption key, generates a
+//! [ReleaseKey] and requests its report with the report data binding the
+//! public half of the key (and the relying party's nonce, if any). The
+//! relying party's [KeyRelease] verifies the report against its certificate
+//! chain, appraises it against its policy and checks the binding, and only
+//! then seals the secret to the guest's key. The resulting [SealedKey] may
+//! travel over any channel, as only the guest can unseal it.
+//!
+//! The report data binds the DER-encoded `SubjectPublicKeyInfo` of the key
+//! as a [TlsBinding](super::TlsBinding) binds a channel, but under its own
+//! label:
+//!
+//! ```text
+//! report_data = nonce || SHA-512("AMD SEV-SNP key release v1" || 0x00 || spki)[..64 - len(nonce)]
+//! ```
+//!
+//! Secrets are sealed to RSA keys of at least 2048 bits with RSA-OAEP and
+//! SHA-256, and to P-384 keys with an ephemeral ECDH key agreement, the
+//! single-step KDF of NIST SP 800-56A with SHA-384 and AES-256-GCM. The fixed
+//! info of the KDF binds both public keys, as uncompressed SEC1 points:
+//!
+//! ```text
+//! key = SHA-384(1 || Z || "AMD SEV-SNP sealed key v1" || ephemeral || recipient)[..32]
+//! ```
+//!
+//! # Example:
+//! ```ignore
+//! // In the guest:
+//! let key = ReleaseKey::generate_ec()?;
+//! let report = firmware.get_report(None, Some(key.report_data(&nonce)?.into_inner()), None)?;
+//! // Send the report, its certificates and `key.public_key_der()?` over.
+//!
+//! // In the relying party:
+//! let context = AppraisalContext::new().nonce(nonce, issued_at);
+//! let sealed = KeyRelease::new(policy).release(&chain, &report, &context, &public_key, &secret)?;
+//!
+//! // Back in the guest:
+//! let secret = key.unseal(&sealed)?;
+//! ```
+
+use super::{
+    binding::{bind, check},
+    AppraisalContext, AppraisalPolicy,
+};
+
+use crate::{
+    certs::snp::{Chain, Verifiable},
+    encoding::ReportData,
+    error::ReleaseError,
+    firmware::guest::AttestationReport,
+};
+
+use openssl::{
+    bn::BigNumContext,
+    derive::Deriver,
+    ec::{EcGroup, EcKey, EcPoint, PointConversionForm},
+    encrypt::{Decrypter, Encrypter},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{Id, PKey, PKeyRef, Private, Public},
+    rand::rand_bytes,
+    rsa::{Padding, Rsa},
+    sha::Sha384,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+
+use serde::{Deserialize, Serialize};
+
+use zeroize::Zeroizing;
+
+/// The label the public key is digested with.
+const DIGEST_LABEL: &[u8] = b"AMD SEV-SNP key release v1\0";
+
+/// The label sealed secrets are encrypted with, as associated data and as
+/// the algorithm ID of the fixed info of the KDF.
+const SEAL_LABEL: &[u8] = b"AMD SEV-SNP sealed key v1";
+
+/// The size of the RSA keys generated by [ReleaseKey::generate_rsa], and the
+/// smallest accepted, in bits.
+pub const MIN_RSA_BITS: u32 = 2048;
+
+/// A secret sealed to a guest's [ReleaseKey]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SealedKey {
+    /// The secret encrypted with RSA-OAEP and SHA-256.
+    Rsa {
+        /// The encrypted secret.
+        ciphertext: Vec<u8>,
+    },
+
+    /// The secret encrypted with AES-256-GCM, under a key agreed with an
+    /// ephemeral P-384 key.
+    Ecdh {
+        /// The uncompressed SEC1 encoding of the ephemeral public key.
+        ephemeral: Vec<u8>,
+
+        /// The AES-GCM initialization vector.
+        iv: [u8; 12],
+
+        /// The encrypted secret.
+        ciphertext: Vec<u8>,
+
+        /// The AES-GCM authentication tag.
+        tag: [u8; 16],
+    },
+}
+
+/// The report data binding the DER-encoded public key `public_key`, starting
+/// with `nonce`.
+pub fn release_report_data(public_key: &[u8], nonce: &[u8]) -> Result<ReportData, ReleaseError> {
+    Ok(bind(DIGEST_LABEL, &[public_key], nonce)?)
+}
+
+/// Releases secrets to guests whose reports satisfy a policy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyRelease {
+    policy: AppraisalPolicy,
+}
+
+impl KeyRelease {
+    /// Release secrets to the guests whose reports satisfy `policy`.
+    pub fn new(policy: AppraisalPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// The policy reports must satisfy.
+    pub fn policy(&self) -> &AppraisalPolicy {
+        &self.policy
+    }
+
+    /// Seal `secret` to the DER-encoded public key `public_key`, once
+    /// `report` verifies against `chain`, satisfies the policy in `context`
+    /// and binds `public_key` after the nonce of `context`, if any.
+    pub fn release(
+        &self,
+        chain: &Chain,
+        report: &AttestationReport,
+        context: &AppraisalContext,
+        public_key: &[u8],
+        secret: &[u8],
+    ) -> Result<SealedKey, ReleaseError> {
+        (chain, report).verify()?;
+
+        let appraisal = self.policy.appraise(report, context);
+        if !appraisal.passed() {
+            return Err(ReleaseError::Appraisal(appraisal));
+        }
+
+        let nonce = context.nonce.as_ref().map_or(&[][..], |n| &n.value[..]);
+        check(&release_report_data(public_key, nonce)?, report)?;
+
+        seal(&PKey::public_key_from_der(public_key)?, secret)
+    }
+}
+
+/// The key pair a guest has secrets sealed to
+pub struct ReleaseKey(PKey<Private>);
+
+impl ReleaseKey {
+    /// Generate an RSA key of [MIN_RSA_BITS] bits.
+    pub fn generate_rsa() -> Result<Self, ReleaseError> {
+        Ok(Self(PKey::from_rsa(Rsa::generate(MIN_RSA_BITS)?)?))
+    }
+
+    /// Generate a P-384 key.
+    pub fn generate_ec() -> Result<Self, ReleaseError> {
+        let group = p384()?;
+        Ok(Self(PKey::from_ec_key(EcKey::generate(&group)?)?))
+    }
+
+    /// Use an existing RSA or P-384 key.
+    pub fn from_pkey(key: PKey<Private>) -> Result<Self, ReleaseError> {
+        supported(&key)?;
+        Ok(Self(key))
+    }
+
+    /// The DER-encoded `SubjectPublicKeyInfo` of the key.
+    pub fn public_key_der(&self) -> Result<Vec<u8>, ReleaseError> {
+        Ok(self.0.public_key_to_der()?)
+    }
+
+    /// The report data binding the key, starting with `nonce`.
+    pub fn report_data(&self, nonce: &[u8]) -> Result<ReportData, ReleaseError> {
+        release_report_data(&self.public_key_der()?, nonce)
+    }
+
+    /// Decrypt a secret sealed to the key. The secret is wiped when dropped.
+    pub fn unseal(&self, sealed: &SealedKey) -> Result<Zeroizing<Vec<u8>>, ReleaseError> {
+        match (sealed, self.0.id()) {
+            (SealedKey::Rsa { ciphertext }, Id::RSA) => {
+                let mut decrypter = Decrypter::new(&self.0)?;
+                decrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                decrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+                decrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+                let mut secret = Zeroizing::new(vec![0; decrypter.decrypt_len(ciphertext)?]);
+                let len = decrypter.decrypt(ciphertext, &mut secret)?;
+                secret.truncate(len);
+
+                Ok(secret)
+            }
+            (
+                SealedKey::Ecdh {
+                    ephemeral,
+                    iv,
+                    ciphertext,
+                    tag,
+                },
+                Id::EC,
+            ) => {
+                let group = p384()?;
+                let mut ctx = BigNumContext::new()?;
+                let point = EcPoint::from_bytes(&group, ephemeral, &mut ctx)?;
+                let public = PKey::from_ec_key(EcKey::from_public_key(&group, &point)?)?;
+
+                let key = agree(&self.0, &public, ephemeral, &encode_point(&self.0)?)?;
+
+                Ok(Zeroizing::new(decrypt_aead(
+                    Cipher::aes_256_gcm(),
+                    &*key,
+                    Some(iv),
+                    SEAL_LABEL,
+                    ciphertext,
+                    tag,
+                )?))
+            }
+            _ => Err(ReleaseError::UnsupportedKey),
+        }
+    }
+}
+
+fn p384() -> Result<EcGroup, ReleaseError> {
+    Ok(EcGroup::from_curve_name(Nid::SECP384R1)?)
+}
+
+/// Check that secrets may be sealed to `key`.
+fn supported<T: openssl::pkey::HasPublic>(key: &PKeyRef<T>) -> Result<(), ReleaseError> {
+    match key.id() {
+        Id::RSA if key.bits() >= MIN_RSA_BITS => Ok(()),
+        Id::EC if key.ec_key()?.group().curve_name() == Some(Nid::SECP384R1) => Ok(()),
+        _ => Err(ReleaseError::UnsupportedKey),
+    }
+}
+
+fn seal(public_key: &PKey<Public>, secret: &[u8]) -> Result<SealedKey, ReleaseError> {
+    supported(public_key)?;
+
+    if public_key.id() == Id::RSA {
+        let mut encrypter = Encrypter::new(public_key)?;
+        encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+        encrypter.set_rsa_oaep_md(MessageDigest::sha256())?;
+        encrypter.set_rsa_mgf1_md(MessageDigest::sha256())?;
+
+        let mut ciphertext = vec![0; encrypter.encrypt_len(secret)?];
+        let len = encrypter.encrypt(secret, &mut ciphertext)?;
+        ciphertext.truncate(len);
+
+        return Ok(SealedKey::Rsa { ciphertext });
+    }
+
+    let group = p384()?;
+    let ephemeral = PKey::from_ec_key(EcKey::generate(&group)?)?;
+    let encoded = encode_point(&ephemeral)?;
+    let key = agree(&ephemeral, public_key, &encoded, &encode_point(public_key)?)?;
+
+    let mut iv = [0; 12];
+    rand_bytes(&mut iv)?;
+
+    let mut tag = [0; 16];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &*key,
+        Some(&iv),
+        SEAL_LABEL,
+        secret,
+        &mut tag,
+    )?;
+
+    Ok(SealedKey::Ecdh {
+        ephemeral: encoded,
+        iv,
+        ciphertext,
+        tag,
+    })
+}
+
+/// The uncompressed SEC1 encoding of the public half of the P-384 `key`.
+fn encode_point<T: openssl::pkey::HasPublic>(key: &PKeyRef<T>) -> Result<Vec<u8>, ReleaseError> {
+    let key = key.ec_key()?;
+    let mut ctx = BigNumContext::new()?;
+    Ok(key
+        .public_key()
+        .to_bytes(key.group(), PointConversionForm::UNCOMPRESSED, &mut ctx)?)
+}
+
+/// The AES-256 key agreed between `private` and `public`, derived from the
+/// shared secret with the single-step KDF of NIST SP 800-56A. The encoded
+/// `ephemeral` and `recipient` public keys are its PartyUInfo and
+/// PartyVInfo.
+fn agree<T: openssl::pkey::HasPrivate>(
+    private: &PKeyRef<T>,
+    public: &PKeyRef<Public>,
+    ephemeral: &[u8],
+    recipient: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, ReleaseError> {
+    let mut deriver = Deriver::new(private)?;
+    deriver.set_peer(public)?;
+    let shared = Zeroizing::new(deriver.derive_to_vec()?);
+
+    let mut hasher = Sha384::new();
+    hasher.update(&1u32.to_be_bytes());
+    hasher.update(&shared);
+    hasher.update(SEAL_LABEL);
+    hasher.update(ephemeral);
+    hasher.update(recipient);
+
+    let digest = Zeroizing::new(hasher.finish());
+    let mut key = Zeroizing::new([0; 32]);
+    key.copy_from_slice(&digest[..32]);
+    Ok(key)
+}
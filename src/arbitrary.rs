@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Arbitrary instances of the core types, for fuzzing and property tests.
+//!
+//! With the `arbitrary` feature, attestation reports, guest policies, TCB
+//! versions, cert table entries and SEV certificates implement
+//! [Arbitrary](::arbitrary::Arbitrary), so that fuzzers can build them from
+//! unstructured bytes. With the `proptest` feature, the `strategy` module
+//! provides proptest strategies for the same types.
+//!
+//! The instances are built through the same decoders as the AMD SP's own
+//! output, so every instance is one those decoders may return. Reserved
+//! fields and signatures are arbitrary as well: the instances exercise
+//! parsing, encoding and appraisal, not valid signatures.
+//!
+//! # Example:
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn round_trip(report in strategy::attestation_report()) {
+//!         let bytes = report.to_bytes();
+//!         prop_assert_eq!(AttestationReport::from_bytes(&bytes).to_bytes(), bytes);
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "sev")]
+use crate::certs::sev::{ca, sev};
+#[cfg(feature = "snp")]
+use crate::firmware::{
+    guest::{AttestationReport, GuestPolicy},
+    host::{CertTableEntry, CertType, TcbVersion},
+};
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+#[cfg(feature = "snp")]
+use std::convert::TryFrom;
+
+/// The certificate types of the cert table, [CertType::OTHER] last.
+#[cfg(feature = "snp")]
+const CERT_TYPES: [CertType; 6] = [
+    CertType::Empty,
+    CertType::ARK,
+    CertType::ASK,
+    CertType::VCEK,
+    CertType::VLEK,
+    CertType::CRL,
+];
+
+/// The size of the preamble of an AMD CA certificate, after its version.
+#[cfg(feature = "sev")]
+const CA_PREAMBLE_SIZE: usize = 60;
+
+#[cfg(feature = "snp")]
+impl<'a> Arbitrary<'a> for AttestationReport {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self::from_bytes(&u.arbitrary()?))
+    }
+}
+
+#[cfg(feature = "snp")]
+impl<'a> Arbitrary<'a> for GuestPolicy {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(u64::arbitrary(u)?.into())
+    }
+}
+
+#[cfg(feature = "snp")]
+impl<'a> Arbitrary<'a> for TcbVersion {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(u64::arbitrary(u)?.into())
+    }
+}
+
+#[cfg(feature = "snp")]
+impl<'a> Arbitrary<'a> for CertType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(cert_type(u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
+/// Cert table entries are never [CertType::Empty], which ends the table.
+#[cfg(feature = "snp")]
+impl<'a> Arbitrary<'a> for CertTableEntry {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(cert_table_entry(
+            u.arbitrary()?,
+            u.arbitrary()?,
+            u.arbitrary()?,
+        ))
+    }
+}
+
+#[cfg(feature = "sev")]
+impl<'a> Arbitrary<'a> for sev::Certificate {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut bytes = vec![0; std::mem::size_of::<Self>()];
+        u.fill_buffer(&mut bytes)?;
+
+        Ok(sev_certificate(&bytes))
+    }
+}
+
+#[cfg(feature = "sev")]
+impl<'a> Arbitrary<'a> for ca::Certificate {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let large = u.arbitrary()?;
+
+        let mut bytes = vec![0; ca_certificate_size(large)];
+        u.fill_buffer(&mut bytes)?;
+
+        Ok(ca_certificate(large, &bytes))
+    }
+}
+
+/// The certificate type `index` selects, with the GUID for
+/// [CertType::OTHER].
+#[cfg(feature = "snp")]
+fn cert_type(index: u8, guid: [u8; 16]) -> CertType {
+    match CERT_TYPES.get(usize::from(index) % (CERT_TYPES.len() + 1)) {
+        Some(cert_type) => cert_type.clone(),
+        // Known GUIDs still map to their own types.
+        None => CertType::try_from(&uuid::Uuid::from_bytes(guid)).unwrap(),
+    }
+}
+
+#[cfg(feature = "snp")]
+fn cert_table_entry(index: u8, guid: [u8; 16], data: Vec<u8>) -> CertTableEntry {
+    let cert_type = match cert_type(index, guid) {
+        CertType::Empty => CertType::VCEK,
+        cert_type => cert_type,
+    };
+
+    CertTableEntry::new(cert_type, data)
+}
+
+/// An SEV certificate with the body and signatures in `bytes`, which hold
+/// a certificate as it is encoded.
+#[cfg(feature = "sev")]
+fn sev_certificate(bytes: &[u8]) -> sev::Certificate {
+    use codicon::Decoder;
+
+    let mut bytes = bytes.to_vec();
+    bytes[..4].copy_from_slice(&1u32.to_le_bytes());
+
+    // Any body and signatures decode with the default options.
+    sev::Certificate::decode(&bytes[..], ()).unwrap()
+}
+
+/// The number of bytes [ca_certificate] needs.
+#[cfg(feature = "sev")]
+fn ca_certificate_size(large: bool) -> usize {
+    let key_size = if large { 512 } else { 256 };
+
+    CA_PREAMBLE_SIZE + 3 * key_size
+}
+
+/// An AMD CA certificate with a 4096-bit key if `large`, and a 2048-bit key
+/// otherwise, with the IDs, usage, key and signature in `bytes`.
+#[cfg(feature = "sev")]
+fn ca_certificate(large: bool, bytes: &[u8]) -> ca::Certificate {
+    use codicon::Decoder;
+
+    let bits: u32 = if large { 4096 } else { 2048 };
+
+    let mut encoded = 1u32.to_le_bytes().to_vec();
+    encoded.extend_from_slice(&bytes[..CA_PREAMBLE_SIZE - 8]);
+    encoded.extend_from_slice(&bits.to_le_bytes());
+    encoded.extend_from_slice(&bits.to_le_bytes());
+    encoded.extend_from_slice(&bytes[CA_PREAMBLE_SIZE..ca_certificate_size(large)]);
+
+    // Any preamble with matching key sizes decodes with the default options.
+    ca::Certificate::decode(&encoded[..], ()).unwrap()
+}
+
+/// [proptest] strategies for the core types
+#[cfg(feature = "proptest")]
+pub mod strategy {
+    use super::*;
+
+    use proptest::{collection::vec, prelude::*};
+
+    #[cfg(feature = "snp")]
+    use std::convert::TryInto;
+
+    /// Attestation reports of arbitrary bytes.
+    #[cfg(feature = "snp")]
+    pub fn attestation_report() -> impl Strategy<Value = AttestationReport> {
+        vec(any::<u8>(), AttestationReport::SIZE)
+            .prop_map(|bytes| AttestationReport::from_bytes(&bytes.try_into().unwrap()))
+    }
+
+    /// Guest policies with arbitrary bits, including reserved ones.
+    #[cfg(feature = "snp")]
+    pub fn guest_policy() -> impl Strategy<Value = GuestPolicy> {
+        any::<u64>().prop_map(GuestPolicy::from)
+    }
+
+    /// TCB versions with arbitrary SVNs and reserved bytes.
+    #[cfg(feature = "snp")]
+    pub fn tcb_version() -> impl Strategy<Value = TcbVersion> {
+        any::<u64>().prop_map(TcbVersion::from)
+    }
+
+    /// Certificate types, including [CertType::Empty].
+    #[cfg(feature = "snp")]
+    pub fn cert_type() -> impl Strategy<Value = CertType> {
+        (any::<u8>(), any::<[u8; 16]>()).prop_map(|(index, guid)| super::cert_type(index, guid))
+    }
+
+    /// Cert table entries of up to `max_len` bytes of data.
+    #[cfg(feature = "snp")]
+    pub fn cert_table_entry(max_len: usize) -> impl Strategy<Value = CertTableEntry> {
+        (
+            any::<u8>(),
+            any::<[u8; 16]>(),
+            vec(any::<u8>(), 0..=max_len),
+        )
+            .prop_map(|(index, guid, data)| super::cert_table_entry(index, guid, data))
+    }
+
+    /// SEV certificates with arbitrary bodies and signatures.
+    #[cfg(feature = "sev")]
+    pub fn sev_certificate() -> impl Strategy<Value = sev::Certificate> {
+        vec(any::<u8>(), std::mem::size_of::<sev::Certificate>())
+            .prop_map(|bytes| super::sev_certificate(&bytes))
+    }
+
+    /// AMD CA certificates with arbitrary 2048-bit or 4096-bit keys.
+    #[cfg(feature = "sev")]
+    pub fn ca_certificate() -> impl Strategy<Value = ca::Certificate> {
+        any::<bool>().prop_flat_map(|large| {
+            vec(any::<u8>(), ca_certificate_size(large))
+                .prop_map(move |bytes| super::ca_certificate(large, &bytes))
+        })
+    }
+}
@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A C FFI interface to parse SEV-SNP attestation reports and verify them
+//! against a certificate chain, declared in `include/sev_capi.h`, which
+//! cbindgen generates from this module (see `cbindgen.toml`).
+//!
+//! Reports are parsed into opaque handles which the caller releases with
+//! [sev_snp_report_free]. Functions return 0 (or a length) on success and a
+//! negative `SEV_CAPI_ERR_*` code on failure.
+
+use crate::{
+    certs::snp::{Chain, Verifiable},
+    firmware::{guest::AttestationReport, host::TcbVersion},
+};
+
+use std::{
+    mem::size_of,
+    os::raw::c_int,
+    ptr::null_mut,
+    slice::{from_raw_parts, from_raw_parts_mut},
+};
+
+/// A pointer argument was NULL.
+pub const SEV_CAPI_ERR_NULL: c_int = -1;
+/// A buffer was too short, or a report was not exactly one report long.
+pub const SEV_CAPI_ERR_LENGTH: c_int = -2;
+/// An argument selecting a format, field or TCB was out of range.
+pub const SEV_CAPI_ERR_ARGUMENT: c_int = -3;
+/// The report or a certificate could not be parsed.
+pub const SEV_CAPI_ERR_PARSE: c_int = -4;
+/// The certificate chain or the report signature did not verify.
+pub const SEV_CAPI_ERR_VERIFY: c_int = -5;
+
+/// Certificates are DER-encoded.
+pub const SEV_CAPI_CERT_DER: c_int = 0;
+/// Certificates are PEM-encoded.
+pub const SEV_CAPI_CERT_PEM: c_int = 1;
+
+/// The launch measurement (48 bytes).
+pub const SEV_SNP_FIELD_MEASUREMENT: c_int = 0;
+/// The guest-provided report data (64 bytes).
+pub const SEV_SNP_FIELD_REPORT_DATA: c_int = 1;
+/// The host data provided at launch (32 bytes).
+pub const SEV_SNP_FIELD_HOST_DATA: c_int = 2;
+/// The family ID provided at launch (16 bytes).
+pub const SEV_SNP_FIELD_FAMILY_ID: c_int = 3;
+/// The image ID provided at launch (16 bytes).
+pub const SEV_SNP_FIELD_IMAGE_ID: c_int = 4;
+/// The digest of the ID key (48 bytes).
+pub const SEV_SNP_FIELD_ID_KEY_DIGEST: c_int = 5;
+/// The digest of the author key (48 bytes).
+pub const SEV_SNP_FIELD_AUTHOR_KEY_DIGEST: c_int = 6;
+/// The report ID of the guest (32 bytes).
+pub const SEV_SNP_FIELD_REPORT_ID: c_int = 7;
+/// The report ID of the guest's migration agent (32 bytes).
+pub const SEV_SNP_FIELD_REPORT_ID_MA: c_int = 8;
+/// The chip ID, zeroes if masked (64 bytes).
+pub const SEV_SNP_FIELD_CHIP_ID: c_int = 9;
+
+/// The current TCB.
+pub const SEV_SNP_TCB_CURRENT: c_int = 0;
+/// The TCB the VCEK signing the report was derived from.
+pub const SEV_SNP_TCB_REPORTED: c_int = 1;
+/// The committed TCB.
+pub const SEV_SNP_TCB_COMMITTED: c_int = 2;
+/// The current TCB when the guest was launched.
+pub const SEV_SNP_TCB_LAUNCH: c_int = 3;
+
+/// An SEV-SNP attestation report parsed for C callers.
+pub struct SevSnpReport(AttestationReport);
+
+/// The SVNs of a TCB version.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SevSnpTcb {
+    /// SVN of the PSP bootloader.
+    pub bootloader: u8,
+    /// SVN of the PSP operating system.
+    pub tee: u8,
+    /// SVN of the SNP firmware.
+    pub snp: u8,
+    /// Lowest patch level of all the cores.
+    pub microcode: u8,
+}
+
+impl From<TcbVersion> for SevSnpTcb {
+    fn from(tcb: TcbVersion) -> Self {
+        Self {
+            bootloader: tcb.bootloader,
+            tee: tcb.tee,
+            snp: tcb.snp,
+            microcode: tcb.microcode,
+        }
+    }
+}
+
+/// View `len` bytes at `ptr`, which may only be NULL if `len` is 0.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (ptr.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(from_raw_parts(ptr, len)),
+    }
+}
+
+/// Parse the `len` bytes at `bytes` as an attestation report, storing a
+/// handle to it in `report`.
+///
+/// # Safety
+///
+/// The caller of this function is responsible for ensuring that the pointer arguments are
+/// valid.
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_parse(
+    bytes: *const u8,
+    len: usize,
+    report: *mut *mut SevSnpReport,
+) -> c_int {
+    if bytes.is_null() || report.is_null() {
+        return SEV_CAPI_ERR_NULL;
+    }
+
+    if len != size_of::<AttestationReport>() {
+        return SEV_CAPI_ERR_LENGTH;
+    }
+
+    match bincode::deserialize::<AttestationReport>(from_raw_parts(bytes, len)) {
+        Ok(parsed) => {
+            *report = Box::into_raw(Box::new(SevSnpReport(parsed)));
+            0
+        }
+        Err(_) => {
+            *report = null_mut();
+            SEV_CAPI_ERR_PARSE
+        }
+    }
+}
+
+/// Release a report returned by [sev_snp_report_parse]. NULL is ignored.
+///
+/// # Safety
+///
+/// `report` must have been returned by [sev_snp_report_parse] and not already been released.
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_free(report: *mut SevSnpReport) {
+    if !report.is_null() {
+        drop(Box::from_raw(report));
+    }
+}
+
+/// Verify that the ARK signs the ASK, the ASK signs the VCEK or VLEK, and
+/// the VCEK or VLEK signs `report`. The certificates are encoded in
+/// `format`.
+///
+/// # Safety
+///
+/// The caller of this function is responsible for ensuring that the pointer arguments are
+/// valid.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn sev_snp_report_verify(
+    report: *const SevSnpReport,
+    format: c_int,
+    ark: *const u8,
+    ark_len: usize,
+    ask: *const u8,
+    ask_len: usize,
+    vek: *const u8,
+    vek_len: usize,
+) -> c_int {
+    let (report, ark, ask, vek) = match (
+        report.as_ref(),
+        bytes(ark, ark_len),
+        bytes(ask, ask_len),
+        bytes(vek, vek_len),
+    ) {
+        (Some(report), Some(ark), Some(ask), Some(vek)) => (report, ark, ask, vek),
+        _ => return SEV_CAPI_ERR_NULL,
+    };
+
+    let chain = match format {
+        SEV_CAPI_CERT_DER => Chain::from_der(ark, ask, vek),
+        SEV_CAPI_CERT_PEM => Chain::from_pem(ark, ask, vek),
+        _ => return SEV_CAPI_ERR_ARGUMENT,
+    };
+
+    let chain = match chain {
+        Ok(chain) => chain,
+        Err(_) => return SEV_CAPI_ERR_PARSE,
+    };
+
+    match (&chain, &report.0).verify() {
+        Ok(()) => 0,
+        Err(_) => SEV_CAPI_ERR_VERIFY,
+    }
+}
+
+/// The version of the report format, or 0 if `report` is NULL.
+///
+/// # Safety
+///
+/// `report` must be NULL or have been returned by [sev_snp_report_parse].
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_version(report: *const SevSnpReport) -> u32 {
+    report.as_ref().map_or(0, |report| report.0.version)
+}
+
+/// The guest SVN, or 0 if `report` is NULL.
+///
+/// # Safety
+///
+/// `report` must be NULL or have been returned by [sev_snp_report_parse].
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_guest_svn(report: *const SevSnpReport) -> u32 {
+    report.as_ref().map_or(0, |report| report.0.guest_svn)
+}
+
+/// The guest policy, or 0 if `report` is NULL.
+///
+/// # Safety
+///
+/// `report` must be NULL or have been returned by [sev_snp_report_parse].
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_policy(report: *const SevSnpReport) -> u64 {
+    report.as_ref().map_or(0, |report| report.0.policy.into())
+}
+
+/// The VMPL the report was requested at, or 0 if `report` is NULL.
+///
+/// # Safety
+///
+/// `report` must be NULL or have been returned by [sev_snp_report_parse].
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_vmpl(report: *const SevSnpReport) -> u32 {
+    report.as_ref().map_or(0, |report| report.0.vmpl)
+}
+
+/// Copy the report field selected by `field` (a `SEV_SNP_FIELD_*`) to the
+/// `len` bytes at `out`, returning the length of the field.
+///
+/// # Safety
+///
+/// The caller of this function is responsible for ensuring that the pointer arguments are
+/// valid.
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_field(
+    report: *const SevSnpReport,
+    field: c_int,
+    out: *mut u8,
+    len: usize,
+) -> c_int {
+    let report = match report.as_ref() {
+        Some(report) if !out.is_null() => &report.0,
+        _ => return SEV_CAPI_ERR_NULL,
+    };
+
+    let value: &[u8] = match field {
+        SEV_SNP_FIELD_MEASUREMENT => report.measurement.as_bytes(),
+        SEV_SNP_FIELD_REPORT_DATA => report.report_data.as_bytes(),
+        SEV_SNP_FIELD_HOST_DATA => &report.host_data,
+        SEV_SNP_FIELD_FAMILY_ID => &report.family_id,
+        SEV_SNP_FIELD_IMAGE_ID => &report.image_id,
+        SEV_SNP_FIELD_ID_KEY_DIGEST => report.id_key_digest.as_bytes(),
+        SEV_SNP_FIELD_AUTHOR_KEY_DIGEST => report.author_key_digest.as_bytes(),
+        SEV_SNP_FIELD_REPORT_ID => &report.report_id,
+        SEV_SNP_FIELD_REPORT_ID_MA => &report.report_id_ma,
+        SEV_SNP_FIELD_CHIP_ID => report.chip_id.as_bytes(),
+        _ => return SEV_CAPI_ERR_ARGUMENT,
+    };
+
+    if len < value.len() {
+        return SEV_CAPI_ERR_LENGTH;
+    }
+
+    from_raw_parts_mut(out, value.len()).copy_from_slice(value);
+
+    value.len() as c_int
+}
+
+/// Store the TCB version selected by `tcb` (a `SEV_SNP_TCB_*`) in `out`.
+///
+/// # Safety
+///
+/// The caller of this function is responsible for ensuring that the pointer arguments are
+/// valid.
+#[no_mangle]
+pub unsafe extern "C" fn sev_snp_report_tcb(
+    report: *const SevSnpReport,
+    tcb: c_int,
+    out: *mut SevSnpTcb,
+) -> c_int {
+    let report = match report.as_ref() {
+        Some(report) if !out.is_null() => &report.0,
+        _ => return SEV_CAPI_ERR_NULL,
+    };
+
+    let version = match tcb {
+        SEV_SNP_TCB_CURRENT => report.current_tcb,
+        SEV_SNP_TCB_REPORTED => report.reported_tcb,
+        SEV_SNP_TCB_COMMITTED => report.committed_tcb,
+        SEV_SNP_TCB_LAUNCH => report.launch_tcb,
+        _ => return SEV_CAPI_ERR_ARGUMENT,
+    };
+
+    *out = version.into();
+
+    0
+}
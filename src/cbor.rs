@@ -0,0 +1,467 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! CBOR encodings of SEV-SNP evidence, for exchanging it with RATS
+//! verifiers.
+//!
+//! [Evidence] is encoded as a CBOR map of the attestation report, as the
+//! bytes the AMD Secure Processor signed, and the certificate table returned
+//! with it:
+//!
+//! ```cddl
+//! sev-snp-evidence = {
+//!   &(report: 1) => bstr .size 1184
+//!   ? &(certificates: 2) => [+ cert-table-entry]
+//! }
+//!
+//! cert-table-entry = [
+//!   guid: #6.37(bstr .size 16)
+//!   data: bstr
+//! ]
+//! ```
+//!
+//! The encoded evidence may be carried in a RATS conceptual message wrapper
+//! (CMW) record of [MEDIA_TYPE], and, with the `cose` feature, in a
+//! `COSE_Sign1` message of the same content type signed by the attester's
+//! key. Decoding evidence fails with a [CborError] if it is malformed.
+//!
+//! # Example:
+//! ```ignore
+//! let evidence = Evidence::from((&report, &certs[..]));
+//! let cmw = evidence.to_cmw()?;
+//!
+//! let (report, certs) = Evidence::from_cmw(&cmw)?.into();
+//! ```
+
+use crate::{
+    error::CborError,
+    firmware::{guest::AttestationReport as Report, host::CertTableEntry},
+};
+
+use ciborium::value::Value;
+
+use std::convert::{TryFrom, TryInto};
+
+#[cfg(feature = "cose")]
+pub use coset::iana::Algorithm;
+
+#[cfg(feature = "cose")]
+use coset::{CborSerializable, TaggedCborSerializable};
+
+/// The media type of encoded SEV-SNP evidence.
+pub const MEDIA_TYPE: &str = "application/vnd.virtee.sev-snp-evidence+cbor";
+
+/// The CMW indicator of evidence.
+const CMW_EVIDENCE: u64 = 1 << 2;
+
+/// The CBOR tag of a UUID.
+const UUID_TAG: u64 = 37;
+
+/// The map key of the attestation report.
+const REPORT_KEY: u64 = 1;
+
+/// The map key of the certificate table.
+const CERTIFICATES_KEY: u64 = 2;
+
+/// An attestation report and the certificates endorsing it
+#[derive(Clone, Debug)]
+pub struct Evidence {
+    /// The attestation report.
+    pub report: Report,
+
+    /// The certificates returned with the report, if any.
+    pub certificates: Vec<CertTableEntry>,
+}
+
+impl Evidence {
+    /// Encode the evidence as CBOR.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, CborError> {
+        let mut map = vec![(
+            Value::from(REPORT_KEY),
+            Value::Bytes(self.report.to_bytes().to_vec()),
+        )];
+
+        if !self.certificates.is_empty() {
+            let entries = self
+                .certificates
+                .iter()
+                .map(|entry| {
+                    let guid = uuid::Uuid::parse_str(&entry.guid_string())
+                        .map_err(|_| CborError::InvalidGuid(entry.guid_string()))?;
+
+                    Ok(Value::Array(vec![
+                        Value::Tag(UUID_TAG, Box::new(Value::Bytes(guid.as_bytes().to_vec()))),
+                        Value::Bytes(entry.data.clone()),
+                    ]))
+                })
+                .collect::<Result<_, CborError>>()?;
+
+            map.push((Value::from(CERTIFICATES_KEY), Value::Array(entries)));
+        }
+
+        encode(&Value::Map(map))
+    }
+
+    /// Decode evidence encoded as CBOR.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, CborError> {
+        let map = match decode(bytes)? {
+            Value::Map(map) => map,
+            _ => return Err(CborError::InvalidField("evidence")),
+        };
+
+        let mut report = None;
+        let mut certificates = vec![];
+
+        for (key, value) in map {
+            match key_of(&key) {
+                Some(REPORT_KEY) if report.is_none() => {
+                    let bytes = bytes_of("report", value)?;
+                    let bytes: [u8; Report::SIZE] =
+                        bytes
+                            .as_slice()
+                            .try_into()
+                            .map_err(|_| CborError::InvalidLength {
+                                field: "report",
+                                expected: Report::SIZE,
+                                actual: bytes.len(),
+                            })?;
+
+                    report = Some(Report::from_bytes(&bytes));
+                }
+                Some(CERTIFICATES_KEY) if certificates.is_empty() => {
+                    let entries = match value {
+                        Value::Array(entries) if !entries.is_empty() => entries,
+                        _ => return Err(CborError::InvalidField("certificates")),
+                    };
+
+                    certificates = entries
+                        .into_iter()
+                        .map(cert_table_entry)
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(CborError::InvalidField("evidence")),
+            }
+        }
+
+        Ok(Self {
+            report: report.ok_or(CborError::InvalidField("report"))?,
+            certificates,
+        })
+    }
+
+    /// Wrap the encoded evidence in a CMW record of [MEDIA_TYPE].
+    pub fn to_cmw(&self) -> Result<Vec<u8>, CborError> {
+        encode(&Value::Array(vec![
+            Value::Text(MEDIA_TYPE.into()),
+            Value::Bytes(self.to_cbor()?),
+            Value::from(CMW_EVIDENCE),
+        ]))
+    }
+
+    /// Decode the evidence wrapped in a CMW record of [MEDIA_TYPE].
+    pub fn from_cmw(bytes: &[u8]) -> Result<Self, CborError> {
+        let mut record = match decode(bytes)? {
+            Value::Array(record) if (2..=3).contains(&record.len()) => record.into_iter(),
+            _ => return Err(CborError::InvalidField("cmw")),
+        };
+
+        match record.next() {
+            Some(Value::Text(media_type)) if media_type == MEDIA_TYPE => (),
+            Some(Value::Text(media_type)) => return Err(CborError::UnexpectedType(media_type)),
+            _ => return Err(CborError::InvalidField("type")),
+        }
+
+        let value = bytes_of("value", record.next().unwrap())?;
+
+        match record.next().map(|ind| key_of(&ind)) {
+            None => (),
+            Some(Some(ind)) if ind & CMW_EVIDENCE != 0 => (),
+            Some(_) => return Err(CborError::InvalidField("ind")),
+        }
+
+        Self::from_cbor(&value)
+    }
+
+    /// Sign the encoded evidence into a tagged `COSE_Sign1` message, whose
+    /// protected header names `algorithm` and [MEDIA_TYPE].
+    ///
+    /// `sign` returns the signature of the bytes it is passed, in the
+    /// encoding COSE specifies for `algorithm`.
+    #[cfg(feature = "cose")]
+    pub fn to_cose_sign1<E: std::fmt::Display>(
+        &self,
+        algorithm: Algorithm,
+        sign: impl FnOnce(&[u8]) -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, CborError> {
+        let protected = coset::HeaderBuilder::new()
+            .algorithm(algorithm)
+            .content_type(MEDIA_TYPE.into())
+            .build();
+
+        coset::CoseSign1Builder::new()
+            .protected(protected)
+            .payload(self.to_cbor()?)
+            .try_create_signature(&[], sign)
+            .map_err(|e| CborError::Signature(e.to_string()))?
+            .build()
+            .to_tagged_vec()
+            .map_err(|e| CborError::Cose(e.to_string()))
+    }
+
+    /// Decode the evidence signed into a `COSE_Sign1` message, tagged or
+    /// not, once `verify` accepts its signature.
+    ///
+    /// `verify` is passed the algorithm named by the protected header, if
+    /// any, the signature and the bytes signed.
+    #[cfg(feature = "cose")]
+    pub fn from_cose_sign1<E: std::fmt::Display>(
+        bytes: &[u8],
+        verify: impl FnOnce(Option<&coset::Algorithm>, &[u8], &[u8]) -> Result<(), E>,
+    ) -> Result<Self, CborError> {
+        let message = coset::CoseSign1::from_tagged_slice(bytes)
+            .or_else(|_| coset::CoseSign1::from_slice(bytes))
+            .map_err(|e| CborError::Cose(e.to_string()))?;
+
+        match &message.protected.header.content_type {
+            Some(coset::ContentType::Text(media_type)) if media_type == MEDIA_TYPE => (),
+            Some(coset::ContentType::Text(media_type)) => {
+                return Err(CborError::UnexpectedType(media_type.clone()))
+            }
+            _ => return Err(CborError::InvalidField("content type")),
+        }
+
+        let algorithm = message.protected.header.alg.as_ref();
+        message
+            .verify_signature(&[], |signature, data| verify(algorithm, signature, data))
+            .map_err(|e| CborError::Signature(e.to_string()))?;
+
+        Self::from_cbor(
+            message
+                .payload
+                .as_deref()
+                .ok_or(CborError::InvalidField("payload"))?,
+        )
+    }
+}
+
+impl From<(&Report, &[CertTableEntry])> for Evidence {
+    fn from((report, certificates): (&Report, &[CertTableEntry])) -> Self {
+        Self {
+            report: *report,
+            certificates: certificates.to_vec(),
+        }
+    }
+}
+
+impl From<Evidence> for (Report, Vec<CertTableEntry>) {
+    fn from(evidence: Evidence) -> Self {
+        (evidence.report, evidence.certificates)
+    }
+}
+
+fn encode(value: &Value) -> Result<Vec<u8>, CborError> {
+    let mut bytes = vec![];
+    ciborium::ser::into_writer(value, &mut bytes).map_err(|e| CborError::Cbor(e.to_string()))?;
+
+    Ok(bytes)
+}
+
+fn decode(mut bytes: &[u8]) -> Result<Value, CborError> {
+    let value =
+        ciborium::de::from_reader(&mut bytes).map_err(|e| CborError::Cbor(e.to_string()))?;
+
+    match bytes.is_empty() {
+        true => Ok(value),
+        false => Err(CborError::Cbor(format!(
+            "{} bytes follow the CBOR item",
+            bytes.len()
+        ))),
+    }
+}
+
+/// The unsigned integer `value` holds, if any.
+fn key_of(value: &Value) -> Option<u64> {
+    value.as_integer().and_then(|key| u64::try_from(key).ok())
+}
+
+fn bytes_of(field: &'static str, value: Value) -> Result<Vec<u8>, CborError> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        _ => Err(CborError::InvalidField(field)),
+    }
+}
+
+fn cert_table_entry(value: Value) -> Result<CertTableEntry, CborError> {
+    let (guid, data) = match value {
+        Value::Array(entry) => match <[Value; 2]>::try_from(entry) {
+            Ok([Value::Tag(UUID_TAG, guid), data]) => (bytes_of("guid", *guid)?, data),
+            _ => return Err(CborError::InvalidField("certificates")),
+        },
+        _ => return Err(CborError::InvalidField("certificates")),
+    };
+
+    let guid = uuid::Uuid::from_slice(&guid).map_err(|_| CborError::InvalidLength {
+        field: "guid",
+        expected: 16,
+        actual: guid.len(),
+    })?;
+
+    CertTableEntry::from_guid(&guid, bytes_of("data", data)?)
+        .map_err(|_| CborError::InvalidGuid(guid.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::firmware::host::CertType;
+
+    const TEST_MILAN_VCEK_DER: &[u8] = include_bytes!("../tests/certs_data/vcek_milan.der");
+
+    fn evidence() -> Evidence {
+        let certs = [
+            CertTableEntry::new(CertType::VCEK, TEST_MILAN_VCEK_DER.to_vec()),
+            CertTableEntry::new(
+                CertType::OTHER(uuid::uuid!("0b7c2a9f-3f55-4b4e-9d4c-2a7f0e6d1c3b")),
+                vec![1, 2, 3],
+            ),
+        ];
+
+        Evidence::from((&Report::milan(), &certs[..]))
+    }
+
+    #[test]
+    fn round_trip() {
+        let evidence = evidence();
+        let bytes = evidence.to_cbor().unwrap();
+
+        // A map of two entries, the first the report as a byte string.
+        assert_eq!(bytes[..5], [0xa2, 0x01, 0x59, 0x04, 0xa0]);
+        assert_eq!(bytes[5..5 + Report::SIZE], Report::milan().to_bytes()[..]);
+
+        let (report, certs) = Evidence::from_cbor(&bytes).unwrap().into();
+        assert_eq!(report.to_bytes()[..], evidence.report.to_bytes()[..]);
+        assert_eq!(certs, evidence.certificates);
+    }
+
+    #[test]
+    fn without_certificates() {
+        let evidence = Evidence::from((&Report::milan(), &[][..]));
+        let bytes = evidence.to_cbor().unwrap();
+        assert_eq!(bytes[0], 0xa1);
+
+        assert!(Evidence::from_cbor(&bytes).unwrap().certificates.is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes = evidence().to_cbor().unwrap();
+
+        bytes.push(0);
+        assert!(matches!(
+            Evidence::from_cbor(&bytes),
+            Err(CborError::Cbor(_))
+        ));
+
+        // A report one byte short.
+        let mut bytes = Evidence::from((&Report::milan(), &[][..]))
+            .to_cbor()
+            .unwrap();
+        bytes[4] -= 1;
+        bytes.pop();
+        assert_eq!(
+            Evidence::from_cbor(&bytes).unwrap_err(),
+            CborError::InvalidLength {
+                field: "report",
+                expected: Report::SIZE,
+                actual: Report::SIZE - 1
+            }
+        );
+
+        // An empty map.
+        assert_eq!(
+            Evidence::from_cbor(&[0xa0]).unwrap_err(),
+            CborError::InvalidField("report")
+        );
+    }
+
+    #[test]
+    fn cmw() {
+        let evidence = evidence();
+        let bytes = evidence.to_cmw().unwrap();
+
+        // A record of the media type, the evidence and the evidence indicator.
+        assert_eq!(bytes[..3], [0x83, 0x78, MEDIA_TYPE.len() as u8]);
+        assert_eq!(bytes[3..3 + MEDIA_TYPE.len()], *MEDIA_TYPE.as_bytes());
+        assert_eq!(bytes[bytes.len() - 1], 0x04);
+
+        let decoded = Evidence::from_cmw(&bytes).unwrap();
+        assert_eq!(decoded.certificates, evidence.certificates);
+
+        let mut bytes = bytes;
+        bytes[3] = b'x';
+        assert!(matches!(
+            Evidence::from_cmw(&bytes),
+            Err(CborError::UnexpectedType(_))
+        ));
+    }
+
+    #[cfg(feature = "cose")]
+    mod cose {
+        use super::*;
+
+        /// A stand-in for a signature: the bytes signed, reversed.
+        fn sign(data: &[u8]) -> Result<Vec<u8>, String> {
+            Ok(data.iter().rev().copied().collect())
+        }
+
+        fn verify(
+            algorithm: Option<&coset::Algorithm>,
+            signature: &[u8],
+            data: &[u8],
+        ) -> Result<(), String> {
+            assert_eq!(
+                algorithm,
+                Some(&coset::Algorithm::Assigned(Algorithm::ES384))
+            );
+
+            match sign(data)? == signature {
+                true => Ok(()),
+                false => Err("bad signature".into()),
+            }
+        }
+
+        #[test]
+        fn round_trip() {
+            let evidence = evidence();
+            let bytes = evidence.to_cose_sign1(Algorithm::ES384, sign).unwrap();
+
+            // Tagged COSE_Sign1.
+            assert_eq!(bytes[..2], [0xd2, 0x84]);
+
+            let decoded = Evidence::from_cose_sign1(&bytes, verify).unwrap();
+            assert_eq!(
+                decoded.report.to_bytes()[..],
+                evidence.report.to_bytes()[..]
+            );
+            assert_eq!(decoded.certificates, evidence.certificates);
+        }
+
+        #[test]
+        fn rejected() {
+            let mut bytes = evidence().to_cose_sign1(Algorithm::ES384, sign).unwrap();
+
+            let last = bytes.len() - 1;
+            bytes[last] ^= 0xff;
+
+            assert_eq!(
+                Evidence::from_cose_sign1(&bytes, verify).unwrap_err(),
+                CborError::Signature("bad signature".into())
+            );
+
+            assert!(matches!(
+                evidence().to_cose_sign1(Algorithm::ES384, |_| Err("no key")),
+                Err(CborError::Signature(_))
+            ));
+        }
+    }
+}
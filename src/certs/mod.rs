@@ -5,5 +5,5 @@
 pub mod sev;
 
 /// SEV-SNP certificates.
-#[cfg(feature = "snp")]
+#[cfg(feature = "snp-types")]
 pub mod snp;
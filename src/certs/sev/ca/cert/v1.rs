@@ -52,6 +52,14 @@ pub struct Preamble {
 }
 
 impl Preamble {
+    /// The offset of the first nonzero reserved byte, if any.
+    pub(crate) fn nonzero_reserved(&self) -> Option<usize> {
+        // The reserved field follows the version, key ID, signer ID and usage.
+        let reserved = self.data.reserved;
+        let pos = reserved.iter().position(|b| *b != 0)?;
+        Some(size_of::<u32>() + 32 + size_of::<Usage>() + pos)
+    }
+
     fn size(&self) -> Result<Size> {
         if self.data.psize != self.data.msize {
             return Err(ErrorKind::InvalidInput.into());
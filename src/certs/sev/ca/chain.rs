@@ -17,6 +17,11 @@ pub struct Chain {
     pub ark: Certificate,
 }
 
+impl crate::snapshot::Versioned for Chain {
+    const KIND: [u8; 4] = *b"OCAC";
+    const VERSION: u16 = 1;
+}
+
 impl codicon::Decoder<()> for Chain {
     type Error = Error;
 
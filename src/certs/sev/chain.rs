@@ -17,6 +17,11 @@ pub struct Chain {
     pub sev: sev::Chain,
 }
 
+impl crate::snapshot::Versioned for Chain {
+    const KIND: [u8; 4] = *b"SEVC";
+    const VERSION: u16 = 1;
+}
+
 impl codicon::Decoder<()> for Chain {
     type Error = Error;
 
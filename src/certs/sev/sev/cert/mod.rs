@@ -6,6 +6,8 @@ pub(crate) mod v1;
 
 use super::*;
 
+use crate::{error::ParseError, ParseOptions};
+
 use std::mem::size_of;
 
 use serde::{de, ser};
@@ -75,13 +77,38 @@ impl<U: Copy + Into<crate::certs::sev::Usage>> PartialEq<U> for Certificate {
 impl codicon::Decoder<()> for Certificate {
     type Error = Error;
 
-    fn decode(mut reader: impl Read, params: ()) -> Result<Self> {
-        Ok(match u32::from_le(reader.load()?) {
+    fn decode(reader: impl Read, _: ()) -> Result<Self> {
+        Self::decode(reader, ParseOptions::default())
+    }
+}
+
+impl codicon::Decoder<ParseOptions> for Certificate {
+    type Error = Error;
+
+    fn decode(mut reader: impl Read, options: ParseOptions) -> Result<Self> {
+        let version = u32::from_le(reader.load()?);
+
+        let cert = match version {
             1 => Certificate {
-                v1: v1::Certificate::decode(reader, params)?,
+                v1: v1::Certificate::decode(reader, ())?,
             },
-            _ => return Err(ErrorKind::InvalidData.into()),
-        })
+            _ if options.allow_unknown_versions => {
+                let mut cert = Certificate {
+                    v1: v1::Certificate::decode(reader, ())?,
+                };
+                cert.version = version.to_le();
+                cert
+            }
+            _ => return Err(ParseError::UnknownVersion(version).into()),
+        };
+
+        if options.strict_reserved {
+            if let Some(offset) = unsafe { cert.v1 }.nonzero_reserved() {
+                return Err(ParseError::Reserved(offset).into());
+            }
+        }
+
+        Ok(cert)
     }
 }
 
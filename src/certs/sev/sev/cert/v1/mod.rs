@@ -29,6 +29,31 @@ impl Certificate {
     }
 }
 
+impl Certificate {
+    /// The offset of the first nonzero reserved byte, if any.
+    pub(crate) fn nonzero_reserved(&self) -> Option<usize> {
+        // The reserved field follows the version and the firmware version.
+        if self.body.data.reserved != 0 {
+            let pos = self
+                .body
+                .data
+                .reserved
+                .to_le_bytes()
+                .iter()
+                .position(|b| *b != 0)?;
+            return Some(size_of::<u32>() + size_of::<crate::Version>() + pos);
+        }
+
+        self.sigs.iter().enumerate().find_map(|(i, sig)| {
+            Some(
+                size_of::<body::Body>()
+                    + i * size_of::<sig::Signature>()
+                    + sig.nonzero_reserved()?,
+            )
+        })
+    }
+}
+
 impl codicon::Decoder<()> for Certificate {
     type Error = Error;
 
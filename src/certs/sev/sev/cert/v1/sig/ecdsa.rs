@@ -3,7 +3,7 @@
 #[cfg(feature = "openssl")]
 use {super::*, openssl::ecdsa};
 
-use crate::util::hexdump;
+use crate::encoding::HexBytes;
 
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
@@ -22,6 +22,14 @@ pub struct Signature {
     _reserved: [u8; 512 - (SIG_PIECE_SIZE * 2)],
 }
 
+impl Signature {
+    /// The offset of the first nonzero reserved byte, if any.
+    pub(crate) fn nonzero_reserved(&self) -> Option<usize> {
+        let pos = self._reserved.iter().position(|b| *b != 0)?;
+        Some(SIG_PIECE_SIZE * 2 + pos)
+    }
+}
+
 impl std::fmt::Debug for Signature {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
@@ -60,8 +68,8 @@ Signature:
   R: {}
   S: {}
             "#,
-            hexdump(&self.r),
-            hexdump(&self.s)
+            HexBytes(self.r),
+            HexBytes(self.s)
         )
     }
 }
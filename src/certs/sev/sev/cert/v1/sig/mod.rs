@@ -62,6 +62,21 @@ impl std::fmt::Debug for Signature {
     }
 }
 
+impl Signature {
+    /// The offset of the first nonzero reserved byte of an ECDSA signature,
+    /// if any.
+    pub(crate) fn nonzero_reserved(&self) -> Option<usize> {
+        match self.algo {
+            Algorithm::ECDSA_SHA256 | Algorithm::ECDSA_SHA384 => Some(
+                std::mem::size_of::<Usage>()
+                    + std::mem::size_of::<Algorithm>()
+                    + unsafe { self.sig.ecdsa }.nonzero_reserved()?,
+            ),
+            _ => None,
+        }
+    }
+}
+
 impl Eq for Signature {}
 impl PartialEq for Signature {
     fn eq(&self, other: &Signature) -> bool {
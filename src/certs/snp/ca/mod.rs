@@ -18,7 +18,7 @@ pub struct Chain {
 impl<'a> Verifiable for &'a Chain {
     type Output = &'a Certificate;
 
-    fn verify(self) -> Result<Self::Output> {
+    fn verify(self) -> std::result::Result<Self::Output, VerificationError> {
         // Verify that ARK is self-signed.
         (&self.ark, &self.ark).verify()?;
 
@@ -63,19 +63,16 @@ impl From<&Certificate> for X509 {
 impl Verifiable for (&Certificate, &Certificate) {
     type Output = ();
 
-    fn verify(self) -> Result<Self::Output> {
+    fn verify(self) -> std::result::Result<Self::Output, VerificationError> {
         let signer: X509 = self.0.into();
         let signee: X509 = self.1.into();
 
-        let key: PKey<Public> = signer.public_key()?;
+        let key: PKey<Public> = signer.public_key().map_err(VerificationError::parse)?;
         let signed = signee.verify(&key)?;
 
         match signed {
             true => Ok(()),
-            false => Err(Error::new(
-                ErrorKind::Other,
-                "Signer certificate does not sign signee certificate",
-            )),
+            false => Err(VerificationError::CertificateSignature),
         }
     }
 }
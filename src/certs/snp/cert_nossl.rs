@@ -23,13 +23,13 @@ const RSA_SSA_PSS_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.
 impl Verifiable for (&Certificate, &Certificate) {
     type Output = ();
 
-    fn verify(self) -> Result<Self::Output> {
+    fn verify(self) -> std::result::Result<Self::Output, VerificationError> {
         let signer = &self.0 .0;
         let signee = &self.1 .0;
 
         if signee.signature_algorithm.oid != RSA_SSA_PSS_OID {
-            return Err(io_error_other(format!(
-                "unsupported signature algorithm: {:?}",
+            return Err(VerificationError::UnsupportedAlgorithm(format!(
+                "{:?}",
                 signee.signature_algorithm
             )));
         }
@@ -40,24 +40,20 @@ impl Verifiable for (&Certificate, &Certificate) {
                 .subject_public_key_info
                 .owned_to_ref();
             let signer_pubkey_rsa = rsa::RsaPublicKey::try_from(signer_spki_ref)
-                .map_err(|e| io_error_other(format!("invalid RSA public key: {e:?}")))?;
+                .map_err(|e| VerificationError::parse(format!("invalid RSA public key: {e:?}")))?;
             rsa::pss::VerifyingKey::<sha2::Sha384>::new(signer_pubkey_rsa)
         };
 
         let message = signee.tbs_certificate.to_der().map_err(|e| {
-            io_error_other(format!("failed to encode tbs_certificate as DER: {e:?}"))
+            VerificationError::parse(format!("failed to encode tbs_certificate as DER: {e:?}"))
         })?;
 
         let rsa_signature = rsa::pss::Signature::try_from(signee.signature.raw_bytes())
-            .map_err(|e| io_error_other(format!("invalid RSA signature: {e:?}")))?;
+            .map_err(|e| VerificationError::parse(format!("invalid RSA signature: {e:?}")))?;
 
         rsa_verifying_key
             .verify(&message, &rsa_signature)
-            .map_err(|e| {
-                io_error_other(format!(
-                    "Signer certificate does not RSA sign signee certificate: {e}"
-                ))
-            })
+            .map_err(|_| VerificationError::CertificateSignature)
     }
 }
 
@@ -2,7 +2,10 @@
 
 use super::*;
 
-use crate::firmware::host::{CertTableEntry, CertType};
+use crate::firmware::{
+    guest::AttestationReport,
+    host::{CertTableEntry, CertType},
+};
 
 /// Interfaces for a complete SEV-SNP certificate chain.
 
@@ -17,7 +20,7 @@ pub struct Chain {
 impl<'a> Verifiable for &'a Chain {
     type Output = &'a Certificate;
 
-    fn verify(self) -> Result<Self::Output> {
+    fn verify(self) -> std::result::Result<Self::Output, VerificationError> {
         // Verify that ARK is self-signed and ARK signs ASK.
         let ask = self.ca.verify()?;
 
@@ -35,63 +38,95 @@ enum ChainEncodingFormat {
 
     /// PEM-encoded.
     Pem,
+
+    /// DER or PEM-encoded, entry by entry.
+    Any,
 }
 
 impl Chain {
     /// Derive a chain from a DER-encoded FFI Certificate table.
-    pub fn from_cert_table_der(entries: Vec<CertTableEntry>) -> Result<Self> {
+    pub fn from_cert_table_der(
+        entries: Vec<CertTableEntry>,
+    ) -> std::result::Result<Self, VerificationError> {
         Self::parse_from_cert_table(entries, ChainEncodingFormat::Der)
     }
 
     /// Derive a chain from a PEM-encoded FFI Certificate table.
-    pub fn from_cert_table_pem(entries: Vec<CertTableEntry>) -> Result<Self> {
+    pub fn from_cert_table_pem(
+        entries: Vec<CertTableEntry>,
+    ) -> std::result::Result<Self, VerificationError> {
         Self::parse_from_cert_table(entries, ChainEncodingFormat::Pem)
     }
 
+    /// Derive the chain verifying `report` from a certificate table with
+    /// DER or PEM-encoded entries: its VEK is the VCEK or the VLEK, whichever
+    /// the report names as its signing key, even if the table holds both.
+    pub fn from_cert_table_for(
+        entries: Vec<CertTableEntry>,
+        report: &AttestationReport,
+    ) -> std::result::Result<Self, VerificationError> {
+        let vek_type = report.vek_type()?;
+
+        if !entries.iter().any(|entry| entry.cert_type == vek_type) {
+            return Err(VerificationError::MissingCertificate(vek_type));
+        }
+
+        let entries = entries
+            .into_iter()
+            .filter(|entry| match entry.cert_type {
+                CertType::VCEK | CertType::VLEK => entry.cert_type == vek_type,
+                _ => true,
+            })
+            .collect();
+
+        Self::parse_from_cert_table(entries, ChainEncodingFormat::Any)
+    }
+
     /// Private function to parse the bytes. Used by both from_der() and from_pem().
     fn parse_from_cert_table(
         entries: Vec<CertTableEntry>,
         format: ChainEncodingFormat,
-    ) -> Result<Self> {
+    ) -> std::result::Result<Self, VerificationError> {
         let mut ark: Option<Certificate> = None;
         let mut ask: Option<Certificate> = None;
         let mut vcek: Option<Certificate> = None;
         let mut vlek: Option<Certificate> = None;
 
-        let other = ErrorKind::Other;
-
         // Traverse each certificate in the table, find the ARK, ASK, and VCEK.
         for entry in entries {
             let cert = match format {
-                ChainEncodingFormat::Der => Certificate::from_der(entry.data.as_slice())?,
-                ChainEncodingFormat::Pem => Certificate::from_pem(entry.data.as_slice())?,
-            };
+                ChainEncodingFormat::Der => Certificate::from_der(entry.data.as_slice()),
+                ChainEncodingFormat::Pem => Certificate::from_pem(entry.data.as_slice()),
+                ChainEncodingFormat::Any => Certificate::from_der(entry.data.as_slice())
+                    .or_else(|_| Certificate::from_pem(entry.data.as_slice())),
+            }
+            .map_err(VerificationError::parse)?;
 
             match entry.cert_type {
                 CertType::ARK => {
                     if ark.is_some() {
-                        return Err(Error::new(other, "more than one ARK certificate found"));
+                        return Err(VerificationError::DuplicateCertificate(CertType::ARK));
                     }
 
                     ark = Some(cert);
                 }
                 CertType::ASK => {
                     if ask.is_some() {
-                        return Err(Error::new(other, "more than one ASK certificate found"));
+                        return Err(VerificationError::DuplicateCertificate(CertType::ASK));
                     }
 
                     ask = Some(cert);
                 }
                 CertType::VCEK => {
                     if vcek.is_some() {
-                        return Err(Error::new(other, "more than one VCEK certificate found"));
+                        return Err(VerificationError::DuplicateCertificate(CertType::VCEK));
                     }
 
                     vcek = Some(cert);
                 }
                 CertType::VLEK => {
                     if vlek.is_some() {
-                        return Err(Error::new(other, "more than one VLEK certificate found"));
+                        return Err(VerificationError::DuplicateCertificate(CertType::VLEK));
                     }
 
                     vlek = Some(cert);
@@ -102,11 +137,11 @@ impl Chain {
 
         // Chain cannot be built without ARK, ASK, and VCEK.
         if ark.is_none() {
-            return Err(Error::new(other, "ARK not found"));
+            return Err(VerificationError::MissingCertificate(CertType::ARK));
         } else if ask.is_none() {
-            return Err(Error::new(other, "ASK not found"));
+            return Err(VerificationError::MissingCertificate(CertType::ASK));
         } else if vcek.is_none() && vlek.is_none() {
-            return Err(Error::new(other, "VCEK/VLEK not found"));
+            return Err(VerificationError::MissingCertificate(CertType::VCEK));
         }
 
         // Use the VLEK whenever it is present, but use the VCEK when VLEK is missing.
@@ -133,6 +168,24 @@ impl Chain {
         })
     }
 
+    /// Serialize the chain to a certificate table with DER-encoded entries,
+    /// the VEK being of type `vek` (either [CertType::VCEK] or
+    /// [CertType::VLEK]). The inverse of [Chain::from_cert_table_der].
+    pub fn to_cert_table(&self, vek: CertType) -> Result<Vec<CertTableEntry>> {
+        if vek != CertType::VCEK && vek != CertType::VLEK {
+            return Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the VEK of a chain is either a VCEK or a VLEK",
+            ));
+        }
+
+        Ok(vec![
+            CertTableEntry::new(CertType::ARK, self.ca.ark.to_der()?),
+            CertTableEntry::new(CertType::ASK, self.ca.ask.to_der()?),
+            CertTableEntry::new(vek, self.vek.to_der()?),
+        ])
+    }
+
     /// Deserialize a DER-encoded ARK, ASK, and VEK to a SEV-SNP chain.
     pub fn from_der(ark: &[u8], ask: &[u8], vek: &[u8]) -> Result<Self> {
         Ok(Self {
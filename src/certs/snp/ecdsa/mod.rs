@@ -3,12 +3,15 @@
 #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
 use super::*;
 
-use crate::util::hexdump;
+use crate::{
+    encoding::HexBytes,
+    util::{LeReader, LeWriter},
+};
 
 #[cfg(feature = "openssl")]
 use crate::certs::snp::{AsLeBytes, FromLe};
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+#[cfg(any(feature = "openssl", feature = "crypto_nossl", feature = "proto"))]
 use std::convert::TryFrom;
 
 use serde::{Deserialize, Serialize};
@@ -17,7 +20,7 @@ use serde_big_array::BigArray;
 #[cfg(feature = "openssl")]
 use openssl::{bn, ecdsa};
 
-const SIG_PIECE_SIZE: usize = std::mem::size_of::<[u8; 72]>();
+const SIG_PIECE_SIZE: usize = core::mem::size_of::<[u8; 72]>();
 const R_S_SIZE: usize = SIG_PIECE_SIZE * 2usize;
 
 #[repr(C)]
@@ -33,6 +36,32 @@ pub struct Signature {
 }
 
 impl Signature {
+    /// Size (in bytes) of a signature in an attestation report.
+    pub const SIZE: usize = 512;
+
+    /// Decode a signature as the AMD Secure Processor lays it out.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let mut reader = LeReader::new(bytes);
+
+        Self {
+            r: reader.bytes(),
+            s: reader.bytes(),
+            _reserved: reader.bytes(),
+        }
+    }
+
+    /// Encode the signature as the AMD Secure Processor lays it out.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+
+        let mut writer = LeWriter::new(&mut bytes);
+        writer.bytes(&self.r);
+        writer.bytes(&self.s);
+        writer.bytes(&self._reserved);
+
+        bytes
+    }
+
     /// Returns the signatures `r` component
     pub fn r(&self) -> &[u8; 72] {
         &self.r
@@ -44,8 +73,8 @@ impl Signature {
     }
 }
 
-impl std::fmt::Debug for Signature {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Debug for Signature {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
             "Signature {{ r: {:?}, s: {:?} }}",
@@ -72,8 +101,8 @@ impl Default for Signature {
     }
 }
 
-impl std::fmt::Display for Signature {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Signature {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             r#"
@@ -81,12 +110,35 @@ Signature:
   R: {}
   S: {}
             "#,
-            hexdump(&self.r),
-            hexdump(&self.s)
+            HexBytes(self.r),
+            HexBytes(self.s)
         )
     }
 }
 
+#[cfg(feature = "proto")]
+impl From<&Signature> for crate::proto::Signature {
+    fn from(value: &Signature) -> Self {
+        Self {
+            r: value.r.to_vec(),
+            s: value.s.to_vec(),
+        }
+    }
+}
+
+#[cfg(feature = "proto")]
+impl TryFrom<crate::proto::Signature> for Signature {
+    type Error = crate::error::ProtoError;
+
+    fn try_from(value: crate::proto::Signature) -> core::result::Result<Self, Self::Error> {
+        Ok(Signature {
+            r: crate::proto::array("r", &value.r)?,
+            s: crate::proto::array("s", &value.s)?,
+            _reserved: [0; 512 - (SIG_PIECE_SIZE * 2)],
+        })
+    }
+}
+
 #[cfg(feature = "openssl")]
 impl From<ecdsa::EcdsaSig> for Signature {
     #[inline]
@@ -99,6 +151,24 @@ impl From<ecdsa::EcdsaSig> for Signature {
     }
 }
 
+#[cfg(feature = "crypto_nossl")]
+impl From<p384::ecdsa::Signature> for Signature {
+    #[inline]
+    fn from(value: p384::ecdsa::Signature) -> Self {
+        let (r_big_endian, s_big_endian) = value.split_bytes();
+
+        let mut signature = Signature::default();
+        for (le, be) in signature.r.iter_mut().zip(r_big_endian.iter().rev()) {
+            *le = *be;
+        }
+        for (le, be) in signature.s.iter_mut().zip(s_big_endian.iter().rev()) {
+            *le = *be;
+        }
+
+        signature
+    }
+}
+
 #[cfg(feature = "openssl")]
 impl TryFrom<&[u8]> for Signature {
     type Error = Error;
@@ -3,40 +3,53 @@
 /// ECDSA signatures.
 pub mod ecdsa;
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
 /// Certificate Authority (CA) certificates.
 pub mod ca;
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
 /// Built-in certificates for Milan and Genoa machines.
 pub mod builtin;
 
-#[cfg(feature = "openssl")]
+#[cfg(all(feature = "snp", feature = "openssl"))]
 mod cert;
-#[cfg(feature = "crypto_nossl")]
+#[cfg(all(feature = "snp", feature = "crypto_nossl"))]
 mod cert_nossl;
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
 mod chain;
 
-#[cfg(feature = "openssl")]
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+mod offline;
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
 pub use cert::Certificate;
-#[cfg(feature = "crypto_nossl")]
+#[cfg(all(feature = "snp", feature = "crypto_nossl"))]
 pub use cert_nossl::Certificate;
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
 pub use chain::Chain;
 
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+pub use offline::*;
+
+#[cfg(feature = "std")]
 use std::io::Result;
 
 #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 
-#[cfg(feature = "openssl")]
+#[cfg(feature = "crypto_nossl")]
+use std::io::ErrorKind;
+
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+use crate::error::VerificationError;
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
 #[allow(dead_code)]
 struct Body;
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
 /// An interface for types that may contain entities such as
 /// signatures that must be verified.
 pub trait Verifiable {
@@ -44,10 +57,10 @@ pub trait Verifiable {
     type Output;
 
     /// Self-verifies signatures.
-    fn verify(self) -> Result<Self::Output>;
+    fn verify(self) -> std::result::Result<Self::Output, VerificationError>;
 }
 
-#[cfg(feature = "openssl")]
+#[cfg(all(feature = "snp", feature = "openssl"))]
 /// An interface for types that can sign another type (i.e., a certificate).
 pub trait Signer<T> {
     /// The now-signed type.
@@ -57,10 +70,12 @@ pub trait Signer<T> {
     fn sign(&self, target: &mut T) -> Result<Self::Output>;
 }
 
+#[cfg(feature = "std")]
 pub(crate) trait FromLe: Sized {
     fn from_le(value: &[u8]) -> Result<Self>;
 }
 
+#[cfg(feature = "std")]
 pub(crate) trait AsLeBytes<T> {
     fn as_le_bytes(&self) -> T;
 }
@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Verifying evidence without network access.
+//!
+//! Air-gapped verifiers cannot fetch the VCEK of a chip, nor the ASK of a
+//! generation, from the AMD KDS: every certificate must ship with the
+//! evidence or be embedded in the verifier. [verify_offline] verifies an
+//! [EvidenceBundle] against [EmbeddedRoots] only, and fails with the list
+//! of every certificate neither provides, so that the missing artifacts can
+//! be gathered in a single round trip.
+//!
+//! The ARK is only ever taken from the embedded roots: an ARK in the bundle
+//! is ignored, as trusting it would let the bundle vouch for itself. The ASK
+//! (or ASVK, for VLEKs) is taken from the bundle if it holds one, and from
+//! the embedded roots otherwise. The VCEK or VLEK, whichever the report
+//! names as its signing key, must be in the bundle.
+//!
+//! # Example:
+//! ```ignore
+//! let bundle = EvidenceBundle::from((report, certificates));
+//!
+//! match verify_offline(&bundle, &EmbeddedRoots::builtin()?) {
+//!     Ok(chain) => println!("verified by {:?}", chain.vek),
+//!     Err(OfflineError::Missing(certs)) => eprintln!("bring {certs:?}"),
+//!     Err(e) => eprintln!("{e}"),
+//! }
+//! ```
+
+use super::*;
+
+use crate::{
+    error::OfflineError,
+    firmware::{
+        guest::AttestationReport,
+        host::{CertTableEntry, CertType},
+    },
+};
+
+/// An attestation report and the certificates shipped with it
+#[derive(Clone, Debug)]
+pub struct EvidenceBundle {
+    /// The attestation report.
+    pub report: AttestationReport,
+
+    /// The certificates shipped with the report, DER or PEM encoded.
+    pub certificates: Vec<CertTableEntry>,
+}
+
+impl From<(AttestationReport, Vec<CertTableEntry>)> for EvidenceBundle {
+    fn from((report, certificates): (AttestationReport, Vec<CertTableEntry>)) -> Self {
+        Self {
+            report,
+            certificates,
+        }
+    }
+}
+
+/// The certificates an offline verifier trusts
+#[derive(Clone, Debug, Default)]
+pub struct EmbeddedRoots {
+    arks: Vec<Certificate>,
+    asks: Vec<Certificate>,
+}
+
+impl EmbeddedRoots {
+    /// Trust no certificate yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust the ARKs and ASKs built into the crate.
+    pub fn builtin() -> std::result::Result<Self, VerificationError> {
+        let roots = Self::new()
+            .ark(builtin::milan::ark().map_err(VerificationError::parse)?)
+            .ask(builtin::milan::ask().map_err(VerificationError::parse)?)
+            .ark(builtin::genoa::ark().map_err(VerificationError::parse)?)
+            .ask(builtin::genoa::ask().map_err(VerificationError::parse)?);
+
+        Ok(roots)
+    }
+
+    /// Trust `ark` as a root.
+    pub fn ark(mut self, ark: Certificate) -> Self {
+        self.arks.push(ark);
+        self
+    }
+
+    /// Use `ask` when a bundle lacks an ASK. It is only trusted once a root
+    /// signs it.
+    pub fn ask(mut self, ask: Certificate) -> Self {
+        self.asks.push(ask);
+        self
+    }
+
+    /// The ARKs trusted.
+    pub fn arks(&self) -> &[Certificate] {
+        &self.arks
+    }
+
+    /// The ASKs used when a bundle lacks one.
+    pub fn asks(&self) -> &[Certificate] {
+        &self.asks
+    }
+}
+
+/// Verify the report of `bundle` with the certificates of `bundle` and
+/// `roots` only, and return the chain it verified against.
+///
+/// Fails with [OfflineError::Missing] listing every certificate needed but
+/// found in neither, ARK first and the VCEK or VLEK last.
+pub fn verify_offline(
+    bundle: &EvidenceBundle,
+    roots: &EmbeddedRoots,
+) -> std::result::Result<Chain, OfflineError> {
+    let vek_type = bundle
+        .report
+        .vek_type()
+        .map_err(|_| OfflineError::Unsigned)?;
+
+    let mut ask = None;
+    let mut vek = None;
+
+    for entry in &bundle.certificates {
+        let slot = match entry.cert_type {
+            CertType::ASK => &mut ask,
+            ref cert_type if *cert_type == vek_type => &mut vek,
+            _ => continue,
+        };
+
+        if slot.is_some() {
+            return Err(VerificationError::DuplicateCertificate(entry.cert_type.clone()).into());
+        }
+
+        *slot = Some(
+            Certificate::from_der(&entry.data)
+                .or_else(|_| Certificate::from_pem(&entry.data))
+                .map_err(VerificationError::parse)?,
+        );
+    }
+
+    let asks: Vec<&Certificate> = match &ask {
+        Some(ask) => vec![ask],
+        None => roots.asks.iter().collect(),
+    };
+
+    let mut missing = vec![];
+    if roots.arks.is_empty() {
+        missing.push(CertType::ARK);
+    }
+    if asks.is_empty() {
+        missing.push(CertType::ASK);
+    }
+    if vek.is_none() {
+        missing.push(vek_type);
+    }
+
+    let vek = match vek {
+        Some(vek) if missing.is_empty() => vek,
+        _ => return Err(OfflineError::Missing(missing)),
+    };
+
+    // An ASK of the bundle must sign the VEK, while an embedded ASK which
+    // does not merely is not the one needed.
+    let ask = match asks.into_iter().find(|ask| (*ask, &vek).verify().is_ok()) {
+        Some(ask) => ask,
+        None if ask.is_some() => {
+            return Err(VerificationError::CertificateSignature.into());
+        }
+        None => return Err(OfflineError::Missing(vec![CertType::ASK])),
+    };
+
+    let ark = roots
+        .arks
+        .iter()
+        .find(|ark| (*ark, *ark).verify().is_ok() && (*ark, ask).verify().is_ok())
+        .ok_or_else(|| OfflineError::Missing(vec![CertType::ARK]))?;
+
+    let chain = Chain {
+        ca: ca::Chain {
+            ark: ark.clone(),
+            ask: ask.clone(),
+        },
+        vek,
+    };
+
+    (&chain, &bundle.report).verify()?;
+
+    Ok(chain)
+}
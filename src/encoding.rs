@@ -0,0 +1,405 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Canonical text encodings of digests and other byte strings.
+//!
+//! Measurements, report data, chip IDs and key digests are written as
+//! lower-case hex without separators, so that they copy and paste cleanly
+//! between tools, allow-lists and the output of `sha384sum`. [HexBytes]
+//! wraps such a byte string to parse and print it, and typed wrappers such
+//! as [Measurement48] and [ChipId] do the same for the fields of the
+//! attestation report. The [serde_hex] module serializes byte arrays as hex
+//! strings in human-readable formats such as JSON. Binary formats such as
+//! bincode keep encoding them as raw bytes.
+//!
+//! # Example:
+//! ```ignore
+//! let expected: Measurement48 = "a1b2...".parse()?;
+//!
+//! assert_eq!(report.measurement, expected);
+//! println!("{}", report.chip_id);
+//! ```
+
+use alloc::string::String;
+use core::{
+    convert::{TryFrom, TryInto},
+    fmt::{self, Display, Write},
+    ops::Deref,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Errors which may be encountered when decoding a byte string from text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodingError {
+    /// The text does not encode the expected number of bytes.
+    InvalidLength {
+        /// The number of bytes expected.
+        expected: usize,
+
+        /// The number of bytes encoded, rounded down.
+        actual: usize,
+    },
+
+    /// The character at this index is not a hex digit.
+    InvalidCharacter(usize),
+
+    /// The text is not valid base64.
+    InvalidBase64,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EncodingError {}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength { expected, actual } => {
+                write!(f, "Expected {expected} bytes, found {actual}.")
+            }
+            Self::InvalidCharacter(index) => {
+                write!(f, "The character at index {index} is not a hex digit.")
+            }
+            Self::InvalidBase64 => write!(f, "The text is not valid base64."),
+        }
+    }
+}
+
+/// Write `bytes` as lower-case hex.
+pub(crate) fn write_hex(f: &mut impl Write, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))
+}
+
+/// Encode `bytes` as lower-case hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+
+    // Writing to a String cannot fail.
+    let _ = write_hex(&mut hex, bytes);
+
+    hex
+}
+
+/// Decode exactly `N` bytes from hex of either case.
+pub fn from_hex<const N: usize>(hex: &str) -> Result<[u8; N], EncodingError> {
+    if hex.len() != N * 2 {
+        return Err(EncodingError::InvalidLength {
+            expected: N,
+            actual: hex.len() / 2,
+        });
+    }
+
+    let digit = |index: usize| {
+        char::from(hex.as_bytes()[index])
+            .to_digit(16)
+            .ok_or(EncodingError::InvalidCharacter(index))
+    };
+
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (digit(i * 2)? << 4 | digit(i * 2 + 1)?) as u8;
+    }
+
+    Ok(bytes)
+}
+
+/// A byte string printed and parsed as canonical hex
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HexBytes<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> HexBytes<N> {
+    /// Encode the bytes as standard, padded base64.
+    #[cfg(feature = "std")]
+    pub fn to_base64(&self) -> String {
+        use base64::Engine;
+
+        base64::engine::general_purpose::STANDARD.encode(self.0)
+    }
+
+    /// Decode exactly `N` bytes from standard, padded base64.
+    #[cfg(feature = "std")]
+    pub fn from_base64(text: &str) -> Result<Self, EncodingError> {
+        use base64::Engine;
+        use core::convert::TryInto;
+
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .map_err(|_| EncodingError::InvalidBase64)?;
+
+        let actual = bytes.len();
+        bytes
+            .try_into()
+            .map(Self)
+            .map_err(|_| EncodingError::InvalidLength {
+                expected: N,
+                actual,
+            })
+    }
+}
+
+impl<const N: usize> Default for HexBytes<N> {
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for HexBytes<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> From<HexBytes<N>> for [u8; N] {
+    fn from(bytes: HexBytes<N>) -> Self {
+        bytes.0
+    }
+}
+
+impl<const N: usize> Deref for HexBytes<N> {
+    type Target = [u8; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for HexBytes<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> Display for HexBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+impl<const N: usize> fmt::Debug for HexBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HexBytes({self})")
+    }
+}
+
+impl<const N: usize> FromStr for HexBytes<N> {
+    type Err = EncodingError;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        from_hex(hex).map(Self)
+    }
+}
+
+impl<const N: usize> Serialize for HexBytes<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_hex::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for HexBytes<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde_hex::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Declare a typed byte string of a fixed size, which converts to and from
+/// its array and [HexBytes] and is printed, parsed and serialized as hex.
+/// Its bytes are only reached explicitly, through `as_bytes` or
+/// `into_inner`, rather than by dereferencing or comparing with arrays.
+///
+/// Each field of a report gets its own type, so that a digest cannot be
+/// passed or compared where another of the same size is expected.
+macro_rules! hex_newtype {
+    ($(#[$meta:meta])* $name:ident($size:literal)) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub [u8; $size]);
+
+        impl $name {
+            /// The size of the byte string, in bytes.
+            pub const SIZE: usize = $size;
+
+            /// The bytes of the byte string.
+            pub fn as_bytes(&self) -> &[u8; $size] {
+                &self.0
+            }
+
+            /// The array of the byte string.
+            pub fn into_inner(self) -> [u8; $size] {
+                self.0
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self([0; $size])
+            }
+        }
+
+        impl From<[u8; $size]> for $name {
+            fn from(bytes: [u8; $size]) -> Self {
+                Self(bytes)
+            }
+        }
+
+        impl From<$name> for [u8; $size] {
+            fn from(bytes: $name) -> Self {
+                bytes.0
+            }
+        }
+
+        impl TryFrom<&[u8]> for $name {
+            type Error = EncodingError;
+
+            fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+                bytes
+                    .try_into()
+                    .map(Self)
+                    .map_err(|_| EncodingError::InvalidLength {
+                        expected: $size,
+                        actual: bytes.len(),
+                    })
+            }
+        }
+
+        impl From<HexBytes<$size>> for $name {
+            fn from(bytes: HexBytes<$size>) -> Self {
+                Self(bytes.0)
+            }
+        }
+
+        impl From<$name> for HexBytes<$size> {
+            fn from(bytes: $name) -> Self {
+                Self(bytes.0)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write_hex(f, &self.0)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({self})", stringify!($name))
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = EncodingError;
+
+            fn from_str(hex: &str) -> Result<Self, Self::Err> {
+                from_hex(hex).map(Self)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serde_hex::serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                serde_hex::deserialize(deserializer).map(Self)
+            }
+        }
+    };
+}
+
+hex_newtype! {
+    /// The SHA-384 launch measurement of a guest
+    Measurement48(48)
+}
+
+hex_newtype! {
+    /// The 512 bits of data a guest binds into its attestation report
+    ReportData(64)
+}
+
+hex_newtype! {
+    /// The identifier unique to an AMD processor, or zeroes if masked
+    ChipId(64)
+}
+
+hex_newtype! {
+    /// The SHA-384 digest of the ID key which signed a guest's ID block
+    IdKeyDigest(48)
+}
+
+hex_newtype! {
+    /// The SHA-384 digest of the author key which certified a guest's ID key
+    AuthorKeyDigest(48)
+}
+
+/// Serialize byte arrays as hex strings in human-readable formats, and as
+/// tuples of bytes otherwise, for use with `#[serde(with = "...")]`.
+///
+/// Human-readable formats also accept the arrays of numbers the arrays were
+/// serialized as before, so that existing documents remain readable.
+pub mod serde_hex {
+    use super::{from_hex, to_hex};
+
+    use core::{fmt, marker::PhantomData};
+
+    use serde::{
+        de::{self, SeqAccess, Visitor},
+        Deserializer, Serializer,
+    };
+    use serde_big_array::BigArray;
+
+    /// Serialize `bytes`.
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&to_hex(bytes))
+        } else {
+            BigArray::serialize(bytes, serializer)
+        }
+    }
+
+    /// Deserialize an array of `N` bytes.
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        struct HexVisitor<const N: usize>(PhantomData<[u8; N]>);
+
+        impl<'de, const N: usize> Visitor<'de> for HexVisitor<N> {
+            type Value = [u8; N];
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{N} bytes as a hex string or an array")
+            }
+
+            fn visit_str<E: de::Error>(self, hex: &str) -> Result<Self::Value, E> {
+                from_hex(hex).map_err(E::custom)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = [0u8; N];
+                for (i, byte) in bytes.iter_mut().enumerate() {
+                    *byte = seq
+                        .next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                }
+
+                Ok(bytes)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(HexVisitor(PhantomData))
+        } else {
+            BigArray::deserialize(deserializer)
+        }
+    }
+}
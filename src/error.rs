@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use bincode;
+
 use std::{
     array::TryFromSliceError,
     convert::From,
@@ -9,6 +10,8 @@ use std::{
     io,
 };
 
+pub use crate::encoding::EncodingError;
+
 use std::os::raw::c_int;
 
 #[cfg(feature = "openssl")]
@@ -58,6 +61,18 @@ pub enum VmmError {
 /// Use the default implementations for std::error::Error here.
 impl error::Error for VmmError {}
 
+impl VmmError {
+    /// Classify the error, so that callers can decide whether to retry the
+    /// request.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            VmmError::InvalidCertificatePageLength => ErrorCategory::Configuration,
+            VmmError::RateLimitRetryRequest => ErrorCategory::Retryable,
+            VmmError::Unknown => ErrorCategory::Unknown,
+        }
+    }
+}
+
 impl From<u32> for VmmError {
     /// Takes a raw u32 and translates it into the correlated [VmmError](self::VmmError)
     /// type.
@@ -140,151 +155,1889 @@ impl From<RawFwError> for (VmmError, Indeterminate<Error>) {
     }
 }
 
-impl Display for RawFwError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "RawFwError: {}", self.0)
+impl Display for RawFwError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RawFwError: {}", self.0)
+    }
+}
+
+/// There are a number of error conditions that can occur between this
+/// layer all the way down to the SEV platform. Most of these cases have
+/// been enumerated; however, there is a possibility that some error
+/// conditions are not encapsulated here.
+#[derive(Debug)]
+pub enum Indeterminate<T: Debug> {
+    /// The error condition is known.
+    Known(T),
+
+    /// The error condition is unknown.
+    Unknown,
+}
+
+#[derive(Debug)]
+/// Wrapper Error for Firmware or User API Errors
+pub enum UserApiError {
+    /// Firmware related errors.
+    FirmwareError(Error),
+
+    /// User API related errors.
+    ApiError(CertError),
+
+    /// Errors returned by the VMM via ioctl().
+    VmmError(VmmError),
+
+    /// Uuid parsing errors.
+    UuidError(uuid::Error),
+
+    /// VLEK Hashstick errors.
+    HashstickError(HashstickError),
+
+    /// SNP configuration errors.
+    ConfigError(ConfigError),
+
+    /// Invalid VMPL.
+    VmplError,
+
+    /// Unknown error
+    Unknown,
+}
+
+impl error::Error for UserApiError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::ApiError(uapi_error) => Some(uapi_error),
+            Self::FirmwareError(firmware_error) => Some(firmware_error),
+            Self::UuidError(uuid_error) => Some(uuid_error),
+            Self::VmmError(vmm_error) => Some(vmm_error),
+            Self::HashstickError(hashstick_error) => Some(hashstick_error),
+            Self::ConfigError(config_error) => Some(config_error),
+            Self::VmplError => None,
+            Self::Unknown => None,
+        }
+    }
+}
+
+impl std::fmt::Display for UserApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let err_msg: String = match self {
+            Self::FirmwareError(error) => format!("Firmware Error Encountered: {error}"),
+            Self::ApiError(error) => format!("Certificate Error Encountered: {error}"),
+            Self::UuidError(error) => format!("UUID Error Encountered: {error}"),
+            Self::VmmError(error) => format!("VMM Error Encountered: {error}"),
+            Self::HashstickError(error) => format!("VLEK Hashstick Error Encountered: {error}"),
+            Self::ConfigError(error) => format!("SNP Config Error Encountered: {error}"),
+            Self::VmplError => "Invalid VM Permission Level (VMPL)".to_string(),
+            Self::Unknown => "Unknown Error Encountered!".to_string(),
+        };
+        write!(f, "{err_msg}")
+    }
+}
+
+impl UserApiError {
+    /// Classify the error, so that callers can decide whether to retry the
+    /// request.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::FirmwareError(error) => error.category(),
+            Self::VmmError(error) => error.category(),
+            Self::Unknown => ErrorCategory::Unknown,
+            Self::ApiError(_)
+            | Self::UuidError(_)
+            | Self::HashstickError(_)
+            | Self::ConfigError(_)
+            | Self::VmplError => ErrorCategory::Configuration,
+        }
+    }
+
+    /// Returns true if reissuing the request may succeed.
+    pub fn is_retryable(&self) -> bool {
+        self.category() == ErrorCategory::Retryable
+    }
+}
+
+impl std::convert::From<HashstickError> for UserApiError {
+    fn from(value: HashstickError) -> Self {
+        Self::HashstickError(value)
+    }
+}
+
+impl std::convert::From<ConfigError> for UserApiError {
+    fn from(value: ConfigError) -> Self {
+        Self::ConfigError(value)
+    }
+}
+
+impl std::convert::From<VmmError> for UserApiError {
+    fn from(value: VmmError) -> Self {
+        Self::VmmError(value)
+    }
+}
+
+impl std::convert::From<uuid::Error> for UserApiError {
+    fn from(uuid_error: uuid::Error) -> Self {
+        Self::UuidError(uuid_error)
+    }
+}
+
+impl std::convert::From<Error> for UserApiError {
+    fn from(firmware_error: Error) -> Self {
+        Self::FirmwareError(firmware_error)
+    }
+}
+
+impl std::convert::From<Indeterminate<Error>> for UserApiError {
+    fn from(firmware_error: Indeterminate<Error>) -> Self {
+        match firmware_error {
+            Indeterminate::Known(error) => Self::FirmwareError(error),
+            Indeterminate::Unknown => Self::Unknown,
+        }
+    }
+}
+
+impl std::convert::From<std::io::Error> for UserApiError {
+    fn from(io_error: std::io::Error) -> Self {
+        Self::FirmwareError(Error::IoError(io_error))
+    }
+}
+
+impl std::convert::From<CertError> for UserApiError {
+    fn from(cert_error: CertError) -> Self {
+        Self::ApiError(cert_error)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// Errors which may be encountered when handling Version Loaded Endorsement Keys
+/// (VLEK) Hashsticks.
+pub enum HashstickError {
+    /// Hashstick length does not match what was specified in the buffer.
+    InvalidLength,
+
+    /// No hashstick was provided
+    EmptyHashstickBuffer,
+
+    /// The wrapped hashstick format version is not supported.
+    UnsupportedVersion(u8),
+
+    /// Unknown Error.
+    UnknownError,
+}
+
+impl std::error::Error for HashstickError {}
+
+impl std::fmt::Display for HashstickError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashstickError::InvalidLength => {
+                write!(
+                    f,
+                    "VLEK hashstick is an invalid length. Should be 432 bytes in size."
+                )
+            }
+            HashstickError::EmptyHashstickBuffer => write!(f, "Hashstick buffer is empty."),
+            HashstickError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported wrapped VLEK hashstick version {version}.")
+            }
+            HashstickError::UnknownError => {
+                write!(
+                    f,
+                    "Unknown error encountered when handling the VLEK Hashstick."
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// Errors which may be encountered when validating an SNP configuration
+/// before it is handed to SNP_SET_CONFIG.
+pub enum ConfigError {
+    /// Reserved bits of the mask ID were set.
+    ReservedMaskBits(u32),
+
+    /// The requested reported TCB is newer than the platform's installed TCB.
+    ReportedTcbTooNew,
+}
+
+impl std::error::Error for ConfigError {}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::ReservedMaskBits(bits) => {
+                write!(f, "Reserved mask ID bits must be zero (found {bits:#x}).")
+            }
+            ConfigError::ReportedTcbTooNew => write!(
+                f,
+                "Reported TCB may not exceed the platform's installed TCB."
+            ),
+        }
+    }
+}
+
+/// Errors which may be encountered while updating the reported TCB.
+#[derive(Debug)]
+pub enum TcbUpdateError {
+    /// The step is not allowed in the current state of the update.
+    OutOfOrder,
+
+    /// The platform did not report the requested TCB after it was set. The
+    /// previous configuration was restored.
+    NotApplied,
+
+    /// Guests failed to attest with the new TCB. The previous configuration
+    /// was restored.
+    VerificationFailed,
+
+    /// A firmware command failed, or the configuration was rejected.
+    FirmwareError(UserApiError),
+}
+
+impl std::error::Error for TcbUpdateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::FirmwareError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TcbUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfOrder => write!(f, "TCB update step issued out of order."),
+            Self::NotApplied => write!(
+                f,
+                "The platform did not apply the reported TCB; it was rolled back."
+            ),
+            Self::VerificationFailed => write!(
+                f,
+                "Guests failed to attest with the reported TCB; it was rolled back."
+            ),
+            Self::FirmwareError(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::convert::From<UserApiError> for TcbUpdateError {
+    fn from(value: UserApiError) -> Self {
+        Self::FirmwareError(value)
+    }
+}
+
+impl std::convert::From<ConfigError> for TcbUpdateError {
+    fn from(value: ConfigError) -> Self {
+        Self::FirmwareError(value.into())
+    }
+}
+
+impl std::convert::From<Indeterminate<Error>> for TcbUpdateError {
+    fn from(value: Indeterminate<Error>) -> Self {
+        Self::FirmwareError(value.into())
+    }
+}
+
+/// Errors which may be encountered while issuing a destructive platform
+/// lifecycle command.
+#[derive(Debug)]
+pub enum LifecycleError {
+    /// The command was refused before reaching the firmware, because the
+    /// platform is supervising this many guests.
+    GuestsActive(u32),
+
+    /// A firmware command failed.
+    FirmwareError(Indeterminate<Error>),
+}
+
+impl std::error::Error for LifecycleError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::FirmwareError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GuestsActive(guests) => write!(
+                f,
+                "Command refused while the platform supervises {guests} guests."
+            ),
+            Self::FirmwareError(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::convert::From<Indeterminate<Error>> for LifecycleError {
+    fn from(value: Indeterminate<Error>) -> Self {
+        Self::FirmwareError(value)
+    }
+}
+
+/// Errors which may be encountered when checking a guest policy against
+/// the platform's SEV-ES requirements.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EsPolicyError {
+    /// The platform does not support SEV-ES.
+    Unsupported,
+
+    /// No ASIDs are reserved for SEV-ES guests.
+    NoAsids,
+
+    /// The policy does not set the ENCRYPTED_STATE flag.
+    EncryptedStateRequired,
+
+    /// The policy requires a newer firmware version than the platform runs.
+    FirmwareTooOld(crate::Version),
+}
+
+impl std::error::Error for EsPolicyError {}
+
+impl std::fmt::Display for EsPolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "The platform does not support SEV-ES."),
+            Self::NoAsids => write!(f, "No ASIDs are available to SEV-ES guests."),
+            Self::EncryptedStateRequired => {
+                write!(f, "SEV-ES guests require the ENCRYPTED_STATE policy flag.")
+            }
+            Self::FirmwareTooOld(minfw) => write!(
+                f,
+                "The policy requires firmware {minfw}, which is newer than the platform's."
+            ),
+        }
+    }
+}
+
+/// Errors which may be encountered when parsing the blobs of an SEV launch
+/// session provisioned out-of-band.
+#[derive(Debug)]
+pub enum SessionBlobError {
+    /// The named blob is not valid base64.
+    InvalidBase64(&'static str),
+
+    /// The named blob is not of the expected size.
+    InvalidLength {
+        /// The blob.
+        blob: &'static str,
+
+        /// The size (in bytes) of the blob.
+        expected: usize,
+
+        /// The size (in bytes) of the data provided.
+        actual: usize,
+    },
+
+    /// The GODH blob is not an SEV certificate.
+    InvalidCertificate(io::Error),
+
+    /// The GODH certificate is not a Diffie-Hellman certificate.
+    NotDiffieHellman,
+
+    /// The named MAC of the session blob is zero, so that the blob was
+    /// truncated or never filled in.
+    ZeroMac(&'static str),
+
+    /// A blob could not be read.
+    Io(io::Error),
+}
+
+impl std::error::Error for SessionBlobError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidCertificate(e) | Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SessionBlobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidBase64(blob) => write!(f, "The {blob} blob is not valid base64."),
+            Self::InvalidLength {
+                blob,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The {blob} blob is {actual} bytes, expected {expected} bytes."
+            ),
+            Self::InvalidCertificate(e) => write!(f, "The GODH blob is not a certificate: {e}"),
+            Self::NotDiffieHellman => {
+                write!(
+                    f,
+                    "The GODH certificate is not a Diffie-Hellman certificate."
+                )
+            }
+            Self::ZeroMac(mac) => write!(f, "The {mac} of the session blob is zero."),
+            Self::Io(e) => write!(f, "Failed to read the blob: {e}"),
+        }
+    }
+}
+
+impl std::convert::From<io::Error> for SessionBlobError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Errors which may be encountered when checking a guest policy before
+/// sending or receiving the guest.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MigrationPolicyError {
+    /// The policy to send the guest with is not the policy the guest runs
+    /// with.
+    PolicyMismatch,
+
+    /// The guest policy forbids sending the guest (NO_SEND).
+    SendForbidden,
+
+    /// The guest is an SEV-ES guest, but the receiver was not initialized
+    /// for SEV-ES, or vice versa.
+    EncryptedStateMismatch,
+}
+
+impl std::error::Error for MigrationPolicyError {}
+
+impl std::fmt::Display for MigrationPolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PolicyMismatch => {
+                write!(f, "The send policy differs from the guest's policy.")
+            }
+            Self::SendForbidden => write!(f, "The guest policy forbids sending the guest."),
+            Self::EncryptedStateMismatch => write!(
+                f,
+                "The guest's SEV-ES policy does not match the receiving context."
+            ),
+        }
+    }
+}
+
+/// Errors which may be encountered when building an SEV guest policy.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PolicyError {
+    /// The policy restricts where the guest may be sent (DOMAIN or SEV),
+    /// but also forbids sending it (NO_SEND).
+    SendRestrictedButForbidden,
+
+    /// The policy does not meet the platform's SEV-ES requirements.
+    EncryptedState(EsPolicyError),
+}
+
+impl std::error::Error for PolicyError {}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SendRestrictedButForbidden => write!(
+                f,
+                "The policy restricts where the guest may be sent, but forbids sending it."
+            ),
+            Self::EncryptedState(e) => write!(f, "SEV-ES policy error: {e}"),
+        }
+    }
+}
+
+impl From<EsPolicyError> for PolicyError {
+    fn from(value: EsPolicyError) -> Self {
+        Self::EncryptedState(value)
+    }
+}
+
+/// Errors which may be encountered when validating an SNP launch update.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PageUpdateError {
+    /// The update covers no memory.
+    Empty,
+
+    /// The userspace address of the update is not page-aligned.
+    Misaligned {
+        /// The userspace address.
+        addr: u64,
+    },
+
+    /// The length of the update is not a whole number of pages.
+    PartialPage {
+        /// The length in bytes.
+        len: usize,
+    },
+
+    /// The page type only permits updating a single page at a time
+    /// (VMSA, SECRETS and CPUID pages).
+    SinglePage {
+        /// The number of pages in the update.
+        pages: usize,
+    },
+
+    /// The page type cannot be populated this way (e.g. VMSA pages of
+    /// guest_memfd launches, which KVM creates itself).
+    UnsupportedPageType,
+
+    /// The page type requires source data, but none was given.
+    MissingSource,
+
+    /// The source data is not the length of the update.
+    SourceSize {
+        /// The length of the source data in bytes.
+        len: usize,
+
+        /// The length of the update in bytes.
+        expected: usize,
+    },
+}
+
+impl std::error::Error for PageUpdateError {}
+
+impl std::fmt::Display for PageUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The launch update covers no memory."),
+            Self::Misaligned { addr } => {
+                write!(
+                    f,
+                    "The launch update address {addr:#x} is not page-aligned."
+                )
+            }
+            Self::PartialPage { len } => write!(
+                f,
+                "The launch update length {len:#x} is not a whole number of pages."
+            ),
+            Self::SinglePage { pages } => write!(
+                f,
+                "The page type permits a single page per launch update, but {pages} were given."
+            ),
+            Self::UnsupportedPageType => {
+                write!(
+                    f,
+                    "The page type cannot be populated by this launch update."
+                )
+            }
+            Self::MissingSource => write!(f, "The launch update requires source data."),
+            Self::SourceSize { len, expected } => write!(
+                f,
+                "The launch update source is {len:#x} bytes, expected {expected:#x}."
+            ),
+        }
+    }
+}
+
+/// Errors which may be encountered when building or parsing an SNP CPUID
+/// page.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CpuidError {
+    /// The page holds more functions than the firmware accepts.
+    TooManyFunctions {
+        /// The number of functions.
+        count: usize,
+    },
+
+    /// The buffer is not the size of a page.
+    PageSize {
+        /// The size of the buffer in bytes.
+        len: usize,
+    },
+}
+
+impl std::error::Error for CpuidError {}
+
+impl std::fmt::Display for CpuidError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyFunctions { count } => write!(
+                f,
+                "The CPUID page holds {count} functions, more than the firmware accepts."
+            ),
+            Self::PageSize { len } => {
+                write!(f, "The CPUID page buffer is {len:#x} bytes, not a page.")
+            }
+        }
+    }
+}
+
+/// Errors which may be encountered when associating an SNP guest with a
+/// migration agent, or checking the association in an attestation report.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MigrationAgentError {
+    /// The guest policy forbids association with a migration agent
+    /// (MIGRATE_MA is 0).
+    AssociationForbidden,
+
+    /// The guest is associated with a migration agent, but none was
+    /// expected.
+    UnexpectedAgent,
+
+    /// The guest is not associated with a migration agent, but one was
+    /// expected.
+    MissingAgent,
+
+    /// The guest is associated with a different migration agent than the
+    /// one expected.
+    AgentMismatch,
+}
+
+impl std::error::Error for MigrationAgentError {}
+
+impl std::fmt::Display for MigrationAgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AssociationForbidden => write!(
+                f,
+                "The guest policy forbids association with a migration agent."
+            ),
+            Self::UnexpectedAgent => write!(
+                f,
+                "The guest is associated with a migration agent, but none was expected."
+            ),
+            Self::MissingAgent => write!(
+                f,
+                "The guest is not associated with the expected migration agent."
+            ),
+            Self::AgentMismatch => write!(
+                f,
+                "The guest is associated with a different migration agent."
+            ),
+        }
+    }
+}
+
+/// Errors which may be encountered when building SNP_LAUNCH_FINISH
+/// parameters.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FinishError {
+    /// The ID block is not 0x60 bytes.
+    IdBlockSize {
+        /// The size of the ID block in bytes.
+        len: usize,
+    },
+
+    /// The ID authentication structure is not 0x1000 bytes.
+    IdAuthSize {
+        /// The size of the ID authentication structure in bytes.
+        len: usize,
+    },
+
+    /// The author key was enabled without an ID block.
+    AuthorKeyWithoutIdBlock,
+
+    /// The author key was enabled, but the ID authentication structure
+    /// holds no author key.
+    AuthorKeyMissing,
+}
+
+impl std::error::Error for FinishError {}
+
+impl std::fmt::Display for FinishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IdBlockSize { len } => {
+                write!(f, "The ID block is {len:#x} bytes, expected 0x60.")
+            }
+            Self::IdAuthSize { len } => write!(
+                f,
+                "The ID authentication structure is {len:#x} bytes, expected 0x1000."
+            ),
+            Self::AuthorKeyWithoutIdBlock => {
+                write!(f, "The author key was enabled without an ID block.")
+            }
+            Self::AuthorKeyMissing => write!(
+                f,
+                "The author key was enabled, but the ID authentication structure holds none."
+            ),
+        }
+    }
+}
+
+/// Errors which may be encountered when checking an attestation report
+/// against an ID block.
+#[derive(Debug)]
+pub enum IdVerificationError {
+    /// The ID block is not 0x60 bytes.
+    IdBlockSize {
+        /// The size of the ID block in bytes.
+        len: usize,
+    },
+
+    /// The ID authentication structure is not 0x1000 bytes.
+    IdAuthSize {
+        /// The size of the ID authentication structure in bytes.
+        len: usize,
+    },
+
+    /// A key of the ID authentication structure is not an ECDSA P-384 key.
+    UnsupportedAlgorithm(u32),
+
+    /// The ID block signature does not verify with the ID key.
+    IdBlockSignature,
+
+    /// The ID key signature does not verify with the author key.
+    IdKeySignature,
+
+    /// The ID block is signed with another ID key than the tenant's.
+    IdKeyMismatch,
+
+    /// The ID key is signed with another author key than the tenant's.
+    AuthorKeyMismatch,
+
+    /// The reported ID key digest is not the digest of the tenant's ID key.
+    IdKeyDigest,
+
+    /// The report does not hold an author key digest.
+    AuthorKeyDisabled,
+
+    /// The reported author key digest is not the digest of the tenant's
+    /// author key.
+    AuthorKeyDigest,
+
+    /// The reported guest SVN is below the minimum.
+    GuestSvn {
+        /// The guest SVN of the report.
+        reported: u32,
+
+        /// The minimum guest SVN.
+        minimum: u32,
+    },
+
+    /// A field of the report differs from the ID block.
+    Mismatch(&'static str),
+
+    /// The keys or signatures of the ID authentication structure could not
+    /// be decoded.
+    IdBlock(IdBlockError),
+}
+
+impl std::error::Error for IdVerificationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IdBlock(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for IdVerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IdBlockSize { len } => {
+                write!(f, "The ID block is {len:#x} bytes, expected 0x60.")
+            }
+            Self::IdAuthSize { len } => write!(
+                f,
+                "The ID authentication structure is {len:#x} bytes, expected 0x1000."
+            ),
+            Self::UnsupportedAlgorithm(algo) => {
+                write!(f, "Unsupported ID key algorithm {algo}, expected 1.")
+            }
+            Self::IdBlockSignature => {
+                write!(f, "The ID block signature does not verify with the ID key.")
+            }
+            Self::IdKeySignature => {
+                write!(
+                    f,
+                    "The ID key signature does not verify with the author key."
+                )
+            }
+            Self::IdKeyMismatch => write!(f, "The ID block is signed with another ID key."),
+            Self::AuthorKeyMismatch => {
+                write!(f, "The ID key is signed with another author key.")
+            }
+            Self::IdKeyDigest => write!(f, "The report holds the digest of another ID key."),
+            Self::AuthorKeyDisabled => write!(f, "The report holds no author key digest."),
+            Self::AuthorKeyDigest => {
+                write!(f, "The report holds the digest of another author key.")
+            }
+            Self::GuestSvn { reported, minimum } => write!(
+                f,
+                "The guest SVN {reported} of the report is below the minimum {minimum}."
+            ),
+            Self::Mismatch(field) => {
+                write!(f, "The {field} of the report differs from the ID block.")
+            }
+            Self::IdBlock(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::convert::From<IdBlockError> for IdVerificationError {
+    fn from(value: IdBlockError) -> Self {
+        Self::IdBlock(value)
+    }
+}
+
+/// A guest policy requirement the SNP platform does not meet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnpPolicyConflict {
+    /// The policy requires ciphertext hiding (CIPHERTEXT_HIDING), but it is
+    /// not enabled on the platform.
+    CiphertextHidingDisabled,
+
+    /// The policy requires RAPL to be disabled (RAPL_DIS), but it is
+    /// enabled on the platform.
+    RaplEnabled,
+
+    /// The policy forbids SMT (SMT is 0), but SMT is active on the host.
+    SmtActive,
+
+    /// The policy requires a newer ABI version than the platform firmware
+    /// implements.
+    FirmwareTooOld(crate::Version),
+}
+
+impl std::fmt::Display for SnpPolicyConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CiphertextHidingDisabled => write!(
+                f,
+                "The policy requires ciphertext hiding, which is not enabled."
+            ),
+            Self::RaplEnabled => write!(
+                f,
+                "The policy requires RAPL to be disabled, but it is enabled."
+            ),
+            Self::SmtActive => write!(f, "The policy forbids SMT, but SMT is active."),
+            Self::FirmwareTooOld(abi) => write!(
+                f,
+                "The policy requires ABI {abi}, which is newer than the platform's."
+            ),
+        }
+    }
+}
+
+/// The SNP platform cannot launch a guest with the requested policy.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SnpPolicyError {
+    /// Each requirement of the policy the platform does not meet.
+    pub conflicts: Vec<SnpPolicyConflict>,
+}
+
+impl std::error::Error for SnpPolicyError {}
+
+impl std::fmt::Display for SnpPolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The guest policy is incompatible with the platform:")?;
+
+        for conflict in self.conflicts.iter() {
+            write!(f, " {conflict}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors which may be encountered when parsing or applying an IGVM file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum IgvmError {
+    /// The file ends before a structure it holds.
+    Truncated {
+        /// The offset of the structure in the file.
+        offset: usize,
+
+        /// The size of the structure in bytes.
+        len: usize,
+    },
+
+    /// The file does not start with the IGVM magic value.
+    Magic(u32),
+
+    /// The file has an unsupported format version.
+    Version(u32),
+
+    /// A variable header is too small for its type.
+    HeaderLength {
+        /// The type of the header.
+        typ: u32,
+
+        /// The length of the header in bytes.
+        len: u32,
+    },
+
+    /// The file does not support the SEV-SNP platform.
+    NoSnpPlatform,
+
+    /// A parameter insert refers to an undeclared parameter area.
+    UnknownParameterArea(u32),
+
+    /// Guest memory is not mapped where the file places data.
+    Unmapped {
+        /// The guest physical address of the data.
+        gpa: u64,
+
+        /// The size of the data in bytes.
+        len: usize,
+    },
+}
+
+impl std::error::Error for IgvmError {}
+
+impl std::fmt::Display for IgvmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated { offset, len } => write!(
+                f,
+                "The IGVM file ends before the {len} bytes at offset {offset:#x}."
+            ),
+            Self::Magic(magic) => write!(f, "The IGVM magic value is {magic:#x}."),
+            Self::Version(version) => write!(f, "IGVM format version {version} is unsupported."),
+            Self::HeaderLength { typ, len } => write!(
+                f,
+                "The IGVM header of type {typ:#x} is too small ({len} bytes)."
+            ),
+            Self::NoSnpPlatform => write!(f, "The IGVM file does not support SEV-SNP."),
+            Self::UnknownParameterArea(index) => {
+                write!(f, "IGVM parameter area {index} is not declared.")
+            }
+            Self::Unmapped { gpa, len } => write!(
+                f,
+                "Guest memory is not mapped for the {len} bytes at {gpa:#x}."
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// Errors which may be encountered when validating SNP_INIT_EX parameters.
+pub enum InitExError {
+    /// The platform is not capable of ciphertext hiding.
+    CiphertextHidingUnsupported,
+
+    /// Ciphertext hiding requires at least one ASID for SNP guests.
+    InvalidMaxSnpAsid,
+
+    /// More ranges were given than fit in the range list page.
+    TooManyRanges(usize),
+
+    /// The range at this base address is not page aligned or is empty.
+    InvalidRange(u64),
+
+    /// The range at this base address overlaps the one before it.
+    OverlappingRanges(u64),
+
+    /// The range at this base address extends past the end of the physical
+    /// address space.
+    RangeOutOfBounds(u64),
+}
+
+impl std::error::Error for InitExError {}
+
+impl std::fmt::Display for InitExError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitExError::CiphertextHidingUnsupported => {
+                write!(f, "The platform is not capable of ciphertext hiding.")
+            }
+            InitExError::InvalidMaxSnpAsid => write!(
+                f,
+                "Ciphertext hiding requires at least one ASID for SNP guests."
+            ),
+            InitExError::TooManyRanges(count) => {
+                write!(
+                    f,
+                    "{count} ranges do not fit in the SNP_INIT_EX range list."
+                )
+            }
+            InitExError::InvalidRange(base) => {
+                write!(f, "Range at {base:#x} is empty or not page aligned.")
+            }
+            InitExError::OverlappingRanges(base) => {
+                write!(f, "Range at {base:#x} overlaps the preceding range.")
+            }
+            InitExError::RangeOutOfBounds(base) => {
+                write!(f, "Range at {base:#x} extends past the address space.")
+            }
+        }
+    }
+}
+
+/// Errors which may be encountered when converting a Protocol Buffers
+/// message into the type it mirrors.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProtoError {
+    /// A message field which must be present is missing.
+    MissingField(&'static str),
+
+    /// A bytes field does not have the length of the array it mirrors.
+    InvalidLength {
+        /// The name of the field.
+        field: &'static str,
+
+        /// The length of the array in bytes.
+        expected: usize,
+
+        /// The length of the field in bytes.
+        actual: usize,
+    },
+
+    /// An integer field does not fit in the integer type it mirrors.
+    OutOfRange(&'static str),
+
+    /// A certificate table entry has a malformed GUID.
+    InvalidGuid(String),
+}
+
+impl std::error::Error for ProtoError {}
+
+impl std::fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "The message has no {field} field."),
+            Self::InvalidLength {
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The {field} field is {actual} bytes long instead of {expected}."
+            ),
+            Self::OutOfRange(field) => write!(f, "The {field} field is out of range."),
+            Self::InvalidGuid(guid) => write!(f, "{guid} is not a valid GUID."),
+        }
+    }
+}
+
+/// Errors which may be encountered when encoding or decoding CBOR evidence
+/// with the [cbor](crate::cbor) module.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CborError {
+    /// The bytes are not a single well-formed CBOR item.
+    Cbor(String),
+
+    /// A field is missing, repeated or of the wrong type.
+    InvalidField(&'static str),
+
+    /// A bytes field does not have the length of the array it holds.
+    InvalidLength {
+        /// The name of the field.
+        field: &'static str,
+
+        /// The length of the array in bytes.
+        expected: usize,
+
+        /// The length of the field in bytes.
+        actual: usize,
+    },
+
+    /// A certificate table entry has a malformed GUID.
+    InvalidGuid(String),
+
+    /// The evidence is wrapped with another media type.
+    UnexpectedType(String),
+
+    /// The `COSE_Sign1` message is malformed.
+    Cose(String),
+
+    /// The evidence could not be signed, or its signature was rejected.
+    Signature(String),
+}
+
+impl std::error::Error for CborError {}
+
+impl std::fmt::Display for CborError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cbor(e) => write!(f, "Malformed CBOR: {e}"),
+            Self::InvalidField(field) => write!(f, "The {field} field is missing or malformed."),
+            Self::InvalidLength {
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The {field} field is {actual} bytes long instead of {expected}."
+            ),
+            Self::InvalidGuid(guid) => write!(f, "{guid} is not a valid GUID."),
+            Self::UnexpectedType(media_type) => {
+                write!(f, "Expected SEV-SNP evidence, found {media_type}.")
+            }
+            Self::Cose(e) => write!(f, "Malformed COSE_Sign1 message: {e}"),
+            Self::Signature(e) => write!(f, "The COSE_Sign1 signature failed: {e}"),
+        }
+    }
+}
+
+/// Errors which may be encountered when verifying that an attestation
+/// report binds a vTPM's attestation key or quote.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VtpmError {
+    /// The report data does not bind the TPM object to the nonce.
+    BindingMismatch,
+
+    /// The quote was requested for another nonce.
+    NonceMismatch,
+
+    /// The `TPMS_ATTEST` structure ends before one of its fields.
+    Truncated,
+
+    /// The `TPMS_ATTEST` structure does not start with the magic value of
+    /// structures generated by a TPM.
+    Magic(u32),
+
+    /// The `TPMS_ATTEST` structure is of another type than a quote.
+    NotAQuote(u16),
+}
+
+impl std::error::Error for VtpmError {}
+
+impl std::fmt::Display for VtpmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BindingMismatch => write!(
+                f,
+                "The report data does not bind the TPM object to the nonce."
+            ),
+            Self::NonceMismatch => write!(f, "The TPM quote was requested for another nonce."),
+            Self::Truncated => write!(f, "The TPMS_ATTEST structure is truncated."),
+            Self::Magic(magic) => write!(f, "The TPMS_ATTEST magic value is {magic:#x}."),
+            Self::NotAQuote(typ) => write!(f, "The TPMS_ATTEST type {typ:#x} is not a quote."),
+        }
+    }
+}
+
+/// Errors which may be encountered when exchanging guest messages without
+/// the kernel driver.
+#[derive(Debug)]
+pub enum GuestMessageError {
+    /// The transport failed to exchange the messages.
+    Transport(std::io::Error),
+
+    /// A previous request failed after it was sent, so that the firmware
+    /// may expect another sequence number.
+    Disabled,
+
+    /// The payload is larger than a message holds.
+    PayloadSize {
+        /// The size of the payload in bytes.
+        len: usize,
+    },
+
+    /// The sequence numbers of the VMPCK are used up.
+    SequenceExhausted,
+
+    /// A field of the message header is invalid.
+    InvalidHeader(&'static str),
+
+    /// The response does not carry the sequence number following the
+    /// request's.
+    UnexpectedSequence {
+        /// The expected sequence number.
+        expected: u64,
+
+        /// The sequence number of the response.
+        actual: u64,
+    },
+
+    /// The response is not of the type answering the request.
+    UnexpectedType {
+        /// The expected message type.
+        expected: u8,
+
+        /// The message type of the response.
+        actual: u8,
+    },
+
+    /// The response is not of the version of the request.
+    UnexpectedVersion {
+        /// The expected message version.
+        expected: u8,
+
+        /// The message version of the response.
+        actual: u8,
+    },
+
+    /// The message does not decrypt with the VMPCK.
+    Authentication,
+
+    /// The message could not be encrypted.
+    Crypto(String),
+
+    /// The payload of the response is malformed.
+    InvalidResponse,
+
+    /// The firmware failed the request with a status.
+    Status(u32),
+}
+
+impl std::error::Error for GuestMessageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for GuestMessageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "Failed to exchange guest messages: {e}"),
+            Self::Disabled => write!(f, "The channel is disabled by a failed request."),
+            Self::PayloadSize { len } => {
+                write!(f, "The payload is {len} bytes, at most 4000 fit a message.")
+            }
+            Self::SequenceExhausted => write!(f, "The sequence numbers are used up."),
+            Self::InvalidHeader(field) => write!(f, "Invalid {field} in the message header."),
+            Self::UnexpectedSequence { expected, actual } => write!(
+                f,
+                "The response has sequence number {actual}, expected {expected}."
+            ),
+            Self::UnexpectedType { expected, actual } => {
+                write!(f, "The response has type {actual}, expected {expected}.")
+            }
+            Self::UnexpectedVersion { expected, actual } => {
+                write!(f, "The response has version {actual}, expected {expected}.")
+            }
+            Self::Authentication => write!(f, "The message does not decrypt with the VMPCK."),
+            Self::Crypto(e) => write!(f, "Failed to encrypt the message: {e}"),
+            Self::InvalidResponse => write!(f, "The response payload is malformed."),
+            Self::Status(status) => write!(f, "The firmware failed the request: {status:#x}"),
+        }
+    }
+}
+
+/// Errors which may be encountered when creating the APs of SEV-SNP guests
+/// at runtime.
+#[derive(Debug)]
+pub enum ApCreationError {
+    /// The GHCB does not hold an AP creation request.
+    InvalidExitCode(u64),
+
+    /// The AP creation request is unknown.
+    UnknownRequest(u16),
+
+    /// The request creating an AP does not carry its SEV_FEATURES in RAX.
+    MissingSevFeatures,
+
+    /// The VMSA page is not page-aligned or starts a large page.
+    InvalidVmsaGpa(u64),
+
+    /// The VMSA is not a whole page.
+    InvalidVmsaLength(usize),
+
+    /// The APs were built without a reset vector.
+    MissingResetVector,
+
+    /// The SEV_FEATURES of the AP do not have SNPActive set.
+    SnpInactive(u64),
+
+    /// The SEV_FEATURES of the AP are not those the guest launched with.
+    SevFeaturesMismatch {
+        /// The SEV_FEATURES the guest launched with.
+        expected: u64,
+
+        /// The SEV_FEATURES of the AP.
+        actual: u64,
+    },
+
+    /// The VMSA could not be built, or its SEV_FEATURES have reserved bits
+    /// set.
+    Measurement(MeasurementError),
+}
+
+impl std::error::Error for ApCreationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Measurement(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ApCreationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidExitCode(code) => {
+                write!(f, "Exit code {code:#x} is not an AP creation request.")
+            }
+            Self::UnknownRequest(request) => {
+                write!(f, "Unknown AP creation request {request}.")
+            }
+            Self::MissingSevFeatures => write!(f, "The request does not carry SEV_FEATURES."),
+            Self::InvalidVmsaGpa(gpa) => write!(
+                f,
+                "The VMSA at {gpa:#x} is not page-aligned or starts a large page."
+            ),
+            Self::InvalidVmsaLength(len) => {
+                write!(f, "The VMSA is {len} bytes, expected a page of 4096 bytes.")
+            }
+            Self::MissingResetVector => write!(f, "The APs have no reset vector."),
+            Self::SnpInactive(features) => {
+                write!(f, "SEV_FEATURES {features:#x} do not have SNPActive set.")
+            }
+            Self::SevFeaturesMismatch { expected, actual } => write!(
+                f,
+                "The AP has SEV_FEATURES {actual:#x}, the guest launched with {expected:#x}."
+            ),
+            Self::Measurement(e) => write!(f, "Invalid VMSA: {e}"),
+        }
+    }
+}
+
+impl std::convert::From<MeasurementError> for ApCreationError {
+    fn from(value: MeasurementError) -> Self {
+        Self::Measurement(value)
+    }
+}
+
+/// Errors which may be encountered when decoding binary structures with
+/// [ParseOptions](crate::ParseOptions).
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input is not the size of the structure.
+    InvalidLength {
+        /// The size of the structure.
+        expected: usize,
+
+        /// The size of the input.
+        actual: usize,
+    },
+
+    /// The structure is of a version this crate does not know.
+    UnknownVersion(u32),
+
+    /// The reserved byte at this offset is not zero.
+    Reserved(usize),
+
+    /// The input ends before the structure does.
+    Truncated,
+
+    /// An entry of a table points outside of the input.
+    OutOfBounds {
+        /// The offset of the entry's data.
+        offset: u32,
+
+        /// The length of the entry's data.
+        length: u32,
+    },
+
+    /// An entry of a table has an invalid GUID.
+    InvalidGuid,
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLength { expected, actual } => {
+                write!(f, "Expected {expected} bytes, found {actual}.")
+            }
+            Self::UnknownVersion(version) => write!(f, "Unknown structure version {version}."),
+            Self::Reserved(offset) => write!(f, "Reserved byte at offset {offset:#x} is not zero."),
+            Self::Truncated => write!(f, "The input is truncated."),
+            Self::OutOfBounds { offset, length } => write!(
+                f,
+                "Table entry data at offset {offset:#x} of length {length:#x} is out of bounds."
+            ),
+            Self::InvalidGuid => write!(f, "Table entry has an invalid GUID."),
+        }
+    }
+}
+
+impl std::convert::From<ParseError> for std::io::Error {
+    fn from(value: ParseError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, value)
+    }
+}
+
+/// Errors which may be encountered when parsing or deriving an AMD KDS URL
+/// with [KdsUrl](crate::firmware::host::KdsUrl).
+#[derive(Debug, PartialEq, Eq)]
+pub enum KdsUrlError {
+    /// The URL is not one of the KDS certificate URLs.
+    InvalidPath(String),
+
+    /// The product name is not one of a known generation.
+    UnknownProduct(String),
+
+    /// The hardware ID is not the hex encoding of a chip ID.
+    InvalidChipId(String),
+
+    /// The query has a parameter the URL does not take.
+    UnknownParameter(String),
+
+    /// The query has this parameter more than once.
+    DuplicateParameter(String),
+
+    /// The query lacks this parameter.
+    MissingParameter(&'static str),
+
+    /// The value of this parameter is not an SPL.
+    InvalidParameter(String),
+
+    /// The report is signed by neither a VCEK nor a VLEK, so that no KDS
+    /// certificate verifies it.
+    Unsigned,
+
+    /// The report names a reserved signing key.
+    ReservedSigningKey(u32),
+}
+
+impl std::error::Error for KdsUrlError {}
+
+impl std::fmt::Display for KdsUrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPath(path) => write!(f, "Not a KDS certificate URL: {path}"),
+            Self::UnknownProduct(product) => write!(f, "Unknown KDS product name {product}."),
+            Self::InvalidChipId(id) => write!(f, "Invalid hardware ID {id}."),
+            Self::UnknownParameter(name) => write!(f, "Unknown query parameter {name}."),
+            Self::DuplicateParameter(name) => write!(f, "Duplicate query parameter {name}."),
+            Self::MissingParameter(name) => write!(f, "Missing query parameter {name}."),
+            Self::InvalidParameter(name) => write!(f, "Invalid value of query parameter {name}."),
+            Self::Unsigned => write!(f, "The report is signed by neither a VCEK nor a VLEK."),
+            Self::ReservedSigningKey(key) => {
+                write!(f, "The report names the reserved signing key {key}.")
+            }
+        }
+    }
+}
+
+/// Errors which may be encountered when reading or writing a versioned
+/// snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot is shorter than its header.
+    Truncated,
+
+    /// The snapshot does not start with the magic bytes.
+    Magic,
+
+    /// The snapshot holds another type.
+    Kind {
+        /// The tag of the type being read.
+        expected: [u8; 4],
+
+        /// The tag of the type in the snapshot.
+        actual: [u8; 4],
+    },
+
+    /// The snapshot was written with a layout this version of the crate
+    /// cannot read.
+    UnsupportedVersion {
+        /// The tag of the type being read.
+        kind: [u8; 4],
+
+        /// The version of the layout.
+        version: u16,
+    },
+
+    /// The type could not be encoded or decoded.
+    Bincode(bincode::ErrorKind),
+
+    /// The JSON document could not be encoded or decoded.
+    Json(serde_json::Error),
+
+    /// The document could not be read or written.
+    Io(std::io::Error),
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "The snapshot is truncated."),
+            Self::Magic => write!(f, "The data is not a snapshot."),
+            Self::Kind { expected, actual } => write!(
+                f,
+                "The snapshot holds a {} rather than a {}.",
+                String::from_utf8_lossy(actual),
+                String::from_utf8_lossy(expected)
+            ),
+            Self::UnsupportedVersion { kind, version } => write!(
+                f,
+                "Version {version} of the {} snapshot layout is not supported.",
+                String::from_utf8_lossy(kind)
+            ),
+            Self::Bincode(e) => write!(f, "The snapshot could not be encoded or decoded: {e}"),
+            Self::Json(e) => write!(f, "The JSON document could not be encoded or decoded: {e}"),
+            Self::Io(e) => write!(f, "The document could not be read or written: {e}"),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Errors which may be encountered when issuing or checking nonces with a
+/// [NonceManager](crate::appraisal::NonceManager).
+#[derive(Debug)]
+pub enum FreshnessError {
+    /// The report data does not start with a nonce issued by the manager.
+    Unknown,
+
+    /// The nonce was issued longer ago than the manager accepts.
+    Expired,
+
+    /// The nonce was already used by another report.
+    Replayed,
+
+    /// The nonce is already outstanding.
+    Duplicate,
+
+    /// The nonce or report data is not as long as the nonces issued.
+    InvalidLength {
+        /// The length of the nonces issued.
+        expected: usize,
+
+        /// The length found.
+        actual: usize,
+    },
+
+    /// No random bytes could be generated.
+    Random(std::io::Error),
+}
+
+impl std::error::Error for FreshnessError {}
+
+impl std::fmt::Display for FreshnessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "The report does not contain a nonce issued."),
+            Self::Expired => write!(f, "The nonce has expired."),
+            Self::Replayed => write!(f, "The nonce was already used."),
+            Self::Duplicate => write!(f, "The nonce is already outstanding."),
+            Self::InvalidLength { expected, actual } => {
+                write!(f, "Expected a nonce of {expected} bytes, found {actual}.")
+            }
+            Self::Random(e) => write!(f, "Could not generate a nonce: {e}"),
+        }
+    }
+}
+
+/// Errors which may be encountered when binding a TLS channel into the
+/// report data with a [TlsBinding](crate::appraisal::TlsBinding).
+#[derive(Debug, PartialEq, Eq)]
+pub enum BindingError {
+    /// The nonce leaves too little of the report data for the channel.
+    NonceTooLong {
+        /// The length of the longest nonce accepted.
+        max: usize,
+
+        /// The length of the nonce.
+        actual: usize,
+    },
+
+    /// The report data does not bind the channel.
+    Mismatch,
+}
+
+impl std::error::Error for BindingError {}
+
+impl std::fmt::Display for BindingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NonceTooLong { max, actual } => write!(
+                f,
+                "Expected a nonce of at most {max} bytes, found {actual}."
+            ),
+            Self::Mismatch => write!(f, "The report data does not bind the TLS channel."),
+        }
+    }
+}
+
+/// Errors which may be encountered when releasing a secret to a guest with a
+/// [KeyRelease](crate::appraisal::KeyRelease), or unsealing it.
+#[cfg(all(feature = "snp", feature = "openssl"))]
+#[derive(Debug)]
+pub enum ReleaseError {
+    /// The report or its certificate chain does not verify.
+    Verification(VerificationError),
+
+    /// The report does not satisfy the policy.
+    Appraisal(crate::appraisal::Appraisal),
+
+    /// The report data does not bind the key.
+    Binding(BindingError),
+
+    /// The key is neither an RSA key of at least 2048 bits nor a P-384 key,
+    /// or the sealed secret is for the other kind of key.
+    UnsupportedKey,
+
+    /// A key could not be decoded or generated, or a secret could not be
+    /// sealed or unsealed.
+    Crypto(openssl::error::ErrorStack),
+}
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
+impl std::error::Error for ReleaseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Verification(e) => Some(e),
+            Self::Binding(e) => Some(e),
+            Self::Crypto(e) => Some(e),
+            Self::Appraisal(_) | Self::UnsupportedKey => None,
+        }
+    }
+}
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
+impl std::fmt::Display for ReleaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Verification(e) => write!(f, "The report does not verify: {e}"),
+            Self::Appraisal(appraisal) => {
+                let failures: Vec<String> = appraisal
+                    .failures()
+                    .map(|failure| failure.to_string())
+                    .collect();
+                write!(
+                    f,
+                    "The report does not satisfy the policy: {}",
+                    failures.join("; ")
+                )
+            }
+            Self::Binding(e) => write!(f, "The report does not bind the key: {e}"),
+            Self::UnsupportedKey => write!(f, "Secrets cannot be sealed to this key."),
+            Self::Crypto(e) => write!(f, "Sealing or unsealing the secret failed: {e}"),
+        }
+    }
+}
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
+impl From<VerificationError> for ReleaseError {
+    fn from(error: VerificationError) -> Self {
+        Self::Verification(error)
+    }
+}
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
+impl From<BindingError> for ReleaseError {
+    fn from(error: BindingError) -> Self {
+        Self::Binding(error)
+    }
+}
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
+impl From<openssl::error::ErrorStack> for ReleaseError {
+    fn from(error: openssl::error::ErrorStack) -> Self {
+        Self::Crypto(error)
     }
 }
 
-/// There are a number of error conditions that can occur between this
-/// layer all the way down to the SEV platform. Most of these cases have
-/// been enumerated; however, there is a possibility that some error
-/// conditions are not encapsulated here.
+/// Errors which may be encountered when verifying SEV-SNP certificates,
+/// certificate chains and attestation reports.
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
 #[derive(Debug)]
-pub enum Indeterminate<T: Debug> {
-    /// The error condition is known.
-    Known(T),
+pub enum VerificationError {
+    /// A certificate is not signed by the key of its issuer.
+    CertificateSignature,
 
-    /// The error condition is unknown.
-    Unknown,
-}
+    /// The attestation report is not signed by the VCEK or VLEK.
+    ReportSignature,
 
-#[derive(Debug)]
-/// Wrapper Error for Firmware or User API Errors
-pub enum UserApiError {
-    /// Firmware related errors.
-    FirmwareError(Error),
+    /// The attestation report is not signed at all, its signing key being
+    /// none (e.g. as the guest masks the chip key).
+    Unsigned,
 
-    /// User API related errors.
-    ApiError(CertError),
+    /// The attestation report names a reserved signing key.
+    ReservedSigningKey(u32),
 
-    /// Errors returned by the VMM via ioctl().
-    VmmError(VmmError),
+    /// The certificate table lacks a certificate of this type, or of either
+    /// VCEK or VLEK type when this is the VCEK.
+    MissingCertificate(crate::firmware::host::CertType),
 
-    /// Uuid parsing errors.
-    UuidError(uuid::Error),
+    /// The certificate table holds more than one certificate of this type.
+    DuplicateCertificate(crate::firmware::host::CertType),
 
-    /// VLEK Hashstick errors.
-    HashstickError(HashstickError),
+    /// A certificate is signed with an algorithm which is not supported.
+    UnsupportedAlgorithm(String),
 
-    /// Invalid VMPL.
-    VmplError,
+    /// A certificate, public key or signature is malformed.
+    Parse(Box<dyn error::Error + Send + Sync>),
 
-    /// Unknown error
-    Unknown,
+    /// The cryptographic library failed.
+    Crypto(Box<dyn error::Error + Send + Sync>),
 }
 
-impl error::Error for UserApiError {
-    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl VerificationError {
+    /// A malformed certificate, public key or signature, caused by `source`.
+    pub(crate) fn parse(source: impl Into<Box<dyn error::Error + Send + Sync>>) -> Self {
+        Self::Parse(source.into())
+    }
+}
+
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::ApiError(uapi_error) => Some(uapi_error),
-            Self::FirmwareError(firmware_error) => Some(firmware_error),
-            Self::UuidError(uuid_error) => Some(uuid_error),
-            Self::VmmError(vmm_error) => Some(vmm_error),
-            Self::HashstickError(hashstick_error) => Some(hashstick_error),
-            Self::VmplError => None,
-            Self::Unknown => None,
+            Self::CertificateSignature => {
+                write!(f, "Signer certificate does not sign signee certificate.")
+            }
+            Self::ReportSignature => {
+                write!(f, "The VEK does not sign the attestation report.")
+            }
+            Self::Unsigned => write!(f, "The attestation report is not signed."),
+            Self::ReservedSigningKey(key) => {
+                write!(
+                    f,
+                    "The attestation report names reserved signing key {key}."
+                )
+            }
+            Self::MissingCertificate(crate::firmware::host::CertType::VCEK) => {
+                write!(f, "Neither a VCEK nor a VLEK certificate was found.")
+            }
+            Self::MissingCertificate(cert_type) => {
+                write!(f, "No {cert_type:?} certificate was found.")
+            }
+            Self::DuplicateCertificate(cert_type) => {
+                write!(f, "More than one {cert_type:?} certificate was found.")
+            }
+            Self::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "Unsupported signature algorithm: {algorithm}")
+            }
+            Self::Parse(e) => write!(f, "Malformed certificate, key or signature: {e}"),
+            Self::Crypto(e) => write!(f, "The cryptographic library failed: {e}"),
         }
     }
 }
 
-impl std::fmt::Display for UserApiError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let err_msg: String = match self {
-            Self::FirmwareError(error) => format!("Firmware Error Encountered: {error}"),
-            Self::ApiError(error) => format!("Certificate Error Encountered: {error}"),
-            Self::UuidError(error) => format!("UUID Error Encountered: {error}"),
-            Self::VmmError(error) => format!("VMM Error Encountered: {error}"),
-            Self::HashstickError(error) => format!("VLEK Hashstick Error Encountered: {error}"),
-            Self::VmplError => "Invalid VM Permission Level (VMPL)".to_string(),
-            Self::Unknown => "Unknown Error Encountered!".to_string(),
-        };
-        write!(f, "{err_msg}")
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl error::Error for VerificationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Parse(e) | Self::Crypto(e) => Some(e.as_ref()),
+            _ => None,
+        }
     }
 }
 
-impl std::convert::From<HashstickError> for UserApiError {
-    fn from(value: HashstickError) -> Self {
-        Self::HashstickError(value)
+#[cfg(all(feature = "snp", feature = "openssl"))]
+impl std::convert::From<openssl::error::ErrorStack> for VerificationError {
+    fn from(value: openssl::error::ErrorStack) -> Self {
+        Self::Crypto(Box::new(value))
     }
 }
 
-impl std::convert::From<VmmError> for UserApiError {
-    fn from(value: VmmError) -> Self {
-        Self::VmmError(value)
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl std::convert::From<VerificationError> for io::Error {
+    fn from(value: VerificationError) -> Self {
+        let kind = match value {
+            VerificationError::Parse(_) => io::ErrorKind::InvalidData,
+            _ => io::ErrorKind::Other,
+        };
+
+        io::Error::new(kind, value)
     }
 }
 
-impl std::convert::From<uuid::Error> for UserApiError {
-    fn from(uuid_error: uuid::Error) -> Self {
-        Self::UuidError(uuid_error)
-    }
+/// Errors which may be encountered when verifying evidence offline with
+/// [verify_offline](crate::certs::snp::verify_offline).
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+#[derive(Debug)]
+pub enum OfflineError {
+    /// Neither the bundle nor the embedded roots hold these certificates.
+    Missing(Vec<crate::firmware::host::CertType>),
+
+    /// The report is not signed, or names a reserved signing key.
+    Unsigned,
+
+    /// The certificates or the report do not verify.
+    Verification(VerificationError),
 }
 
-impl std::convert::From<Error> for UserApiError {
-    fn from(firmware_error: Error) -> Self {
-        Self::FirmwareError(firmware_error)
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl std::fmt::Display for OfflineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(cert_types) => {
+                let names: Vec<String> = cert_types.iter().map(|t| format!("{t:?}")).collect();
+                write!(f, "Missing certificates: {}.", names.join(", "))
+            }
+            Self::Unsigned => write!(f, "The attestation report is not signed."),
+            Self::Verification(e) => write!(f, "The evidence does not verify: {e}"),
+        }
     }
 }
 
-impl std::convert::From<std::io::Error> for UserApiError {
-    fn from(io_error: std::io::Error) -> Self {
-        Self::FirmwareError(Error::IoError(io_error))
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl error::Error for OfflineError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Verification(e) => Some(e),
+            Self::Missing(_) | Self::Unsigned => None,
+        }
     }
 }
 
-impl std::convert::From<CertError> for UserApiError {
-    fn from(cert_error: CertError) -> Self {
-        Self::ApiError(cert_error)
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl std::convert::From<VerificationError> for OfflineError {
+    fn from(value: VerificationError) -> Self {
+        Self::Verification(value)
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-/// Errors which may be encountered when handling Version Loaded Endorsement Keys
-/// (VLEK) Hashsticks.
-pub enum HashstickError {
-    /// Hashstick length does not match what was specified in the buffer.
-    InvalidLength,
+/// Errors which may be encountered when verifying the launch measurement of
+/// a SEV guest with a [Session](crate::session::Session).
+#[cfg(all(target_os = "linux", feature = "openssl", feature = "sev"))]
+#[derive(Debug)]
+pub enum SessionError {
+    /// The measurement does not match the one computed by the session.
+    MeasurementMismatch,
 
-    /// No hashstick was provided
-    EmptyHashstickBuffer,
+    /// The measurement was taken in another launch session.
+    WrongSession,
 
-    /// Unknown Error.
-    UnknownError,
+    /// The cryptographic library failed.
+    Crypto(openssl::error::ErrorStack),
 }
 
-impl std::error::Error for HashstickError {}
-
-impl std::fmt::Display for HashstickError {
+#[cfg(all(target_os = "linux", feature = "openssl", feature = "sev"))]
+impl std::fmt::Display for SessionError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            HashstickError::InvalidLength => {
-                write!(
-                    f,
-                    "VLEK hashstick is an invalid length. Should be 432 bytes in size."
-                )
+            Self::MeasurementMismatch => {
+                write!(f, "The launch measurement does not match the session's.")
             }
-            HashstickError::EmptyHashstickBuffer => write!(f, "Hashstick buffer is empty."),
-            HashstickError::UnknownError => {
-                write!(
-                    f,
-                    "Unknown error encountered when handling the VLEK Hashstick."
-                )
+            Self::WrongSession => {
+                write!(f, "The measurement belongs to a different launch session.")
             }
+            Self::Crypto(e) => write!(f, "The cryptographic library failed: {e}"),
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "openssl", feature = "sev"))]
+impl error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Crypto(e) => Some(e),
+            _ => None,
         }
     }
 }
 
+#[cfg(all(target_os = "linux", feature = "openssl", feature = "sev"))]
+impl std::convert::From<openssl::error::ErrorStack> for SessionError {
+    fn from(value: openssl::error::ErrorStack) -> Self {
+        Self::Crypto(value)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "openssl", feature = "sev"))]
+impl std::convert::From<SessionError> for io::Error {
+    fn from(value: SessionError) -> Self {
+        let kind = match value {
+            SessionError::Crypto(_) => io::ErrorKind::Other,
+            _ => io::ErrorKind::InvalidInput,
+        };
+
+        io::Error::new(kind, value)
+    }
+}
+
 #[derive(Debug)]
 /// Errors which may be encountered through misuse of the User API.
 pub enum CertError {
@@ -300,6 +2053,15 @@ pub enum CertError {
     /// No certificates were set by the Host.
     EmptyCertBuffer,
 
+    /// The certificates need a buffer larger than the maximum accepted.
+    TableTooLarge {
+        /// The size (in bytes) of the certificates.
+        len: u32,
+
+        /// The maximum size (in bytes) accepted.
+        max: u32,
+    },
+
     /// Unknown Error.
     UnknownError,
 }
@@ -323,6 +2085,10 @@ impl std::fmt::Display for CertError {
                     "No certificates were provided by the host, please contact your CSP."
                 )
             }
+            CertError::TableTooLarge { len, max } => write!(
+                f,
+                "The host provided {len} bytes of certificates, more than the {max} accepted."
+            ),
         }
     }
 }
@@ -459,6 +2225,15 @@ pub enum Error {
 
     /// The key requested is invalid, not present, or not allowed.
     InvalidKey, // 0x0027
+
+    /// SNP_SHUTDOWN could not complete (e.g. guests are still running).
+    ShutdownIncomplete, // 0x0028
+
+    /// The length of a buffer is not the length the command requires.
+    IncorrectBufferLength = 0x0030, // 0x0030
+
+    /// The buffer is too small; a larger buffer must be provided.
+    ExpandBufferLengthRequest, // 0x0031
 }
 
 impl std::fmt::Display for Error {
@@ -511,6 +2286,9 @@ impl std::fmt::Display for Error {
             Error::RestoreRequired => "Installation of the committed firmware image required.",
             Error::RMPInitFailed => "The RMP initialization failed.",
             Error::InvalidKey => "The key requested is invalid, not present, or not allowed",
+            Error::ShutdownIncomplete => "SNP_SHUTDOWN could not complete.",
+            Error::IncorrectBufferLength => "The buffer length is incorrect for this command.",
+            Error::ExpandBufferLengthRequest => "The buffer provided is too small.",
         };
         write!(f, "{err_description}")
     }
@@ -532,8 +2310,286 @@ impl From<io::Error> for Error {
     }
 }
 
+/// A machine-readable classification of an [Error], which lets callers
+/// decide whether to retry a command or give up on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// The condition is transient. Reissuing the command, possibly after
+    /// the step given by [Error::remediation], may succeed.
+    Retryable,
+
+    /// The command, its parameters, or the platform or guest state it was
+    /// issued in are wrong. Reissuing the same command will fail again.
+    Configuration,
+
+    /// The platform hit a hardware or integrity fault. It may need to be
+    /// reset, or its firmware reinstalled, before it can be used again.
+    HardwareFault,
+
+    /// The error could not be classified.
+    Unknown,
+}
+
+impl std::fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let category = match self {
+            ErrorCategory::Retryable => "retryable",
+            ErrorCategory::Configuration => "configuration error",
+            ErrorCategory::HardwareFault => "hardware fault",
+            ErrorCategory::Unknown => "unknown",
+        };
+
+        write!(f, "{category}")
+    }
+}
+
+impl Error {
+    /// The firmware status code of the error, or `None` for errors which
+    /// did not originate in the firmware.
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            Error::IoError(_) => None,
+            Error::InvalidPlatformState => Some(0x01),
+            Error::InvalidGuestState => Some(0x02),
+            Error::InvalidConfig => Some(0x03),
+            Error::InvalidLen => Some(0x04),
+            Error::AlreadyOwned => Some(0x05),
+            Error::InvalidCertificate => Some(0x06),
+            Error::PolicyFailure => Some(0x07),
+            Error::Inactive => Some(0x08),
+            Error::InvalidAddress => Some(0x09),
+            Error::BadSignature => Some(0x0A),
+            Error::BadMeasurement => Some(0x0B),
+            Error::AsidOwned => Some(0x0C),
+            Error::InvalidAsid => Some(0x0D),
+            Error::WbinvdRequired => Some(0x0E),
+            Error::DfFlushRequired => Some(0x0F),
+            Error::InvalidGuest => Some(0x10),
+            Error::InvalidCommand => Some(0x11),
+            Error::Active => Some(0x12),
+            Error::HardwarePlatform => Some(0x13),
+            Error::HardwareUnsafe => Some(0x14),
+            Error::Unsupported => Some(0x15),
+            Error::InvalidParam => Some(0x16),
+            Error::ResourceLimit => Some(0x17),
+            Error::SecureDataInvalid => Some(0x18),
+            Error::InvalidPageSize => Some(0x19),
+            Error::InvalidPageState => Some(0x1A),
+            Error::InvalidMdataEntry => Some(0x1B),
+            Error::InvalidPageOwner => Some(0x1C),
+            Error::AEADOFlow => Some(0x1D),
+            Error::RbModeExited => Some(0x1F),
+            Error::RMPInitRequired => Some(0x20),
+            Error::BadSvn => Some(0x21),
+            Error::BadVersion => Some(0x22),
+            Error::ShutdownRequired => Some(0x23),
+            Error::UpdateFailed => Some(0x24),
+            Error::RestoreRequired => Some(0x25),
+            Error::RMPInitFailed => Some(0x26),
+            Error::InvalidKey => Some(0x27),
+            Error::ShutdownIncomplete => Some(0x28),
+            Error::IncorrectBufferLength => Some(0x30),
+            Error::ExpandBufferLengthRequest => Some(0x31),
+        }
+    }
+
+    /// Classify the error.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Error::IoError(e) => match e.raw_os_error() {
+                Some(libc::EINTR | libc::EAGAIN | libc::EBUSY | libc::ETIMEDOUT) => {
+                    ErrorCategory::Retryable
+                }
+                _ => ErrorCategory::Configuration,
+            },
+
+            Error::WbinvdRequired
+            | Error::DfFlushRequired
+            | Error::HardwarePlatform
+            | Error::ResourceLimit
+            | Error::RbModeExited
+            | Error::ExpandBufferLengthRequest => ErrorCategory::Retryable,
+
+            Error::HardwareUnsafe
+            | Error::SecureDataInvalid
+            | Error::AEADOFlow
+            | Error::UpdateFailed
+            | Error::RestoreRequired
+            | Error::RMPInitFailed => ErrorCategory::HardwareFault,
+
+            Error::InvalidPlatformState
+            | Error::InvalidGuestState
+            | Error::InvalidConfig
+            | Error::InvalidLen
+            | Error::AlreadyOwned
+            | Error::InvalidCertificate
+            | Error::PolicyFailure
+            | Error::Inactive
+            | Error::InvalidAddress
+            | Error::BadSignature
+            | Error::BadMeasurement
+            | Error::AsidOwned
+            | Error::InvalidAsid
+            | Error::InvalidGuest
+            | Error::InvalidCommand
+            | Error::Active
+            | Error::Unsupported
+            | Error::InvalidParam
+            | Error::InvalidPageSize
+            | Error::InvalidPageState
+            | Error::InvalidMdataEntry
+            | Error::InvalidPageOwner
+            | Error::RMPInitRequired
+            | Error::BadSvn
+            | Error::BadVersion
+            | Error::ShutdownRequired
+            | Error::InvalidKey
+            | Error::ShutdownIncomplete
+            | Error::IncorrectBufferLength => ErrorCategory::Configuration,
+        }
+    }
+
+    /// Returns true if reissuing the command may succeed.
+    pub fn is_retryable(&self) -> bool {
+        self.category() == ErrorCategory::Retryable
+    }
+
+    /// A short description of what an operator can do to recover from the
+    /// error.
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            Error::IoError(_) => {
+                "Check that the SEV device exists, that the ccp (or kvm_amd) driver is \
+                 loaded and that the caller may open it."
+            }
+            Error::InvalidPlatformState => {
+                "Query the platform status and bring the platform into the state the \
+                 command requires (e.g. INIT or SNP_INIT) before retrying."
+            }
+            Error::InvalidGuestState => {
+                "Query the guest status; the command was issued out of order in the \
+                 guest's launch or migration flow."
+            }
+            Error::InvalidConfig => "Review the platform configuration passed to the command.",
+            Error::InvalidLen => "Enlarge the buffer to the length reported by the firmware.",
+            Error::AlreadyOwned => {
+                "The platform is owned; reset it (PLATFORM_RESET) before importing a new \
+                 owner."
+            }
+            Error::InvalidCertificate => {
+                "Check that the certificates are well-formed, and that they are issued \
+                 by this platform's chain."
+            }
+            Error::PolicyFailure => "The guest policy forbids this command; review the policy.",
+            Error::Inactive => "Activate the guest before issuing this command.",
+            Error::InvalidAddress => {
+                "Check that the addresses passed are valid, aligned and mapped."
+            }
+            Error::BadSignature => "Check the key used to sign the data.",
+            Error::BadMeasurement => {
+                "The measurement does not match; check the launch digest and the \
+                 guest image."
+            }
+            Error::AsidOwned => "Deactivate the guest which owns the ASID, or use another ASID.",
+            Error::InvalidAsid => "Use an ASID in the range the platform reserves for SEV guests.",
+            Error::WbinvdRequired => "Execute WBINVD on all cores, then retry.",
+            Error::DfFlushRequired => "Issue DF_FLUSH, then retry.",
+            Error::InvalidGuest => {
+                "Check the guest handle; the guest may have been decommissioned."
+            }
+            Error::InvalidCommand => "The command is not known to this firmware version.",
+            Error::Active => "Deactivate the guest before issuing this command.",
+            Error::HardwarePlatform => {
+                "A transient hardware condition occurred; parameter buffers may be \
+                 reused and the command retried."
+            }
+            Error::HardwareUnsafe => {
+                "A hardware condition occurred; do not reuse parameter buffers. Reset \
+                 the platform and contact the hardware vendor if it persists."
+            }
+            Error::Unsupported => {
+                "The feature is not supported by this platform or firmware version; \
+                 update the firmware or disable the feature."
+            }
+            Error::InvalidParam => "Review the parameters passed to the command.",
+            Error::ResourceLimit => {
+                "The firmware ran out of resources; release unused guests or contexts, \
+                 then retry."
+            }
+            Error::SecureDataInvalid => {
+                "An integrity check failed; the data may have been tampered with. Do \
+                 not retry with the same data."
+            }
+            Error::InvalidPageSize => "Use the page size recorded in the RMP for the page.",
+            Error::InvalidPageState => {
+                "Transition the page into the state the command requires (e.g. \
+                 firmware or guest owned)."
+            }
+            Error::InvalidMdataEntry => "Check the metadata entry passed with the page.",
+            Error::InvalidPageOwner => "Check that the page belongs to the guest.",
+            Error::AEADOFlow => {
+                "The guest context has exhausted its AEAD sequence numbers; \
+                 decommission and relaunch the guest."
+            }
+            Error::RbModeExited => "The firmware left Ring Buffer mode; retry the command.",
+            Error::RMPInitRequired => "Initialize SNP (SNP_INIT) so that the RMP is set up.",
+            Error::BadSvn => "Install a firmware image whose SVN is not below the committed SVN.",
+            Error::BadVersion => {
+                "Install a firmware version which is not older than the committed version."
+            }
+            Error::ShutdownRequired => "Shut down SNP (SNP_SHUTDOWN), then retry.",
+            Error::UpdateFailed => {
+                "The firmware state update failed; reset the platform and retry the update."
+            }
+            Error::RestoreRequired => {
+                "Install the committed firmware image (e.g. reload the ccp driver or \
+                 reboot)."
+            }
+            Error::RMPInitFailed => {
+                "RMP initialization failed; check the memory configuration and reboot."
+            }
+            Error::InvalidKey => {
+                "The key is unavailable; check that it was loaded (e.g. the VLEK) and \
+                 that the guest may use it."
+            }
+            Error::ShutdownIncomplete => "Decommission all SNP guests before shutting down SNP.",
+            Error::IncorrectBufferLength => "Pass a buffer of exactly the required length.",
+            Error::ExpandBufferLengthRequest => {
+                "Enlarge the buffer to the length reported by the firmware, then retry."
+            }
+        }
+    }
+}
+
 impl error::Error for Indeterminate<Error> {}
 
+impl Indeterminate<Error> {
+    /// Classify the error. Unknown errors are [ErrorCategory::Unknown].
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Indeterminate::Known(e) => e.category(),
+            Indeterminate::Unknown => ErrorCategory::Unknown,
+        }
+    }
+
+    /// Returns true if reissuing the command may succeed.
+    pub fn is_retryable(&self) -> bool {
+        self.category() == ErrorCategory::Retryable
+    }
+
+    /// A short description of what an operator can do to recover from the
+    /// error.
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            Indeterminate::Known(e) => e.remediation(),
+            Indeterminate::Unknown => {
+                "The firmware returned an unknown status; check the kernel log and \
+                 the firmware version."
+            }
+        }
+    }
+}
+
 impl Display for Indeterminate<Error> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let err_description = match self {
@@ -611,6 +2667,9 @@ impl From<u32> for Indeterminate<Error> {
             0x25 => Error::RestoreRequired,
             0x26 => Error::RMPInitFailed,
             0x27 => Error::InvalidKey,
+            0x28 => Error::ShutdownIncomplete,
+            0x30 => Error::IncorrectBufferLength,
+            0x31 => Error::ExpandBufferLengthRequest,
             _ => return Indeterminate::Unknown,
         })
     }
@@ -660,6 +2719,9 @@ impl From<Indeterminate<Error>> for c_int {
                 Error::RestoreRequired => 0x25,
                 Error::RMPInitFailed => 0x26,
                 Error::InvalidKey => 0x27,
+                Error::ShutdownIncomplete => 0x28,
+                Error::IncorrectBufferLength => 0x30,
+                Error::ExpandBufferLengthRequest => 0x31,
             },
         }
     }
@@ -767,6 +2829,9 @@ pub enum SevHashError {
     /// Provided page has invalid offset
     InvalidOffset(usize, usize),
 
+    /// No kernel was provided to hash
+    MissingKernel,
+
     /// Unknown Error.
     UnknownError,
 }
@@ -780,6 +2845,7 @@ impl std::fmt::Display for SevHashError {
             SevHashError::InvalidSize(actual, expected) => {
                 write!(f, "Invalid page Size: {actual} vs {expected}")
             }
+            SevHashError::MissingKernel => write!(f, "No kernel provided for the SEV hashes table"),
             SevHashError::UnknownError => write!(f, "An unknown SEV Hashing error encountered"),
         }
     }
@@ -929,6 +2995,21 @@ pub enum MeasurementError {
 
     /// OVMF is missing required section with kernel specified
     MissingSection(String),
+
+    /// Raw VMSA override (offset, length) which does not fit in the VMSA page
+    InvalidVmsaPatch(usize, usize),
+
+    /// SVSM layout which cannot be launched
+    InvalidSvsmLayout(String),
+
+    /// Guest features with reserved bits set
+    InvalidGuestFeatures(u64),
+
+    /// VMSAs of a guest with this many vCPUs built without an AP reset vector
+    MissingApResetVector(u64),
+
+    /// Firmware region (start, end) which is not page-aligned
+    InvalidFirmwareRegion(u64, u64),
 }
 
 impl std::fmt::Display for MeasurementError {
@@ -960,6 +3041,26 @@ impl std::fmt::Display for MeasurementError {
                 f,
                 "Kernel specified but OVMF metadata doesn't include {section} section"
             ),
+            MeasurementError::InvalidVmsaPatch(offset, len) => write!(
+                f,
+                "VMSA override of {len} bytes at offset {offset} exceeds the VMSA page"
+            ),
+            MeasurementError::InvalidSvsmLayout(reason) => {
+                write!(f, "Invalid SVSM layout: {reason}")
+            }
+            MeasurementError::InvalidGuestFeatures(features) => {
+                write!(f, "Guest features {features:#x} set reserved bits")
+            }
+            MeasurementError::MissingApResetVector(vcpus) => write!(
+                f,
+                "No reset vector for the auxiliary processors of a guest with {vcpus} vCPUs"
+            ),
+            MeasurementError::InvalidFirmwareRegion(start, end) => {
+                write!(
+                    f,
+                    "Firmware region {start:#x}..{end:#x} is not page-aligned"
+                )
+            }
         }
     }
 }
@@ -1013,3 +3114,85 @@ impl std::convert::From<SevHashError> for MeasurementError {
         Self::SevHashError(value)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_code_round_trip() {
+        for code in 0x01..=0x40u32 {
+            if let Indeterminate::Known(error) = Indeterminate::<Error>::from(code) {
+                assert_eq!(error.code(), Some(code));
+                assert!(!error.remediation().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_category() {
+        assert!(Error::DfFlushRequired.is_retryable());
+        assert!(Error::IoError(io::Error::from_raw_os_error(libc::EBUSY)).is_retryable());
+        assert_eq!(
+            Error::IoError(io::Error::from_raw_os_error(libc::ENOENT)).category(),
+            ErrorCategory::Configuration
+        );
+        assert_eq!(
+            Error::HardwareUnsafe.category(),
+            ErrorCategory::HardwareFault
+        );
+        assert_eq!(
+            Indeterminate::<Error>::from(0x2Fu32).category(),
+            ErrorCategory::Unknown
+        );
+        assert!(UserApiError::VmmError(VmmError::RateLimitRetryRequest).is_retryable());
+    }
+
+    #[test]
+    fn test_user_api_category() {
+        use ErrorCategory::*;
+
+        let cases: Vec<(UserApiError, ErrorCategory)> = vec![
+            (
+                UserApiError::FirmwareError(Error::DfFlushRequired),
+                Retryable,
+            ),
+            (
+                UserApiError::FirmwareError(Error::HardwareUnsafe),
+                HardwareFault,
+            ),
+            (
+                UserApiError::ApiError(CertError::InvalidGUID),
+                Configuration,
+            ),
+            (
+                UserApiError::VmmError(VmmError::InvalidCertificatePageLength),
+                Configuration,
+            ),
+            (
+                UserApiError::VmmError(VmmError::RateLimitRetryRequest),
+                Retryable,
+            ),
+            (UserApiError::VmmError(VmmError::Unknown), Unknown),
+            (
+                UserApiError::UuidError(uuid::Uuid::parse_str("vcek").unwrap_err()),
+                Configuration,
+            ),
+            (
+                UserApiError::HashstickError(HashstickError::InvalidLength),
+                Configuration,
+            ),
+            (
+                UserApiError::ConfigError(ConfigError::ReservedMaskBits(0b100)),
+                Configuration,
+            ),
+            (UserApiError::VmplError, Configuration),
+            (UserApiError::Unknown, Unknown),
+        ];
+
+        for (error, category) in cases {
+            assert_eq!(error.category(), category, "{error:?}");
+            assert_eq!(error.is_retryable(), category == Retryable, "{error:?}");
+        }
+    }
+}
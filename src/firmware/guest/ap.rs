@@ -0,0 +1,427 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Creating the APs of SEV-SNP guests at runtime.
+//!
+//! The hypervisor cannot set up the vCPUs of an SNP guest, whose VMSAs are
+//! encrypted. To bring up a vCPU after launch, e.g. one the VMM hot-plugged,
+//! the guest builds the VMSA of the AP in one of its pages, turns it into a
+//! VMSA page with RMPADJUST and names it to the hypervisor with the AP
+//! creation event of the GHCB protocol:
+//!
+//! - [ApVmsa]: the VMSA page of an AP, e.g. built with a
+//!   [VmsaBuilder](crate::measurement::vmsa::VmsaBuilder).
+//! - [ApCreation]: the AP creation request, written to and read from the
+//!   [Ghcb].
+//!
+//! Every VMSA of a guest must carry the SEV_FEATURES the guest launched
+//! with, which the hypervisor checks on the features the request carries in
+//! RAX. Both sides check them with [ApVmsa::validate] and
+//! [ApCreation::validate] before the vCPU runs.
+//!
+//! # Example:
+//! ```ignore
+//! let vmsa = ApVmsa::build(
+//!     VmsaBuilder::new(VMMType::QEMU)
+//!         .guest_features(features)
+//!         .ap_reset_vector(ovmf.ap_reset_vector()?),
+//! )?;
+//! vmsa.validate(features)?;
+//!
+//! // Copy the page to `gpa`, RMPADJUST it to a VMSA page, then:
+//! ApCreation::create(apic_id, gpa, &vmsa).write(&mut ghcb);
+//!
+//! // The VMM handling the VMGEXIT:
+//! let request = ApCreation::read(&ghcb)?;
+//! request.validate(launched_features)?;
+//! ```
+
+use crate::{
+    error::ApCreationError,
+    firmware::guest::ghcb::{ExitCode, Ghcb, GhcbField},
+    measurement::vmsa::{GuestFeatures, VmsaBuilder, VMSA_PAGE_SIZE},
+};
+
+use std::convert::TryFrom;
+
+/// The offset of the VMPL in a VMSA page.
+const VMPL_OFFSET: usize = 0xca;
+
+/// The offset of SEV_FEATURES in a VMSA page.
+const SEV_FEATURES_OFFSET: usize = 0x3b0;
+
+/// The size (in bytes) of the large pages a VMSA page must not start.
+const LARGE_PAGE_SIZE: u64 = 0x20_0000;
+
+/// The requests of the AP creation event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+pub enum ApCreateRequest {
+    /// Run the AP from the VMSA once it receives INIT.
+    CreateOnInit = 0,
+
+    /// Run the AP from the VMSA now.
+    Create = 1,
+
+    /// Stop the AP, no longer running it from its VMSA.
+    Destroy = 2,
+}
+
+impl From<ApCreateRequest> for u16 {
+    fn from(value: ApCreateRequest) -> Self {
+        value as u16
+    }
+}
+
+impl TryFrom<u16> for ApCreateRequest {
+    type Error = ApCreationError;
+
+    fn try_from(value: u16) -> Result<Self, ApCreationError> {
+        Ok(match value {
+            0 => Self::CreateOnInit,
+            1 => Self::Create,
+            2 => Self::Destroy,
+            _ => return Err(ApCreationError::UnknownRequest(value)),
+        })
+    }
+}
+
+/// The VMSA page of an AP
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApVmsa(Vec<u8>);
+
+impl ApVmsa {
+    /// The VMSA of the APs `builder` builds, which needs their reset vector.
+    pub fn build(builder: VmsaBuilder) -> Result<Self, ApCreationError> {
+        builder
+            .build()?
+            .ap_page()?
+            .map(Self)
+            .ok_or(ApCreationError::MissingResetVector)
+    }
+
+    /// The VMSA of `page`, which must be a whole VMSA page.
+    pub fn from_page(page: Vec<u8>) -> Result<Self, ApCreationError> {
+        if page.len() != VMSA_PAGE_SIZE {
+            return Err(ApCreationError::InvalidVmsaLength(page.len()));
+        }
+
+        Ok(Self(page))
+    }
+
+    /// The VMSA page, to be copied into the guest page the AP runs from.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The VMPL the AP runs at.
+    pub fn vmpl(&self) -> u8 {
+        self.0[VMPL_OFFSET]
+    }
+
+    /// The SEV_FEATURES the AP runs with.
+    pub fn sev_features(&self) -> GuestFeatures {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self.0[SEV_FEATURES_OFFSET..][..8]);
+        GuestFeatures::from(u64::from_le_bytes(bytes))
+    }
+
+    /// Check that the AP runs with `launched`, the SEV_FEATURES the guest
+    /// launched with, which must have SNPActive and no reserved bit set.
+    pub fn validate(&self, launched: GuestFeatures) -> Result<(), ApCreationError> {
+        check_sev_features(self.sev_features(), launched)
+    }
+}
+
+/// An AP creation request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ApCreation {
+    /// What to do with the AP.
+    pub request: ApCreateRequest,
+
+    /// The APIC ID of the AP.
+    pub apic_id: u32,
+
+    /// The VMPL the VMSA runs at, which hypervisors without multi-VMPL
+    /// support ignore.
+    pub vmpl: u8,
+
+    /// The GPA of the VMSA page.
+    pub vmsa_gpa: u64,
+
+    /// The SEV_FEATURES of the VMSA, carried in RAX.
+    pub sev_features: GuestFeatures,
+}
+
+impl ApCreation {
+    /// Run the AP of `apic_id` now from `vmsa`, which the guest placed at
+    /// `vmsa_gpa`.
+    pub fn create(apic_id: u32, vmsa_gpa: u64, vmsa: &ApVmsa) -> Self {
+        Self::with_vmsa(ApCreateRequest::Create, apic_id, vmsa_gpa, vmsa)
+    }
+
+    /// Run the AP of `apic_id` from `vmsa`, which the guest placed at
+    /// `vmsa_gpa`, once it receives INIT.
+    pub fn create_on_init(apic_id: u32, vmsa_gpa: u64, vmsa: &ApVmsa) -> Self {
+        Self::with_vmsa(ApCreateRequest::CreateOnInit, apic_id, vmsa_gpa, vmsa)
+    }
+
+    /// Stop the AP of `apic_id`.
+    pub fn destroy(apic_id: u32) -> Self {
+        Self {
+            request: ApCreateRequest::Destroy,
+            apic_id,
+            vmpl: 0,
+            vmsa_gpa: 0,
+            sev_features: GuestFeatures::default(),
+        }
+    }
+
+    fn with_vmsa(request: ApCreateRequest, apic_id: u32, vmsa_gpa: u64, vmsa: &ApVmsa) -> Self {
+        Self {
+            request,
+            apic_id,
+            vmpl: vmsa.vmpl(),
+            vmsa_gpa,
+            sev_features: vmsa.sev_features(),
+        }
+    }
+
+    /// Whether the request runs the AP from a VMSA.
+    pub fn creates(&self) -> bool {
+        self.request != ApCreateRequest::Destroy
+    }
+
+    /// Write the request to `ghcb`, ready for VMGEXIT.
+    pub fn write(&self, ghcb: &mut Ghcb) {
+        let info1 = u64::from(u16::from(self.request))
+            | u64::from(self.vmpl) << 16
+            | u64::from(self.apic_id) << 32;
+
+        if self.creates() {
+            ghcb.set(GhcbField::Rax, self.sev_features.into());
+        }
+        ghcb.set_exit(ExitCode::ApCreation, info1, self.vmsa_gpa);
+    }
+
+    /// Read the request the guest wrote to `ghcb`.
+    pub fn read(ghcb: &Ghcb) -> Result<Self, ApCreationError> {
+        let code = ghcb.get(GhcbField::SwExitCode);
+        if code != u64::from(ExitCode::ApCreation) {
+            return Err(ApCreationError::InvalidExitCode(code));
+        }
+
+        let info1 = ghcb.get(GhcbField::SwExitInfo1);
+        let request = ApCreateRequest::try_from(info1 as u16)?;
+
+        let sev_features = match (request, ghcb.get_valid(GhcbField::Rax)) {
+            (ApCreateRequest::Destroy, _) => GuestFeatures::default(),
+            (_, Some(features)) => GuestFeatures::from(features),
+            (_, None) => return Err(ApCreationError::MissingSevFeatures),
+        };
+
+        Ok(Self {
+            request,
+            apic_id: (info1 >> 32) as u32,
+            vmpl: (info1 >> 16) as u8,
+            vmsa_gpa: ghcb.get(GhcbField::SwExitInfo2),
+            sev_features,
+        })
+    }
+
+    /// Check that the AP is created with `launched`, the SEV_FEATURES the
+    /// guest launched with, from a VMSA page which is page-aligned but does
+    /// not start a large page (which the VMSA erratum of some processors
+    /// forbids).
+    pub fn validate(&self, launched: GuestFeatures) -> Result<(), ApCreationError> {
+        if !self.creates() {
+            return Ok(());
+        }
+
+        let gpa = self.vmsa_gpa;
+        if gpa % VMSA_PAGE_SIZE as u64 != 0 || gpa % LARGE_PAGE_SIZE == 0 {
+            return Err(ApCreationError::InvalidVmsaGpa(gpa));
+        }
+
+        check_sev_features(self.sev_features, launched)
+    }
+}
+
+/// Check that `features` are the valid SEV_FEATURES of an SNP guest, equal
+/// to those it launched with.
+fn check_sev_features(
+    features: GuestFeatures,
+    launched: GuestFeatures,
+) -> Result<(), ApCreationError> {
+    features.validate()?;
+
+    if features.snp_active() == 0 {
+        return Err(ApCreationError::SnpInactive(features.into()));
+    }
+
+    if features != launched {
+        return Err(ApCreationError::SevFeaturesMismatch {
+            expected: launched.into(),
+            actual: features.into(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{error::MeasurementError, measurement::vmsa::VMMType};
+
+    const AP_EIP: u64 = 0xffff_f000;
+    const VMSA_GPA: u64 = 0x1_0000_1000;
+
+    fn features() -> GuestFeatures {
+        GuestFeatures::snp(1 << 5).unwrap()
+    }
+
+    fn vmsa() -> ApVmsa {
+        ApVmsa::build(
+            VmsaBuilder::new(VMMType::QEMU)
+                .guest_features(features())
+                .ap_eip(AP_EIP),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_vmsa() {
+        let vmsa = vmsa();
+
+        assert_eq!(vmsa.as_bytes().len(), VMSA_PAGE_SIZE);
+        assert_eq!(vmsa.vmpl(), 0);
+        assert_eq!(vmsa.sev_features(), features());
+        vmsa.validate(features()).unwrap();
+
+        assert_eq!(ApVmsa::from_page(vmsa.as_bytes().to_vec()).unwrap(), vmsa);
+        assert!(matches!(
+            ApVmsa::from_page(vec![0; 16]),
+            Err(ApCreationError::InvalidVmsaLength(16))
+        ));
+    }
+
+    #[test]
+    fn test_vmsa_reset_vector() {
+        assert!(matches!(
+            ApVmsa::build(VmsaBuilder::new(VMMType::QEMU)),
+            Err(ApCreationError::MissingResetVector)
+        ));
+    }
+
+    #[test]
+    fn test_vmsa_features() {
+        let build = |features: u64| {
+            ApVmsa::build(
+                VmsaBuilder::new(VMMType::QEMU)
+                    .guest_features(GuestFeatures::from(features))
+                    .ap_eip(AP_EIP),
+            )
+        };
+
+        assert!(matches!(
+            build(1 << 5).unwrap().validate(features()),
+            Err(ApCreationError::SnpInactive(0x20))
+        ));
+        assert!(matches!(
+            build(0x1).unwrap().validate(features()),
+            Err(ApCreationError::SevFeaturesMismatch {
+                expected: 0x21,
+                actual: 0x1
+            })
+        ));
+        assert!(matches!(
+            build(1 << 11),
+            Err(ApCreationError::Measurement(
+                MeasurementError::InvalidGuestFeatures(0x800)
+            ))
+        ));
+
+        let vmpl1 = ApVmsa::build(
+            VmsaBuilder::new(VMMType::QEMU)
+                .guest_features(features())
+                .ap_eip(AP_EIP)
+                .ap_override(VMPL_OFFSET, &[1]),
+        )
+        .unwrap();
+        assert_eq!(vmpl1.vmpl(), 1);
+        assert_eq!(ApCreation::create(1, VMSA_GPA, &vmpl1).vmpl, 1);
+    }
+
+    #[test]
+    fn test_ghcb_round_trip() {
+        let vmsa = vmsa();
+
+        for request in [
+            ApCreation::create(3, VMSA_GPA, &vmsa),
+            ApCreation::create_on_init(0x1_0000, VMSA_GPA, &vmsa),
+            ApCreation::destroy(7),
+        ] {
+            let mut ghcb = Ghcb::new();
+            request.write(&mut ghcb);
+
+            assert_eq!(ghcb.get(GhcbField::SwExitCode), 0x8000_0013);
+            assert_eq!(ApCreation::read(&ghcb).unwrap(), request);
+            request.validate(features()).unwrap();
+        }
+
+        let mut ghcb = Ghcb::new();
+        ApCreation::create(3, VMSA_GPA, &vmsa).write(&mut ghcb);
+        assert_eq!(ghcb.get(GhcbField::SwExitInfo1), 0x3_0000_0001);
+        assert_eq!(ghcb.get(GhcbField::SwExitInfo2), VMSA_GPA);
+        assert_eq!(ghcb.get(GhcbField::Rax), 0x21);
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let mut ghcb = Ghcb::new();
+        ghcb.set_exit(ExitCode::Cpuid, 0, 0);
+        assert!(matches!(
+            ApCreation::read(&ghcb),
+            Err(ApCreationError::InvalidExitCode(0x72))
+        ));
+
+        ghcb.set_exit(ExitCode::ApCreation, 3, VMSA_GPA);
+        assert!(matches!(
+            ApCreation::read(&ghcb),
+            Err(ApCreationError::UnknownRequest(3))
+        ));
+
+        ghcb.set_exit(ExitCode::ApCreation, 1, VMSA_GPA);
+        assert!(matches!(
+            ApCreation::read(&ghcb),
+            Err(ApCreationError::MissingSevFeatures)
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let request = ApCreation::create(1, VMSA_GPA, &vmsa());
+        request.validate(features()).unwrap();
+
+        for gpa in [VMSA_GPA + 8, 0x20_0000] {
+            let request = ApCreation {
+                vmsa_gpa: gpa,
+                ..request
+            };
+            assert!(matches!(
+                request.validate(features()),
+                Err(ApCreationError::InvalidVmsaGpa(g)) if g == gpa
+            ));
+        }
+
+        let launched = GuestFeatures::snp(0).unwrap();
+        assert!(matches!(
+            request.validate(launched),
+            Err(ApCreationError::SevFeaturesMismatch {
+                expected: 0x1,
+                actual: 0x21
+            })
+        ));
+    }
+}
@@ -0,0 +1,897 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The Guest-Hypervisor Communication Block (GHCB) protocol.
+//!
+//! SEV-ES and SEV-SNP guests hand the hypervisor the state it needs to
+//! emulate an instruction through a shared page, the GHCB, and exit with
+//! VMGEXIT. Before a GHCB is established (and for a few simple requests),
+//! they communicate through the GHCB MSR instead. This module defines both,
+//! as the GHCB specification (AMD publication 56421) lays them out:
+//!
+//! - [Ghcb]: the GHCB page, whose fields are read and written by
+//!   [GhcbField] and marked valid in its bitmap as they are written.
+//! - [MsrProtocol]: the requests and responses of the GHCB MSR protocol.
+//! - [ExitCode]: the exit codes of the SW_EXITCODE field.
+//!
+//! Like the rest of the crate, the fields are encoded little-endian byte by
+//! byte, so that the definitions hold whatever the host they are built on.
+//!
+//! # Example:
+//! ```ignore
+//! let mut ghcb = Ghcb::new();
+//! ghcb.set_protocol_version(GHCB_PROTOCOL_MAX);
+//! ghcb.set(GhcbField::Rax, leaf);
+//! ghcb.set(GhcbField::Rcx, subleaf);
+//! ghcb.set_exit(ExitCode::Cpuid, 0, 0);
+//!
+//! // Write the GHCB's GPA to the GHCB MSR and VMGEXIT, then:
+//! let eax = ghcb.get(GhcbField::Rax);
+//! ```
+
+use core::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+
+use bitfield::bitfield;
+
+/// The GHCB MSR.
+pub const GHCB_MSR: u32 = 0xc001_0130;
+
+/// The size (in bytes) of the GHCB page.
+pub const GHCB_SIZE: usize = 0x1000;
+
+/// The earliest GHCB protocol version this module describes.
+pub const GHCB_PROTOCOL_MIN: u16 = 1;
+
+/// The latest GHCB protocol version this module describes.
+pub const GHCB_PROTOCOL_MAX: u16 = 2;
+
+/// The GHCB usage of the standard GHCB layout.
+pub const GHCB_USAGE_STANDARD: u32 = 0;
+
+/// The offset of the shared buffer in the GHCB page.
+pub const SHARED_BUFFER_OFFSET: usize = 0x800;
+
+/// The size (in bytes) of the shared buffer.
+pub const SHARED_BUFFER_SIZE: usize = 0x7f0;
+
+const VALID_BITMAP_OFFSET: usize = 0x3f0;
+const PROTOCOL_VERSION_OFFSET: usize = 0xffa;
+const USAGE_OFFSET: usize = 0xffc;
+
+/// The fields of the save area of the GHCB
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GhcbField {
+    /// The current privilege level.
+    Cpl,
+    /// The XSS register.
+    Xss,
+    /// The DR7 register.
+    Dr7,
+    /// The RIP register.
+    Rip,
+    /// The RSP register.
+    Rsp,
+    /// The RAX register.
+    Rax,
+    /// The RCX register.
+    Rcx,
+    /// The RDX register.
+    Rdx,
+    /// The RBX register.
+    Rbx,
+    /// The RBP register.
+    Rbp,
+    /// The RSI register.
+    Rsi,
+    /// The RDI register.
+    Rdi,
+    /// The R8 register.
+    R8,
+    /// The R9 register.
+    R9,
+    /// The R10 register.
+    R10,
+    /// The R11 register.
+    R11,
+    /// The R12 register.
+    R12,
+    /// The R13 register.
+    R13,
+    /// The R14 register.
+    R14,
+    /// The R15 register.
+    R15,
+    /// The exit code of the request, which [ExitCode] names.
+    SwExitCode,
+    /// The first exit information of the request.
+    SwExitInfo1,
+    /// The second exit information of the request.
+    SwExitInfo2,
+    /// The GPA of the scratch area of the request.
+    SwScratch,
+    /// The XCR0 register.
+    Xcr0,
+    /// The GPA of the x87 state (protocol version 2).
+    X87StateGpa,
+}
+
+impl GhcbField {
+    /// Every field, by offset.
+    pub const ALL: [GhcbField; 26] = [
+        Self::Cpl,
+        Self::Xss,
+        Self::Dr7,
+        Self::Rip,
+        Self::Rsp,
+        Self::Rax,
+        Self::Rcx,
+        Self::Rdx,
+        Self::Rbx,
+        Self::Rbp,
+        Self::Rsi,
+        Self::Rdi,
+        Self::R8,
+        Self::R9,
+        Self::R10,
+        Self::R11,
+        Self::R12,
+        Self::R13,
+        Self::R14,
+        Self::R15,
+        Self::SwExitCode,
+        Self::SwExitInfo1,
+        Self::SwExitInfo2,
+        Self::SwScratch,
+        Self::Xcr0,
+        Self::X87StateGpa,
+    ];
+
+    /// The offset of the field in the GHCB page.
+    pub const fn offset(self) -> usize {
+        match self {
+            Self::Cpl => 0x0cb,
+            Self::Xss => 0x140,
+            Self::Dr7 => 0x160,
+            Self::Rip => 0x178,
+            Self::Rsp => 0x1d8,
+            Self::Rax => 0x1f8,
+            Self::Rcx => 0x308,
+            Self::Rdx => 0x310,
+            Self::Rbx => 0x318,
+            Self::Rbp => 0x328,
+            Self::Rsi => 0x330,
+            Self::Rdi => 0x338,
+            Self::R8 => 0x340,
+            Self::R9 => 0x348,
+            Self::R10 => 0x350,
+            Self::R11 => 0x358,
+            Self::R12 => 0x360,
+            Self::R13 => 0x368,
+            Self::R14 => 0x370,
+            Self::R15 => 0x378,
+            Self::SwExitCode => 0x390,
+            Self::SwExitInfo1 => 0x398,
+            Self::SwExitInfo2 => 0x3a0,
+            Self::SwScratch => 0x3a8,
+            Self::Xcr0 => 0x3e8,
+            Self::X87StateGpa => 0x400,
+        }
+    }
+
+    /// The size (in bytes) of the field.
+    pub const fn size(self) -> usize {
+        match self {
+            Self::Cpl => 1,
+            _ => 8,
+        }
+    }
+
+    /// The bit of the field in the valid bitmap: its offset in quadwords.
+    pub const fn valid_bit(self) -> usize {
+        self.offset() / 8
+    }
+}
+
+/// The GHCB page
+#[derive(Clone, PartialEq, Eq)]
+#[repr(C, align(4096))]
+pub struct Ghcb([u8; GHCB_SIZE]);
+
+impl Ghcb {
+    /// A zeroed GHCB, with no field valid.
+    pub const fn new() -> Self {
+        Self([0; GHCB_SIZE])
+    }
+
+    /// The GHCB of the bytes of a GHCB page.
+    pub const fn from_bytes(bytes: [u8; GHCB_SIZE]) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of the page.
+    pub fn as_bytes(&self) -> &[u8; GHCB_SIZE] {
+        &self.0
+    }
+
+    /// The bytes of the page, to be written.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8; GHCB_SIZE] {
+        &mut self.0
+    }
+
+    /// The value of `field`, valid or not.
+    pub fn get(&self, field: GhcbField) -> u64 {
+        let bytes = &self.0[field.offset()..][..field.size()];
+
+        match bytes.try_into() {
+            Ok(qword) => u64::from_le_bytes(qword),
+            Err(_) => bytes[0].into(),
+        }
+    }
+
+    /// The value of `field`, or None if it is not valid.
+    pub fn get_valid(&self, field: GhcbField) -> Option<u64> {
+        match self.is_valid(field) {
+            true => Some(self.get(field)),
+            false => None,
+        }
+    }
+
+    /// Write `value` to `field`, and mark the field valid. Only the low
+    /// byte of `value` is written to [GhcbField::Cpl].
+    pub fn set(&mut self, field: GhcbField, value: u64) {
+        let size = field.size();
+        self.0[field.offset()..][..size].copy_from_slice(&value.to_le_bytes()[..size]);
+
+        let bit = field.valid_bit();
+        self.0[VALID_BITMAP_OFFSET + bit / 8] |= 1 << (bit % 8);
+    }
+
+    /// Whether `field` is marked valid.
+    pub fn is_valid(&self, field: GhcbField) -> bool {
+        let bit = field.valid_bit();
+        self.0[VALID_BITMAP_OFFSET + bit / 8] & (1 << (bit % 8)) != 0
+    }
+
+    /// Mark every field invalid, as before a new request.
+    pub fn invalidate(&mut self) {
+        self.0[VALID_BITMAP_OFFSET..][..16].fill(0);
+    }
+
+    /// Set the exit code and information of a request, marking them valid.
+    pub fn set_exit(&mut self, code: impl Into<u64>, info1: u64, info2: u64) {
+        self.set(GhcbField::SwExitCode, code.into());
+        self.set(GhcbField::SwExitInfo1, info1);
+        self.set(GhcbField::SwExitInfo2, info2);
+    }
+
+    /// The shared buffer, e.g. for the scratch area of a request.
+    pub fn shared_buffer(&self) -> &[u8] {
+        &self.0[SHARED_BUFFER_OFFSET..][..SHARED_BUFFER_SIZE]
+    }
+
+    /// The shared buffer, to be written.
+    pub fn shared_buffer_mut(&mut self) -> &mut [u8] {
+        &mut self.0[SHARED_BUFFER_OFFSET..][..SHARED_BUFFER_SIZE]
+    }
+
+    /// The GHCB protocol version the GHCB is used with.
+    pub fn protocol_version(&self) -> u16 {
+        u16::from_le_bytes([
+            self.0[PROTOCOL_VERSION_OFFSET],
+            self.0[PROTOCOL_VERSION_OFFSET + 1],
+        ])
+    }
+
+    /// Use the GHCB with the GHCB protocol `version`.
+    pub fn set_protocol_version(&mut self, version: u16) {
+        self.0[PROTOCOL_VERSION_OFFSET..][..2].copy_from_slice(&version.to_le_bytes());
+    }
+
+    /// The usage of the GHCB, [GHCB_USAGE_STANDARD] for the standard layout.
+    pub fn usage(&self) -> u32 {
+        let mut usage: [u8; 4] = [0; 4];
+        usage.copy_from_slice(&self.0[USAGE_OFFSET..][..4]);
+        u32::from_le_bytes(usage)
+    }
+
+    /// Set the usage of the GHCB.
+    pub fn set_usage(&mut self, usage: u32) {
+        self.0[USAGE_OFFSET..][..4].copy_from_slice(&usage.to_le_bytes());
+    }
+}
+
+impl Default for Ghcb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Ghcb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ghcb")
+            .field("protocol_version", &self.protocol_version())
+            .field("usage", &self.usage())
+            .field("valid", &ValidFields(self))
+            .finish()
+    }
+}
+
+/// The valid fields of a GHCB, for [Debug](fmt::Debug).
+struct ValidFields<'a>(&'a Ghcb);
+
+impl fmt::Debug for ValidFields<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = f.debug_map();
+        for field in GhcbField::ALL.iter() {
+            if let Some(value) = self.0.get_valid(*field) {
+                fields.entry(field, &format_args!("{value:#x}"));
+            }
+        }
+
+        fields.finish()
+    }
+}
+
+/// The exit codes of the SW_EXITCODE field
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u64)]
+pub enum ExitCode {
+    /// Read of DR7.
+    Dr7Read = 0x27,
+    /// Write of DR7.
+    Dr7Write = 0x37,
+    /// RDTSC.
+    Rdtsc = 0x6e,
+    /// RDPMC.
+    Rdpmc = 0x6f,
+    /// CPUID.
+    Cpuid = 0x72,
+    /// INVD.
+    Invd = 0x76,
+    /// IN or OUT.
+    Ioio = 0x7b,
+    /// RDMSR or WRMSR.
+    Msr = 0x7c,
+    /// VMMCALL.
+    Vmmcall = 0x81,
+    /// RDTSCP.
+    Rdtscp = 0x87,
+    /// WBINVD.
+    Wbinvd = 0x89,
+    /// MONITOR.
+    Monitor = 0x8a,
+    /// MWAIT.
+    Mwait = 0x8b,
+    /// A nested page fault.
+    Npf = 0x400,
+    /// A read of MMIO.
+    MmioRead = 0x8000_0001,
+    /// A write of MMIO.
+    MmioWrite = 0x8000_0002,
+    /// The end of an NMI handler.
+    NmiComplete = 0x8000_0003,
+    /// An AP entering the HLT loop.
+    ApHltLoop = 0x8000_0004,
+    /// The AP jump table.
+    ApJumpTable = 0x8000_0005,
+    /// A page state change.
+    PageStateChange = 0x8000_0010,
+    /// An SNP guest request.
+    GuestRequest = 0x8000_0011,
+    /// An SNP extended guest request.
+    ExtGuestRequest = 0x8000_0012,
+    /// The creation of an AP.
+    ApCreation = 0x8000_0013,
+    /// The hypervisor doorbell page.
+    HvDoorbellPage = 0x8000_0014,
+    /// A hypervisor IPI.
+    HvIpi = 0x8000_0015,
+    /// The hypervisor timer.
+    HvTimer = 0x8000_0016,
+    /// The APIC IDs of the vCPUs.
+    GetApicIds = 0x8000_0017,
+    /// Running a VMPL.
+    RunVmpl = 0x8000_0018,
+    /// The features the hypervisor supports.
+    HvFeatures = 0x8000_fffd,
+    /// A termination request.
+    Termination = 0x8000_fffe,
+    /// An unsupported event.
+    Unsupported = 0x8000_ffff,
+}
+
+impl From<ExitCode> for u64 {
+    fn from(value: ExitCode) -> Self {
+        value as u64
+    }
+}
+
+impl TryFrom<u64> for ExitCode {
+    type Error = u64;
+
+    fn try_from(value: u64) -> Result<Self, u64> {
+        Ok(match value {
+            0x27 => Self::Dr7Read,
+            0x37 => Self::Dr7Write,
+            0x6e => Self::Rdtsc,
+            0x6f => Self::Rdpmc,
+            0x72 => Self::Cpuid,
+            0x76 => Self::Invd,
+            0x7b => Self::Ioio,
+            0x7c => Self::Msr,
+            0x81 => Self::Vmmcall,
+            0x87 => Self::Rdtscp,
+            0x89 => Self::Wbinvd,
+            0x8a => Self::Monitor,
+            0x8b => Self::Mwait,
+            0x400 => Self::Npf,
+            0x8000_0001 => Self::MmioRead,
+            0x8000_0002 => Self::MmioWrite,
+            0x8000_0003 => Self::NmiComplete,
+            0x8000_0004 => Self::ApHltLoop,
+            0x8000_0005 => Self::ApJumpTable,
+            0x8000_0010 => Self::PageStateChange,
+            0x8000_0011 => Self::GuestRequest,
+            0x8000_0012 => Self::ExtGuestRequest,
+            0x8000_0013 => Self::ApCreation,
+            0x8000_0014 => Self::HvDoorbellPage,
+            0x8000_0015 => Self::HvIpi,
+            0x8000_0016 => Self::HvTimer,
+            0x8000_0017 => Self::GetApicIds,
+            0x8000_0018 => Self::RunVmpl,
+            0x8000_fffd => Self::HvFeatures,
+            0x8000_fffe => Self::Termination,
+            0x8000_ffff => Self::Unsupported,
+            _ => return Err(value),
+        })
+    }
+}
+
+bitfield! {
+    /// The features the hypervisor supports, as answered to a hypervisor
+    /// feature request.
+    ///
+    /// | Bit(s) | Name                     | Description                                           |
+    /// |--------|--------------------------|-------------------------------------------------------|
+    /// | 0      | SNP                      | SEV-SNP is supported.                                 |
+    /// | 1      | AP_CREATION              | The AP creation event is supported.                   |
+    /// | 2      | RESTRICTED_INJECTION     | Restricted injection is supported.                    |
+    /// | 3      | RESTRICTED_INJECTION_TMR | Restricted injection of the timer is supported.       |
+    /// | 4      | APIC_ID_LIST             | The APIC ID list event is supported.                  |
+    /// | 5      | MULTI_VMPL               | Running guests at multiple VMPLs is supported.        |
+    #[derive(Default, Clone, Copy, PartialEq, Eq)]
+    pub struct HvFeatures(u64);
+    impl Debug;
+    /// SNP field: SEV-SNP is supported.
+    pub snp, _: 0;
+    /// AP_CREATION field: the AP creation event is supported.
+    pub ap_creation, _: 1;
+    /// RESTRICTED_INJECTION field: restricted injection is supported.
+    pub restricted_injection, _: 2;
+    /// RESTRICTED_INJECTION_TMR field: restricted injection of the timer
+    /// is supported.
+    pub restricted_injection_timer, _: 3;
+    /// APIC_ID_LIST field: the APIC ID list event is supported.
+    pub apic_id_list, _: 4;
+    /// MULTI_VMPL field: running guests at multiple VMPLs is supported.
+    pub multi_vmpl, _: 5;
+}
+
+impl From<u64> for HvFeatures {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<HvFeatures> for u64 {
+    fn from(value: HvFeatures) -> Self {
+        value.0
+    }
+}
+
+/// The registers a CPUID request of the MSR protocol reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum CpuidRegister {
+    /// EAX.
+    Eax = 0,
+    /// EBX.
+    Ebx = 1,
+    /// ECX.
+    Ecx = 2,
+    /// EDX.
+    Edx = 3,
+}
+
+impl CpuidRegister {
+    fn from_bits(bits: u64) -> Self {
+        match bits & 0x3 {
+            0 => Self::Eax,
+            1 => Self::Ebx,
+            2 => Self::Ecx,
+            _ => Self::Edx,
+        }
+    }
+}
+
+/// The operations of a page state change request of the MSR protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum PageState {
+    /// Make the page private.
+    Private = 1,
+    /// Make the page shared.
+    Shared = 2,
+}
+
+/// The requests and responses of the GHCB MSR protocol, as written to and
+/// read from the GHCB MSR
+///
+/// Bits 11:0 of the MSR hold the type of the request or response, and the
+/// upper bits its data. Any other value of the MSR is the GPA of a GHCB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MsrProtocol {
+    /// The SEV information the hypervisor provides (0x001).
+    SevInfo {
+        /// The latest GHCB protocol version supported.
+        max_version: u16,
+        /// The earliest GHCB protocol version supported.
+        min_version: u16,
+        /// The position of the encryption bit in page table entries.
+        c_bit: u8,
+    },
+
+    /// A request for the SEV information (0x002).
+    SevInfoRequest,
+
+    /// A request for a register of a CPUID function (0x004).
+    CpuidRequest {
+        /// The register requested.
+        register: CpuidRegister,
+        /// The CPUID function.
+        function: u32,
+    },
+
+    /// A register of a CPUID function (0x005).
+    CpuidResponse {
+        /// The register returned.
+        register: CpuidRegister,
+        /// The value of the register.
+        value: u32,
+    },
+
+    /// A request to park the AP until it is woken up (0x006).
+    ApResetHoldRequest,
+
+    /// The AP was woken up (0x007). The result is non-zero on success.
+    ApResetHoldResponse {
+        /// The result of the request.
+        result: u64,
+    },
+
+    /// A request for the GFN of the GHCB the hypervisor prefers (0x010).
+    PreferredGhcbRequest,
+
+    /// The GFN of the GHCB the hypervisor prefers (0x011), all ones if it
+    /// has no preference.
+    PreferredGhcbResponse {
+        /// The GFN of the GHCB.
+        gfn: u64,
+    },
+
+    /// A request to register the GHCB at `gfn` (0x012).
+    RegisterGhcbRequest {
+        /// The GFN of the GHCB.
+        gfn: u64,
+    },
+
+    /// The GHCB registered (0x013), all ones if the registration failed.
+    RegisterGhcbResponse {
+        /// The GFN of the GHCB.
+        gfn: u64,
+    },
+
+    /// A request to change the state of the page at `gfn` (0x014).
+    PageStateRequest {
+        /// The GFN of the page.
+        gfn: u64,
+        /// The state to change the page to.
+        state: PageState,
+    },
+
+    /// The page state changed (0x015), unless the error is non-zero.
+    PageStateResponse {
+        /// The error code of the change.
+        error: u32,
+    },
+
+    /// A request for the features the hypervisor supports (0x080).
+    HvFeaturesRequest,
+
+    /// The features the hypervisor supports (0x081).
+    HvFeaturesResponse {
+        /// The features.
+        features: HvFeatures,
+    },
+
+    /// A request to terminate the guest (0x100).
+    TerminationRequest {
+        /// The set of reason codes, 0 for the GHCB specification's own.
+        reason_set: u8,
+        /// The reason code.
+        reason_code: u8,
+    },
+}
+
+impl MsrProtocol {
+    /// The type of the request or response, in bits 11:0 of the MSR.
+    pub fn info(&self) -> u16 {
+        match self {
+            Self::SevInfo { .. } => 0x001,
+            Self::SevInfoRequest => 0x002,
+            Self::CpuidRequest { .. } => 0x004,
+            Self::CpuidResponse { .. } => 0x005,
+            Self::ApResetHoldRequest => 0x006,
+            Self::ApResetHoldResponse { .. } => 0x007,
+            Self::PreferredGhcbRequest => 0x010,
+            Self::PreferredGhcbResponse { .. } => 0x011,
+            Self::RegisterGhcbRequest { .. } => 0x012,
+            Self::RegisterGhcbResponse { .. } => 0x013,
+            Self::PageStateRequest { .. } => 0x014,
+            Self::PageStateResponse { .. } => 0x015,
+            Self::HvFeaturesRequest => 0x080,
+            Self::HvFeaturesResponse { .. } => 0x081,
+            Self::TerminationRequest { .. } => 0x100,
+        }
+    }
+}
+
+impl From<MsrProtocol> for u64 {
+    fn from(value: MsrProtocol) -> Self {
+        let data: u64 = match value {
+            MsrProtocol::SevInfo {
+                max_version,
+                min_version,
+                c_bit,
+            } => {
+                (u64::from(max_version) << 48)
+                    | (u64::from(min_version) << 32)
+                    | (u64::from(c_bit) << 24)
+            }
+            MsrProtocol::CpuidRequest { register, function } => {
+                (u64::from(function) << 32) | ((register as u64) << 30)
+            }
+            MsrProtocol::CpuidResponse { register, value } => {
+                (u64::from(value) << 32) | ((register as u64) << 30)
+            }
+            MsrProtocol::ApResetHoldResponse { result } => result << 12,
+            MsrProtocol::PreferredGhcbResponse { gfn }
+            | MsrProtocol::RegisterGhcbRequest { gfn }
+            | MsrProtocol::RegisterGhcbResponse { gfn } => gfn << 12,
+            MsrProtocol::PageStateRequest { gfn, state } => {
+                ((state as u64) << 52) | ((gfn & 0xff_ffff_ffff) << 12)
+            }
+            MsrProtocol::PageStateResponse { error } => u64::from(error) << 32,
+            MsrProtocol::HvFeaturesResponse { features } => u64::from(features) << 12,
+            MsrProtocol::TerminationRequest {
+                reason_set,
+                reason_code,
+            } => (u64::from(reason_code) << 16) | (u64::from(reason_set & 0xf) << 12),
+            MsrProtocol::SevInfoRequest
+            | MsrProtocol::ApResetHoldRequest
+            | MsrProtocol::PreferredGhcbRequest
+            | MsrProtocol::HvFeaturesRequest => 0,
+        };
+
+        data | u64::from(value.info())
+    }
+}
+
+impl TryFrom<u64> for MsrProtocol {
+    type Error = u64;
+
+    /// Decode the value of the GHCB MSR, failing with the value if it is
+    /// not a request or response of the MSR protocol.
+    fn try_from(value: u64) -> Result<Self, u64> {
+        Ok(match value & 0xfff {
+            0x001 => Self::SevInfo {
+                max_version: (value >> 48) as u16,
+                min_version: (value >> 32) as u16,
+                c_bit: (value >> 24) as u8,
+            },
+            0x002 => Self::SevInfoRequest,
+            0x004 => Self::CpuidRequest {
+                register: CpuidRegister::from_bits(value >> 30),
+                function: (value >> 32) as u32,
+            },
+            0x005 => Self::CpuidResponse {
+                register: CpuidRegister::from_bits(value >> 30),
+                value: (value >> 32) as u32,
+            },
+            0x006 => Self::ApResetHoldRequest,
+            0x007 => Self::ApResetHoldResponse {
+                result: value >> 12,
+            },
+            0x010 => Self::PreferredGhcbRequest,
+            0x011 => Self::PreferredGhcbResponse { gfn: value >> 12 },
+            0x012 => Self::RegisterGhcbRequest { gfn: value >> 12 },
+            0x013 => Self::RegisterGhcbResponse { gfn: value >> 12 },
+            0x014 => Self::PageStateRequest {
+                gfn: (value >> 12) & 0xff_ffff_ffff,
+                state: match (value >> 52) & 0xf {
+                    1 => PageState::Private,
+                    2 => PageState::Shared,
+                    _ => return Err(value),
+                },
+            },
+            0x015 => Self::PageStateResponse {
+                error: (value >> 32) as u32,
+            },
+            0x080 => Self::HvFeaturesRequest,
+            0x081 => Self::HvFeaturesResponse {
+                features: (value >> 12).into(),
+            },
+            0x100 => Self::TerminationRequest {
+                reason_set: ((value >> 12) & 0xf) as u8,
+                reason_code: (value >> 16) as u8,
+            },
+            _ => return Err(value),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let mut ghcb = Ghcb::new();
+
+        for (i, field) in GhcbField::ALL.iter().copied().enumerate() {
+            assert!(!ghcb.is_valid(field));
+            ghcb.set(field, 0x0102_0304_0506_0700 | i as u64);
+        }
+
+        for (i, field) in GhcbField::ALL.iter().copied().enumerate() {
+            let expected = match field {
+                GhcbField::Cpl => i as u64,
+                _ => 0x0102_0304_0506_0700 | i as u64,
+            };
+            assert_eq!(ghcb.get_valid(field), Some(expected));
+        }
+
+        assert_eq!(ghcb.as_bytes()[0x1f8], 5);
+        assert_eq!(ghcb.as_bytes()[0x1ff], 1);
+
+        // RAX is the 0x3f-th quadword, CPL lies in the 0x19-th.
+        assert_eq!(ghcb.as_bytes()[0x3f0 + 0x3f / 8] & (1 << (0x3f % 8)), 0x80);
+        assert_eq!(ghcb.as_bytes()[0x3f0 + 0x19 / 8] & (1 << (0x19 % 8)), 0x02);
+
+        ghcb.invalidate();
+        assert_eq!(ghcb.get_valid(GhcbField::Rax), None);
+        assert_eq!(ghcb.get(GhcbField::Rax), 0x0102_0304_0506_0705);
+    }
+
+    #[test]
+    fn test_fields_disjoint() {
+        let mut fields = GhcbField::ALL;
+        fields.sort_by_key(|field| field.offset());
+        assert_eq!(fields, GhcbField::ALL);
+
+        for pair in fields.windows(2) {
+            assert!(pair[0].offset() + pair[0].size() <= pair[1].offset());
+        }
+
+        let last = fields[fields.len() - 1];
+        assert!(last.offset() + last.size() <= SHARED_BUFFER_OFFSET);
+        assert!(VALID_BITMAP_OFFSET + 16 <= GhcbField::X87StateGpa.offset());
+    }
+
+    #[test]
+    fn test_page() {
+        let mut ghcb = Ghcb::default();
+        ghcb.set_protocol_version(GHCB_PROTOCOL_MAX);
+        ghcb.set_usage(GHCB_USAGE_STANDARD);
+        ghcb.set_exit(ExitCode::GuestRequest, 0x1000, 0x2000);
+        ghcb.shared_buffer_mut()[0] = 0xaa;
+
+        assert_eq!(ghcb.protocol_version(), 2);
+        assert_eq!(ghcb.as_bytes()[0xffa..0xffc], [2, 0]);
+        assert_eq!(ghcb.usage(), 0);
+        assert_eq!(ghcb.as_bytes()[0x800], 0xaa);
+        assert_eq!(ghcb.shared_buffer().len(), 0x7f0);
+        assert_eq!(
+            ExitCode::try_from(ghcb.get(GhcbField::SwExitCode)),
+            Ok(ExitCode::GuestRequest)
+        );
+        assert_eq!(ghcb.get_valid(GhcbField::SwExitInfo2), Some(0x2000));
+        assert_eq!(core::mem::align_of::<Ghcb>(), GHCB_SIZE);
+
+        let copy = Ghcb::from_bytes(*ghcb.as_bytes());
+        assert_eq!(copy, ghcb);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        for code in [0x27, 0x72, 0x400, 0x8000_0011, 0x8000_ffff] {
+            assert_eq!(u64::from(ExitCode::try_from(code).unwrap()), code);
+        }
+
+        assert_eq!(ExitCode::try_from(0x8000_0006), Err(0x8000_0006));
+    }
+
+    #[test]
+    fn test_msr_protocol() {
+        let messages = [
+            (
+                MsrProtocol::SevInfo {
+                    max_version: 2,
+                    min_version: 1,
+                    c_bit: 51,
+                },
+                0x0002_0001_3300_0001,
+            ),
+            (MsrProtocol::SevInfoRequest, 0x002),
+            (
+                MsrProtocol::CpuidRequest {
+                    register: CpuidRegister::Ebx,
+                    function: 0x8000_001f,
+                },
+                0x8000_001f_4000_0004,
+            ),
+            (
+                MsrProtocol::CpuidResponse {
+                    register: CpuidRegister::Edx,
+                    value: 0x1234,
+                },
+                0x0000_1234_c000_0005,
+            ),
+            (
+                MsrProtocol::RegisterGhcbRequest { gfn: 0x12345 },
+                0x1234_5012,
+            ),
+            (
+                MsrProtocol::PageStateRequest {
+                    gfn: 0x100,
+                    state: PageState::Shared,
+                },
+                0x0020_0000_0010_0014,
+            ),
+            (
+                MsrProtocol::PageStateResponse { error: 3 },
+                0x0000_0003_0000_0015,
+            ),
+            (
+                MsrProtocol::HvFeaturesResponse {
+                    features: 0x3.into(),
+                },
+                0x3081,
+            ),
+            (
+                MsrProtocol::TerminationRequest {
+                    reason_set: 0,
+                    reason_code: 1,
+                },
+                0x10100,
+            ),
+        ];
+
+        for (message, value) in messages {
+            assert_eq!(u64::from(message), value, "{:?}", message);
+            assert_eq!(MsrProtocol::try_from(value), Ok(message));
+        }
+
+        assert_eq!(MsrProtocol::try_from(0x1000), Err(0x1000));
+        assert_eq!(
+            MsrProtocol::try_from(0x0030_0000_0010_0014),
+            Err(0x0030_0000_0010_0014)
+        );
+
+        if let Ok(MsrProtocol::HvFeaturesResponse { features }) = MsrProtocol::try_from(0x3081) {
+            assert!(features.snp() && features.ap_creation() && !features.multi_vmpl());
+        }
+    }
+}
@@ -0,0 +1,289 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Caching of keys derived by the AMD Secure Processor.
+//!
+//! Requesting a derived key is a round trip through the VMM and the AMD
+//! Secure Processor. Guest services which need the same key repeatedly can
+//! use a [DerivedKeyManager] to request it once and serve later lookups
+//! from memory.
+
+use super::types::{DerivedKey, GuestFieldSelect};
+
+use crate::{error::UserApiError, firmware::host::TcbVersion};
+
+#[cfg(target_os = "linux")]
+use super::Firmware;
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use zeroize::Zeroizing;
+
+/// Size (in bytes) of a key derived by the AMD Secure Processor.
+pub const DERIVED_KEY_LEN: usize = 32;
+
+/// The parameters a derived key is mixed from. Two requests with the same
+/// parameters always yield the same key for a given guest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct DerivedKeyId {
+    root_key_select: u32,
+    guest_field_select: u64,
+    vmpl: u32,
+    guest_svn: u32,
+    tcb_version: u64,
+}
+
+impl From<&DerivedKey> for DerivedKeyId {
+    fn from(value: &DerivedKey) -> Self {
+        Self {
+            root_key_select: value.get_root_key_select(),
+            guest_field_select: value.guest_field_select.0,
+            vmpl: value.vmpl,
+            guest_svn: value.guest_svn,
+            tcb_version: value.tcb_version,
+        }
+    }
+}
+
+/// A derived key held in memory. The key material is overwritten with
+/// zeroes when the value is dropped.
+pub struct CachedKey(Zeroizing<[u8; DERIVED_KEY_LEN]>);
+
+impl CachedKey {
+    /// Get an immutable reference to the key material.
+    pub fn as_bytes(&self) -> &[u8; DERIVED_KEY_LEN] {
+        &self.0
+    }
+
+    /// Copy the key material out of the cache, into a copy which is wiped
+    /// when dropped as well.
+    pub fn to_zeroizing(&self) -> Zeroizing<[u8; DERIVED_KEY_LEN]> {
+        self.0.clone()
+    }
+}
+
+impl std::fmt::Debug for CachedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CachedKey(<redacted>)")
+    }
+}
+
+/// A cache of derived keys, indexed by the [GuestFieldSelect], VMPL, guest
+/// SVN and TCB version (and root key) which were mixed into each key.
+///
+/// Keys which mix in the TCB version are tied to the platform's reported
+/// TCB. Once the reported TCB changes (e.g. after a firmware update and
+/// SNP_COMMIT on the host), call [observe_tcb](Self::observe_tcb) with the
+/// new value to rotate the cache: every cached key is zeroized and the
+/// next lookup goes back to the firmware.
+///
+/// # Example:
+/// ```ignore
+/// let mut fw: Firmware = Firmware::open().unwrap();
+/// let mut keys: DerivedKeyManager = DerivedKeyManager::new();
+///
+/// let mut fields: GuestFieldSelect = GuestFieldSelect::default();
+/// fields.set_measurement(1);
+///
+/// let request: DerivedKey = DerivedKey::new(false, fields, 0, 0, 0);
+///
+/// // Only the first call reaches the AMD Secure Processor.
+/// let key: Zeroizing<[u8; 32]> = keys.get(&mut fw, None, request).unwrap().to_zeroizing();
+/// let again: &CachedKey = keys.get(&mut fw, None, request).unwrap();
+/// assert_eq!(&*key, again.as_bytes());
+/// ```
+#[derive(Debug, Default)]
+pub struct DerivedKeyManager {
+    keys: HashMap<DerivedKeyId, CachedKey>,
+    reported_tcb: Option<TcbVersion>,
+}
+
+impl DerivedKeyManager {
+    /// Create an empty key cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of keys currently held in the cache.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns true when no keys are cached.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Check if the key described by `request` is already cached.
+    pub fn contains(&self, request: &DerivedKey) -> bool {
+        self.keys.contains_key(&request.into())
+    }
+
+    /// The reported TCB the cached keys are bound to, if one was observed.
+    pub fn reported_tcb(&self) -> Option<TcbVersion> {
+        self.reported_tcb
+    }
+
+    /// Fetch the key described by `request` from the cache, invoking
+    /// `derive` to obtain it only when it has not been cached yet.
+    ///
+    /// The key is borrowed from the cache rather than copied out of it: use
+    /// [CachedKey::to_zeroizing] for a copy which outlives the borrow.
+    pub fn get_or_derive<F>(
+        &mut self,
+        request: DerivedKey,
+        derive: F,
+    ) -> Result<&CachedKey, UserApiError>
+    where
+        F: FnOnce(DerivedKey) -> Result<Zeroizing<[u8; DERIVED_KEY_LEN]>, UserApiError>,
+    {
+        match self.keys.entry((&request).into()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(CachedKey(derive(request)?))),
+        }
+    }
+
+    /// Fetch the key described by `request`, requesting it from the AMD
+    /// Secure Processor through `firmware` on a cache miss. The
+    /// `message_version` will default to `1` if `None` is specified.
+    #[cfg(target_os = "linux")]
+    pub fn get(
+        &mut self,
+        firmware: &mut Firmware,
+        message_version: Option<u8>,
+        request: DerivedKey,
+    ) -> Result<&CachedKey, UserApiError> {
+        self.get_or_derive(request, |request| {
+            firmware
+                .get_derived_key(message_version, request)
+                .map(Zeroizing::new)
+        })
+    }
+
+    /// Record the platform's current reported TCB. If it differs from the
+    /// previously observed value, every cached key is zeroized and dropped.
+    ///
+    /// Returns `true` if the cache was rotated.
+    pub fn observe_tcb(&mut self, reported_tcb: TcbVersion) -> bool {
+        let rotated = match self.reported_tcb {
+            Some(previous) => previous != reported_tcb,
+            None => false,
+        };
+
+        if rotated {
+            self.clear();
+        }
+
+        self.reported_tcb = Some(reported_tcb);
+
+        rotated
+    }
+
+    /// Read the reported TCB from a fresh attestation report and rotate the
+    /// cache if it changed. See [observe_tcb](Self::observe_tcb).
+    #[cfg(target_os = "linux")]
+    pub fn refresh_tcb(
+        &mut self,
+        firmware: &mut Firmware,
+        message_version: Option<u8>,
+    ) -> Result<bool, UserApiError> {
+        let report = firmware.get_report(message_version, None, None)?;
+
+        Ok(self.observe_tcb(report.reported_tcb))
+    }
+
+    /// Zeroize and drop the key described by `request`, if it is cached.
+    pub fn evict(&mut self, request: &DerivedKey) -> bool {
+        self.keys.remove(&request.into()).is_some()
+    }
+
+    /// Zeroize and drop every key derived with the given field selection.
+    pub fn evict_fields(&mut self, guest_field_select: GuestFieldSelect) {
+        self.keys
+            .retain(|id, _| id.guest_field_select != guest_field_select.0);
+    }
+
+    /// Zeroize and drop every cached key.
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::Cell;
+
+    fn request(vmpl: u32, tcb_version: u64) -> DerivedKey {
+        DerivedKey::new(false, GuestFieldSelect(0b100001), vmpl, 0, tcb_version)
+    }
+
+    #[test]
+    fn test_cache_hit() {
+        let calls: Cell<u8> = Cell::new(0);
+        let mut manager: DerivedKeyManager = DerivedKeyManager::new();
+
+        for _ in 0..3 {
+            let key = manager
+                .get_or_derive(request(1, 7), |_| {
+                    calls.set(calls.get() + 1);
+                    Ok(Zeroizing::new([calls.get(); DERIVED_KEY_LEN]))
+                })
+                .unwrap();
+
+            assert_eq!(key.as_bytes(), &[1u8; DERIVED_KEY_LEN]);
+            assert_eq!(*key.to_zeroizing(), [1u8; DERIVED_KEY_LEN]);
+        }
+
+        assert_eq!(calls.get(), 1);
+        assert_eq!(manager.len(), 1);
+    }
+
+    #[test]
+    fn test_distinct_parameters() {
+        let mut manager: DerivedKeyManager = DerivedKeyManager::new();
+
+        manager
+            .get_or_derive(request(1, 7), |_| Ok(Zeroizing::new([1; DERIVED_KEY_LEN])))
+            .unwrap();
+        manager
+            .get_or_derive(request(2, 7), |_| Ok(Zeroizing::new([2; DERIVED_KEY_LEN])))
+            .unwrap();
+        manager
+            .get_or_derive(request(1, 8), |_| Ok(Zeroizing::new([3; DERIVED_KEY_LEN])))
+            .unwrap();
+
+        assert_eq!(manager.len(), 3);
+        assert!(manager.evict(&request(2, 7)));
+        assert!(!manager.contains(&request(2, 7)));
+        assert_eq!(manager.len(), 2);
+    }
+
+    #[test]
+    fn test_error_not_cached() {
+        let mut manager: DerivedKeyManager = DerivedKeyManager::new();
+
+        assert!(manager
+            .get_or_derive(request(1, 7), |_| Err(UserApiError::Unknown))
+            .is_err());
+        assert!(manager.is_empty());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let mut manager: DerivedKeyManager = DerivedKeyManager::new();
+
+        assert!(!manager.observe_tcb(TcbVersion::new(3, 0, 8, 115)));
+
+        manager
+            .get_or_derive(request(1, 7), |_| Ok(Zeroizing::new([1; DERIVED_KEY_LEN])))
+            .unwrap();
+
+        assert!(!manager.observe_tcb(TcbVersion::new(3, 0, 8, 115)));
+        assert_eq!(manager.len(), 1);
+
+        assert!(manager.observe_tcb(TcbVersion::new(3, 0, 10, 169)));
+        assert!(manager.is_empty());
+        assert_eq!(manager.reported_tcb(), Some(TcbVersion::new(3, 0, 10, 169)));
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The binary layout of attestation reports.
+//!
+//! Each field of a report, as the SEV-SNP Firmware ABI specification names
+//! it, is a [Field] constant with its offset and size. The maps returned by
+//! [fields] list the fields of a report of a given version in order,
+//! reserved ranges included, so that they cover every byte of the report.
+//!
+//! Version 3 reports added the CPUID family, model and stepping after
+//! REPORTED_TCB, and version 5 reports the launch and current mitigation
+//! vectors after LAUNCH_TCB. Version 4 reports are laid out as version 3.
+//!
+//! # Example:
+//! ```ignore
+//! let bytes = report.to_bytes();
+//! let measurement = &bytes[layout::MEASUREMENT.range()];
+//!
+//! for field in layout::fields(report.version) {
+//!     println!("{:#05x} {:>3} {}", field.offset, field.size, field.name);
+//! }
+//! ```
+
+use super::AttestationReport;
+
+use core::ops::Range;
+
+/// A field of an attestation report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the field in the SEV-SNP Firmware ABI specification, or
+    /// `RESERVED`.
+    pub name: &'static str,
+
+    /// The offset of the field in the report, in bytes.
+    pub offset: usize,
+
+    /// The size of the field, in bytes.
+    pub size: usize,
+}
+
+impl Field {
+    const fn new(name: &'static str, offset: usize, size: usize) -> Self {
+        Self { name, offset, size }
+    }
+
+    const fn reserved(offset: usize, end: usize) -> Self {
+        Self::new("RESERVED", offset, end - offset)
+    }
+
+    /// The bytes of the report the field spans.
+    pub const fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.size
+    }
+
+    /// Whether the field is reserved, and must be zero.
+    pub fn is_reserved(&self) -> bool {
+        self.name == "RESERVED"
+    }
+}
+
+/// Version number of the report.
+pub const VERSION: Field = Field::new("VERSION", 0x00, 4);
+/// The guest SVN.
+pub const GUEST_SVN: Field = Field::new("GUEST_SVN", 0x04, 4);
+/// The guest policy.
+pub const POLICY: Field = Field::new("POLICY", 0x08, 8);
+/// The family ID provided at launch.
+pub const FAMILY_ID: Field = Field::new("FAMILY_ID", 0x10, 16);
+/// The image ID provided at launch.
+pub const IMAGE_ID: Field = Field::new("IMAGE_ID", 0x20, 16);
+/// The VMPL the report was requested for.
+pub const VMPL: Field = Field::new("VMPL", 0x30, 4);
+/// The algorithm the report is signed with.
+pub const SIGNATURE_ALGO: Field = Field::new("SIGNATURE_ALGO", 0x34, 4);
+/// The current TCB.
+pub const CURRENT_TCB: Field = Field::new("CURRENT_TCB", 0x38, 8);
+/// Information about the platform.
+pub const PLATFORM_INFO: Field = Field::new("PLATFORM_INFO", 0x40, 8);
+/// Information about the key the report is signed with.
+pub const KEY_INFO: Field = Field::new("KEY_INFO", 0x48, 4);
+/// The data provided by the guest.
+pub const REPORT_DATA: Field = Field::new("REPORT_DATA", 0x50, 64);
+/// The measurement calculated at launch.
+pub const MEASUREMENT: Field = Field::new("MEASUREMENT", 0x90, 48);
+/// The data provided by the hypervisor at launch.
+pub const HOST_DATA: Field = Field::new("HOST_DATA", 0xC0, 32);
+/// The digest of the ID key which signed the ID block.
+pub const ID_KEY_DIGEST: Field = Field::new("ID_KEY_DIGEST", 0xE0, 48);
+/// The digest of the author key which certified the ID key.
+pub const AUTHOR_KEY_DIGEST: Field = Field::new("AUTHOR_KEY_DIGEST", 0x110, 48);
+/// The report ID of the guest.
+pub const REPORT_ID: Field = Field::new("REPORT_ID", 0x140, 32);
+/// The report ID of the guest's migration agent.
+pub const REPORT_ID_MA: Field = Field::new("REPORT_ID_MA", 0x160, 32);
+/// The TCB the VCEK which signed the report is derived from.
+pub const REPORTED_TCB: Field = Field::new("REPORTED_TCB", 0x180, 8);
+/// The CPUID family of the processor, since version 3.
+pub const CPUID_FAM_ID: Field = Field::new("CPUID_FAM_ID", 0x188, 1);
+/// The CPUID model of the processor, since version 3.
+pub const CPUID_MOD_ID: Field = Field::new("CPUID_MOD_ID", 0x189, 1);
+/// The CPUID stepping of the processor, since version 3.
+pub const CPUID_STEP: Field = Field::new("CPUID_STEP", 0x18A, 1);
+/// The identifier of the chip, unless masked.
+pub const CHIP_ID: Field = Field::new("CHIP_ID", 0x1A0, 64);
+/// The committed TCB.
+pub const COMMITTED_TCB: Field = Field::new("COMMITTED_TCB", 0x1E0, 8);
+/// The build number of the current firmware.
+pub const CURRENT_BUILD: Field = Field::new("CURRENT_BUILD", 0x1E8, 1);
+/// The minor version of the current firmware.
+pub const CURRENT_MINOR: Field = Field::new("CURRENT_MINOR", 0x1E9, 1);
+/// The major version of the current firmware.
+pub const CURRENT_MAJOR: Field = Field::new("CURRENT_MAJOR", 0x1EA, 1);
+/// The build number of the committed firmware.
+pub const COMMITTED_BUILD: Field = Field::new("COMMITTED_BUILD", 0x1EC, 1);
+/// The minor version of the committed firmware.
+pub const COMMITTED_MINOR: Field = Field::new("COMMITTED_MINOR", 0x1ED, 1);
+/// The major version of the committed firmware.
+pub const COMMITTED_MAJOR: Field = Field::new("COMMITTED_MAJOR", 0x1EE, 1);
+/// The current TCB when the guest was launched or imported.
+pub const LAUNCH_TCB: Field = Field::new("LAUNCH_TCB", 0x1F0, 8);
+/// The mitigation vector when the guest was launched, since version 5.
+pub const LAUNCH_MIT_VECTOR: Field = Field::new("LAUNCH_MIT_VECTOR", 0x1F8, 8);
+/// The current mitigation vector, since version 5.
+pub const CURRENT_MIT_VECTOR: Field = Field::new("CURRENT_MIT_VECTOR", 0x200, 8);
+/// The `R` component of the signature.
+pub const SIGNATURE_R: Field = Field::new("SIGNATURE_R", 0x2A0, 72);
+/// The `S` component of the signature.
+pub const SIGNATURE_S: Field = Field::new("SIGNATURE_S", 0x2E8, 72);
+
+/// The bytes the signature covers: every byte before it.
+pub const SIGNED: Range<usize> = 0..SIGNATURE_R.offset;
+
+/// The fields of a version 2 report.
+pub const V2: &[Field] = &[
+    VERSION,
+    GUEST_SVN,
+    POLICY,
+    FAMILY_ID,
+    IMAGE_ID,
+    VMPL,
+    SIGNATURE_ALGO,
+    CURRENT_TCB,
+    PLATFORM_INFO,
+    KEY_INFO,
+    Field::reserved(0x4C, 0x50),
+    REPORT_DATA,
+    MEASUREMENT,
+    HOST_DATA,
+    ID_KEY_DIGEST,
+    AUTHOR_KEY_DIGEST,
+    REPORT_ID,
+    REPORT_ID_MA,
+    REPORTED_TCB,
+    Field::reserved(0x188, 0x1A0),
+    CHIP_ID,
+    COMMITTED_TCB,
+    CURRENT_BUILD,
+    CURRENT_MINOR,
+    CURRENT_MAJOR,
+    Field::reserved(0x1EB, 0x1EC),
+    COMMITTED_BUILD,
+    COMMITTED_MINOR,
+    COMMITTED_MAJOR,
+    Field::reserved(0x1EF, 0x1F0),
+    LAUNCH_TCB,
+    Field::reserved(0x1F8, 0x2A0),
+    SIGNATURE_R,
+    SIGNATURE_S,
+    Field::reserved(0x330, AttestationReport::SIZE),
+];
+
+/// The fields of a version 3 or 4 report.
+pub const V3: &[Field] = &[
+    VERSION,
+    GUEST_SVN,
+    POLICY,
+    FAMILY_ID,
+    IMAGE_ID,
+    VMPL,
+    SIGNATURE_ALGO,
+    CURRENT_TCB,
+    PLATFORM_INFO,
+    KEY_INFO,
+    Field::reserved(0x4C, 0x50),
+    REPORT_DATA,
+    MEASUREMENT,
+    HOST_DATA,
+    ID_KEY_DIGEST,
+    AUTHOR_KEY_DIGEST,
+    REPORT_ID,
+    REPORT_ID_MA,
+    REPORTED_TCB,
+    CPUID_FAM_ID,
+    CPUID_MOD_ID,
+    CPUID_STEP,
+    Field::reserved(0x18B, 0x1A0),
+    CHIP_ID,
+    COMMITTED_TCB,
+    CURRENT_BUILD,
+    CURRENT_MINOR,
+    CURRENT_MAJOR,
+    Field::reserved(0x1EB, 0x1EC),
+    COMMITTED_BUILD,
+    COMMITTED_MINOR,
+    COMMITTED_MAJOR,
+    Field::reserved(0x1EF, 0x1F0),
+    LAUNCH_TCB,
+    Field::reserved(0x1F8, 0x2A0),
+    SIGNATURE_R,
+    SIGNATURE_S,
+    Field::reserved(0x330, AttestationReport::SIZE),
+];
+
+/// The fields of a version 5 report.
+pub const V5: &[Field] = &[
+    VERSION,
+    GUEST_SVN,
+    POLICY,
+    FAMILY_ID,
+    IMAGE_ID,
+    VMPL,
+    SIGNATURE_ALGO,
+    CURRENT_TCB,
+    PLATFORM_INFO,
+    KEY_INFO,
+    Field::reserved(0x4C, 0x50),
+    REPORT_DATA,
+    MEASUREMENT,
+    HOST_DATA,
+    ID_KEY_DIGEST,
+    AUTHOR_KEY_DIGEST,
+    REPORT_ID,
+    REPORT_ID_MA,
+    REPORTED_TCB,
+    CPUID_FAM_ID,
+    CPUID_MOD_ID,
+    CPUID_STEP,
+    Field::reserved(0x18B, 0x1A0),
+    CHIP_ID,
+    COMMITTED_TCB,
+    CURRENT_BUILD,
+    CURRENT_MINOR,
+    CURRENT_MAJOR,
+    Field::reserved(0x1EB, 0x1EC),
+    COMMITTED_BUILD,
+    COMMITTED_MINOR,
+    COMMITTED_MAJOR,
+    Field::reserved(0x1EF, 0x1F0),
+    LAUNCH_TCB,
+    LAUNCH_MIT_VECTOR,
+    CURRENT_MIT_VECTOR,
+    Field::reserved(0x208, 0x2A0),
+    SIGNATURE_R,
+    SIGNATURE_S,
+    Field::reserved(0x330, AttestationReport::SIZE),
+];
+
+/// The fields of a report of `version`, in order. Versions this crate does
+/// not know are laid out as the nearest version it knows.
+pub fn fields(version: u32) -> &'static [Field] {
+    match version.clamp(
+        AttestationReport::MIN_VERSION,
+        AttestationReport::MAX_VERSION,
+    ) {
+        2 => V2,
+        3 | 4 => V3,
+        _ => V5,
+    }
+}
+
+/// The field named `name` in a report of `version`, if it has one.
+pub fn field(version: u32, name: &str) -> Option<Field> {
+    fields(version)
+        .iter()
+        .find(|field| !field.is_reserved() && field.name == name)
+        .copied()
+}
@@ -0,0 +1,976 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The SEV-SNP guest message protocol, without the kernel driver.
+//!
+//! Guests talk to the AMD Secure Processor through guest messages: a
+//! request page the hypervisor hands to the firmware (e.g. through the
+//! SNP Guest Request event of the GHCB), and a response page the firmware
+//! fills in. Each page holds a 0x60 byte header followed by a payload
+//! encrypted with AES-256-GCM under one of the four VM Platform
+//! Communication Keys (VMPCKs) of the guest's secrets page:
+//!
+//! ```text
+//! 0x00  AUTHTAG       the GCM tag, in the first 16 bytes
+//! 0x20  MSG_SEQNO     the sequence number, also the GCM IV
+//! 0x30  ALGO          1: AES-256-GCM         ┐
+//! 0x31  HDR_VERSION   1                      │
+//! 0x32  HDR_SIZE      0x60                   │
+//! 0x34  MSG_TYPE      e.g. 5: report request │ authenticated
+//! 0x35  MSG_VERSION                          │ but not encrypted
+//! 0x36  MSG_SIZE      the payload size       │
+//! 0x3c  MSG_VMPCK     the VMPCK's index      ┘
+//! 0x60  payload
+//! ```
+//!
+//! Every request and response consumes a sequence number: requests carry
+//! odd ones and responses the following even ones, so that an IV is never
+//! used twice with a key. The firmware tracks the last sequence number of
+//! each VMPCK, starting at 0 when the guest launches.
+//!
+//! Where the `/dev/sev-guest` driver is unavailable, e.g. in unikernels or
+//! SVSMs, a [GuestChannel] implements the protocol over any
+//! [GuestTransport] the guest provides to exchange the pages with the
+//! firmware. Like the kernel driver, a channel refuses further requests
+//! once a request it sent fails, as the firmware and the channel may no
+//! longer agree on the sequence number.
+//!
+//! # Example:
+//! ```ignore
+//! struct Ghcb { /* ... */ }
+//!
+//! impl GuestTransport for Ghcb {
+//!     fn exchange(&mut self, request: &[u8; MESSAGE_SIZE], response: &mut [u8; MESSAGE_SIZE]) -> io::Result<()> {
+//!         // Issue the SNP Guest Request event with shared copies of the pages.
+//!     }
+//! }
+//!
+//! let vmpck = Vmpck::new(0, secrets_page.vmpck0);
+//! let mut channel = GuestChannel::new(ghcb, vmpck);
+//! let report = channel.get_report(report_data, 0)?;
+//! ```
+
+use crate::{
+    error::GuestMessageError,
+    firmware::guest::{AttestationReport, DerivedKey},
+    util::{LeReader, LeWriter},
+};
+
+use std::{convert::TryFrom, fmt};
+
+use zeroize::{Zeroize, Zeroizing};
+
+/// The size (in bytes) of a guest message.
+pub const MESSAGE_SIZE: usize = 0x1000;
+
+/// The size (in bytes) of the header of a guest message.
+pub const HEADER_SIZE: usize = 0x60;
+
+/// The largest payload (in bytes) a guest message holds.
+pub const MAX_PAYLOAD_SIZE: usize = MESSAGE_SIZE - HEADER_SIZE;
+
+/// AES-256-GCM, the only algorithm guest messages are encrypted with.
+const ALGO_AES_256_GCM: u8 = 1;
+
+const HEADER_VERSION: u8 = 1;
+
+/// The authenticated part of the header, from ALGO to its end.
+const AAD_OFFSET: usize = 0x30;
+
+const TAG_SIZE: usize = 16;
+
+/// The types of guest messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum MessageType {
+    /// A CPUID request.
+    CpuidReq = 1,
+
+    /// A CPUID response.
+    CpuidRsp = 2,
+
+    /// A key derivation request.
+    KeyReq = 3,
+
+    /// A key derivation response.
+    KeyRsp = 4,
+
+    /// An attestation report request.
+    ReportReq = 5,
+
+    /// An attestation report response.
+    ReportRsp = 6,
+
+    /// A guest export request.
+    ExportReq = 7,
+
+    /// A guest export response.
+    ExportRsp = 8,
+
+    /// A guest import request.
+    ImportReq = 9,
+
+    /// A guest import response.
+    ImportRsp = 10,
+
+    /// A guest absorb request.
+    AbsorbReq = 11,
+
+    /// A guest absorb response.
+    AbsorbRsp = 12,
+
+    /// A VMRK request.
+    VmrkReq = 13,
+
+    /// A VMRK response.
+    VmrkRsp = 14,
+
+    /// A guest absorb request without a migration agent.
+    AbsorbNomaReq = 15,
+
+    /// A guest absorb response without a migration agent.
+    AbsorbNomaRsp = 16,
+
+    /// A TSC information request.
+    TscInfoReq = 17,
+
+    /// A TSC information response.
+    TscInfoRsp = 18,
+}
+
+impl MessageType {
+    /// The type of the responses to requests of this type, or None if this
+    /// is a response type.
+    pub fn response(self) -> Option<Self> {
+        match (self as u8) % 2 {
+            1 => Self::try_from(self as u8 + 1).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(value: MessageType) -> Self {
+        value as u8
+    }
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, u8> {
+        Ok(match value {
+            1 => Self::CpuidReq,
+            2 => Self::CpuidRsp,
+            3 => Self::KeyReq,
+            4 => Self::KeyRsp,
+            5 => Self::ReportReq,
+            6 => Self::ReportRsp,
+            7 => Self::ExportReq,
+            8 => Self::ExportRsp,
+            9 => Self::ImportReq,
+            10 => Self::ImportRsp,
+            11 => Self::AbsorbReq,
+            12 => Self::AbsorbRsp,
+            13 => Self::VmrkReq,
+            14 => Self::VmrkRsp,
+            15 => Self::AbsorbNomaReq,
+            16 => Self::AbsorbNomaRsp,
+            17 => Self::TscInfoReq,
+            18 => Self::TscInfoRsp,
+            _ => return Err(value),
+        })
+    }
+}
+
+/// The header of a guest message
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MessageHeader {
+    /// The GCM tag of the message, in the first 16 bytes.
+    pub auth_tag: [u8; 32],
+
+    /// The sequence number of the message.
+    pub seqno: u64,
+
+    /// The algorithm the payload is encrypted with.
+    pub algo: u8,
+
+    /// The version of the header.
+    pub header_version: u8,
+
+    /// The size of the header in bytes.
+    pub header_size: u16,
+
+    /// The type of the message, which [MessageType] names.
+    pub msg_type: u8,
+
+    /// The version of the message type.
+    pub msg_version: u8,
+
+    /// The size of the payload in bytes.
+    pub msg_size: u16,
+
+    /// The index of the VMPCK the payload is encrypted with.
+    pub vmpck: u8,
+}
+
+impl MessageHeader {
+    /// Decode the header at the start of `message`.
+    pub fn from_bytes(message: &[u8; MESSAGE_SIZE]) -> Self {
+        let mut reader = LeReader::new(message);
+
+        let auth_tag = reader.bytes();
+        let seqno = reader.u64();
+        reader.bytes::<8>();
+        let algo = reader.u8();
+        let header_version = reader.u8();
+        let header_size = u16::from_le_bytes(reader.bytes());
+        let msg_type = reader.u8();
+        let msg_version = reader.u8();
+        let msg_size = u16::from_le_bytes(reader.bytes());
+        reader.u32();
+        let vmpck = reader.u8();
+
+        Self {
+            auth_tag,
+            seqno,
+            algo,
+            header_version,
+            header_size,
+            msg_type,
+            msg_version,
+            msg_size,
+            vmpck,
+        }
+    }
+
+    /// Encode the header at the start of `message`, zeroing its reserved
+    /// bytes.
+    pub fn write(&self, message: &mut [u8; MESSAGE_SIZE]) {
+        let mut writer = LeWriter::new(&mut message[..HEADER_SIZE]);
+
+        writer.bytes(&self.auth_tag);
+        writer.u64(self.seqno);
+        writer.bytes(&[0; 8]);
+        writer.u8(self.algo);
+        writer.u8(self.header_version);
+        writer.bytes(&self.header_size.to_le_bytes());
+        writer.u8(self.msg_type);
+        writer.u8(self.msg_version);
+        writer.bytes(&self.msg_size.to_le_bytes());
+        writer.u32(0);
+        writer.u8(self.vmpck);
+        writer.bytes(&[0; HEADER_SIZE - 0x3d]);
+    }
+}
+
+/// A VM Platform Communication Key of the guest's secrets page
+///
+/// The key is wiped when dropped. It is deliberately not `Clone`, so that
+/// no unwiped copies of the key material are left behind, nor comparable,
+/// as comparing keys would not be constant-time.
+pub struct Vmpck {
+    id: u8,
+    key: [u8; 32],
+}
+
+impl Vmpck {
+    /// The VMPCK `key` of index `id`, which is the VMPL it is meant for.
+    pub fn new(id: u8, key: [u8; 32]) -> Self {
+        Self { id, key }
+    }
+
+    /// The index of the key.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Encrypt `payload` into a message of type `msg_type` and version
+    /// `msg_version`, with the sequence number `seqno`.
+    pub fn seal(
+        &self,
+        seqno: u64,
+        msg_type: u8,
+        msg_version: u8,
+        payload: &[u8],
+    ) -> Result<Box<[u8; MESSAGE_SIZE]>, GuestMessageError> {
+        if payload.len() > MAX_PAYLOAD_SIZE {
+            return Err(GuestMessageError::PayloadSize { len: payload.len() });
+        }
+
+        let mut header = MessageHeader {
+            seqno,
+            algo: ALGO_AES_256_GCM,
+            header_version: HEADER_VERSION,
+            header_size: HEADER_SIZE as u16,
+            msg_type,
+            msg_version,
+            msg_size: payload.len() as u16,
+            vmpck: self.id,
+            ..Default::default()
+        };
+
+        let mut message = Box::new([0; MESSAGE_SIZE]);
+        header.write(&mut message);
+
+        let (head, body) = message.split_at_mut(HEADER_SIZE);
+        let body = &mut body[..payload.len()];
+        body.copy_from_slice(payload);
+
+        let tag: [u8; TAG_SIZE] = encrypt(&self.key, &iv(seqno), &head[AAD_OFFSET..], body)?;
+        header.auth_tag[..TAG_SIZE].copy_from_slice(&tag);
+        header.write(&mut message);
+
+        Ok(message)
+    }
+
+    /// Decrypt `message`, returning its header and payload, which is wiped
+    /// when dropped.
+    ///
+    /// Only the algorithm, header version and size, payload size and VMPCK
+    /// of the header are checked: the caller checks its sequence number,
+    /// type and version.
+    pub fn open(
+        &self,
+        message: &[u8; MESSAGE_SIZE],
+    ) -> Result<(MessageHeader, Zeroizing<Vec<u8>>), GuestMessageError> {
+        let header = MessageHeader::from_bytes(message);
+
+        if header.algo != ALGO_AES_256_GCM {
+            return Err(GuestMessageError::InvalidHeader("algorithm"));
+        }
+
+        if header.header_version != HEADER_VERSION {
+            return Err(GuestMessageError::InvalidHeader("header version"));
+        }
+
+        if usize::from(header.header_size) != HEADER_SIZE {
+            return Err(GuestMessageError::InvalidHeader("header size"));
+        }
+
+        if usize::from(header.msg_size) > MAX_PAYLOAD_SIZE {
+            return Err(GuestMessageError::InvalidHeader("message size"));
+        }
+
+        if header.vmpck != self.id {
+            return Err(GuestMessageError::InvalidHeader("VMPCK"));
+        }
+
+        let mut payload =
+            Zeroizing::new(message[HEADER_SIZE..][..usize::from(header.msg_size)].to_vec());
+        let mut tag: [u8; TAG_SIZE] = [0; TAG_SIZE];
+        tag.copy_from_slice(&header.auth_tag[..TAG_SIZE]);
+
+        decrypt(
+            &self.key,
+            &iv(header.seqno),
+            &message[AAD_OFFSET..HEADER_SIZE],
+            &mut payload,
+            &tag,
+        )?;
+
+        Ok((header, payload))
+    }
+}
+
+impl fmt::Debug for Vmpck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Vmpck")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Vmpck {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// Exchanges guest messages with the AMD Secure Processor
+pub trait GuestTransport {
+    /// Hand the encrypted `request` to the firmware, and fill `response`
+    /// with the encrypted response, e.g. by issuing an SNP Guest Request
+    /// event with shared copies of the pages.
+    fn exchange(
+        &mut self,
+        request: &[u8; MESSAGE_SIZE],
+        response: &mut [u8; MESSAGE_SIZE],
+    ) -> std::io::Result<()>;
+}
+
+impl<T: GuestTransport + ?Sized> GuestTransport for &mut T {
+    fn exchange(
+        &mut self,
+        request: &[u8; MESSAGE_SIZE],
+        response: &mut [u8; MESSAGE_SIZE],
+    ) -> std::io::Result<()> {
+        (**self).exchange(request, response)
+    }
+}
+
+/// Issues guest requests over a [GuestTransport], encrypted with a VMPCK
+#[derive(Debug)]
+pub struct GuestChannel<T> {
+    transport: T,
+    vmpck: Vmpck,
+
+    /// The last sequence number used.
+    seqno: u64,
+
+    disabled: bool,
+}
+
+impl<T: GuestTransport> GuestChannel<T> {
+    /// Issue requests over `transport` with `vmpck`, which has not been
+    /// used since the guest launched.
+    pub fn new(transport: T, vmpck: Vmpck) -> Self {
+        Self {
+            transport,
+            vmpck,
+            seqno: 0,
+            disabled: false,
+        }
+    }
+
+    /// Continue after the sequence number `seqno`, the last one another
+    /// user of the VMPCK (e.g. a previous boot stage) used.
+    pub fn with_seqno(mut self, seqno: u64) -> Self {
+        self.seqno = seqno;
+        self
+    }
+
+    /// The last sequence number used.
+    pub fn seqno(&self) -> u64 {
+        self.seqno
+    }
+
+    /// Whether a failed request disabled the channel.
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// The transport of the channel.
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Send `payload` in a request of type `msg_type` and version
+    /// `msg_version`, returning the payload of the response, which is wiped
+    /// when dropped.
+    pub fn request(
+        &mut self,
+        msg_type: MessageType,
+        msg_version: u8,
+        payload: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, GuestMessageError> {
+        if self.disabled {
+            return Err(GuestMessageError::Disabled);
+        }
+
+        let rsp_type: MessageType = msg_type
+            .response()
+            .ok_or(GuestMessageError::InvalidHeader("message type"))?;
+
+        let seqno: u64 = self
+            .seqno
+            .checked_add(1)
+            .filter(|seqno| seqno.checked_add(1).is_some())
+            .ok_or(GuestMessageError::SequenceExhausted)?;
+
+        let request = self
+            .vmpck
+            .seal(seqno, msg_type.into(), msg_version, payload)?;
+
+        // From here on, the firmware may have consumed the sequence number:
+        // it must not be used again, and may have to be skipped.
+        self.seqno = seqno + 1;
+        self.disabled = true;
+
+        let mut response = Box::new([0; MESSAGE_SIZE]);
+        self.transport
+            .exchange(&request, &mut response)
+            .map_err(GuestMessageError::Transport)?;
+
+        let (header, payload) = self.vmpck.open(&response)?;
+
+        if header.seqno != seqno + 1 {
+            return Err(GuestMessageError::UnexpectedSequence {
+                expected: seqno + 1,
+                actual: header.seqno,
+            });
+        }
+
+        if header.msg_type != u8::from(rsp_type) {
+            return Err(GuestMessageError::UnexpectedType {
+                expected: rsp_type.into(),
+                actual: header.msg_type,
+            });
+        }
+
+        if header.msg_version != msg_version {
+            return Err(GuestMessageError::UnexpectedVersion {
+                expected: msg_version,
+                actual: header.msg_version,
+            });
+        }
+
+        self.disabled = false;
+
+        Ok(payload)
+    }
+
+    /// Request an attestation report with `report_data` at `vmpl`.
+    pub fn get_report(
+        &mut self,
+        report_data: [u8; 64],
+        vmpl: u32,
+    ) -> Result<AttestationReport, GuestMessageError> {
+        let mut request: [u8; 0x60] = [0; 0x60];
+        let mut writer = LeWriter::new(&mut request);
+        writer.bytes(&report_data);
+        writer.u32(vmpl);
+
+        let response: Zeroizing<Vec<u8>> = self.request(MessageType::ReportReq, 1, &request)?;
+        if response.len() < 0x20 + AttestationReport::SIZE {
+            return Err(GuestMessageError::InvalidResponse);
+        }
+
+        let mut reader = LeReader::new(&response);
+        match reader.u32() {
+            0 => (),
+            status => return Err(GuestMessageError::Status(status)),
+        }
+
+        if reader.u32() as usize != AttestationReport::SIZE {
+            return Err(GuestMessageError::InvalidResponse);
+        }
+
+        reader.bytes::<24>();
+        Ok(AttestationReport::from_bytes(&reader.bytes()))
+    }
+
+    /// Request a key derived as `request` describes, which is wiped when
+    /// dropped.
+    pub fn get_derived_key(
+        &mut self,
+        request: &DerivedKey,
+    ) -> Result<Zeroizing<[u8; 32]>, GuestMessageError> {
+        let mut payload: [u8; 0x20] = [0; 0x20];
+        let mut writer = LeWriter::new(&mut payload);
+        writer.u32(request.get_root_key_select());
+        writer.u32(0);
+        writer.u64(request.guest_field_select.0);
+        writer.u32(request.vmpl);
+        writer.u32(request.guest_svn);
+        writer.u64(request.tcb_version);
+
+        let response: Zeroizing<Vec<u8>> = self.request(MessageType::KeyReq, 1, &payload)?;
+        if response.len() < 0x40 {
+            return Err(GuestMessageError::InvalidResponse);
+        }
+
+        let mut reader = LeReader::new(&response);
+        match reader.u32() {
+            0 => (),
+            status => return Err(GuestMessageError::Status(status)),
+        }
+
+        reader.bytes::<28>();
+        Ok(Zeroizing::new(reader.bytes()))
+    }
+
+    /// Request the TSC parameters of a SecureTSC guest.
+    ///
+    /// The `/dev/sev-guest` driver offers no TSC_INFO request, as the
+    /// kernel issues it itself when it boots with SecureTSC, so SecureTSC
+    /// guests without that kernel support (e.g. SVSMs) obtain them here.
+    pub fn get_tsc_info(&mut self) -> Result<TscInfo, GuestMessageError> {
+        let response: Zeroizing<Vec<u8>> = self.request(MessageType::TscInfoReq, 1, &[0; 0x80])?;
+        if response.len() < 0x80 {
+            return Err(GuestMessageError::InvalidResponse);
+        }
+
+        let mut reader = LeReader::new(&response);
+        match reader.u32() {
+            0 => (),
+            status => return Err(GuestMessageError::Status(status)),
+        }
+
+        reader.u32();
+        Ok(TscInfo {
+            tsc_scale: reader.u64(),
+            tsc_offset: reader.u64(),
+            tsc_factor: reader.u32(),
+        })
+    }
+}
+
+/// The TSC parameters the firmware programs for a SecureTSC guest
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TscInfo {
+    /// The GUEST_TSC_SCALE of the guest's VMSAs.
+    pub tsc_scale: u64,
+
+    /// The GUEST_TSC_OFFSET of the guest's VMSAs.
+    pub tsc_offset: u64,
+
+    /// The reduction of the guest's TSC frequency, in parts per 100000,
+    /// which accounts for the time the hypervisor spends on guest entries
+    /// and exits.
+    pub tsc_factor: u32,
+}
+
+/// The GCM IV of the message with sequence number `seqno`.
+fn iv(seqno: u64) -> [u8; 12] {
+    let mut iv: [u8; 12] = [0; 12];
+    iv[..8].copy_from_slice(&seqno.to_le_bytes());
+    iv
+}
+
+#[cfg(feature = "openssl")]
+fn encrypt(
+    key: &[u8; 32],
+    iv: &[u8; 12],
+    aad: &[u8],
+    data: &mut [u8],
+) -> Result<[u8; TAG_SIZE], GuestMessageError> {
+    use openssl::symm::{encrypt_aead, Cipher};
+
+    let mut tag: [u8; TAG_SIZE] = [0; TAG_SIZE];
+    let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), key, Some(iv), aad, data, &mut tag)
+        .map_err(|e| GuestMessageError::Crypto(e.to_string()))?;
+    data.copy_from_slice(&ciphertext);
+
+    Ok(tag)
+}
+
+#[cfg(feature = "openssl")]
+fn decrypt(
+    key: &[u8; 32],
+    iv: &[u8; 12],
+    aad: &[u8],
+    data: &mut [u8],
+    tag: &[u8; TAG_SIZE],
+) -> Result<(), GuestMessageError> {
+    use openssl::symm::{decrypt_aead, Cipher};
+
+    let plaintext = decrypt_aead(Cipher::aes_256_gcm(), key, Some(iv), aad, data, tag)
+        .map(Zeroizing::new)
+        .map_err(|_| GuestMessageError::Authentication)?;
+    data.copy_from_slice(&plaintext);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "crypto_nossl", not(feature = "openssl")))]
+fn encrypt(
+    key: &[u8; 32],
+    iv: &[u8; 12],
+    aad: &[u8],
+    data: &mut [u8],
+) -> Result<[u8; TAG_SIZE], GuestMessageError> {
+    use aes_gcm::{aead::AeadInPlace, Aes256Gcm, KeyInit};
+
+    let tag = Aes256Gcm::new(key.into())
+        .encrypt_in_place_detached(iv.into(), aad, data)
+        .map_err(|e| GuestMessageError::Crypto(e.to_string()))?;
+
+    Ok(tag.into())
+}
+
+#[cfg(all(feature = "crypto_nossl", not(feature = "openssl")))]
+fn decrypt(
+    key: &[u8; 32],
+    iv: &[u8; 12],
+    aad: &[u8],
+    data: &mut [u8],
+    tag: &[u8; TAG_SIZE],
+) -> Result<(), GuestMessageError> {
+    use aes_gcm::{aead::AeadInPlace, Aes256Gcm, KeyInit};
+
+    Aes256Gcm::new(key.into())
+        .decrypt_in_place_detached(iv.into(), aad, data, tag.into())
+        .map_err(|_| GuestMessageError::Authentication)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Answers requests as the firmware does.
+    struct Firmware {
+        vmpck: Vmpck,
+        seqno: u64,
+        reply: fn(MessageType, &[u8]) -> Vec<u8>,
+    }
+
+    impl GuestTransport for Firmware {
+        fn exchange(
+            &mut self,
+            request: &[u8; MESSAGE_SIZE],
+            response: &mut [u8; MESSAGE_SIZE],
+        ) -> std::io::Result<()> {
+            let (header, payload) = self.vmpck.open(request).unwrap();
+            assert_eq!(header.seqno, self.seqno + 1);
+            self.seqno += 2;
+
+            let msg_type = MessageType::try_from(header.msg_type).unwrap();
+            let reply = (self.reply)(msg_type, &payload);
+            let sealed = self
+                .vmpck
+                .seal(
+                    self.seqno,
+                    msg_type.response().unwrap().into(),
+                    header.msg_version,
+                    &reply,
+                )
+                .unwrap();
+            response.copy_from_slice(&sealed[..]);
+
+            Ok(())
+        }
+    }
+
+    fn firmware(reply: fn(MessageType, &[u8]) -> Vec<u8>) -> Firmware {
+        Firmware {
+            vmpck: Vmpck::new(0, [0x42; 32]),
+            seqno: 0,
+            reply,
+        }
+    }
+
+    fn report(msg_type: MessageType, payload: &[u8]) -> Vec<u8> {
+        assert_eq!(msg_type, MessageType::ReportReq);
+        assert_eq!(payload.len(), 0x60);
+
+        let mut report = AttestationReport::default();
+        report.report_data.0.copy_from_slice(&payload[..64]);
+        report.vmpl = u32::from_le_bytes([payload[64], payload[65], payload[66], payload[67]]);
+
+        let mut response = vec![0; 0x20];
+        response[4..8].copy_from_slice(&(AttestationReport::SIZE as u32).to_le_bytes());
+        response.extend_from_slice(&report.to_bytes());
+        response
+    }
+
+    #[test]
+    fn test_seal_open() {
+        let vmpck = Vmpck::new(2, [7; 32]);
+
+        let message = vmpck.seal(5, 3, 1, b"payload").unwrap();
+        let header = MessageHeader::from_bytes(&message);
+        assert_eq!(header.seqno, 5);
+        assert_eq!(
+            (header.algo, header.header_version, header.header_size),
+            (1, 1, 0x60)
+        );
+        assert_eq!(
+            (header.msg_type, header.msg_version, header.msg_size),
+            (3, 1, 7)
+        );
+        assert_eq!(header.vmpck, 2);
+        assert_ne!(&message[HEADER_SIZE..HEADER_SIZE + 7], b"payload");
+
+        let (opened, payload) = vmpck.open(&message).unwrap();
+        assert_eq!(opened, header);
+        assert_eq!(*payload, b"payload");
+
+        // The header is authenticated.
+        let mut tampered = message.clone();
+        tampered[0x35] = 2;
+        assert!(matches!(
+            vmpck.open(&tampered),
+            Err(GuestMessageError::Authentication)
+        ));
+
+        let mut tampered = message;
+        tampered[HEADER_SIZE] ^= 1;
+        assert!(matches!(
+            vmpck.open(&tampered),
+            Err(GuestMessageError::Authentication)
+        ));
+
+        assert!(matches!(
+            Vmpck::new(2, [8; 32]).open(&vmpck.seal(5, 3, 1, b"payload").unwrap()),
+            Err(GuestMessageError::Authentication)
+        ));
+        assert!(matches!(
+            vmpck.seal(1, 3, 1, &[0; MAX_PAYLOAD_SIZE + 1]),
+            Err(GuestMessageError::PayloadSize { len: 4001 })
+        ));
+    }
+
+    #[test]
+    fn test_get_report() {
+        let mut channel = GuestChannel::new(firmware(report), Vmpck::new(0, [0x42; 32]));
+
+        let report = channel.get_report([0xab; 64], 1).unwrap();
+        assert_eq!(report.report_data.into_inner(), [0xab; 64]);
+        assert_eq!(report.vmpl, 1);
+        assert_eq!(channel.seqno(), 2);
+
+        channel.get_report([0; 64], 0).unwrap();
+        assert_eq!(channel.seqno(), 4);
+        assert_eq!(channel.into_inner().seqno, 4);
+    }
+
+    #[test]
+    fn test_get_derived_key() {
+        let mut channel = GuestChannel::new(
+            firmware(|msg_type, payload| {
+                assert_eq!(msg_type, MessageType::KeyReq);
+                assert_eq!(payload.len(), 0x20);
+
+                let mut response = vec![0; 0x20];
+                response.extend_from_slice(&[payload[16]; 32]);
+                response
+            }),
+            Vmpck::new(0, [0x42; 32]),
+        );
+
+        let request = DerivedKey::new(false, Default::default(), 3, 0, 0);
+        assert_eq!(*channel.get_derived_key(&request).unwrap(), [3; 32]);
+    }
+
+    #[test]
+    fn test_get_tsc_info() {
+        let mut channel = GuestChannel::new(
+            firmware(|msg_type, payload| {
+                assert_eq!(msg_type, MessageType::TscInfoReq);
+                assert_eq!(payload, &[0; 0x80][..]);
+
+                let mut response = vec![0; 0x80];
+                response[8..16].copy_from_slice(&0x1_0000_0000u64.to_le_bytes());
+                response[16..24].copy_from_slice(&(-42i64 as u64).to_le_bytes());
+                response[24..28].copy_from_slice(&60u32.to_le_bytes());
+                response
+            }),
+            Vmpck::new(0, [0x42; 32]),
+        );
+
+        assert_eq!(
+            channel.get_tsc_info().unwrap(),
+            TscInfo {
+                tsc_scale: 0x1_0000_0000,
+                tsc_offset: -42i64 as u64,
+                tsc_factor: 60,
+            }
+        );
+        assert_eq!(channel.seqno(), 2);
+    }
+
+    #[test]
+    fn test_status() {
+        let mut channel = GuestChannel::new(
+            firmware(|_, _| {
+                let mut response = vec![0; 0x20 + AttestationReport::SIZE];
+                response[0] = 0x16;
+                response
+            }),
+            Vmpck::new(0, [0x42; 32]),
+        );
+
+        assert!(matches!(
+            channel.get_report([0; 64], 4),
+            Err(GuestMessageError::Status(0x16))
+        ));
+        assert!(!channel.is_disabled());
+        assert_eq!(channel.seqno(), 2);
+    }
+
+    #[test]
+    fn test_disabled() {
+        struct Unplugged;
+
+        impl GuestTransport for Unplugged {
+            fn exchange(
+                &mut self,
+                _: &[u8; MESSAGE_SIZE],
+                _: &mut [u8; MESSAGE_SIZE],
+            ) -> std::io::Result<()> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let mut channel = GuestChannel::new(Unplugged, Vmpck::new(0, [0x42; 32]));
+        assert!(matches!(
+            channel.get_report([0; 64], 0),
+            Err(GuestMessageError::Transport(_))
+        ));
+
+        // The firmware may have consumed the sequence number.
+        assert!(channel.is_disabled());
+        assert_eq!(channel.seqno(), 2);
+        assert!(matches!(
+            channel.get_report([0; 64], 0),
+            Err(GuestMessageError::Disabled)
+        ));
+    }
+
+    #[test]
+    fn test_unexpected_response() {
+        struct Replay(Box<[u8; MESSAGE_SIZE]>);
+
+        impl GuestTransport for Replay {
+            fn exchange(
+                &mut self,
+                _: &[u8; MESSAGE_SIZE],
+                response: &mut [u8; MESSAGE_SIZE],
+            ) -> std::io::Result<()> {
+                response.copy_from_slice(&self.0[..]);
+                Ok(())
+            }
+        }
+
+        let vmpck = Vmpck::new(0, [0x42; 32]);
+
+        let stale = vmpck.seal(2, 6, 1, &[0; 0x20]).unwrap();
+        let mut channel = GuestChannel::new(Replay(stale), Vmpck::new(0, [0x42; 32])).with_seqno(2);
+        assert!(matches!(
+            channel.request(MessageType::ReportReq, 1, &[0; 0x60]),
+            Err(GuestMessageError::UnexpectedSequence {
+                expected: 4,
+                actual: 2
+            })
+        ));
+        assert!(channel.is_disabled());
+        assert_eq!(channel.seqno(), 4);
+
+        let wrong_type = vmpck.seal(2, 4, 1, &[0; 0x20]).unwrap();
+        let mut channel = GuestChannel::new(Replay(wrong_type), Vmpck::new(0, [0x42; 32]));
+        assert!(matches!(
+            channel.request(MessageType::ReportReq, 1, &[0; 0x60]),
+            Err(GuestMessageError::UnexpectedType {
+                expected: 6,
+                actual: 4
+            })
+        ));
+
+        let mut channel = GuestChannel::new(Replay(vmpck.seal(2, 6, 1, &[]).unwrap()), vmpck)
+            .with_seqno(u64::MAX - 1);
+        assert!(matches!(
+            channel.request(MessageType::ReportReq, 1, &[]),
+            Err(GuestMessageError::SequenceExhausted)
+        ));
+        assert!(!channel.is_disabled());
+        assert!(matches!(
+            channel.request(MessageType::ReportRsp, 1, &[]),
+            Err(GuestMessageError::InvalidHeader("message type"))
+        ));
+    }
+
+    #[test]
+    fn test_message_type() {
+        for value in 1..=18 {
+            let msg_type = MessageType::try_from(value).unwrap();
+            assert_eq!(u8::from(msg_type), value);
+            assert_eq!(
+                msg_type.response().map(u8::from),
+                (value % 2 == 1).then_some(value + 1)
+            );
+        }
+
+        assert_eq!(MessageType::try_from(0), Err(0));
+        assert_eq!(MessageType::try_from(19), Err(19));
+    }
+}
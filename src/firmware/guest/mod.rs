@@ -5,24 +5,62 @@
 //! The Guest owner is a tenant of a virtualization provider. They may have
 //! one or more guest confidential virtual-machines (VM) or containers which
 //! may be deployed in a Platform Owner's environment..
+//!
+//! With the `snp-types` feature alone, the crate is `no_std` and this module
+//! only provides the attestation report, the guest policy and the other
+//! fields of the report along with their binary encoding, so that boot-time
+//! components such as UEFI applications and SVSM modules can parse and
+//! construct them. Everything issuing ioctls requires `std`.
 
+#[cfg(all(
+    feature = "snp",
+    target_os = "linux",
+    any(feature = "openssl", feature = "crypto_nossl")
+))]
+pub mod ap;
+#[cfg(feature = "snp-types")]
+pub mod ghcb;
+#[cfg(feature = "snp")]
+mod key_manager;
+#[cfg(feature = "snp-types")]
+pub mod layout;
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+pub mod message;
+#[cfg(feature = "snp-types")]
+mod redact;
+#[cfg(feature = "snp-types")]
+mod text;
+#[cfg(feature = "snp")]
+mod throttle;
 mod types;
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+pub mod vtpm;
 
+#[cfg(feature = "snp")]
+pub use key_manager::*;
+#[cfg(feature = "snp-types")]
+pub use redact::*;
+#[cfg(feature = "snp")]
+pub use throttle::*;
 pub use types::*;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "snp"))]
 use crate::{
     error::*,
     firmware::{
         host::CertTableEntry,
         linux::{
             guest::{ioctl::*, types::*},
-            host as HostFFI,
+            trace::Metrics,
         },
     },
+    ParseOptions,
 };
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "snp", feature = "metrics"))]
+use crate::firmware::metrics::MetricsRecorder;
+
+#[cfg(all(target_os = "linux", feature = "snp"))]
 use std::fs::{File, OpenOptions};
 
 // Disabled until upstream Linux kernel is patched.
@@ -48,11 +86,42 @@ use std::fs::{File, OpenOptions};
 //     Ok(())
 // }
 
+/// The largest certificate buffer (in bytes) an extended report request
+/// allocates by default, which is also the largest the kernel accepts.
+#[cfg(all(target_os = "linux", feature = "snp"))]
+pub const DEFAULT_MAX_CERTS_LEN: u32 = 0x4000;
+
+/// How many times an extended report is requested before giving up on the
+/// hypervisor settling on the size of the certificates.
+#[cfg(all(target_os = "linux", feature = "snp"))]
+const EXT_REPORT_ATTEMPTS: usize = 3;
+
+/// The page aligned size of a buffer for `required` bytes of certificates,
+/// unless it exceeds `max`.
+#[cfg(all(target_os = "linux", feature = "snp"))]
+fn certs_buffer_len(required: u32, max: u32) -> Result<u32, CertError> {
+    const PAGE_SIZE: u32 = 0x1000;
+
+    if required == 0 {
+        return Err(CertError::EmptyCertBuffer);
+    }
+
+    match required.checked_add(PAGE_SIZE - 1) {
+        Some(len) if len & !(PAGE_SIZE - 1) <= max => Ok(len & !(PAGE_SIZE - 1)),
+        _ => Err(CertError::TableTooLarge { len: required, max }),
+    }
+}
+
 /// A handle to the SEV-SNP guest device.
-#[cfg(target_os = "linux")]
-pub struct Firmware(File);
+///
+/// The device offers no TSC_INFO request, as the kernel issues it itself
+/// while booting a SecureTSC guest, so there is no `get_tsc_info` here:
+/// guests that need the TSC parameters request them over a
+/// `message::GuestChannel` (`openssl` or `crypto_nossl` feature).
+#[cfg(all(target_os = "linux", feature = "snp"))]
+pub struct Firmware(File, Option<&'static RequestThrottle>, u32, Metrics);
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "snp"))]
 impl Firmware {
     /// Generate a new file handle to the SEV guest platform via `/dev/sev-guest`.
     ///
@@ -64,9 +133,59 @@ impl Firmware {
     pub fn open() -> std::io::Result<Firmware> {
         Ok(Firmware(
             OpenOptions::new().read(true).open("/dev/sev-guest")?,
+            None,
+            DEFAULT_MAX_CERTS_LEN,
+            Metrics::default(),
         ))
     }
 
+    /// Issue the requests of this handle one at a time with those of every
+    /// other handle sharing `throttle`, e.g. [RequestThrottle::global].
+    ///
+    /// # Example:
+    ///
+    /// ```ignore
+    /// let mut fw: Firmware = Firmware::open()
+    ///     .unwrap()
+    ///     .throttled(RequestThrottle::global());
+    /// ```
+    pub fn throttled(self, throttle: &'static RequestThrottle) -> Self {
+        Self(self.0, Some(throttle), self.2, self.3)
+    }
+
+    /// Fail extended report requests for which the hypervisor asks for a
+    /// certificate buffer larger than `len` bytes, rather than allocating
+    /// it. Defaults to [DEFAULT_MAX_CERTS_LEN].
+    ///
+    /// # Example:
+    ///
+    /// ```ignore
+    /// let mut fw: Firmware = Firmware::open().unwrap().max_certs_len(0x2000);
+    /// ```
+    pub fn max_certs_len(self, len: u32) -> Self {
+        Self(self.0, self.1, len, self.3)
+    }
+
+    /// Report the metrics of the requests issued on this handle to
+    /// `recorder`.
+    ///
+    /// # Example:
+    ///
+    /// ```ignore
+    /// static STATS: FirmwareStats = FirmwareStats::new();
+    ///
+    /// let mut fw: Firmware = Firmware::open().unwrap().recorded(&STATS);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn recorded(self, recorder: &'static dyn MetricsRecorder) -> Self {
+        Self(self.0, self.1, self.2, Metrics::new(recorder))
+    }
+
+    /// Wait for the turn of a request, if the handle is throttled.
+    fn permit(&self) -> Option<ThrottlePermit<'static>> {
+        self.1.map(RequestThrottle::acquire)
+    }
+
     /// Requests an attestation report from the AMD Secure Processor. The `message_version` will default
     /// to `1` if `None` is specified.
     ///
@@ -99,13 +218,51 @@ impl Firmware {
         data: Option<[u8; 64]>,
         vmpl: Option<u32>,
     ) -> Result<AttestationReport, UserApiError> {
-        let mut input = ReportReq::new(data, vmpl)?;
+        self.report(message_version, ReportReq::new(data, vmpl)?)
+    }
+
+    /// Requests an attestation report signed with a specific key from the AMD Secure Processor.
+    /// The `message_version` will default to `1` if `None` is specified.
+    ///
+    /// The key which actually signed the report can be read back from
+    /// [KeyInfo::signing_key](crate::firmware::guest::KeyInfo::signing_key).
+    ///
+    /// # Example:
+    ///
+    /// ```ignore
+    /// let mut fw: Firmware = Firmware::open().unwrap();
+    ///
+    /// let report: AttestationReport = fw
+    ///     .get_report_with_key(None, Some([0; 64]), None, KeySelect::Vlek)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(report.key_info.signing_key(), Ok(SigningKey::Vlek));
+    /// ```
+    pub fn get_report_with_key(
+        &mut self,
+        message_version: Option<u8>,
+        data: Option<[u8; 64]>,
+        vmpl: Option<u32>,
+        key: KeySelect,
+    ) -> Result<AttestationReport, UserApiError> {
+        self.report(
+            message_version,
+            ReportReq::new(data, vmpl)?.with_key_select(key),
+        )
+    }
+
+    fn report(
+        &mut self,
+        message_version: Option<u8>,
+        mut input: ReportReq,
+    ) -> Result<AttestationReport, UserApiError> {
         let mut response = ReportRsp::default();
 
+        let _permit = self.permit();
         let mut request: GuestRequest<ReportReq, ReportRsp> =
             GuestRequest::new(message_version, &mut input, &mut response);
 
-        SNP_GET_REPORT.ioctl(&mut self.0, &mut request)?;
+        request.issue(SNP_GET_REPORT, &mut self.0, self.3)?;
 
         // Disabled until upstream Linux kernel is patched.
         // check_fw_err(request.fw_err.into())?;
@@ -117,83 +274,117 @@ impl Firmware {
     /// The `message_version` will default to `1` if `None` is specified.
     ///
     /// Behaves the same as [get_report](crate::firmware::guest::Firmware::get_report).
+    ///
+    /// When the hypervisor holds certificates, the request is repeated with a
+    /// buffer of the size it asks for, up to [max_certs_len](Self::max_certs_len).
     pub fn get_ext_report(
         &mut self,
         message_version: Option<u8>,
         data: Option<[u8; 64]>,
         vmpl: Option<u32>,
     ) -> Result<(AttestationReport, Option<Vec<CertTableEntry>>), UserApiError> {
-        let report_request = ReportReq::new(data, vmpl)?;
+        self.ext_report(message_version, ReportReq::new(data, vmpl)?)
+    }
+
+    /// Request an extended attestation report signed with a specific key
+    /// from the AMD Secure Processor. The `message_version` will default to
+    /// `1` if `None` is specified.
+    ///
+    /// Behaves the same as [get_report_with_key](crate::firmware::guest::Firmware::get_report_with_key).
+    pub fn get_ext_report_with_key(
+        &mut self,
+        message_version: Option<u8>,
+        data: Option<[u8; 64]>,
+        vmpl: Option<u32>,
+        key: KeySelect,
+    ) -> Result<(AttestationReport, Option<Vec<CertTableEntry>>), UserApiError> {
+        self.ext_report(
+            message_version,
+            ReportReq::new(data, vmpl)?.with_key_select(key),
+        )
+    }
 
+    fn ext_report(
+        &mut self,
+        message_version: Option<u8>,
+        report_request: ReportReq,
+    ) -> Result<(AttestationReport, Option<Vec<CertTableEntry>>), UserApiError> {
         let mut report_response = ReportRsp::default();
 
-        // Define a buffer to store the certificates in.
-        let mut certificate_bytes: Vec<u8>;
+        // Define a buffer to store the certificates in, left empty until the
+        // hypervisor tells us how large the certificates are.
+        let mut certificate_bytes: Vec<u8> = vec![];
 
         // Due to the complex buffer allocation, we will take the ReportReq
         // provided by the caller, and create an extended report request object
         // for them.
         let mut ext_report_request = ExtReportReq::new(&report_request);
 
-        // Construct the object needed to perform the IOCTL request.
-        // *NOTE:* This is __important__ because a fw_err value which matches
-        // [InvalidCertificatePageLength](crate::error::VmmError::InvalidCertificatePageLength) will indicate the buffer was not large
-        // enough.
-        let mut guest_request: GuestRequest<ExtReportReq, ReportRsp> = GuestRequest::new(
-            message_version,
-            &mut ext_report_request,
-            &mut report_response,
-        );
+        // All requests, if the buffer is too small, take a single turn.
+        let _permit = self.permit();
+
+        let mut attempts: usize = 0;
+        loop {
+            // Construct the object needed to perform the IOCTL request.
+            // *NOTE:* This is __important__ because a fw_err value which matches
+            // [InvalidCertificatePageLength](crate::error::VmmError::InvalidCertificatePageLength) will indicate the buffer was not large
+            // enough.
+            let mut guest_request: GuestRequest<ExtReportReq, ReportRsp> = GuestRequest::new(
+                message_version,
+                &mut ext_report_request,
+                &mut report_response,
+            );
 
-        // KEEP for Kernels before 47894e0f (5.19), as userspace broke at that hash.
-        if let Err(ioctl_error) = SNP_GET_EXT_REPORT.ioctl(&mut self.0, &mut guest_request) {
-            match guest_request.fw_err.into() {
+            let result = guest_request.issue(SNP_GET_EXT_REPORT, &mut self.0, self.3);
+            let fw_err = guest_request.fw_err;
+
+            // Kernels before 47894e0f (5.19) fail the ioctl when the buffer is
+            // too small, while later ones succeed and only set fw_err, so the
+            // ioctl error only matters when fw_err does not explain it.
+            match VmmError::from(fw_err) {
+                _ if fw_err == 0 => {
+                    result?;
+                    break;
+                }
                 VmmError::InvalidCertificatePageLength => (),
                 VmmError::RateLimitRetryRequest => {
                     return Err(VmmError::RateLimitRetryRequest.into())
                 }
-                _ => return Err(ioctl_error.into()),
+                _ => {
+                    result?;
+
+                    // This shouldn't be possible, but if it happens, throw an error.
+                    return Err(UserApiError::FirmwareError(Error::InvalidConfig));
+                }
             }
 
-            // Eventually the code below will be moved back into this scope.
-        }
+            // The certificates may change between two requests, so that the
+            // buffer is resized more than once, but not endlessly.
+            attempts += 1;
+            if attempts == EXT_REPORT_ATTEMPTS {
+                return Err(VmmError::InvalidCertificatePageLength.into());
+            }
 
-        // The kernel patch by pgonda@google.com in kernel hash 47894e0f
-        // changed the ioctl return to succeed instead of returning an
-        // error when encountering an invalid certificate length. This was
-        // done to keep the cryptography safe, so we will now just check
-        // the guest_request.fw_err for a new value.
-        //
-        // Check to see if the buffer needs to be resized. If it does, the
-        // we need to resize the buffer to the correct size, and
-        // re-request for the certificates.
-        if VmmError::InvalidCertificatePageLength == guest_request.fw_err.into() {
-            certificate_bytes = vec![0u8; ext_report_request.certs_len as usize];
+            let len = certs_buffer_len(ext_report_request.certs_len, self.2)?;
+            certificate_bytes = vec![0u8; len as usize];
             ext_report_request.certs_address = certificate_bytes.as_mut_ptr() as u64;
-            let mut guest_request_retry: GuestRequest<ExtReportReq, ReportRsp> = GuestRequest::new(
-                message_version,
-                &mut ext_report_request,
-                &mut report_response,
-            );
-            SNP_GET_EXT_REPORT.ioctl(&mut self.0, &mut guest_request_retry)?;
-        } else if guest_request.fw_err != 0 {
-            // This shouldn't be possible, but if it happens, throw an error.
-            return Err(UserApiError::FirmwareError(Error::InvalidConfig));
+            ext_report_request.certs_len = len;
         }
 
-        if ext_report_request.certs_len == 0 {
+        if certificate_bytes.is_empty() || ext_report_request.certs_len == 0 {
             return Ok((report_response.report, None));
         }
 
-        let mut certificates: Vec<CertTableEntry>;
-
-        unsafe {
-            let entries = (ext_report_request.certs_address as *mut HostFFI::types::CertTableEntry)
-                .as_mut()
-                .ok_or(CertError::EmptyCertBuffer)?;
-            certificates = HostFFI::types::CertTableEntry::parse_table(entries)?;
-            certificates.sort();
-        }
+        // The hypervisor fills the buffer, so the table is not trusted to
+        // stay within it.
+        let mut certificates =
+            CertTableEntry::parse_cert_table(&certificate_bytes, ParseOptions::default()).map_err(
+                |e| match e {
+                    ParseError::InvalidGuid => CertError::InvalidGUID,
+                    _ => CertError::BufferOverflow,
+                },
+            )?;
+        certificates.sort();
 
         // Return both the Attestation Report, as well as the Cert Table.
         Ok((report_response.report, Some(certificates)))
@@ -216,13 +407,15 @@ impl Firmware {
         let mut ffi_derived_key_request: DerivedKeyReq = derived_key_request.into();
         let mut ffi_derived_key_response: DerivedKeyRsp = Default::default();
 
+        let _permit = self.permit();
+
         let mut request: GuestRequest<DerivedKeyReq, DerivedKeyRsp> = GuestRequest::new(
             message_version,
             &mut ffi_derived_key_request,
             &mut ffi_derived_key_response,
         );
 
-        SNP_GET_DERIVED_KEY.ioctl(&mut self.0, &mut request)?;
+        request.issue(SNP_GET_DERIVED_KEY, &mut self.0, self.3)?;
 
         // Disabled until upstream Linux kernel is patched.
         // check_fw_err(request.fw_err.into())?;
@@ -230,3 +423,31 @@ impl Firmware {
         Ok(ffi_derived_key_response.key)
     }
 }
+
+#[cfg(all(test, target_os = "linux", feature = "snp"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_certs_buffer_len() {
+        assert_eq!(certs_buffer_len(0x1000, 0x4000).unwrap(), 0x1000);
+        assert_eq!(certs_buffer_len(0x1001, 0x4000).unwrap(), 0x2000);
+        assert_eq!(certs_buffer_len(0x4000, 0x4000).unwrap(), 0x4000);
+
+        assert!(matches!(
+            certs_buffer_len(0x3001, 0x3800),
+            Err(CertError::TableTooLarge {
+                len: 0x3001,
+                max: 0x3800
+            })
+        ));
+        assert!(matches!(
+            certs_buffer_len(u32::MAX, u32::MAX),
+            Err(CertError::TableTooLarge { .. })
+        ));
+        assert!(matches!(
+            certs_buffer_len(0, 0x4000),
+            Err(CertError::EmptyCertBuffer)
+        ));
+    }
+}
@@ -0,0 +1,280 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Redaction of the hardware-identifying fields of attestation reports.
+//!
+//! The chip ID identifies the processor a guest runs on, and the report IDs
+//! identify the guest and its migration agent across reports. A
+//! [RedactedReport] keeps every other field of a report intact, so that it
+//! can be logged, printed and serialized without leaking either. A
+//! REPORT_ID_MA of all ones, marking a guest without migration agent, is
+//! kept as well.
+
+use super::AttestationReport;
+
+use core::{
+    fmt::{self, Display},
+    ops::Deref,
+};
+
+use serde::{Serialize, Serializer};
+
+/// How the chip and report IDs of a report were redacted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Redacted {
+    /// The IDs were zeroed.
+    Masked,
+
+    /// The IDs were replaced by a keyed hash of them.
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    Hashed,
+}
+
+/// How to redact the chip and report IDs of a report
+#[derive(Clone, PartialEq, Eq)]
+pub enum Redaction {
+    /// Zero the IDs.
+    Mask,
+
+    /// Replace each ID with the HMAC-SHA512 of it under this key, truncated
+    /// to the size of the ID.
+    ///
+    /// Reports from the same chip or guest can then still be correlated by
+    /// their redacted IDs, while the IDs cannot be recovered without the key.
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    KeyedHash([u8; 32]),
+}
+
+impl fmt::Debug for Redaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mask => write!(f, "Mask"),
+            // Keep the key out of logs.
+            #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+            Self::KeyedHash(_) => write!(f, "KeyedHash(..)"),
+        }
+    }
+}
+
+/// A copy of an attestation report with its chip and report IDs redacted
+///
+/// The signature is kept, but no longer verifies against the redacted
+/// report. Verify the original report before redacting it.
+#[derive(Clone, Copy, Debug)]
+pub struct RedactedReport {
+    report: AttestationReport,
+    redacted: Redacted,
+}
+
+impl RedactedReport {
+    /// The redacted report.
+    pub fn into_inner(self) -> AttestationReport {
+        self.report
+    }
+}
+
+impl Deref for RedactedReport {
+    type Target = AttestationReport;
+
+    fn deref(&self) -> &Self::Target {
+        &self.report
+    }
+}
+
+impl Display for RedactedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.report.fmt_redacted(f, Some(self.redacted))
+    }
+}
+
+impl Serialize for RedactedReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.report.serialize(serializer)
+    }
+}
+
+impl AttestationReport {
+    /// A copy of the report with its chip and report IDs zeroed.
+    pub fn redacted(&self) -> RedactedReport {
+        self.redacted_with(&Redaction::Mask)
+    }
+
+    /// A copy of the report with its chip and report IDs redacted as
+    /// `redaction` requires.
+    pub fn redacted_with(&self, redaction: &Redaction) -> RedactedReport {
+        let mut report = *self;
+        let migration_agent = self.migration_agent().is_some();
+
+        let redacted = match redaction {
+            Redaction::Mask => {
+                report.chip_id = Default::default();
+                report.report_id = [0; 32];
+                if migration_agent {
+                    report.report_id_ma = [0; 32];
+                }
+                Redacted::Masked
+            }
+
+            #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+            Redaction::KeyedHash(key) => {
+                report.chip_id = hmac_sha512(key, b"chip_id", self.chip_id.as_bytes()).into();
+                keyed_hash(key, b"report_id", &mut report.report_id);
+                if migration_agent {
+                    keyed_hash(key, b"report_id_ma", &mut report.report_id_ma);
+                }
+                Redacted::Hashed
+            }
+        };
+
+        RedactedReport { report, redacted }
+    }
+}
+
+/// Replace `id` with its truncated HMAC under `key`, labelled `label`.
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+fn keyed_hash(key: &[u8; 32], label: &[u8], id: &mut [u8; 32]) {
+    let hash = hmac_sha512(key, label, id);
+    id.copy_from_slice(&hash[..32]);
+}
+
+/// HMAC-SHA512 of `label` followed by `data` under `key`.
+///
+/// The label separates the hashes of the different IDs, so that equal IDs
+/// in different fields do not hash alike.
+#[cfg(feature = "openssl")]
+fn hmac_sha512(key: &[u8; 32], label: &[u8], data: &[u8]) -> [u8; 64] {
+    use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+
+    // OpenSSL only fails to compute an HMAC when it runs out of memory.
+    let hmac = || -> Result<Vec<u8>, openssl::error::ErrorStack> {
+        let key = PKey::hmac(key)?;
+        let mut signer = Signer::new(MessageDigest::sha512(), &key)?;
+        signer.update(label)?;
+        signer.update(data)?;
+        signer.sign_to_vec()
+    };
+
+    let mut hash = [0u8; 64];
+    hash.copy_from_slice(&hmac().expect("failed to compute an HMAC-SHA512"));
+    hash
+}
+
+/// HMAC-SHA512 of `label` followed by `data` under `key`.
+///
+/// The label separates the hashes of the different IDs, so that equal IDs
+/// in different fields do not hash alike.
+#[cfg(feature = "crypto_nossl")]
+fn hmac_sha512(key: &[u8; 32], label: &[u8], data: &[u8]) -> [u8; 64] {
+    use hmac::{Hmac, Mac};
+
+    let mut mac =
+        <Hmac<sha2::Sha512> as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(label);
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(all(test, feature = "snp"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn masked() {
+        let report = AttestationReport::milan();
+        let redacted = report.redacted();
+
+        assert_eq!(redacted.chip_id.into_inner(), [0; 64]);
+        assert_eq!(redacted.report_id, [0; 32]);
+        assert_eq!(report.migration_agent(), None);
+        assert_eq!(redacted.report_id_ma, [0xff; 32]);
+
+        assert_eq!(redacted.measurement, report.measurement);
+        assert_eq!(redacted.report_data, report.report_data);
+        assert_eq!(redacted.signature, report.signature);
+        assert_eq!(
+            redacted.to_bytes()[..0x140],
+            report.to_bytes()[..0x140],
+            "fields before the report IDs changed"
+        );
+    }
+
+    #[test]
+    fn display() {
+        let report = AttestationReport::milan();
+        let display = report.redacted().to_string();
+
+        assert!(display.contains("Chip ID:                      <redacted>\n"));
+        assert!(display.contains("Report ID:                    <redacted>\n"));
+        assert!(!display.contains(&hex::encode(report.report_id)));
+        assert!(display.contains(&format!(
+            "Report ID Migration Agent:    {}\n",
+            hex::encode([0xff; 32])
+        )));
+
+        let mut report = report;
+        report.report_id_ma = [1; 32];
+        let redacted = report.redacted();
+        assert_eq!(redacted.report_id_ma, [0; 32]);
+        assert!(redacted
+            .to_string()
+            .contains("Report ID Migration Agent:    <redacted>\n"));
+        assert!(display.contains(&hex::encode(report.measurement)));
+        assert!(!display.contains(&hex::encode(report.chip_id)));
+    }
+
+    #[test]
+    fn serialize() {
+        let report = AttestationReport::milan();
+        let redacted = report.redacted();
+
+        let json = serde_json::to_value(redacted).unwrap();
+        assert_eq!(json["chip_id"], hex::encode([0; 64]));
+        assert_eq!(json["measurement"], hex::encode(report.measurement));
+    }
+
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    mod keyed_hash {
+        use super::*;
+
+        #[test]
+        fn deterministic() {
+            let report = AttestationReport::milan();
+
+            let first = report.redacted_with(&Redaction::KeyedHash([1; 32]));
+            let second = report.redacted_with(&Redaction::KeyedHash([1; 32]));
+            let other = report.redacted_with(&Redaction::KeyedHash([2; 32]));
+
+            assert_eq!(first.chip_id, second.chip_id);
+            assert_eq!(first.report_id, second.report_id);
+            assert_ne!(first.chip_id, report.chip_id);
+            assert_ne!(first.chip_id, other.chip_id);
+            assert_eq!(first.measurement, report.measurement);
+
+            let display = first.to_string();
+            assert!(display.contains(&format!("{} (keyed hash)", hex::encode(first.chip_id))));
+            assert_eq!(
+                format!("{:?}", Redaction::KeyedHash([1; 32])),
+                "KeyedHash(..)"
+            );
+        }
+
+        #[cfg(feature = "openssl")]
+        #[test]
+        fn hmac() {
+            use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+
+            let report = AttestationReport::milan();
+            let key = [7; 32];
+
+            let pkey = PKey::hmac(&key).unwrap();
+            let mut signer = Signer::new(MessageDigest::sha512(), &pkey).unwrap();
+            signer.update(b"chip_id").unwrap();
+            signer.update(report.chip_id.as_bytes()).unwrap();
+
+            let redacted = report.redacted_with(&Redaction::KeyedHash(key));
+            assert_eq!(
+                redacted.chip_id.as_bytes()[..],
+                signer.sign_to_vec().unwrap()[..]
+            );
+        }
+    }
+}
@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Stable text renderings of attestation reports.
+//!
+//! The [Display](core::fmt::Display) output of a report is meant to be read
+//! by people, and its layout may change between releases. Tools scraping
+//! reports should use one of the renderings below instead:
+//!
+//! - [AttestationReport::to_text_v1] renders one `Label: value` line per
+//!   field, with the values starting at column 33. Its layout is frozen:
+//!   later layouts will be added as new versions.
+//! - [AttestationReport::to_parsable] renders one `key=value` line per
+//!   field. Keys are never renamed or removed, but keys may be added, so
+//!   parsers should skip the keys they do not know.
+//!
+//! Both render the same fields in the same order. Integers are decimal,
+//! byte strings lowercase hex, flags `true` or `false`, and the raw guest
+//! policy, platform info and key info `0x`-prefixed hex.
+
+use super::{AttestationReport, SigningKey};
+
+use crate::firmware::host::TcbVersion;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write;
+
+/// The column the values of [AttestationReport::to_text_v1] start at.
+const TEXT_V1_COLUMN: usize = 32;
+
+/// A field of a rendered report
+struct Field {
+    key: &'static str,
+    label: &'static str,
+    value: String,
+}
+
+/// Collects the fields of a report.
+#[derive(Default)]
+struct Fields(Vec<Field>);
+
+impl Fields {
+    fn push(&mut self, key: &'static str, label: &'static str, value: impl ToString) {
+        self.0.push(Field {
+            key,
+            label,
+            value: value.to_string(),
+        });
+    }
+
+    fn flag(&mut self, key: &'static str, label: &'static str, bit: u64) {
+        self.push(key, label, bit != 0);
+    }
+
+    fn hex(&mut self, key: &'static str, label: &'static str, bytes: &[u8]) {
+        let mut hex = String::with_capacity(bytes.len() * 2);
+        for byte in bytes {
+            // Writing to a String does not fail.
+            let _ = write!(hex, "{byte:02x}");
+        }
+
+        self.push(key, label, hex);
+    }
+
+    fn tcb(&mut self, keys: [&'static str; 4], labels: [&'static str; 4], tcb: &TcbVersion) {
+        let svns = [tcb.bootloader, tcb.tee, tcb.snp, tcb.microcode];
+
+        for ((key, label), svn) in keys.iter().zip(labels.iter()).zip(svns.iter()) {
+            self.push(key, label, svn);
+        }
+    }
+}
+
+/// The keys and labels of the SVNs of the TCB version `$key`.
+macro_rules! tcb_fields {
+    ($key:literal, $label:literal) => {
+        (
+            [
+                concat!($key, ".bootloader"),
+                concat!($key, ".tee"),
+                concat!($key, ".snp"),
+                concat!($key, ".microcode"),
+            ],
+            [
+                concat!($label, " Bootloader"),
+                concat!($label, " TEE"),
+                concat!($label, " SNP"),
+                concat!($label, " Microcode"),
+            ],
+        )
+    };
+}
+
+impl AttestationReport {
+    fn fields(&self) -> Fields {
+        let mut fields = Fields::default();
+
+        fields.push("version", "Version", self.version);
+        fields.push("guest_svn", "Guest SVN", self.guest_svn);
+
+        let policy = self.policy;
+        fields.push("policy", "Policy", format!("{:#018x}", u64::from(policy)));
+        fields.push("policy.abi_major", "Policy ABI Major", policy.abi_major());
+        fields.push("policy.abi_minor", "Policy ABI Minor", policy.abi_minor());
+        fields.flag("policy.smt", "Policy SMT Allowed", policy.smt_allowed());
+        fields.flag(
+            "policy.migrate_ma",
+            "Policy Migrate MA",
+            policy.migrate_ma_allowed(),
+        );
+        fields.flag(
+            "policy.debug",
+            "Policy Debug Allowed",
+            policy.debug_allowed(),
+        );
+        fields.flag(
+            "policy.single_socket",
+            "Policy Single Socket",
+            policy.single_socket_required(),
+        );
+
+        fields.hex("family_id", "Family ID", &self.family_id);
+        fields.hex("image_id", "Image ID", &self.image_id);
+        fields.push("vmpl", "VMPL", self.vmpl);
+        fields.push("sig_algo", "Signature Algorithm", self.sig_algo);
+
+        let (keys, labels) = tcb_fields!("current_tcb", "Current TCB");
+        fields.tcb(keys, labels, &self.current_tcb);
+
+        let info = self.plat_info;
+        fields.push(
+            "plat_info",
+            "Platform Info",
+            format!("{:#018x}", u64::from(info)),
+        );
+        fields.flag("plat_info.smt", "Platform SMT Enabled", info.smt_enabled());
+        fields.flag(
+            "plat_info.tsme",
+            "Platform TSME Enabled",
+            info.tsme_enabled(),
+        );
+        fields.flag("plat_info.ecc", "Platform ECC Enabled", info.ecc_enabled());
+        fields.flag(
+            "plat_info.rapl_disabled",
+            "Platform RAPL Disabled",
+            info.rapl_disabled(),
+        );
+        fields.flag(
+            "plat_info.ciphertext_hiding",
+            "Platform Ciphertext Hiding",
+            info.ciphertext_hiding_enabled(),
+        );
+
+        let key_info = self.key_info;
+        fields.push(
+            "key_info",
+            "Key Info",
+            format!("{:#010x}", u32::from(key_info)),
+        );
+        fields.flag(
+            "key_info.author_key_en",
+            "Author Key Enabled",
+            key_info.author_key_en().into(),
+        );
+        fields.flag(
+            "key_info.mask_chip_key",
+            "Mask Chip Key",
+            key_info.mask_chip_key().into(),
+        );
+        let signing_key = match key_info.signing_key() {
+            Ok(SigningKey::Vcek) => "vcek".to_string(),
+            Ok(SigningKey::Vlek) => "vlek".to_string(),
+            Ok(SigningKey::None) => "none".to_string(),
+            Err(raw) => format!("reserved({raw})"),
+        };
+        fields.push("key_info.signing_key", "Signing Key", signing_key);
+
+        fields.hex("report_data", "Report Data", self.report_data.as_bytes());
+        fields.hex("measurement", "Measurement", self.measurement.as_bytes());
+        fields.hex("host_data", "Host Data", &self.host_data);
+        fields.hex(
+            "id_key_digest",
+            "ID Key Digest",
+            self.id_key_digest.as_bytes(),
+        );
+        fields.hex(
+            "author_key_digest",
+            "Author Key Digest",
+            self.author_key_digest.as_bytes(),
+        );
+        fields.hex("report_id", "Report ID", &self.report_id);
+        fields.hex(
+            "report_id_ma",
+            "Report ID Migration Agent",
+            &self.report_id_ma,
+        );
+
+        let (keys, labels) = tcb_fields!("reported_tcb", "Reported TCB");
+        fields.tcb(keys, labels, &self.reported_tcb);
+
+        fields.hex("chip_id", "Chip ID", self.chip_id.as_bytes());
+
+        let (keys, labels) = tcb_fields!("committed_tcb", "Committed TCB");
+        fields.tcb(keys, labels, &self.committed_tcb);
+
+        fields.push("current_build", "Current Build", self.current_build);
+        fields.push("current_minor", "Current Minor", self.current_minor);
+        fields.push("current_major", "Current Major", self.current_major);
+        fields.push("committed_build", "Committed Build", self.committed_build);
+        fields.push("committed_minor", "Committed Minor", self.committed_minor);
+        fields.push("committed_major", "Committed Major", self.committed_major);
+
+        let (keys, labels) = tcb_fields!("launch_tcb", "Launch TCB");
+        fields.tcb(keys, labels, &self.launch_tcb);
+
+        fields.hex("signature.r", "Signature R", self.signature.r());
+        fields.hex("signature.s", "Signature S", self.signature.s());
+
+        fields
+    }
+
+    /// Render the report in version 1 of its text layout: a header line,
+    /// then one `Label: value` line per field, the values starting at
+    /// column 33.
+    ///
+    /// Unlike [Display](core::fmt::Display), this layout never changes.
+    pub fn to_text_v1(&self) -> String {
+        let mut text = String::from("SEV-SNP Attestation Report (text v1)\n");
+
+        for field in self.fields().0 {
+            let label = format!("{}:", field.label);
+            // Writing to a String does not fail.
+            let _ = writeln!(text, "{label:<TEXT_V1_COLUMN$}{}", field.value);
+        }
+
+        text
+    }
+
+    /// Render the report as one `key=value` line per field, for tools to
+    /// parse.
+    pub fn to_parsable(&self) -> String {
+        let mut text = String::new();
+
+        for field in self.fields().0 {
+            // Writing to a String does not fail.
+            let _ = writeln!(text, "{}={}", field.key, field.value);
+        }
+
+        text
+    }
+}
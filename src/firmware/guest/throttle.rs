@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serializing guest requests between threads.
+//!
+//! Guest requests are encrypted with the VMPCK under a single message
+//! sequence number, and the AMD Secure Processor rate limits them, so
+//! threads issuing requests concurrently (e.g. fetching reports for every
+//! incoming connection) make each other fail. A [RequestThrottle] issues
+//! the requests of every [Firmware](super::Firmware) handle sharing it one
+//! at a time, in the order they were made, and optionally spaces them out.
+//! [RequestThrottle::global] is a throttle shared by the whole process.
+//!
+//! # Example:
+//! ```ignore
+//! RequestThrottle::global().set_min_interval(Duration::from_millis(10));
+//!
+//! let mut fw = Firmware::open()?.throttled(RequestThrottle::global());
+//! let report = fw.get_report(None, Some(data), None)?;
+//!
+//! let stats = RequestThrottle::global().stats();
+//! println!("{} requests waited {:?} at most", stats.requests, stats.max_wait);
+//! ```
+
+use std::{
+    convert::TryFrom,
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// The throttle shared by the whole process.
+static GLOBAL: RequestThrottle = RequestThrottle::new(Duration::ZERO);
+
+/// The metrics of a [RequestThrottle]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThrottleStats {
+    /// How many requests were issued.
+    pub requests: u64,
+
+    /// How many of the requests had to wait for another one.
+    pub contended: u64,
+
+    /// The time all the requests waited.
+    pub total_wait: Duration,
+
+    /// The time the longest waiting request waited.
+    pub max_wait: Duration,
+
+    /// The most requests waiting at once.
+    pub max_waiting: usize,
+}
+
+impl ThrottleStats {
+    /// The average time a request waited.
+    pub fn mean_wait(&self) -> Duration {
+        match u32::try_from(self.requests) {
+            Ok(0) => Duration::ZERO,
+            Ok(requests) => self.total_wait / requests,
+            Err(_) => self.total_wait.div_f64(self.requests as f64),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    /// The ticket of the next request to be made.
+    next: u64,
+
+    /// The ticket of the request which may be issued.
+    serving: u64,
+
+    /// How many requests are waiting.
+    waiting: usize,
+
+    /// The minimum time between the end of a request and the next.
+    min_interval: Duration,
+
+    /// When the last request ended.
+    released: Option<Instant>,
+
+    stats: ThrottleStats,
+}
+
+/// Issues guest requests one at a time, first come first served
+#[derive(Debug)]
+pub struct RequestThrottle {
+    state: Mutex<State>,
+    turn: Condvar,
+}
+
+impl RequestThrottle {
+    /// Create a throttle which waits at least `min_interval` between the end
+    /// of a request and the start of the next.
+    pub const fn new(min_interval: Duration) -> Self {
+        Self {
+            state: Mutex::new(State {
+                next: 0,
+                serving: 0,
+                waiting: 0,
+                min_interval,
+                released: None,
+                stats: ThrottleStats {
+                    requests: 0,
+                    contended: 0,
+                    total_wait: Duration::ZERO,
+                    max_wait: Duration::ZERO,
+                    max_waiting: 0,
+                },
+            }),
+            turn: Condvar::new(),
+        }
+    }
+
+    /// The throttle shared by the whole process, which does not space
+    /// requests out unless told to.
+    pub fn global() -> &'static Self {
+        &GLOBAL
+    }
+
+    /// Wait at least `min_interval` between requests from now on.
+    pub fn set_min_interval(&self, min_interval: Duration) {
+        self.lock().min_interval = min_interval;
+        self.turn.notify_all();
+    }
+
+    /// The minimum time between requests.
+    pub fn min_interval(&self) -> Duration {
+        self.lock().min_interval
+    }
+
+    /// Wait for the turn of a request, which lasts as long as the returned
+    /// permit. Requests get their turns in the order they called this.
+    pub fn acquire(&self) -> ThrottlePermit<'_> {
+        let start = Instant::now();
+        let mut state = self.lock();
+
+        let ticket = state.next;
+        state.next += 1;
+        let contended = ticket != state.serving;
+
+        state.waiting += 1;
+        state.stats.max_waiting = state.stats.max_waiting.max(state.waiting);
+
+        loop {
+            if ticket != state.serving {
+                state = self.turn.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            }
+
+            let ready = state.released.map(|released| released + state.min_interval);
+            match ready.and_then(|ready| ready.checked_duration_since(Instant::now())) {
+                Some(delay) if !delay.is_zero() => {
+                    state = self
+                        .turn
+                        .wait_timeout(state, delay)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+                _ => break,
+            }
+        }
+
+        let waited = start.elapsed();
+
+        state.waiting -= 1;
+        state.stats.requests += 1;
+        state.stats.contended += u64::from(contended);
+        state.stats.total_wait += waited;
+        state.stats.max_wait = state.stats.max_wait.max(waited);
+
+        ThrottlePermit(self)
+    }
+
+    /// How many requests are waiting for their turn.
+    pub fn waiting(&self) -> usize {
+        self.lock().waiting
+    }
+
+    /// The metrics of the requests issued so far.
+    pub fn stats(&self) -> ThrottleStats {
+        self.lock().stats.clone()
+    }
+
+    /// Forget the requests issued so far.
+    pub fn reset_stats(&self) {
+        self.lock().stats = ThrottleStats::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn release(&self) {
+        let mut state = self.lock();
+        state.serving += 1;
+        state.released = Some(Instant::now());
+        drop(state);
+
+        self.turn.notify_all();
+    }
+}
+
+impl Default for RequestThrottle {
+    fn default() -> Self {
+        Self::new(Duration::ZERO)
+    }
+}
+
+/// The turn of a request, which ends when the permit is dropped
+#[derive(Debug)]
+#[must_use = "the turn ends when the permit is dropped"]
+pub struct ThrottlePermit<'a>(&'a RequestThrottle);
+
+impl Drop for ThrottlePermit<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    #[test]
+    fn test_fifo() {
+        let throttle = Arc::new(RequestThrottle::default());
+        let order = Arc::new(Mutex::new(vec![]));
+
+        let permit = throttle.acquire();
+
+        let mut threads = vec![];
+        for i in 0..4 {
+            let (queued, order) = (throttle.clone(), order.clone());
+            threads.push(thread::spawn(move || {
+                let _permit = queued.acquire();
+                order.lock().unwrap().push(i);
+            }));
+
+            // Queue the threads one after the other.
+            while throttle.waiting() != i + 1 {
+                thread::yield_now();
+            }
+        }
+
+        drop(permit);
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3]);
+
+        let stats = throttle.stats();
+        assert_eq!(stats.requests, 5);
+        assert_eq!(stats.contended, 4);
+        assert_eq!(stats.max_waiting, 4);
+        assert!(stats.max_wait >= stats.mean_wait());
+
+        throttle.reset_stats();
+        assert_eq!(throttle.stats(), ThrottleStats::default());
+    }
+
+    #[test]
+    fn test_min_interval() {
+        let throttle = RequestThrottle::new(Duration::from_millis(20));
+
+        drop(throttle.acquire());
+        let released = Instant::now();
+        drop(throttle.acquire());
+        assert!(released.elapsed() >= Duration::from_millis(20));
+
+        throttle.set_min_interval(Duration::ZERO);
+        assert_eq!(throttle.min_interval(), Duration::ZERO);
+        assert_eq!(throttle.stats().contended, 0);
+    }
+
+    #[test]
+    fn test_global() {
+        assert!(std::ptr::eq(RequestThrottle::global(), &GLOBAL));
+    }
+}
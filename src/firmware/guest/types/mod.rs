@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
-#[cfg(feature = "snp")]
+#[cfg(feature = "snp-types")]
 mod snp;
 
-#[cfg(feature = "snp")]
+#[cfg(feature = "snp-types")]
 pub use self::snp::*;
@@ -1,21 +1,46 @@
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{certs::snp::ecdsa::Signature, firmware::host::TcbVersion, util::hexdump};
+use crate::{
+    certs::snp::ecdsa::Signature,
+    encoding::{
+        write_hex, AuthorKeyDigest, ChipId, HexBytes, IdKeyDigest, Measurement48, ReportData,
+    },
+    firmware::{guest::redact::Redacted, host::TcbVersion},
+    util::{LeReader, LeWriter},
+    Build, Version,
+};
+
+#[cfg(feature = "std")]
+use crate::{
+    error::{MigrationAgentError, ParseError},
+    ParseOptions,
+};
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
-use crate::certs::snp::{Chain, Verifiable};
+#[cfg(any(feature = "std", feature = "openssl", feature = "crypto_nossl"))]
+use crate::firmware::guest::layout;
 
-use std::fmt::Display;
+#[cfg(feature = "proto")]
+use crate::{error::ProtoError, proto};
 
-#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
-use std::{
-    convert::TryFrom,
-    io::{self, Error, ErrorKind},
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+use crate::{
+    certs::snp::{Chain, Verifiable},
+    error::VerificationError,
+    firmware::host::CertType,
+};
+
+use alloc::{format, string::ToString};
+use core::{
+    array::TryFromSliceError,
+    convert::{TryFrom, TryInto},
+    fmt::Display,
+    mem::size_of,
 };
 
 use bitfield::bitfield;
+use static_assertions::const_assert;
 
-#[cfg(feature = "openssl")]
+#[cfg(all(feature = "snp", feature = "openssl"))]
 use openssl::{ecdsa::EcdsaSig, sha::Sha384};
 
 use serde::{Deserialize, Serialize};
@@ -143,26 +168,22 @@ pub struct AttestationReport {
     pub current_tcb: TcbVersion,
     /// Information about the platform. See PlatformInfo
     pub plat_info: PlatformInfo,
-    /// Private variable as only the first bit is important.
-    /// See [author_key_en()](self::AttestationReport::author_key_en).
-    _author_key_en: u32,
+    /// Information about the key used to sign this report. See KeyInfo
+    pub key_info: KeyInfo,
     _reserved_0: u32,
-    #[serde(with = "BigArray")]
     /// Guest-provided 512 Bits of Data
-    pub report_data: [u8; 64],
-    #[serde(with = "BigArray")]
+    pub report_data: ReportData,
     /// The measurement calculated at launch.
-    pub measurement: [u8; 48],
+    pub measurement: Measurement48,
+    #[serde(with = "crate::encoding::serde_hex")]
     /// Data provided by the hypervisor at launch.
     pub host_data: [u8; 32],
-    #[serde(with = "BigArray")]
     /// SHA-384 digest of the ID public key that signed the ID block provided
     /// in SNP_LANUNCH_FINISH.
-    pub id_key_digest: [u8; 48],
-    #[serde(with = "BigArray")]
+    pub id_key_digest: IdKeyDigest,
     /// SHA-384 digest of the Author public key that certified the ID key,
     /// if provided in SNP_LAUNCH_FINSIH. Zeroes if AUTHOR_KEY_EN is 1.
-    pub author_key_digest: [u8; 48],
+    pub author_key_digest: AuthorKeyDigest,
     /// Report ID of this guest.
     pub report_id: [u8; 32],
     /// Report ID of this guest's migration agent (if applicable).
@@ -170,10 +191,9 @@ pub struct AttestationReport {
     /// Reported TCB version used to derive the VCEK that signed this report.
     pub reported_tcb: TcbVersion,
     _reserved_1: [u8; 24],
-    #[serde(with = "BigArray")]
     /// If MaskChipId is set to 0, Identifier unique to the chip.
     /// Otherwise set to 0h.
-    pub chip_id: [u8; 64],
+    pub chip_id: ChipId,
     /// CommittedTCB
     pub committed_tcb: TcbVersion,
     /// The build number of CurrentVersion
@@ -199,12 +219,6 @@ pub struct AttestationReport {
     pub signature: Signature,
 }
 
-impl AttestationReport {
-    fn author_key_en(&self) -> bool {
-        self._author_key_en == 1
-    }
-}
-
 impl Default for AttestationReport {
     fn default() -> Self {
         Self {
@@ -217,18 +231,18 @@ impl Default for AttestationReport {
             sig_algo: Default::default(),
             current_tcb: Default::default(),
             plat_info: Default::default(),
-            _author_key_en: Default::default(),
+            key_info: Default::default(),
             _reserved_0: Default::default(),
-            report_data: [0; 64],
-            measurement: [0; 48],
+            report_data: Default::default(),
+            measurement: Default::default(),
             host_data: Default::default(),
-            id_key_digest: [0; 48],
-            author_key_digest: [0; 48],
+            id_key_digest: Default::default(),
+            author_key_digest: Default::default(),
             report_id: Default::default(),
             report_id_ma: Default::default(),
             reported_tcb: Default::default(),
             _reserved_1: Default::default(),
-            chip_id: [0; 64],
+            chip_id: Default::default(),
             committed_tcb: Default::default(),
             current_build: Default::default(),
             current_minor: Default::default(),
@@ -245,8 +259,318 @@ impl Default for AttestationReport {
     }
 }
 
+impl AttestationReport {
+    /// Size (in bytes) of an attestation report.
+    pub const SIZE: usize = 0x4a0;
+
+    /// Decode a report from the bytes returned by the AMD Secure Processor.
+    ///
+    /// Every field is a little-endian integer or a byte array, so any bytes
+    /// are a valid report, whatever the byte order of the host.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let mut reader = LeReader::new(bytes);
+
+        Self {
+            version: reader.u32(),
+            guest_svn: reader.u32(),
+            policy: GuestPolicy(reader.u64()),
+            family_id: reader.bytes(),
+            image_id: reader.bytes(),
+            vmpl: reader.u32(),
+            sig_algo: reader.u32(),
+            current_tcb: reader.u64().into(),
+            plat_info: PlatformInfo(reader.u64()),
+            key_info: KeyInfo(reader.u32()),
+            _reserved_0: reader.u32(),
+            report_data: ReportData(reader.bytes()),
+            measurement: Measurement48(reader.bytes()),
+            host_data: reader.bytes(),
+            id_key_digest: IdKeyDigest(reader.bytes()),
+            author_key_digest: AuthorKeyDigest(reader.bytes()),
+            report_id: reader.bytes(),
+            report_id_ma: reader.bytes(),
+            reported_tcb: reader.u64().into(),
+            _reserved_1: reader.bytes(),
+            chip_id: ChipId(reader.bytes()),
+            committed_tcb: reader.u64().into(),
+            current_build: reader.u8(),
+            current_minor: reader.u8(),
+            current_major: reader.u8(),
+            _reserved_2: reader.u8(),
+            committed_build: reader.u8(),
+            committed_minor: reader.u8(),
+            committed_major: reader.u8(),
+            _reserved_3: reader.u8(),
+            launch_tcb: reader.u64().into(),
+            _reserved_4: reader.bytes(),
+            signature: Signature::from_bytes(&reader.bytes()),
+        }
+    }
+
+    /// Encode the report as the AMD Secure Processor lays it out.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let mut writer = LeWriter::new(&mut bytes);
+
+        writer.u32(self.version);
+        writer.u32(self.guest_svn);
+        writer.u64(self.policy.0);
+        writer.bytes(&self.family_id);
+        writer.bytes(&self.image_id);
+        writer.u32(self.vmpl);
+        writer.u32(self.sig_algo);
+        writer.u64(self.current_tcb.into());
+        writer.u64(self.plat_info.0);
+        writer.u32(self.key_info.0);
+        writer.u32(self._reserved_0);
+        writer.bytes(self.report_data.as_bytes());
+        writer.bytes(self.measurement.as_bytes());
+        writer.bytes(&self.host_data);
+        writer.bytes(self.id_key_digest.as_bytes());
+        writer.bytes(self.author_key_digest.as_bytes());
+        writer.bytes(&self.report_id);
+        writer.bytes(&self.report_id_ma);
+        writer.u64(self.reported_tcb.into());
+        writer.bytes(&self._reserved_1);
+        writer.bytes(self.chip_id.as_bytes());
+        writer.u64(self.committed_tcb.into());
+        writer.u8(self.current_build);
+        writer.u8(self.current_minor);
+        writer.u8(self.current_major);
+        writer.u8(self._reserved_2);
+        writer.u8(self.committed_build);
+        writer.u8(self.committed_minor);
+        writer.u8(self.committed_major);
+        writer.u8(self._reserved_3);
+        writer.u64(self.launch_tcb.into());
+        writer.bytes(&self._reserved_4);
+        writer.bytes(&self.signature.to_bytes());
+
+        bytes
+    }
+
+    /// Earliest report version this crate knows.
+    pub const MIN_VERSION: u32 = 2;
+
+    /// Latest report version this crate knows.
+    pub const MAX_VERSION: u32 = 5;
+
+    /// Decode a report from `bytes`, validating its version and reserved
+    /// bytes as `options` require. A version this crate does not know is
+    /// decoded with the layout of [Self::MAX_VERSION].
+    #[cfg(feature = "std")]
+    pub fn from_bytes_with(bytes: &[u8], options: ParseOptions) -> Result<Self, ParseError> {
+        let bytes: &[u8; Self::SIZE] = bytes.try_into().map_err(|_| ParseError::InvalidLength {
+            expected: Self::SIZE,
+            actual: bytes.len(),
+        })?;
+        let report = Self::from_bytes(bytes);
+
+        let known = (Self::MIN_VERSION..=Self::MAX_VERSION).contains(&report.version);
+        if !known && !options.allow_unknown_versions {
+            return Err(ParseError::UnknownVersion(report.version));
+        }
+
+        if options.strict_reserved {
+            for field in layout::fields(report.version) {
+                if !field.is_reserved() {
+                    continue;
+                }
+
+                if let Some(pos) = bytes[field.range()].iter().position(|b| *b != 0) {
+                    return Err(ParseError::Reserved(field.offset + pos));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// The report ID of the guest's migration agent, or None if the guest
+    /// is not associated with one (REPORT_ID_MA is all ones).
+    pub fn migration_agent(&self) -> Option<[u8; 32]> {
+        if self.report_id_ma == [0xff; 32] {
+            None
+        } else {
+            Some(self.report_id_ma)
+        }
+    }
+
+    /// Check the guest's association with a migration agent against the
+    /// `agent`'s own attestation report, or that the guest has no migration
+    /// agent if `agent` is None.
+    #[cfg(feature = "std")]
+    pub fn check_migration_agent(
+        &self,
+        agent: Option<&AttestationReport>,
+    ) -> Result<(), MigrationAgentError> {
+        match (self.migration_agent(), agent) {
+            (None, None) => Ok(()),
+            (None, Some(_)) => Err(MigrationAgentError::MissingAgent),
+            (Some(_), None) => Err(MigrationAgentError::UnexpectedAgent),
+            (Some(_), Some(_)) if self.policy.migrate_ma_allowed() == 0 => {
+                Err(MigrationAgentError::AssociationForbidden)
+            }
+            (Some(id), Some(agent)) if id != agent.report_id => {
+                Err(MigrationAgentError::AgentMismatch)
+            }
+            (Some(_), Some(_)) => Ok(()),
+        }
+    }
+
+    /// The version of the firmware which generated the report
+    /// (CURRENT_MAJOR, CURRENT_MINOR and CURRENT_BUILD).
+    pub fn current_version(&self) -> Build {
+        Build {
+            version: Version {
+                major: self.current_major,
+                minor: self.current_minor,
+            },
+            build: self.current_build,
+        }
+    }
+
+    /// The committed version of the firmware, which the platform cannot
+    /// be rolled back below (COMMITTED_MAJOR, COMMITTED_MINOR and
+    /// COMMITTED_BUILD).
+    pub fn committed_version(&self) -> Build {
+        Build {
+            version: Version {
+                major: self.committed_major,
+                minor: self.committed_minor,
+            },
+            build: self.committed_build,
+        }
+    }
+}
+
+const_assert!(size_of::<AttestationReport>() == AttestationReport::SIZE);
+
+#[cfg(feature = "std")]
+impl crate::snapshot::Versioned for AttestationReport {
+    const KIND: [u8; 4] = *b"SNPR";
+    const VERSION: u16 = 1;
+}
+
+impl TryFrom<&[u8]> for AttestationReport {
+    type Error = TryFromSliceError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self::from_bytes(bytes.try_into()?))
+    }
+}
+
+#[cfg(all(test, feature = "snp"))]
+impl AttestationReport {
+    /// The report of a Milan guest from the test data, signed by the VCEK
+    /// in `tests/certs_data/vcek_milan.der`.
+    pub(crate) fn milan() -> Self {
+        let report = include_bytes!("../../../../tests/certs_data/report_milan.hex");
+        Self::try_from(&hex::decode(report).unwrap()[..]).unwrap()
+    }
+}
+
+#[cfg(feature = "proto")]
+impl From<&AttestationReport> for proto::AttestationReport {
+    fn from(report: &AttestationReport) -> Self {
+        let mut reserved = report._reserved_0.to_le_bytes().to_vec();
+        reserved.extend_from_slice(&report._reserved_1);
+        reserved.extend_from_slice(&[report._reserved_2, report._reserved_3]);
+
+        Self {
+            version: report.version,
+            guest_svn: report.guest_svn,
+            policy: report.policy.0,
+            family_id: report.family_id.to_vec(),
+            image_id: report.image_id.to_vec(),
+            vmpl: report.vmpl,
+            sig_algo: report.sig_algo,
+            current_tcb: Some(report.current_tcb.into()),
+            plat_info: report.plat_info.0,
+            key_info: report.key_info.0,
+            report_data: report.report_data.as_bytes().to_vec(),
+            measurement: report.measurement.as_bytes().to_vec(),
+            host_data: report.host_data.to_vec(),
+            id_key_digest: report.id_key_digest.as_bytes().to_vec(),
+            author_key_digest: report.author_key_digest.as_bytes().to_vec(),
+            report_id: report.report_id.to_vec(),
+            report_id_ma: report.report_id_ma.to_vec(),
+            reported_tcb: Some(report.reported_tcb.into()),
+            chip_id: report.chip_id.as_bytes().to_vec(),
+            committed_tcb: Some(report.committed_tcb.into()),
+            current_build: report.current_build.into(),
+            current_minor: report.current_minor.into(),
+            current_major: report.current_major.into(),
+            committed_build: report.committed_build.into(),
+            committed_minor: report.committed_minor.into(),
+            committed_major: report.committed_major.into(),
+            launch_tcb: Some(report.launch_tcb.into()),
+            signature: Some((&report.signature).into()),
+            reserved,
+            reserved_tail: report._reserved_4.to_vec(),
+        }
+    }
+}
+
+#[cfg(feature = "proto")]
+impl TryFrom<proto::AttestationReport> for AttestationReport {
+    type Error = ProtoError;
+
+    fn try_from(report: proto::AttestationReport) -> Result<Self, Self::Error> {
+        let reserved: [u8; 30] = proto::array("reserved", &report.reserved)?;
+
+        Ok(Self {
+            version: report.version,
+            guest_svn: report.guest_svn,
+            policy: GuestPolicy(report.policy),
+            family_id: proto::array("family_id", &report.family_id)?,
+            image_id: proto::array("image_id", &report.image_id)?,
+            vmpl: report.vmpl,
+            sig_algo: report.sig_algo,
+            current_tcb: proto::required("current_tcb", report.current_tcb)?.try_into()?,
+            plat_info: PlatformInfo(report.plat_info),
+            key_info: KeyInfo(report.key_info),
+            _reserved_0: u32::from_le_bytes([reserved[0], reserved[1], reserved[2], reserved[3]]),
+            report_data: proto::array("report_data", &report.report_data)?.into(),
+            measurement: proto::array("measurement", &report.measurement)?.into(),
+            host_data: proto::array("host_data", &report.host_data)?,
+            id_key_digest: proto::array("id_key_digest", &report.id_key_digest)?.into(),
+            author_key_digest: proto::array("author_key_digest", &report.author_key_digest)?.into(),
+            report_id: proto::array("report_id", &report.report_id)?,
+            report_id_ma: proto::array("report_id_ma", &report.report_id_ma)?,
+            reported_tcb: proto::required("reported_tcb", report.reported_tcb)?.try_into()?,
+            _reserved_1: proto::array("reserved", &reserved[4..28])?,
+            chip_id: proto::array("chip_id", &report.chip_id)?.into(),
+            committed_tcb: proto::required("committed_tcb", report.committed_tcb)?.try_into()?,
+            current_build: proto::narrow("current_build", report.current_build)?,
+            current_minor: proto::narrow("current_minor", report.current_minor)?,
+            current_major: proto::narrow("current_major", report.current_major)?,
+            _reserved_2: reserved[28],
+            committed_build: proto::narrow("committed_build", report.committed_build)?,
+            committed_minor: proto::narrow("committed_minor", report.committed_minor)?,
+            committed_major: proto::narrow("committed_major", report.committed_major)?,
+            _reserved_3: reserved[29],
+            launch_tcb: proto::required("launch_tcb", report.launch_tcb)?.try_into()?,
+            _reserved_4: proto::array("reserved_tail", &report.reserved_tail)?,
+            signature: proto::required("signature", report.signature)?.try_into()?,
+        })
+    }
+}
+
 impl Display for AttestationReport {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.fmt_redacted(f, None)
+    }
+}
+
+impl AttestationReport {
+    /// Write the report as [Display] does, marking the chip and report IDs
+    /// as `redacted` if they are.
+    pub(crate) fn fmt_redacted(
+        &self,
+        f: &mut core::fmt::Formatter<'_>,
+        redacted: Option<Redacted>,
+    ) -> core::fmt::Result {
         write!(
             f,
             r#"
@@ -261,7 +585,7 @@ Signature Algorithm:          {}
 Current TCB:
 {}
 {}
-Author Key Encryption:        {}
+{}
 Report Data:                  {}
 Measurement:                  {}
 Host Data:                    {}
@@ -283,26 +607,29 @@ Launch TCB:
 {}
 {}
 "#,
-            std::mem::size_of_val(self),
+            core::mem::size_of_val(self),
             self.version,
             self.guest_svn,
             self.policy,
-            hexdump(&self.family_id),
-            hexdump(&self.image_id),
+            HexBytes(self.family_id),
+            HexBytes(self.image_id),
             self.vmpl,
             self.sig_algo,
             self.current_tcb,
             self.plat_info,
-            self.author_key_en(),
-            hexdump(&self.report_data),
-            hexdump(&self.measurement),
-            hexdump(&self.host_data),
-            hexdump(&self.id_key_digest),
-            hexdump(&self.author_key_digest),
-            hexdump(&self.report_id),
-            hexdump(&self.report_id_ma),
+            self.key_info,
+            self.report_data,
+            self.measurement,
+            HexBytes(self.host_data),
+            self.id_key_digest,
+            self.author_key_digest,
+            Identifier(&self.report_id, redacted),
+            Identifier(
+                &self.report_id_ma,
+                redacted.filter(|_| self.migration_agent().is_some())
+            ),
             self.reported_tcb,
-            hexdump(&self.chip_id),
+            Identifier(self.chip_id.as_bytes(), redacted),
             self.committed_tcb,
             self.current_build,
             self.current_minor,
@@ -316,77 +643,304 @@ Launch TCB:
     }
 }
 
-#[cfg(feature = "openssl")]
+/// A chip or report ID, printed as hex unless redacted.
+struct Identifier<'a>(&'a [u8], Option<Redacted>);
+
+impl Display for Identifier<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.1 {
+            None => write_hex(f, self.0),
+            Some(Redacted::Masked) => write!(f, "<redacted>"),
+            #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+            Some(Redacted::Hashed) => {
+                write_hex(f, self.0)?;
+                write!(f, " (keyed hash)")
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+impl AttestationReport {
+    /// The type of the certificate of the key which signs the report, the
+    /// VCEK or the VLEK, as its [KeyInfo::signing_key] names it.
+    ///
+    /// Fails with [VerificationError::Unsigned] if the report is not signed,
+    /// as no certificate then verifies it.
+    pub fn vek_type(&self) -> Result<CertType, VerificationError> {
+        match self.key_info.signing_key() {
+            Ok(SigningKey::Vcek) => Ok(CertType::VCEK),
+            Ok(SigningKey::Vlek) => Ok(CertType::VLEK),
+            Ok(SigningKey::None) => Err(VerificationError::Unsigned),
+            Err(raw) => Err(VerificationError::ReservedSigningKey(raw)),
+        }
+    }
+}
+
+#[cfg(all(feature = "snp", feature = "openssl"))]
 impl Verifiable for (&Chain, &AttestationReport) {
     type Output = ();
 
-    fn verify(self) -> io::Result<Self::Output> {
+    fn verify(self) -> Result<Self::Output, VerificationError> {
+        self.1.vek_type()?;
+
         let vcek = self.0.verify()?;
 
-        let sig = EcdsaSig::try_from(&self.1.signature)?;
-        let measurable_bytes: &[u8] = &bincode::serialize(self.1).map_err(|e| {
-            Error::new(
-                ErrorKind::Other,
-                format!("Unable to serialize bytes: {}", e),
-            )
-        })?[..0x2a0];
+        let sig = EcdsaSig::try_from(&self.1.signature).map_err(VerificationError::parse)?;
+        let measurable_bytes: &[u8] = &self.1.to_bytes()[layout::SIGNED];
 
         let mut hasher = Sha384::new();
         hasher.update(measurable_bytes);
         let base_digest = hasher.finish();
 
-        let ec = vcek.public_key()?.ec_key()?;
+        let ec = vcek
+            .public_key()
+            .and_then(|key| Ok(key.ec_key()?))
+            .map_err(VerificationError::parse)?;
         let signed = sig.verify(&base_digest, &ec)?;
 
         match signed {
             true => Ok(()),
-            false => Err(Error::new(
-                ErrorKind::Other,
-                "VCEK does not sign the attestation report",
-            )),
+            false => Err(VerificationError::ReportSignature),
         }
     }
 }
 
-#[cfg(feature = "crypto_nossl")]
+#[cfg(all(feature = "snp", feature = "crypto_nossl"))]
 impl Verifiable for (&Chain, &AttestationReport) {
     type Output = ();
 
-    fn verify(self) -> io::Result<Self::Output> {
+    fn verify(self) -> Result<Self::Output, VerificationError> {
         // According to Chapter 3 of the [Versioned Chip Endorsement Key (VCEK) Certificate and
         // KDS Interface Specification][spec], the VCEK certificate certifies an ECDSA public key on curve P-384,
         // and the signature hash algorithm is sha384.
         // [spec]: https://www.amd.com/content/dam/amd/en/documents/epyc-technical-docs/specifications/57230.pdf
 
+        self.1.vek_type()?;
+
         let vcek = self.0.verify()?;
 
-        let sig = p384::ecdsa::Signature::try_from(&self.1.signature)?;
+        let sig = p384::ecdsa::Signature::try_from(&self.1.signature)
+            .map_err(VerificationError::parse)?;
 
-        let measurable_bytes: &[u8] = &bincode::serialize(self.1).map_err(|e| {
-            Error::new(
-                ErrorKind::Other,
-                format!("Unable to serialize bytes: {}", e),
-            )
-        })?[..0x2a0];
+        let measurable_bytes: &[u8] = &self.1.to_bytes()[layout::SIGNED];
 
         use sha2::Digest;
         let base_digest = sha2::Sha384::new_with_prefix(measurable_bytes);
 
         let verifying_key = p384::ecdsa::VerifyingKey::from_sec1_bytes(vcek.public_key_sec1())
             .map_err(|e| {
-                io::Error::new(
-                    ErrorKind::Other,
-                    format!("failed to deserialize public key from sec1 bytes: {e:?}"),
-                )
+                VerificationError::parse(format!(
+                    "failed to deserialize public key from sec1 bytes: {e:?}"
+                ))
             })?;
 
         use p384::ecdsa::signature::DigestVerifier;
-        verifying_key.verify_digest(base_digest, &sig).map_err(|e| {
-            io::Error::new(
-                ErrorKind::Other,
-                format!("VCEK does not sign the attestation report: {e:?}"),
+        verifying_key
+            .verify_digest(base_digest, &sig)
+            .map_err(|_| VerificationError::ReportSignature)
+    }
+}
+
+#[cfg(all(feature = "simulation", feature = "openssl"))]
+impl AttestationReport {
+    /// Sign the report with the P-384 `key`, as the AMD Secure Processor
+    /// signs it with the VCEK or VLEK: over the SHA-384 digest of the first
+    /// 0x2a0 bytes of the report.
+    ///
+    /// Reports signed with a key certified by a test chain verify against
+    /// that chain, which makes synthetic reports usable in verifier tests.
+    pub fn sign_with(
+        &mut self,
+        key: &openssl::ec::EcKeyRef<openssl::pkey::Private>,
+    ) -> std::io::Result<()> {
+        if key.group().curve_name() != Some(openssl::nid::Nid::SECP384R1) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "attestation reports are signed with P-384 keys",
+            ));
+        }
+
+        let mut hasher = Sha384::new();
+        hasher.update(&self.to_bytes()[layout::SIGNED]);
+        let base_digest = hasher.finish();
+
+        self.signature = EcdsaSig::sign(&base_digest, key)?.into();
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "simulation", feature = "crypto_nossl"))]
+impl AttestationReport {
+    /// Sign the report with the P-384 `key`, as the AMD Secure Processor
+    /// signs it with the VCEK or VLEK: over the SHA-384 digest of the first
+    /// 0x2a0 bytes of the report.
+    ///
+    /// Reports signed with a key certified by a test chain verify against
+    /// that chain, which makes synthetic reports usable in verifier tests.
+    pub fn sign_with(&mut self, key: &p384::ecdsa::SigningKey) -> std::io::Result<()> {
+        use p384::ecdsa::signature::DigestSigner;
+        use sha2::Digest;
+
+        let base_digest = sha2::Sha384::new_with_prefix(&self.to_bytes()[layout::SIGNED]);
+
+        let sig: p384::ecdsa::Signature = key.try_sign_digest(base_digest).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("failed to sign attestation report: {e:?}"),
             )
-        })
+        })?;
+
+        self.signature = sig.into();
+        Ok(())
+    }
+}
+
+/// The key the guest asks the AMD Secure Processor to sign an attestation
+/// report with.
+///
+/// Selecting a VLEK is only honored by firmware which supports VLEKs
+/// (SEV-SNP Firmware ABI 1.54 and newer) and only when a VLEK hashstick
+/// has been loaded by the host.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum KeySelect {
+    /// Sign with the VLEK if one is installed, otherwise with the VCEK.
+    #[default]
+    Any = 0,
+
+    /// Sign with the Versioned Chip Endorsement Key (VCEK).
+    Vcek = 1,
+
+    /// Sign with the Versioned Loaded Endorsement Key (VLEK).
+    Vlek = 2,
+}
+
+impl From<KeySelect> for u32 {
+    fn from(value: KeySelect) -> Self {
+        value as u32
+    }
+}
+
+impl Display for KeySelect {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let key = match self {
+            KeySelect::Any => "any",
+            KeySelect::Vcek => "VCEK",
+            KeySelect::Vlek => "VLEK",
+        };
+        write!(f, "{key}")
+    }
+}
+
+/// The key which signed an attestation report, as encoded in the
+/// SIGNING_KEY field of [KeyInfo].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum SigningKey {
+    /// The report was signed by the Versioned Chip Endorsement Key (VCEK).
+    Vcek,
+
+    /// The report was signed by the Versioned Loaded Endorsement Key (VLEK).
+    Vlek,
+
+    /// The report is not signed.
+    None,
+}
+
+impl TryFrom<u32> for SigningKey {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SigningKey::Vcek),
+            1 => Ok(SigningKey::Vlek),
+            7 => Ok(SigningKey::None),
+            other => Err(other),
+        }
+    }
+}
+
+impl Display for SigningKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let key = match self {
+            SigningKey::Vcek => "VCEK",
+            SigningKey::Vlek => "VLEK",
+            SigningKey::None => "None",
+        };
+        write!(f, "{key}")
+    }
+}
+
+bitfield! {
+    /// Information about the key used to sign an attestation report.
+    ///
+    /// | Bit(s) | Name          | Description                                                                 |
+    /// |--------|---------------|-----------------------------------------------------------------------------|
+    /// | 0      | AUTHOR_KEY_EN | Indicates that the digest of the author key is present in AUTHOR_KEY_DIGEST. |
+    /// | 1      | MASK_CHIP_KEY | The value of MaskChipKey at the time the report was generated.              |
+    /// | 4:2    | SIGNING_KEY   | 0: VCEK.<br>1: VLEK.<br>7: None.<br>Other values are reserved.              |
+    /// | 31:5   | -             | Reserved.                                                                   |
+    #[derive(Default, Clone, Copy, Eq, PartialEq)]
+    #[derive(Deserialize, Serialize)]
+    #[repr(C)]
+    pub struct KeyInfo(u32);
+    impl Debug;
+    /// AUTHOR_KEY_EN field: Indicates the author key digest is present.
+    pub author_key_en, _: 0, 0;
+    /// MASK_CHIP_KEY field: Indicates that the VCEK was masked.
+    pub mask_chip_key, _: 1, 1;
+    signing_key_bits, _: 4, 2;
+}
+
+impl KeyInfo {
+    /// The key which signed the report, or the raw SIGNING_KEY value if it
+    /// is reserved by the specification.
+    pub fn signing_key(&self) -> Result<SigningKey, u32> {
+        SigningKey::try_from(self.signing_key_bits())
+    }
+
+    /// Check whether the report was signed with the requested key.
+    /// [KeySelect::Any] matches any report which carries a signature.
+    pub fn signed_with(&self, key: KeySelect) -> bool {
+        match (key, self.signing_key()) {
+            (KeySelect::Any, Ok(signer)) => signer != SigningKey::None,
+            (KeySelect::Vcek, Ok(SigningKey::Vcek)) => true,
+            (KeySelect::Vlek, Ok(SigningKey::Vlek)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<u32> for KeyInfo {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<KeyInfo> for u32 {
+    fn from(value: KeyInfo) -> Self {
+        value.0
+    }
+}
+
+impl Display for KeyInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let signing_key = match self.signing_key() {
+            Ok(key) => key.to_string(),
+            Err(raw) => format!("Reserved ({raw})"),
+        };
+
+        write!(
+            f,
+            r#"Key Information ({}):
+  Author Key Enabled:         {}
+  Mask Chip Key:              {}
+  Signing Key:                {}"#,
+            self.0,
+            self.author_key_en(),
+            self.mask_chip_key(),
+            signing_key,
+        )
     }
 }
 
@@ -412,7 +966,8 @@ bitfield! {
     /// | 22     | MEM_AES_256_XTS   | 0: Allow either AES 128 XEX or AES 256 XTS for memory encryption.<br>1: Require AES 256 XTS for memory encryption. >
     /// | 23     | RAPL_DIS          | 0: Allow Running Average Power Limit (RAPL).<br>1: RAPL must be disabled.                                          >
     /// | 24     | CIPHERTEXT_HIDING | 0: Ciphertext hiding may be enabled or disabled.<br>1: Ciphertext hiding must be enabled.                          >
-    /// | 63:25  | -                 | Reserved. MBZ.                                                                                                     >
+    /// | 25     | PAGE_SWAP_DISABLE | 0: Allow SNP_PAGE_MOVE, SNP_SWAP_OUT and SNP_SWAP_IN.<br>1: Disallow them for this guest.                          >
+    /// | 63:26  | -                 | Reserved. MBZ.                                                                                                     >
     ///
     #[derive(Default, Clone, Copy,Eq, PartialEq)]
     #[derive(Deserialize, Serialize)]
@@ -440,10 +995,12 @@ bitfield! {
     pub rapl_dis, set_rapl_dis: 23, 23;
     /// CIPHERTEXT_HIDING field: (1) ciphertext hiding must be enabled, (0) ciphertext hiding may be enabled/disabled
     pub ciphertext_hiding, set_ciphertext_hiding: 24, 24;
+    /// PAGE_SWAP_DISABLE field: (1) disable guest support for the SNP_PAGE_MOVE, SNP_SWAP_OUT and SNP_SWAP_IN commands, (0) allow them
+    pub page_swap_disable, set_page_swap_disable: 25, 25;
 }
 
 impl Display for GuestPolicy {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             r#"
@@ -465,12 +1022,76 @@ impl Display for GuestPolicy {
     }
 }
 
+impl GuestPolicy {
+    /// The minimum ABI version of the firmware the guest may be launched
+    /// on (ABI_MAJOR and ABI_MINOR).
+    pub fn abi_version(&self) -> Version {
+        Version {
+            major: self.abi_major() as u8,
+            minor: self.abi_minor() as u8,
+        }
+    }
+
+    /// The policy bits every version of the firmware ABI defines:
+    /// ABI_MINOR, ABI_MAJOR, SMT, the must-be-one bit 17, MIGRATE_MA, DEBUG
+    /// and SINGLE_SOCKET.
+    const BASE_BITS: u64 = (1 << 21) - 1;
+
+    /// The policy bits added by ABI 1.55, along with version 3 of the
+    /// attestation report: CXL_ALLOW, MEM_AES_256_XTS, RAPL_DIS and
+    /// CIPHERTEXT_HIDING.
+    const ABI_1_55_BITS: u64 = 0xf << 21;
+
+    /// The ABI version that added [`Self::ABI_1_55_BITS`].
+    const ABI_1_55: Version = Version {
+        major: 1,
+        minor: 55,
+    };
+
+    /// The policy bit added by ABI 1.57: PAGE_SWAP_DISABLE.
+    const ABI_1_57_BITS: u64 = 1 << 25;
+
+    /// The ABI version that added [`Self::ABI_1_57_BITS`].
+    const ABI_1_57: Version = Version {
+        major: 1,
+        minor: 57,
+    };
+
+    /// Whether `firmware`, which produces attestation reports of version
+    /// `report_version`, may launch a guest with this policy, e.g. as given
+    /// by the version and current firmware version of an attestation report.
+    ///
+    /// The firmware refuses policies whose ABI_MAJOR exceeds its major
+    /// version, or whose ABI_MAJOR equals its major version and whose
+    /// ABI_MINOR exceeds its minor version. It also refuses policies setting
+    /// bits its ABI does not define: the bits added by ABI 1.55 need both
+    /// that ABI and reports of version 3 or later, the bit added by ABI 1.57
+    /// needs that ABI, and the bits above them are reserved.
+    pub fn is_compatible_with(&self, report_version: u32, firmware: Build) -> bool {
+        let mut defined = Self::BASE_BITS;
+        if report_version >= 3 && firmware.version >= Self::ABI_1_55 {
+            defined |= Self::ABI_1_55_BITS;
+        }
+        if firmware.version >= Self::ABI_1_57 {
+            defined |= Self::ABI_1_57_BITS;
+        }
+
+        self.abi_version() <= firmware.version && self.0 & !defined == 0
+    }
+}
+
 impl From<GuestPolicy> for u64 {
     fn from(value: GuestPolicy) -> Self {
         value.0
     }
 }
 
+impl From<u64> for GuestPolicy {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
 bitfield! {
     /// A structure with a bit-field unsigned 64 bit integer:
     /// Bit 0 representing the status of SMT enablement.
@@ -498,8 +1119,14 @@ bitfield! {
     reserved, _: 5, 63;
 }
 
+impl From<PlatformInfo> for u64 {
+    fn from(value: PlatformInfo) -> Self {
+        value.0
+    }
+}
+
 impl Display for PlatformInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             r#"
@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bind the attestation key or a quote of a vTPM to an SEV-SNP attestation
+//! report.
+//!
+//! Deployments pairing SNP reports with the quotes of a vTPM, e.g. the one
+//! an SVSM provides, tie the two together through the report data: the
+//! guest requests its report with the SHA-512 digest of a nonce followed by
+//! the marshalled TPM object (the public area of the attestation key, or
+//! the `TPMS_ATTEST` structure of a quote), the way an SVSM binds the
+//! manifests of its services. The relying party recomputes the digest from
+//! the nonce it issued and the TPM object it received, and checks it
+//! against a verified report before trusting the TPM.
+//!
+//! # Example:
+//! ```ignore
+//! // In the guest:
+//! let data = vtpm::report_data(&nonce, &ak_public);
+//! let report = fw.get_report(None, Some(data), None)?;
+//!
+//! // On the relying party, once the report is verified:
+//! vtpm::verify_binding(&report, &nonce, &ak_public)?;
+//! ```
+
+use crate::{error::VtpmError, firmware::guest::AttestationReport};
+
+use std::convert::TryInto;
+
+/// The magic value of the `TPMS_ATTEST` structures produced by a TPM.
+const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+
+/// The `TPMS_ATTEST` type of quotes.
+const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+
+/// The report data binding the marshalled TPM `object` to a report
+/// requested for `nonce`.
+pub fn report_data(nonce: &[u8], object: &[u8]) -> [u8; 64] {
+    #[cfg(feature = "openssl")]
+    {
+        let mut hash = openssl::sha::Sha512::new();
+        hash.update(nonce);
+        hash.update(object);
+        hash.finish()
+    }
+
+    #[cfg(not(feature = "openssl"))]
+    {
+        use sha2::Digest;
+
+        let mut hash = sha2::Sha512::new();
+        hash.update(nonce);
+        hash.update(object);
+        hash.finalize().into()
+    }
+}
+
+/// Check that `report` was requested for `nonce` with the report data
+/// binding the marshalled TPM `object`.
+pub fn verify_binding(
+    report: &AttestationReport,
+    nonce: &[u8],
+    object: &[u8],
+) -> Result<(), VtpmError> {
+    if *report.report_data.as_bytes() != report_data(nonce, object) {
+        return Err(VtpmError::BindingMismatch);
+    }
+
+    Ok(())
+}
+
+/// Read the `TPM2B` at the start of `bytes`, returning its contents and
+/// the bytes following it.
+fn tpm2b(bytes: &[u8]) -> Result<(&[u8], &[u8]), VtpmError> {
+    let (size, rest) = split(bytes, 2)?;
+    let size = u16::from_be_bytes(size.try_into().unwrap());
+
+    split(rest, size.into())
+}
+
+/// Split the first `len` bytes off `bytes`.
+fn split(bytes: &[u8], len: usize) -> Result<(&[u8], &[u8]), VtpmError> {
+    if bytes.len() < len {
+        return Err(VtpmError::Truncated);
+    }
+
+    Ok(bytes.split_at(len))
+}
+
+/// The qualifying data (`extraData`) of the marshalled `TPMS_ATTEST`
+/// structure of a `quote`, i.e. the nonce the quote was requested for.
+pub fn quote_nonce(quote: &[u8]) -> Result<&[u8], VtpmError> {
+    let (magic, rest) = split(quote, 4)?;
+    let magic = u32::from_be_bytes(magic.try_into().unwrap());
+    if magic != TPM_GENERATED_VALUE {
+        return Err(VtpmError::Magic(magic));
+    }
+
+    let (typ, rest) = split(rest, 2)?;
+    let typ = u16::from_be_bytes(typ.try_into().unwrap());
+    if typ != TPM_ST_ATTEST_QUOTE {
+        return Err(VtpmError::NotAQuote(typ));
+    }
+
+    let (_qualified_signer, rest) = tpm2b(rest)?;
+    let (extra_data, _) = tpm2b(rest)?;
+
+    Ok(extra_data)
+}
+
+/// Check that `report` binds the marshalled `TPMS_ATTEST` structure of a
+/// `quote`, and that both were requested for `nonce`.
+///
+/// The signature of the quote is not verified: check it against the
+/// attestation key of the vTPM as well.
+pub fn verify_quote(
+    report: &AttestationReport,
+    nonce: &[u8],
+    quote: &[u8],
+) -> Result<(), VtpmError> {
+    if quote_nonce(quote)? != nonce {
+        return Err(VtpmError::NonceMismatch);
+    }
+
+    verify_binding(report, nonce, quote)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NONCE: [u8; 32] = [0x5a; 32];
+
+    fn quote(nonce: &[u8]) -> Vec<u8> {
+        let mut quote = TPM_GENERATED_VALUE.to_be_bytes().to_vec();
+        quote.extend_from_slice(&TPM_ST_ATTEST_QUOTE.to_be_bytes());
+        quote.extend_from_slice(&[0x00, 0x02, 0x00, 0x0b]);
+        quote.extend_from_slice(&(nonce.len() as u16).to_be_bytes());
+        quote.extend_from_slice(nonce);
+        quote.extend_from_slice(&[0; 17]);
+        quote
+    }
+
+    #[test]
+    fn test_report_data() {
+        // SHA-512("abc")
+        assert_eq!(
+            hex::encode(report_data(b"a", b"bc")),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+    }
+
+    #[test]
+    fn test_verify_binding() {
+        let mut report = AttestationReport::default();
+        report.report_data = report_data(&NONCE, b"ak").into();
+
+        assert_eq!(verify_binding(&report, &NONCE, b"ak"), Ok(()));
+        assert_eq!(
+            verify_binding(&report, &NONCE, b"other"),
+            Err(VtpmError::BindingMismatch)
+        );
+        assert_eq!(
+            verify_binding(&report, &[0; 32], b"ak"),
+            Err(VtpmError::BindingMismatch)
+        );
+    }
+
+    #[test]
+    fn test_quote_nonce() {
+        assert_eq!(quote_nonce(&quote(&NONCE)), Ok(&NONCE[..]));
+
+        let mut bad = quote(&NONCE);
+        bad[0] = 0;
+        assert_eq!(quote_nonce(&bad), Err(VtpmError::Magic(0x0054_4347)));
+
+        let mut bad = quote(&NONCE);
+        bad[5] = 0x14;
+        assert_eq!(quote_nonce(&bad), Err(VtpmError::NotAQuote(0x8014)));
+
+        assert_eq!(quote_nonce(&quote(&NONCE)[..20]), Err(VtpmError::Truncated));
+    }
+
+    #[test]
+    fn test_verify_quote() {
+        let quote = quote(&NONCE);
+        let mut report = AttestationReport::default();
+        report.report_data = report_data(&NONCE, &quote).into();
+
+        assert_eq!(verify_quote(&report, &NONCE, &quote), Ok(()));
+        assert_eq!(
+            verify_quote(&report, &[0; 32], &quote),
+            Err(VtpmError::NonceMismatch)
+        );
+    }
+}
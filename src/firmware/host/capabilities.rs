@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Discovery of the features and commands a platform supports.
+//!
+//! Support is derived from the processor's CPUID leaf 0x8000_001F, and from
+//! the status the SEV and SNP firmware report, so callers can feature-detect
+//! instead of issuing commands and interpreting their failures.
+
+use super::HostFirmware;
+
+#[cfg(feature = "sev")]
+use super::{PlatformStatusFlags, Status};
+
+#[cfg(feature = "snp")]
+use super::SnpPlatformStatus;
+
+/// The memory encryption features the processor advertises in CPUID leaf
+/// 0x8000_001F. Features which the BIOS has not enabled may still be
+/// advertised.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcessorFeatures {
+    /// Secure Memory Encryption.
+    pub sme: bool,
+
+    /// Secure Encrypted Virtualization.
+    pub sev: bool,
+
+    /// SEV Encrypted State.
+    pub sev_es: bool,
+
+    /// SEV Secure Nested Paging.
+    pub snp: bool,
+
+    /// The position of the C-bit in page table entries.
+    pub cbit: u8,
+
+    /// The number of encrypted guests supported simultaneously, which is
+    /// also the highest usable ASID.
+    pub max_asid: u32,
+
+    /// The lowest ASID usable by SEV guests which are not SEV-ES guests.
+    pub min_sev_asid: u32,
+}
+
+impl ProcessorFeatures {
+    /// Read the features of the running processor.
+    #[cfg(target_arch = "x86_64")]
+    pub fn detect() -> Self {
+        use std::arch::x86_64::__cpuid;
+
+        #[allow(unused_unsafe)]
+        let max_leaf: u32 = unsafe { __cpuid(0x8000_0000) }.eax;
+
+        if max_leaf < 0x8000_001f {
+            return Self::default();
+        }
+
+        #[allow(unused_unsafe)]
+        let leaf = unsafe { __cpuid(0x8000_001f) };
+
+        Self {
+            sme: leaf.eax & (1 << 0) != 0,
+            sev: leaf.eax & (1 << 1) != 0,
+            sev_es: leaf.eax & (1 << 3) != 0,
+            snp: leaf.eax & (1 << 4) != 0,
+            cbit: (leaf.ebx & 0x3f) as u8,
+            max_asid: leaf.ecx,
+            min_sev_asid: leaf.edx,
+        }
+    }
+
+    /// Read the features of the running processor. Only x86_64 processors
+    /// support memory encryption.
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn detect() -> Self {
+        Self::default()
+    }
+}
+
+/// A command issued to the platform through `/dev/sev`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostCommand {
+    /// PLATFORM_RESET
+    #[cfg(feature = "sev")]
+    PlatformReset,
+
+    /// PLATFORM_STATUS
+    #[cfg(feature = "sev")]
+    PlatformStatus,
+
+    /// PEK_GEN
+    #[cfg(feature = "sev")]
+    PekGenerate,
+
+    /// PEK_CSR
+    #[cfg(feature = "sev")]
+    PekCsr,
+
+    /// PDH_GEN
+    #[cfg(feature = "sev")]
+    PdhGenerate,
+
+    /// PDH_CERT_EXPORT
+    #[cfg(feature = "sev")]
+    PdhCertExport,
+
+    /// PEK_CERT_IMPORT
+    #[cfg(feature = "sev")]
+    PekCertImport,
+
+    /// GET_ID
+    #[cfg(feature = "sev")]
+    GetIdentifier,
+
+    /// SNP_PLATFORM_STATUS
+    #[cfg(feature = "snp")]
+    SnpPlatformStatus,
+
+    /// SNP_COMMIT
+    #[cfg(feature = "snp")]
+    SnpCommit,
+
+    /// SNP_SET_CONFIG
+    #[cfg(feature = "snp")]
+    SnpSetConfig,
+
+    /// SNP_VLEK_LOAD
+    #[cfg(feature = "snp")]
+    SnpVlekLoad,
+}
+
+/// The features and commands a platform supports.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The features the processor advertises.
+    pub processor: ProcessorFeatures,
+
+    /// The SEV platform status, if the firmware reported it.
+    #[cfg(feature = "sev")]
+    pub sev: Option<Status>,
+
+    /// The SNP platform status, if the firmware reported it.
+    #[cfg(feature = "snp")]
+    pub snp: Option<SnpPlatformStatus>,
+}
+
+impl Capabilities {
+    /// Probe the platform behind `firmware`. Status queries which fail are
+    /// recorded as the corresponding firmware being unavailable.
+    pub fn probe<F: HostFirmware + ?Sized>(firmware: &mut F) -> Self {
+        Self {
+            processor: ProcessorFeatures::detect(),
+            #[cfg(feature = "sev")]
+            sev: firmware.platform_status().ok(),
+            #[cfg(feature = "snp")]
+            snp: firmware.snp_platform_status().ok(),
+        }
+    }
+
+    /// Returns true if SEV guests can be launched.
+    #[cfg(feature = "sev")]
+    pub fn sev(&self) -> bool {
+        self.sev.is_some()
+    }
+
+    /// Returns true if SEV-ES guests can be launched.
+    #[cfg(feature = "sev")]
+    pub fn sev_es(&self) -> bool {
+        self.sev
+            .as_ref()
+            .map(|status| status.flags.contains(PlatformStatusFlags::ENCRYPTED_STATE))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if SNP guests can be launched: the SNP firmware
+    /// responded and the RMP is initialized.
+    #[cfg(feature = "snp")]
+    pub fn snp(&self) -> bool {
+        self.snp
+            .as_ref()
+            .map(|status| status.rmp_initialized())
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the VLEK is in use for attestation.
+    #[cfg(feature = "snp")]
+    pub fn vlek(&self) -> bool {
+        self.snp
+            .as_ref()
+            .map(|status| status.flags.vlek_en() != 0)
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the firmware can hide ciphertext from the hypervisor.
+    #[cfg(feature = "snp")]
+    pub fn ciphertext_hiding(&self) -> bool {
+        self.snp
+            .as_ref()
+            .map(|status| status.flags.ciphertext_hiding_cap() != 0)
+            .unwrap_or(false)
+    }
+
+    /// Returns true if the platform accepts `command`.
+    pub fn supports(&self, command: HostCommand) -> bool {
+        match command {
+            #[cfg(feature = "sev")]
+            HostCommand::GetIdentifier => self
+                .sev
+                .as_ref()
+                .map(|status| {
+                    let version = status.build.version;
+                    (version.major, version.minor) >= (0, 16)
+                })
+                .unwrap_or(false),
+
+            #[cfg(feature = "sev")]
+            HostCommand::PlatformReset
+            | HostCommand::PlatformStatus
+            | HostCommand::PekGenerate
+            | HostCommand::PekCsr
+            | HostCommand::PdhGenerate
+            | HostCommand::PdhCertExport
+            | HostCommand::PekCertImport => self.sev(),
+
+            #[cfg(feature = "snp")]
+            HostCommand::SnpPlatformStatus => self.snp.is_some(),
+
+            #[cfg(feature = "snp")]
+            HostCommand::SnpCommit | HostCommand::SnpSetConfig | HostCommand::SnpVlekLoad => {
+                self.snp()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::firmware::host::MockFirmware;
+
+    #[test]
+    fn test_probe_unavailable() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        let caps: Capabilities = Capabilities::probe(&mut mock);
+
+        #[cfg(feature = "sev")]
+        {
+            assert!(!caps.sev());
+            assert!(!caps.supports(HostCommand::PdhCertExport));
+        }
+
+        #[cfg(feature = "snp")]
+        assert!(!caps.supports(HostCommand::SnpPlatformStatus));
+    }
+
+    #[cfg(feature = "sev")]
+    #[test]
+    fn test_probe_sev() {
+        use crate::{firmware::host::State, Build, Version};
+
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_platform_status(Ok(Status {
+            build: Build {
+                version: Version {
+                    major: 0,
+                    minor: 15,
+                },
+                build: 0,
+            },
+            state: State::Initialized,
+            flags: PlatformStatusFlags::ENCRYPTED_STATE,
+            guests: 0,
+        }));
+
+        let caps: Capabilities = Capabilities::probe(&mut mock);
+
+        assert!(caps.sev_es());
+        assert!(caps.supports(HostCommand::PekCsr));
+        assert!(!caps.supports(HostCommand::GetIdentifier));
+    }
+
+    #[cfg(feature = "snp")]
+    #[test]
+    fn test_probe_snp() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_snp_platform_status(Ok(SnpPlatformStatus {
+            is_rmp_init: 1,
+            ..Default::default()
+        }));
+
+        let caps: Capabilities = Capabilities::probe(&mut mock);
+
+        assert!(caps.snp());
+        assert!(!caps.ciphertext_hiding());
+        assert!(caps.supports(HostCommand::SnpSetConfig));
+    }
+}
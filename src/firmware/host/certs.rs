@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serving certificates to guests on extended guest requests.
+//!
+//! When a guest requests an extended report, the hypervisor returns the
+//! certificates of the platform along with it, in the GUID table format of
+//! the GHCB specification. Current kernels leave the certificates to the
+//! VMM, which copies them into the guest's pages, e.g. when KVM exits to
+//! userspace for them. [GuestCerts] builds that table once, e.g. from a
+//! [Chain](crate::certs::snp::Chain), and serves it to every request.
+//!
+//! # Example:
+//! ```ignore
+//! let certs = GuestCerts::from_chain(&chain, CertType::VCEK)?;
+//!
+//! // Upon the VMM's certificate request for `pages` of guest memory:
+//! match certs.serve(&mut pages) {
+//!     Ok(()) => (),
+//!     Err(CertError::TableTooLarge { .. }) => retry_with(certs.pages()),
+//!     Err(e) => return Err(e),
+//! }
+//! ```
+
+use crate::{error::CertError, firmware::host::CertTableEntry};
+
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+use crate::{certs::snp::Chain, firmware::host::CertType};
+
+use std::{fs, io, path::Path};
+
+/// The size (in bytes) of the guest pages certificates are served in.
+const PAGE_SIZE: usize = 0x1000;
+
+/// The certificate table a host serves to its guests
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuestCerts {
+    entries: Vec<CertTableEntry>,
+    blob: Vec<u8>,
+}
+
+impl GuestCerts {
+    /// Serve the certificates of `entries`, sorted by type.
+    pub fn new(mut entries: Vec<CertTableEntry>) -> Result<Self, CertError> {
+        entries.sort();
+
+        let mut blob = CertTableEntry::cert_table_to_vec_bytes(&entries)?;
+        blob.resize(pages(blob.len()) * PAGE_SIZE, 0);
+
+        Ok(Self { entries, blob })
+    }
+
+    /// Serve the certificates of `chain`, DER-encoded, its VEK being of
+    /// type `vek` (either [CertType::VCEK] or [CertType::VLEK]).
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    pub fn from_chain(chain: &Chain, vek: CertType) -> io::Result<Self> {
+        Self::new(chain.to_cert_table(vek)?).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    /// The certificates served.
+    pub fn entries(&self) -> &[CertTableEntry] {
+        &self.entries
+    }
+
+    /// The certificate table, padded to a whole number of pages.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.blob
+    }
+
+    /// How many pages the certificate table takes, which the hypervisor
+    /// reports to guests whose buffer is too small.
+    pub fn pages(&self) -> usize {
+        self.blob.len() / PAGE_SIZE
+    }
+
+    /// Copy the certificate table into `buffer`, the guest's pages, and
+    /// zero the rest of it. Fails with [CertError::TableTooLarge] when the
+    /// table does not fit, leaving `buffer` untouched.
+    pub fn serve(&self, buffer: &mut [u8]) -> Result<(), CertError> {
+        if buffer.len() < self.blob.len() {
+            return Err(CertError::TableTooLarge {
+                len: self.blob.len() as u32,
+                max: buffer.len() as u32,
+            });
+        }
+
+        let (table, rest) = buffer.split_at_mut(self.blob.len());
+        table.copy_from_slice(&self.blob);
+        rest.fill(0);
+
+        Ok(())
+    }
+
+    /// Write the certificate table to `path`, for hypervisors which read it
+    /// from a file.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, &self.blob)
+    }
+}
+
+/// The number of pages `len` bytes take.
+fn pages(len: usize) -> usize {
+    (len + PAGE_SIZE - 1) / PAGE_SIZE
+}
@@ -0,0 +1,296 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parameters of the SNP_INIT_EX command.
+//!
+//! On Linux the `ccp` driver issues SNP_INIT_EX itself while initializing
+//! the platform, so these types do not issue any command. They describe and
+//! validate an initialization (RMP initialization, HV-fixed page ranges and
+//! ciphertext hiding) and encode the command buffer and range list in the
+//! layout the SEV-SNP Firmware ABI expects, for hypervisors which drive the
+//! AMD Secure Processor directly.
+
+use super::SnpPlatformStatus;
+
+use crate::error::InitExError;
+
+/// Size (in bytes) of a page as understood by SNP_INIT_EX.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// The most ranges that fit in the single page holding the range list.
+pub const MAX_INIT_RANGES: usize = (PAGE_SIZE as usize - 8) / 16;
+
+/// A range of system physical memory which is marked HV-fixed during
+/// initialization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitRange {
+    /// The page-aligned physical base address of the range.
+    pub base: u64,
+
+    /// The number of 4K pages in the range.
+    pub page_count: u32,
+}
+
+impl InitRange {
+    /// Describe a range of `page_count` pages starting at `base`.
+    pub fn new(base: u64, page_count: u32) -> Self {
+        Self { base, page_count }
+    }
+
+    /// The address of the last byte of the range, or `None` if the range
+    /// extends past the end of the physical address space.
+    fn last(&self) -> Option<u64> {
+        (self.page_count as u64)
+            .checked_mul(PAGE_SIZE)?
+            .checked_sub(1)
+            .and_then(|len| self.base.checked_add(len))
+    }
+}
+
+/// A validated set of SNP_INIT_EX parameters. Build one with an
+/// [SnpInitExBuilder].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnpInitEx {
+    init_rmp: bool,
+    rapl_dis: bool,
+    ciphertext_hiding_en: bool,
+    max_snp_asid: u16,
+    ranges: Vec<InitRange>,
+}
+
+impl SnpInitEx {
+    /// Start building a set of parameters.
+    pub fn builder() -> SnpInitExBuilder {
+        SnpInitExBuilder::default()
+    }
+
+    /// Whether the firmware initializes the RMP.
+    pub fn init_rmp(&self) -> bool {
+        self.init_rmp
+    }
+
+    /// Whether RAPL is disabled.
+    pub fn rapl_dis(&self) -> bool {
+        self.rapl_dis
+    }
+
+    /// Whether ciphertext hiding is enabled.
+    pub fn ciphertext_hiding_en(&self) -> bool {
+        self.ciphertext_hiding_en
+    }
+
+    /// The highest ASID available to SNP guests when ciphertext hiding is
+    /// enabled.
+    pub fn max_snp_asid(&self) -> u16 {
+        self.max_snp_asid
+    }
+
+    /// The ranges marked HV-fixed during initialization.
+    pub fn ranges(&self) -> &[InitRange] {
+        &self.ranges
+    }
+
+    /// Encode the 64-byte command buffer. `list_paddr` is the physical
+    /// address of the page holding [range_list](Self::range_list), and is
+    /// ignored when no ranges were given.
+    pub fn command_buffer(&self, list_paddr: u64) -> [u8; 64] {
+        let mut buffer: [u8; 64] = [0; 64];
+        let list_paddr_en: bool = !self.ranges.is_empty();
+
+        let flags: u32 = self.init_rmp as u32
+            | (list_paddr_en as u32) << 1
+            | (self.rapl_dis as u32) << 2
+            | (self.ciphertext_hiding_en as u32) << 3;
+
+        buffer[0..4].copy_from_slice(&flags.to_le_bytes());
+
+        if list_paddr_en {
+            buffer[8..16].copy_from_slice(&list_paddr.to_le_bytes());
+        }
+
+        buffer[16..18].copy_from_slice(&self.max_snp_asid.to_le_bytes());
+
+        buffer
+    }
+
+    /// Encode the range list, which must be placed in a single page.
+    pub fn range_list(&self) -> Vec<u8> {
+        let mut list: Vec<u8> = Vec::with_capacity(8 + self.ranges.len() * 16);
+
+        list.extend_from_slice(&(self.ranges.len() as u32).to_le_bytes());
+        list.extend_from_slice(&[0; 4]);
+
+        for range in self.ranges.iter() {
+            list.extend_from_slice(&range.base.to_le_bytes());
+            list.extend_from_slice(&range.page_count.to_le_bytes());
+            list.extend_from_slice(&[0; 4]);
+        }
+
+        list
+    }
+}
+
+/// Builds a set of [SnpInitEx] parameters, validating them against the
+/// platform's capabilities.
+///
+/// # Example:
+/// ```ignore
+/// let mut firmware: Firmware = Firmware::open().unwrap();
+/// let status: SnpPlatformStatus = firmware.snp_platform_status().unwrap();
+///
+/// let init: SnpInitEx = SnpInitEx::builder()
+///     .init_rmp(true)
+///     .range(InitRange::new(0x1_0000_0000, 16))
+///     .ciphertext_hiding(255)
+///     .build(&status)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SnpInitExBuilder {
+    params: SnpInitEx,
+}
+
+impl SnpInitExBuilder {
+    /// Request that the firmware initializes the RMP.
+    pub fn init_rmp(mut self, init_rmp: bool) -> Self {
+        self.params.init_rmp = init_rmp;
+        self
+    }
+
+    /// Disable RAPL.
+    pub fn rapl_dis(mut self, rapl_dis: bool) -> Self {
+        self.params.rapl_dis = rapl_dis;
+        self
+    }
+
+    /// Enable ciphertext hiding, reserving ASIDs `1..=max_snp_asid` for SNP
+    /// guests.
+    pub fn ciphertext_hiding(mut self, max_snp_asid: u16) -> Self {
+        self.params.ciphertext_hiding_en = true;
+        self.params.max_snp_asid = max_snp_asid;
+        self
+    }
+
+    /// Mark a range of memory HV-fixed during initialization.
+    pub fn range(mut self, range: InitRange) -> Self {
+        self.params.ranges.push(range);
+        self
+    }
+
+    /// Validate the parameters against the platform described by `status`.
+    pub fn build(self, status: &SnpPlatformStatus) -> Result<SnpInitEx, InitExError> {
+        let mut params: SnpInitEx = self.params;
+
+        if params.ciphertext_hiding_en {
+            if status.flags.ciphertext_hiding_cap() == 0 {
+                return Err(InitExError::CiphertextHidingUnsupported);
+            }
+
+            if params.max_snp_asid == 0 {
+                return Err(InitExError::InvalidMaxSnpAsid);
+            }
+        }
+
+        if params.ranges.len() > MAX_INIT_RANGES {
+            return Err(InitExError::TooManyRanges(params.ranges.len()));
+        }
+
+        params.ranges.sort_by_key(|range| range.base);
+
+        for range in params.ranges.iter() {
+            if range.base % PAGE_SIZE != 0 || range.page_count == 0 {
+                return Err(InitExError::InvalidRange(range.base));
+            }
+
+            if range.last().is_none() {
+                return Err(InitExError::RangeOutOfBounds(range.base));
+            }
+        }
+
+        for pair in params.ranges.windows(2) {
+            if pair[0].last() >= Some(pair[1].base) {
+                return Err(InitExError::OverlappingRanges(pair[1].base));
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::firmware::host::SnpStatusFlags;
+
+    use std::convert::TryInto;
+
+    fn status(ciphertext_hiding_cap: bool) -> SnpPlatformStatus {
+        SnpPlatformStatus {
+            flags: SnpStatusFlags((ciphertext_hiding_cap as u32) << 5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_command_buffer() {
+        let init: SnpInitEx = SnpInitEx::builder()
+            .init_rmp(true)
+            .ciphertext_hiding(100)
+            .range(InitRange::new(0x2000, 2))
+            .build(&status(true))
+            .unwrap();
+
+        let buffer: [u8; 64] = init.command_buffer(0xdead_0000);
+
+        assert_eq!(u32::from_le_bytes(buffer[0..4].try_into().unwrap()), 0b1011);
+        assert_eq!(
+            u64::from_le_bytes(buffer[8..16].try_into().unwrap()),
+            0xdead_0000
+        );
+        assert_eq!(u16::from_le_bytes(buffer[16..18].try_into().unwrap()), 100);
+
+        let list: Vec<u8> = init.range_list();
+        assert_eq!(list.len(), 24);
+        assert_eq!(list[0], 1);
+        assert_eq!(u64::from_le_bytes(list[8..16].try_into().unwrap()), 0x2000);
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(
+            SnpInitEx::builder()
+                .ciphertext_hiding(100)
+                .build(&status(false)),
+            Err(InitExError::CiphertextHidingUnsupported)
+        );
+        assert_eq!(
+            SnpInitEx::builder()
+                .ciphertext_hiding(0)
+                .build(&status(true)),
+            Err(InitExError::InvalidMaxSnpAsid)
+        );
+        assert_eq!(
+            SnpInitEx::builder()
+                .range(InitRange::new(0x2001, 1))
+                .build(&status(false)),
+            Err(InitExError::InvalidRange(0x2001))
+        );
+        assert_eq!(
+            SnpInitEx::builder()
+                .range(InitRange::new(0x4000, 1))
+                .range(InitRange::new(0x2000, 3))
+                .build(&status(false)),
+            Err(InitExError::OverlappingRanges(0x4000))
+        );
+        assert_eq!(
+            SnpInitEx::builder()
+                .range(InitRange::new(0xffff_ffff_ffff_f000, 2))
+                .build(&status(false)),
+            Err(InitExError::RangeOutOfBounds(0xffff_ffff_ffff_f000))
+        );
+        assert!(SnpInitEx::builder()
+            .range(InitRange::new(0xffff_ffff_ffff_f000, 1))
+            .build(&status(false))
+            .is_ok());
+    }
+}
@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A serializable record of the platform's firmware, keys and TCB.
+//!
+//! A [PlatformSnapshot] gathers everything a fleet inventory needs to know
+//! about a host into a single value, which serializes with any `serde`
+//! format (e.g. JSON or CBOR). Comparing two snapshots of the same host
+//! detects drift.
+
+use super::{HostFirmware, PlatformId};
+
+#[cfg(feature = "sev")]
+use super::{PlatformStatusFlags, Status};
+
+#[cfg(feature = "sev")]
+use crate::Build;
+
+#[cfg(feature = "snp")]
+use super::{Build as SnpBuild, SnpPlatformStatus, TcbVersion};
+
+use serde::{Deserialize, Serialize};
+
+/// SHA-256 digests of the certificates the SEV platform exports, encoded
+/// as hexadecimal.
+#[cfg(feature = "sev")]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainDigests {
+    /// The Platform Diffie-Hellman certificate.
+    pub pdh: String,
+
+    /// The Platform Endorsement Key certificate.
+    pub pek: String,
+
+    /// The Owner Certificate Authority certificate.
+    pub oca: String,
+
+    /// The Chip Endorsement Key certificate.
+    pub cek: String,
+}
+
+/// The state of the SEV firmware.
+#[cfg(feature = "sev")]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SevSnapshot {
+    /// The firmware build.
+    pub build: Build,
+
+    /// The platform state.
+    pub state: String,
+
+    /// Whether the platform is owned.
+    pub owned: bool,
+
+    /// Whether SEV-ES is available.
+    pub encrypted_state: bool,
+
+    /// The number of guests the platform supervises.
+    pub guests: u32,
+
+    /// Digests of the exported certificate chain. Only collected with the
+    /// `openssl` feature, and if the chain could be exported.
+    pub chain_digests: Option<ChainDigests>,
+}
+
+/// The state of the SNP firmware.
+#[cfg(feature = "snp")]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnpSnapshot {
+    /// The firmware build.
+    pub build: SnpBuild,
+
+    /// Whether SNP is initialized.
+    pub initialized: bool,
+
+    /// Whether the RMP is initialized.
+    pub rmp_initialized: bool,
+
+    /// The number of guests the platform supervises.
+    pub guests: u32,
+
+    /// The installed TCB.
+    pub platform_tcb: TcbVersion,
+
+    /// The TCB reported to guests.
+    pub reported_tcb: TcbVersion,
+
+    /// Whether the chip ID is masked in attestation reports.
+    pub mask_chip_id: bool,
+
+    /// Whether the VCEK is unused in attestation and key derivation.
+    pub mask_chip_key: bool,
+
+    /// Whether a VLEK is loaded.
+    pub vlek: bool,
+}
+
+/// A record of the platform, for fleet inventory and drift detection.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlatformSnapshot {
+    /// The identifier of each socket, encoded as hexadecimal.
+    pub platform_id: Option<Vec<String>>,
+
+    /// The SEV firmware, if it reported its status.
+    #[cfg(feature = "sev")]
+    pub sev: Option<SevSnapshot>,
+
+    /// The SNP firmware, if it reported its status.
+    #[cfg(feature = "snp")]
+    pub snp: Option<SnpSnapshot>,
+}
+
+/// Record the platform behind `firmware`. Queries which fail leave the
+/// corresponding part of the snapshot empty.
+///
+/// # Example:
+/// ```ignore
+/// let mut firmware: Firmware = Firmware::open().unwrap();
+///
+/// let json: String = serde_json::to_string(&snapshot(&mut firmware)).unwrap();
+/// ```
+pub fn snapshot<F: HostFirmware + ?Sized>(firmware: &mut F) -> PlatformSnapshot {
+    PlatformSnapshot {
+        platform_id: firmware.get_platform_id().ok().map(socket_ids),
+        #[cfg(feature = "sev")]
+        sev: firmware
+            .platform_status()
+            .ok()
+            .map(|status| sev_snapshot(firmware, status)),
+        #[cfg(feature = "snp")]
+        snp: firmware.snp_platform_status().ok().map(snp_snapshot),
+    }
+}
+
+fn socket_ids(id: PlatformId) -> Vec<String> {
+    id.sockets()
+        .iter()
+        .map(|socket| socket.to_string())
+        .collect()
+}
+
+#[cfg(feature = "sev")]
+fn sev_snapshot<F: HostFirmware + ?Sized>(firmware: &mut F, status: Status) -> SevSnapshot {
+    #[cfg(feature = "openssl")]
+    let chain_digests: Option<ChainDigests> = firmware
+        .pdh_cert_export()
+        .ok()
+        .and_then(|chain| chain_digests(&chain).ok());
+
+    #[cfg(not(feature = "openssl"))]
+    let chain_digests: Option<ChainDigests> = {
+        let _ = firmware;
+        None
+    };
+
+    SevSnapshot {
+        build: status.build,
+        state: status.state.to_string(),
+        owned: status.flags.contains(PlatformStatusFlags::OWNED),
+        encrypted_state: status.flags.contains(PlatformStatusFlags::ENCRYPTED_STATE),
+        guests: status.guests,
+        chain_digests,
+    }
+}
+
+#[cfg(all(feature = "sev", feature = "openssl"))]
+fn chain_digests(chain: &crate::certs::sev::sev::Chain) -> std::io::Result<ChainDigests> {
+    use crate::certs::sev::sev::Certificate;
+    use codicon::Encoder;
+
+    fn digest(cert: &Certificate) -> std::io::Result<String> {
+        let mut bytes: Vec<u8> = vec![];
+        cert.encode(&mut bytes, ())?;
+
+        Ok(hex::encode(openssl::sha::sha256(&bytes)))
+    }
+
+    Ok(ChainDigests {
+        pdh: digest(&chain.pdh)?,
+        pek: digest(&chain.pek)?,
+        oca: digest(&chain.oca)?,
+        cek: digest(&chain.cek)?,
+    })
+}
+
+#[cfg(feature = "snp")]
+fn snp_snapshot(status: SnpPlatformStatus) -> SnpSnapshot {
+    SnpSnapshot {
+        build: SnpBuild {
+            version: status.version,
+            build: status.build_id,
+        },
+        initialized: status.state != 0,
+        rmp_initialized: status.rmp_initialized(),
+        guests: status.guest_count,
+        platform_tcb: status.platform_tcb_version,
+        reported_tcb: status.reported_tcb_version,
+        mask_chip_id: status.flags.mask_chip_id() != 0,
+        mask_chip_key: status.flags.mask_chip_key() != 0,
+        vlek: status.flags.vlek_en() != 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::firmware::host::MockFirmware;
+
+    #[test]
+    fn test_snapshot_unavailable() {
+        let mut mock: MockFirmware = MockFirmware::new();
+
+        assert_eq!(snapshot(&mut mock), PlatformSnapshot::default());
+    }
+
+    #[cfg(feature = "snp")]
+    #[test]
+    fn test_snapshot_snp() {
+        use crate::firmware::host::Identifier;
+
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_get_identifier(Ok(Identifier(vec![0xab; PlatformId::SOCKET_ID_LEN])))
+            .on_snp_platform_status(Ok(SnpPlatformStatus {
+                state: 1,
+                guest_count: 2,
+                reported_tcb_version: TcbVersion::new(3, 0, 8, 115),
+                ..Default::default()
+            }));
+
+        let snapshot: PlatformSnapshot = snapshot(&mut mock);
+        let snp: SnpSnapshot = snapshot.snp.unwrap();
+
+        assert_eq!(snapshot.platform_id, Some(vec!["AB".repeat(64)]));
+        assert!(snp.initialized);
+        assert_eq!(snp.guests, 2);
+        assert_eq!(snp.reported_tcb, TcbVersion::new(3, 0, 8, 115));
+    }
+}
@@ -0,0 +1,458 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The certificate URLs of the AMD Key Distribution Server (KDS).
+//!
+//! A [KdsUrl] names a certificate the KDS serves: the VCEK of a chip at a
+//! reported TCB, the VLEK of a cloud provider at a reported TCB, or the
+//! certificate chain endorsing either kind of key. It is printed as the URL
+//! the certificate is fetched from, and parsed back from such URLs, so that
+//! tools logging or auditing KDS traffic can work with the crate's types.
+//!
+//! ```text
+//! https://kdsintf.amd.com/vcek/v1/{product}/{hwid}?blSPL={}&teeSPL={}&snpSPL={}&ucodeSPL={}
+//! https://kdsintf.amd.com/vlek/v1/{product}?blSPL={}&teeSPL={}&snpSPL={}&ucodeSPL={}[&csp_id={}]
+//! https://kdsintf.amd.com/{vcek,vlek}/v1/{product}/cert_chain
+//! ```
+//!
+//! URLs are printed with the SPLs zero-padded to two digits, as the KDS
+//! documents them. URLs are parsed with or without the scheme and host, with
+//! the SPLs padded or not and in any order, and with the product name in any
+//! case, so that the URLs of other clients parse as well.
+//!
+//! # Example:
+//! ```ignore
+//! let url = KdsUrl::vcek(Generation::Milan, &report);
+//! let body = http_get(&url.to_string())?;
+//!
+//! let url: KdsUrl = logged_url.parse()?;
+//! if let KdsUrl::Vcek { chip_id, tcb, .. } = url {
+//!     println!("{chip_id} at {tcb:?}");
+//! }
+//! ```
+
+use super::{TcbVersion, KDS_URL};
+
+use crate::{encoding::ChipId, error::KdsUrlError, firmware::guest::AttestationReport, Generation};
+
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+use crate::{error::VerificationError, firmware::host::CertType};
+
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// The query parameters of the SPLs of a TCB version, in the order of the
+/// fields of [TcbVersion] they are printed from.
+const SPL_PARAMETERS: [&str; 4] = ["blSPL", "teeSPL", "snpSPL", "ucodeSPL"];
+
+/// The query parameter of the ID of a cloud provider.
+const CSP_ID_PARAMETER: &str = "csp_id";
+
+/// A certificate served by the AMD KDS
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KdsUrl {
+    /// The VCEK of a chip at a reported TCB.
+    Vcek {
+        /// The generation of the chip.
+        product: Generation,
+
+        /// The identifier of the chip.
+        chip_id: ChipId,
+
+        /// The reported TCB the VCEK is derived from.
+        tcb: TcbVersion,
+    },
+
+    /// The VLEK at a reported TCB.
+    Vlek {
+        /// The generation of the chips the VLEK is loaded into.
+        product: Generation,
+
+        /// The cloud provider the VLEK is issued to, if any.
+        csp_id: Option<String>,
+
+        /// The reported TCB the VLEK is derived from.
+        tcb: TcbVersion,
+    },
+
+    /// The ASK and ARK endorsing VCEKs.
+    VcekChain {
+        /// The generation of the chips.
+        product: Generation,
+    },
+
+    /// The ASVK and ARK endorsing VLEKs.
+    VlekChain {
+        /// The generation of the chips.
+        product: Generation,
+    },
+}
+
+impl KdsUrl {
+    /// The VCEK which signs `report` if it is signed by a VCEK, on a chip
+    /// of generation `product`.
+    pub fn vcek(product: Generation, report: &AttestationReport) -> Self {
+        Self::Vcek {
+            product,
+            chip_id: report.chip_id,
+            tcb: report.reported_tcb,
+        }
+    }
+
+    /// The VCEK or the VLEK, whichever signs `report` as its
+    /// [vek_type](AttestationReport::vek_type) names it, on a chip of
+    /// generation `product`. VLEKs are named without a cloud provider.
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    pub fn vek(product: Generation, report: &AttestationReport) -> Result<Self, KdsUrlError> {
+        if vek_type(report)? == CertType::VLEK {
+            Ok(Self::Vlek {
+                product,
+                csp_id: None,
+                tcb: report.reported_tcb,
+            })
+        } else {
+            Ok(Self::vcek(product, report))
+        }
+    }
+
+    /// The certificate chain endorsing the VCEK or the VLEK, whichever
+    /// signs `report` as its [vek_type](AttestationReport::vek_type) names
+    /// it, on a chip of generation `product`.
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    pub fn vek_chain(product: Generation, report: &AttestationReport) -> Result<Self, KdsUrlError> {
+        if vek_type(report)? == CertType::VLEK {
+            Ok(Self::VlekChain { product })
+        } else {
+            Ok(Self::VcekChain { product })
+        }
+    }
+
+    /// The product the certificate is for.
+    pub fn product(&self) -> Generation {
+        match self {
+            Self::Vcek { product, .. }
+            | Self::Vlek { product, .. }
+            | Self::VcekChain { product }
+            | Self::VlekChain { product } => *product,
+        }
+    }
+}
+
+impl Display for KdsUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let product = self.product().titlecase();
+
+        match self {
+            Self::Vcek { chip_id, tcb, .. } => {
+                write!(f, "{KDS_URL}/vcek/v1/{product}/{chip_id}?")?;
+                write_spls(f, tcb)
+            }
+            Self::Vlek { csp_id, tcb, .. } => {
+                write!(f, "{KDS_URL}/vlek/v1/{product}?")?;
+                write_spls(f, tcb)?;
+
+                match csp_id {
+                    Some(csp_id) => write!(f, "&{CSP_ID_PARAMETER}={csp_id}"),
+                    None => Ok(()),
+                }
+            }
+            Self::VcekChain { .. } => write!(f, "{KDS_URL}/vcek/v1/{product}/cert_chain"),
+            Self::VlekChain { .. } => write!(f, "{KDS_URL}/vlek/v1/{product}/cert_chain"),
+        }
+    }
+}
+
+impl FromStr for KdsUrl {
+    type Err = KdsUrlError;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        // Strip the scheme and host, if any.
+        let path = match url.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("", |start| &rest[start..]),
+            None => url,
+        };
+
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path, None),
+        };
+
+        let invalid = || KdsUrlError::InvalidPath(url.to_string());
+
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let (kind, product, rest) = match segments[..] {
+            [kind, "v1", product, ref rest @ ..] => (kind, product, rest),
+            _ => return Err(invalid()),
+        };
+
+        let product = Generation::try_from(product.to_string())
+            .map_err(|_| KdsUrlError::UnknownProduct(product.to_string()))?;
+
+        match (kind, rest, query) {
+            ("vcek", ["cert_chain"], None) => Ok(Self::VcekChain { product }),
+            ("vlek", ["cert_chain"], None) => Ok(Self::VlekChain { product }),
+            ("vcek", [hwid], Some(query)) => {
+                let chip_id = ChipId::from_str(hwid)
+                    .map_err(|_| KdsUrlError::InvalidChipId(hwid.to_string()))?;
+                let (tcb, _) = parse_query(query, false)?;
+
+                Ok(Self::Vcek {
+                    product,
+                    chip_id,
+                    tcb,
+                })
+            }
+            ("vlek", [], Some(query)) => {
+                let (tcb, csp_id) = parse_query(query, true)?;
+
+                Ok(Self::Vlek {
+                    product,
+                    csp_id,
+                    tcb,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn write_spls(f: &mut fmt::Formatter<'_>, tcb: &TcbVersion) -> fmt::Result {
+    let spls = [tcb.bootloader, tcb.tee, tcb.snp, tcb.microcode];
+
+    for (i, (name, spl)) in SPL_PARAMETERS.iter().zip(spls.iter()).enumerate() {
+        let separator = if i == 0 { "" } else { "&" };
+        write!(f, "{separator}{name}={spl:02}")?;
+    }
+
+    Ok(())
+}
+
+/// The TCB version and, if `csp_id` is accepted, the cloud provider in
+/// `query`.
+fn parse_query(query: &str, csp_id: bool) -> Result<(TcbVersion, Option<String>), KdsUrlError> {
+    let mut spls: [Option<u8>; 4] = [None; 4];
+    let mut csp = None;
+
+    for parameter in query.split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+
+        if csp_id && name == CSP_ID_PARAMETER {
+            if csp.replace(value.to_string()).is_some() {
+                return Err(KdsUrlError::DuplicateParameter(name.to_string()));
+            }
+            continue;
+        }
+
+        let index = SPL_PARAMETERS
+            .iter()
+            .position(|spl| *spl == name)
+            .ok_or_else(|| KdsUrlError::UnknownParameter(name.to_string()))?;
+
+        let spl = value
+            .parse()
+            .map_err(|_| KdsUrlError::InvalidParameter(name.to_string()))?;
+
+        if spls[index].replace(spl).is_some() {
+            return Err(KdsUrlError::DuplicateParameter(name.to_string()));
+        }
+    }
+
+    let mut svns = [0; 4];
+    for (svn, (spl, name)) in svns.iter_mut().zip(spls.iter().zip(SPL_PARAMETERS)) {
+        *svn = spl.ok_or(KdsUrlError::MissingParameter(name))?;
+    }
+
+    let [bootloader, tee, snp, microcode] = svns;
+    Ok((TcbVersion::new(bootloader, tee, snp, microcode), csp))
+}
+
+/// The type of the key which signs `report`, the VCEK or the VLEK.
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+fn vek_type(report: &AttestationReport) -> Result<CertType, KdsUrlError> {
+    report.vek_type().map_err(|e| match e {
+        VerificationError::ReservedSigningKey(key) => KdsUrlError::ReservedSigningKey(key),
+        _ => KdsUrlError::Unsigned,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chip_id() -> ChipId {
+        ChipId([0xab; 64])
+    }
+
+    fn tcb() -> TcbVersion {
+        TcbVersion::new(3, 0, 8, 115)
+    }
+
+    #[test]
+    fn test_vcek() {
+        let url = KdsUrl::Vcek {
+            product: Generation::Milan,
+            chip_id: chip_id(),
+            tcb: tcb(),
+        };
+        let printed = format!(
+            "https://kdsintf.amd.com/vcek/v1/Milan/{}?blSPL=03&teeSPL=00&snpSPL=08&ucodeSPL=115",
+            "ab".repeat(64)
+        );
+
+        assert_eq!(url.to_string(), printed);
+        assert_eq!(printed.parse::<KdsUrl>().unwrap(), url);
+
+        // Unpadded SPLs in another order, without the host.
+        let path = format!(
+            "/vcek/v1/milan/{}?ucodeSPL=115&snpSPL=8&teeSPL=0&blSPL=3",
+            "AB".repeat(64)
+        );
+        assert_eq!(path.parse::<KdsUrl>().unwrap(), url);
+    }
+
+    #[test]
+    fn test_vlek() {
+        let mut url = KdsUrl::Vlek {
+            product: Generation::Genoa,
+            csp_id: None,
+            tcb: tcb(),
+        };
+        let printed =
+            "https://kdsintf.amd.com/vlek/v1/Genoa?blSPL=03&teeSPL=00&snpSPL=08&ucodeSPL=115";
+
+        assert_eq!(url.to_string(), printed);
+        assert_eq!(printed.parse::<KdsUrl>().unwrap(), url);
+
+        if let KdsUrl::Vlek { csp_id, .. } = &mut url {
+            *csp_id = Some("acme".to_string());
+        }
+        let printed = format!("{printed}&csp_id=acme");
+
+        assert_eq!(url.to_string(), printed);
+        assert_eq!(printed.parse::<KdsUrl>().unwrap(), url);
+    }
+
+    #[test]
+    fn test_chains() {
+        for url in [
+            KdsUrl::VcekChain {
+                product: Generation::Milan,
+            },
+            KdsUrl::VlekChain {
+                product: Generation::Genoa,
+            },
+        ] {
+            assert_eq!(url.to_string().parse::<KdsUrl>().unwrap(), url);
+        }
+
+        assert_eq!(
+            KdsUrl::VcekChain {
+                product: Generation::Milan
+            }
+            .to_string(),
+            "https://kdsintf.amd.com/vcek/v1/Milan/cert_chain"
+        );
+    }
+
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    #[test]
+    fn test_signing_key() {
+        use crate::firmware::guest::KeyInfo;
+
+        let mut report = AttestationReport::default();
+        report.chip_id = chip_id();
+        report.reported_tcb = tcb();
+
+        assert_eq!(
+            KdsUrl::vek(Generation::Milan, &report),
+            Ok(KdsUrl::vcek(Generation::Milan, &report))
+        );
+        assert_eq!(
+            KdsUrl::vek_chain(Generation::Milan, &report),
+            Ok(KdsUrl::VcekChain {
+                product: Generation::Milan
+            })
+        );
+
+        report.key_info = KeyInfo::from(1 << 2);
+        assert_eq!(
+            KdsUrl::vek(Generation::Genoa, &report),
+            Ok(KdsUrl::Vlek {
+                product: Generation::Genoa,
+                csp_id: None,
+                tcb: tcb(),
+            })
+        );
+        assert_eq!(
+            KdsUrl::vek_chain(Generation::Genoa, &report),
+            Ok(KdsUrl::VlekChain {
+                product: Generation::Genoa
+            })
+        );
+
+        report.key_info = KeyInfo::from(7 << 2);
+        assert_eq!(
+            KdsUrl::vek(Generation::Milan, &report),
+            Err(KdsUrlError::Unsigned)
+        );
+        assert_eq!(
+            KdsUrl::vek_chain(Generation::Milan, &report),
+            Err(KdsUrlError::Unsigned)
+        );
+
+        report.key_info = KeyInfo::from(3 << 2);
+        assert_eq!(
+            KdsUrl::vek(Generation::Milan, &report),
+            Err(KdsUrlError::ReservedSigningKey(3))
+        );
+        assert_eq!(
+            KdsUrl::vek_chain(Generation::Milan, &report),
+            Err(KdsUrlError::ReservedSigningKey(3))
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let hwid = "ab".repeat(64);
+        let query = "blSPL=3&teeSPL=0&snpSPL=8&ucodeSPL=115";
+
+        for (url, error) in [
+            (
+                "/cek/id/00".to_string(),
+                KdsUrlError::InvalidPath("/cek/id/00".to_string()),
+            ),
+            (
+                format!("/vcek/v1/Turin/{hwid}?{query}"),
+                KdsUrlError::UnknownProduct("Turin".to_string()),
+            ),
+            (
+                format!("/vcek/v1/Milan/abcd?{query}"),
+                KdsUrlError::InvalidChipId("abcd".to_string()),
+            ),
+            (
+                format!("/vcek/v1/Milan/{hwid}?{query}&fmcSPL=1"),
+                KdsUrlError::UnknownParameter("fmcSPL".to_string()),
+            ),
+            (
+                format!("/vcek/v1/Milan/{hwid}?{query}&csp_id=acme"),
+                KdsUrlError::UnknownParameter("csp_id".to_string()),
+            ),
+            (
+                format!("/vcek/v1/Milan/{hwid}?{query}&snpSPL=8"),
+                KdsUrlError::DuplicateParameter("snpSPL".to_string()),
+            ),
+            (
+                format!("/vcek/v1/Milan/{hwid}?blSPL=3&teeSPL=0&snpSPL=8"),
+                KdsUrlError::MissingParameter("ucodeSPL"),
+            ),
+            (
+                format!("/vcek/v1/Milan/{hwid}?blSPL=3&teeSPL=0&snpSPL=8&ucodeSPL=256"),
+                KdsUrlError::InvalidParameter("ucodeSPL".to_string()),
+            ),
+        ] {
+            assert_eq!(url.parse::<KdsUrl>().unwrap_err(), error, "{}", url);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Guardrails for the destructive platform lifecycle commands.
+//!
+//! PLATFORM_RESET (FACTORY_RESET), PEK_GEN and PDH_GEN irrevocably replace
+//! platform keys, invalidating certificate chains which were handed out
+//! earlier. Issuing them requires a [DangerousOps] token, so that they
+//! cannot be called by accident.
+
+use super::Status;
+
+/// A capability token which must be presented to issue a destructive
+/// platform lifecycle command.
+///
+/// The token carries no data; creating one is an explicit statement that
+/// the caller intends to destroy platform state.
+#[derive(Debug)]
+pub struct DangerousOps(());
+
+impl DangerousOps {
+    /// Acknowledge that the commands this token is presented to destroy
+    /// platform keys and certificates.
+    pub fn acknowledge() -> Self {
+        Self(())
+    }
+}
+
+/// A destructive platform lifecycle command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LifecycleOp {
+    /// Reset the platform persistent state, including the platform owner
+    /// and all keys (PLATFORM_RESET, also known as FACTORY_RESET).
+    PlatformReset,
+
+    /// Regenerate the Platform Endorsement Key, which also regenerates the
+    /// PDH and resets ownership of the platform (PEK_GEN).
+    PekGenerate,
+
+    /// Regenerate the Platform Diffie-Hellman key (PDH_GEN).
+    PdhGenerate,
+}
+
+impl std::fmt::Display for LifecycleOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LifecycleOp::PlatformReset => "PLATFORM_RESET",
+            LifecycleOp::PekGenerate => "PEK_GEN",
+            LifecycleOp::PdhGenerate => "PDH_GEN",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// The platform status captured immediately before and after a lifecycle
+/// command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transition {
+    /// The command which was issued.
+    pub op: LifecycleOp,
+
+    /// The platform status before the command was issued.
+    pub before: Status,
+
+    /// The platform status after the command completed.
+    pub after: Status,
+}
@@ -0,0 +1,405 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! An in-memory stand-in for the SEV platform.
+//!
+//! A [MockFirmware] implements [HostFirmware] by replaying responses which
+//! were queued ahead of time, and records every command it receives, so
+//! that code driving the platform can be unit tested on any machine.
+
+use super::*;
+
+#[cfg(feature = "sev")]
+use crate::certs::sev::sev::{Certificate, Chain};
+
+use std::collections::VecDeque;
+
+/// A command received by a [MockFirmware].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockCommand {
+    /// PLATFORM_RESET
+    #[cfg(feature = "sev")]
+    PlatformReset,
+
+    /// PLATFORM_STATUS
+    #[cfg(feature = "sev")]
+    PlatformStatus,
+
+    /// PEK_GEN
+    #[cfg(feature = "sev")]
+    PekGenerate,
+
+    /// PEK_CSR
+    #[cfg(feature = "sev")]
+    PekCsr,
+
+    /// PDH_GEN
+    #[cfg(feature = "sev")]
+    PdhGenerate,
+
+    /// PDH_CERT_EXPORT
+    #[cfg(feature = "sev")]
+    PdhCertExport,
+
+    /// PEK_CERT_IMPORT
+    #[cfg(feature = "sev")]
+    PekCertImport,
+
+    /// GET_ID
+    GetIdentifier,
+
+    /// SNP_PLATFORM_STATUS
+    #[cfg(feature = "snp")]
+    SnpPlatformStatus,
+
+    /// SNP_COMMIT
+    #[cfg(feature = "snp")]
+    SnpCommit,
+
+    /// SNP_SET_CONFIG, with the requested configuration.
+    #[cfg(feature = "snp")]
+    SnpSetConfig(Config),
+
+    /// SNP_VLEK_LOAD, with the hashstick which was loaded.
+    #[cfg(feature = "snp")]
+    SnpVlekLoad(Vec<u8>),
+}
+
+/// Responses queued for a single command.
+struct Script<T>(VecDeque<T>);
+
+impl<T> Default for Script<T> {
+    fn default() -> Self {
+        Self(VecDeque::new())
+    }
+}
+
+impl<T> Script<T> {
+    fn next_or(&mut self, default: impl FnOnce() -> T) -> T {
+        self.0.pop_front().unwrap_or_else(default)
+    }
+}
+
+/// A scriptable, in-memory implementation of [HostFirmware].
+///
+/// Responses are queued per command with the `on_*` methods and are
+/// returned in order. Once a command's queue is empty, commands which only
+/// report success return `Ok(())` while commands which return data fail
+/// with an unknown error.
+///
+/// # Example:
+/// ```ignore
+/// let mut mock: MockFirmware = MockFirmware::new();
+/// mock.on_snp_platform_status(Ok(SnpPlatformStatus::default()));
+///
+/// let status: SnpPlatformStatus = mock.snp_platform_status().unwrap();
+///
+/// assert_eq!(mock.commands(), &[MockCommand::SnpPlatformStatus]);
+/// ```
+#[derive(Default)]
+pub struct MockFirmware {
+    commands: Vec<MockCommand>,
+
+    #[cfg(feature = "sev")]
+    platform_reset: Script<Result<(), Indeterminate<Error>>>,
+    #[cfg(feature = "sev")]
+    platform_status: Script<Result<Status, Indeterminate<Error>>>,
+    #[cfg(feature = "sev")]
+    pek_generate: Script<Result<(), Indeterminate<Error>>>,
+    #[cfg(feature = "sev")]
+    pek_csr: Script<Result<Certificate, Indeterminate<Error>>>,
+    #[cfg(feature = "sev")]
+    pdh_generate: Script<Result<(), Indeterminate<Error>>>,
+    #[cfg(feature = "sev")]
+    pdh_cert_export: Script<Result<Chain, Indeterminate<Error>>>,
+    #[cfg(feature = "sev")]
+    pek_cert_import: Script<Result<(), Indeterminate<Error>>>,
+
+    get_identifier: Script<Result<Identifier, Indeterminate<Error>>>,
+
+    #[cfg(feature = "snp")]
+    snp_platform_status: Script<Result<SnpPlatformStatus, Indeterminate<Error>>>,
+    #[cfg(feature = "snp")]
+    snp_commit: Script<Result<(), UserApiError>>,
+    #[cfg(feature = "snp")]
+    snp_set_config: Script<Result<(), UserApiError>>,
+    #[cfg(feature = "snp")]
+    snp_vlek_load: Script<Result<(), UserApiError>>,
+}
+
+impl MockFirmware {
+    /// Create a mock with no scripted responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every command received so far, in order.
+    pub fn commands(&self) -> &[MockCommand] {
+        &self.commands
+    }
+
+    /// Queue a response to PLATFORM_RESET.
+    #[cfg(feature = "sev")]
+    pub fn on_platform_reset(&mut self, response: Result<(), Indeterminate<Error>>) -> &mut Self {
+        self.platform_reset.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to PLATFORM_STATUS.
+    #[cfg(feature = "sev")]
+    pub fn on_platform_status(
+        &mut self,
+        response: Result<Status, Indeterminate<Error>>,
+    ) -> &mut Self {
+        self.platform_status.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to PEK_GEN.
+    #[cfg(feature = "sev")]
+    pub fn on_pek_generate(&mut self, response: Result<(), Indeterminate<Error>>) -> &mut Self {
+        self.pek_generate.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to PEK_CSR.
+    #[cfg(feature = "sev")]
+    pub fn on_pek_csr(&mut self, response: Result<Certificate, Indeterminate<Error>>) -> &mut Self {
+        self.pek_csr.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to PDH_GEN.
+    #[cfg(feature = "sev")]
+    pub fn on_pdh_generate(&mut self, response: Result<(), Indeterminate<Error>>) -> &mut Self {
+        self.pdh_generate.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to PDH_CERT_EXPORT.
+    #[cfg(feature = "sev")]
+    pub fn on_pdh_cert_export(
+        &mut self,
+        response: Result<Chain, Indeterminate<Error>>,
+    ) -> &mut Self {
+        self.pdh_cert_export.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to PEK_CERT_IMPORT.
+    #[cfg(feature = "sev")]
+    pub fn on_pek_cert_import(&mut self, response: Result<(), Indeterminate<Error>>) -> &mut Self {
+        self.pek_cert_import.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to GET_ID.
+    pub fn on_get_identifier(
+        &mut self,
+        response: Result<Identifier, Indeterminate<Error>>,
+    ) -> &mut Self {
+        self.get_identifier.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to SNP_PLATFORM_STATUS.
+    #[cfg(feature = "snp")]
+    pub fn on_snp_platform_status(
+        &mut self,
+        response: Result<SnpPlatformStatus, Indeterminate<Error>>,
+    ) -> &mut Self {
+        self.snp_platform_status.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to SNP_COMMIT.
+    #[cfg(feature = "snp")]
+    pub fn on_snp_commit(&mut self, response: Result<(), UserApiError>) -> &mut Self {
+        self.snp_commit.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to SNP_SET_CONFIG.
+    #[cfg(feature = "snp")]
+    pub fn on_snp_set_config(&mut self, response: Result<(), UserApiError>) -> &mut Self {
+        self.snp_set_config.0.push_back(response);
+        self
+    }
+
+    /// Queue a response to SNP_VLEK_LOAD.
+    #[cfg(feature = "snp")]
+    pub fn on_snp_vlek_load(&mut self, response: Result<(), UserApiError>) -> &mut Self {
+        self.snp_vlek_load.0.push_back(response);
+        self
+    }
+}
+
+impl HostFirmware for MockFirmware {
+    #[cfg(feature = "sev")]
+    fn platform_reset(&mut self, _ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        self.commands.push(MockCommand::PlatformReset);
+        self.platform_reset.next_or(|| Ok(()))
+    }
+
+    #[cfg(feature = "sev")]
+    fn platform_status(&mut self) -> Result<Status, Indeterminate<Error>> {
+        self.commands.push(MockCommand::PlatformStatus);
+        self.platform_status.next_or(|| Err(Indeterminate::Unknown))
+    }
+
+    #[cfg(feature = "sev")]
+    fn pek_generate(&mut self, _ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        self.commands.push(MockCommand::PekGenerate);
+        self.pek_generate.next_or(|| Ok(()))
+    }
+
+    #[cfg(feature = "sev")]
+    fn pek_csr(&mut self) -> Result<Certificate, Indeterminate<Error>> {
+        self.commands.push(MockCommand::PekCsr);
+        self.pek_csr.next_or(|| Err(Indeterminate::Unknown))
+    }
+
+    #[cfg(feature = "sev")]
+    fn pdh_generate(&mut self, _ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        self.commands.push(MockCommand::PdhGenerate);
+        self.pdh_generate.next_or(|| Ok(()))
+    }
+
+    #[cfg(feature = "sev")]
+    fn pdh_cert_export(&mut self) -> Result<Chain, Indeterminate<Error>> {
+        self.commands.push(MockCommand::PdhCertExport);
+        self.pdh_cert_export.next_or(|| Err(Indeterminate::Unknown))
+    }
+
+    #[cfg(feature = "sev")]
+    fn pek_cert_import(
+        &mut self,
+        _pek: &Certificate,
+        _oca: &Certificate,
+    ) -> Result<(), Indeterminate<Error>> {
+        self.commands.push(MockCommand::PekCertImport);
+        self.pek_cert_import.next_or(|| Ok(()))
+    }
+
+    fn get_identifier(&mut self) -> Result<Identifier, Indeterminate<Error>> {
+        self.commands.push(MockCommand::GetIdentifier);
+        self.get_identifier.next_or(|| Err(Indeterminate::Unknown))
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_platform_status(&mut self) -> Result<SnpPlatformStatus, Indeterminate<Error>> {
+        self.commands.push(MockCommand::SnpPlatformStatus);
+        self.snp_platform_status
+            .next_or(|| Err(Indeterminate::Unknown))
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_commit(&mut self) -> Result<(), UserApiError> {
+        self.commands.push(MockCommand::SnpCommit);
+        self.snp_commit.next_or(|| Ok(()))
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_set_config(&mut self, new_config: Config) -> Result<(), UserApiError> {
+        self.commands.push(MockCommand::SnpSetConfig(new_config));
+        self.snp_set_config.next_or(|| Ok(()))
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_vlek_load(&mut self, hashstick_bytes: &[u8]) -> Result<(), UserApiError> {
+        self.commands
+            .push(MockCommand::SnpVlekLoad(hashstick_bytes.to_vec()));
+        self.snp_vlek_load.next_or(|| Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scripted_responses() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_get_identifier(Ok(Identifier(vec![1; 64])))
+            .on_get_identifier(Err(Indeterminate::Known(Error::InvalidPlatformState)));
+
+        assert_eq!(mock.get_identifier().unwrap(), Identifier(vec![1; 64]));
+        assert!(matches!(
+            mock.get_identifier(),
+            Err(Indeterminate::Known(Error::InvalidPlatformState))
+        ));
+        assert!(matches!(mock.get_identifier(), Err(Indeterminate::Unknown)));
+        assert_eq!(mock.commands().len(), 3);
+    }
+
+    #[cfg(feature = "sev")]
+    fn status(state: State, guests: u32) -> Status {
+        Status {
+            build: Default::default(),
+            state,
+            flags: PlatformStatusFlags::empty(),
+            guests,
+        }
+    }
+
+    #[cfg(feature = "sev")]
+    #[test]
+    fn test_lifecycle() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_platform_status(Ok(status(State::Initialized, 0)))
+            .on_platform_status(Ok(status(State::Uninitialized, 0)));
+
+        let transition: Transition = mock
+            .lifecycle(LifecycleOp::PlatformReset, &DangerousOps::acknowledge())
+            .unwrap();
+
+        assert_eq!(transition.before.state, State::Initialized);
+        assert_eq!(transition.after.state, State::Uninitialized);
+        assert_eq!(
+            mock.commands(),
+            &[
+                MockCommand::PlatformStatus,
+                MockCommand::PlatformReset,
+                MockCommand::PlatformStatus,
+            ]
+        );
+    }
+
+    #[cfg(feature = "sev")]
+    #[test]
+    fn test_lifecycle_refused_with_guests() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_platform_status(Ok(status(State::Working, 2)));
+
+        assert!(matches!(
+            mock.lifecycle(LifecycleOp::PekGenerate, &DangerousOps::acknowledge()),
+            Err(LifecycleError::GuestsActive(2))
+        ));
+        assert_eq!(mock.commands(), &[MockCommand::PlatformStatus]);
+    }
+
+    #[cfg(feature = "snp")]
+    #[test]
+    fn test_snp_commands() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        let config: Config = Config::new(TcbVersion::new(3, 0, 8, 115), MaskId(0));
+
+        mock.on_snp_platform_status(Ok(SnpPlatformStatus {
+            reported_tcb_version: TcbVersion::new(3, 0, 8, 115),
+            ..Default::default()
+        }));
+
+        assert_eq!(mock.snp_get_config().unwrap(), config);
+
+        mock.snp_commit().unwrap();
+        mock.snp_set_config(config).unwrap();
+
+        assert_eq!(
+            mock.commands(),
+            &[
+                MockCommand::SnpPlatformStatus,
+                MockCommand::SnpCommit,
+                MockCommand::SnpSetConfig(config),
+            ]
+        );
+    }
+}
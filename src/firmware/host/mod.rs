@@ -3,55 +3,123 @@
 //! Operations for managing the SEV platform.
 mod types;
 
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod capabilities;
+#[cfg(all(feature = "snp", target_os = "linux", feature = "std"))]
+mod certs;
+#[cfg(all(feature = "snp", feature = "std"))]
+mod init;
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod inventory;
+#[cfg(all(feature = "snp", feature = "std"))]
+mod kds;
+
+#[cfg(feature = "sev")]
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod lifecycle;
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod mock;
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod pending;
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod shared;
+#[cfg(feature = "snp")]
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod tcb;
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod update;
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod watch;
+
 pub use types::*;
 
-#[cfg(target_os = "linux")]
-use super::linux::host::{ioctl::*, types::GetId};
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use capabilities::*;
+#[cfg(all(feature = "snp", target_os = "linux", feature = "std"))]
+pub use certs::*;
+#[cfg(all(feature = "snp", feature = "std"))]
+pub use init::*;
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use inventory::*;
+#[cfg(all(feature = "snp", feature = "std"))]
+pub use kds::*;
 
 #[cfg(feature = "sev")]
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use lifecycle::*;
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use mock::*;
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use pending::PendingCommand;
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use shared::*;
+#[cfg(feature = "snp")]
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use tcb::*;
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use update::*;
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub use watch::*;
+
+#[cfg(all(target_os = "linux", feature = "std"))]
+use super::linux::{
+    host::{ioctl::*, types::GetId},
+    trace::Metrics,
+};
+
+#[cfg(all(target_os = "linux", feature = "metrics"))]
+use super::metrics::MetricsRecorder;
+
+#[cfg(feature = "sev")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 use super::linux::host::types::{
     PdhCertExport, PdhGen, PekCertImport, PekCsr, PekGen, PlatformReset, PlatformStatus,
 };
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 use crate::error::*;
 
 #[cfg(feature = "sev")]
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 use crate::{
     certs::sev::sev::{Certificate, Chain},
     Build as CertBuild, Version as CertVersion,
 };
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 use std::{
     fs::{File, OpenOptions},
-    os::unix::io::{AsRawFd, RawFd},
+    os::unix::{
+        fs::MetadataExt,
+        io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
+    },
 };
 
 #[cfg(feature = "sev")]
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 use std::mem::MaybeUninit;
 
 #[cfg(feature = "snp")]
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 use std::convert::TryInto;
 
 #[cfg(feature = "snp")]
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 use super::linux::host::types::SnpCommit;
 
+#[cfg(feature = "std")]
 /// The CPU-unique identifier for the platform.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Identifier(pub Vec<u8>);
 
+#[cfg(feature = "std")]
 impl From<Identifier> for Vec<u8> {
     fn from(id: Identifier) -> Vec<u8> {
         id.0
     }
 }
 
+#[cfg(feature = "std")]
 impl std::fmt::Display for Identifier {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for b in self.0.iter() {
@@ -62,23 +130,203 @@ impl std::fmt::Display for Identifier {
     }
 }
 
+#[cfg(feature = "std")]
+/// Base URL of the AMD Key Distribution Server (KDS).
+pub const KDS_URL: &str = "https://kdsintf.amd.com";
+
+#[cfg(feature = "std")]
+/// The unique identifiers of each socket of the platform, as returned by
+/// GET_ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlatformId(Vec<Identifier>);
+
+#[cfg(feature = "std")]
+impl PlatformId {
+    /// Size (in bytes) of the identifier of a single socket.
+    pub const SOCKET_ID_LEN: usize = 64;
+
+    /// The identifier of each socket, in socket order.
+    pub fn sockets(&self) -> &[Identifier] {
+        &self.0
+    }
+
+    /// The KDS URL of the signed CEK certificate of `socket` (SEV).
+    pub fn cek_url(&self, socket: usize) -> Option<String> {
+        self.0
+            .get(socket)
+            .map(|id| format!("{KDS_URL}/cek/id/{id}"))
+    }
+
+    /// The KDS URL of the VCEK certificate of `socket` for the given
+    /// processor generation and reported TCB (SEV-SNP).
+    #[cfg(feature = "snp")]
+    pub fn vcek_url(
+        &self,
+        socket: usize,
+        generation: crate::Generation,
+        tcb: &TcbVersion,
+    ) -> Option<String> {
+        use std::convert::TryFrom;
+
+        let id = self.0.get(socket)?;
+        let chip_id = crate::encoding::ChipId::try_from(&id.0[..]).ok()?;
+
+        Some(
+            KdsUrl::Vcek {
+                product: generation,
+                chip_id,
+                tcb: *tcb,
+            }
+            .to_string(),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Identifier> for PlatformId {
+    fn from(id: Identifier) -> Self {
+        Self(
+            id.0.chunks(Self::SOCKET_ID_LEN)
+                .map(|socket| Identifier(socket.to_vec()))
+                .collect(),
+        )
+    }
+}
+
+/// The path of the SEV platform device.
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub const SEV_DEVICE_PATH: &str = "/dev/sev";
+
 /// A handle to the SEV platform.
-#[cfg(target_os = "linux")]
-pub struct Firmware(File);
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub struct Firmware(File, Metrics);
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "std"))]
 impl Firmware {
     /// Create a handle to the SEV platform.
     pub fn open() -> std::io::Result<Firmware> {
         Ok(Firmware(
-            OpenOptions::new().read(true).write(true).open("/dev/sev")?,
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(SEV_DEVICE_PATH)?,
+            Metrics::default(),
         ))
     }
 
+    /// Report the metrics of the commands issued on this handle to
+    /// `recorder`.
+    ///
+    /// # Example:
+    ///
+    /// ```ignore
+    /// static STATS: FirmwareStats = FirmwareStats::new();
+    ///
+    /// let mut fw: Firmware = Firmware::open().unwrap().recorded(&STATS);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn recorded(self, recorder: &'static dyn MetricsRecorder) -> Self {
+        Self(self.0, Metrics::new(recorder))
+    }
+
+    /// Replace the handle with a newly opened one, e.g. after the `ccp`
+    /// driver was reloaded and the old handle stopped working. The new
+    /// handle reports to the same recorder.
+    pub fn reopen(&mut self) -> std::io::Result<()> {
+        self.0 = Self::open()?.0;
+        Ok(())
+    }
+
+    /// Returns true if the handle no longer refers to the device node at
+    /// [SEV_DEVICE_PATH], which happens when the driver was reloaded since
+    /// the handle was opened.
+    ///
+    /// A handle is only stale if both it and the device node can be
+    /// inspected and they differ. Handles whose device node cannot be seen,
+    /// e.g. file descriptors passed into a sandbox without `/dev/sev`, are
+    /// kept as they are.
+    pub fn is_stale(&self) -> bool {
+        match (self.0.metadata(), std::fs::metadata(SEV_DEVICE_PATH)) {
+            (Ok(held), Ok(current)) => {
+                (held.dev(), held.ino(), held.rdev())
+                    != (current.dev(), current.ino(), current.rdev())
+            }
+            _ => false,
+        }
+    }
+
+    /// Reopen the handle if it [is stale](Self::is_stale). Returns true if
+    /// the handle was replaced.
+    pub fn refresh(&mut self) -> std::io::Result<bool> {
+        if !self.is_stale() {
+            return Ok(false);
+        }
+
+        self.reopen()?;
+        Ok(true)
+    }
+
+    /// Discover the features and commands the platform supports.
+    pub fn capabilities(&mut self) -> Capabilities {
+        Capabilities::probe(self)
+    }
+
+    /// Create another handle to the SEV platform, sharing the underlying
+    /// file descriptor and recorder.
+    pub fn try_clone(&self) -> std::io::Result<Firmware> {
+        Ok(Firmware(self.0.try_clone()?, self.1))
+    }
+
+    /// Run `command` against a new handle to the platform on a background
+    /// thread. The returned [PendingCommand] can be waited on with a
+    /// timeout, or `.await`ed from any async runtime.
+    ///
+    /// # Example:
+    /// ```ignore
+    /// let firmware: Firmware = Firmware::open().unwrap();
+    ///
+    /// let pending = firmware.spawn(|fw| fw.snp_platform_status()).unwrap();
+    /// let status: SnpPlatformStatus = pending.await.unwrap();
+    /// ```
+    pub fn spawn<R, F>(&self, command: F) -> std::io::Result<PendingCommand<R>>
+    where
+        F: FnOnce(&mut Firmware) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let mut firmware: Firmware = self.try_clone()?;
+
+        PendingCommand::spawn(move || command(&mut firmware))
+    }
+
+    /// Run `command` against the platform, waiting at most `timeout` for it
+    /// to complete. A command which times out keeps running in the
+    /// background; the firmware cannot abort it.
+    ///
+    /// # Example:
+    /// ```ignore
+    /// let firmware: Firmware = Firmware::open().unwrap();
+    ///
+    /// let status = firmware
+    ///     .with_timeout(Duration::from_secs(5), |fw| fw.snp_platform_status())
+    ///     .unwrap();
+    /// ```
+    pub fn with_timeout<R, F>(&self, timeout: std::time::Duration, command: F) -> std::io::Result<R>
+    where
+        F: FnOnce(&mut Firmware) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.spawn(command)?
+            .wait_timeout(timeout)
+            .map_err(|_| pending::timed_out(timeout))
+    }
+
     /// Reset the platform persistent state.
+    ///
+    /// This destroys platform keys; see [DangerousOps] and
+    /// [HostFirmware::lifecycle].
     #[cfg(feature = "sev")]
-    pub fn platform_reset(&mut self) -> Result<(), Indeterminate<Error>> {
-        PLATFORM_RESET.ioctl(&mut self.0, &mut Command::from(&PlatformReset))?;
+    pub fn platform_reset(&mut self, _ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        Command::from(&PlatformReset).issue(PLATFORM_RESET, &mut self.0, self.1)?;
         Ok(())
     }
 
@@ -86,7 +334,7 @@ impl Firmware {
     #[cfg(feature = "sev")]
     pub fn platform_status(&mut self) -> Result<Status, Indeterminate<Error>> {
         let mut info: PlatformStatus = Default::default();
-        PLATFORM_STATUS.ioctl(&mut self.0, &mut Command::from_mut(&mut info))?;
+        Command::from_mut(&mut info).issue(PLATFORM_STATUS, &mut self.0, self.1)?;
 
         Ok(Status {
             build: CertBuild {
@@ -108,9 +356,12 @@ impl Firmware {
     }
 
     /// Generate a new Platform Encryption Key (PEK).
+    ///
+    /// This destroys platform keys; see [DangerousOps] and
+    /// [HostFirmware::lifecycle].
     #[cfg(feature = "sev")]
-    pub fn pek_generate(&mut self) -> Result<(), Indeterminate<Error>> {
-        PEK_GEN.ioctl(&mut self.0, &mut Command::from(&PekGen))?;
+    pub fn pek_generate(&mut self, _ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        Command::from(&PekGen).issue(PEK_GEN, &mut self.0, self.1)?;
         Ok(())
     }
 
@@ -120,15 +371,18 @@ impl Firmware {
         #[allow(clippy::uninit_assumed_init)]
         let mut pek: Certificate = unsafe { MaybeUninit::uninit().assume_init() };
         let mut csr = PekCsr::new(&mut pek);
-        PEK_CSR.ioctl(&mut self.0, &mut Command::from_mut(&mut csr))?;
+        Command::from_mut(&mut csr).issue(PEK_CSR, &mut self.0, self.1)?;
 
         Ok(pek)
     }
 
     /// Generate a new Platform Diffie-Hellman (PDH) key pair.
+    ///
+    /// This destroys platform keys; see [DangerousOps] and
+    /// [HostFirmware::lifecycle].
     #[cfg(feature = "sev")]
-    pub fn pdh_generate(&mut self) -> Result<(), Indeterminate<Error>> {
-        PDH_GEN.ioctl(&mut self.0, &mut Command::from(&PdhGen))?;
+    pub fn pdh_generate(&mut self, _ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        Command::from(&PdhGen).issue(PDH_GEN, &mut self.0, self.1)?;
         Ok(())
     }
 
@@ -141,7 +395,7 @@ impl Firmware {
         let mut pdh: Certificate = unsafe { MaybeUninit::uninit().assume_init() };
 
         let mut pdh_cert_export = PdhCertExport::new(&mut pdh, &mut chain);
-        PDH_CERT_EXPORT.ioctl(&mut self.0, &mut Command::from_mut(&mut pdh_cert_export))?;
+        Command::from_mut(&mut pdh_cert_export).issue(PDH_CERT_EXPORT, &mut self.0, self.1)?;
 
         Ok(Chain {
             pdh,
@@ -151,6 +405,57 @@ impl Firmware {
         })
     }
 
+    /// Export the complete certificate chain of the platform: the SEV chain
+    /// from PDH_CERT_EXPORT together with the AMD ASK and ARK of the
+    /// processor generation which signed its CEK.
+    ///
+    /// The CEK PDH_CERT_EXPORT returns is not signed by the AMD ASK. The
+    /// ASK-signed CEK of the platform is published by the AMD KDS at the URL
+    /// [PlatformId::cek_url] gives, from which the caller downloads `cek`.
+    /// It replaces the exported CEK in the chain, and the chain is verified
+    /// before it is returned, so callers can hand it to guest owners as is.
+    ///
+    /// # Example:
+    /// ```ignore
+    /// let mut firmware: Firmware = Firmware::open().unwrap();
+    ///
+    /// let url = firmware.get_platform_id().unwrap().cek_url(0).unwrap();
+    /// let cek = Certificate::decode(&mut download(&url)?, ())?;
+    ///
+    /// let chain: sev::certs::sev::Chain = firmware.export_cert_chain(cek).unwrap();
+    /// ```
+    #[cfg(all(feature = "sev", feature = "openssl"))]
+    pub fn export_cert_chain(
+        &mut self,
+        cek: Certificate,
+    ) -> Result<crate::certs::sev::Chain, Indeterminate<Error>> {
+        use crate::{certs::sev::Verifiable, Generation};
+        use std::convert::TryFrom;
+
+        let sev = Chain {
+            cek,
+            ..self.pdh_cert_export()?
+        };
+
+        let generation: Generation = Generation::try_from(&sev).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "CEK is not signed by a known AMD signing key",
+            )
+        })?;
+
+        let chain = crate::certs::sev::Chain {
+            ca: generation.into(),
+            sev,
+        };
+
+        // The PEK is signed by the CEK, so that a CEK of another platform
+        // fails verification.
+        (&chain).verify()?;
+
+        Ok(chain)
+    }
+
     /// Take ownership of the SEV platform.
     #[cfg(feature = "sev")]
     pub fn pek_cert_import(
@@ -159,7 +464,7 @@ impl Firmware {
         oca: &Certificate,
     ) -> Result<(), Indeterminate<Error>> {
         let pek_cert_import = PekCertImport::new(pek, oca);
-        PEK_CERT_IMPORT.ioctl(&mut self.0, &mut Command::from(&pek_cert_import))?;
+        Command::from(&pek_cert_import).issue(PEK_CERT_IMPORT, &mut self.0, self.1)?;
         Ok(())
     }
 
@@ -167,16 +472,45 @@ impl Firmware {
     ///
     /// This is especially helpful for sending AMD an HTTP request to fetch
     /// the signed CEK certificate.
+    ///
+    /// The length of the identifier, which grows with the number of
+    /// sockets, is queried first so that the IDs of all the sockets are
+    /// returned.
     #[cfg(any(feature = "sev", feature = "snp"))]
     pub fn get_identifier(&mut self) -> Result<Identifier, Indeterminate<Error>> {
-        let mut bytes = [0u8; 64];
+        // The firmware rejects the empty buffer but writes back the length
+        // it needs, so only fail if no length came back.
+        let mut query = GetId::query();
+        let queried = Command::from_mut(&mut query).issue(GET_ID, &mut self.0, self.1);
+
+        if query.len() == 0 {
+            queried?;
+            return Err(Indeterminate::Known(Error::InvalidLen));
+        }
+
+        let mut bytes = vec![0u8; query.len()];
         let mut id = GetId::new(&mut bytes);
 
-        GET_ID.ioctl(&mut self.0, &mut Command::from_mut(&mut id))?;
+        Command::from_mut(&mut id).issue(GET_ID, &mut self.0, self.1)?;
 
         Ok(Identifier(id.as_slice().to_vec()))
     }
 
+    /// Get the unique identifier of each socket of the platform, from which
+    /// the AMD KDS certificate URLs can be derived.
+    ///
+    /// # Example:
+    /// ```ignore
+    /// let mut firmware: Firmware = Firmware::open().unwrap();
+    ///
+    /// let id: PlatformId = firmware.get_platform_id().unwrap();
+    /// let url: String = id.cek_url(0).unwrap();
+    /// ```
+    #[cfg(any(feature = "sev", feature = "snp"))]
+    pub fn get_platform_id(&mut self) -> Result<PlatformId, Indeterminate<Error>> {
+        Ok(self.get_identifier()?.into())
+    }
+
     /// Query the SNP platform status.
     ///
     /// # Example:
@@ -191,7 +525,7 @@ impl Firmware {
     pub fn snp_platform_status(&mut self) -> Result<SnpPlatformStatus, Indeterminate<Error>> {
         let mut platform_status: SnpPlatformStatus = SnpPlatformStatus::default();
 
-        SNP_PLATFORM_STATUS.ioctl(&mut self.0, &mut Command::from_mut(&mut platform_status))?;
+        Command::from_mut(&mut platform_status).issue(SNP_PLATFORM_STATUS, &mut self.0, self.1)?;
 
         Ok(platform_status)
     }
@@ -211,13 +545,33 @@ impl Firmware {
     #[cfg(feature = "snp")]
     pub fn snp_commit(&mut self) -> Result<(), UserApiError> {
         let mut buf: SnpCommit = Default::default();
-        SNP_COMMIT.ioctl(&mut self.0, &mut Command::from_mut(&mut buf))?;
+        Command::from_mut(&mut buf).issue(SNP_COMMIT, &mut self.0, self.1)?;
 
         Ok(())
     }
 
+    /// Read the SNP configuration currently in effect (the reported TCB and
+    /// mask ID) from the platform status.
+    ///
+    /// # Example:
+    /// ```ignore
+    /// let mut firmware: Firmware = Firmware::open().unwrap();
+    ///
+    /// let config: Config = firmware.snp_get_config().unwrap();
+    /// ```
+    #[cfg(feature = "snp")]
+    pub fn snp_get_config(&mut self) -> Result<Config, UserApiError> {
+        let status: SnpPlatformStatus = self.snp_platform_status()?;
+
+        Ok(Config::from_status(&status))
+    }
+
     /// Set the SNP Configuration.
     ///
+    /// The configuration is validated before it is sent to the firmware:
+    /// reserved mask ID bits must be zero and the reported TCB may not be
+    /// newer than the platform's installed TCB.
+    ///
     /// # Example:
     /// ```ignore
     /// let configuration = Config::new(
@@ -230,14 +584,48 @@ impl Firmware {
     /// ```
     #[cfg(feature = "snp")]
     pub fn snp_set_config(&mut self, new_config: Config) -> Result<(), UserApiError> {
-        SNP_SET_CONFIG.ioctl(
+        let status: SnpPlatformStatus = self.snp_platform_status()?;
+        new_config.validate_for(&status.platform_tcb_version)?;
+
+        Command::from_mut(&mut new_config.try_into()?).issue(
+            SNP_SET_CONFIG,
             &mut self.0,
-            &mut Command::from_mut(&mut new_config.try_into()?),
+            self.1,
         )?;
 
         Ok(())
     }
 
+    /// Download a firmware update to the AMD Secure Processor.
+    ///
+    /// Linux performs DOWNLOAD_FIRMWARE itself when the platform is
+    /// initialized, so this stages `image` in the kernel's firmware search
+    /// path for the running CPU and the update takes effect the next time
+    /// the `ccp` driver initializes the platform. With `dry_run` set
+    /// nothing is written and only the destination path is returned.
+    ///
+    /// Only the image's header and length were checked when `image` was
+    /// read; its signature is verified by the AMD Secure Processor when the
+    /// driver downloads it.
+    ///
+    /// No command is issued through `/dev/sev`, so no handle is needed.
+    ///
+    /// # Example:
+    /// ```ignore
+    /// let image: FirmwareImage = FirmwareImage::from_path("amd_sev_fam19h_model0xh.sbin").unwrap();
+    ///
+    /// let path: PathBuf = Firmware::download_firmware(&image, true).unwrap();
+    /// ```
+    #[cfg(target_arch = "x86_64")]
+    pub fn download_firmware(
+        image: &FirmwareImage,
+        dry_run: bool,
+    ) -> std::io::Result<std::path::PathBuf> {
+        let (family, model) = FirmwareImage::cpu_signature();
+
+        image.stage(FIRMWARE_SEARCH_PATH, family, model, dry_run)
+    }
+
     #[cfg(feature = "snp")]
     /// Insert a Version Loaded Endorsement Key Hashstick into the AMD Secure Processor.
     ///
@@ -255,17 +643,294 @@ impl Firmware {
 
         let parsed_bytes: WrappedVlekHashstick = hashstick_bytes.try_into()?;
 
-        let mut vlek_load: SnpVlekLoad = SnpVlekLoad::new(&parsed_bytes);
+        self.issue_vlek_load(SnpVlekLoad::new(&parsed_bytes))
+    }
 
-        SNP_VLEK_LOAD.ioctl(&mut self.0, &mut Command::from_mut(&mut vlek_load))?;
+    #[cfg(feature = "snp")]
+    /// Insert a Version Loaded Endorsement Key Hashstick in the given wrapped
+    /// format version into the AMD Secure Processor. Only version 0 is
+    /// currently defined; other versions are rejected before the firmware is
+    /// called.
+    ///
+    /// The firmware clears a loaded VLEK whenever the reported TCB changes
+    /// (see [snp_commit](Self::snp_commit)), so a hashstick matching the new
+    /// TCB must be loaded again afterwards.
+    pub fn snp_vlek_load_version(
+        &mut self,
+        hashstick_bytes: &[u8],
+        version: u8,
+    ) -> Result<(), UserApiError> {
+        use types::FFI::types::{SnpVlekLoad, WrappedVlekHashstick};
+
+        let parsed_bytes: WrappedVlekHashstick = hashstick_bytes.try_into()?;
+
+        self.issue_vlek_load(SnpVlekLoad::with_version(&parsed_bytes, version)?)
+    }
+
+    #[cfg(feature = "snp")]
+    fn issue_vlek_load(
+        &mut self,
+        mut vlek_load: types::FFI::types::SnpVlekLoad,
+    ) -> Result<(), UserApiError> {
+        Command::from_mut(&mut vlek_load).issue(SNP_VLEK_LOAD, &mut self.0, self.1)?;
 
         Ok(())
     }
 }
 
-#[cfg(target_os = "linux")]
+/// The operations the SEV platform offers to the host.
+///
+/// [Firmware] issues them to the AMD Secure Processor, while [MockFirmware]
+/// serves scripted responses so that code driving the platform (e.g. a VMM)
+/// can be tested without SEV hardware.
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub trait HostFirmware {
+    /// Reset the platform persistent state.
+    #[cfg(feature = "sev")]
+    fn platform_reset(&mut self, ops: &DangerousOps) -> Result<(), Indeterminate<Error>>;
+
+    /// Query the platform status.
+    #[cfg(feature = "sev")]
+    fn platform_status(&mut self) -> Result<Status, Indeterminate<Error>>;
+
+    /// Generate a new Platform Endorsement Key (PEK).
+    #[cfg(feature = "sev")]
+    fn pek_generate(&mut self, ops: &DangerousOps) -> Result<(), Indeterminate<Error>>;
+
+    /// Issue a destructive lifecycle command, capturing the platform status
+    /// before and after it.
+    ///
+    /// The command is refused with [LifecycleError::GuestsActive], without
+    /// reaching the firmware, while the platform is supervising guests.
+    #[cfg(feature = "sev")]
+    fn lifecycle(
+        &mut self,
+        op: LifecycleOp,
+        ops: &DangerousOps,
+    ) -> Result<Transition, LifecycleError> {
+        let before: Status = self.platform_status()?;
+
+        if before.guests > 0 {
+            return Err(LifecycleError::GuestsActive(before.guests));
+        }
+
+        match op {
+            LifecycleOp::PlatformReset => self.platform_reset(ops)?,
+            LifecycleOp::PekGenerate => self.pek_generate(ops)?,
+            LifecycleOp::PdhGenerate => self.pdh_generate(ops)?,
+        }
+
+        let after: Status = self.platform_status()?;
+
+        Ok(Transition { op, before, after })
+    }
+
+    /// Request a signature for the PEK.
+    #[cfg(feature = "sev")]
+    fn pek_csr(&mut self) -> Result<Certificate, Indeterminate<Error>>;
+
+    /// Generate a new Platform Diffie-Hellman (PDH) key pair.
+    #[cfg(feature = "sev")]
+    fn pdh_generate(&mut self, ops: &DangerousOps) -> Result<(), Indeterminate<Error>>;
+
+    /// Export the SEV certificate chain.
+    #[cfg(feature = "sev")]
+    fn pdh_cert_export(&mut self) -> Result<Chain, Indeterminate<Error>>;
+
+    /// Take ownership of the SEV platform.
+    #[cfg(feature = "sev")]
+    fn pek_cert_import(
+        &mut self,
+        pek: &Certificate,
+        oca: &Certificate,
+    ) -> Result<(), Indeterminate<Error>>;
+
+    /// Get the unique CPU identifier.
+    fn get_identifier(&mut self) -> Result<Identifier, Indeterminate<Error>>;
+
+    /// Get the unique identifier of each socket of the platform.
+    fn get_platform_id(&mut self) -> Result<PlatformId, Indeterminate<Error>> {
+        Ok(self.get_identifier()?.into())
+    }
+
+    /// Query the SNP platform status.
+    #[cfg(feature = "snp")]
+    fn snp_platform_status(&mut self) -> Result<SnpPlatformStatus, Indeterminate<Error>>;
+
+    /// Commit the current firmware and TCB.
+    #[cfg(feature = "snp")]
+    fn snp_commit(&mut self) -> Result<(), UserApiError>;
+
+    /// Read the SNP configuration currently in effect.
+    #[cfg(feature = "snp")]
+    fn snp_get_config(&mut self) -> Result<Config, UserApiError> {
+        Ok(Config::from_status(&self.snp_platform_status()?))
+    }
+
+    /// Set the SNP configuration.
+    #[cfg(feature = "snp")]
+    fn snp_set_config(&mut self, new_config: Config) -> Result<(), UserApiError>;
+
+    /// Insert a Version Loaded Endorsement Key Hashstick into the AMD Secure Processor.
+    #[cfg(feature = "snp")]
+    fn snp_vlek_load(&mut self, hashstick_bytes: &[u8]) -> Result<(), UserApiError>;
+}
+
+#[cfg(all(target_os = "linux", feature = "std"))]
+impl HostFirmware for Firmware {
+    #[cfg(feature = "sev")]
+    fn platform_reset(&mut self, ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        Firmware::platform_reset(self, ops)
+    }
+
+    #[cfg(feature = "sev")]
+    fn platform_status(&mut self) -> Result<Status, Indeterminate<Error>> {
+        Firmware::platform_status(self)
+    }
+
+    #[cfg(feature = "sev")]
+    fn pek_generate(&mut self, ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        Firmware::pek_generate(self, ops)
+    }
+
+    #[cfg(feature = "sev")]
+    fn pek_csr(&mut self) -> Result<Certificate, Indeterminate<Error>> {
+        Firmware::pek_csr(self)
+    }
+
+    #[cfg(feature = "sev")]
+    fn pdh_generate(&mut self, ops: &DangerousOps) -> Result<(), Indeterminate<Error>> {
+        Firmware::pdh_generate(self, ops)
+    }
+
+    #[cfg(feature = "sev")]
+    fn pdh_cert_export(&mut self) -> Result<Chain, Indeterminate<Error>> {
+        Firmware::pdh_cert_export(self)
+    }
+
+    #[cfg(feature = "sev")]
+    fn pek_cert_import(
+        &mut self,
+        pek: &Certificate,
+        oca: &Certificate,
+    ) -> Result<(), Indeterminate<Error>> {
+        Firmware::pek_cert_import(self, pek, oca)
+    }
+
+    fn get_identifier(&mut self) -> Result<Identifier, Indeterminate<Error>> {
+        Firmware::get_identifier(self)
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_platform_status(&mut self) -> Result<SnpPlatformStatus, Indeterminate<Error>> {
+        Firmware::snp_platform_status(self)
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_commit(&mut self) -> Result<(), UserApiError> {
+        Firmware::snp_commit(self)
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_set_config(&mut self, new_config: Config) -> Result<(), UserApiError> {
+        Firmware::snp_set_config(self, new_config)
+    }
+
+    #[cfg(feature = "snp")]
+    fn snp_vlek_load(&mut self, hashstick_bytes: &[u8]) -> Result<(), UserApiError> {
+        Firmware::snp_vlek_load(self, hashstick_bytes)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "std"))]
 impl AsRawFd for Firmware {
     fn as_raw_fd(&self) -> RawFd {
         self.0.as_raw_fd()
     }
 }
+
+#[cfg(all(target_os = "linux", feature = "std"))]
+impl AsFd for Firmware {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// Adopt a file descriptor for the SEV platform, e.g. one inherited from a
+/// privileged parent process or received over a Unix socket.
+#[cfg(all(target_os = "linux", feature = "std"))]
+impl From<OwnedFd> for Firmware {
+    fn from(fd: OwnedFd) -> Self {
+        Firmware(File::from(fd), Metrics::default())
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "std"))]
+impl From<Firmware> for OwnedFd {
+    fn from(firmware: Firmware) -> Self {
+        firmware.0.into()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    fn platform_id() -> PlatformId {
+        let mut bytes: Vec<u8> = vec![0xab; PlatformId::SOCKET_ID_LEN];
+        bytes.extend_from_slice(&[0x01; PlatformId::SOCKET_ID_LEN]);
+
+        Identifier(bytes).into()
+    }
+
+    #[test]
+    fn test_from_owned_fd() {
+        let fd: OwnedFd = File::open("/dev/null").unwrap().into();
+        let raw: RawFd = fd.as_raw_fd();
+
+        let firmware: Firmware = fd.into();
+        assert_eq!(firmware.as_raw_fd(), raw);
+        // Stale only next to a device node it does not refer to.
+        assert_eq!(
+            firmware.is_stale(),
+            std::path::Path::new(SEV_DEVICE_PATH).exists()
+        );
+
+        let fd: OwnedFd = firmware.into();
+        assert_eq!(fd.as_raw_fd(), raw);
+    }
+
+    #[test]
+    fn test_platform_id_sockets() {
+        let id: PlatformId = platform_id();
+
+        assert_eq!(id.sockets().len(), 2);
+        assert_eq!(id.sockets()[1].0, vec![0x01; PlatformId::SOCKET_ID_LEN]);
+    }
+
+    #[test]
+    fn test_cek_url() {
+        let id: PlatformId = platform_id();
+
+        assert_eq!(
+            id.cek_url(0).unwrap(),
+            format!("https://kdsintf.amd.com/cek/id/{}", "AB".repeat(64))
+        );
+        assert!(id.cek_url(2).is_none());
+    }
+
+    #[cfg(feature = "snp")]
+    #[test]
+    fn test_vcek_url() {
+        let id: PlatformId = platform_id();
+        let tcb: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+
+        assert_eq!(
+            id.vcek_url(1, crate::Generation::Milan, &tcb).unwrap(),
+            format!(
+                "https://kdsintf.amd.com/vcek/v1/Milan/{}?blSPL=03&teeSPL=00&snpSPL=08&ucodeSPL=115",
+                "01".repeat(64)
+            )
+        );
+    }
+}
@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Commands running in the background.
+//!
+//! Some platform commands (e.g. SNP initialization with RMP setup) can keep
+//! the AMD Secure Processor busy for seconds. A [PendingCommand] runs such a
+//! command on its own thread so that the caller can bound how long it waits,
+//! or `.await` the result from an async runtime.
+//!
+//! Once issued, a command cannot be aborted: the firmware always runs it to
+//! completion. Timing out or dropping a [PendingCommand] only stops waiting
+//! for the result.
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+struct Slot<R> {
+    result: Option<R>,
+    waker: Option<Waker>,
+}
+
+struct Shared<R> {
+    slot: Mutex<Slot<R>>,
+    done: Condvar,
+}
+
+impl<R> Shared<R> {
+    fn complete(&self, result: R) {
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        slot.result = Some(result);
+
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+
+        self.done.notify_all();
+    }
+}
+
+/// The eventual result of a command running in the background.
+pub struct PendingCommand<R> {
+    shared: Arc<Shared<R>>,
+}
+
+impl<R: Send + 'static> PendingCommand<R> {
+    /// Run `command` on a new thread.
+    pub(crate) fn spawn<F>(command: F) -> io::Result<Self>
+    where
+        F: FnOnce() -> R + Send + 'static,
+    {
+        let shared: Arc<Shared<R>> = Arc::new(Shared {
+            slot: Mutex::new(Slot {
+                result: None,
+                waker: None,
+            }),
+            done: Condvar::new(),
+        });
+
+        let worker: Arc<Shared<R>> = shared.clone();
+
+        thread::Builder::new()
+            .name("sev-command".to_string())
+            .spawn(move || worker.complete(command()))?;
+
+        Ok(Self { shared })
+    }
+}
+
+impl<R> PendingCommand<R> {
+    /// Returns true once the command has completed.
+    pub fn is_done(&self) -> bool {
+        self.lock().result.is_some()
+    }
+
+    /// Block until the command completes.
+    pub fn wait(self) -> R {
+        let mut slot = self.lock();
+
+        loop {
+            if let Some(result) = slot.result.take() {
+                return result;
+            }
+
+            slot = self
+                .shared
+                .done
+                .wait(slot)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Block until the command completes or `timeout` elapses. On timeout
+    /// the command is handed back, so the caller may keep waiting later.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<R, Self> {
+        let deadline: Instant = Instant::now() + timeout;
+
+        {
+            let mut slot = self.lock();
+
+            loop {
+                if let Some(result) = slot.result.take() {
+                    return Ok(result);
+                }
+
+                let now: Instant = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+
+                slot = self
+                    .shared
+                    .done
+                    .wait_timeout(slot, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+
+        Err(self)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Slot<R>> {
+        self.shared.slot.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R> Future for PendingCommand<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut slot = self.lock();
+
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<R> std::fmt::Debug for PendingCommand<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingCommand")
+            .field("done", &self.is_done())
+            .finish()
+    }
+}
+
+/// The error returned when a command does not complete in time.
+pub(crate) fn timed_out(timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("firmware command did not complete within {timeout:?}"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{sync::mpsc, task::Wake};
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker: Waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx: Context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_wait() {
+        let pending: PendingCommand<u32> = PendingCommand::spawn(|| 7).unwrap();
+
+        assert_eq!(pending.wait(), 7);
+    }
+
+    #[test]
+    fn test_wait_timeout() {
+        let (tx, rx) = mpsc::channel::<()>();
+        let pending: PendingCommand<u32> = PendingCommand::spawn(move || {
+            rx.recv().unwrap();
+            9
+        })
+        .unwrap();
+
+        let pending: PendingCommand<u32> =
+            pending.wait_timeout(Duration::from_millis(10)).unwrap_err();
+        assert!(!pending.is_done());
+
+        tx.send(()).unwrap();
+        assert_eq!(pending.wait_timeout(Duration::from_secs(10)).ok(), Some(9));
+    }
+
+    #[test]
+    fn test_future() {
+        let pending: PendingCommand<&str> = PendingCommand::spawn(|| {
+            thread::sleep(Duration::from_millis(5));
+            "done"
+        })
+        .unwrap();
+
+        assert_eq!(block_on(pending), "done");
+    }
+}
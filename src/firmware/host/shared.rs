@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A handle to the SEV platform for long-lived services.
+//!
+//! A [SharedFirmware] can be cloned into every thread of a daemon. All
+//! clones use a single handle, and before each command it is checked
+//! against `/dev/sev`, so that the daemon keeps working after the `ccp`
+//! driver is reloaded (e.g. during a kernel module upgrade).
+
+use super::Firmware;
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+/// A handle to the SEV platform which can be shared between threads, and
+/// which is reopened when the driver is reloaded.
+///
+/// # Example:
+/// ```ignore
+/// let firmware: SharedFirmware = SharedFirmware::open().unwrap();
+///
+/// let worker: SharedFirmware = firmware.clone();
+/// std::thread::spawn(move || {
+///     let status = worker.run(|fw| fw.snp_platform_status()).unwrap();
+/// });
+/// ```
+#[derive(Clone)]
+pub struct SharedFirmware(Arc<Mutex<Firmware>>);
+
+impl SharedFirmware {
+    /// Create a shared handle to the SEV platform.
+    pub fn open() -> io::Result<Self> {
+        Ok(Firmware::open()?.into())
+    }
+
+    /// Run `command` against the platform, reopening the handle first if
+    /// the driver was reloaded (see [Firmware::is_stale]). Commands from
+    /// different clones are issued one at a time.
+    pub fn run<R, F>(&self, command: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut Firmware) -> R,
+    {
+        let mut firmware = self.0.lock().unwrap_or_else(|e| e.into_inner());
+
+        firmware.refresh()?;
+
+        Ok(command(&mut firmware))
+    }
+
+    /// Reopen the handle unconditionally.
+    pub fn reopen(&self) -> io::Result<()> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).reopen()
+    }
+}
+
+impl From<Firmware> for SharedFirmware {
+    fn from(firmware: Firmware) -> Self {
+        Self(Arc::new(Mutex::new(firmware)))
+    }
+}
+
+impl std::fmt::Debug for SharedFirmware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SharedFirmware").finish()
+    }
+}
@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Guarded updates of the TCB reported to SNP guests.
+//!
+//! The reported TCB decides which VCEK guests' attestation reports are
+//! verified against, and SNP_COMMIT permanently forbids rolling the
+//! firmware back below the committed TCB. Changing the reported TCB and
+//! committing by hand risks leaving a fleet of guests unable to attest, so
+//! a [TcbUpdate] walks through the update one guarded step at a time:
+//!
+//! 1. [report](TcbUpdate::report) the new TCB with SNP_SET_CONFIG, and
+//!    confirm that the platform applied it;
+//! 2. [verify](TcbUpdate::verify) that guests still attest with the new
+//!    TCB, rolling back to the previous configuration if they do not;
+//! 3. [commit](TcbUpdate::commit) the firmware and TCB with SNP_COMMIT.
+//!
+//! An update which is dropped after the new TCB was reported, but before it
+//! was committed, restores the previous configuration.
+
+use super::{Config, HostFirmware, SnpPlatformStatus, TcbVersion};
+
+use crate::error::TcbUpdateError;
+
+/// The progress of a [TcbUpdate].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TcbUpdateState {
+    /// The update was validated, but nothing was sent to the platform.
+    Planned,
+
+    /// The platform reports the new TCB to guests.
+    Reported,
+
+    /// Guests were verified to attest with the new TCB.
+    Verified,
+
+    /// The firmware and TCB were committed.
+    Committed,
+
+    /// The previous configuration was restored.
+    RolledBack,
+}
+
+/// An update of the reported TCB, driven one step at a time.
+///
+/// # Example:
+/// ```ignore
+/// let mut firmware: Firmware = Firmware::open().unwrap();
+/// let target: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+///
+/// let mut update = TcbUpdate::begin(&mut firmware, target).unwrap();
+/// update.report().unwrap();
+/// update.verify(|tcb| guests_attest_with(tcb)).unwrap();
+/// update.commit().unwrap();
+/// ```
+pub struct TcbUpdate<'a, F: HostFirmware + ?Sized> {
+    firmware: &'a mut F,
+    previous: Config,
+    target: Config,
+    state: TcbUpdateState,
+}
+
+impl<'a, F: HostFirmware + ?Sized> TcbUpdate<'a, F> {
+    /// Plan an update which reports `target` to guests. The chip ID mask
+    /// currently in effect is kept, and `target` must not be newer than the
+    /// TCB installed on the platform.
+    pub fn begin(firmware: &'a mut F, target: TcbVersion) -> Result<Self, TcbUpdateError> {
+        let status: SnpPlatformStatus = firmware.snp_platform_status()?;
+        let previous: Config = Config::from_status(&status);
+        let target: Config = Config::new(target, previous.mask_id);
+
+        target.validate_for(&status.platform_tcb_version)?;
+
+        Ok(Self {
+            firmware,
+            previous,
+            target,
+            state: TcbUpdateState::Planned,
+        })
+    }
+
+    /// The progress of the update.
+    pub fn state(&self) -> TcbUpdateState {
+        self.state
+    }
+
+    /// The configuration in effect before the update.
+    pub fn previous(&self) -> Config {
+        self.previous
+    }
+
+    /// The TCB the update reports to guests.
+    pub fn target(&self) -> TcbVersion {
+        self.target.reported_tcb
+    }
+
+    /// Report the new TCB to guests, and confirm that the platform applied
+    /// it. If it was not applied, the previous configuration is restored.
+    pub fn report(&mut self) -> Result<(), TcbUpdateError> {
+        self.expect(&[TcbUpdateState::Planned])?;
+
+        self.firmware.snp_set_config(self.target)?;
+        self.state = TcbUpdateState::Reported;
+
+        let applied: TcbVersion = self.firmware.snp_get_config()?.reported_tcb;
+
+        if applied != self.target() {
+            self.rollback()?;
+            return Err(TcbUpdateError::NotApplied);
+        }
+
+        Ok(())
+    }
+
+    /// Check that guests still attest with the new TCB. `check` is handed
+    /// the reported TCB, and returns whether attestation succeeded (e.g.
+    /// after fetching a report from a canary guest and verifying it against
+    /// the VCEK for that TCB). If it did not, the previous configuration is
+    /// restored.
+    pub fn verify<C>(&mut self, check: C) -> Result<(), TcbUpdateError>
+    where
+        C: FnOnce(&TcbVersion) -> bool,
+    {
+        self.expect(&[TcbUpdateState::Reported])?;
+
+        if !check(&self.target()) {
+            self.rollback()?;
+            return Err(TcbUpdateError::VerificationFailed);
+        }
+
+        self.state = TcbUpdateState::Verified;
+
+        Ok(())
+    }
+
+    /// Commit the firmware and TCB. This cannot be undone.
+    pub fn commit(&mut self) -> Result<(), TcbUpdateError> {
+        self.expect(&[TcbUpdateState::Verified])?;
+
+        self.firmware.snp_commit()?;
+        self.state = TcbUpdateState::Committed;
+
+        Ok(())
+    }
+
+    /// Restore the configuration in effect before the update.
+    pub fn rollback(&mut self) -> Result<(), TcbUpdateError> {
+        self.expect(&[TcbUpdateState::Reported, TcbUpdateState::Verified])?;
+
+        self.firmware.snp_set_config(self.previous)?;
+        self.state = TcbUpdateState::RolledBack;
+
+        Ok(())
+    }
+
+    fn expect(&self, states: &[TcbUpdateState]) -> Result<(), TcbUpdateError> {
+        if !states.contains(&self.state) {
+            return Err(TcbUpdateError::OutOfOrder);
+        }
+
+        Ok(())
+    }
+}
+
+impl<F: HostFirmware + ?Sized> Drop for TcbUpdate<'_, F> {
+    fn drop(&mut self) {
+        if matches!(
+            self.state,
+            TcbUpdateState::Reported | TcbUpdateState::Verified
+        ) {
+            let _ = self.rollback();
+        }
+    }
+}
+
+impl<F: HostFirmware + ?Sized> std::fmt::Debug for TcbUpdate<'_, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcbUpdate")
+            .field("previous", &self.previous)
+            .field("target", &self.target)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        error::{ConfigError, UserApiError},
+        firmware::host::{MaskId, MockCommand, MockFirmware},
+    };
+
+    fn status(reported: TcbVersion) -> SnpPlatformStatus {
+        SnpPlatformStatus {
+            platform_tcb_version: TcbVersion::new(3, 0, 8, 115),
+            reported_tcb_version: reported,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_update_committed() {
+        let old: TcbVersion = TcbVersion::new(2, 0, 8, 115);
+        let new: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_snp_platform_status(Ok(status(old)))
+            .on_snp_platform_status(Ok(status(new)));
+
+        let mut update = TcbUpdate::begin(&mut mock, new).unwrap();
+        assert!(matches!(update.commit(), Err(TcbUpdateError::OutOfOrder)));
+
+        update.report().unwrap();
+        update.verify(|tcb| *tcb == new).unwrap();
+        update.commit().unwrap();
+        assert_eq!(update.state(), TcbUpdateState::Committed);
+        drop(update);
+
+        assert_eq!(
+            mock.commands(),
+            &[
+                MockCommand::SnpPlatformStatus,
+                MockCommand::SnpSetConfig(Config::new(new, MaskId(0))),
+                MockCommand::SnpPlatformStatus,
+                MockCommand::SnpCommit,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_rolled_back() {
+        let old: TcbVersion = TcbVersion::new(2, 0, 8, 115);
+        let new: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_snp_platform_status(Ok(status(old)))
+            .on_snp_platform_status(Ok(status(new)));
+
+        let mut update = TcbUpdate::begin(&mut mock, new).unwrap();
+        update.report().unwrap();
+        assert!(matches!(
+            update.verify(|_| false),
+            Err(TcbUpdateError::VerificationFailed)
+        ));
+        assert_eq!(update.state(), TcbUpdateState::RolledBack);
+        drop(update);
+
+        assert_eq!(
+            mock.commands().last(),
+            Some(&MockCommand::SnpSetConfig(Config::new(old, MaskId(0))))
+        );
+    }
+
+    #[test]
+    fn test_update_dropped() {
+        let old: TcbVersion = TcbVersion::new(2, 0, 8, 115);
+        let new: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_snp_platform_status(Ok(status(old)))
+            .on_snp_platform_status(Ok(status(new)));
+
+        TcbUpdate::begin(&mut mock, new).unwrap().report().unwrap();
+
+        assert_eq!(
+            mock.commands().last(),
+            Some(&MockCommand::SnpSetConfig(Config::new(old, MaskId(0))))
+        );
+    }
+
+    #[test]
+    fn test_update_too_new() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_snp_platform_status(Ok(status(TcbVersion::default())));
+
+        assert!(matches!(
+            TcbUpdate::begin(&mut mock, TcbVersion::new(4, 0, 8, 115)),
+            Err(TcbUpdateError::FirmwareError(UserApiError::ConfigError(
+                ConfigError::ReportedTcbTooNew
+            )))
+        ));
+    }
+}
@@ -3,15 +3,21 @@
 #[cfg(feature = "sev")]
 mod sev;
 
-#[cfg(feature = "snp")]
+#[cfg(all(feature = "snp", feature = "std"))]
 mod snp;
 
+#[cfg(feature = "snp-types")]
+mod tcb_version;
+
 #[cfg(feature = "sev")]
 pub use self::sev::*;
 
-#[cfg(feature = "snp")]
+#[cfg(all(feature = "snp", feature = "std"))]
 pub use self::snp::*;
 
+#[cfg(feature = "snp-types")]
+pub use self::tcb_version::TcbVersion;
+
 /// The platform state.
 ///
 /// The underlying SEV platform behaves like a state machine and can
@@ -31,8 +37,8 @@ pub enum State {
     Working,
 }
 
-impl std::fmt::Display for State {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for State {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let state = match self {
             State::Uninitialized => "uninitialized",
             State::Initialized => "initialized",
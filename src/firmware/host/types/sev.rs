@@ -76,7 +76,7 @@ impl LegacyAttestationReport {
         let mut bytes: [u8; MEASURABLE_BYTES] = [0; 52];
         bytes[0..MNONCE_SIZE].copy_from_slice(&self.mnonce);
         bytes[MNONCE_SIZE..POLICY_OFFSET].copy_from_slice(&self.launch_digest);
-        bytes[POLICY_OFFSET..].copy_from_slice(&self.policy.to_ne_bytes());
+        bytes[POLICY_OFFSET..].copy_from_slice(&self.policy.to_le_bytes());
         bytes
     }
 }
@@ -5,7 +5,11 @@ pub use crate::firmware::linux::host::types::RawData;
 
 pub(crate) use crate::firmware::linux::host as FFI;
 
-use crate::Version;
+use crate::{error::ParseError, ParseOptions, Version};
+
+use super::TcbVersion;
+
+use crate::error::ConfigError;
 
 #[cfg(target_os = "linux")]
 use crate::error::CertError;
@@ -13,6 +17,8 @@ use crate::error::CertError;
 use std::{
     convert::{TryFrom, TryInto},
     fmt::Display,
+    fs, io,
+    path::Path,
 };
 
 use bitfield::bitfield;
@@ -106,6 +112,45 @@ impl TryFrom<&uuid::Uuid> for CertType {
     }
 }
 
+impl CertType {
+    /// The name of the file holding a certificate of this type in a
+    /// certificate directory, as laid out by `snpguest`: `ark.der`, `ask.der`,
+    /// `vcek.der`, `vlek.der` and `crl.der`, or `<guid>.bin` for other types.
+    /// [CertType::Empty] has no file.
+    pub fn file_name(&self) -> Option<String> {
+        Some(match self {
+            CertType::Empty => return None,
+            CertType::ARK => "ark.der".to_string(),
+            CertType::ASK => "ask.der".to_string(),
+            CertType::VCEK => "vcek.der".to_string(),
+            CertType::VLEK => "vlek.der".to_string(),
+            CertType::CRL => "crl.der".to_string(),
+            CertType::OTHER(guid) => format!("{}.bin", guid),
+        })
+    }
+
+    /// The certificate type stored under `name` in a certificate directory,
+    /// if any. The inverse of [CertType::file_name].
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "ark.der" => CertType::ARK,
+            "ask.der" => CertType::ASK,
+            "vcek.der" => CertType::VCEK,
+            "vlek.der" => CertType::VLEK,
+            "crl.der" => CertType::CRL,
+            _ => {
+                let guid = uuid::Uuid::parse_str(name.strip_suffix(".bin")?).ok()?;
+
+                match CertType::try_from(&guid).ok()? {
+                    CertType::OTHER(guid) => CertType::OTHER(guid),
+                    // Known types are only ever stored under their own names.
+                    _ => return None,
+                }
+            }
+        })
+    }
+}
+
 impl Ord for CertType {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         match (self, other) {
@@ -188,6 +233,92 @@ impl CertTableEntry {
 
         Ok(unsafe { FFI::types::CertTableEntry::parse_table(cert_bytes_ptr).unwrap() })
     }
+
+    /// Writes the certificates of `table` to the directory `dir`, creating it
+    /// if needed, one file per entry named by [CertType::file_name]. Existing
+    /// files of the same names are replaced.
+    ///
+    /// # Example:
+    /// ```ignore
+    /// let (report, certs) = firmware.get_ext_report(None, Some(data), None)?;
+    ///
+    /// if let Some(certs) = certs {
+    ///     CertTableEntry::save_dir(&certs, "./certs")?;
+    /// }
+    /// ```
+    pub fn save_dir(table: &[Self], dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        for entry in table {
+            if let Some(name) = entry.cert_type.file_name() {
+                fs::write(dir.join(name), &entry.data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the certificates stored in the directory `dir` by
+    /// [CertTableEntry::save_dir] or `snpguest`, in [CertType] order. Files
+    /// not named by [CertType::file_name] are ignored.
+    pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Vec<Self>> {
+        let mut table = vec![];
+
+        for file in fs::read_dir(dir)? {
+            let file = file?;
+
+            let cert_type = match file.file_name().to_str().and_then(CertType::from_file_name) {
+                Some(cert_type) => cert_type,
+                None => continue,
+            };
+
+            if file.file_type()?.is_file() {
+                table.push(Self::new(cert_type, fs::read(file.path())?));
+            }
+        }
+
+        table.sort();
+        Ok(table)
+    }
+
+    /// Parses bytes in kernel CertTable format, checking that every entry
+    /// lies within `bytes`. With `options.strict_reserved`, the offset and
+    /// length of the entry terminating the table must be zero.
+    pub fn parse_cert_table(bytes: &[u8], options: ParseOptions) -> Result<Vec<Self>, ParseError> {
+        const ENTRY_SIZE: usize = 24;
+
+        let mut table = vec![];
+
+        for (index, entry) in bytes.chunks(ENTRY_SIZE).enumerate() {
+            if entry.len() < ENTRY_SIZE {
+                break;
+            }
+
+            let (guid, rest) = entry.split_at(16);
+            let offset = u32::from_le_bytes(rest[..4].try_into().unwrap());
+            let length = u32::from_le_bytes(rest[4..].try_into().unwrap());
+
+            if guid.iter().all(|b| *b == 0) {
+                if options.strict_reserved && (offset != 0 || length != 0) {
+                    let pos = rest.iter().position(|b| *b != 0).unwrap_or_default();
+                    return Err(ParseError::Reserved(index * ENTRY_SIZE + 16 + pos));
+                }
+
+                return Ok(table);
+            }
+
+            let data = (offset as usize)
+                .checked_add(length as usize)
+                .and_then(|end| bytes.get(offset as usize..end))
+                .ok_or(ParseError::OutOfBounds { offset, length })?;
+
+            let guid = uuid::Uuid::from_slice(guid).map_err(|_| ParseError::InvalidGuid)?;
+            table.push(Self::from_guid(&guid, data.to_vec()).map_err(|_| ParseError::InvalidGuid)?);
+        }
+
+        Err(ParseError::Truncated)
+    }
 }
 
 impl Ord for CertTableEntry {
@@ -223,10 +354,79 @@ pub struct Build {
     pub build: u32,
 }
 
+impl From<Build> for Version {
+    fn from(build: Build) -> Self {
+        build.version
+    }
+}
+
+bitfield! {
+    /// Configuration and capability bits reported by SNP_PLATFORM_STATUS.
+    /// Firmware which predates a bit reports it as zero.
+    ///
+    /// | Bit(s) | Name | Description |
+    /// |--------|------|-------------|
+    /// |0|MASK_CHIP_ID|Indicates that the CHIP_ID field in the attestation report will always be zero.|
+    /// |1|MASK_CHIP_KEY|Indicates that the VCEK is not used in attestation and guest key derivation.|
+    /// |2|VLEK_EN|Indicates that a VLEK hashstick is loaded.|
+    /// |3|FEATURE_INFO|Indicates that SNP_FEATURE_INFO is supported.|
+    /// |4|RAPL_DIS|Indicates that RAPL is disabled.|
+    /// |5|CIPHERTEXT_HIDING_CAP|Indicates that the platform is capable of ciphertext hiding.|
+    /// |6|CIPHERTEXT_HIDING_EN|Indicates that ciphertext hiding is enabled.|
+    #[repr(C)]
+    #[derive(Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct SnpStatusFlags(u32);
+    impl Debug;
+    /// Indicates that the CHIP_ID field in the attestation report will always be zero.
+    pub mask_chip_id, _: 0, 0;
+    /// Indicates that the VCEK is not used in attestation and guest key derivation.
+    pub mask_chip_key, _: 1, 1;
+    /// Indicates that a VLEK hashstick is loaded.
+    pub vlek_en, _: 2, 2;
+    /// Indicates that SNP_FEATURE_INFO is supported.
+    pub feature_info, _: 3, 3;
+    /// Indicates that RAPL is disabled.
+    pub rapl_dis, _: 4, 4;
+    /// Indicates that the platform is capable of ciphertext hiding.
+    pub ciphertext_hiding_cap, _: 5, 5;
+    /// Indicates that ciphertext hiding is enabled.
+    pub ciphertext_hiding_en, _: 6, 6;
+}
+
+impl SnpStatusFlags {
+    /// The mask ID currently configured on the platform.
+    pub fn mask_id(&self) -> MaskId {
+        MaskId(self.0 & MaskId::VALID_BITS)
+    }
+}
+
+impl Display for SnpStatusFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            r#"
+  Mask Chip ID:          {}
+  Mask Chip Key:         {}
+  VLEK Enabled:          {}
+  Feature Info:          {}
+  RAPL Disabled:         {}
+  Ciphertext Hiding Cap: {}
+  Ciphertext Hiding En:  {}"#,
+            self.mask_chip_id(),
+            self.mask_chip_key(),
+            self.vlek_en(),
+            self.feature_info(),
+            self.rapl_dis(),
+            self.ciphertext_hiding_cap(),
+            self.ciphertext_hiding_en(),
+        )
+    }
+}
+
 /// Query the SEV-SNP platform status.
 ///
 /// (Chapter 8.3; Table 38)
-#[derive(Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 #[repr(C)]
 pub struct SnpPlatformStatus {
     /// The firmware API version (major.minor)
@@ -241,8 +441,8 @@ pub struct SnpPlatformStatus {
     /// The platform build ID.
     pub build_id: u32,
 
-    /// MaskChipId
-    pub mask_chip_id: u32,
+    /// Configuration and capability bits of the platform.
+    pub flags: SnpStatusFlags,
 
     /// The number of valid guests maintained by the SEV-SNP firmware.
     pub guest_count: u32,
@@ -254,6 +454,46 @@ pub struct SnpPlatformStatus {
     pub reported_tcb_version: TcbVersion,
 }
 
+impl SnpPlatformStatus {
+    /// Whether the RMP has been initialized.
+    pub fn rmp_initialized(&self) -> bool {
+        self.is_rmp_init & 1 == 1
+    }
+
+    /// The raw configuration and capability bits of the platform, which
+    /// were exposed as the `mask_chip_id` field before [flags](Self::flags)
+    /// decoded them.
+    #[deprecated(note = "use `flags`, e.g. `flags.mask_chip_id()`")]
+    pub fn mask_chip_id(&self) -> u32 {
+        self.flags.0
+    }
+}
+
+impl Display for SnpPlatformStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            r#"
+SNP Platform Status:
+  API Version:      {}
+  Build ID:         {}
+  State:            {}
+  RMP Initialized:  {}
+  Guest Count:      {}{}
+Platform {}
+Reported {}"#,
+            self.version,
+            self.build_id,
+            self.state,
+            self.rmp_initialized(),
+            self.guest_count,
+            self.flags,
+            self.platform_tcb_version,
+            self.reported_tcb_version,
+        )
+    }
+}
+
 /// Sets the system wide configuration values for SNP.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(C, packed)]
@@ -288,13 +528,47 @@ impl Config {
             reserved: [0; 52],
         }
     }
+
+    /// The configuration currently in effect, as reported by the platform
+    /// status.
+    pub fn from_status(status: &SnpPlatformStatus) -> Self {
+        Self::new(status.reported_tcb_version, status.flags.mask_id())
+    }
+
+    /// Check that the configuration is well formed: no reserved mask ID
+    /// bits may be set.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let reserved: u32 = self.mask_id.0 & !MaskId::VALID_BITS;
+
+        if reserved != 0 {
+            return Err(ConfigError::ReservedMaskBits(reserved));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the configuration is well formed and may be applied to
+    /// a platform whose installed TCB is `platform_tcb`. The firmware will
+    /// not report a TCB newer than the one installed.
+    pub fn validate_for(&self, platform_tcb: &TcbVersion) -> Result<(), ConfigError> {
+        self.validate()?;
+
+        let reported_tcb: TcbVersion = self.reported_tcb;
+
+        match reported_tcb.partial_cmp(platform_tcb) {
+            Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal) => Ok(()),
+            _ => Err(ConfigError::ReportedTcbTooNew),
+        }
+    }
 }
 
 #[cfg(feature = "snp")]
 impl TryFrom<Config> for FFI::types::SnpSetConfig {
-    type Error = uuid::Error;
+    type Error = ConfigError;
 
     fn try_from(value: Config) -> Result<Self, Self::Error> {
+        value.validate()?;
+
         let mut snp_config: SnpSetConfig = Default::default();
 
         snp_config.reported_tcb = value.reported_tcb;
@@ -317,55 +591,6 @@ impl TryFrom<FFI::types::SnpSetConfig> for Config {
     }
 }
 
-/// TcbVersion represents the version of the firmware.
-///
-/// (Chapter 2.2; Table 3)
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
-#[repr(C)]
-pub struct TcbVersion {
-    /// Current bootloader version.
-    /// SVN of PSP bootloader.
-    pub bootloader: u8,
-    /// Current PSP OS version.
-    /// SVN of PSP operating system.
-    pub tee: u8,
-    _reserved: [u8; 4],
-    /// Version of the SNP firmware.
-    /// Security Version Number (SVN) of SNP firmware.
-    pub snp: u8,
-    /// Lowest current patch level of all the cores.
-    pub microcode: u8,
-}
-
-impl Display for TcbVersion {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            r#"
-TCB Version:
-  Microcode:   {}
-  SNP:         {}
-  TEE:         {}
-  Boot Loader: {}
-  "#,
-            self.microcode, self.snp, self.tee, self.bootloader
-        )
-    }
-}
-
-impl TcbVersion {
-    /// Creates a new instance of a TcbVersion
-    pub fn new(bootloader: u8, tee: u8, snp: u8, microcode: u8) -> Self {
-        Self {
-            bootloader,
-            tee,
-            snp,
-            microcode,
-            _reserved: Default::default(),
-        }
-    }
-}
-
 bitfield! {
     /// Mask ID values that would go into an SNP CONFIG
     ///
@@ -383,6 +608,11 @@ bitfield! {
     pub mask_chip_key, _: 1, 1;
 }
 
+impl MaskId {
+    /// The bits of the mask ID which are not reserved.
+    const VALID_BITS: u32 = 0b11;
+}
+
 impl Display for MaskId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -400,9 +630,92 @@ impl Display for MaskId {
 
 #[cfg(test)]
 mod tests {
-    use super::CertType;
+    use super::{CertType, Config, MaskId, SnpPlatformStatus, SnpStatusFlags, TcbVersion};
+    use crate::error::ConfigError;
     use uuid::Uuid;
 
+    #[test]
+    fn test_tcb_version_ordering() {
+        let base: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+
+        assert!(base <= base);
+        assert!(base < TcbVersion::new(3, 0, 10, 115));
+        assert!(TcbVersion::new(2, 0, 8, 115) < base);
+        assert_eq!(base.partial_cmp(&TcbVersion::new(4, 0, 8, 100)), None);
+    }
+
+    #[test]
+    fn test_tcb_version_eq_consistent_with_ordering() {
+        let base: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+        let reserved: TcbVersion = TcbVersion::from(u64::from(base) | 0x0000_0012_3400_0000);
+
+        assert_ne!(u64::from(reserved), u64::from(base));
+        assert_eq!(reserved, base);
+        assert_eq!(reserved.partial_cmp(&base), Some(std::cmp::Ordering::Equal));
+
+        for other in [
+            reserved,
+            TcbVersion::new(3, 0, 9, 115),
+            TcbVersion::new(4, 0, 8, 100),
+        ] {
+            assert_eq!(
+                base == other,
+                base.partial_cmp(&other) == Some(std::cmp::Ordering::Equal)
+            );
+        }
+    }
+
+    #[test]
+    fn test_tcb_version_u64() {
+        let tcb: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+
+        assert_eq!(u64::from(tcb), 0x7308_0000_0000_0003);
+        assert_eq!(TcbVersion::from(0x7308_0000_0000_0003), tcb);
+    }
+
+    #[test]
+    fn test_status_flags() {
+        let flags: SnpStatusFlags = SnpStatusFlags(0b1100101);
+
+        assert_eq!(flags.mask_chip_id(), 1);
+        assert_eq!(flags.vlek_en(), 1);
+        assert_eq!(flags.ciphertext_hiding_en(), 1);
+        assert_eq!(flags.mask_id(), MaskId(0b01));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_platform_status_mask_chip_id() {
+        let status: SnpPlatformStatus = SnpPlatformStatus {
+            flags: SnpStatusFlags(0b101),
+            ..Default::default()
+        };
+
+        assert_eq!(status.mask_chip_id(), 0b101);
+    }
+
+    #[test]
+    fn test_platform_status_size() {
+        assert_eq!(std::mem::size_of::<SnpPlatformStatus>(), 32);
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let platform: TcbVersion = TcbVersion::new(3, 0, 10, 169);
+
+        let config: Config = Config::new(TcbVersion::new(3, 0, 8, 115), MaskId(0b11));
+        assert_eq!(config.validate_for(&platform), Ok(()));
+
+        let config: Config = Config::new(TcbVersion::new(3, 0, 8, 115), MaskId(0b101));
+        assert_eq!(config.validate(), Err(ConfigError::ReservedMaskBits(0b100)));
+
+        let config: Config = Config::new(TcbVersion::new(3, 0, 11, 115), MaskId(0));
+        assert_eq!(
+            config.validate_for(&platform),
+            Err(ConfigError::ReportedTcbTooNew)
+        );
+    }
+
     #[test]
     fn test_cert_type_sort_vcek() {
         let mut certs: Vec<CertType> = vec![
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+/// TcbVersion represents the version of the firmware.
+///
+/// (Chapter 2.2; Table 3)
+#[derive(Clone, Copy, Debug, Default, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct TcbVersion {
+    /// Current bootloader version.
+    /// SVN of PSP bootloader.
+    pub bootloader: u8,
+    /// Current PSP OS version.
+    /// SVN of PSP operating system.
+    pub tee: u8,
+    _reserved: [u8; 4],
+    /// Version of the SNP firmware.
+    /// Security Version Number (SVN) of SNP firmware.
+    pub snp: u8,
+    /// Lowest current patch level of all the cores.
+    pub microcode: u8,
+}
+
+impl core::fmt::Display for TcbVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            r#"
+TCB Version:
+  Microcode:   {}
+  SNP:         {}
+  TEE:         {}
+  Boot Loader: {}
+  "#,
+            self.microcode, self.snp, self.tee, self.bootloader
+        )
+    }
+}
+
+/// TCB versions are equal if their SVNs are equal. Like their ordering,
+/// equality ignores the reserved bytes.
+impl PartialEq for TcbVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.bootloader == other.bootloader
+            && self.tee == other.tee
+            && self.snp == other.snp
+            && self.microcode == other.microcode
+    }
+}
+
+/// TCB versions are ordered component by component: one version is only
+/// less than another if none of its SVNs are greater. Versions where some
+/// SVNs are greater and others are less are unordered.
+impl PartialOrd for TcbVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        use core::cmp::Ordering::*;
+
+        let components = [
+            self.bootloader.cmp(&other.bootloader),
+            self.tee.cmp(&other.tee),
+            self.snp.cmp(&other.snp),
+            self.microcode.cmp(&other.microcode),
+        ];
+
+        match (components.contains(&Less), components.contains(&Greater)) {
+            (false, false) => Some(Equal),
+            (true, false) => Some(Less),
+            (false, true) => Some(Greater),
+            (true, true) => None,
+        }
+    }
+}
+
+impl TcbVersion {
+    /// Creates a new instance of a TcbVersion
+    pub fn new(bootloader: u8, tee: u8, snp: u8, microcode: u8) -> Self {
+        Self {
+            bootloader,
+            tee,
+            snp,
+            microcode,
+            _reserved: Default::default(),
+        }
+    }
+}
+
+impl From<u64> for TcbVersion {
+    fn from(value: u64) -> Self {
+        let bytes = value.to_le_bytes();
+
+        Self {
+            bootloader: bytes[0],
+            tee: bytes[1],
+            _reserved: [bytes[2], bytes[3], bytes[4], bytes[5]],
+            snp: bytes[6],
+            microcode: bytes[7],
+        }
+    }
+}
+
+impl From<TcbVersion> for u64 {
+    fn from(value: TcbVersion) -> Self {
+        let [r0, r1, r2, r3] = value._reserved;
+
+        u64::from_le_bytes([
+            value.bootloader,
+            value.tee,
+            r0,
+            r1,
+            r2,
+            r3,
+            value.snp,
+            value.microcode,
+        ])
+    }
+}
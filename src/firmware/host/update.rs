@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Staging of SEV firmware updates.
+//!
+//! Linux does not expose the DOWNLOAD_FIRMWARE command through `/dev/sev`.
+//! Instead the `ccp` driver issues it while initializing the platform, using
+//! the first image it finds in the firmware search path among
+//! `amd/amd_sev_fam<family>h_model<model>h.sbin`,
+//! `amd/amd_sev_fam<family>h_model<model / 0x10>xh.sbin` and `amd/sev.fw`.
+//! A [FirmwareImage] checks that an update is a whole AMD Secure Processor
+//! binary and places it under the first, model-specific, name; the update
+//! is applied the next time the driver initializes the platform (e.g. after
+//! reloading `ccp` or rebooting).
+//!
+//! The image's signature is not verified here: the AMD Secure Processor
+//! verifies it when the image is downloaded, and rejects the update if it
+//! does not verify.
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
+};
+
+/// The default firmware search path of the Linux kernel.
+pub const FIRMWARE_SEARCH_PATH: &str = "/lib/firmware";
+
+/// The largest image the kernel can hand to DOWNLOAD_FIRMWARE. The image and
+/// its command buffer must fit in a single 4 MiB allocation.
+pub const MAX_FIRMWARE_IMAGE_LEN: usize = (4 << 20) - 16;
+
+/// The size (in bytes) of the header of a firmware image.
+pub const FIRMWARE_HEADER_LEN: usize = 0x100;
+
+/// The magic value of the header of AMD Secure Processor binaries.
+const FIRMWARE_MAGIC: [u8; 4] = *b"$PS1";
+
+const MAGIC_OFFSET: usize = 0x10;
+const SIGNED_LEN_OFFSET: usize = 0x14;
+const VERSION_OFFSET: usize = 0x60;
+
+/// The header of a SEV firmware image
+///
+/// Firmware images are AMD Secure Processor binaries: a 256-byte header,
+/// the body it describes and the signature of both. Only the fields needed
+/// to check that the image is whole are decoded. The signature (the only
+/// integrity check of the image) is verified by the AMD Secure Processor
+/// when the image is downloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FirmwareHeader {
+    /// The size (in bytes) of the signed body following the header.
+    pub signed_len: u32,
+
+    /// The version of the firmware, as the header records it.
+    pub version: [u8; 4],
+}
+
+impl FirmwareHeader {
+    /// Parse the header at the start of `image`.
+    pub fn parse(image: &[u8]) -> Result<Self> {
+        if image.len() < FIRMWARE_HEADER_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "firmware image is {} bytes, shorter than its {FIRMWARE_HEADER_LEN}-byte header",
+                    image.len()
+                ),
+            ));
+        }
+
+        let field = |offset: usize| -> [u8; 4] {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&image[offset..offset + 4]);
+            bytes
+        };
+
+        if field(MAGIC_OFFSET) != FIRMWARE_MAGIC {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "firmware image does not start with an AMD Secure Processor header",
+            ));
+        }
+
+        Ok(Self {
+            signed_len: u32::from_le_bytes(field(SIGNED_LEN_OFFSET)),
+            version: field(VERSION_OFFSET),
+        })
+    }
+}
+
+/// A SEV firmware image to be downloaded to the AMD Secure Processor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirmwareImage(Vec<u8>);
+
+impl FirmwareImage {
+    /// Validate an in-memory firmware image: it must fit in the kernel's
+    /// command buffer, start with a [FirmwareHeader] and hold the whole
+    /// body the header describes.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        if bytes.len() > MAX_FIRMWARE_IMAGE_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "firmware image is {} bytes; at most {MAX_FIRMWARE_IMAGE_LEN} are supported",
+                    bytes.len()
+                ),
+            ));
+        }
+
+        let header = FirmwareHeader::parse(&bytes)?;
+        let body = (bytes.len() - FIRMWARE_HEADER_LEN) as u64;
+
+        if u64::from(header.signed_len) > body {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "firmware image body is {body} bytes, its header describes {}",
+                    header.signed_len
+                ),
+            ));
+        }
+
+        Ok(Self(bytes))
+    }
+
+    /// The header of the image.
+    pub fn header(&self) -> FirmwareHeader {
+        // The header was parsed when the image was validated.
+        FirmwareHeader::parse(&self.0).unwrap()
+    }
+
+    /// Read and validate a firmware image from disk.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Get an immutable reference to the image.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The file name the kernel loads the image for a CPU `family` and
+    /// `model` from first, relative to the firmware search path.
+    pub fn file_name(family: u32, model: u32) -> String {
+        format!("amd/amd_sev_fam{family:02x}h_model{model:02x}h.sbin")
+    }
+
+    /// The CPU family and model of the running platform, as the kernel
+    /// derives them to look up the firmware image.
+    #[cfg(target_arch = "x86_64")]
+    pub fn cpu_signature() -> (u32, u32) {
+        #[allow(unused_unsafe)]
+        let eax: u32 = unsafe { std::arch::x86_64::__cpuid(1) }.eax;
+
+        let mut family: u32 = (eax >> 8) & 0xf;
+        let mut model: u32 = (eax >> 4) & 0xf;
+
+        if family == 0xf {
+            family += (eax >> 20) & 0xff;
+            model |= ((eax >> 16) & 0xf) << 4;
+        }
+
+        (family, model)
+    }
+
+    /// Place the image where the kernel will download it to the AMD Secure
+    /// Processor of a CPU `family` and `model`, under the firmware search
+    /// path `root` (normally [FIRMWARE_SEARCH_PATH]). The image is staged
+    /// under the [model-specific name](Self::file_name), so that images
+    /// already installed for the model's range or as `amd/sev.fw` do not
+    /// shadow it.
+    ///
+    /// The image is written next to its destination and renamed into place,
+    /// so the kernel never observes a partially written file. With `dry_run`
+    /// set nothing is written, and only the destination path is returned.
+    pub fn stage(
+        &self,
+        root: impl AsRef<Path>,
+        family: u32,
+        model: u32,
+        dry_run: bool,
+    ) -> Result<PathBuf> {
+        let path: PathBuf = root.as_ref().join(Self::file_name(family, model));
+
+        if dry_run {
+            return Ok(path);
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let staging: PathBuf = path.with_extension("sbin.new");
+        let mut file: fs::File = fs::File::create(&staging)?;
+        file.write_all(&self.0)?;
+        file.sync_all()?;
+        fs::rename(&staging, &path)?;
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(
+            FirmwareImage::file_name(0x17, 0x31),
+            "amd/amd_sev_fam17h_model31h.sbin"
+        );
+        assert_eq!(
+            FirmwareImage::file_name(0x19, 0x01),
+            "amd/amd_sev_fam19h_model01h.sbin"
+        );
+    }
+
+    /// An image whose header describes a body of `signed_len` bytes,
+    /// followed by `body` bytes.
+    fn image(signed_len: u32, body: usize) -> Vec<u8> {
+        let mut image = vec![0u8; FIRMWARE_HEADER_LEN + body];
+        image[MAGIC_OFFSET..][..4].copy_from_slice(b"$PS1");
+        image[SIGNED_LEN_OFFSET..][..4].copy_from_slice(&signed_len.to_le_bytes());
+        image[VERSION_OFFSET..][..4].copy_from_slice(&[0x18, 0x01, 0x00, 0x00]);
+        image
+    }
+
+    #[test]
+    fn test_header() {
+        let header = FirmwareHeader::parse(&image(0x40, 0x140)).unwrap();
+        assert_eq!(header.signed_len, 0x40);
+        assert_eq!(header.version, [0x18, 0x01, 0x00, 0x00]);
+
+        assert!(FirmwareHeader::parse(&image(0x40, 0x140)[..0xff]).is_err());
+
+        let mut magic = image(0x40, 0x140);
+        magic[MAGIC_OFFSET] = b'#';
+        assert!(FirmwareHeader::parse(&magic).is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(FirmwareImage::from_bytes(vec![]).is_err());
+        assert!(FirmwareImage::from_bytes(vec![1; 0x400]).is_err());
+        assert!(FirmwareImage::from_bytes(image(0, MAX_FIRMWARE_IMAGE_LEN)).is_err());
+        assert!(FirmwareImage::from_bytes(image(0x200, 0x1ff)).is_err());
+
+        let image = FirmwareImage::from_bytes(image(0x200, 0x300)).unwrap();
+        assert_eq!(image.header().signed_len, 0x200);
+    }
+
+    #[test]
+    fn test_stage() {
+        let root: PathBuf = std::env::temp_dir().join(format!("sev-fw-{}", std::process::id()));
+        let image: FirmwareImage = FirmwareImage::from_bytes(image(0x40, 0x40)).unwrap();
+
+        let path: PathBuf = image.stage(&root, 0x19, 0x11, true).unwrap();
+        assert_eq!(path, root.join("amd/amd_sev_fam19h_model11h.sbin"));
+        assert!(!path.exists());
+
+        assert_eq!(image.stage(&root, 0x19, 0x11, false).unwrap(), path);
+        assert_eq!(fs::read(&path).unwrap(), image.as_bytes());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,547 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Notifications of platform state changes.
+//!
+//! A [PlatformWatcher] polls the platform status on a background thread and
+//! sends a [PlatformEvent] over a channel whenever it changes (e.g. after a
+//! firmware update, SNP initialization or a change of ownership), so that
+//! inventory services can keep attestation metadata current without
+//! re-reading everything themselves.
+
+use super::HostFirmware;
+
+#[cfg(feature = "sev")]
+use super::{PlatformStatusFlags, State, Status};
+
+#[cfg(feature = "sev")]
+use crate::{certs::sev::sev::Chain, Build};
+
+#[cfg(feature = "snp")]
+use super::{Build as SnpBuild, SnpPlatformStatus, TcbVersion};
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// A change of the platform state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlatformEvent {
+    /// The platform started or stopped answering status queries, e.g.
+    /// because the driver was unloaded or loaded.
+    AvailabilityChanged {
+        /// Whether the platform answers status queries.
+        available: bool,
+    },
+
+    /// The SEV firmware build changed.
+    #[cfg(feature = "sev")]
+    SevFirmwareUpdated {
+        /// The previous build.
+        before: Build,
+
+        /// The current build.
+        after: Build,
+    },
+
+    /// The SEV platform state changed.
+    #[cfg(feature = "sev")]
+    SevStateChanged {
+        /// The previous state.
+        before: State,
+
+        /// The current state.
+        after: State,
+    },
+
+    /// The SEV platform was taken or released by an owner.
+    #[cfg(feature = "sev")]
+    OwnershipChanged {
+        /// Whether the platform is owned.
+        owned: bool,
+    },
+
+    /// The SEV certificate chain changed, e.g. because the PEK or PDH was
+    /// regenerated. Only reported when the watcher
+    /// [exports certificates](PlatformWatcher::certificates).
+    #[cfg(feature = "sev")]
+    CertificatesChanged,
+
+    /// The SNP firmware build changed.
+    #[cfg(feature = "snp")]
+    SnpFirmwareUpdated {
+        /// The previous build.
+        before: SnpBuild,
+
+        /// The current build.
+        after: SnpBuild,
+    },
+
+    /// SNP was initialized or shut down.
+    #[cfg(feature = "snp")]
+    SnpStateChanged {
+        /// Whether SNP is initialized.
+        initialized: bool,
+    },
+
+    /// The TCB reported to SNP guests changed.
+    #[cfg(feature = "snp")]
+    ReportedTcbChanged {
+        /// The previous reported TCB.
+        before: TcbVersion,
+
+        /// The current reported TCB.
+        after: TcbVersion,
+    },
+
+    /// The VLEK was enabled or disabled.
+    #[cfg(feature = "snp")]
+    VlekChanged {
+        /// Whether the VLEK is used for attestation.
+        enabled: bool,
+    },
+}
+
+/// The platform state observed by a single poll.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The SEV platform status, if the firmware reported it.
+    #[cfg(feature = "sev")]
+    pub sev: Option<Status>,
+
+    /// The SEV certificate chain, if it was exported.
+    #[cfg(feature = "sev")]
+    pub chain: Option<Chain>,
+
+    /// The SNP platform status, if the firmware reported it.
+    #[cfg(feature = "snp")]
+    pub snp: Option<SnpPlatformStatus>,
+}
+
+impl Snapshot {
+    /// Query the platform behind `firmware`. With `certificates` set, the
+    /// SEV certificate chain is exported as well.
+    pub fn capture<F: HostFirmware + ?Sized>(firmware: &mut F, certificates: bool) -> Self {
+        #[cfg(not(feature = "sev"))]
+        let _ = certificates;
+
+        Self {
+            #[cfg(feature = "sev")]
+            sev: firmware.platform_status().ok(),
+            #[cfg(feature = "sev")]
+            chain: match certificates {
+                true => firmware.pdh_cert_export().ok(),
+                false => None,
+            },
+            #[cfg(feature = "snp")]
+            snp: firmware.snp_platform_status().ok(),
+        }
+    }
+
+    /// Returns true if any status query succeeded.
+    pub fn available(&self) -> bool {
+        #[allow(unused_mut)]
+        let mut available: bool = false;
+
+        #[cfg(feature = "sev")]
+        {
+            available |= self.sev.is_some();
+        }
+
+        #[cfg(feature = "snp")]
+        {
+            available |= self.snp.is_some();
+        }
+
+        available
+    }
+
+    /// The events which lead from this snapshot to `next`.
+    pub fn changes(&self, next: &Snapshot) -> Vec<PlatformEvent> {
+        let mut events: Vec<PlatformEvent> = vec![];
+
+        if self.available() != next.available() {
+            events.push(PlatformEvent::AvailabilityChanged {
+                available: next.available(),
+            });
+        }
+
+        events.extend(self.updates(next));
+        events
+    }
+
+    /// The events which lead from the statuses in this snapshot to those
+    /// in `next`. Statuses missing from either snapshot are not compared.
+    fn updates(&self, next: &Snapshot) -> Vec<PlatformEvent> {
+        let mut events: Vec<PlatformEvent> = vec![];
+
+        #[cfg(feature = "sev")]
+        if let (Some(before), Some(after)) = (&self.sev, &next.sev) {
+            if before.build != after.build {
+                events.push(PlatformEvent::SevFirmwareUpdated {
+                    before: before.build,
+                    after: after.build,
+                });
+            }
+
+            if before.state != after.state {
+                events.push(PlatformEvent::SevStateChanged {
+                    before: before.state,
+                    after: after.state,
+                });
+            }
+
+            let owned: bool = after.flags.contains(PlatformStatusFlags::OWNED);
+            if before.flags.contains(PlatformStatusFlags::OWNED) != owned {
+                events.push(PlatformEvent::OwnershipChanged { owned });
+            }
+        }
+
+        #[cfg(feature = "sev")]
+        if let (Some(before), Some(after)) = (&self.chain, &next.chain) {
+            if before != after {
+                events.push(PlatformEvent::CertificatesChanged);
+            }
+        }
+
+        #[cfg(feature = "snp")]
+        if let (Some(before), Some(after)) = (&self.snp, &next.snp) {
+            let (build_before, build_after) = (snp_build(before), snp_build(after));
+            if build_before != build_after {
+                events.push(PlatformEvent::SnpFirmwareUpdated {
+                    before: build_before,
+                    after: build_after,
+                });
+            }
+
+            if before.state != after.state {
+                events.push(PlatformEvent::SnpStateChanged {
+                    initialized: after.state != 0,
+                });
+            }
+
+            if before.reported_tcb_version != after.reported_tcb_version {
+                events.push(PlatformEvent::ReportedTcbChanged {
+                    before: before.reported_tcb_version,
+                    after: after.reported_tcb_version,
+                });
+            }
+
+            if before.flags.vlek_en() != after.flags.vlek_en() {
+                events.push(PlatformEvent::VlekChanged {
+                    enabled: after.flags.vlek_en() != 0,
+                });
+            }
+        }
+
+        events
+    }
+
+    /// Replace the statuses in this snapshot with those reported in `next`,
+    /// keeping the last known value of any status `next` is missing.
+    fn remember(&mut self, next: Snapshot) {
+        #[cfg(feature = "sev")]
+        {
+            if next.sev.is_some() {
+                self.sev = next.sev;
+            }
+
+            if next.chain.is_some() {
+                self.chain = next.chain;
+            }
+        }
+
+        #[cfg(feature = "snp")]
+        if next.snp.is_some() {
+            self.snp = next.snp;
+        }
+    }
+}
+
+#[cfg(feature = "snp")]
+fn snp_build(status: &SnpPlatformStatus) -> SnpBuild {
+    SnpBuild {
+        version: status.version,
+        build: status.build_id,
+    }
+}
+
+/// Polls the platform for state changes.
+///
+/// # Example:
+/// ```ignore
+/// let watch: Watch = PlatformWatcher::new(Duration::from_secs(30))
+///     .watch(Firmware::open)
+///     .unwrap();
+///
+/// for event in watch.events() {
+///     println!("{event:?}");
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PlatformWatcher {
+    interval: Duration,
+    certificates: bool,
+}
+
+impl PlatformWatcher {
+    /// Poll the platform every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            certificates: false,
+        }
+    }
+
+    /// Export the SEV certificate chain on every poll, to report
+    /// [PlatformEvent::CertificatesChanged].
+    #[cfg(feature = "sev")]
+    pub fn certificates(mut self, certificates: bool) -> Self {
+        self.certificates = certificates;
+        self
+    }
+
+    /// Start polling the platform on a background thread, with a handle
+    /// returned by `open`. Polling stops when the returned [Watch] is
+    /// dropped.
+    ///
+    /// While the platform does not answer, the handle is dropped and `open`
+    /// is called again on every poll, so that the watcher picks the platform
+    /// up again after the driver is reloaded. Changes made while the
+    /// platform was unavailable are reported against the last status it
+    /// reported.
+    pub fn watch<F, O>(self, mut open: O) -> io::Result<Watch>
+    where
+        F: HostFirmware,
+        O: FnMut() -> io::Result<F> + Send + 'static,
+    {
+        let (sender, events): (Sender<PlatformEvent>, Receiver<PlatformEvent>) = mpsc::channel();
+        let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let stopped: Arc<AtomicBool> = stop.clone();
+
+        let thread: JoinHandle<()> =
+            thread::Builder::new()
+                .name("sev-watch".to_string())
+                .spawn(move || {
+                    let mut firmware: Option<F> = None;
+
+                    let mut known: Snapshot = poll(&mut firmware, &mut open, self.certificates);
+                    let mut available: bool = known.available();
+
+                    loop {
+                        thread::park_timeout(self.interval);
+
+                        if stopped.load(Ordering::Acquire) {
+                            return;
+                        }
+
+                        let next: Snapshot = poll(&mut firmware, &mut open, self.certificates);
+
+                        let mut changes: Vec<PlatformEvent> = vec![];
+                        if next.available() != available {
+                            available = next.available();
+                            changes.push(PlatformEvent::AvailabilityChanged { available });
+                        }
+                        changes.extend(known.updates(&next));
+                        known.remember(next);
+
+                        for event in changes {
+                            if sender.send(event).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                })?;
+
+        Ok(Watch {
+            events,
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Capture a snapshot with `firmware`, opening it first if needed. The
+/// handle is dropped if the platform does not answer, to be reopened on the
+/// next poll.
+fn poll<F, O>(firmware: &mut Option<F>, open: &mut O, certificates: bool) -> Snapshot
+where
+    F: HostFirmware,
+    O: FnMut() -> io::Result<F>,
+{
+    if firmware.is_none() {
+        *firmware = open().ok();
+    }
+
+    let snapshot: Snapshot = match firmware {
+        Some(firmware) => Snapshot::capture(firmware, certificates),
+        None => Snapshot::default(),
+    };
+
+    if !snapshot.available() {
+        *firmware = None;
+    }
+
+    snapshot
+}
+
+/// A running [PlatformWatcher].
+#[derive(Debug)]
+pub struct Watch {
+    events: Receiver<PlatformEvent>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watch {
+    /// The channel events are delivered on.
+    pub fn events(&self) -> &Receiver<PlatformEvent> {
+        &self.events
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "snp")]
+mod test {
+    use super::*;
+
+    use crate::{firmware::host::MockFirmware, Version};
+
+    fn snp_status(state: u8, reported: TcbVersion) -> SnpPlatformStatus {
+        SnpPlatformStatus {
+            state,
+            reported_tcb_version: reported,
+            ..Default::default()
+        }
+    }
+
+    fn snp_snapshot(snp: SnpPlatformStatus) -> Snapshot {
+        Snapshot {
+            #[cfg(feature = "sev")]
+            sev: None,
+            #[cfg(feature = "sev")]
+            chain: None,
+            snp: Some(snp),
+        }
+    }
+
+    #[test]
+    fn test_snp_changes() {
+        let tcb: TcbVersion = TcbVersion::new(3, 0, 8, 115);
+
+        let before: Snapshot = snp_snapshot(snp_status(0, TcbVersion::default()));
+        let after: Snapshot = snp_snapshot(snp_status(1, tcb));
+
+        assert_eq!(
+            before.changes(&after),
+            vec![
+                PlatformEvent::SnpStateChanged { initialized: true },
+                PlatformEvent::ReportedTcbChanged {
+                    before: TcbVersion::default(),
+                    after: tcb,
+                },
+            ]
+        );
+        assert!(after.changes(&after).is_empty());
+        assert_eq!(
+            after.changes(&Snapshot::default()),
+            vec![PlatformEvent::AvailabilityChanged { available: false }]
+        );
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut mock: MockFirmware = MockFirmware::new();
+        mock.on_snp_platform_status(Ok(snp_status(0, TcbVersion::default())))
+            .on_snp_platform_status(Ok(snp_status(1, TcbVersion::default())));
+
+        let mut mock = Some(mock);
+        let watch: Watch = PlatformWatcher::new(Duration::from_millis(1))
+            .watch(move || {
+                mock.take()
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+            })
+            .unwrap();
+
+        assert_eq!(
+            watch.events().recv_timeout(Duration::from_secs(10)),
+            Ok(PlatformEvent::SnpStateChanged { initialized: true })
+        );
+    }
+
+    #[test]
+    fn test_watch_reload() {
+        let status = |version: u8| SnpPlatformStatus {
+            version: Version {
+                major: 1,
+                minor: version,
+            },
+            ..Default::default()
+        };
+
+        // The first handle stops answering once its status is read, as after
+        // the driver is unloaded; the platform comes back with new firmware.
+        let mut before: MockFirmware = MockFirmware::new();
+        before.on_snp_platform_status(Ok(status(55)));
+
+        let mut after: MockFirmware = MockFirmware::new();
+        after
+            .on_snp_platform_status(Ok(status(57)))
+            .on_snp_platform_status(Ok(status(57)));
+
+        let mut handles = vec![Err(io::ErrorKind::NotFound.into()), Ok(after), Ok(before)];
+        let watch: Watch = PlatformWatcher::new(Duration::from_millis(1))
+            .watch(move || {
+                handles
+                    .pop()
+                    .unwrap_or_else(|| Err(io::ErrorKind::NotFound.into()))
+            })
+            .unwrap();
+
+        let mut events = watch.events().iter();
+        assert_eq!(
+            events.next(),
+            Some(PlatformEvent::AvailabilityChanged { available: false })
+        );
+        assert_eq!(
+            events.next(),
+            Some(PlatformEvent::AvailabilityChanged { available: true })
+        );
+        assert_eq!(
+            events.next(),
+            Some(PlatformEvent::SnpFirmwareUpdated {
+                before: SnpBuild {
+                    version: Version {
+                        major: 1,
+                        minor: 55
+                    },
+                    build: 0,
+                },
+                after: SnpBuild {
+                    version: Version {
+                        major: 1,
+                        minor: 57
+                    },
+                    build: 0,
+                },
+            })
+        );
+    }
+}
@@ -1,10 +1,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::firmware::linux::guest::types::{
-    DerivedKeyReq, DerivedKeyRsp, ExtReportReq, ReportReq, ReportRsp,
+use crate::firmware::linux::{
+    guest::types::{DerivedKeyReq, DerivedKeyRsp, ExtReportReq, ReportReq, ReportRsp},
+    trace::{IoctlCall, Metrics},
 };
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, os::raw::c_uint, os::unix::io::AsRawFd};
 
 use iocuddle::{Group, Ioctl, WriteRead};
 
@@ -60,4 +61,19 @@ impl<'a, 'b, Req, Rsp> GuestRequest<'a, 'b, Req, Rsp> {
             _phantom_rsp: PhantomData,
         }
     }
+
+    /// Issue this request on `fd` through the given ioctl. The call is
+    /// instrumented when the `tracing` feature is enabled, and
+    /// reported to `metrics` with the `metrics` feature.
+    pub fn issue(
+        &mut self,
+        ioctl: Ioctl<WriteRead, &GuestRequest<'a, 'b, Req, Rsp>>,
+        fd: &mut impl AsRawFd,
+        metrics: Metrics,
+    ) -> std::io::Result<c_uint> {
+        let call = IoctlCall::guest::<Req>(metrics);
+        let result = ioctl.ioctl(fd, self);
+        call.finish(&result, self.fw_err);
+        result
+    }
 }
@@ -116,8 +116,12 @@ pub struct ReportReq {
     /// equal to the current VMPL and at most three.
     vmpl: u32,
 
+    /// Selects which key to use for signing the report (bits 1:0).
+    /// The remaining bits are reserved and must be zero.
+    key_sel: u32,
+
     /// Reserved memory slot, must be zero.
-    _reserved: [u8; 28],
+    _reserved: [u8; 24],
 }
 
 impl Default for ReportReq {
@@ -125,6 +129,7 @@ impl Default for ReportReq {
         Self {
             report_data: [0; 64],
             vmpl: 1,
+            key_sel: KeySelect::Any.into(),
             _reserved: Default::default(),
         }
     }
@@ -154,6 +159,12 @@ impl ReportReq {
 
         Ok(request)
     }
+
+    /// Request the attestation report to be signed with the given key.
+    pub fn with_key_select(mut self, key: KeySelect) -> Self {
+        self.key_sel = key.into();
+        self
+    }
 }
 
 /// The response from the PSP containing the generated attestation report.
@@ -228,7 +239,8 @@ mod test {
             let expected: ReportReq = ReportReq {
                 report_data,
                 vmpl: 0,
-                _reserved: [0; 28],
+                key_sel: 0,
+                _reserved: [0; 24],
             };
 
             let actual: ReportReq = ReportReq::new(Some(report_data), Some(0)).unwrap();
@@ -248,12 +260,25 @@ mod test {
             let expected: ReportReq = ReportReq {
                 report_data,
                 vmpl: 7,
-                _reserved: [0; 28],
+                key_sel: 0,
+                _reserved: [0; 24],
             };
 
             let actual: ReportReq = ReportReq::new(Some(report_data), Some(0)).unwrap();
 
             assert_eq!(expected, actual);
         }
+
+        #[test]
+        pub fn test_with_key_select() {
+            use crate::firmware::guest::KeySelect;
+
+            let actual: ReportReq = ReportReq::new(None, Some(0))
+                .unwrap()
+                .with_key_select(KeySelect::Vlek);
+
+            assert_eq!(actual.key_sel, 2);
+            assert_eq!(std::mem::size_of::<ReportReq>(), 96);
+        }
     }
 }
@@ -5,12 +5,15 @@
 
 use super::types::*;
 
-use crate::impl_const_id;
+use crate::{
+    firmware::linux::trace::{IoctlCall, Metrics},
+    impl_const_id,
+};
 
 #[cfg(feature = "snp")]
 use crate::firmware::host::SnpPlatformStatus;
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, os::raw::c_uint, os::unix::io::AsRawFd};
 
 use iocuddle::*;
 
@@ -158,4 +161,19 @@ impl<'a, T: Id> Command<'a, T> {
             _phantom: PhantomData,
         }
     }
+
+    /// Issue this command on `fd` through the given ioctl. The call is
+    /// instrumented when the `tracing` feature is enabled, and
+    /// reported to `metrics` with the `metrics` feature.
+    pub fn issue(
+        &mut self,
+        ioctl: Ioctl<WriteRead, &Command<'a, T>>,
+        fd: &mut impl AsRawFd,
+        metrics: Metrics,
+    ) -> std::io::Result<c_uint> {
+        let call = IoctlCall::host::<T>(metrics);
+        let result = ioctl.ioctl(fd, self);
+        call.finish(&result, self.error as u64);
+        result
+    }
 }
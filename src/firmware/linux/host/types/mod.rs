@@ -30,7 +30,7 @@ pub struct GetId<'a> {
 #[cfg(any(feature = "sev", feature = "snp"))]
 #[cfg(target_os = "linux")]
 impl<'a> GetId<'a> {
-    pub fn new(id: &'a mut [u8; 64]) -> Self {
+    pub fn new(id: &'a mut [u8]) -> Self {
         Self {
             id_addr: id.as_mut_ptr() as _,
             id_len: id.len() as _,
@@ -38,6 +38,22 @@ impl<'a> GetId<'a> {
         }
     }
 
+    /// A request without a buffer, for which the kernel only writes back
+    /// the length of the unique CPU ID of all the sockets.
+    pub fn query() -> Self {
+        Self {
+            id_addr: 0,
+            id_len: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The length of the buffer, or once the GET_ID2 ioctl was called, of
+    /// the unique CPU ID.
+    pub fn len(&self) -> usize {
+        self.id_len as _
+    }
+
     /// This method is only meaningful if called *after* the GET_ID2 ioctl is called because the
     /// kernel will write the length of the unique CPU ID to `GetId.id_len`.
     pub fn as_slice(&self) -> &[u8] {
@@ -129,10 +129,10 @@ impl CertTableEntry {
             bytes.extend_from_slice(guid.as_bytes());
 
             // Append the offset location to the byte array.
-            bytes.extend_from_slice(&offset.to_ne_bytes());
+            bytes.extend_from_slice(&offset.to_le_bytes());
 
             // Append the length to the byte array.
-            bytes.extend_from_slice(&(entry.data.len() as u32).to_ne_bytes());
+            bytes.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
 
             // Copy the certificate data out until concatenating it later.
             raw_certificates.extend_from_slice(entry.data.as_slice());
@@ -278,12 +278,16 @@ impl<'a, 'b: 'a> std::convert::TryFrom<&'b [u8]> for WrappedVlekHashstick<'a> {
     }
 }
 
+/// The only wrapped VLEK hashstick format version currently defined by the
+/// SEV-SNP Firmware ABI.
+pub const VLEK_WRAPPED_VERSION: u8 = 0;
+
 #[cfg(feature = "snp")]
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 #[repr(C, packed)]
 /// Structure used to load a VLEK hashstick into the AMD Secure Processor.
 pub struct SnpVlekLoad {
-    /// Length of the command buffer read by the AMD Secure Processor.
+    /// Length of this command buffer, as checked by the kernel.
     pub len: u32,
 
     /// Version of wrapped VLEK hashstick (Must be 0h).
@@ -291,7 +295,7 @@ pub struct SnpVlekLoad {
 
     _reserved: [u8; 3],
 
-    /// Address of wrapped VLEK hashstick ([WrappedVlekHashstick])
+    /// Address of the wrapped VLEK hashstick bytes ([WrappedVlekHashstick])
     pub vlek_wrapped_address: u64,
 }
 
@@ -301,15 +305,31 @@ impl SnpVlekLoad {
     pub fn new(hashstick: &WrappedVlekHashstick) -> Self {
         hashstick.into()
     }
+
+    /// Creates a new VLEK load instruction for a hashstick in the given
+    /// wrapped format version.
+    pub fn with_version(
+        hashstick: &WrappedVlekHashstick,
+        version: u8,
+    ) -> Result<Self, HashstickError> {
+        if version != VLEK_WRAPPED_VERSION {
+            return Err(HashstickError::UnsupportedVersion(version));
+        }
+
+        Ok(Self {
+            vlek_wrapped_version: version,
+            ..hashstick.into()
+        })
+    }
 }
 
 impl<'a> std::convert::From<&WrappedVlekHashstick<'a>> for SnpVlekLoad {
     fn from(value: &WrappedVlekHashstick<'a>) -> Self {
         Self {
-            len: value.data.len() as u32,
-            vlek_wrapped_version: 0u8,
+            len: std::mem::size_of::<Self>() as u32,
+            vlek_wrapped_version: VLEK_WRAPPED_VERSION,
             _reserved: Default::default(),
-            vlek_wrapped_address: value as *const WrappedVlekHashstick as u64,
+            vlek_wrapped_address: value.data.as_ptr() as u64,
         }
     }
 }
@@ -380,7 +400,7 @@ mod test {
 
         use super::super::{WrappedVlekHashstick, HASHSTICK_BUFFER_LEN};
 
-        const VALID_HASHSTICK_BYTES: [u8; HASHSTICK_BUFFER_LEN] = [1u8; HASHSTICK_BUFFER_LEN];
+        static VALID_HASHSTICK_BYTES: [u8; HASHSTICK_BUFFER_LEN] = [1u8; HASHSTICK_BUFFER_LEN];
         const INVALID_HASHSTICK_BYTES: [u8; 25] = [2u8; 25];
 
         #[test]
@@ -417,10 +437,10 @@ mod test {
             let actual: SnpVlekLoad = (&test_hashstick).into();
 
             let expected: SnpVlekLoad = SnpVlekLoad {
-                len: HASHSTICK_BUFFER_LEN as u32,
+                len: 16,
                 vlek_wrapped_version: 0u8,
                 _reserved: Default::default(),
-                vlek_wrapped_address: &test_hashstick as *const WrappedVlekHashstick as u64,
+                vlek_wrapped_address: VALID_HASHSTICK_BYTES.as_ptr() as u64,
             };
 
             assert_eq!(actual, expected);
@@ -434,14 +454,29 @@ mod test {
             let actual: SnpVlekLoad = SnpVlekLoad::new(&test_hashstick);
 
             let expected: SnpVlekLoad = SnpVlekLoad {
-                len: HASHSTICK_BUFFER_LEN as u32,
+                len: 16,
                 vlek_wrapped_version: 0u8,
                 _reserved: Default::default(),
-                vlek_wrapped_address: &test_hashstick as *const WrappedVlekHashstick as u64,
+                vlek_wrapped_address: VALID_HASHSTICK_BYTES.as_ptr() as u64,
             };
 
             assert_eq!(actual, expected);
         }
+
+        #[test]
+        fn test_snp_vlek_load_version() {
+            let test_hashstick: WrappedVlekHashstick =
+                WrappedVlekHashstick::try_from(VALID_HASHSTICK_BYTES.as_slice()).unwrap();
+
+            assert_eq!(
+                SnpVlekLoad::with_version(&test_hashstick, 0).unwrap(),
+                SnpVlekLoad::new(&test_hashstick)
+            );
+            assert_eq!(
+                SnpVlekLoad::with_version(&test_hashstick, 1).unwrap_err(),
+                HashstickError::UnsupportedVersion(1)
+            );
+        }
     }
 
     #[cfg(target_os = "linux")]
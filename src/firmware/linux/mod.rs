@@ -5,4 +5,7 @@ pub mod host;
 #[cfg(feature = "snp")]
 pub mod guest;
 
+#[cfg(target_os = "linux")]
+pub(crate) mod trace;
+
 pub(crate) const _4K_PAGE: usize = 4096;
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional instrumentation of the firmware ioctls.
+//!
+//! With the `tracing` feature enabled, every ioctl issued to `/dev/sev` or
+//! `/dev/sev-guest`, and every KVM ioctl issuing a launch command or
+//! registering encrypted guest memory, runs inside a `sev_ioctl` span
+//! carrying the command name and request size, and emits an event once the
+//! call returns with the elapsed time and any firmware error code. With the
+//! `metrics` feature enabled, the calls to `/dev/sev` and `/dev/sev-guest`
+//! are also reported to the
+//! [MetricsRecorder](crate::firmware::metrics::MetricsRecorder) of the
+//! firmware handle issuing them, if any. Without either feature the helpers
+//! in this module compile down to nothing.
+
+#[cfg(feature = "tracing")]
+use crate::error::Indeterminate;
+
+#[cfg(feature = "metrics")]
+use crate::firmware::metrics::{Device, FirmwareCall, MetricsRecorder, Outcome};
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use std::time::Instant;
+
+/// The recorder a firmware handle reports the metrics of its ioctls to.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Metrics {
+    #[cfg(feature = "metrics")]
+    recorder: Option<&'static dyn MetricsRecorder>,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    /// Report the ioctls to `recorder`.
+    pub fn new(recorder: &'static dyn MetricsRecorder) -> Self {
+        Self {
+            recorder: Some(recorder),
+        }
+    }
+}
+
+/// Bookkeeping for a single in-flight ioctl.
+pub(crate) struct IoctlCall {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    start: Instant,
+
+    #[cfg(feature = "metrics")]
+    recorder: Option<&'static dyn MetricsRecorder>,
+
+    #[cfg(feature = "metrics")]
+    device: Device,
+
+    #[cfg(feature = "metrics")]
+    command: &'static str,
+
+    #[cfg(feature = "metrics")]
+    request_size: usize,
+}
+
+impl IoctlCall {
+    /// Record the start of an ioctl to `/dev/sev` for the request type `T`,
+    /// to be reported to `metrics`.
+    #[inline]
+    pub fn host<T>(metrics: Metrics) -> Self {
+        Self::start::<T>(false, metrics)
+    }
+
+    /// Record the start of an ioctl to `/dev/sev-guest` for the request
+    /// type `T`, to be reported to `metrics`.
+    #[cfg(feature = "snp")]
+    #[inline]
+    pub fn guest<T>(metrics: Metrics) -> Self {
+        Self::start::<T>(true, metrics)
+    }
+
+    /// Record the start of a KVM ioctl launching a guest, for the launch
+    /// command or request type `T`. Launch commands are traced, but not
+    /// reported as metrics.
+    #[inline]
+    pub fn launch<T>() -> Self {
+        Self::start::<T>(false, Metrics::default())
+    }
+
+    #[inline]
+    #[cfg_attr(
+        not(any(feature = "tracing", feature = "metrics")),
+        allow(clippy::extra_unused_type_parameters)
+    )]
+    fn start<T>(guest: bool, metrics: Metrics) -> Self {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "sev_ioctl",
+            command = command_name::<T>(),
+            request_size = std::mem::size_of::<T>(),
+        );
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(parent: &span, "issuing ioctl");
+
+        #[cfg(not(feature = "metrics"))]
+        let _ = (guest, metrics);
+
+        Self {
+            #[cfg(feature = "tracing")]
+            span,
+
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            start: Instant::now(),
+
+            #[cfg(feature = "metrics")]
+            recorder: metrics.recorder,
+
+            #[cfg(feature = "metrics")]
+            device: if guest { Device::Guest } else { Device::Host },
+
+            #[cfg(feature = "metrics")]
+            command: command_name::<T>(),
+
+            #[cfg(feature = "metrics")]
+            request_size: std::mem::size_of::<T>(),
+        }
+    }
+
+    /// Record the outcome of the ioctl. `fw_error` is the raw error value
+    /// the kernel wrote back into the request.
+    #[inline]
+    pub fn finish<R>(self, result: &std::io::Result<R>, fw_error: u64) {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let elapsed = self.start.elapsed();
+
+        #[cfg(feature = "tracing")]
+        {
+            let _entered = self.span.enter();
+            let elapsed_us = elapsed.as_micros() as u64;
+
+            match result {
+                Ok(_) if fw_error == 0 => tracing::debug!(elapsed_us, "ioctl succeeded"),
+                Ok(_) => tracing::warn!(
+                    elapsed_us,
+                    fw_error,
+                    "ioctl succeeded with a firmware error"
+                ),
+                Err(os_error) => tracing::warn!(
+                    elapsed_us,
+                    fw_error,
+                    firmware = %describe(fw_error),
+                    %os_error,
+                    "ioctl failed"
+                ),
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(recorder) = self.recorder {
+            recorder.record(&FirmwareCall {
+                device: self.device,
+                command: self.command,
+                request_size: self.request_size,
+                elapsed,
+                outcome: Outcome::new(result, fw_error),
+            });
+        }
+
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        let _ = (self, result, fw_error);
+    }
+}
+
+/// The unqualified name of the request type, e.g. `SnpPlatformStatus`.
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+fn command_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// A human readable description of the lower 32 bits of a firmware error.
+#[cfg(feature = "tracing")]
+fn describe(fw_error: u64) -> String {
+    match fw_error as u32 {
+        0 => "none".to_string(),
+        code => match Indeterminate::from(code) {
+            Indeterminate::Known(error) => error.to_string(),
+            Indeterminate::Unknown => format!("unknown firmware error {code:#x}"),
+        },
+    }
+}
+
+#[cfg(all(test, any(feature = "tracing", feature = "metrics")))]
+mod test {
+    use super::command_name;
+
+    #[test]
+    fn test_command_name() {
+        assert_eq!(command_name::<u32>(), "u32");
+        assert_eq!(command_name::<std::fs::File>(), "File");
+        assert_eq!(command_name::<std::borrow::Cow<'_, str>>(), "Cow");
+    }
+}
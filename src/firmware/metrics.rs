@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional metrics of the firmware commands.
+//!
+//! With the `metrics` feature enabled, every ioctl issued to the host
+//! (`/dev/sev`) or guest (`/dev/sev-guest`) firmware through a handle
+//! built with `recorded` is reported to that handle's [MetricsRecorder] once
+//! it returns, along with its duration and outcome, so that fleet operators
+//! can alert when attestation degrades.
+//! [FirmwareStats] aggregates the calls in memory, and [OtelRecorder]
+//! (with the `otel` feature) exports them as OpenTelemetry instruments.
+//!
+//! # Example:
+//! ```ignore
+//! static STATS: FirmwareStats = FirmwareStats::new();
+//!
+//! let mut fw = Firmware::open()?.recorded(&STATS);
+//! let report = fw.get_report(None, Some(data), None)?;
+//!
+//! let stats = &STATS.snapshot()[&(Device::Guest, "ReportReq")];
+//! assert_eq!(stats.failures, 0);
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt::{self, Display},
+    io,
+    sync::Mutex,
+    time::Duration,
+};
+
+/// The firmware device a command was issued to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Device {
+    /// The host firmware (`/dev/sev`).
+    Host,
+
+    /// The guest firmware (`/dev/sev-guest`).
+    Guest,
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Host => write!(f, "host"),
+            Self::Guest => write!(f, "guest"),
+        }
+    }
+}
+
+/// How a firmware command ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The command succeeded.
+    Success,
+
+    /// The firmware rejected the command with this status code.
+    FirmwareError(u32),
+
+    /// The VMM rejected a guest request with this error code.
+    VmmError(u32),
+
+    /// The ioctl failed with this OS error number before reaching the
+    /// firmware.
+    OsError(i32),
+}
+
+impl Outcome {
+    /// Classify the result of an ioctl. `fw_error` is the raw error value
+    /// the kernel wrote back into the request: the firmware status in its
+    /// lower 32 bits, and for guest requests the VMM error in its upper 32.
+    pub(crate) fn new<R>(result: &io::Result<R>, fw_error: u64) -> Self {
+        match (fw_error as u32, (fw_error >> 32) as u32, result) {
+            (0, 0, Ok(_)) => Self::Success,
+            (0, 0, Err(e)) => Self::OsError(e.raw_os_error().unwrap_or(0)),
+            (0, vmm, _) => Self::VmmError(vmm),
+            (code, _, _) => Self::FirmwareError(code),
+        }
+    }
+
+    /// Whether the command failed.
+    pub fn is_failure(&self) -> bool {
+        *self != Self::Success
+    }
+}
+
+/// A firmware command which returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FirmwareCall {
+    /// The device the command was issued to.
+    pub device: Device,
+
+    /// The name of the request type, e.g. `SnpPlatformStatus`.
+    pub command: &'static str,
+
+    /// The size of the request in bytes.
+    pub request_size: usize,
+
+    /// The time the ioctl took.
+    pub elapsed: Duration,
+
+    /// How the command ended.
+    pub outcome: Outcome,
+}
+
+/// A sink for the metrics of firmware commands
+pub trait MetricsRecorder: Send + Sync {
+    /// Record a command which returned.
+    fn record(&self, call: &FirmwareCall);
+}
+
+/// The aggregated metrics of a command
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    /// How many times the command was issued.
+    pub calls: u64,
+
+    /// How many of the calls failed.
+    pub failures: u64,
+
+    /// The time all the calls took.
+    pub total_time: Duration,
+
+    /// The time the slowest call took.
+    pub max_time: Duration,
+
+    /// How many calls the firmware rejected, by status code.
+    pub firmware_errors: BTreeMap<u32, u64>,
+}
+
+impl CommandStats {
+    /// The fraction of the calls which failed.
+    pub fn failure_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.calls as f64
+        }
+    }
+
+    /// The average time a call took.
+    pub fn mean_time(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(0) => Duration::ZERO,
+            Ok(calls) => self.total_time / calls,
+            Err(_) => self.total_time.div_f64(self.calls as f64),
+        }
+    }
+}
+
+/// A [MetricsRecorder] aggregating the calls of each command in memory
+#[derive(Debug, Default)]
+pub struct FirmwareStats(Mutex<BTreeMap<(Device, &'static str), CommandStats>>);
+
+impl FirmwareStats {
+    /// Create empty statistics.
+    pub const fn new() -> Self {
+        Self(Mutex::new(BTreeMap::new()))
+    }
+
+    /// The statistics of each command issued so far.
+    pub fn snapshot(&self) -> BTreeMap<(Device, &'static str), CommandStats> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Forget the commands issued so far.
+    pub fn clear(&self) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+impl MetricsRecorder for FirmwareStats {
+    fn record(&self, call: &FirmwareCall) {
+        let mut stats = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = stats.entry((call.device, call.command)).or_default();
+
+        stats.calls += 1;
+        stats.total_time += call.elapsed;
+        stats.max_time = stats.max_time.max(call.elapsed);
+
+        if call.outcome.is_failure() {
+            stats.failures += 1;
+        }
+
+        if let Outcome::FirmwareError(code) = call.outcome {
+            *stats.firmware_errors.entry(code).or_default() += 1;
+        }
+    }
+}
+
+/// A [MetricsRecorder] exporting the calls as OpenTelemetry instruments
+///
+/// Calls are counted by `sev.firmware.calls` and failures by
+/// `sev.firmware.failures`, and their durations are recorded in seconds by
+/// the `sev.firmware.duration` histogram. Each measurement carries the
+/// `device` and `command` attributes, and failures an `error` attribute
+/// naming the outcome.
+#[cfg(feature = "otel")]
+pub struct OtelRecorder {
+    calls: opentelemetry::metrics::Counter<u64>,
+    failures: opentelemetry::metrics::Counter<u64>,
+    duration: opentelemetry::metrics::Histogram<f64>,
+}
+
+#[cfg(feature = "otel")]
+impl OtelRecorder {
+    /// Create the instruments with `meter`.
+    pub fn new(meter: &opentelemetry::metrics::Meter) -> Self {
+        Self {
+            calls: meter
+                .u64_counter("sev.firmware.calls")
+                .with_description("Firmware commands issued")
+                .build(),
+            failures: meter
+                .u64_counter("sev.firmware.failures")
+                .with_description("Firmware commands which failed")
+                .build(),
+            duration: meter
+                .f64_histogram("sev.firmware.duration")
+                .with_description("Duration of the firmware commands")
+                .with_unit("s")
+                .build(),
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+impl MetricsRecorder for OtelRecorder {
+    fn record(&self, call: &FirmwareCall) {
+        use opentelemetry::KeyValue;
+
+        let attributes = [
+            KeyValue::new("device", call.device.to_string()),
+            KeyValue::new("command", call.command),
+        ];
+
+        self.calls.add(1, &attributes);
+        self.duration
+            .record(call.elapsed.as_secs_f64(), &attributes);
+
+        let error = match call.outcome {
+            Outcome::Success => return,
+            Outcome::FirmwareError(code) => format!("firmware:{code:#x}"),
+            Outcome::VmmError(code) => format!("vmm:{code:#x}"),
+            Outcome::OsError(errno) => format!("os:{errno}"),
+        };
+
+        let [device, command] = attributes;
+        self.failures
+            .add(1, &[device, command, KeyValue::new("error", error)]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call(command: &'static str, elapsed_ms: u64, outcome: Outcome) -> FirmwareCall {
+        FirmwareCall {
+            device: Device::Host,
+            command,
+            request_size: 8,
+            elapsed: Duration::from_millis(elapsed_ms),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(Outcome::new(&Ok(0), 0), Outcome::Success);
+        assert_eq!(Outcome::new(&Ok(0), 0x16), Outcome::FirmwareError(0x16));
+        assert_eq!(
+            Outcome::new::<()>(&Err(io::Error::from_raw_os_error(5)), 0x1_0000_0000),
+            Outcome::VmmError(1)
+        );
+        assert_eq!(
+            Outcome::new::<()>(&Err(io::Error::from_raw_os_error(5)), 0),
+            Outcome::OsError(5)
+        );
+    }
+
+    #[test]
+    fn test_firmware_stats() {
+        let stats = FirmwareStats::new();
+        stats.record(&call("PlatformStatus", 2, Outcome::Success));
+        stats.record(&call("PlatformStatus", 4, Outcome::FirmwareError(0x16)));
+        stats.record(&call("PlatformStatus", 6, Outcome::OsError(5)));
+        stats.record(&call("SnpPlatformStatus", 1, Outcome::Success));
+
+        let snapshot = stats.snapshot();
+        let status = &snapshot[&(Device::Host, "PlatformStatus")];
+
+        assert_eq!(status.calls, 3);
+        assert_eq!(status.failures, 2);
+        assert_eq!(status.max_time, Duration::from_millis(6));
+        assert_eq!(status.mean_time(), Duration::from_millis(4));
+        assert_eq!(status.firmware_errors, BTreeMap::from([(0x16, 1)]));
+        assert!((status.failure_rate() - 2.0 / 3.0).abs() < f64::EPSILON);
+        assert_eq!(snapshot[&(Device::Host, "SnpPlatformStatus")].failures, 0);
+
+        stats.clear();
+        assert!(stats.snapshot().is_empty());
+    }
+}
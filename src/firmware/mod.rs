@@ -6,8 +6,20 @@
 #[cfg(any(feature = "sev", feature = "snp"))]
 pub mod host;
 
-#[cfg(feature = "snp")]
+/// The SEV-SNP platform types which are available without `std`.
+#[cfg(all(feature = "snp-types", not(any(feature = "sev", feature = "snp"))))]
+pub mod host {
+    #[path = "types/tcb_version.rs"]
+    mod tcb_version;
+
+    pub use tcb_version::TcbVersion;
+}
+
+#[cfg(feature = "snp-types")]
 pub mod guest;
 
-#[cfg(any(feature = "sev", feature = "snp"))]
+#[cfg(all(any(feature = "sev", feature = "snp"), feature = "std"))]
 pub(crate) mod linux;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Summarizing the attestation reports of a fleet of guests.
+//!
+//! Audits across many confidential VMs ask the same questions of every
+//! report: which images run, on which TCBs, signed by which keys, and which
+//! guests were launched with unusual policies. A [Fleet] ingests reports one
+//! by one and produces a [FleetSummary] answering them, which serializes to
+//! JSON or any other serde format for the audit trail.
+//!
+//! The fleet only inspects the contents of the reports. Verify each report's
+//! signature and certificate chain before ingesting it.
+//!
+//! # Example:
+//! ```ignore
+//! let fleet: Fleet = reports.iter().collect();
+//! let summary = fleet.summary();
+//!
+//! serde_json::to_writer_pretty(File::create("fleet.json")?, &summary)?;
+//! ```
+
+use crate::{
+    encoding::Measurement48,
+    firmware::{
+        guest::{AttestationReport, GuestPolicy, SigningKey},
+        host::TcbVersion,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use std::{cmp::Reverse, collections::BTreeMap, iter::FromIterator};
+
+/// How many reports share a value
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Tally<T> {
+    /// The value.
+    pub value: T,
+
+    /// The number of reports with the value.
+    pub count: usize,
+}
+
+/// Why a report stands out of its fleet
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// The guest may be debugged by the host.
+    DebugAllowed,
+
+    /// The guest may be associated with a migration agent.
+    MigrateMaAllowed,
+
+    /// The guest was launched with another policy than most of the fleet.
+    UncommonPolicy,
+
+    /// The report names a reserved signing key.
+    ReservedSigningKey(u32),
+}
+
+/// A report standing out of its fleet
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Anomaly {
+    /// The position of the report among those ingested.
+    pub report: usize,
+
+    /// Why the report stands out.
+    pub kind: AnomalyKind,
+}
+
+/// The aggregate of the reports of a fleet
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FleetSummary {
+    /// The number of reports ingested.
+    pub reports: usize,
+
+    /// The distinct launch measurements, most common first.
+    pub measurements: Vec<Tally<Measurement48>>,
+
+    /// The distinct reported TCB versions, most common first.
+    pub reported_tcbs: Vec<Tally<TcbVersion>>,
+
+    /// The distinct guest policies, most common first.
+    pub policies: Vec<Tally<GuestPolicy>>,
+
+    /// The keys signing the reports, most common first.
+    pub signers: Vec<Tally<SigningKey>>,
+
+    /// The reports standing out, in the order they were ingested.
+    pub anomalies: Vec<Anomaly>,
+}
+
+/// Ingests the reports of a fleet of guests
+#[derive(Clone, Debug, Default)]
+pub struct Fleet {
+    reports: usize,
+    measurements: BTreeMap<Measurement48, usize>,
+    reported_tcbs: BTreeMap<u64, usize>,
+    signers: BTreeMap<SigningKey, usize>,
+
+    /// The reports launched with each policy.
+    policies: BTreeMap<u64, Vec<usize>>,
+
+    /// The anomalies found in single reports.
+    anomalies: Vec<Anomaly>,
+}
+
+impl Fleet {
+    /// A fleet without reports.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of reports ingested.
+    pub fn len(&self) -> usize {
+        self.reports
+    }
+
+    /// Whether no report was ingested.
+    pub fn is_empty(&self) -> bool {
+        self.reports == 0
+    }
+
+    /// Ingest `report`.
+    pub fn add(&mut self, report: &AttestationReport) {
+        let index = self.reports;
+        self.reports += 1;
+
+        *self.measurements.entry(report.measurement).or_default() += 1;
+        *self
+            .reported_tcbs
+            .entry(report.reported_tcb.into())
+            .or_default() += 1;
+
+        match report.key_info.signing_key() {
+            Ok(signer) => *self.signers.entry(signer).or_default() += 1,
+            Err(raw) => self.anomaly(index, AnomalyKind::ReservedSigningKey(raw)),
+        }
+
+        self.policies
+            .entry(report.policy.into())
+            .or_default()
+            .push(index);
+
+        if report.policy.debug_allowed() != 0 {
+            self.anomaly(index, AnomalyKind::DebugAllowed);
+        }
+        if report.policy.migrate_ma_allowed() != 0 {
+            self.anomaly(index, AnomalyKind::MigrateMaAllowed);
+        }
+    }
+
+    /// Summarize the reports ingested so far.
+    ///
+    /// Reports are launched with an uncommon policy if another policy is
+    /// strictly more common in the fleet, so that a fleet split evenly
+    /// between policies has no such anomaly.
+    pub fn summary(&self) -> FleetSummary {
+        let mut anomalies = self.anomalies.clone();
+
+        let most_common = self.policies.values().map(Vec::len).max().unwrap_or(0);
+        let ties = self
+            .policies
+            .values()
+            .filter(|reports| reports.len() == most_common)
+            .count();
+
+        if ties == 1 {
+            for reports in self.policies.values() {
+                if reports.len() < most_common {
+                    anomalies.extend(reports.iter().map(|report| Anomaly {
+                        report: *report,
+                        kind: AnomalyKind::UncommonPolicy,
+                    }));
+                }
+            }
+        }
+        anomalies.sort_by_key(|anomaly| (anomaly.report, anomaly.kind));
+
+        FleetSummary {
+            reports: self.reports,
+            measurements: tallies(self.measurements.iter().map(|(m, n)| (*m, *n))),
+            reported_tcbs: tallies(
+                self.reported_tcbs
+                    .iter()
+                    .map(|(tcb, n)| (TcbVersion::from(*tcb), *n)),
+            ),
+            policies: tallies(
+                self.policies
+                    .iter()
+                    .map(|(policy, reports)| (GuestPolicy::from(*policy), reports.len())),
+            ),
+            signers: tallies(self.signers.iter().map(|(s, n)| (*s, *n))),
+            anomalies,
+        }
+    }
+
+    fn anomaly(&mut self, report: usize, kind: AnomalyKind) {
+        self.anomalies.push(Anomaly { report, kind });
+    }
+}
+
+impl<'a> Extend<&'a AttestationReport> for Fleet {
+    fn extend<I: IntoIterator<Item = &'a AttestationReport>>(&mut self, reports: I) {
+        for report in reports {
+            self.add(report);
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a AttestationReport> for Fleet {
+    fn from_iter<I: IntoIterator<Item = &'a AttestationReport>>(reports: I) -> Self {
+        let mut fleet = Self::new();
+        fleet.extend(reports);
+        fleet
+    }
+}
+
+/// The tallies of `counts`, in key order, sorted most common first.
+fn tallies<T>(counts: impl Iterator<Item = (T, usize)>) -> Vec<Tally<T>> {
+    let mut tallies: Vec<Tally<T>> = counts
+        .map(|(value, count)| Tally { value, count })
+        .collect();
+
+    // The sort is stable, so that ties stay in key order.
+    tallies.sort_by_key(|tally| Reverse(tally.count));
+    tallies
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::firmware::guest::KeyInfo;
+
+    fn fleet() -> Vec<AttestationReport> {
+        let mut reports = vec![AttestationReport::milan(); 5];
+
+        reports[1].measurement.0[0] ^= 0xff;
+        reports[2].reported_tcb = TcbVersion::new(4, 0, 9, 209);
+        reports[3].key_info = KeyInfo::from(1 << 2);
+        reports[4].key_info = KeyInfo::from(3 << 2);
+
+        let mut policy = reports[2].policy;
+        policy.set_debug_allowed(1);
+        reports[2].policy = policy;
+
+        reports
+    }
+
+    #[test]
+    fn empty() {
+        let fleet = Fleet::new();
+
+        assert!(fleet.is_empty());
+        assert_eq!(fleet.summary(), FleetSummary::default());
+    }
+
+    #[test]
+    fn summary() {
+        let reports = fleet();
+        let fleet: Fleet = reports.iter().collect();
+        assert_eq!(fleet.len(), 5);
+
+        let summary = fleet.summary();
+        assert_eq!(summary.reports, 5);
+
+        assert_eq!(summary.measurements.len(), 2);
+        assert_eq!(summary.measurements[0].value, reports[0].measurement);
+        assert_eq!(summary.measurements[0].count, 4);
+        assert_eq!(summary.measurements[1].value, reports[1].measurement);
+
+        assert_eq!(
+            summary
+                .reported_tcbs
+                .iter()
+                .map(|tally| (tally.value, tally.count))
+                .collect::<Vec<_>>(),
+            [(reports[0].reported_tcb, 4), (reports[2].reported_tcb, 1)]
+        );
+
+        assert_eq!(
+            summary.policies,
+            [
+                Tally {
+                    value: reports[0].policy,
+                    count: 4
+                },
+                Tally {
+                    value: reports[2].policy,
+                    count: 1
+                },
+            ]
+        );
+
+        assert_eq!(
+            summary.signers,
+            [
+                Tally {
+                    value: SigningKey::Vcek,
+                    count: 3
+                },
+                Tally {
+                    value: SigningKey::Vlek,
+                    count: 1
+                },
+            ]
+        );
+
+        assert_eq!(
+            summary.anomalies,
+            [
+                Anomaly {
+                    report: 2,
+                    kind: AnomalyKind::DebugAllowed
+                },
+                Anomaly {
+                    report: 2,
+                    kind: AnomalyKind::UncommonPolicy
+                },
+                Anomaly {
+                    report: 4,
+                    kind: AnomalyKind::ReservedSigningKey(3)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn even_policies() {
+        let mut reports = vec![AttestationReport::milan(); 2];
+        reports[1].policy = GuestPolicy::from(u64::from(reports[0].policy) ^ (1 << 16));
+
+        let summary = reports.iter().collect::<Fleet>().summary();
+        assert_eq!(summary.policies.len(), 2);
+        assert!(summary.anomalies.is_empty());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let summary = fleet().iter().collect::<Fleet>().summary();
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["reports"], 5);
+        assert_eq!(json["anomalies"][0]["kind"], "debug_allowed");
+        assert_eq!(
+            json["anomalies"][2]["kind"],
+            serde_json::json!({ "reserved_signing_key": 3 })
+        );
+
+        let decoded: FleetSummary = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, summary);
+    }
+}
@@ -0,0 +1,666 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! IGVM (Independent Guest Virtual Machine) files for SEV-SNP guests.
+//!
+//! An IGVM file describes the initial state of a guest as a list of
+//! directives: pages of data to place in guest memory, parameter areas the
+//! VMM fills in, and the register state (VMSA) of the vCPUs. The same
+//! [IgvmFile] predicts the guest's launch digest with
+//! [measure](IgvmFile::measure) and drives its launch with
+//! [launch](IgvmFile::launch), so that what is measured and what is
+//! launched match by construction.
+//!
+//! Only the directives which apply to the SEV-SNP platform of the file are
+//! kept. The file checksum is not verified.
+//!
+//! # Example:
+//! ```ignore
+//! let igvm = IgvmFile::parse(&std::fs::read("guest.igvm")?)?;
+//!
+//! let expected = igvm.measure()?;
+//!
+//! let mut launcher = Launcher::new(vm_fd, sev)?.start(start)?;
+//! for context in igvm.launch(&mut launcher, &mut guest_memory)? {
+//!     // Load the VMSA into the vCPU `context.vp_index`.
+//! }
+//! ```
+
+use crate::error::IgvmError;
+
+#[cfg(target_os = "linux")]
+use crate::launch::{
+    snp::{Launcher, PageType, Started, Update, UpdateBatch, VmplPerms},
+    vmm::{SevDevice, VmHandle},
+};
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "openssl", feature = "crypto_nossl")
+))]
+use crate::{
+    error::GCTXError,
+    measurement::gctx::{LaunchDigest, PageUpdate, LD_SIZE},
+};
+
+use std::{collections::HashMap, convert::TryInto};
+
+/// The magic value which starts an IGVM file ("IGVM").
+const MAGIC: u32 = u32::from_le_bytes(*b"IGVM");
+
+/// Size of the fixed header of format version 1.
+const FIXED_HEADER_SIZE: usize = 24;
+
+/// Size of a variable header's type and length.
+const VARIABLE_HEADER_SIZE: usize = 8;
+
+const IGVM_VHT_SUPPORTED_PLATFORM: u32 = 0x1;
+const IGVM_VHT_PARAMETER_AREA: u32 = 0x301;
+const IGVM_VHT_PAGE_DATA: u32 = 0x302;
+const IGVM_VHT_PARAMETER_INSERT: u32 = 0x303;
+const IGVM_VHT_VP_CONTEXT: u32 = 0x304;
+
+/// The platform type of SEV-SNP.
+const PLATFORM_TYPE_SEV_SNP: u8 = 0x2;
+
+const PAGE_SIZE: usize = 4096;
+const PAGE_SIZE_2MB: usize = 0x20_0000;
+
+const PAGE_DATA_FLAG_2MB: u32 = 1 << 0;
+const PAGE_DATA_FLAG_UNMEASURED: u32 = 1 << 1;
+
+/// The contents of a page of data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IgvmPageDataType {
+    /// Guest data.
+    Normal,
+
+    /// The SNP secrets page.
+    Secrets,
+
+    /// The SNP CPUID page.
+    CpuidData,
+
+    /// The SNP CPUID page, with XSAVE features.
+    CpuidXf,
+}
+
+impl IgvmPageDataType {
+    fn from_u16(value: u16) -> Option<Self> {
+        match value {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Secrets),
+            2 => Some(Self::CpuidData),
+            3 => Some(Self::CpuidXf),
+            _ => None,
+        }
+    }
+}
+
+/// A directive of an IGVM file, in the order of the file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IgvmDirective {
+    /// A page of data.
+    PageData {
+        /// The guest physical address of the page.
+        gpa: u64,
+
+        /// What the page holds.
+        data_type: IgvmPageDataType,
+
+        /// Whether the page is encrypted without being measured.
+        unmeasured: bool,
+
+        /// The contents of the page, or none for a page of zeroes.
+        data: Vec<u8>,
+
+        /// The size of the page in bytes.
+        size: usize,
+    },
+
+    /// A parameter area filled in by the VMM, which is not measured.
+    ParameterInsert {
+        /// The guest physical address of the area.
+        gpa: u64,
+
+        /// The size of the area in bytes.
+        size: u64,
+    },
+
+    /// The initial register state of a vCPU.
+    VpContext {
+        /// The guest physical address of the VMSA.
+        gpa: u64,
+
+        /// The index of the vCPU.
+        vp_index: u16,
+
+        /// The VMSA page.
+        vmsa: Vec<u8>,
+    },
+}
+
+/// The directives of an IGVM file for the SEV-SNP platform.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IgvmFile {
+    directives: Vec<IgvmDirective>,
+}
+
+/// Reads little-endian fields of an IGVM file, checking its bounds.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], IgvmError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.0.get(offset..end))
+            .ok_or(IgvmError::Truncated { offset, len })
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, IgvmError> {
+        Ok(u16::from_le_bytes(
+            self.bytes(offset, 2)?.try_into().unwrap(),
+        ))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, IgvmError> {
+        Ok(u32::from_le_bytes(
+            self.bytes(offset, 4)?.try_into().unwrap(),
+        ))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64, IgvmError> {
+        Ok(u64::from_le_bytes(
+            self.bytes(offset, 8)?.try_into().unwrap(),
+        ))
+    }
+
+    /// Up to `len` bytes at `offset`, padded with zeroes to `len` bytes.
+    fn padded(&self, offset: usize, len: usize) -> Result<Vec<u8>, IgvmError> {
+        let available: usize = self.0.len().saturating_sub(offset).min(len);
+        let mut data: Vec<u8> = self.bytes(offset, available)?.to_vec();
+        data.resize(len, 0);
+        Ok(data)
+    }
+}
+
+/// Check that a header of type `typ` holds at least `min` bytes.
+fn expect_len(typ: u32, body: &[u8], min: usize) -> Result<(), IgvmError> {
+    if body.len() < min {
+        return Err(IgvmError::HeaderLength {
+            typ,
+            len: body.len() as u32,
+        });
+    }
+
+    Ok(())
+}
+
+impl IgvmFile {
+    /// Parse an IGVM file, keeping the directives for its SEV-SNP platform.
+    pub fn parse(bytes: &[u8]) -> Result<Self, IgvmError> {
+        let file: Reader = Reader(bytes);
+
+        let magic: u32 = file.u32(0)?;
+        if magic != MAGIC {
+            return Err(IgvmError::Magic(magic));
+        }
+
+        let version: u32 = file.u32(4)?;
+        if version != 1 && version != 2 {
+            return Err(IgvmError::Version(version));
+        }
+        file.bytes(0, FIXED_HEADER_SIZE)?;
+
+        let offset: usize = file.u32(8)? as usize;
+        let size: usize = file.u32(12)? as usize;
+        let variable: Reader = Reader(file.bytes(offset, size)?);
+
+        let mut headers: Vec<(u32, &[u8])> = vec![];
+        let mut at: usize = 0;
+        while at < size {
+            let typ: u32 = variable.u32(at)?;
+            let len: usize = variable.u32(at + 4)? as usize;
+            headers.push((typ, variable.bytes(at + VARIABLE_HEADER_SIZE, len)?));
+
+            at += VARIABLE_HEADER_SIZE + ((len + 7) & !7);
+        }
+
+        let mut mask: u32 = 0;
+        for (typ, body) in headers
+            .iter()
+            .filter(|h| h.0 == IGVM_VHT_SUPPORTED_PLATFORM)
+        {
+            expect_len(*typ, body, 8)?;
+            if body[5] == PLATFORM_TYPE_SEV_SNP {
+                mask |= Reader(body).u32(0)?;
+            }
+        }
+        if mask == 0 {
+            return Err(IgvmError::NoSnpPlatform);
+        }
+
+        let mut areas: HashMap<u32, u64> = HashMap::new();
+        let mut directives: Vec<IgvmDirective> = vec![];
+
+        for (typ, body) in headers {
+            let header: Reader = Reader(body);
+
+            match typ {
+                IGVM_VHT_PARAMETER_AREA => {
+                    expect_len(typ, body, 16)?;
+                    areas.insert(header.u32(8)?, header.u64(0)?);
+                }
+                IGVM_VHT_PAGE_DATA => {
+                    expect_len(typ, body, 24)?;
+                    let data_type = IgvmPageDataType::from_u16(header.u16(20)?);
+
+                    match data_type {
+                        Some(data_type) if header.u32(8)? & mask != 0 => {
+                            let flags: u32 = header.u32(16)?;
+                            let size: usize = if flags & PAGE_DATA_FLAG_2MB != 0 {
+                                PAGE_SIZE_2MB
+                            } else {
+                                PAGE_SIZE
+                            };
+                            let data: Vec<u8> = match header.u32(12)? as usize {
+                                0 => vec![],
+                                file_offset => file.padded(file_offset, size)?,
+                            };
+
+                            directives.push(IgvmDirective::PageData {
+                                gpa: header.u64(0)?,
+                                data_type,
+                                unmeasured: flags & PAGE_DATA_FLAG_UNMEASURED != 0,
+                                data,
+                                size,
+                            });
+                        }
+                        _ => continue,
+                    }
+                }
+                IGVM_VHT_PARAMETER_INSERT => {
+                    expect_len(typ, body, 16)?;
+                    if header.u32(8)? & mask == 0 {
+                        continue;
+                    }
+
+                    let index: u32 = header.u32(12)?;
+                    let size: u64 = *areas
+                        .get(&index)
+                        .ok_or(IgvmError::UnknownParameterArea(index))?;
+
+                    directives.push(IgvmDirective::ParameterInsert {
+                        gpa: header.u64(0)?,
+                        size,
+                    });
+                }
+                IGVM_VHT_VP_CONTEXT => {
+                    expect_len(typ, body, 18)?;
+                    if header.u32(8)? & mask == 0 {
+                        continue;
+                    }
+
+                    directives.push(IgvmDirective::VpContext {
+                        gpa: header.u64(0)?,
+                        vp_index: header.u16(16)?,
+                        vmsa: file.padded(header.u32(12)? as usize, PAGE_SIZE)?,
+                    });
+                }
+                _ => continue,
+            }
+        }
+
+        Ok(Self { directives })
+    }
+
+    /// The directives for the SEV-SNP platform, in the order of the file.
+    pub fn directives(&self) -> &[IgvmDirective] {
+        &self.directives
+    }
+
+    /// The VMSAs of the vCPUs, which the VMM loads into its vCPUs before
+    /// finishing the launch.
+    pub fn vp_contexts(&self) -> impl Iterator<Item = &IgvmDirective> {
+        self.directives
+            .iter()
+            .filter(|directive| matches!(directive, IgvmDirective::VpContext { .. }))
+    }
+
+    /// The page updates the launch issues, in order, with the VMSAs last.
+    #[cfg(all(
+        target_os = "linux",
+        any(feature = "openssl", feature = "crypto_nossl")
+    ))]
+    pub fn page_updates(&self) -> Vec<PageUpdate<'_>> {
+        let mut updates: Vec<PageUpdate> = vec![];
+
+        for directive in self.directives.iter() {
+            match directive {
+                IgvmDirective::PageData {
+                    gpa,
+                    data_type,
+                    unmeasured,
+                    data,
+                    size,
+                } => updates.push(match (data_type, unmeasured) {
+                    (_, true) => PageUpdate::Unmeasured {
+                        gpa: *gpa,
+                        len: *size,
+                    },
+                    (IgvmPageDataType::Secrets, _) => PageUpdate::Secrets { gpa: *gpa },
+                    (IgvmPageDataType::CpuidData | IgvmPageDataType::CpuidXf, _) => {
+                        PageUpdate::Cpuid { gpa: *gpa }
+                    }
+                    (IgvmPageDataType::Normal, _) if data.is_empty() => PageUpdate::Zero {
+                        gpa: *gpa,
+                        len: *size,
+                    },
+                    (IgvmPageDataType::Normal, _) => PageUpdate::Normal { gpa: *gpa, data },
+                }),
+                IgvmDirective::ParameterInsert { gpa, size } => {
+                    updates.push(PageUpdate::Unmeasured {
+                        gpa: *gpa,
+                        len: *size as usize,
+                    })
+                }
+                IgvmDirective::VpContext { .. } => continue,
+            }
+        }
+
+        for directive in self.vp_contexts() {
+            if let IgvmDirective::VpContext { vmsa, .. } = directive {
+                updates.push(PageUpdate::Vmsa { page: vmsa });
+            }
+        }
+
+        updates
+    }
+
+    /// The expected launch digest of the guest.
+    #[cfg(all(
+        target_os = "linux",
+        any(feature = "openssl", feature = "crypto_nossl")
+    ))]
+    pub fn measure(&self) -> Result<[u8; LD_SIZE], GCTXError> {
+        let mut digest: LaunchDigest = LaunchDigest::new();
+        digest.replay(&self.page_updates())?;
+
+        Ok(digest.digest())
+    }
+
+    /// Place the pages of the file in `memory` and encrypt them with
+    /// `launcher`, coalescing contiguous pages of the same type. Parameter
+    /// areas must have been filled in beforehand.
+    ///
+    /// KVM creates the VMSAs from the state of the vCPUs when the launch
+    /// finishes, so the VMSAs of the file are returned for the VMM to load
+    /// into its vCPUs instead.
+    #[cfg(target_os = "linux")]
+    pub fn launch<U: VmHandle, V: SevDevice>(
+        &self,
+        launcher: &mut Launcher<Started, U, V>,
+        memory: &mut impl GuestMemory,
+    ) -> std::io::Result<Vec<&IgvmDirective>> {
+        let unmapped = |gpa: u64, len: usize| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                IgvmError::Unmapped { gpa, len },
+            )
+        };
+
+        let mut pages: Vec<(u64, usize, PageType)> = vec![];
+
+        for directive in self.directives.iter() {
+            let (gpa, len, page_type) = match directive {
+                IgvmDirective::PageData {
+                    gpa,
+                    data_type,
+                    unmeasured,
+                    data,
+                    size,
+                } => {
+                    let page = memory
+                        .slice_mut(*gpa, *size)
+                        .ok_or_else(|| unmapped(*gpa, *size))?;
+
+                    match data.is_empty() {
+                        true => page.fill(0),
+                        false => page.copy_from_slice(data),
+                    }
+
+                    let page_type: PageType = match (data_type, unmeasured) {
+                        (_, true) => PageType::Unmeasured,
+                        (IgvmPageDataType::Secrets, _) => PageType::Secrets,
+                        (IgvmPageDataType::CpuidData | IgvmPageDataType::CpuidXf, _) => {
+                            PageType::Cpuid
+                        }
+                        (IgvmPageDataType::Normal, _) if data.is_empty() => PageType::Zero,
+                        (IgvmPageDataType::Normal, _) => PageType::Normal,
+                    };
+
+                    (*gpa, *size, page_type)
+                }
+                IgvmDirective::ParameterInsert { gpa, size } => {
+                    (*gpa, *size as usize, PageType::Unmeasured)
+                }
+                IgvmDirective::VpContext { .. } => continue,
+            };
+
+            pages.push((gpa, len, page_type));
+        }
+
+        let mut batch: UpdateBatch = UpdateBatch::new();
+        let perms = (VmplPerms::empty(), VmplPerms::empty(), VmplPerms::empty());
+
+        for (gpa, len, page_type) in pages {
+            let uaddr: &[u8] = memory.slice(gpa, len).ok_or_else(|| unmapped(gpa, len))?;
+
+            batch
+                .push(Update::new(gpa >> 12, uaddr, false, page_type, perms))
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        }
+
+        launcher.update_batch(&batch)?;
+
+        Ok(self.vp_contexts().collect())
+    }
+}
+
+/// Guest memory, as mapped by the VMM.
+pub trait GuestMemory {
+    /// The host mapping of the `len` bytes of guest memory at `gpa`, if
+    /// mapped.
+    fn slice(&self, gpa: u64, len: usize) -> Option<&[u8]>;
+
+    /// The mutable host mapping of the `len` bytes of guest memory at
+    /// `gpa`, if mapped.
+    fn slice_mut(&mut self, gpa: u64, len: usize) -> Option<&mut [u8]>;
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod test {
+    use super::*;
+
+    use crate::launch::{snp::Start, vmm::Loopback};
+
+    const SNP: u32 = 0x1;
+    const OTHER: u32 = 0x2;
+
+    fn header(typ: u32, body: &[u8]) -> Vec<u8> {
+        let mut header: Vec<u8> = [typ.to_le_bytes(), (body.len() as u32).to_le_bytes()].concat();
+        header.extend_from_slice(body);
+        header.resize((header.len() + 7) & !7, 0);
+        header
+    }
+
+    fn page_data(gpa: u64, mask: u32, file_offset: u32, flags: u32, data_type: u16) -> Vec<u8> {
+        let mut body: Vec<u8> = gpa.to_le_bytes().to_vec();
+        body.extend_from_slice(&mask.to_le_bytes());
+        body.extend_from_slice(&file_offset.to_le_bytes());
+        body.extend_from_slice(&flags.to_le_bytes());
+        body.extend_from_slice(&data_type.to_le_bytes());
+        body.extend_from_slice(&[0, 0]);
+        header(IGVM_VHT_PAGE_DATA, &body)
+    }
+
+    /// An IGVM file with a page of data, a zero page, the secrets and
+    /// CPUID pages, a parameter area and the VMSA of the boot vCPU.
+    fn file() -> Vec<u8> {
+        let data_offset: u32 = 0x1000;
+
+        let mut platform: Vec<u8> = SNP.to_le_bytes().to_vec();
+        platform.extend_from_slice(&[0, PLATFORM_TYPE_SEV_SNP, 1, 0]);
+        platform.extend_from_slice(&0u64.to_le_bytes());
+
+        let mut area: Vec<u8> = 0x1000u64.to_le_bytes().to_vec();
+        area.extend_from_slice(&[0; 8]);
+
+        let mut insert: Vec<u8> = 0x5000u64.to_le_bytes().to_vec();
+        insert.extend_from_slice(&SNP.to_le_bytes());
+        insert.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut context: Vec<u8> = 0x6000u64.to_le_bytes().to_vec();
+        context.extend_from_slice(&SNP.to_le_bytes());
+        context.extend_from_slice(&(data_offset + 0x2000).to_le_bytes());
+        context.extend_from_slice(&[0; 4]);
+
+        let variable: Vec<u8> = [
+            header(IGVM_VHT_SUPPORTED_PLATFORM, &platform),
+            page_data(0x1000, SNP, data_offset, 0, 0),
+            page_data(0x1000, OTHER, data_offset + 0x1000, 0, 0),
+            page_data(0x2000, SNP, 0, 0, 0),
+            page_data(0x3000, SNP, 0, 0, 1),
+            page_data(0x4000, SNP, data_offset + 0x1000, 0, 2),
+            header(IGVM_VHT_PARAMETER_AREA, &area),
+            header(IGVM_VHT_PARAMETER_INSERT, &insert),
+            header(IGVM_VHT_VP_CONTEXT, &context),
+        ]
+        .concat();
+
+        let mut file: Vec<u8> = MAGIC.to_le_bytes().to_vec();
+        file.extend_from_slice(&1u32.to_le_bytes());
+        file.extend_from_slice(&(FIXED_HEADER_SIZE as u32).to_le_bytes());
+        file.extend_from_slice(&(variable.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(data_offset + 0x3000).to_le_bytes());
+        file.extend_from_slice(&0u32.to_le_bytes());
+        file.extend_from_slice(&variable);
+
+        file.resize(data_offset as usize, 0);
+        file.extend_from_slice(&[0xaa; PAGE_SIZE]);
+        file.extend_from_slice(&[0xcc; PAGE_SIZE]);
+        file.extend_from_slice(&[0xee; PAGE_SIZE]);
+        file
+    }
+
+    #[repr(C, align(4096))]
+    struct Memory([u8; 8 * PAGE_SIZE]);
+
+    impl GuestMemory for Memory {
+        fn slice(&self, gpa: u64, len: usize) -> Option<&[u8]> {
+            self.0.get(gpa as usize..gpa as usize + len)
+        }
+
+        fn slice_mut(&mut self, gpa: u64, len: usize) -> Option<&mut [u8]> {
+            self.0.get_mut(gpa as usize..gpa as usize + len)
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let igvm: IgvmFile = IgvmFile::parse(&file()).unwrap();
+
+        assert_eq!(igvm.directives().len(), 6);
+        assert_eq!(
+            igvm.directives()[0],
+            IgvmDirective::PageData {
+                gpa: 0x1000,
+                data_type: IgvmPageDataType::Normal,
+                unmeasured: false,
+                data: vec![0xaa; PAGE_SIZE],
+                size: PAGE_SIZE,
+            }
+        );
+        assert_eq!(
+            igvm.directives()[4],
+            IgvmDirective::ParameterInsert {
+                gpa: 0x5000,
+                size: 0x1000
+            }
+        );
+        assert_eq!(igvm.vp_contexts().count(), 1);
+
+        let mut bytes: Vec<u8> = file();
+        bytes[0] = b'X';
+        assert!(matches!(IgvmFile::parse(&bytes), Err(IgvmError::Magic(_))));
+        assert!(matches!(
+            IgvmFile::parse(&file()[..40]),
+            Err(IgvmError::Truncated { .. })
+        ));
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_measure() {
+        let igvm: IgvmFile = IgvmFile::parse(&file()).unwrap();
+
+        let mut expected: LaunchDigest = LaunchDigest::new();
+        expected
+            .replay(&[
+                PageUpdate::Normal {
+                    gpa: 0x1000,
+                    data: &[0xaa; PAGE_SIZE],
+                },
+                PageUpdate::Zero {
+                    gpa: 0x2000,
+                    len: PAGE_SIZE,
+                },
+                PageUpdate::Secrets { gpa: 0x3000 },
+                PageUpdate::Cpuid { gpa: 0x4000 },
+                PageUpdate::Unmeasured {
+                    gpa: 0x5000,
+                    len: PAGE_SIZE,
+                },
+                PageUpdate::Vmsa {
+                    page: &[0xee; PAGE_SIZE],
+                },
+            ])
+            .unwrap();
+
+        assert_eq!(igvm.measure().unwrap(), expected.digest());
+    }
+
+    #[test]
+    fn test_launch() {
+        let igvm: IgvmFile = IgvmFile::parse(&file()).unwrap();
+        let mut memory = Memory([0x11; 8 * PAGE_SIZE]);
+
+        let mut launcher = Launcher::new(Loopback::new(), -1)
+            .unwrap()
+            .start(Start::default())
+            .unwrap();
+        let contexts = igvm.launch(&mut launcher, &mut memory).unwrap();
+
+        assert_eq!(contexts.len(), 1);
+        assert_eq!(
+            launcher.as_ref().command_ids(),
+            vec![22, 23, 24, 24, 24, 24, 24]
+        );
+        assert_eq!(&memory.0[0x1000..0x2000], &[0xaa; PAGE_SIZE][..]);
+        assert_eq!(&memory.0[0x2000..0x3000], &[0; PAGE_SIZE][..]);
+        assert_eq!(&memory.0[0x4000..0x5000], &[0xcc; PAGE_SIZE][..]);
+        assert_eq!(&memory.0[0x5000..0x6000], &[0x11; PAGE_SIZE][..]);
+
+        let mut small = Memory([0; 8 * PAGE_SIZE]);
+        let igvm: IgvmFile = IgvmFile {
+            directives: vec![IgvmDirective::PageData {
+                gpa: 0x8000,
+                data_type: IgvmPageDataType::Normal,
+                unmeasured: false,
+                data: vec![],
+                size: PAGE_SIZE,
+            }],
+        };
+        let error = igvm.launch(&mut launcher, &mut small).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,387 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A launcher for SEV, SEV-ES and SEV-SNP guests alike.
+//!
+//! The [sev](super::sev) and [snp](super::snp) launchers enforce the order
+//! of the launch steps at compile time, which leaves VMMs supporting several
+//! flavors of SEV with one code path per flavor. An [AnyLauncher] walks the
+//! same start → update → measure → finish steps for every [Flavor],
+//! checking their order at runtime instead. Steps which a flavor does not
+//! have succeed without issuing any command.
+//!
+//! # Example:
+//! ```ignore
+//! let launcher = match flavor {
+//!     Flavor::Sev => AnyLauncher::sev(vm_fd, sev)?,
+//!     Flavor::SevEs => AnyLauncher::sev_es(vm_fd, sev)?,
+//!     Flavor::Snp => AnyLauncher::snp(vm_fd, sev)?,
+//! };
+//!
+//! let mut launcher = launcher.start(start)?;
+//! launcher.update_data(gfn, &firmware)?;
+//!
+//! let launcher = launcher.update_vmsas(vcpus)?.measure()?;
+//! if let Some(measurement) = launcher.measurement() {
+//!     // Have the guest owner verify the measurement.
+//! }
+//!
+//! let finished = launcher.finish(None)?;
+//! ```
+
+use crate::launch::{
+    observer::LaunchObserver,
+    sev::{self, Measurement, Secret},
+    snp::{self, PageType, VmplPerms},
+    vmm::{SevDevice, VmHandle},
+};
+
+use std::io::{Error, ErrorKind, Result};
+
+/// The flavor of SEV an [AnyLauncher] launches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flavor {
+    /// An SEV guest.
+    Sev,
+
+    /// An SEV-ES guest, whose vCPU register state is encrypted.
+    SevEs,
+
+    /// An SEV-SNP guest.
+    Snp,
+}
+
+/// The parameters of the first launch step, for the launcher's [Flavor].
+#[allow(clippy::large_enum_variant)]
+pub enum AnyStart<'a> {
+    /// Starts an SEV or SEV-ES launch.
+    Sev(sev::Start),
+
+    /// Starts an SEV-SNP launch.
+    Snp(snp::Start<'a>),
+}
+
+/// A completed launch.
+pub enum AnyFinished<U: VmHandle, V: SevDevice> {
+    /// An SEV or SEV-ES launcher, which can still fetch an attestation
+    /// report.
+    Sev(sev::Launcher<sev::Finished, U, V>),
+
+    /// The VM and SEV device of an SEV-SNP guest.
+    Snp(U, V),
+}
+
+enum State<U: VmHandle, V: SevDevice> {
+    SevNew(sev::Launcher<sev::New, U, V>),
+    SevStarted(sev::Launcher<sev::Started, U, V>),
+    SevVmsaUpdated(sev::Launcher<sev::VmsaUpdated, U, V>),
+    SevMeasured(sev::Launcher<sev::Measured, U, V>),
+    SnpNew(snp::Launcher<snp::New, U, V>),
+    SnpStarted(snp::Launcher<snp::Started, U, V>),
+}
+
+impl<U: VmHandle, V: SevDevice> State<U, V> {
+    fn step(&self) -> &'static str {
+        match self {
+            State::SevNew(_) | State::SnpNew(_) => "new",
+            State::SevStarted(_) | State::SnpStarted(_) => "started",
+            State::SevVmsaUpdated(_) => "VMSA updated",
+            State::SevMeasured(_) => "measured",
+        }
+    }
+}
+
+/// Facilitates the launch of SEV, SEV-ES and SEV-SNP guests through one
+/// interface.
+///
+/// Steps move the launcher from one state to the next and are checked at
+/// runtime: calling a step out of order fails with
+/// [ErrorKind::InvalidInput], consuming the launcher if the step would.
+pub struct AnyLauncher<U: VmHandle, V: SevDevice> {
+    flavor: Flavor,
+    state: State<U, V>,
+}
+
+impl<U: VmHandle, V: SevDevice> AnyLauncher<U, V> {
+    /// Begin the SEV launch process.
+    pub fn sev(vm_fd: U, sev: V) -> Result<Self> {
+        Ok(Self {
+            flavor: Flavor::Sev,
+            state: State::SevNew(sev::Launcher::new(vm_fd, sev)?),
+        })
+    }
+
+    /// Begin the SEV-ES launch process.
+    pub fn sev_es(vm_fd: U, sev: V) -> Result<Self> {
+        Ok(Self {
+            flavor: Flavor::SevEs,
+            state: State::SevNew(sev::Launcher::new_es(vm_fd, sev)?),
+        })
+    }
+
+    /// Begin the SEV-SNP launch process.
+    pub fn snp(vm_fd: U, sev: V) -> Result<Self> {
+        Ok(Self {
+            flavor: Flavor::Snp,
+            state: State::SnpNew(snp::Launcher::new(vm_fd, sev)?),
+        })
+    }
+
+    /// The flavor of SEV being launched.
+    pub fn flavor(&self) -> Flavor {
+        self.flavor
+    }
+
+    /// Notify `observer` as each phase of the launch completes. Has no
+    /// effect once the launch has started.
+    pub fn observe(mut self, observer: impl LaunchObserver + 'static) -> Self {
+        self.state = match self.state {
+            State::SevNew(launcher) => State::SevNew(launcher.observe(observer)),
+            State::SnpNew(launcher) => State::SnpNew(launcher.observe(observer)),
+            state => state,
+        };
+        self
+    }
+
+    /// Give access to the vm fd to create vCPUs or such.
+    pub fn as_mut_vmfd(&mut self) -> &mut U {
+        match &mut self.state {
+            State::SevNew(launcher) => launcher.as_mut_vmfd(),
+            State::SevStarted(launcher) => launcher.as_mut_vmfd(),
+            State::SevVmsaUpdated(launcher) => launcher.as_mut_vmfd(),
+            State::SevMeasured(launcher) => launcher.as_mut_vmfd(),
+            State::SnpNew(launcher) => launcher.as_mut(),
+            State::SnpStarted(launcher) => launcher.as_mut(),
+        }
+    }
+
+    /// Create an encrypted guest context. `start` must match the flavor.
+    pub fn start(self, start: AnyStart) -> Result<Self> {
+        let state = match (self.state, start) {
+            (State::SevNew(launcher), AnyStart::Sev(start)) => {
+                State::SevStarted(launcher.start(start)?)
+            }
+            (State::SnpNew(launcher), AnyStart::Snp(start)) => {
+                State::SnpStarted(launcher.start(start)?)
+            }
+            (State::SevNew(_), AnyStart::Snp(_)) | (State::SnpNew(_), AnyStart::Sev(_)) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("start parameters do not match the {:?} launch", self.flavor),
+                ))
+            }
+            (state, _) => return Err(out_of_order("start", &state)),
+        };
+
+        Ok(Self {
+            flavor: self.flavor,
+            state,
+        })
+    }
+
+    /// Encrypt guest data, to be placed at guest frame number `gfn`.
+    ///
+    /// SEV and SEV-ES guests have no use for `gfn`. SEV-SNP guests receive
+    /// the data as normal pages, so it must be page-aligned; use
+    /// [update_snp](Self::update_snp) for other page types.
+    pub fn update_data(&mut self, gfn: u64, data: &[u8]) -> Result<()> {
+        match &mut self.state {
+            State::SevStarted(launcher) => launcher.update_data(data),
+            State::SnpStarted(launcher) => launcher.update_data(snp::Update::new(
+                gfn,
+                data,
+                false,
+                PageType::Normal,
+                (VmplPerms::empty(), VmplPerms::empty(), VmplPerms::empty()),
+            )),
+            state => Err(out_of_order("update", state)),
+        }
+    }
+
+    /// Insert `update` into an SEV-SNP guest, e.g. its CPUID or secrets
+    /// page.
+    pub fn update_snp(&mut self, update: snp::Update) -> Result<()> {
+        match &mut self.state {
+            State::SnpStarted(launcher) => launcher.update_data(update),
+            state => Err(out_of_order("SNP update", state)),
+        }
+    }
+
+    /// Encrypt the VMSA of each of the guest's `vcpus` vCPUs, all of which
+    /// must have been created beforehand. No further guest data can be
+    /// encrypted afterwards.
+    ///
+    /// Only SEV-ES guests issue a command here: SEV guests have no
+    /// encrypted VMSAs and KVM encrypts those of SEV-SNP guests when the
+    /// launch finishes.
+    pub fn update_vmsas(self, vcpus: u32) -> Result<Self> {
+        let state = match (self.flavor, self.state) {
+            (Flavor::SevEs, State::SevStarted(launcher)) => {
+                State::SevVmsaUpdated(launcher.update_vmsas(vcpus)?)
+            }
+            (Flavor::Sev, state @ State::SevStarted(_)) => state,
+            (Flavor::Snp, state @ State::SnpStarted(_)) => state,
+            (_, state) => return Err(out_of_order("VMSA update", &state)),
+        };
+
+        Ok(Self {
+            flavor: self.flavor,
+            state,
+        })
+    }
+
+    /// Request a measurement from the SEV firmware, after the VMSAs of an
+    /// SEV-ES guest were encrypted.
+    ///
+    /// SEV-SNP guests are measured by their attestation reports instead,
+    /// so no measurement is requested.
+    pub fn measure(self) -> Result<Self> {
+        let state = match (self.flavor, self.state) {
+            (Flavor::Sev, State::SevStarted(launcher)) => State::SevMeasured(launcher.measure()?),
+            (Flavor::SevEs, State::SevVmsaUpdated(launcher)) => {
+                State::SevMeasured(launcher.measure()?)
+            }
+            (Flavor::Snp, state @ State::SnpStarted(_)) => state,
+            (_, state) => return Err(out_of_order("measure", &state)),
+        };
+
+        Ok(Self {
+            flavor: self.flavor,
+            state,
+        })
+    }
+
+    /// The measurement that the SEV platform recorded, once an SEV or
+    /// SEV-ES guest was measured.
+    pub fn measurement(&self) -> Option<Measurement> {
+        match &self.state {
+            State::SevMeasured(launcher) => Some(launcher.measurement()),
+            _ => None,
+        }
+    }
+
+    /// Inject a secret into a measured SEV or SEV-ES guest.
+    ///
+    /// This should only be called after a successful attestation flow.
+    pub fn inject(&mut self, secret: &Secret, guest: usize) -> Result<()> {
+        match &mut self.state {
+            State::SevMeasured(launcher) => launcher.inject(secret, guest),
+            state => Err(out_of_order("inject", state)),
+        }
+    }
+
+    /// Complete the launch process. SEV-SNP guests are finished with
+    /// `finish`, or without an ID block if there is none; SEV and SEV-ES
+    /// guests take no parameters.
+    pub fn finish(self, finish: Option<snp::Finish>) -> Result<AnyFinished<U, V>> {
+        match (self.state, finish) {
+            (State::SevMeasured(launcher), None) => {
+                Ok(AnyFinished::Sev(launcher.finish_attestable()?))
+            }
+            (State::SnpStarted(launcher), finish) => {
+                let finish = finish.unwrap_or_else(|| snp::Finish::new(None, None, [0; 32]));
+                let (vm_fd, sev) = launcher.finish(finish)?;
+
+                Ok(AnyFinished::Snp(vm_fd, sev))
+            }
+            (State::SevMeasured(_), Some(_)) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "SNP finish parameters given for the {:?} launch",
+                    self.flavor
+                ),
+            )),
+            (state, _) => Err(out_of_order("finish", &state)),
+        }
+    }
+}
+
+fn out_of_order<U: VmHandle, V: SevDevice>(step: &str, state: &State<U, V>) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("cannot {} a launch which is {}", step, state.step()),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::launch::vmm::Loopback;
+
+    use codicon::Decoder;
+
+    #[repr(C, align(4096))]
+    struct Page([u8; 4096]);
+
+    fn sev_start() -> sev::Start {
+        let zeroes = [0u8; std::mem::size_of::<sev::Start>()];
+        sev::Start::decode(&mut &zeroes[..], ()).unwrap()
+    }
+
+    fn launch(launcher: AnyLauncher<Loopback, i32>, start: AnyStart) -> Vec<u32> {
+        let page = Page([0; 4096]);
+
+        let mut launcher = launcher.start(start).unwrap();
+        launcher.update_data(0x10, &page.0).unwrap();
+
+        let mut launcher = launcher.update_vmsas(2).unwrap().measure().unwrap();
+        assert_eq!(
+            launcher.flavor() == Flavor::Snp,
+            launcher.measurement().is_none()
+        );
+        let ids = launcher.as_mut_vmfd().command_ids();
+
+        launcher.finish(None).unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_flavors() {
+        assert_eq!(
+            launch(
+                AnyLauncher::sev(Loopback::new(), -1).unwrap(),
+                AnyStart::Sev(sev_start())
+            ),
+            vec![0, 2, 3, 6]
+        );
+        assert_eq!(
+            launch(
+                AnyLauncher::sev_es(Loopback::new(), -1).unwrap(),
+                AnyStart::Sev(sev_start())
+            ),
+            vec![1, 2, 3, 4, 6]
+        );
+        assert_eq!(
+            launch(
+                AnyLauncher::snp(Loopback::new(), -1).unwrap(),
+                AnyStart::Snp(snp::Start::default())
+            ),
+            vec![22, 23, 24]
+        );
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let launcher = AnyLauncher::sev(Loopback::new(), -1).unwrap();
+        let error = launcher
+            .start(AnyStart::Snp(snp::Start::default()))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+
+        let mut launcher = AnyLauncher::snp(Loopback::new(), -1).unwrap();
+        assert!(launcher.update_data(0, &[]).is_err());
+        assert!(launcher.measurement().is_none());
+
+        let launcher = AnyLauncher::sev_es(Loopback::new(), -1)
+            .unwrap()
+            .start(AnyStart::Sev(sev_start()))
+            .unwrap();
+        let error = launcher.measure().err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            error.to_string(),
+            "cannot measure a launch which is started"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Launch guests from the `kvm_ioctls::VmFd` of a rust-vmm based VMM.
+//!
+//! A [KvmVm] issues the launch commands through the `kvm-ioctls` API
+//! (`encrypt_op_sev`, `register_enc_memory_region` and
+//! `set_memory_attributes`) rather than raw ioctls, and accepts the VM by
+//! value, by reference or shared (e.g. `Arc<VmFd>`), so that the VMM keeps
+//! using its VM while the guest launches. The functions of this module
+//! create the launcher of each guest type around it.
+//!
+//! # Example:
+//! ```ignore
+//! let kvm = Kvm::new()?;
+//! let vm = kvm.create_vm()?;
+//! let sev = Firmware::open()?;
+//!
+//! let launcher = kvm::sev_launcher(&vm, sev)?;
+//! let mut launcher = launcher.start(start)?;
+//! launcher.update_data(&memory)?;
+//! ```
+
+use crate::launch::{
+    linux::ioctl::max_vcpus,
+    sev,
+    snp::{
+        self,
+        gmem::{GmemInit, GmemLauncher, KVM_X86_SNP_VM},
+    },
+    vmm::{EncryptOp, SevDevice, VmHandle},
+};
+
+use kvm_bindings::{kvm_enc_region, kvm_memory_attributes, kvm_sev_cmd};
+use kvm_ioctls::{Kvm, VmFd};
+
+use std::{borrow::Borrow, io::Result, os::unix::io::AsRawFd};
+
+/// The KVM VM type of SEV guests initialized with `KVM_SEV_INIT2`.
+pub const KVM_X86_SEV_VM: u64 = 2;
+
+/// The KVM VM type of SEV-ES guests initialized with `KVM_SEV_INIT2`.
+pub const KVM_X86_SEV_ES_VM: u64 = 3;
+
+/// A [VmHandle] issuing commands through a `kvm_ioctls::VmFd`.
+#[derive(Debug)]
+pub struct KvmVm<V: Borrow<VmFd> = VmFd>(V);
+
+impl<V: Borrow<VmFd>> KvmVm<V> {
+    /// Issue commands through `vm`.
+    pub fn new(vm: V) -> Self {
+        Self(vm)
+    }
+
+    /// The VM the commands are issued through.
+    pub fn vm(&self) -> &VmFd {
+        self.0.borrow()
+    }
+
+    /// Recover the VM, e.g. once the launch has finished.
+    pub fn into_inner(self) -> V {
+        self.0
+    }
+}
+
+impl<V: Borrow<VmFd>> VmHandle for KvmVm<V> {
+    fn encrypt_op(&mut self, op: &mut EncryptOp) -> Result<()> {
+        let mut cmd = kvm_sev_cmd {
+            id: op.id,
+            data: op.data,
+            error: op.error,
+            sev_fd: op.sev_fd,
+            ..Default::default()
+        };
+
+        let result = self.vm().encrypt_op_sev(&mut cmd);
+        op.error = cmd.error;
+
+        Ok(result?)
+    }
+
+    fn register_region(&mut self, addr: u64, size: u64) -> Result<()> {
+        Ok(self
+            .vm()
+            .register_enc_memory_region(&kvm_enc_region { addr, size })?)
+    }
+
+    fn set_memory_attributes(&mut self, gpa: u64, size: u64, attributes: u64) -> Result<()> {
+        Ok(self.vm().set_memory_attributes(kvm_memory_attributes {
+            address: gpa,
+            size,
+            attributes,
+            flags: 0,
+        })?)
+    }
+
+    fn max_vcpus(&mut self) -> Result<u32> {
+        max_vcpus(&mut self.vm().as_raw_fd())
+    }
+}
+
+/// Begin launching an SEV guest on `vm`, issuing `KVM_SEV_INIT`.
+pub fn sev_launcher<V: Borrow<VmFd>, S: SevDevice>(
+    vm: V,
+    sev: S,
+) -> Result<sev::Launcher<sev::New, KvmVm<V>, S>> {
+    sev::Launcher::new(KvmVm::new(vm), sev)
+}
+
+/// Begin launching an SEV-ES guest on `vm`, issuing `KVM_SEV_ES_INIT`.
+pub fn sev_es_launcher<V: Borrow<VmFd>, S: SevDevice>(
+    vm: V,
+    sev: S,
+) -> Result<sev::Launcher<sev::New, KvmVm<V>, S>> {
+    sev::Launcher::new_es(KvmVm::new(vm), sev)
+}
+
+/// Begin launching an SEV-SNP guest on `vm`, issuing `KVM_SEV_SNP_INIT`.
+pub fn snp_launcher<V: Borrow<VmFd>, S: SevDevice>(
+    vm: V,
+    sev: S,
+) -> Result<snp::Launcher<snp::New, KvmVm<V>, S>> {
+    snp::Launcher::new(KvmVm::new(vm), sev)
+}
+
+/// Create a [KVM_X86_SNP_VM] and begin launching an SEV-SNP guest backed
+/// by guest_memfd on it, issuing `KVM_SEV_INIT2`. The VMM sets up the
+/// guest's memory with [KvmVm::vm].
+pub fn snp_gmem_launcher<S: SevDevice>(
+    kvm: &Kvm,
+    sev: S,
+    init: GmemInit,
+) -> Result<GmemLauncher<snp::New, KvmVm, S>> {
+    let vm = kvm.create_vm_with_type(KVM_X86_SNP_VM)?;
+
+    GmemLauncher::new(KvmVm::new(vm), sev, init)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::mem::{align_of, size_of};
+
+    #[test]
+    fn test_encrypt_op_layout() {
+        assert_eq!(size_of::<EncryptOp>(), size_of::<kvm_sev_cmd>());
+        assert_eq!(align_of::<EncryptOp>(), align_of::<kvm_sev_cmd>());
+    }
+}
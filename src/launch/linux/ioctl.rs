@@ -5,7 +5,9 @@
 
 use crate::{
     error::{Error, Indeterminate},
+    firmware::linux::trace::IoctlCall,
     impl_const_id,
+    launch::vmm::{EncryptOp, SevDevice, VmHandle},
 };
 
 #[cfg(feature = "sev")]
@@ -16,7 +18,10 @@ use crate::launch::linux::snp;
 
 use std::{
     marker::PhantomData,
-    os::{raw::c_ulong, unix::io::AsRawFd},
+    os::{
+        raw::{c_int, c_ulong},
+        unix::io::AsRawFd,
+    },
 };
 
 use iocuddle::*;
@@ -36,12 +41,27 @@ impl_const_id! {
     sev::LaunchSecret<'_> = 5,
     sev::LaunchMeasure<'_> = 6,
     sev::LaunchFinish = 7,
+    sev::SendStart<'_> = 8,
+    sev::SendUpdateData<'_> = 9,
+    sev::SendFinish = 11,
+    sev::ReceiveStart<'_> = 12,
+    sev::ReceiveUpdateData<'_> = 13,
+    sev::ReceiveFinish = 15,
+    sev::SevGuestStatus = 16,
+    sev::DbgDecrypt<'_> = 17,
+    sev::DbgEncrypt<'_> = 18,
     sev::LaunchAttestation<'_> = 20,
+    sev::SendCancel = 21,
 
     snp::Init = 22,
     snp::LaunchStart<'_> = 23,
     snp::LaunchUpdate<'_> = 24,
     snp::LaunchFinish<'_> = 25,
+
+    snp::Init2 = 22,
+    snp::GmemLaunchStart = 100,
+    snp::GmemLaunchUpdate<'_> = 101,
+    snp::GmemLaunchFinish<'_> = 102,
 }
 
 #[cfg(all(feature = "sev", not(feature = "snp")))]
@@ -57,7 +77,17 @@ impl_const_id! {
     sev::LaunchSecret<'_> = 5,
     sev::LaunchMeasure<'_> = 6,
     sev::LaunchFinish = 7,
+    sev::SendStart<'_> = 8,
+    sev::SendUpdateData<'_> = 9,
+    sev::SendFinish = 11,
+    sev::ReceiveStart<'_> = 12,
+    sev::ReceiveUpdateData<'_> = 13,
+    sev::ReceiveFinish = 15,
+    sev::SevGuestStatus = 16,
+    sev::DbgDecrypt<'_> = 17,
+    sev::DbgEncrypt<'_> = 18,
     sev::LaunchAttestation<'_> = 20,
+    sev::SendCancel = 21,
 }
 
 #[cfg(all(not(feature = "sev"), feature = "snp"))]
@@ -69,6 +99,11 @@ impl_const_id! {
     snp::LaunchStart<'_> = 23,
     snp::LaunchUpdate<'_> = 24,
     snp::LaunchFinish<'_> = 25,
+
+    snp::Init2 = 22,
+    snp::GmemLaunchStart = 100,
+    snp::GmemLaunchUpdate<'_> = 101,
+    snp::GmemLaunchFinish<'_> = 102,
 }
 
 const KVM: Group = Group::new(0xAE);
@@ -86,70 +121,14 @@ const ENC_OP: Ioctl<WriteRead, &c_ulong> = unsafe { KVM.write_read(0xBA) };
 // which would require extra work to wrap around the design decision for
 // that ioctl.
 
-/// Initialize the SEV platform context.
-#[cfg(feature = "sev")]
-pub const INIT: Ioctl<WriteRead, &Command<sev::Init>> = unsafe { ENC_OP.lie() };
-
-/// Initialize the SEV-ES platform context.
-#[cfg(feature = "sev")]
-pub const ES_INIT: Ioctl<WriteRead, &Command<sev::EsInit>> = unsafe { ENC_OP.lie() };
-
-/// Create encrypted guest context.
-#[cfg(feature = "sev")]
-pub const LAUNCH_START: Ioctl<WriteRead, &Command<sev::LaunchStart>> = unsafe { ENC_OP.lie() };
-
-/// Encrypt guest data with its VEK.
-#[cfg(feature = "sev")]
-pub const LAUNCH_UPDATE_DATA: Ioctl<WriteRead, &Command<sev::LaunchUpdateData>> =
-    unsafe { ENC_OP.lie() };
-
-/// Encrypt the VMSA contents for SEV-ES.
-#[cfg(feature = "sev")]
-pub const LAUNCH_UPDATE_VMSA: Ioctl<WriteRead, &Command<sev::LaunchUpdateVmsa>> =
-    unsafe { ENC_OP.lie() };
-
-/// Inject a secret into the guest.
-#[cfg(feature = "sev")]
-pub const LAUNCH_SECRET: Ioctl<WriteRead, &Command<sev::LaunchSecret>> = unsafe { ENC_OP.lie() };
-
-/// Get the guest's measurement.
-#[cfg(feature = "sev")]
-pub const LAUNCH_MEASUREMENT: Ioctl<WriteRead, &Command<sev::LaunchMeasure>> =
-    unsafe { ENC_OP.lie() };
-
-/// Complete the SEV launch flow and transition the guest into
-/// the ready state.
-#[cfg(feature = "sev")]
-pub const LAUNCH_FINISH: Ioctl<WriteRead, &Command<sev::LaunchFinish>> = unsafe { ENC_OP.lie() };
-
-/// Fetch a legacy SEV VM's attestation report.
-#[cfg(feature = "sev")]
-pub const LAUNCH_ATTESTATION: Ioctl<WriteRead, &Command<sev::LaunchAttestation>> =
-    unsafe { ENC_OP.lie() };
+/// Issue a command to the SEV firmware.
+pub const ENCRYPT_OP: Ioctl<WriteRead, &EncryptOp> = unsafe { ENC_OP.lie() };
 
 /// Corresponds to the `KVM_MEMORY_ENCRYPT_REG_REGION` ioctl
 #[cfg(any(feature = "sev", feature = "snp"))]
 pub const ENC_REG_REGION: Ioctl<Write, &KvmEncRegion> =
     unsafe { KVM.read::<KvmEncRegion>(0xBB).lie() };
 
-/// Initialize the SEV-SNP platform in KVM.
-#[cfg(feature = "snp")]
-pub const SNP_INIT: Ioctl<WriteRead, &Command<snp::Init>> = unsafe { ENC_OP.lie() };
-
-/// Initialize the flow to launch a guest.
-#[cfg(feature = "snp")]
-pub const SNP_LAUNCH_START: Ioctl<WriteRead, &Command<snp::LaunchStart>> = unsafe { ENC_OP.lie() };
-
-/// Insert pages into the guest physical address space.
-#[cfg(feature = "snp")]
-pub const SNP_LAUNCH_UPDATE: Ioctl<WriteRead, &Command<snp::LaunchUpdate>> =
-    unsafe { ENC_OP.lie() };
-
-/// Complete the guest launch flow.
-#[cfg(feature = "snp")]
-pub const SNP_LAUNCH_FINISH: Ioctl<WriteRead, &Command<snp::LaunchFinish>> =
-    unsafe { ENC_OP.lie() };
-
 /// Corresponds to the kernel struct `kvm_enc_region`
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
@@ -169,9 +148,77 @@ impl<'a> KvmEncRegion<'a> {
         }
     }
 
+    /// Create a new `KvmEncRegion` referencing `size` bytes of memory at
+    /// `addr`, which must outlive the region.
+    pub fn from_raw(addr: u64, size: u64) -> Self {
+        Self {
+            addr,
+            size,
+            phantom: PhantomData,
+        }
+    }
+
     /// Register the encrypted memory region to a virtual machine
-    pub fn register(&mut self, vm_fd: &mut impl AsRawFd) -> std::io::Result<std::os::raw::c_uint> {
-        ENC_REG_REGION.ioctl(vm_fd, self)
+    pub fn register(&mut self, vm_fd: &mut impl AsRawFd) -> std::io::Result<()> {
+        ENC_REG_REGION.ioctl(vm_fd, self)?;
+        Ok(())
+    }
+
+    /// Register the encrypted memory region through `vm`.
+    pub fn register_with(&self, vm: &mut impl VmHandle) -> std::io::Result<()> {
+        let call = IoctlCall::launch::<Self>();
+        let result = vm.register_region(self.addr, self.size);
+        call.finish(&result, 0);
+        result
+    }
+}
+
+/// Corresponds to the `KVM_CHECK_EXTENSION` ioctl
+const CHECK_EXTENSION: Ioctl<Write, c_int> = unsafe { Ioctl::classic(0xAE03) };
+
+const KVM_CAP_NR_VCPUS: c_int = 9;
+const KVM_CAP_MAX_VCPUS: c_int = 66;
+
+/// The largest number of vCPUs KVM supports in the VM of `vm_fd`, falling
+/// back as the KVM API documents for kernels lacking `KVM_CAP_MAX_VCPUS`.
+pub fn max_vcpus(vm_fd: &mut impl AsRawFd) -> std::io::Result<u32> {
+    match CHECK_EXTENSION.ioctl(vm_fd, KVM_CAP_MAX_VCPUS)? {
+        0 => match CHECK_EXTENSION.ioctl(vm_fd, KVM_CAP_NR_VCPUS)? {
+            0 => Ok(4),
+            max => Ok(max),
+        },
+        max => Ok(max),
+    }
+}
+
+/// Corresponds to the `KVM_SET_MEMORY_ATTRIBUTES` ioctl
+pub const SET_MEMORY_ATTRIBUTES: Ioctl<Write, &KvmMemoryAttributes> = unsafe { KVM.write(0xD2) };
+
+/// Corresponds to the kernel struct `kvm_memory_attributes`
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct KvmMemoryAttributes {
+    address: u64,
+    size: u64,
+    attributes: u64,
+    flags: u64,
+}
+
+impl KvmMemoryAttributes {
+    /// Set `attributes` on `size` bytes of guest physical memory at `address`.
+    pub fn new(address: u64, size: u64, attributes: u64) -> Self {
+        Self {
+            address,
+            size,
+            attributes,
+            flags: 0,
+        }
+    }
+
+    /// Set the attributes of the memory of a virtual machine
+    pub fn set(&self, vm_fd: &mut impl AsRawFd) -> std::io::Result<()> {
+        SET_MEMORY_ATTRIBUTES.ioctl(vm_fd, self)?;
+        Ok(())
     }
 }
 
@@ -187,27 +234,45 @@ pub struct Command<'a, T: Id> {
 
 impl<'a, T: Id> Command<'a, T> {
     /// create the command from a mutable subcommand
-    pub fn from_mut(sev: &'a impl AsRawFd, subcmd: &'a mut T) -> Self {
+    pub fn from_mut(sev: &'a impl SevDevice, subcmd: &'a mut T) -> Self {
         Self {
             code: T::ID,
             data: subcmd as *mut T as _,
             error: 0,
-            sev_fd: sev.as_raw_fd() as _,
+            sev_fd: sev.sev_fd() as _,
             _phantom: PhantomData,
         }
     }
 
     /// create the command from a subcommand reference
-    pub fn from(sev: &'a impl AsRawFd, subcmd: &'a T) -> Self {
+    pub fn from(sev: &'a impl SevDevice, subcmd: &'a T) -> Self {
         Self {
             code: T::ID,
             data: subcmd as *const T as _,
             error: 0,
-            sev_fd: sev.as_raw_fd() as _,
+            sev_fd: sev.sev_fd() as _,
             _phantom: PhantomData,
         }
     }
 
+    /// issue the command through `vm`, instrumented when the `tracing`
+    /// feature is enabled
+    pub fn issue(&mut self, vm: &mut impl VmHandle) -> Result<(), Indeterminate<Error>> {
+        let mut op = EncryptOp {
+            id: self.code,
+            data: self.data,
+            error: 0,
+            sev_fd: self.sev_fd,
+        };
+
+        let call = IoctlCall::launch::<T>();
+        let result = vm.encrypt_op(&mut op);
+        call.finish(&result, op.error as u64);
+        self.error = op.error;
+
+        result.map_err(|e| self.encapsulate(e))
+    }
+
     /// encapsulate a `std::io::Error` in an `Indeterminate<Error>`
     pub fn encapsulate(&self, err: std::io::Error) -> Indeterminate<Error> {
         match self.error {
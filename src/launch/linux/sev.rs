@@ -17,10 +17,24 @@ pub struct Init;
 #[repr(C)]
 pub struct EsInit;
 
-#[derive(Clone, Copy)]
+/// The firmware's handle of a guest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct Handle(u32);
 
+impl Handle {
+    /// Wrap the raw handle `handle`, e.g. as reported by GUEST_STATUS.
+    pub fn new(handle: u32) -> Self {
+        Self(handle)
+    }
+}
+
+impl From<Handle> for u32 {
+    fn from(handle: Handle) -> Self {
+        handle.0
+    }
+}
+
 impl From<LaunchStart<'_>> for Handle {
     fn from(ls: LaunchStart) -> Self {
         ls.handle
@@ -69,6 +83,16 @@ impl<'a> LaunchUpdateData<'a> {
             _phantom: PhantomData,
         }
     }
+
+    /// Encrypt `len` bytes of host memory at `addr`, which must outlive the
+    /// command.
+    pub fn from_raw(addr: u64, len: u32) -> Self {
+        Self {
+            addr,
+            len,
+            _phantom: PhantomData,
+        }
+    }
 }
 
 /// Update VMSA for setting up vCPUs on SEV-ES.
@@ -150,3 +174,186 @@ impl<'a> LaunchAttestation<'a> {
         }
     }
 }
+
+/// Query the status of a guest.
+#[repr(C)]
+#[derive(Default)]
+pub struct SevGuestStatus {
+    pub handle: u32,
+    pub policy: u32,
+    pub state: u32,
+}
+
+/// Create an outgoing guest context for migration.
+#[repr(C)]
+pub struct SendStart<'a> {
+    policy: Policy,
+    pdh_cert_addr: u64,
+    pdh_cert_len: u32,
+    plat_certs_addr: u64,
+    plat_certs_len: u32,
+    amd_certs_addr: u64,
+    amd_certs_len: u32,
+    session_addr: u64,
+    session_len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> SendStart<'a> {
+    pub fn new(
+        policy: &'a Policy,
+        pdh_cert: &'a Certificate,
+        plat_certs: &'a [u8],
+        amd_certs: &'a [u8],
+        session: &'a mut MaybeUninit<Session>,
+    ) -> Self {
+        Self {
+            policy: *policy,
+            pdh_cert_addr: pdh_cert as *const _ as _,
+            pdh_cert_len: size_of_val(pdh_cert) as _,
+            plat_certs_addr: plat_certs.as_ptr() as _,
+            plat_certs_len: plat_certs.len() as _,
+            amd_certs_addr: amd_certs.as_ptr() as _,
+            amd_certs_len: amd_certs.len() as _,
+            session_addr: session.as_mut_ptr() as _,
+            session_len: size_of_val(session) as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Encrypt a region of guest memory with the transport keys for migration.
+#[repr(C)]
+pub struct SendUpdateData<'a> {
+    hdr_addr: u64,
+    hdr_len: u32,
+    guest_addr: u64,
+    guest_len: u32,
+    trans_addr: u64,
+    trans_len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> SendUpdateData<'a> {
+    pub fn new(header: &'a mut MaybeUninit<Header>, guest: &'a [u8], trans: &'a mut [u8]) -> Self {
+        Self {
+            hdr_addr: header.as_mut_ptr() as _,
+            hdr_len: size_of_val(header) as _,
+            guest_addr: guest.as_ptr() as _,
+            guest_len: guest.len() as _,
+            trans_addr: trans.as_mut_ptr() as _,
+            trans_len: trans.len() as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Complete the outgoing migration flow.
+#[repr(C)]
+pub struct SendFinish;
+
+/// Abort an outgoing migration flow.
+#[repr(C)]
+pub struct SendCancel;
+
+/// Create an incoming guest context for migration.
+#[repr(C)]
+pub struct ReceiveStart<'a> {
+    handle: Handle,
+    policy: Policy,
+    pdh_addr: u64,
+    pdh_len: u32,
+    session_addr: u64,
+    session_len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> ReceiveStart<'a> {
+    pub fn new(policy: &'a Policy, pdh: &'a Certificate, session: &'a Session) -> Self {
+        Self {
+            handle: Handle(0), /* platform will generate one for us */
+            policy: *policy,
+            pdh_addr: pdh as *const _ as _,
+            pdh_len: size_of_val(pdh) as _,
+            session_addr: session as *const _ as _,
+            session_len: size_of_val(session) as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl From<ReceiveStart<'_>> for Handle {
+    fn from(rs: ReceiveStart) -> Self {
+        rs.handle
+    }
+}
+
+/// Decrypt a migrated region of guest memory into the incoming guest.
+#[repr(C)]
+pub struct ReceiveUpdateData<'a> {
+    hdr_addr: u64,
+    hdr_len: u32,
+    guest_addr: u64,
+    guest_len: u32,
+    trans_addr: u64,
+    trans_len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> ReceiveUpdateData<'a> {
+    pub fn new(header: &'a Header, guest: &'a mut [u8], trans: &'a [u8]) -> Self {
+        Self {
+            hdr_addr: header as *const _ as _,
+            hdr_len: size_of_val(header) as _,
+            guest_addr: guest.as_mut_ptr() as _,
+            guest_len: guest.len() as _,
+            trans_addr: trans.as_ptr() as _,
+            trans_len: trans.len() as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Complete the incoming migration flow.
+#[repr(C)]
+pub struct ReceiveFinish;
+
+/// Decrypt a region of guest memory for debugging.
+#[repr(C)]
+pub struct DbgDecrypt<'a> {
+    src_addr: u64,
+    dst_addr: u64,
+    len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> DbgDecrypt<'a> {
+    pub fn new(guest: &'a [u8], plain: &'a mut [u8]) -> Self {
+        Self {
+            src_addr: guest.as_ptr() as _,
+            dst_addr: plain.as_mut_ptr() as _,
+            len: guest.len() as _,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Encrypt data into a region of guest memory for debugging.
+#[repr(C)]
+pub struct DbgEncrypt<'a> {
+    src_addr: u64,
+    dst_addr: u64,
+    len: u32,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> DbgEncrypt<'a> {
+    pub fn new(plain: &'a [u8], guest: &'a mut [u8]) -> Self {
+        Self {
+            src_addr: plain.as_ptr() as _,
+            dst_addr: guest.as_mut_ptr() as _,
+            len: plain.len() as _,
+            _phantom: PhantomData,
+        }
+    }
+}
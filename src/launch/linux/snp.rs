@@ -2,7 +2,7 @@
 
 //! Types for interacting with the KVM SEV-SNP guest management API.
 
-use crate::launch::snp::*;
+use crate::launch::snp::{gmem::*, *};
 
 use std::marker::PhantomData;
 
@@ -106,6 +106,19 @@ impl From<Update<'_>> for LaunchUpdate<'_> {
     }
 }
 
+impl LaunchUpdate<'_> {
+    /// Insert `len` bytes at `uaddr` at guest frame `start_gfn`, with the
+    /// page type and permissions of `update`.
+    pub(crate) fn with_range(update: Update, start_gfn: u64, uaddr: u64, len: u32) -> Self {
+        Self {
+            start_gfn,
+            uaddr,
+            len,
+            ..Self::from(update)
+        }
+    }
+}
+
 pub const KVM_SEV_SNP_FINISH_DATA_SIZE: usize = 32;
 
 /// Complete the guest launch flow.
@@ -150,10 +163,164 @@ impl From<Finish<'_, '_>> for LaunchFinish<'_> {
             id_block_uaddr: id_block,
             id_auth_uaddr: id_auth,
             id_block_en: u8::from(finish.id_block.is_some()),
-            auth_key_en: u8::from(finish.id_auth.is_some()),
+            auth_key_en: u8::from(finish.auth_key_en),
             host_data: finish.host_data,
             pad: [0u8; 6],
             _phantom: PhantomData,
         }
     }
 }
+
+/// Initialize an SEV-SNP VM backed by guest_memfd (`KVM_SEV_INIT2`).
+#[derive(Default)]
+#[repr(C)]
+pub struct Init2 {
+    /// The SEV features enabled in the VMSA of each vCPU.
+    vmsa_features: u64,
+
+    /// Reserved, must be 0.
+    flags: u32,
+
+    /// The GHCB protocol version presented to the guest.
+    ghcb_version: u16,
+
+    pad1: u16,
+
+    pad2: [u32; 8],
+}
+
+impl From<GmemInit> for Init2 {
+    fn from(init: GmemInit) -> Self {
+        Self {
+            vmsa_features: init.vmsa_features,
+            ghcb_version: init.ghcb_version,
+            ..Default::default()
+        }
+    }
+}
+
+/// Initialize the flow to launch a guest backed by guest_memfd.
+#[repr(C)]
+pub struct GmemLaunchStart {
+    /// Guest policy.
+    policy: u64,
+
+    /// Hypervisor provided value to indicate guest OS visible workarounds.
+    gosvw: [u8; 16],
+
+    /// Reserved, must be 0.
+    flags: u16,
+
+    pad0: [u8; 6],
+
+    pad1: [u64; 4],
+}
+
+impl From<Start<'_>> for GmemLaunchStart {
+    fn from(start: Start) -> Self {
+        Self {
+            policy: start.policy.into(),
+            gosvw: start.gosvw,
+            flags: 0,
+            pad0: [0; 6],
+            pad1: [0; 4],
+        }
+    }
+}
+
+/// Populate private guest memory backed by guest_memfd. KVM advances the
+/// range as it is processed.
+#[repr(C)]
+pub struct GmemLaunchUpdate<'a> {
+    /// The first guest frame number of the range.
+    pub(crate) gfn_start: u64,
+
+    /// Userspace address of the source data, or 0 for ZERO pages.
+    pub(crate) uaddr: u64,
+
+    /// The length of the range in bytes.
+    pub(crate) len: u64,
+
+    /// Encoded page type.
+    page_type: u8,
+
+    pad0: u8,
+
+    /// Reserved, must be 0.
+    flags: u16,
+
+    pad1: u32,
+
+    pad2: [u64; 4],
+
+    _phantom: PhantomData<&'a [u8]>,
+}
+
+impl<'a> From<GmemUpdate<'a>> for GmemLaunchUpdate<'a> {
+    fn from(update: GmemUpdate<'a>) -> Self {
+        Self {
+            gfn_start: update.start_gfn,
+            uaddr: match update.source {
+                Some(source) if update.page_type != PageType::Zero => source.as_ptr() as u64,
+                _ => 0,
+            },
+            len: update.len as u64,
+            page_type: update.page_type as _,
+            pad0: 0,
+            flags: 0,
+            pad1: 0,
+            pad2: [0; 4],
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Complete the launch flow of a guest backed by guest_memfd.
+#[repr(C)]
+pub struct GmemLaunchFinish<'a> {
+    /// Userspace address of the ID block. Ignored if ID_BLOCK_EN is 0.
+    id_block_uaddr: u64,
+
+    /// Userspace address of the ID authentication information. Ignored if ID_BLOCK_EN is 0.
+    id_auth_uaddr: u64,
+
+    /// Indicates that the ID block is present.
+    id_block_en: u8,
+
+    /// Indicates that the author key is present in the ID authentication information.
+    auth_key_en: u8,
+
+    /// Indicates that the VCEK may not be used to sign attestation reports.
+    vcek_disabled: u8,
+
+    /// Opaque host-supplied data to describe the guest.
+    host_data: [u8; KVM_SEV_SNP_FINISH_DATA_SIZE],
+
+    pad0: [u8; 3],
+
+    /// Reserved, must be 0.
+    flags: u16,
+
+    pad1: [u64; 4],
+
+    _phantom: PhantomData<&'a [u8]>,
+}
+
+impl From<Finish<'_, '_>> for GmemLaunchFinish<'_> {
+    fn from(finish: Finish) -> Self {
+        let launch_finish: LaunchFinish = LaunchFinish::from(finish);
+
+        Self {
+            id_block_uaddr: launch_finish.id_block_uaddr,
+            id_auth_uaddr: launch_finish.id_auth_uaddr,
+            id_block_en: launch_finish.id_block_en,
+            auth_key_en: launch_finish.auth_key_en,
+            vcek_disabled: 0,
+            host_data: launch_finish.host_data,
+            pad0: [0; 3],
+            flags: 0,
+            pad1: [0; 4],
+            _phantom: PhantomData,
+        }
+    }
+}
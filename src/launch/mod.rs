@@ -10,8 +10,35 @@
 #[cfg(any(feature = "sev", feature = "snp"))]
 mod linux;
 
+#[cfg(target_os = "linux")]
+#[cfg(any(feature = "sev", feature = "snp"))]
+pub mod vmm;
+
+#[cfg(any(feature = "sev", feature = "snp"))]
+pub mod observer;
+
+#[cfg(target_os = "linux")]
+#[cfg(all(feature = "sev", feature = "snp"))]
+pub mod any;
+
+#[cfg(target_os = "linux")]
+#[cfg(all(feature = "sev", feature = "snp"))]
+pub use any::AnyLauncher;
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64", feature = "kvm"))]
+pub mod kvm;
+
 #[cfg(feature = "sev")]
 pub mod sev;
 
 #[cfg(feature = "snp")]
 pub mod snp;
+
+/// Returns true if simultaneous multithreading is active on the host.
+#[cfg(target_os = "linux")]
+#[cfg(any(feature = "sev", feature = "snp"))]
+pub(crate) fn smt_active() -> bool {
+    std::fs::read_to_string("/sys/devices/system/cpu/smt/active")
+        .map(|active| active.trim() == "1")
+        .unwrap_or(false)
+}
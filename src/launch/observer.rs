@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Progress notifications from launchers.
+//!
+//! A [LaunchObserver] attached to a launcher is notified as each phase of
+//! the launch completes, so that VMMs can report launch progress and
+//! collect metrics without wrapping every launcher call.
+
+/// A phase of a launch which completed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LaunchEvent {
+    /// The encrypted guest context was created.
+    Started,
+
+    /// A chunk of guest memory was encrypted.
+    Updated {
+        /// The size of the chunk in bytes.
+        bytes: u64,
+    },
+
+    /// The VMSAs of an SEV-ES guest were encrypted.
+    VmsaUpdated {
+        /// The number of vCPUs.
+        vcpus: u32,
+    },
+
+    /// The firmware measured the guest.
+    Measured,
+
+    /// The launch completed.
+    Finished,
+}
+
+/// Receives the [LaunchEvent]s of a launch.
+///
+/// Closures taking a `&LaunchEvent` are observers, e.g.
+///
+/// ```ignore
+/// let launcher = Launcher::new(vm_fd, sev)?.observe(|event: &LaunchEvent| {
+///     log::info!("launch progress: {event:?}");
+/// });
+/// ```
+pub trait LaunchObserver: Send {
+    /// Called once each phase of the launch completes.
+    fn on_event(&self, event: &LaunchEvent);
+}
+
+impl<F: Fn(&LaunchEvent) + Send> LaunchObserver for F {
+    fn on_event(&self, event: &LaunchEvent) {
+        self(event)
+    }
+}
+
+/// The observer attached to a launcher, if any.
+#[derive(Default)]
+pub(crate) struct Observer(Option<Box<dyn LaunchObserver>>);
+
+impl Observer {
+    pub(crate) fn new(observer: impl LaunchObserver + 'static) -> Self {
+        Self(Some(Box::new(observer)))
+    }
+
+    pub(crate) fn notify(&self, event: LaunchEvent) {
+        if let Some(observer) = &self.0 {
+            observer.on_event(&event);
+        }
+    }
+}
@@ -4,18 +4,28 @@
 //! This ensures (at compile time) that the right steps are called in the
 //! right order.
 
-use crate::error::{Error::InvalidLen, Indeterminate};
+use crate::error::{
+    Error::InvalidLen, EsPolicyError, Indeterminate, MigrationPolicyError, PolicyError,
+    SessionBlobError,
+};
+
+#[cfg(target_os = "linux")]
+use crate::firmware::host::{Capabilities, PlatformStatusFlags};
 
 #[cfg(target_os = "linux")]
 use crate::launch::linux::ioctl::*;
 #[cfg(target_os = "linux")]
+pub use crate::launch::linux::sev::Handle;
+#[cfg(target_os = "linux")]
 use crate::launch::linux::sev::*;
+use crate::launch::observer::{LaunchEvent, LaunchObserver, Observer};
+#[cfg(target_os = "linux")]
+use crate::launch::vmm::{SevDevice, VmHandle};
 use crate::*;
 
-use std::convert::TryFrom;
+use std::convert::{TryFrom, TryInto};
 use std::io::Result;
 use std::mem::MaybeUninit;
-use std::os::unix::io::AsRawFd;
 
 use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
@@ -24,41 +34,69 @@ use serde::{Deserialize, Serialize};
 pub struct New;
 
 /// Launcher type-state that indicates an in-progress launch.
-pub struct Started(Handle);
+pub struct Started(Handle, Session);
+
+/// Launcher type-state that indicates an in-progress SEV-ES launch whose vCPU
+/// register state (VMSA) has been encrypted, recording the number of vCPUs.
+pub struct VmsaUpdated(Handle, u32, Session);
 
 /// Launcher type-state that indicates the availability of a measurement.
-pub struct Measured(Handle, Measurement);
+pub struct Measured(Handle, Measurement, Session);
 
 /// Launcher type-state that indicates the launcher is finished launching, and it's attestation
 /// report can be fetched.
-pub struct Finished;
+pub struct Finished(Handle);
 
 /// Facilitates the correct execution of the SEV launch process.
-pub struct Launcher<T, U: AsRawFd, V: AsRawFd> {
+pub struct Launcher<T, U: VmHandle, V: SevDevice> {
     state: T,
     vm_fd: U,
     sev: V,
+    observer: Observer,
 }
 
-impl<T, U: AsRawFd, V: AsRawFd> Launcher<T, U, V> {
+impl<T, U: VmHandle, V: SevDevice> Launcher<T, U, V> {
     /// Give access to the vm fd to create vCPUs or such.
     pub fn as_mut_vmfd(&mut self) -> &mut U {
         &mut self.vm_fd
     }
 }
 
-impl<U: AsRawFd, V: AsRawFd> Launcher<New, U, V> {
+impl<T, U: VmHandle, V: SevDevice> Launcher<T, U, V> {
+    /// Query the firmware's view of the guest.
+    pub fn status(&mut self) -> Result<GuestStatus> {
+        guest_status(&mut self.vm_fd, &self.sev)
+    }
+}
+
+/// Query the firmware's view of the SEV guest running in `vm_fd`, e.g. to
+/// reconcile a VMM's bookkeeping with the platform.
+///
+/// The ASID of the guest is managed by KVM and is not reported.
+pub fn guest_status<U: VmHandle, V: SevDevice>(vm_fd: &mut U, sev: &V) -> Result<GuestStatus> {
+    let mut status = SevGuestStatus::default();
+    let mut cmd = Command::from_mut(sev, &mut status);
+    cmd.issue(vm_fd)?;
+
+    Ok(GuestStatus {
+        handle: status.handle,
+        policy: Policy::from(status.policy),
+        state: GuestState::try_from(status.state)?,
+    })
+}
+
+impl<U: VmHandle, V: SevDevice> Launcher<New, U, V> {
     /// Begin the SEV launch process.
     pub fn new(kvm: U, sev: V) -> Result<Self> {
         let mut launcher = Launcher {
             vm_fd: kvm,
             sev,
             state: New,
+            observer: Observer::default(),
         };
 
         let mut cmd = Command::from(&launcher.sev, &Init);
-        INIT.ioctl(&mut launcher.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut launcher.vm_fd)?;
 
         Ok(launcher)
     }
@@ -69,45 +107,98 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<New, U, V> {
             vm_fd: kvm,
             sev,
             state: New,
+            observer: Observer::default(),
         };
 
         let mut cmd = Command::from(&launcher.sev, &EsInit);
-        ES_INIT
-            .ioctl(&mut launcher.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut launcher.vm_fd)?;
 
         Ok(launcher)
     }
 
+    /// Notify `observer` as each phase of the launch completes.
+    pub fn observe(mut self, observer: impl LaunchObserver + 'static) -> Self {
+        self.observer = Observer::new(observer);
+        self
+    }
+
     /// Create an encrypted guest context.
     pub fn start(mut self, start: Start) -> Result<Launcher<Started, U, V>> {
         let mut launch_start = LaunchStart::new(&start.policy, &start.cert, &start.session);
         let mut cmd = Command::from_mut(&self.sev, &mut launch_start);
-        LAUNCH_START
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Started);
 
         let next = Launcher {
-            state: Started(launch_start.into()),
+            state: Started(launch_start.into(), start.session),
             vm_fd: self.vm_fd,
             sev: self.sev,
+            observer: self.observer,
         };
 
         Ok(next)
     }
+
+    /// Create an encrypted guest context for an SEV-ES guest, after
+    /// checking its policy against what the platform `requires`.
+    pub fn start_es(
+        self,
+        start: Start,
+        requires: &EsRequirements,
+    ) -> Result<Launcher<Started, U, V>> {
+        requires.check(&start.policy).map_err(invalid_input)?;
+
+        self.start(start)
+    }
 }
 
-impl<U: AsRawFd, V: AsRawFd> Launcher<Started, U, V> {
+impl<U: VmHandle, V: SevDevice> Launcher<Started, U, V> {
     /// Encrypt guest data with its VEK.
     pub fn update_data(&mut self, data: &[u8]) -> Result<()> {
         let launch_update_data = LaunchUpdateData::new(data);
         let mut cmd = Command::from(&self.sev, &launch_update_data);
 
-        KvmEncRegion::new(data).register(&mut self.vm_fd)?;
+        KvmEncRegion::new(data).register_with(&mut self.vm_fd)?;
+
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Updated {
+            bytes: data.len() as u64,
+        });
 
-        LAUNCH_UPDATE_DATA
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        Ok(())
+    }
+
+    /// Encrypt guest data with its VEK, and add it to `digest`, which the
+    /// tenant computes identically with
+    /// [Session::update_data](crate::session::Session::update_data). Data
+    /// which is not made of whole blocks is rejected before it is encrypted.
+    #[cfg(feature = "openssl")]
+    pub fn update_data_measured(
+        &mut self,
+        data: &[u8],
+        digest: &mut crate::session::LaunchDigest,
+    ) -> Result<()> {
+        crate::session::LaunchDigest::check(data)?;
+        self.update_data(data)?;
+        digest.update(data)
+    }
+
+    /// Encrypt several regions of guest data with its VEK, in order, e.g.
+    /// when guest memory is not mapped contiguously in the VMM.
+    ///
+    /// Consecutive regions which are adjacent in the host's address space
+    /// are registered and encrypted by a single command. The measurement
+    /// is the same as encrypting each region with [update_data](Self::update_data).
+    pub fn update_data_vectored(&mut self, regions: &[&[u8]]) -> Result<()> {
+        for (addr, len) in coalesce(regions) {
+            KvmEncRegion::from_raw(addr, len).register_with(&mut self.vm_fd)?;
+
+            let launch_update_data = LaunchUpdateData::from_raw(addr, len as u32);
+            let mut cmd = Command::from(&self.sev, &launch_update_data);
+
+            cmd.issue(&mut self.vm_fd)?;
+            self.observer.notify(LaunchEvent::Updated { bytes: len });
+        }
 
         Ok(())
     }
@@ -115,7 +206,7 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<Started, U, V> {
     /// Register the encrypted memory region to a virtual machine.
     /// Corresponds to the `KVM_MEMORY_ENCRYPT_REG_REGION` ioctl.
     pub fn register_kvm_enc_region(&mut self, data: &[u8]) -> Result<()> {
-        KvmEncRegion::new(data).register(&mut self.vm_fd)?;
+        KvmEncRegion::new(data).register_with(&mut self.vm_fd)?;
         Ok(())
     }
 
@@ -124,50 +215,167 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<Started, U, V> {
         let launch_update_data = LaunchUpdateData::new(data);
         let mut cmd = Command::from(&self.sev, &launch_update_data);
 
-        LAUNCH_UPDATE_DATA
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Updated {
+            bytes: data.len() as u64,
+        });
 
         Ok(())
     }
 
-    /// Encrypt the VMSA on SEV-ES.
+    /// Encrypt the VMSA on SEV-ES. Prefer [update_vmsas](Self::update_vmsas),
+    /// which also ensures that no guest data is added afterwards.
     pub fn update_vmsa(&mut self) -> Result<()> {
         let launch_update_vmsa = LaunchUpdateVmsa::new();
         let mut cmd = Command::from(&self.sev, &launch_update_vmsa);
 
-        LAUNCH_UPDATE_VMSA
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
 
         Ok(())
     }
 
+    /// Encrypt the VMSA of each of the SEV-ES guest's `vcpus` vCPUs, all of
+    /// which must have been created beforehand. No further guest data can
+    /// be encrypted afterwards.
+    ///
+    /// `vcpus` must be at least one and at most the
+    /// [max_vcpus](VmHandle::max_vcpus) of the VM.
+    pub fn update_vmsas(mut self, vcpus: u32) -> Result<Launcher<VmsaUpdated, U, V>> {
+        if vcpus == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "an SEV-ES guest needs at least one vCPU",
+            ));
+        }
+
+        let max = self.vm_fd.max_vcpus()?;
+        if vcpus > max {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("the VM supports at most {max} vCPUs, not {vcpus}"),
+            ));
+        }
+
+        self.update_vmsa()?;
+        self.observer.notify(LaunchEvent::VmsaUpdated { vcpus });
+
+        let next = Launcher {
+            state: VmsaUpdated(self.state.0, vcpus, self.state.1),
+            vm_fd: self.vm_fd,
+            sev: self.sev,
+            observer: self.observer,
+        };
+
+        Ok(next)
+    }
+
+    /// Request a measurement from the SEV firmware.
+    pub fn measure(mut self) -> Result<Launcher<Measured, U, V>> {
+        let measurement = launch_measure(&mut self.vm_fd, &self.sev)?;
+        self.observer.notify(LaunchEvent::Measured);
+
+        let next = Launcher {
+            state: Measured(self.state.0, measurement, self.state.1),
+            vm_fd: self.vm_fd,
+            sev: self.sev,
+            observer: self.observer,
+        };
+
+        Ok(next)
+    }
+}
+
+impl<U: VmHandle, V: SevDevice> Launcher<VmsaUpdated, U, V> {
+    /// The number of vCPUs whose VMSA was encrypted.
+    pub fn vcpus(&self) -> u32 {
+        self.state.1
+    }
+
     /// Request a measurement from the SEV firmware.
     pub fn measure(mut self) -> Result<Launcher<Measured, U, V>> {
-        let mut measurement = MaybeUninit::uninit();
-        let mut launch_measure = LaunchMeasure::new(&mut measurement);
-        let mut cmd = Command::from_mut(&self.sev, &mut launch_measure);
-        LAUNCH_MEASUREMENT
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        let measurement = launch_measure(&mut self.vm_fd, &self.sev)?;
+        self.observer.notify(LaunchEvent::Measured);
 
         let next = Launcher {
-            state: Measured(self.state.0, unsafe { measurement.assume_init() }),
+            state: Measured(self.state.0, measurement, self.state.2),
             vm_fd: self.vm_fd,
             sev: self.sev,
+            observer: self.observer,
         };
 
         Ok(next)
     }
 }
 
-impl<U: AsRawFd, V: AsRawFd> Launcher<Measured, U, V> {
+/// The most bytes a single LAUNCH_UPDATE_DATA command encrypts, rounded
+/// down to a whole page.
+const MAX_UPDATE_LEN: u64 = u32::MAX as u64 & !0xfff;
+
+/// Merge consecutive `regions` which are adjacent in the host's address
+/// space into `(address, length)` spans, preserving their order. Spans are
+/// split so that none is longer than [MAX_UPDATE_LEN].
+fn coalesce(regions: &[&[u8]]) -> Vec<(u64, u64)> {
+    let mut merged: Vec<(u64, u64)> = vec![];
+
+    for region in regions.iter().filter(|region| !region.is_empty()) {
+        let (addr, len) = (region.as_ptr() as u64, region.len() as u64);
+
+        match merged.last_mut() {
+            Some(last) if last.0 + last.1 == addr => last.1 += len,
+            _ => merged.push((addr, len)),
+        }
+    }
+
+    let mut spans: Vec<(u64, u64)> = vec![];
+
+    for (mut addr, mut len) in merged {
+        while len > MAX_UPDATE_LEN {
+            spans.push((addr, MAX_UPDATE_LEN));
+            addr += MAX_UPDATE_LEN;
+            len -= MAX_UPDATE_LEN;
+        }
+
+        spans.push((addr, len));
+    }
+
+    spans
+}
+
+fn launch_measure<U: VmHandle, V: SevDevice>(vm_fd: &mut U, sev: &V) -> Result<Measurement> {
+    let mut measurement = MaybeUninit::zeroed();
+    let mut launch_measure = LaunchMeasure::new(&mut measurement);
+    let mut cmd = Command::from_mut(sev, &mut launch_measure);
+    cmd.issue(vm_fd)?;
+
+    Ok(unsafe { measurement.assume_init() })
+}
+
+impl<U: VmHandle, V: SevDevice> Launcher<Measured, U, V> {
     /// Get the measurement that the SEV platform recorded.
     pub fn measurement(&self) -> Measurement {
         self.state.1
     }
 
+    /// Get the measurement together with the launch session it belongs
+    /// to, so that it can only be verified by the matching
+    /// `session::Session`.
+    pub fn bound_measurement(&self) -> BoundMeasurement {
+        BoundMeasurement {
+            measurement: self.state.1,
+            session: self.state.2,
+        }
+    }
+
+    /// Request a new measurement from the SEV firmware, e.g. after the
+    /// previous one was lost or failed to verify. The firmware generates a
+    /// new nonce, so the measurement differs from the previous one.
+    pub fn remeasure(&mut self) -> Result<Measurement> {
+        self.state.1 = launch_measure(&mut self.vm_fd, &self.sev)?;
+        self.observer.notify(LaunchEvent::Measured);
+
+        Ok(self.state.1)
+    }
+
     /// Inject a secret into the guest.
     ///
     /// ## Remarks
@@ -176,18 +384,15 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<Measured, U, V> {
     pub fn inject(&mut self, secret: &Secret, guest: usize) -> Result<()> {
         let launch_secret = LaunchSecret::new(&secret.header, guest, &secret.ciphertext[..]);
         let mut cmd = Command::from(&self.sev, &launch_secret);
-        LAUNCH_SECRET
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
         Ok(())
     }
 
     /// Complete the SEV launch process.
     pub fn finish(mut self) -> Result<Handle> {
         let mut cmd = Command::from(&self.sev, &LaunchFinish);
-        LAUNCH_FINISH
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Finished);
         Ok(self.state.0)
     }
 
@@ -195,30 +400,33 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<Measured, U, V> {
     /// attestation report.
     pub fn finish_attestable(mut self) -> Result<Launcher<Finished, U, V>> {
         let mut cmd = Command::from(&self.sev, &LaunchFinish);
-        LAUNCH_FINISH
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Finished);
 
         let next = Launcher {
-            state: Finished,
+            state: Finished(self.state.0),
             vm_fd: self.vm_fd,
             sev: self.sev,
+            observer: self.observer,
         };
 
         Ok(next)
     }
 }
 
-impl<U: AsRawFd, V: AsRawFd> Launcher<Finished, U, V> {
+impl<U: VmHandle, V: SevDevice> Launcher<Finished, U, V> {
+    /// The firmware's handle of the launched guest.
+    pub fn handle(&self) -> Handle {
+        self.state.0
+    }
+
     /// Get the attestation report of the VM.
     pub fn report(&mut self, mnonce: [u8; 16]) -> Result<Vec<u8>> {
         let mut first = LaunchAttestation::default();
         let mut cmd = Command::from_mut(&self.sev, &mut first);
         let mut len = 0;
 
-        let e = LAUNCH_ATTESTATION
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e));
+        let e = cmd.issue(&mut self.vm_fd);
         if let Err(err) = e {
             if let Indeterminate::Known(InvalidLen) = err {
                 len = first.len;
@@ -231,149 +439,1107 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<Finished, U, V> {
         let mut second = LaunchAttestation::new(mnonce, &mut bytes);
         cmd = Command::from_mut(&self.sev, &mut second);
 
-        LAUNCH_ATTESTATION
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
 
         Ok(bytes)
     }
 }
 
-bitflags! {
-    /// Configurable SEV Policy options.
-    #[derive(Default, Deserialize, Serialize)]
-    pub struct PolicyFlags: u16 {
-        /// When set, debugging the guest is forbidden.
-        const NO_DEBUG        = 0b00000001u16.to_le();
+/// Sender type-state that indicates an in-progress outgoing migration.
+pub struct Sending(Handle);
 
-        /// When set, sharing keys with other guests is prohibited.
-        const NO_KEY_SHARING  = 0b00000010u16.to_le();
+/// Receiver type-state that indicates an in-progress incoming migration.
+pub struct Receiving(Handle);
 
-        /// When set, SEV-ES protections are required.
-        const ENCRYPTED_STATE = 0b00000100u16.to_le();
+/// Facilitates the correct execution of the SEV send (outgoing migration)
+/// process for a running guest.
+///
+/// On Linux the send commands are issued through the KVM VM file descriptor,
+/// in the same manner as the launch commands.
+pub struct Sender<T, U: VmHandle, V: SevDevice> {
+    state: T,
+    vm_fd: U,
+    sev: V,
+}
 
-        /// When set, the guest may not be sent to another platform.
-        const NO_SEND         = 0b00001000u16.to_le();
+impl<T, U: VmHandle, V: SevDevice> Sender<T, U, V> {
+    /// Give access to the vm fd.
+    pub fn as_mut_vmfd(&mut self) -> &mut U {
+        &mut self.vm_fd
+    }
 
-        /// When set, the guest may not be transmitted to a platform
-        /// that is outside of the domain.
-        const DOMAIN          = 0b00010000u16.to_le();
+    /// Query the firmware's view of the guest.
+    pub fn status(&mut self) -> Result<GuestStatus> {
+        guest_status(&mut self.vm_fd, &self.sev)
+    }
+}
 
-        /// When set, the guest may not be transmitted to another
-        /// platform that is not SEV-capable.
-        const SEV             = 0b00100000u16.to_le();
+impl<U: VmHandle, V: SevDevice> Sender<New, U, V> {
+    /// Prepare to send the guest running in `kvm` to another platform.
+    pub fn new(kvm: U, sev: V) -> Self {
+        Sender {
+            vm_fd: kvm,
+            sev,
+            state: New,
+        }
+    }
+
+    /// Create an outgoing guest context. The returned [Session] wraps the
+    /// transport keys for the target platform and must be delivered to it
+    /// alongside the origin's PDH (see [Outgoing::incoming]).
+    ///
+    /// The send is refused if `outgoing` does not carry the policy the
+    /// guest runs with, or if that policy forbids sending the guest.
+    pub fn start(mut self, outgoing: &Outgoing) -> Result<(Sender<Sending, U, V>, Session)> {
+        let status: GuestStatus = self.status()?;
+        check_send(&status.policy, &outgoing.policy).map_err(invalid_input)?;
+
+        let mut session = MaybeUninit::zeroed();
+        let mut send_start = SendStart::new(
+            &outgoing.policy,
+            &outgoing.pdh,
+            &outgoing.plat_certs,
+            &outgoing.amd_certs,
+            &mut session,
+        );
+        let mut cmd = Command::from_mut(&self.sev, &mut send_start);
+        cmd.issue(&mut self.vm_fd)?;
+
+        let next = Sender {
+            state: Sending(Handle::new(status.handle)),
+            vm_fd: self.vm_fd,
+            sev: self.sev,
+        };
+
+        Ok((next, unsafe { session.assume_init() }))
     }
 }
 
-/// Describes a policy that the AMD Secure Processor will
-/// enforce.
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
-pub struct Policy {
-    /// The various policy optons are encoded as bit flags.
-    pub flags: PolicyFlags,
+impl<U: VmHandle, V: SevDevice> Sender<Sending, U, V> {
+    /// Encrypt a region of guest memory with the transport keys.
+    ///
+    /// The kernel encrypts at most one page per command: `guest` must not
+    /// cross a page boundary, so larger regions are sent page by page.
+    pub fn update_data(&mut self, guest: &[u8]) -> Result<Packet> {
+        let mut header = MaybeUninit::zeroed();
+        let mut data = vec![0u8; guest.len()];
+        let send_update_data = SendUpdateData::new(&mut header, guest, &mut data);
+        let mut cmd = Command::from(&self.sev, &send_update_data);
+        cmd.issue(&mut self.vm_fd)?;
+
+        Ok(Packet {
+            header: unsafe { header.assume_init() },
+            data,
+        })
+    }
 
-    /// The desired minimum platform firmware version.
-    pub minfw: Version,
+    /// Complete the SEV send process, returning the handle of the sent
+    /// guest, e.g. to decommission it once the target has received it.
+    pub fn finish(mut self) -> Result<Handle> {
+        let mut cmd = Command::from(&self.sev, &SendFinish);
+        cmd.issue(&mut self.vm_fd)?;
+        Ok(self.state.0)
+    }
+
+    /// Abort the SEV send process. The guest keeps running on this
+    /// platform and a new send may be started.
+    pub fn cancel(mut self) -> Result<Sender<New, U, V>> {
+        let mut cmd = Command::from(&self.sev, &SendCancel);
+        cmd.issue(&mut self.vm_fd)?;
+
+        let next = Sender {
+            state: New,
+            vm_fd: self.vm_fd,
+            sev: self.sev,
+        };
+
+        Ok(next)
+    }
 }
 
-/// Convert a policy represented as a u32 to a Policy struct.
-impl From<u32> for Policy {
-    fn from(p: u32) -> Self {
-        let flags = p as u16;
-        let flags = PolicyFlags::from_bits_truncate(flags);
+/// Facilitates the correct execution of the SEV receive (incoming
+/// migration) process.
+///
+/// On Linux the receive commands are issued through the KVM VM file
+/// descriptor, in the same manner as the launch commands.
+pub struct Receiver<T, U: VmHandle, V: SevDevice> {
+    state: T,
+    vm_fd: U,
+    sev: V,
+    es: bool,
+}
 
-        let p = p >> 16;
-        let p = p as u16;
-        let minfw = Version::from(p);
+impl<T, U: VmHandle, V: SevDevice> Receiver<T, U, V> {
+    /// Give access to the vm fd to create vCPUs or such.
+    pub fn as_mut_vmfd(&mut self) -> &mut U {
+        &mut self.vm_fd
+    }
 
-        Self { flags, minfw }
+    /// Query the firmware's view of the guest.
+    pub fn status(&mut self) -> Result<GuestStatus> {
+        guest_status(&mut self.vm_fd, &self.sev)
     }
 }
 
-/// A secure channel between the tenant and the AMD Secure
-/// Processor.
-#[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Session {
-    /// Used for deriving a shared secret between the tenant
-    /// and the AMD SP.
-    pub nonce: [u8; 16],
+impl<U: VmHandle, V: SevDevice> Receiver<New, U, V> {
+    /// Begin the SEV receive process.
+    pub fn new(kvm: U, sev: V) -> Result<Self> {
+        let mut receiver = Receiver {
+            vm_fd: kvm,
+            sev,
+            state: New,
+            es: false,
+        };
 
-    /// The TEK and TIK concatenated together and wrapped by
-    /// the Key Encryption Key and the Key Integrity Key.
-    /// (KIK (KEK (TEK|TIK))).
-    pub wrap_tk: [u8; 32],
+        let mut cmd = Command::from(&receiver.sev, &Init);
+        cmd.issue(&mut receiver.vm_fd)?;
 
-    /// The initialization vector.
-    pub wrap_iv: [u8; 16],
+        Ok(receiver)
+    }
 
-    /// Integrity protection for the wrapped keys (see the
-    /// `wrap_tk` field of this struct).
-    pub wrap_mac: [u8; 32],
+    /// Begin the SEV-ES receive process.
+    pub fn new_es(kvm: U, sev: V) -> Result<Self> {
+        let mut receiver = Receiver {
+            vm_fd: kvm,
+            sev,
+            state: New,
+            es: true,
+        };
 
-    /// The integrity-protected SEV policy.
-    pub policy_mac: [u8; 32],
-}
+        let mut cmd = Command::from(&receiver.sev, &EsInit);
+        cmd.issue(&mut receiver.vm_fd)?;
 
-/// Used to establish a secure session with the AMD SP.
-#[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
-pub struct Start {
-    /// The tenant's policy for this SEV guest.
-    pub policy: Policy,
+        Ok(receiver)
+    }
 
-    /// The tenant's Diffie-Hellman certificate.
-    pub cert: certs::sev::sev::Certificate,
+    /// Create an incoming guest context.
+    ///
+    /// The receive is refused if the guest's policy forbids sending it, or
+    /// if it is an SEV-ES guest and the receiver was not created with
+    /// [new_es](Self::new_es) (or vice versa).
+    pub fn start(mut self, incoming: Incoming) -> Result<Receiver<Receiving, U, V>> {
+        check_receive(&incoming.policy, self.es).map_err(invalid_input)?;
+
+        let mut receive_start =
+            ReceiveStart::new(&incoming.policy, &incoming.cert, &incoming.session);
+        let mut cmd = Command::from_mut(&self.sev, &mut receive_start);
+        cmd.issue(&mut self.vm_fd)?;
+
+        let next = Receiver {
+            state: Receiving(receive_start.into()),
+            vm_fd: self.vm_fd,
+            sev: self.sev,
+            es: self.es,
+        };
 
-    /// A secure channel with the AMD SP.
-    pub session: Session,
+        Ok(next)
+    }
 }
 
-impl codicon::Decoder<()> for Start {
-    type Error = std::io::Error;
+impl<U: VmHandle, V: SevDevice> Receiver<Receiving, U, V> {
+    /// Register the encrypted memory region to a virtual machine.
+    /// Corresponds to the `KVM_MEMORY_ENCRYPT_REG_REGION` ioctl.
+    pub fn register_kvm_enc_region(&mut self, data: &[u8]) -> Result<()> {
+        KvmEncRegion::new(data).register_with(&mut self.vm_fd)?;
+        Ok(())
+    }
 
-    fn decode(mut reader: impl Read, _: ()) -> std::io::Result<Self> {
-        reader.load()
+    /// Decrypt a packet produced by [Sender::update_data] into the
+    /// incoming guest's memory. `guest` must be the same size as the
+    /// packet's data.
+    pub fn update_data(&mut self, packet: &Packet, guest: &mut [u8]) -> Result<()> {
+        if guest.len() != packet.data.len() {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        }
+
+        let receive_update_data = ReceiveUpdateData::new(&packet.header, guest, &packet.data);
+        let mut cmd = Command::from(&self.sev, &receive_update_data);
+        cmd.issue(&mut self.vm_fd)?;
+
+        Ok(())
     }
-}
 
-impl codicon::Encoder<()> for Start {
-    type Error = std::io::Error;
+    /// Import the pages of `snapshot` into `memory`, the incoming guest's
+    /// memory, placing each page at its offset. `memory` is registered as
+    /// an encrypted region first.
+    ///
+    /// Nothing is imported if a page does not fit into `memory`, or if
+    /// pages overlap.
+    pub fn restore(&mut self, snapshot: &Snapshot, memory: &mut [u8]) -> Result<()> {
+        snapshot.check_placement(memory.len() as u64)?;
 
-    fn encode(&self, mut writer: impl Write, _: ()) -> std::io::Result<()> {
-        writer.save(self)
+        self.register_kvm_enc_region(memory)?;
+
+        for page in snapshot.pages.iter() {
+            let start: usize = page.offset as usize;
+            let end: usize = start + page.packet.data.len();
+
+            self.update_data(&page.packet, &mut memory[start..end])?;
+        }
+
+        Ok(())
+    }
+
+    /// Complete the SEV receive process.
+    pub fn finish(mut self) -> Result<Handle> {
+        let mut cmd = Command::from(&self.sev, &ReceiveFinish);
+        cmd.issue(&mut self.vm_fd)?;
+        Ok(self.state.0)
     }
 }
 
-bitflags! {
-    /// Additional descriptions of the secret header packet.
-    #[derive(Default, Deserialize, Serialize)]
-    pub struct HeaderFlags: u32 {
-        /// If set, the contents of the packet are compressed and
-        /// the AMD SP must decompress them.
-        const COMPRESSED = 0b00000001u32.to_le();
+/// Check that a guest running with `guest` policy may be sent with the
+/// `outgoing` policy.
+fn check_send(guest: &Policy, outgoing: &Policy) -> std::result::Result<(), MigrationPolicyError> {
+    if guest != outgoing {
+        return Err(MigrationPolicyError::PolicyMismatch);
+    }
+
+    if guest.flags.contains(PolicyFlags::NO_SEND) {
+        return Err(MigrationPolicyError::SendForbidden);
     }
+
+    Ok(())
 }
 
-/// The header for a data packet that contains secret information
-/// to be injected into the guest.
-#[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
-pub struct Header {
-    /// Describes the secret packet (for example: if it is
-    /// compressed).
-    pub flags: HeaderFlags,
+/// Check that a guest with `policy` may be received by a receiver which was
+/// (`es`) or was not initialized for SEV-ES.
+fn check_receive(policy: &Policy, es: bool) -> std::result::Result<(), MigrationPolicyError> {
+    if policy.flags.contains(PolicyFlags::NO_SEND) {
+        return Err(MigrationPolicyError::SendForbidden);
+    }
 
-    /// The initialization vector.
-    pub iv: [u8; 16],
+    if policy.flags.contains(PolicyFlags::ENCRYPTED_STATE) != es {
+        return Err(MigrationPolicyError::EncryptedStateMismatch);
+    }
 
-    /// Integrity protection MAC.
-    pub mac: [u8; 32],
+    Ok(())
 }
 
-/// A packet containing secret information to be injected
-/// into the guest.
+/// Guest context type-state that indicates the guest's key is installed
+/// in its ASID, so that the guest can run.
+pub struct Active(u32);
+
+/// Guest context type-state that indicates the guest's key is not
+/// installed in any ASID.
+pub struct Inactive;
+
+/// A guest lifecycle command of the SEV firmware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LifecycleCommand {
+    /// Install the guest's key in `asid`.
+    Activate {
+        /// The guest's handle.
+        handle: Handle,
+
+        /// The ASID to run the guest in.
+        asid: u32,
+    },
+
+    /// Remove the guest's key from its ASID.
+    Deactivate {
+        /// The guest's handle.
+        handle: Handle,
+    },
+
+    /// Destroy the guest context.
+    Decommission {
+        /// The guest's handle.
+        handle: Handle,
+    },
+}
+
+impl LifecycleCommand {
+    /// The command ID defined by the SEV API.
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::Decommission { .. } => 0x20,
+            Self::Activate { .. } => 0x21,
+            Self::Deactivate { .. } => 0x22,
+        }
+    }
+
+    /// Encode the command buffer in the layout the SEV API defines.
+    pub fn command_buffer(&self) -> Vec<u8> {
+        match self {
+            Self::Activate { handle, asid } => {
+                let mut buffer: Vec<u8> = u32::from(*handle).to_le_bytes().to_vec();
+                buffer.extend_from_slice(&asid.to_le_bytes());
+                buffer
+            }
+            Self::Deactivate { handle } | Self::Decommission { handle } => {
+                u32::from(*handle).to_le_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/// Issues guest lifecycle commands to the AMD Secure Processor.
+pub trait LifecycleIssuer {
+    /// Issue `command`, returning once the firmware completed it.
+    fn issue(&mut self, command: &LifecycleCommand) -> Result<()>;
+}
+
+/// The lifecycle of an SEV guest context after its launch, as a type-state
+/// machine.
+///
+/// On Linux, KVM activates the guest during LAUNCH_START and deactivates
+/// and decommissions it when the VM is destroyed; userspace cannot issue
+/// these commands. A `GuestContext` tracks the lifecycle for hypervisors
+/// which drive the AMD Secure Processor directly, issuing each
+/// [LifecycleCommand] through their [LifecycleIssuer].
+///
+/// # Example:
+/// ```ignore
+/// let guest = GuestContext::active(launcher.handle(), asid);
+///
+/// guest.deactivate(&mut psp)?.decommission(&mut psp)?;
+/// ```
+pub struct GuestContext<T> {
+    handle: Handle,
+    state: T,
+}
+
+impl<T> GuestContext<T> {
+    /// The firmware's handle of the guest.
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+}
+
+impl GuestContext<Active> {
+    /// Track a guest which runs in `asid`, e.g. after its launch.
+    pub fn active(handle: Handle, asid: u32) -> Self {
+        Self {
+            handle,
+            state: Active(asid),
+        }
+    }
+
+    /// The ASID the guest runs in.
+    pub fn asid(&self) -> u32 {
+        self.state.0
+    }
+
+    /// Remove the guest's key from its ASID.
+    pub fn deactivate(self, issuer: &mut impl LifecycleIssuer) -> Result<GuestContext<Inactive>> {
+        issuer.issue(&LifecycleCommand::Deactivate {
+            handle: self.handle,
+        })?;
+
+        Ok(GuestContext {
+            handle: self.handle,
+            state: Inactive,
+        })
+    }
+}
+
+impl GuestContext<Inactive> {
+    /// Track a guest whose key is not installed in any ASID.
+    pub fn inactive(handle: Handle) -> Self {
+        Self {
+            handle,
+            state: Inactive,
+        }
+    }
+
+    /// Install the guest's key in `asid`.
+    pub fn activate(
+        self,
+        issuer: &mut impl LifecycleIssuer,
+        asid: u32,
+    ) -> Result<GuestContext<Active>> {
+        issuer.issue(&LifecycleCommand::Activate {
+            handle: self.handle,
+            asid,
+        })?;
+
+        Ok(GuestContext::active(self.handle, asid))
+    }
+
+    /// Destroy the guest context. The handle is invalid afterwards.
+    pub fn decommission(self, issuer: &mut impl LifecycleIssuer) -> Result<()> {
+        issuer.issue(&LifecycleCommand::Decommission {
+            handle: self.handle,
+        })
+    }
+}
+
+/// Reads and writes the memory of an SEV guest whose policy allows
+/// debugging (i.e. does not set NO_DEBUG), for troubleshooting during
+/// development.
+///
+/// The guest's policy is queried from the firmware before every command,
+/// so a `Debugger` fails rather than issuing a command the firmware would
+/// reject.
+pub struct Debugger<U: VmHandle, V: SevDevice> {
+    vm_fd: U,
+    sev: V,
+}
+
+impl<U: VmHandle, V: SevDevice> Debugger<U, V> {
+    /// Debug the guest running in `kvm`.
+    ///
+    /// # Safety
+    ///
+    /// Decrypted guest memory exposes the guest's secrets to the host, and
+    /// encrypting into guest memory overwrites it behind the guest's back.
+    /// This method must only be used to troubleshoot guests in development.
+    pub unsafe fn new(kvm: U, sev: V) -> Result<Self> {
+        let mut debugger = Self { vm_fd: kvm, sev };
+        debugger.check_policy()?;
+
+        Ok(debugger)
+    }
+
+    /// Decrypt the guest memory mapped at `guest` into `plain`, which must
+    /// have the same length.
+    pub fn decrypt(&mut self, guest: &[u8], plain: &mut [u8]) -> Result<()> {
+        check_lengths(guest, plain)?;
+        self.check_policy()?;
+        self.dbg_decrypt(guest, plain)
+    }
+
+    /// Encrypt `plain` into the guest memory mapped at `guest`, which must
+    /// have the same length.
+    pub fn encrypt(&mut self, plain: &[u8], guest: &mut [u8]) -> Result<()> {
+        check_lengths(plain, guest)?;
+        self.check_policy()?;
+        self.dbg_encrypt(plain, guest)
+    }
+
+    /// Decrypt the VMSA page mapped at `vmsa` into the typed save area of
+    /// an SEV-ES guest, e.g. to inspect the registers of a crashed guest.
+    ///
+    /// The firmware only decrypts guest memory mapped into the VMM, so
+    /// `vmsa` must be a page of guest memory holding a save area encrypted
+    /// with the guest's key. KVM keeps the VMSA each vCPU runs with in
+    /// kernel memory, which is not mapped into the VMM and cannot be passed
+    /// here. The guest policy must require SEV-ES (ENCRYPTED_STATE).
+    pub fn export_vmsa(&mut self, vmsa: &[u8]) -> Result<crate::vmsa::Vmsa> {
+        use codicon::Decoder;
+
+        let plain = self.decrypt_vmsa(vmsa)?;
+        crate::vmsa::Vmsa::decode(&plain[..], ())
+    }
+
+    /// Encrypt `state` into the VMSA page mapped at `vmsa`, e.g. to resume
+    /// an SEV-ES guest with patched registers. The rest of the page, beyond
+    /// the fields of the save area, is kept as it is.
+    ///
+    /// As for [export_vmsa](Self::export_vmsa), `vmsa` must be a page of
+    /// guest memory mapped into the VMM, not the VMSA KVM keeps for a vCPU.
+    pub fn import_vmsa(&mut self, state: &crate::vmsa::Vmsa, vmsa: &mut [u8]) -> Result<()> {
+        use codicon::Encoder;
+
+        let mut plain = self.decrypt_vmsa(vmsa)?;
+        state.encode(&mut &mut plain[..], ())?;
+
+        self.dbg_encrypt(&plain, vmsa)
+    }
+
+    /// Stop debugging, returning the file descriptors.
+    pub fn into_inner(self) -> (U, V) {
+        (self.vm_fd, self.sev)
+    }
+
+    fn dbg_decrypt(&mut self, guest: &[u8], plain: &mut [u8]) -> Result<()> {
+        let dbg_decrypt = DbgDecrypt::new(guest, plain);
+        let mut cmd = Command::from(&self.sev, &dbg_decrypt);
+        cmd.issue(&mut self.vm_fd)?;
+
+        Ok(())
+    }
+
+    fn dbg_encrypt(&mut self, plain: &[u8], guest: &mut [u8]) -> Result<()> {
+        let dbg_encrypt = DbgEncrypt::new(plain, guest);
+        let mut cmd = Command::from(&self.sev, &dbg_encrypt);
+        cmd.issue(&mut self.vm_fd)?;
+
+        Ok(())
+    }
+
+    fn decrypt_vmsa(&mut self, vmsa: &[u8]) -> Result<Vec<u8>> {
+        if vmsa.len() != VMSA_PAGE_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the VMSA must be a whole page",
+            ));
+        }
+
+        if !self.check_policy()?.contains(PolicyFlags::ENCRYPTED_STATE) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the guest policy does not require SEV-ES, so it has no VMSA",
+            ));
+        }
+
+        let mut plain = vec![0u8; VMSA_PAGE_SIZE];
+        self.dbg_decrypt(vmsa, &mut plain)?;
+
+        Ok(plain)
+    }
+
+    /// Check that the guest policy allows debugging, returning its flags.
+    fn check_policy(&mut self) -> Result<PolicyFlags> {
+        let status: GuestStatus = guest_status(&mut self.vm_fd, &self.sev)?;
+
+        if status.policy.flags.contains(PolicyFlags::NO_DEBUG) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "the guest policy forbids debugging",
+            ));
+        }
+
+        Ok(status.policy.flags)
+    }
+}
+
+/// The size (in bytes) of a VMSA page.
+const VMSA_PAGE_SIZE: usize = 4096;
+
+fn check_lengths(src: &[u8], dst: &[u8]) -> Result<()> {
+    if src.len() != dst.len() || src.is_empty() || u32::try_from(src.len()).is_err() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "debug buffers must be non-empty and of equal length",
+        ));
+    }
+
+    Ok(())
+}
+
+fn invalid_input(error: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, error)
+}
+
+bitflags! {
+    /// Configurable SEV Policy options.
+    #[derive(Default, Deserialize, Serialize)]
+    pub struct PolicyFlags: u16 {
+        /// When set, debugging the guest is forbidden.
+        const NO_DEBUG        = 0b00000001u16.to_le();
+
+        /// When set, sharing keys with other guests is prohibited.
+        const NO_KEY_SHARING  = 0b00000010u16.to_le();
+
+        /// When set, SEV-ES protections are required.
+        const ENCRYPTED_STATE = 0b00000100u16.to_le();
+
+        /// When set, the guest may not be sent to another platform.
+        const NO_SEND         = 0b00001000u16.to_le();
+
+        /// When set, the guest may not be transmitted to a platform
+        /// that is outside of the domain.
+        const DOMAIN          = 0b00010000u16.to_le();
+
+        /// When set, the guest may not be transmitted to another
+        /// platform that is not SEV-capable.
+        const SEV             = 0b00100000u16.to_le();
+    }
+}
+
+/// Describes a policy that the AMD Secure Processor will
+/// enforce.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Policy {
+    /// The various policy optons are encoded as bit flags.
+    pub flags: PolicyFlags,
+
+    /// The desired minimum platform firmware version.
+    pub minfw: Version,
+}
+
+/// Convert a policy represented as a u32 to a Policy struct.
+impl From<u32> for Policy {
+    fn from(p: u32) -> Self {
+        let flags = p as u16;
+        let flags = PolicyFlags::from_bits_truncate(flags);
+
+        let p = p >> 16;
+        let p = p as u16;
+        let minfw = Version::from(p);
+
+        Self { flags, minfw }
+    }
+}
+
+impl Policy {
+    /// The policy as the AMD SP measures it: the flags as a little-endian
+    /// 16-bit word, followed by the major and minor minimum firmware version.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        // The flag constants hold their little-endian representation.
+        let [lo, hi] = u16::from_le(self.flags.bits()).to_le_bytes();
+
+        [lo, hi, self.minfw.major, self.minfw.minor]
+    }
+
+    /// Decode a policy laid out as [to_bytes](Self::to_bytes) lays it out,
+    /// dropping unknown flags.
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        let flags = u16::from_le_bytes([bytes[0], bytes[1]]);
+
+        Self {
+            flags: PolicyFlags::from_bits_truncate(flags.to_le()),
+            minfw: Version {
+                major: bytes[2],
+                minor: bytes[3],
+            },
+        }
+    }
+
+    /// Start building a policy which allows everything.
+    pub fn builder() -> PolicyBuilder {
+        PolicyBuilder::default()
+    }
+
+    /// Describe what the policy allows and forbids, e.g. for audit logs.
+    pub fn explain(&self) -> String {
+        let flags: PolicyFlags = self.flags;
+        let mut clauses: Vec<String> = vec![];
+
+        clauses.push(match flags.contains(PolicyFlags::NO_DEBUG) {
+            true => "debugging is forbidden".to_string(),
+            false => "debugging is allowed".to_string(),
+        });
+
+        clauses.push(match flags.contains(PolicyFlags::NO_KEY_SHARING) {
+            true => "key sharing is forbidden".to_string(),
+            false => "key sharing is allowed".to_string(),
+        });
+
+        clauses.push(match flags.contains(PolicyFlags::ENCRYPTED_STATE) {
+            true => "SEV-ES is required".to_string(),
+            false => "SEV-ES is not required".to_string(),
+        });
+
+        clauses.push(
+            match (
+                flags.contains(PolicyFlags::NO_SEND),
+                flags.contains(PolicyFlags::DOMAIN),
+                flags.contains(PolicyFlags::SEV),
+            ) {
+                (true, _, _) => "sending is forbidden".to_string(),
+                (false, false, false) => "sending is allowed".to_string(),
+                (false, true, false) => "sending is allowed within the domain".to_string(),
+                (false, false, true) => "sending is allowed to SEV-capable platforms".to_string(),
+                (false, true, true) => {
+                    "sending is allowed to SEV-capable platforms within the domain".to_string()
+                }
+            },
+        );
+
+        clauses.push(format!("the minimum firmware version is {}", self.minfw));
+
+        clauses.join("; ")
+    }
+}
+
+/// Builds a [Policy], checking that its flags are consistent.
+///
+/// # Example:
+/// ```ignore
+/// let policy: Policy = Policy::builder()
+///     .require_es()
+///     .forbid_debug()
+///     .min_firmware(0, 17)
+///     .build()
+///     .unwrap();
+///
+/// log::info!("launching with policy: {}", policy.explain());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PolicyBuilder {
+    policy: Policy,
+}
+
+impl PolicyBuilder {
+    /// Require SEV-ES protections.
+    pub fn require_es(mut self) -> Self {
+        self.policy.flags |= PolicyFlags::ENCRYPTED_STATE;
+        self
+    }
+
+    /// Forbid debugging the guest.
+    pub fn forbid_debug(mut self) -> Self {
+        self.policy.flags |= PolicyFlags::NO_DEBUG;
+        self
+    }
+
+    /// Forbid sharing keys with other guests.
+    pub fn forbid_key_sharing(mut self) -> Self {
+        self.policy.flags |= PolicyFlags::NO_KEY_SHARING;
+        self
+    }
+
+    /// Forbid sending the guest to another platform.
+    pub fn forbid_send(mut self) -> Self {
+        self.policy.flags |= PolicyFlags::NO_SEND;
+        self
+    }
+
+    /// Only allow sending the guest to platforms within the domain.
+    pub fn send_within_domain(mut self) -> Self {
+        self.policy.flags |= PolicyFlags::DOMAIN;
+        self
+    }
+
+    /// Only allow sending the guest to SEV-capable platforms.
+    pub fn send_to_sev_only(mut self) -> Self {
+        self.policy.flags |= PolicyFlags::SEV;
+        self
+    }
+
+    /// Require at least firmware version `major`.`minor`.
+    pub fn min_firmware(mut self, major: u8, minor: u8) -> Self {
+        self.policy.minfw = Version { major, minor };
+        self
+    }
+
+    /// Check that the flags are consistent.
+    pub fn build(self) -> std::result::Result<Policy, PolicyError> {
+        let flags: PolicyFlags = self.policy.flags;
+
+        if flags.contains(PolicyFlags::NO_SEND)
+            && flags.intersects(PolicyFlags::DOMAIN | PolicyFlags::SEV)
+        {
+            return Err(PolicyError::SendRestrictedButForbidden);
+        }
+
+        Ok(self.policy)
+    }
+
+    /// Check that the flags are consistent and, if SEV-ES is required, that
+    /// the platform described by `requires` can launch the guest.
+    pub fn build_for(self, requires: &EsRequirements) -> std::result::Result<Policy, PolicyError> {
+        let policy: Policy = self.build()?;
+
+        if policy.flags.contains(PolicyFlags::ENCRYPTED_STATE) {
+            requires.check(&policy)?;
+        }
+
+        Ok(policy)
+    }
+}
+
+/// What the platform requires of SEV-ES guests.
+///
+/// Check a guest's [Policy] against it before issuing INIT or
+/// LAUNCH_START, rather than interpreting the firmware's failure.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EsRequirements {
+    /// The platform firmware supports SEV-ES.
+    pub supported: bool,
+
+    /// The ASIDs usable by SEV-ES guests. ASIDs from the end of this range
+    /// upwards are reserved for SEV guests without ES.
+    pub asids: std::ops::Range<u32>,
+
+    /// Simultaneous multithreading is active on the host. SEV-ES does not
+    /// isolate a guest from its sibling hyperthreads, so tenants may want
+    /// to reject such hosts.
+    pub smt_active: bool,
+
+    /// The platform firmware version. A policy may not require a newer
+    /// minimum firmware version.
+    pub firmware: Version,
+}
+
+impl EsRequirements {
+    /// Derive the requirements from the probed platform capabilities.
+    #[cfg(target_os = "linux")]
+    pub fn from_capabilities(caps: &Capabilities) -> Self {
+        let (supported, firmware) = match &caps.sev {
+            Some(status) => (
+                status.flags.contains(PlatformStatusFlags::ENCRYPTED_STATE),
+                status.build.version,
+            ),
+            None => (false, Version::default()),
+        };
+
+        Self {
+            supported: supported && caps.processor.sev_es,
+            asids: 1..caps.processor.min_sev_asid.max(1),
+            smt_active: crate::launch::smt_active(),
+            firmware,
+        }
+    }
+
+    /// Check that a guest with `policy` can be launched as an SEV-ES guest.
+    pub fn check(&self, policy: &Policy) -> std::result::Result<(), EsPolicyError> {
+        if !self.supported {
+            return Err(EsPolicyError::Unsupported);
+        }
+
+        if self.asids.is_empty() {
+            return Err(EsPolicyError::NoAsids);
+        }
+
+        if !policy.flags.contains(PolicyFlags::ENCRYPTED_STATE) {
+            return Err(EsPolicyError::EncryptedStateRequired);
+        }
+
+        if policy.minfw > self.firmware {
+            return Err(EsPolicyError::FirmwareTooOld(policy.minfw));
+        }
+
+        Ok(())
+    }
+}
+
+/// A secure channel between the tenant and the AMD Secure
+/// Processor.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Used for deriving a shared secret between the tenant
+    /// and the AMD SP.
+    pub nonce: [u8; 16],
+
+    /// The TEK and TIK concatenated together and wrapped by
+    /// the Key Encryption Key and the Key Integrity Key.
+    /// (KIK (KEK (TEK|TIK))).
+    pub wrap_tk: [u8; 32],
+
+    /// The initialization vector.
+    pub wrap_iv: [u8; 16],
+
+    /// Integrity protection for the wrapped keys (see the
+    /// `wrap_tk` field of this struct).
+    pub wrap_mac: [u8; 32],
+
+    /// The integrity-protected SEV policy.
+    pub policy_mac: [u8; 32],
+}
+
+impl crate::snapshot::Versioned for Session {
+    const KIND: [u8; 4] = *b"SESS";
+    const VERSION: u16 = 1;
+}
+
+/// Used to establish a secure session with the AMD SP.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Start {
+    /// The tenant's policy for this SEV guest.
+    pub policy: Policy,
+
+    /// The tenant's Diffie-Hellman certificate.
+    pub cert: certs::sev::sev::Certificate,
+
+    /// A secure channel with the AMD SP.
+    pub session: Session,
+}
+
+impl crate::snapshot::Versioned for Start {
+    const KIND: [u8; 4] = *b"STRT";
+    const VERSION: u16 = 1;
+}
+
+impl codicon::Decoder<()> for Start {
+    type Error = std::io::Error;
+
+    fn decode(mut reader: impl Read, _: ()) -> std::io::Result<Self> {
+        reader.load()
+    }
+}
+
+impl codicon::Encoder<()> for Start {
+    type Error = std::io::Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> std::io::Result<()> {
+        writer.save(self)
+    }
+}
+
+impl Session {
+    /// Parse a session blob, e.g. generated out-of-band, whose fields are
+    /// laid out as the AMD SP expects them and whose MACs are not zero.
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<Self, SessionBlobError> {
+        if bytes.len() != SESSION_BLOB_LEN {
+            return Err(SessionBlobError::InvalidLength {
+                blob: "session",
+                expected: SESSION_BLOB_LEN,
+                actual: bytes.len(),
+            });
+        }
+
+        let (nonce, rest) = bytes.split_at(16);
+        let (wrap_tk, rest) = rest.split_at(32);
+        let (wrap_iv, rest) = rest.split_at(16);
+        let (wrap_mac, policy_mac) = rest.split_at(32);
+
+        for (name, mac) in [("wrap MAC", wrap_mac), ("policy MAC", policy_mac)] {
+            if mac.iter().all(|b| *b == 0) {
+                return Err(SessionBlobError::ZeroMac(name));
+            }
+        }
+
+        // The slices are split at the sizes of the fields.
+        Ok(Self {
+            nonce: nonce.try_into().unwrap(),
+            wrap_tk: wrap_tk.try_into().unwrap(),
+            wrap_iv: wrap_iv.try_into().unwrap(),
+            wrap_mac: wrap_mac.try_into().unwrap(),
+            policy_mac: policy_mac.try_into().unwrap(),
+        })
+    }
+
+    /// The session blob, as [Session::from_bytes] parses it.
+    pub fn to_bytes(&self) -> [u8; SESSION_BLOB_LEN] {
+        let mut bytes = [0u8; SESSION_BLOB_LEN];
+
+        bytes[..16].copy_from_slice(&self.nonce);
+        bytes[16..48].copy_from_slice(&self.wrap_tk);
+        bytes[48..64].copy_from_slice(&self.wrap_iv);
+        bytes[64..96].copy_from_slice(&self.wrap_mac);
+        bytes[96..].copy_from_slice(&self.policy_mac);
+
+        bytes
+    }
+}
+
+impl Start {
+    /// Start a launch with `policy` from the GODH and session blobs of a
+    /// session provisioned out-of-band.
+    pub fn from_blobs(
+        policy: Policy,
+        godh: &[u8],
+        session: &[u8],
+    ) -> std::result::Result<Self, SessionBlobError> {
+        Ok(SessionBlobs::from_bytes(godh, session)?.start(policy))
+    }
+}
+
+/// The size (in bytes) of a session blob.
+pub const SESSION_BLOB_LEN: usize = std::mem::size_of::<Session>();
+
+/// The size (in bytes) of a GODH blob, an SEV certificate.
+pub const GODH_BLOB_LEN: usize = std::mem::size_of::<certs::sev::sev::Certificate>();
+
+/// The blobs of a launch session provisioned out-of-band
+///
+/// Tools such as `sevctl session` and cloud APIs generate the guest owner's
+/// Diffie-Hellman certificate (GODH) and the session blob without handing
+/// the session keys to the host, which passes the blobs to the AMD SP as
+/// they are. The blobs are checked to have the layout the AMD SP expects
+/// before a [Start] is built from them, as the AMD SP only rejects them
+/// once the launch has begun.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SessionBlobs {
+    /// The guest owner's Diffie-Hellman certificate.
+    pub godh: certs::sev::sev::Certificate,
+
+    /// A secure channel with the AMD SP.
+    pub session: Session,
+}
+
+impl SessionBlobs {
+    /// Parse the raw GODH and session blobs.
+    pub fn from_bytes(godh: &[u8], session: &[u8]) -> std::result::Result<Self, SessionBlobError> {
+        use codicon::Decoder;
+
+        if godh.len() != GODH_BLOB_LEN {
+            return Err(SessionBlobError::InvalidLength {
+                blob: "GODH",
+                expected: GODH_BLOB_LEN,
+                actual: godh.len(),
+            });
+        }
+
+        let cert = certs::sev::sev::Certificate::decode(godh, ParseOptions::default())
+            .map_err(SessionBlobError::InvalidCertificate)?;
+
+        if certs::sev::Usage::try_from(&cert).ok() != Some(certs::sev::Usage::PDH) {
+            return Err(SessionBlobError::NotDiffieHellman);
+        }
+
+        Ok(Self {
+            godh: cert,
+            session: Session::from_bytes(session)?,
+        })
+    }
+
+    /// Parse the GODH and session blobs from base64, as `sevctl session`
+    /// writes them and QEMU's `dh-cert-file` and `session-file` read them.
+    pub fn from_base64(godh: &str, session: &str) -> std::result::Result<Self, SessionBlobError> {
+        let decode = |blob: &'static str, text: &str| {
+            use base64::Engine;
+
+            base64::engine::general_purpose::STANDARD
+                .decode(text.trim())
+                .map_err(|_| SessionBlobError::InvalidBase64(blob))
+        };
+
+        Self::from_bytes(&decode("GODH", godh)?, &decode("session", session)?)
+    }
+
+    /// Read the base64 GODH and session blobs from the files at `godh` and
+    /// `session`, e.g. the `*_godh.b64` and `*_session.b64` files of
+    /// `sevctl session`.
+    pub fn read(
+        godh: impl AsRef<std::path::Path>,
+        session: impl AsRef<std::path::Path>,
+    ) -> std::result::Result<Self, SessionBlobError> {
+        Self::from_base64(
+            &std::fs::read_to_string(godh)?,
+            &std::fs::read_to_string(session)?,
+        )
+    }
+
+    /// Start a launch with `policy`, which must be the policy the session
+    /// blob was generated for: the AMD SP checks it against the policy MAC.
+    pub fn start(self, policy: Policy) -> Start {
+        Start {
+            policy,
+            cert: self.godh,
+            session: self.session,
+        }
+    }
+}
+
+bitflags! {
+    /// Additional descriptions of the secret header packet.
+    #[derive(Default, Deserialize, Serialize)]
+    pub struct HeaderFlags: u32 {
+        /// If set, the contents of the packet are compressed and
+        /// the AMD SP must decompress them.
+        const COMPRESSED = 0b00000001u32.to_le();
+    }
+}
+
+impl HeaderFlags {
+    /// The flags as the little-endian 32-bit word the AMD SP authenticates.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        // The flag constants hold their little-endian representation.
+        u32::from_le(self.bits()).to_le_bytes()
+    }
+
+    /// Decode flags from a little-endian 32-bit word, dropping unknown flags.
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self::from_bits_truncate(u32::from_le_bytes(bytes).to_le())
+    }
+}
+
+/// The header for a data packet that contains secret information
+/// to be injected into the guest.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Header {
+    /// Describes the secret packet (for example: if it is
+    /// compressed).
+    pub flags: HeaderFlags,
+
+    /// The initialization vector.
+    pub iv: [u8; 16],
+
+    /// Integrity protection MAC.
+    pub mac: [u8; 32],
+}
+
+/// A packet containing secret information to be injected
+/// into the guest.
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Secret {
     /// The header for this packet.
@@ -403,6 +1569,279 @@ impl codicon::Encoder<()> for Secret {
     }
 }
 
+/// Used to start sending a guest to another platform.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outgoing {
+    /// The policy the guest was launched with.
+    pub policy: Policy,
+
+    /// The target platform's Diffie-Hellman certificate.
+    pub pdh: certs::sev::sev::Certificate,
+
+    /// The target platform's PEK, OCA and CEK certificates, encoded back
+    /// to back.
+    pub plat_certs: Vec<u8>,
+
+    /// The ASK and ARK certificates of the target platform, encoded back
+    /// to back.
+    pub amd_certs: Vec<u8>,
+}
+
+impl Outgoing {
+    /// Build the send parameters from the target platform's certificate
+    /// chain.
+    pub fn new(policy: Policy, target: &certs::sev::Chain) -> Result<Self> {
+        use codicon::Encoder;
+
+        let mut plat_certs = vec![];
+        target.sev.pek.encode(&mut plat_certs, ())?;
+        target.sev.oca.encode(&mut plat_certs, ())?;
+        target.sev.cek.encode(&mut plat_certs, ())?;
+
+        let mut amd_certs = vec![];
+        target.ca.encode(&mut amd_certs, ())?;
+
+        Ok(Self {
+            policy,
+            pdh: target.sev.pdh,
+            plat_certs,
+            amd_certs,
+        })
+    }
+
+    /// Bundle the transport keys produced by [Sender::start] with the
+    /// origin platform's PDH, for delivery to the target platform.
+    pub fn incoming(&self, origin_pdh: certs::sev::sev::Certificate, session: Session) -> Incoming {
+        Incoming {
+            policy: self.policy,
+            cert: origin_pdh,
+            session,
+        }
+    }
+}
+
+/// Used to start receiving a guest from another platform.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Incoming {
+    /// The policy the guest was launched with.
+    pub policy: Policy,
+
+    /// The origin platform's Diffie-Hellman certificate.
+    pub cert: certs::sev::sev::Certificate,
+
+    /// The session produced by [Sender::start] on the origin platform.
+    pub session: Session,
+}
+
+impl codicon::Decoder<()> for Incoming {
+    type Error = std::io::Error;
+
+    fn decode(mut reader: impl Read, _: ()) -> std::io::Result<Self> {
+        reader.load()
+    }
+}
+
+impl codicon::Encoder<()> for Incoming {
+    type Error = std::io::Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> std::io::Result<()> {
+        writer.save(self)
+    }
+}
+
+/// A region of guest memory encrypted with the transport keys.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Packet {
+    /// The header for this packet.
+    pub header: Header,
+
+    /// The encrypted guest memory.
+    pub data: Vec<u8>,
+}
+
+impl codicon::Decoder<()> for Packet {
+    type Error = std::io::Error;
+
+    fn decode(mut reader: impl Read, _: ()) -> std::io::Result<Self> {
+        let header = reader.load()?;
+        let mut data = vec![];
+        let _ = reader.read_to_end(&mut data)?;
+        Ok(Self { header, data })
+    }
+}
+
+impl codicon::Encoder<()> for Packet {
+    type Error = std::io::Error;
+
+    fn encode(&self, mut writer: impl Write, _: ()) -> std::io::Result<()> {
+        writer.save(&self.header)?;
+        writer.write_all(&self.data)
+    }
+}
+
+/// A [Packet] of a [Snapshot], and where it is placed in guest memory.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SnapshotPage {
+    /// The offset of the packet's data in guest memory.
+    pub offset: u64,
+
+    /// The encrypted guest memory.
+    pub packet: Packet,
+}
+
+/// The encrypted memory of an SEV guest, from which a new guest can be
+/// launched.
+///
+/// A snapshot is produced by sending a guest to its own platform (i.e.
+/// with an [Outgoing] built from the platform's own certificate chain),
+/// and restored by receiving it into a new guest with
+/// [Receiver::restore].
+///
+/// # Example:
+/// ```ignore
+/// let (mut sender, session) = Sender::new(vm_fd, sev).start(&outgoing)?;
+///
+/// let mut snapshot = Snapshot::new(outgoing.incoming(pdh, session));
+/// snapshot.capture(&mut sender, 0, &memory[..0x10000])?;
+/// sender.finish()?;
+///
+/// let mut receiver = Receiver::new(new_vm_fd, sev)?.start(snapshot.incoming)?;
+/// receiver.restore(&snapshot, &mut new_memory)?;
+/// receiver.finish()?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// The parameters for receiving the guest.
+    pub incoming: Incoming,
+
+    /// The encrypted guest memory, in the order it was captured.
+    pub pages: Vec<SnapshotPage>,
+}
+
+impl Snapshot {
+    /// Create an empty snapshot, to be received with `incoming`.
+    pub fn new(incoming: Incoming) -> Self {
+        Self {
+            incoming,
+            pages: vec![],
+        }
+    }
+
+    /// Encrypt `guest`, found at `offset` in guest memory, with the
+    /// transport keys of `sender` and add it to the snapshot.
+    #[cfg(target_os = "linux")]
+    pub fn capture<U: VmHandle, V: SevDevice>(
+        &mut self,
+        sender: &mut Sender<Sending, U, V>,
+        offset: u64,
+        guest: &[u8],
+    ) -> Result<()> {
+        let packet: Packet = sender.update_data(guest)?;
+        self.pages.push(SnapshotPage { offset, packet });
+
+        Ok(())
+    }
+
+    /// The size of the smallest guest memory which holds every page.
+    pub fn memory_size(&self) -> u64 {
+        self.pages
+            .iter()
+            .map(|page| page.offset + page.packet.data.len() as u64)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Check that the pages fit into `size` bytes of guest memory without
+    /// overlapping.
+    fn check_placement(&self, size: u64) -> Result<()> {
+        let mut spans: Vec<(u64, u64)> = vec![];
+
+        for page in self.pages.iter() {
+            let end: Option<u64> = page.offset.checked_add(page.packet.data.len() as u64);
+
+            match end {
+                Some(end) if end <= size => spans.push((page.offset, end)),
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("snapshot page at {:#x} exceeds guest memory", page.offset),
+                    ))
+                }
+            }
+        }
+
+        spans.sort_unstable();
+
+        for pair in spans.windows(2) {
+            if pair[0].1 > pair[1].0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("snapshot pages overlap at {:#x}", pair[1].0),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The state of an SEV guest, as tracked by the firmware.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum GuestState {
+    /// The guest is uninitialized.
+    Uninitialized = 0,
+
+    /// The guest is being launched and accepts LAUNCH_UPDATE commands.
+    LaunchUpdate = 1,
+
+    /// The guest has been measured and accepts LAUNCH_SECRET commands.
+    LaunchSecret = 2,
+
+    /// The guest is fully launched or migrated in, and is running.
+    Running = 3,
+
+    /// The guest is being migrated out and accepts SEND_UPDATE commands.
+    SendUpdate = 4,
+
+    /// The guest is being migrated in and accepts RECEIVE_UPDATE commands.
+    ReceiveUpdate = 5,
+
+    /// The guest has been sent to another platform.
+    Sent = 6,
+}
+
+impl TryFrom<u32> for GuestState {
+    type Error = std::io::Error;
+
+    fn try_from(value: u32) -> std::io::Result<Self> {
+        Ok(match value {
+            0 => Self::Uninitialized,
+            1 => Self::LaunchUpdate,
+            2 => Self::LaunchSecret,
+            3 => Self::Running,
+            4 => Self::SendUpdate,
+            5 => Self::ReceiveUpdate,
+            6 => Self::Sent,
+            _ => return Err(std::io::ErrorKind::InvalidData.into()),
+        })
+    }
+}
+
+/// The firmware's view of an SEV guest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GuestStatus {
+    /// The firmware handle of the guest.
+    pub handle: u32,
+
+    /// The policy the guest was launched with.
+    pub policy: Policy,
+
+    /// The current state of the guest.
+    pub state: GuestState,
+}
+
 /// A measurement of the SEV guest.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
@@ -414,6 +1853,16 @@ pub struct Measurement {
     pub mnonce: [u8; 16],
 }
 
+/// A [Measurement] bound to the launch session it was taken in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoundMeasurement {
+    /// The measurement.
+    pub measurement: Measurement,
+
+    /// The session the launch was started with.
+    pub session: Session,
+}
+
 impl codicon::Decoder<()> for Measurement {
     type Error = std::io::Error;
 
@@ -429,3 +1878,666 @@ impl codicon::Encoder<()> for Measurement {
         writer.save(self)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_coalesce() {
+        let memory: Vec<u8> = vec![0; 0x4000];
+        let base: u64 = memory.as_ptr() as u64;
+
+        let regions: [&[u8]; 4] = [
+            &memory[0..0x1000],
+            &memory[0x1000..0x2000],
+            &memory[0x3000..0x4000],
+            &memory[0x2000..0x3000],
+        ];
+
+        assert_eq!(
+            coalesce(&regions),
+            vec![
+                (base, 0x2000),
+                (base + 0x3000, 0x1000),
+                (base + 0x2000, 0x1000)
+            ]
+        );
+        assert!(coalesce(&[&memory[0..0]]).is_empty());
+    }
+
+    #[test]
+    fn test_migration_policy() {
+        let policy = Policy {
+            flags: PolicyFlags::NO_DEBUG,
+            minfw: Version::default(),
+        };
+        let es = Policy {
+            flags: PolicyFlags::ENCRYPTED_STATE,
+            ..policy
+        };
+        let no_send = Policy {
+            flags: PolicyFlags::NO_SEND,
+            ..policy
+        };
+
+        assert_eq!(check_send(&policy, &policy), Ok(()));
+        assert_eq!(
+            check_send(&policy, &es),
+            Err(MigrationPolicyError::PolicyMismatch)
+        );
+        assert_eq!(
+            check_send(&no_send, &no_send),
+            Err(MigrationPolicyError::SendForbidden)
+        );
+
+        assert_eq!(check_receive(&es, true), Ok(()));
+        assert_eq!(
+            check_receive(&es, false),
+            Err(MigrationPolicyError::EncryptedStateMismatch)
+        );
+        assert_eq!(
+            check_receive(&no_send, false),
+            Err(MigrationPolicyError::SendForbidden)
+        );
+    }
+
+    #[test]
+    fn test_es_requirements() {
+        let requires = EsRequirements {
+            supported: true,
+            asids: 1..100,
+            smt_active: false,
+            firmware: Version { major: 1, minor: 0 },
+        };
+
+        let mut policy = Policy {
+            flags: PolicyFlags::NO_DEBUG,
+            minfw: Version {
+                major: 0,
+                minor: 24,
+            },
+        };
+        assert_eq!(
+            requires.check(&policy),
+            Err(EsPolicyError::EncryptedStateRequired)
+        );
+
+        policy.flags |= PolicyFlags::ENCRYPTED_STATE;
+        assert_eq!(requires.check(&policy), Ok(()));
+
+        policy.minfw = Version { major: 1, minor: 1 };
+        assert_eq!(
+            requires.check(&policy),
+            Err(EsPolicyError::FirmwareTooOld(policy.minfw))
+        );
+
+        let requires = EsRequirements {
+            asids: 1..1,
+            ..requires
+        };
+        assert_eq!(requires.check(&policy), Err(EsPolicyError::NoAsids));
+    }
+
+    #[test]
+    fn test_policy_builder() {
+        let policy: Policy = Policy::builder()
+            .require_es()
+            .forbid_debug()
+            .send_within_domain()
+            .min_firmware(0, 24)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            policy.flags,
+            PolicyFlags::ENCRYPTED_STATE | PolicyFlags::NO_DEBUG | PolicyFlags::DOMAIN
+        );
+        assert_eq!(
+            policy.explain(),
+            "debugging is forbidden; key sharing is allowed; SEV-ES is required; \
+             sending is allowed within the domain; the minimum firmware version is 0.24"
+        );
+
+        assert_eq!(
+            Policy::builder().forbid_send().send_to_sev_only().build(),
+            Err(PolicyError::SendRestrictedButForbidden)
+        );
+
+        let requires = EsRequirements {
+            supported: false,
+            ..Default::default()
+        };
+        assert!(Policy::builder().build_for(&requires).is_ok());
+        assert_eq!(
+            Policy::builder().require_es().build_for(&requires),
+            Err(PolicyError::EncryptedState(EsPolicyError::Unsupported))
+        );
+    }
+
+    #[test]
+    fn test_debugger() {
+        use crate::launch::vmm::Loopback;
+
+        let guest = [0u8; 16];
+        let mut plain = [0u8; 16];
+
+        let mut debugger = unsafe { Debugger::new(Loopback::new(), 7) }.unwrap();
+        debugger.decrypt(&guest, &mut plain).unwrap();
+        assert!(debugger.decrypt(&guest, &mut plain[..8]).is_err());
+
+        let (vm, _) = debugger.into_inner();
+        assert_eq!(vm.command_ids(), vec![16, 16, 17]);
+    }
+
+    /// A [Loopback](crate::launch::vmm::Loopback) whose guest status
+    /// reports an SEV-ES policy.
+    #[derive(Default)]
+    struct EsGuest(crate::launch::vmm::Loopback);
+
+    impl VmHandle for EsGuest {
+        fn encrypt_op(&mut self, op: &mut crate::launch::vmm::EncryptOp) -> Result<()> {
+            if op.id == 16 {
+                let status = unsafe { &mut *(op.data as *mut SevGuestStatus) };
+                status.policy = PolicyFlags::ENCRYPTED_STATE.bits().into();
+            }
+
+            self.0.encrypt_op(op)
+        }
+
+        fn register_region(&mut self, addr: u64, size: u64) -> Result<()> {
+            self.0.register_region(addr, size)
+        }
+    }
+
+    #[test]
+    fn test_debugger_vmsa() {
+        use crate::launch::vmm::Loopback;
+
+        let mut page = vec![0u8; 4096];
+
+        let mut debugger = unsafe { Debugger::new(Loopback::new(), 7) }.unwrap();
+        assert!(debugger.export_vmsa(&page).is_err());
+
+        let (vm, _) = debugger.into_inner();
+        assert_eq!(vm.command_ids(), vec![16, 16]);
+
+        let mut debugger = unsafe { Debugger::new(EsGuest::default(), 7) }.unwrap();
+        let vmsa = debugger.export_vmsa(&page).unwrap();
+        assert_eq!(vmsa.rip(), 0);
+        assert_eq!(vmsa.cs().selector(), 0);
+
+        debugger.import_vmsa(&vmsa, &mut page).unwrap();
+        assert!(debugger.export_vmsa(&page[..2048]).is_err());
+
+        let (vm, _) = debugger.into_inner();
+        assert_eq!(vm.0.command_ids(), vec![16, 16, 17, 16, 17, 18]);
+    }
+
+    #[test]
+    fn test_observer() {
+        use crate::launch::vmm::Loopback;
+        use codicon::Decoder;
+        use std::sync::{Arc, Mutex};
+
+        let events: Arc<Mutex<Vec<LaunchEvent>>> = Arc::new(Mutex::new(vec![]));
+        let observed = events.clone();
+
+        let zeroes = [0u8; std::mem::size_of::<Start>()];
+        let start: Start = Start::decode(&mut &zeroes[..], ()).unwrap();
+        let data = [0u8; 4096];
+
+        let mut launcher = Launcher::new(Loopback::new(), 7)
+            .unwrap()
+            .observe(move |event: &LaunchEvent| observed.lock().unwrap().push(*event))
+            .start(start)
+            .unwrap();
+        launcher.update_data(&data).unwrap();
+        launcher.measure().unwrap().finish().unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                LaunchEvent::Started,
+                LaunchEvent::Updated { bytes: 4096 },
+                LaunchEvent::Measured,
+                LaunchEvent::Finished,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_vmsas_vcpus() {
+        use crate::launch::vmm::{Loopback, KVM_MAX_VCPUS};
+        use codicon::Decoder;
+
+        let zeroes = [0u8; std::mem::size_of::<Start>()];
+        let launcher = || {
+            let start: Start = Start::decode(&mut &zeroes[..], ()).unwrap();
+            Launcher::new(Loopback::new(), 7)
+                .unwrap()
+                .start(start)
+                .unwrap()
+        };
+
+        assert!(launcher().update_vmsas(0).is_err());
+        assert!(launcher().update_vmsas(KVM_MAX_VCPUS + 1).is_err());
+        assert_eq!(
+            launcher().update_vmsas(KVM_MAX_VCPUS).unwrap().vcpus(),
+            KVM_MAX_VCPUS
+        );
+    }
+
+    #[cfg(feature = "openssl")]
+    #[test]
+    fn test_update_data_measured() {
+        use crate::{launch::vmm::Loopback, session::LaunchDigest};
+        use codicon::Decoder;
+
+        let zeroes = [0u8; std::mem::size_of::<Start>()];
+        let start: Start = Start::decode(&mut &zeroes[..], ()).unwrap();
+        let data: Vec<u8> = (0..0x2000).map(|i| i as u8).collect();
+
+        let mut launcher = Launcher::new(Loopback::new(), 7)
+            .unwrap()
+            .start(start)
+            .unwrap();
+
+        let mut digest = LaunchDigest::new();
+        launcher
+            .update_data_measured(&data[..0x1000], &mut digest)
+            .unwrap();
+        launcher
+            .update_data_measured(&data[0x1000..], &mut digest)
+            .unwrap();
+        assert!(launcher
+            .update_data_measured(&data[..20], &mut digest)
+            .is_err());
+        assert_eq!(launcher.as_mut_vmfd().command_ids(), [0, 2, 3, 3]);
+
+        let mut expected = LaunchDigest::new();
+        expected.update(&data).unwrap();
+        assert_eq!(digest.finish(), expected.finish());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        use crate::launch::vmm::{Loopback, VmCommand};
+        use codicon::Decoder;
+
+        let zeroes = [0u8; std::mem::size_of::<Incoming>()];
+        let incoming: Incoming = Incoming::decode(&mut &zeroes[..], ()).unwrap();
+        let header = Header {
+            flags: HeaderFlags::default(),
+            iv: [0; 16],
+            mac: [0; 32],
+        };
+        let page = |offset: u64| SnapshotPage {
+            offset,
+            packet: Packet {
+                header,
+                data: vec![0; 0x1000],
+            },
+        };
+
+        let mut snapshot: Snapshot = Snapshot::new(incoming);
+        snapshot.pages = vec![page(0x2000), page(0)];
+        assert_eq!(snapshot.memory_size(), 0x3000);
+
+        let mut memory = vec![0u8; 0x3000];
+        let mut receiver = Receiver::new(Loopback::new(), 7)
+            .unwrap()
+            .start(snapshot.incoming)
+            .unwrap();
+
+        assert!(receiver.restore(&snapshot, &mut memory[..0x2000]).is_err());
+        receiver.restore(&snapshot, &mut memory).unwrap();
+
+        assert_eq!(
+            receiver.as_mut_vmfd().commands()[2..],
+            [
+                VmCommand::RegisterRegion {
+                    addr: memory.as_ptr() as u64,
+                    size: 0x3000,
+                },
+                VmCommand::EncryptOp { id: 13, sev_fd: 7 },
+                VmCommand::EncryptOp { id: 13, sev_fd: 7 },
+            ]
+        );
+
+        snapshot.pages.push(page(0x2800));
+        assert!(receiver.restore(&snapshot, &mut memory).is_err());
+    }
+
+    /// A [Loopback](crate::launch::vmm::Loopback) whose guest status
+    /// reports the given handle, policy and (raw) state. The guest is
+    /// running unless the state is changed.
+    struct ScriptedGuest {
+        vm: crate::launch::vmm::Loopback,
+        handle: u32,
+        policy: Policy,
+        state: u32,
+    }
+
+    impl ScriptedGuest {
+        fn new(handle: u32, policy: Policy) -> Self {
+            Self {
+                vm: crate::launch::vmm::Loopback::new(),
+                handle,
+                policy,
+                state: GuestState::Running as u32,
+            }
+        }
+    }
+
+    impl VmHandle for ScriptedGuest {
+        fn encrypt_op(&mut self, op: &mut crate::launch::vmm::EncryptOp) -> Result<()> {
+            if op.id == 16 {
+                let status = unsafe { &mut *(op.data as *mut SevGuestStatus) };
+                status.handle = self.handle;
+                status.policy = u32::from_le_bytes(self.policy.to_bytes());
+                status.state = self.state;
+            }
+
+            self.vm.encrypt_op(op)
+        }
+
+        fn register_region(&mut self, addr: u64, size: u64) -> Result<()> {
+            self.vm.register_region(addr, size)
+        }
+    }
+
+    fn incoming(policy: Policy) -> Incoming {
+        use codicon::Decoder;
+
+        let zeroes = [0u8; std::mem::size_of::<Incoming>()];
+        let incoming: Incoming = Incoming::decode(&mut &zeroes[..], ()).unwrap();
+
+        Incoming { policy, ..incoming }
+    }
+
+    fn outgoing(policy: Policy) -> Outgoing {
+        Outgoing {
+            policy,
+            pdh: incoming(policy).cert,
+            plat_certs: vec![],
+            amd_certs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        let policy = Policy::builder().forbid_debug().build().unwrap();
+
+        let (mut sender, _) = Sender::new(ScriptedGuest::new(5, policy), 7)
+            .start(&outgoing(policy))
+            .unwrap();
+        let packet = sender.update_data(&[0u8; 16]).unwrap();
+        assert_eq!(packet.data.len(), 16);
+        assert_eq!(sender.as_mut_vmfd().vm.command_ids(), vec![16, 8, 9]);
+        assert_eq!(sender.finish().unwrap(), Handle::new(5));
+
+        let (sender, _) = Sender::new(ScriptedGuest::new(5, policy), 7)
+            .start(&outgoing(policy))
+            .unwrap();
+        let mut sender = sender.cancel().unwrap();
+        assert_eq!(sender.as_mut_vmfd().vm.command_ids(), vec![16, 8, 21]);
+
+        let mut guest = ScriptedGuest::new(5, policy);
+        guest.vm.fail(8, 1);
+        assert!(Sender::new(guest, 7).start(&outgoing(policy)).is_err());
+    }
+
+    #[test]
+    fn test_send_refused() {
+        let policy = Policy::builder().forbid_debug().build().unwrap();
+        let no_send = Policy::builder().forbid_send().build().unwrap();
+
+        let mismatch = Sender::new(ScriptedGuest::new(5, policy), 7)
+            .start(&outgoing(no_send))
+            .err()
+            .unwrap();
+        assert_eq!(mismatch.kind(), std::io::ErrorKind::InvalidInput);
+
+        let forbidden = Sender::new(ScriptedGuest::new(5, no_send), 7)
+            .start(&outgoing(no_send))
+            .err()
+            .unwrap();
+        assert_eq!(forbidden.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_guest_status() {
+        use codicon::Decoder;
+
+        let policy = Policy::builder().forbid_debug().build().unwrap();
+        let running = GuestStatus {
+            handle: 5,
+            policy,
+            state: GuestState::Running,
+        };
+
+        let mut sender = Sender::new(ScriptedGuest::new(5, policy), 7);
+        assert_eq!(sender.status().unwrap(), running);
+        assert_eq!(sender.as_mut_vmfd().vm.command_ids(), vec![16]);
+
+        let mut receiver = Receiver::new(ScriptedGuest::new(5, policy), 7).unwrap();
+        assert_eq!(receiver.status().unwrap(), running);
+        assert_eq!(receiver.as_mut_vmfd().vm.command_ids(), vec![0, 16]);
+
+        let zeroes = [0u8; std::mem::size_of::<Start>()];
+        let start: Start = Start::decode(&mut &zeroes[..], ()).unwrap();
+        let mut launcher = Launcher::new(ScriptedGuest::new(5, policy), 7)
+            .unwrap()
+            .start(start)
+            .unwrap();
+        launcher.as_mut_vmfd().state = GuestState::LaunchUpdate as u32;
+        assert_eq!(
+            launcher.status().unwrap(),
+            GuestStatus {
+                state: GuestState::LaunchUpdate,
+                ..running
+            }
+        );
+
+        let mut guest = ScriptedGuest::new(5, policy);
+        guest.state = 7;
+        let invalid = guest_status(&mut guest, &7).unwrap_err();
+        assert_eq!(invalid.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut guest = ScriptedGuest::new(5, policy);
+        guest.vm.fail(16, 1);
+        assert!(guest_status(&mut guest, &7).is_err());
+    }
+
+    #[test]
+    fn test_receive() {
+        use crate::launch::vmm::Loopback;
+
+        let policy = Policy::builder().forbid_debug().build().unwrap();
+        let header = Header {
+            flags: HeaderFlags::default(),
+            iv: [0; 16],
+            mac: [0; 32],
+        };
+        let packet = Packet {
+            header,
+            data: vec![0; 16],
+        };
+        let mut guest = [0u8; 16];
+        let mut receiver = Receiver::new(Loopback::new(), 7)
+            .unwrap()
+            .start(incoming(policy))
+            .unwrap();
+        receiver.update_data(&packet, &mut guest).unwrap();
+
+        let short = receiver.update_data(&packet, &mut guest[..8]).unwrap_err();
+        assert_eq!(short.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(receiver.as_mut_vmfd().command_ids(), vec![0, 12, 13]);
+        assert_eq!(receiver.finish().unwrap(), Handle::new(0));
+
+        let es = Policy::builder().require_es().build().unwrap();
+        let mismatch = Receiver::new(Loopback::new(), 7)
+            .unwrap()
+            .start(incoming(es))
+            .err()
+            .unwrap();
+        assert_eq!(mismatch.kind(), std::io::ErrorKind::InvalidInput);
+
+        let mut receiver = Receiver::new_es(Loopback::new(), 7)
+            .unwrap()
+            .start(incoming(es))
+            .unwrap();
+        assert_eq!(receiver.as_mut_vmfd().command_ids(), vec![1, 12]);
+
+        let mut vm = Loopback::new();
+        vm.fail(12, 1);
+        assert!(Receiver::new(vm, 7)
+            .unwrap()
+            .start(incoming(policy))
+            .is_err());
+    }
+
+    impl LifecycleIssuer for Vec<LifecycleCommand> {
+        fn issue(&mut self, command: &LifecycleCommand) -> Result<()> {
+            self.push(*command);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_guest_lifecycle() {
+        let handle: Handle = Handle::new(5);
+        let mut issued: Vec<LifecycleCommand> = vec![];
+
+        let guest = GuestContext::active(handle, 3)
+            .deactivate(&mut issued)
+            .unwrap()
+            .activate(&mut issued, 4)
+            .unwrap();
+        assert_eq!(guest.asid(), 4);
+        guest
+            .deactivate(&mut issued)
+            .unwrap()
+            .decommission(&mut issued)
+            .unwrap();
+
+        assert_eq!(
+            issued.iter().map(|c| c.id()).collect::<Vec<u32>>(),
+            vec![0x22, 0x21, 0x22, 0x20]
+        );
+        assert_eq!(issued[1].command_buffer(), vec![5, 0, 0, 0, 4, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_policy_bytes() {
+        let policy = Policy {
+            flags: PolicyFlags::NO_DEBUG | PolicyFlags::ENCRYPTED_STATE | PolicyFlags::SEV,
+            minfw: Version {
+                major: 1,
+                minor: 24,
+            },
+        };
+
+        assert_eq!(policy.to_bytes(), [0x25, 0x00, 0x01, 0x18]);
+        assert_eq!(Policy::from_bytes(policy.to_bytes()), policy);
+        assert_eq!(Policy::default().to_bytes(), [0; 4]);
+
+        // Unknown flags are dropped.
+        assert_eq!(
+            Policy::from_bytes([0xc1, 0xff, 0, 0]).flags,
+            PolicyFlags::NO_DEBUG
+        );
+    }
+
+    #[test]
+    fn test_header_flags_bytes() {
+        assert_eq!(HeaderFlags::COMPRESSED.to_bytes(), [1, 0, 0, 0]);
+        assert_eq!(HeaderFlags::default().to_bytes(), [0; 4]);
+        assert_eq!(
+            HeaderFlags::from_bytes([0xff, 0xff, 0xff, 0xff]),
+            HeaderFlags::COMPRESSED
+        );
+    }
+
+    const PDH: &[u8] = include_bytes!("../../tests/naples/pdh.cert");
+    const PEK: &[u8] = include_bytes!("../../tests/naples/pek.cert");
+
+    fn session_blob() -> Vec<u8> {
+        (0..SESSION_BLOB_LEN as u8).collect()
+    }
+
+    #[test]
+    fn test_session_blob() {
+        let blob = session_blob();
+        let session = Session::from_bytes(&blob).unwrap();
+
+        assert_eq!(session.nonce[0], 0);
+        assert_eq!(session.wrap_tk[0], 16);
+        assert_eq!(session.wrap_iv[0], 48);
+        assert_eq!(session.wrap_mac[0], 64);
+        assert_eq!(session.policy_mac[0], 96);
+        assert_eq!(session.to_bytes()[..], blob[..]);
+
+        assert!(matches!(
+            Session::from_bytes(&blob[..96]),
+            Err(SessionBlobError::InvalidLength {
+                blob: "session",
+                expected: 128,
+                actual: 96
+            })
+        ));
+
+        let mut zero = blob;
+        zero[96..].fill(0);
+        assert!(matches!(
+            Session::from_bytes(&zero),
+            Err(SessionBlobError::ZeroMac("policy MAC"))
+        ));
+    }
+
+    #[test]
+    fn test_start_from_blobs() {
+        let policy = Policy::from(0x0001_0005);
+        let start = Start::from_blobs(policy, PDH, &session_blob()).unwrap();
+
+        assert_eq!(start.policy, policy);
+        assert_eq!(start.session.to_bytes()[..], session_blob()[..]);
+        assert_eq!(start.cert, certs::sev::Usage::PDH);
+
+        assert!(matches!(
+            Start::from_blobs(policy, PEK, &session_blob()),
+            Err(SessionBlobError::NotDiffieHellman)
+        ));
+        assert!(matches!(
+            Start::from_blobs(policy, &PDH[1..], &session_blob()),
+            Err(SessionBlobError::InvalidLength { blob: "GODH", .. })
+        ));
+
+        let mut version = PDH.to_vec();
+        version[0] = 2;
+        assert!(matches!(
+            Start::from_blobs(policy, &version, &session_blob()),
+            Err(SessionBlobError::InvalidCertificate(_))
+        ));
+    }
+
+    #[test]
+    fn test_blobs_from_base64() {
+        use base64::Engine;
+
+        let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+        let blobs =
+            SessionBlobs::from_base64(&encode(PDH), &format!("{}\n", encode(&session_blob())))
+                .unwrap();
+        assert_eq!(
+            blobs,
+            SessionBlobs::from_bytes(PDH, &session_blob()).unwrap()
+        );
+
+        assert!(matches!(
+            SessionBlobs::from_base64("not base64!", &encode(&session_blob())),
+            Err(SessionBlobError::InvalidBase64("GODH"))
+        ));
+    }
+}
@@ -4,15 +4,44 @@
 //! This ensures (at compile time) that the right steps are called in the
 //! right order.
 
-use crate::firmware::guest::GuestPolicy;
+pub mod cpuid;
 #[cfg(target_os = "linux")]
-use crate::launch::linux::{ioctl::*, snp::*};
+pub mod gmem;
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+pub mod idblock;
 
-use std::{io::Result, marker::PhantomData, os::unix::io::AsRawFd};
+#[cfg(target_os = "linux")]
+use crate::launch::linux::{ioctl::*, snp::*};
+use crate::launch::observer::{LaunchEvent, LaunchObserver, Observer};
+#[cfg(target_os = "linux")]
+use crate::launch::vmm::{SevDevice, VmHandle};
+use crate::{
+    error::{FinishError, MigrationAgentError, PageUpdateError, SnpPolicyConflict, SnpPolicyError},
+    firmware::{guest::GuestPolicy, host::SnpPlatformStatus},
+    Version,
+};
+
+use std::{
+    io::Result,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
 
 use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
 
+/// Size (in bytes) of an ID block.
+pub const ID_BLOCK_SIZE: usize = 0x60;
+
+/// Size (in bytes) of an ID authentication structure.
+pub const ID_AUTH_SIZE: usize = 0x1000;
+
+/// Size (in bytes) of an encoded public key, as hashed for the key
+/// digests of attestation reports.
+pub const ID_KEY_SIZE: usize = 0x404;
+
+pub(crate) const AUTHOR_KEY_OFFSET: usize = 0x880;
+
 /// Launcher type-state that indicates a brand new launch.
 pub struct New;
 
@@ -20,27 +49,28 @@ pub struct New;
 pub struct Started;
 
 /// Facilitates the correct execution of the SEV launch process.
-pub struct Launcher<T, U: AsRawFd, V: AsRawFd> {
+pub struct Launcher<T, U: VmHandle, V: SevDevice> {
     vm_fd: U,
     sev: V,
     state: PhantomData<T>,
+    observer: Observer,
 }
 
-impl<T, U: AsRawFd, V: AsRawFd> AsRef<U> for Launcher<T, U, V> {
+impl<T, U: VmHandle, V: SevDevice> AsRef<U> for Launcher<T, U, V> {
     /// Give access to the vm fd to create vCPUs or such.
     fn as_ref(&self) -> &U {
         &self.vm_fd
     }
 }
 
-impl<T, U: AsRawFd, V: AsRawFd> AsMut<U> for Launcher<T, U, V> {
+impl<T, U: VmHandle, V: SevDevice> AsMut<U> for Launcher<T, U, V> {
     /// Give access to the vm fd to create vCPUs or such.
     fn as_mut(&mut self) -> &mut U {
         &mut self.vm_fd
     }
 }
 
-impl<U: AsRawFd, V: AsRawFd> Launcher<New, U, V> {
+impl<U: VmHandle, V: SevDevice> Launcher<New, U, V> {
     /// Begin the SEV-SNP launch process by creating a Launcher and issuing the
     /// KVM_SNP_INIT ioctl.
     pub fn new(vm_fd: U, sev: V) -> Result<Self> {
@@ -48,60 +78,130 @@ impl<U: AsRawFd, V: AsRawFd> Launcher<New, U, V> {
             vm_fd,
             sev,
             state: PhantomData,
+            observer: Observer::default(),
         };
 
         let init = Init::default();
 
         let mut cmd = Command::from(&launcher.sev, &init);
-        SNP_INIT
-            .ioctl(&mut launcher.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut launcher.vm_fd)?;
 
         Ok(launcher)
     }
 
+    /// Notify `observer` as each phase of the launch completes.
+    pub fn observe(mut self, observer: impl LaunchObserver + 'static) -> Self {
+        self.observer = Observer::new(observer);
+        self
+    }
+
     /// Initialize the flow to launch a guest.
     pub fn start(mut self, start: Start) -> Result<Launcher<Started, U, V>> {
+        start
+            .validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
         let mut launch_start = LaunchStart::from(start);
         let mut cmd = Command::from_mut(&self.sev, &mut launch_start);
 
-        SNP_LAUNCH_START
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Started);
 
         let launcher = Launcher {
             vm_fd: self.vm_fd,
             sev: self.sev,
             state: PhantomData,
+            observer: self.observer,
         };
 
         Ok(launcher)
     }
+
+    /// Initialize the flow to launch a guest, after checking that the
+    /// platform described by `requires` can launch a guest with its policy.
+    pub fn start_for(
+        self,
+        start: Start,
+        requires: &SnpRequirements,
+    ) -> Result<Launcher<Started, U, V>> {
+        requires
+            .check(&start.policy)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+        self.start(start)
+    }
 }
 
-impl<U: AsRawFd, V: AsRawFd> Launcher<Started, U, V> {
-    /// Encrypt guest SNP data.
+impl<U: VmHandle, V: SevDevice> Launcher<Started, U, V> {
+    /// Encrypt guest SNP data. The update is validated for its page type
+    /// and issued in chunks of at most [MAX_UPDATE_LEN] bytes.
     pub fn update_data(&mut self, update: Update) -> Result<()> {
-        let launch_update_data = LaunchUpdate::from(update);
-        let mut cmd = Command::from(&self.sev, &launch_update_data);
+        update
+            .validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
 
-        KvmEncRegion::new(update.uaddr).register(&mut self.vm_fd)?;
+        KvmEncRegion::new(update.uaddr).register_with(&mut self.vm_fd)?;
 
-        SNP_LAUNCH_UPDATE
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        for chunk in update.split(MAX_UPDATE_LEN) {
+            let launch_update_data = LaunchUpdate::from(chunk);
+            let mut cmd = Command::from(&self.sev, &launch_update_data);
+
+            cmd.issue(&mut self.vm_fd)?;
+            self.observer.notify(LaunchEvent::Updated {
+                bytes: chunk.uaddr.len() as u64,
+            });
+        }
 
         Ok(())
     }
 
+    /// Encrypt the updates of `batch`, issuing one SNP_LAUNCH_UPDATE per
+    /// coalesced range (split at [MAX_UPDATE_LEN] bytes) and registering
+    /// each range once.
+    pub fn update_batch(&mut self, batch: &UpdateBatch) -> Result<BatchStats> {
+        let begin: Instant = Instant::now();
+        let mut stats: BatchStats = BatchStats {
+            updates: batch.updates,
+            ..Default::default()
+        };
+
+        for range in batch.ranges.iter() {
+            let addr: u64 = range.first.uaddr.as_ptr() as u64;
+
+            KvmEncRegion::from_raw(addr, range.len).register_with(&mut self.vm_fd)?;
+
+            let mut offset: u64 = 0;
+            while offset < range.len {
+                let len: u64 = std::cmp::min(range.len - offset, MAX_UPDATE_LEN as u64);
+                let launch_update_data = LaunchUpdate::with_range(
+                    range.first,
+                    range.first.start_gfn + offset / PAGE_SIZE as u64,
+                    addr + offset,
+                    len as u32,
+                );
+                let mut cmd = Command::from(&self.sev, &launch_update_data);
+
+                cmd.issue(&mut self.vm_fd)?;
+                self.observer.notify(LaunchEvent::Updated { bytes: len });
+
+                stats.commands += 1;
+                stats.bytes += len;
+                offset += len;
+            }
+        }
+
+        stats.elapsed = begin.elapsed();
+
+        Ok(stats)
+    }
+
     /// Complete the SNP launch process.
     pub fn finish(mut self, finish: Finish) -> Result<(U, V)> {
         let launch_finish = LaunchFinish::from(finish);
         let mut cmd = Command::from(&self.sev, &launch_finish);
 
-        SNP_LAUNCH_FINISH
-            .ioctl(&mut self.vm_fd, &mut cmd)
-            .map_err(|e| cmd.encapsulate(e))?;
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Finished);
 
         Ok((self.vm_fd, self.sev))
     }
@@ -138,6 +238,90 @@ impl<'a> Start<'a> {
             gosvw,
         }
     }
+
+    /// Associate the guest with the migration agent whose guest context is
+    /// at `ma_uaddr`. The guest policy must allow it (MIGRATE_MA).
+    pub fn with_migration_agent(mut self, ma_uaddr: &'a [u8]) -> Self {
+        self.ma_uaddr = Some(ma_uaddr);
+        self
+    }
+
+    /// The guest context of the migration agent the guest is associated
+    /// with, if any.
+    pub fn migration_agent(&self) -> Option<&'a [u8]> {
+        self.ma_uaddr
+    }
+
+    /// Check that the guest policy allows the migration agent association,
+    /// if any.
+    pub fn validate(&self) -> std::result::Result<(), MigrationAgentError> {
+        if self.ma_uaddr.is_some() && self.policy.migrate_ma_allowed() == 0 {
+            return Err(MigrationAgentError::AssociationForbidden);
+        }
+
+        Ok(())
+    }
+}
+
+/// What the platform provides to SNP guest policies.
+///
+/// Check a guest's [GuestPolicy] against it before SNP_LAUNCH_START (e.g.
+/// with [Launcher::start_for]), rather than interpreting the firmware's
+/// failure.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnpRequirements {
+    /// Ciphertext hiding is enabled on the platform.
+    pub ciphertext_hiding: bool,
+
+    /// RAPL is disabled on the platform.
+    pub rapl_disabled: bool,
+
+    /// Simultaneous multithreading is active on the host.
+    pub smt_active: bool,
+
+    /// The ABI version the platform firmware implements.
+    pub firmware: Version,
+}
+
+impl SnpRequirements {
+    /// Derive the requirements from the platform status and the host's SMT
+    /// state.
+    #[cfg(target_os = "linux")]
+    pub fn from_status(status: &SnpPlatformStatus) -> Self {
+        Self {
+            ciphertext_hiding: status.flags.ciphertext_hiding_en() != 0,
+            rapl_disabled: status.flags.rapl_dis() != 0,
+            smt_active: crate::launch::smt_active(),
+            firmware: status.version,
+        }
+    }
+
+    /// Check that a guest with `policy` can be launched, listing every
+    /// requirement of the policy the platform does not meet.
+    pub fn check(&self, policy: &GuestPolicy) -> std::result::Result<(), SnpPolicyError> {
+        let mut conflicts: Vec<SnpPolicyConflict> = vec![];
+
+        if policy.ciphertext_hiding() != 0 && !self.ciphertext_hiding {
+            conflicts.push(SnpPolicyConflict::CiphertextHidingDisabled);
+        }
+
+        if policy.rapl_dis() != 0 && !self.rapl_disabled {
+            conflicts.push(SnpPolicyConflict::RaplEnabled);
+        }
+
+        if policy.smt_allowed() == 0 && self.smt_active {
+            conflicts.push(SnpPolicyConflict::SmtActive);
+        }
+
+        if policy.abi_version() > self.firmware {
+            conflicts.push(SnpPolicyConflict::FirmwareTooOld(policy.abi_version()));
+        }
+
+        match conflicts.is_empty() {
+            true => Ok(()),
+            false => Err(SnpPolicyError { conflicts }),
+        }
+    }
 }
 
 /// Encapsulates the various data needed to begin the update process.
@@ -184,6 +368,171 @@ impl<'a> Update<'a> {
             vmpl1_perms: perms.0,
         }
     }
+
+    /// Check that the update covers whole, page-aligned pages, and a
+    /// single page for page types which require it.
+    pub fn validate(&self) -> std::result::Result<(), PageUpdateError> {
+        let len: usize = self.uaddr.len();
+        let addr: u64 = self.uaddr.as_ptr() as u64;
+
+        if len == 0 {
+            return Err(PageUpdateError::Empty);
+        }
+
+        if addr % PAGE_SIZE as u64 != 0 {
+            return Err(PageUpdateError::Misaligned { addr });
+        }
+
+        if len % PAGE_SIZE != 0 {
+            return Err(PageUpdateError::PartialPage { len });
+        }
+
+        if self.page_type.single_page() && len != PAGE_SIZE {
+            return Err(PageUpdateError::SinglePage {
+                pages: len / PAGE_SIZE,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Split the update into consecutive updates of at most `max_len`
+    /// bytes (rounded down to whole pages), advancing the guest frame
+    /// number of each.
+    pub fn split(&self, max_len: usize) -> impl Iterator<Item = Update<'a>> {
+        let update: Update<'a> = *self;
+        let max_len: usize = std::cmp::max(max_len - max_len % PAGE_SIZE, PAGE_SIZE);
+
+        update
+            .uaddr
+            .chunks(max_len)
+            .enumerate()
+            .map(move |(i, uaddr)| Update {
+                start_gfn: update.start_gfn + (i * max_len / PAGE_SIZE) as u64,
+                uaddr,
+                ..update
+            })
+    }
+}
+
+/// The size of a guest page.
+pub const PAGE_SIZE: usize = 4096;
+
+/// The largest number of bytes a single SNP_LAUNCH_UPDATE command
+/// encrypts; [Launcher::update_data] splits larger updates.
+pub const MAX_UPDATE_LEN: usize = u32::MAX as usize & !(PAGE_SIZE - 1);
+
+/// A run of updates which continue one another in guest and userspace
+/// memory, with the page type and permissions of the first.
+#[derive(Clone, Copy, Debug)]
+struct UpdateRange<'a> {
+    first: Update<'a>,
+    len: u64,
+}
+
+impl<'a> UpdateRange<'a> {
+    fn continues(&self, update: &Update) -> bool {
+        let end: u64 = self.first.uaddr.as_ptr() as u64 + self.len;
+
+        !self.first.page_type.single_page()
+            && update.start_gfn == self.first.start_gfn + self.len / PAGE_SIZE as u64
+            && update.uaddr.as_ptr() as u64 == end
+            && update.page_type == self.first.page_type
+            && update.imi_page == self.first.imi_page
+            && update.vmpl3_perms == self.first.vmpl3_perms
+            && update.vmpl2_perms == self.first.vmpl2_perms
+            && update.vmpl1_perms == self.first.vmpl1_perms
+    }
+}
+
+/// Launch updates, coalesced so that they are encrypted with as few
+/// SNP_LAUNCH_UPDATE commands as possible.
+///
+/// An update is merged into the previous one when it continues it in both
+/// guest and userspace memory, and has the same page type and permissions.
+/// Updates are never reordered, as the order determines the launch digest.
+///
+/// # Example:
+/// ```ignore
+/// let mut batch: UpdateBatch = UpdateBatch::new();
+///
+/// for (gfn, region) in regions {
+///     batch.push(Update::new(gfn, region, false, PageType::Normal, perms))?;
+/// }
+///
+/// let stats: BatchStats = launcher.update_batch(&batch)?;
+/// println!("{} MiB/s", stats.throughput() / (1 << 20) as f64);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UpdateBatch<'a> {
+    ranges: Vec<UpdateRange<'a>>,
+    updates: usize,
+}
+
+impl<'a> UpdateBatch<'a> {
+    /// An empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Validate `update` and add it to the batch.
+    pub fn push(&mut self, update: Update<'a>) -> std::result::Result<(), PageUpdateError> {
+        update.validate()?;
+
+        match self.ranges.last_mut() {
+            Some(range) if range.continues(&update) => range.len += update.uaddr.len() as u64,
+            _ => self.ranges.push(UpdateRange {
+                first: update,
+                len: update.uaddr.len() as u64,
+            }),
+        }
+
+        self.updates += 1;
+
+        Ok(())
+    }
+
+    /// The number of updates pushed.
+    pub fn len(&self) -> usize {
+        self.updates
+    }
+
+    /// Returns true if no updates were pushed.
+    pub fn is_empty(&self) -> bool {
+        self.updates == 0
+    }
+
+    /// The number of ranges the updates were coalesced into.
+    pub fn ranges(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+/// The work done by [Launcher::update_batch].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchStats {
+    /// The number of updates in the batch.
+    pub updates: usize,
+
+    /// The number of SNP_LAUNCH_UPDATE commands issued.
+    pub commands: usize,
+
+    /// The number of bytes encrypted.
+    pub bytes: u64,
+
+    /// The time taken to encrypt the batch.
+    pub elapsed: Duration,
+}
+
+impl BatchStats {
+    /// The bytes encrypted per second, or 0 if no time was measured.
+    pub fn throughput(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+
+        self.bytes as f64 / self.elapsed.as_secs_f64()
+    }
 }
 
 bitflags! {
@@ -229,6 +578,13 @@ pub enum PageType {
     Cpuid = 0x6,
 }
 
+impl PageType {
+    /// Returns true if pages of this type must be updated one at a time.
+    pub fn single_page(&self) -> bool {
+        matches!(self, Self::Vmsa | Self::Secrets | Self::Cpuid)
+    }
+}
+
 /// Encapsulates the data needed to complete a guest launch.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Finish<'a, 'b> {
@@ -238,13 +594,19 @@ pub struct Finish<'a, 'b> {
     /// The userspace address of the authentication information of the ID block.
     pub(crate) id_auth: Option<&'b [u8]>,
 
+    /// Indicates that the author key is present in the ID authentication
+    /// information.
+    pub(crate) auth_key_en: bool,
+
     /// Opaque host-supplied data to describe the guest. The firmware does not interpret this
     /// value.
     pub(crate) host_data: [u8; KVM_SEV_SNP_FINISH_DATA_SIZE],
 }
 
 impl<'a, 'b> Finish<'a, 'b> {
-    /// Encapsulate all data needed for the SNP_LAUNCH_FINISH ioctl.
+    /// Encapsulate all data needed for the SNP_LAUNCH_FINISH ioctl. The
+    /// author key is enabled whenever `id_auth` is given; use
+    /// [Finish::builder] to control it explicitly.
     pub fn new(
         id_block: Option<&'a [u8]>,
         id_auth: Option<&'b [u8]>,
@@ -253,7 +615,377 @@ impl<'a, 'b> Finish<'a, 'b> {
         Self {
             id_block,
             id_auth,
+            auth_key_en: id_auth.is_some(),
             host_data,
         }
     }
+
+    /// Build the parameters for SNP_LAUNCH_FINISH option by option.
+    pub fn builder() -> FinishBuilder<'a, 'b> {
+        FinishBuilder::default()
+    }
+
+    /// The host data, reported in the `host_data` field of attestation
+    /// reports.
+    pub fn host_data(&self) -> &[u8; KVM_SEV_SNP_FINISH_DATA_SIZE] {
+        &self.host_data
+    }
+}
+
+/// Builds validated [Finish] parameters.
+///
+/// # Example:
+/// ```ignore
+/// let finish: Finish = Finish::builder()
+///     .host_data(digest_of_config)
+///     .id_block(&signed.id_block, &signed.id_auth)
+///     .author_key(true)
+///     .build()?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FinishBuilder<'a, 'b> {
+    id_block: Option<(&'a [u8], &'b [u8])>,
+    auth_key_en: bool,
+    host_data: [u8; KVM_SEV_SNP_FINISH_DATA_SIZE],
+}
+
+impl<'a, 'b> FinishBuilder<'a, 'b> {
+    /// Report `host_data` in the guest's attestation reports. Zeroes unless
+    /// set.
+    pub fn host_data(mut self, host_data: [u8; KVM_SEV_SNP_FINISH_DATA_SIZE]) -> Self {
+        self.host_data = host_data;
+        self
+    }
+
+    /// Check the launch against `id_block`, authenticated by `id_auth`.
+    pub fn id_block(mut self, id_block: &'a [u8], id_auth: &'b [u8]) -> Self {
+        self.id_block = Some((id_block, id_auth));
+        self
+    }
+
+    /// Launch without an ID block (the default).
+    pub fn no_id_block(mut self) -> Self {
+        self.id_block = None;
+        self
+    }
+
+    /// Whether the ID authentication structure holds an author key which
+    /// signed the ID key.
+    pub fn author_key(mut self, enabled: bool) -> Self {
+        self.auth_key_en = enabled;
+        self
+    }
+
+    /// Validate the options.
+    pub fn build(self) -> std::result::Result<Finish<'a, 'b>, FinishError> {
+        let (id_block, id_auth) = match self.id_block {
+            Some((id_block, id_auth)) => {
+                if id_block.len() != ID_BLOCK_SIZE {
+                    return Err(FinishError::IdBlockSize {
+                        len: id_block.len(),
+                    });
+                }
+
+                if id_auth.len() != ID_AUTH_SIZE {
+                    return Err(FinishError::IdAuthSize { len: id_auth.len() });
+                }
+
+                let author_key: &[u8] =
+                    &id_auth[AUTHOR_KEY_OFFSET..AUTHOR_KEY_OFFSET + ID_KEY_SIZE];
+                if self.auth_key_en && author_key.iter().all(|b| *b == 0) {
+                    return Err(FinishError::AuthorKeyMissing);
+                }
+
+                (Some(id_block), Some(id_auth))
+            }
+            None if self.auth_key_en => return Err(FinishError::AuthorKeyWithoutIdBlock),
+            None => (None, None),
+        };
+
+        Ok(Finish {
+            id_block,
+            id_auth,
+            auth_key_en: self.auth_key_en,
+            host_data: self.host_data,
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod test {
+    use super::*;
+
+    use crate::launch::vmm::{Loopback, VmCommand};
+
+    #[repr(C, align(4096))]
+    struct Pages([u8; 3 * PAGE_SIZE]);
+
+    fn update(uaddr: &[u8], page_type: PageType) -> Update<'_> {
+        let dp = VmplPerms::empty();
+
+        Update::new(0x10, uaddr, false, page_type, (dp, dp, dp))
+    }
+
+    #[test]
+    fn test_update_validate() {
+        let pages = Pages([0; 3 * PAGE_SIZE]);
+
+        assert_eq!(update(&pages.0, PageType::Normal).validate(), Ok(()));
+        assert_eq!(
+            update(&pages.0[..PAGE_SIZE], PageType::Cpuid).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            update(&[], PageType::Zero).validate(),
+            Err(PageUpdateError::Empty)
+        );
+        assert_eq!(
+            update(&pages.0[1..PAGE_SIZE + 1], PageType::Normal).validate(),
+            Err(PageUpdateError::Misaligned {
+                addr: pages.0.as_ptr() as u64 + 1
+            })
+        );
+        assert_eq!(
+            update(&pages.0[..100], PageType::Normal).validate(),
+            Err(PageUpdateError::PartialPage { len: 100 })
+        );
+        assert_eq!(
+            update(&pages.0, PageType::Secrets).validate(),
+            Err(PageUpdateError::SinglePage { pages: 3 })
+        );
+    }
+
+    #[test]
+    fn test_update_split() {
+        let pages = Pages([0; 3 * PAGE_SIZE]);
+        let chunks: Vec<Update> = update(&pages.0, PageType::Normal)
+            .split(2 * PAGE_SIZE + 1)
+            .collect();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].start_gfn, 0x10);
+        assert_eq!(chunks[0].uaddr.len(), 2 * PAGE_SIZE);
+        assert_eq!(chunks[1].start_gfn, 0x12);
+        assert_eq!(chunks[1].uaddr, &pages.0[2 * PAGE_SIZE..]);
+        assert_eq!(chunks[1].page_type, PageType::Normal);
+    }
+
+    #[test]
+    fn test_update_data_rejects_invalid() {
+        let pages = Pages([0; 3 * PAGE_SIZE]);
+        let mut launcher = Launcher::new(Loopback::new(), -1)
+            .unwrap()
+            .start(Start::default())
+            .unwrap();
+
+        let error = launcher
+            .update_data(update(&pages.0, PageType::Vmsa))
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        launcher
+            .update_data(update(&pages.0, PageType::Normal))
+            .unwrap();
+        assert_eq!(launcher.as_ref().command_ids().len(), 3);
+    }
+
+    #[test]
+    fn test_migration_agent() {
+        use crate::firmware::guest::AttestationReport;
+
+        let agent_context = Pages([0; 3 * PAGE_SIZE]);
+        let mut policy = GuestPolicy(0x30000);
+
+        let start = Start::default().with_migration_agent(&agent_context.0[..PAGE_SIZE]);
+        assert_eq!(
+            start.validate(),
+            Err(MigrationAgentError::AssociationForbidden)
+        );
+        assert!(Launcher::new(Loopback::new(), -1)
+            .unwrap()
+            .start(start)
+            .is_err());
+
+        policy.set_migrate_ma_allowed(1);
+        let start = Start::new(None, policy, false, [0; 16])
+            .with_migration_agent(&agent_context.0[..PAGE_SIZE]);
+        assert_eq!(start.validate(), Ok(()));
+        assert!(start.migration_agent().is_some());
+
+        let mut agent = AttestationReport::default();
+        agent.report_id = [0xa; 32];
+        agent.report_id_ma = [0xff; 32];
+
+        let mut guest = AttestationReport::default();
+        guest.policy = policy;
+        guest.report_id = [0xb; 32];
+        guest.report_id_ma = [0xa; 32];
+
+        assert_eq!(agent.migration_agent(), None);
+        assert_eq!(agent.check_migration_agent(None), Ok(()));
+        assert_eq!(guest.migration_agent(), Some([0xa; 32]));
+        assert_eq!(guest.check_migration_agent(Some(&agent)), Ok(()));
+        assert_eq!(
+            guest.check_migration_agent(None),
+            Err(MigrationAgentError::UnexpectedAgent)
+        );
+        assert_eq!(
+            agent.check_migration_agent(Some(&guest)),
+            Err(MigrationAgentError::MissingAgent)
+        );
+        assert_eq!(
+            guest.check_migration_agent(Some(&guest)),
+            Err(MigrationAgentError::AgentMismatch)
+        );
+
+        guest.policy.set_migrate_ma_allowed(0);
+        assert_eq!(
+            guest.check_migration_agent(Some(&agent)),
+            Err(MigrationAgentError::AssociationForbidden)
+        );
+    }
+
+    #[test]
+    fn test_update_batch() {
+        let pages = Pages([0; 3 * PAGE_SIZE]);
+        let dp = VmplPerms::empty();
+        let page = |i: usize, page_type: PageType| {
+            Update::new(
+                0x10 + i as u64,
+                &pages.0[i * PAGE_SIZE..(i + 1) * PAGE_SIZE],
+                false,
+                page_type,
+                (dp, dp, dp),
+            )
+        };
+
+        let mut batch = UpdateBatch::new();
+        batch.push(page(0, PageType::Normal)).unwrap();
+        batch.push(page(1, PageType::Normal)).unwrap();
+        batch.push(page(2, PageType::Cpuid)).unwrap();
+        batch.push(page(0, PageType::Secrets)).unwrap();
+        batch.push(page(1, PageType::Secrets)).unwrap();
+        assert_eq!(
+            batch.push(update(&pages.0[..100], PageType::Normal)),
+            Err(PageUpdateError::PartialPage { len: 100 })
+        );
+
+        assert_eq!(batch.len(), 5);
+        assert_eq!(batch.ranges(), 4);
+
+        let mut launcher = Launcher::new(Loopback::new(), -1)
+            .unwrap()
+            .start(Start::default())
+            .unwrap();
+        let stats = launcher.update_batch(&batch).unwrap();
+
+        assert_eq!(stats.updates, 5);
+        assert_eq!(stats.commands, 4);
+        assert_eq!(stats.bytes, 5 * PAGE_SIZE as u64);
+        assert_eq!(
+            launcher.as_ref().commands()[2],
+            VmCommand::RegisterRegion {
+                addr: pages.0.as_ptr() as u64,
+                size: 2 * PAGE_SIZE as u64,
+            }
+        );
+    }
+
+    #[test]
+    fn test_finish_builder() {
+        let id_block = [0u8; ID_BLOCK_SIZE];
+        let mut id_auth = [0u8; ID_AUTH_SIZE];
+
+        let finish = Finish::builder().host_data([0xab; 32]).build().unwrap();
+        assert_eq!(finish.host_data(), &[0xab; 32]);
+        assert_eq!(finish.id_block, None);
+        assert!(!finish.auth_key_en);
+
+        assert_eq!(
+            Finish::builder().author_key(true).build(),
+            Err(FinishError::AuthorKeyWithoutIdBlock)
+        );
+        assert_eq!(
+            Finish::builder()
+                .id_block(&id_block[..0x40], &id_auth)
+                .build(),
+            Err(FinishError::IdBlockSize { len: 0x40 })
+        );
+        assert_eq!(
+            Finish::builder()
+                .id_block(&id_block, &id_auth[..0x800])
+                .build(),
+            Err(FinishError::IdAuthSize { len: 0x800 })
+        );
+
+        let finish = Finish::builder()
+            .id_block(&id_block, &id_auth)
+            .build()
+            .unwrap();
+        assert_eq!(finish.id_auth, Some(&id_auth[..]));
+        assert!(!finish.auth_key_en);
+
+        assert_eq!(
+            Finish::builder()
+                .id_block(&id_block, &id_auth)
+                .author_key(true)
+                .build(),
+            Err(FinishError::AuthorKeyMissing)
+        );
+
+        id_auth[AUTHOR_KEY_OFFSET] = 2;
+        let finish = Finish::builder()
+            .id_block(&id_block, &id_auth)
+            .author_key(true)
+            .no_id_block()
+            .id_block(&id_block, &id_auth)
+            .build()
+            .unwrap();
+        assert!(finish.auth_key_en);
+    }
+
+    #[test]
+    fn test_snp_requirements() {
+        let requires = SnpRequirements {
+            smt_active: true,
+            firmware: Version {
+                major: 1,
+                minor: 55,
+            },
+            ..Default::default()
+        };
+
+        let mut policy = GuestPolicy(0x30000);
+        policy.set_abi_major(1);
+        policy.set_abi_minor(51);
+        assert_eq!(requires.check(&policy), Ok(()));
+
+        policy.set_smt_allowed(0);
+        policy.set_abi_minor(58);
+        policy.set_ciphertext_hiding(1);
+        policy.set_rapl_dis(1);
+
+        let error = requires.check(&policy).unwrap_err();
+        assert_eq!(
+            error.conflicts,
+            vec![
+                SnpPolicyConflict::CiphertextHidingDisabled,
+                SnpPolicyConflict::RaplEnabled,
+                SnpPolicyConflict::SmtActive,
+                SnpPolicyConflict::FirmwareTooOld(Version {
+                    major: 1,
+                    minor: 58,
+                }),
+            ]
+        );
+
+        let start = Start::new(None, policy, false, [0; 16]);
+        let error = Launcher::new(Loopback::new(), -1)
+            .unwrap()
+            .start_for(start, &requires)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
 }
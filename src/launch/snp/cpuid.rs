@@ -0,0 +1,311 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! CPUID pages for SNP_LAUNCH_UPDATE.
+//!
+//! A CPUID page tells an SNP guest the CPUID function values it may trust.
+//! The firmware checks each function against what the platform supports
+//! when the page is inserted with [PageType::Cpuid](super::PageType::Cpuid).
+//! If any function is rejected, the firmware fails the update and (through
+//! KVM) writes a sanitized copy of the page back to the update's userspace
+//! address, which [CpuidPage::from_bytes] parses and [CpuidPage::diff]
+//! compares with the requested page.
+//!
+//! # Example:
+//! ```ignore
+//! let mut page: CpuidPage = CpuidPage::new();
+//! page.push(CpuidFunction::new(0x8000_001f, 0, [0x1, 0x0, 0x0, 0x0]))?;
+//!
+//! guest_page.copy_from_slice(&page.to_bytes());
+//!
+//! if launcher.update_data(update).is_err() {
+//!     for mismatch in page.diff(&CpuidPage::from_bytes(guest_page)?) {
+//!         println!("{mismatch:?}");
+//!     }
+//! }
+//! ```
+
+use super::PAGE_SIZE;
+use crate::error::CpuidError;
+
+use std::convert::TryInto;
+
+use serde::{Deserialize, Serialize};
+
+/// The largest number of functions a CPUID page holds.
+pub const CPUID_COUNT_MAX: usize = 64;
+
+/// Size (in bytes) of the header preceding the functions.
+const HEADER_SIZE: usize = 16;
+
+/// Size (in bytes) of an encoded function.
+const FUNCTION_SIZE: usize = 48;
+
+/// A CPUID function and the values it returns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CpuidFunction {
+    /// The function (input EAX).
+    pub eax_in: u32,
+
+    /// The sub-function (input ECX).
+    pub ecx_in: u32,
+
+    /// The XCR0 value the function applies to.
+    pub xcr0_in: u64,
+
+    /// The XSS value the function applies to.
+    pub xss_in: u64,
+
+    /// The returned EAX.
+    pub eax: u32,
+
+    /// The returned EBX.
+    pub ebx: u32,
+
+    /// The returned ECX.
+    pub ecx: u32,
+
+    /// The returned EDX.
+    pub edx: u32,
+}
+
+impl CpuidFunction {
+    /// A function returning `[eax, ebx, ecx, edx]`, for any XCR0 and XSS.
+    pub fn new(eax_in: u32, ecx_in: u32, values: [u32; 4]) -> Self {
+        Self {
+            eax_in,
+            ecx_in,
+            eax: values[0],
+            ebx: values[1],
+            ecx: values[2],
+            edx: values[3],
+            ..Default::default()
+        }
+    }
+
+    /// The inputs which identify the function.
+    fn inputs(&self) -> (u32, u32, u64, u64) {
+        (self.eax_in, self.ecx_in, self.xcr0_in, self.xss_in)
+    }
+
+    fn encode(&self, bytes: &mut [u8]) {
+        bytes[0..4].copy_from_slice(&self.eax_in.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.ecx_in.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.xcr0_in.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.xss_in.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.eax.to_le_bytes());
+        bytes[28..32].copy_from_slice(&self.ebx.to_le_bytes());
+        bytes[32..36].copy_from_slice(&self.ecx.to_le_bytes());
+        bytes[36..40].copy_from_slice(&self.edx.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+
+        Self {
+            eax_in: u32_at(0),
+            ecx_in: u32_at(4),
+            xcr0_in: u64_at(8),
+            xss_in: u64_at(16),
+            eax: u32_at(24),
+            ebx: u32_at(28),
+            ecx: u32_at(32),
+            edx: u32_at(36),
+        }
+    }
+}
+
+/// A function the firmware did not accept as requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuidMismatch {
+    /// The requested function.
+    pub requested: CpuidFunction,
+
+    /// The function the firmware accepted in its place, if any.
+    pub accepted: Option<CpuidFunction>,
+}
+
+/// The CPUID functions of an SNP guest, laid out as the firmware's CPUID
+/// page.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CpuidPage {
+    functions: Vec<CpuidFunction>,
+}
+
+impl CpuidPage {
+    /// A page with no functions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `function` to the page.
+    pub fn push(&mut self, function: CpuidFunction) -> Result<(), CpuidError> {
+        if self.functions.len() == CPUID_COUNT_MAX {
+            return Err(CpuidError::TooManyFunctions {
+                count: CPUID_COUNT_MAX + 1,
+            });
+        }
+
+        self.functions.push(function);
+        Ok(())
+    }
+
+    /// The functions of the page, in order.
+    pub fn functions(&self) -> &[CpuidFunction] {
+        &self.functions
+    }
+
+    /// Encode the page for SNP_LAUNCH_UPDATE.
+    pub fn to_bytes(&self) -> [u8; PAGE_SIZE] {
+        let mut page: [u8; PAGE_SIZE] = [0; PAGE_SIZE];
+
+        page[0..4].copy_from_slice(&(self.functions.len() as u32).to_le_bytes());
+
+        for (i, function) in self.functions.iter().enumerate() {
+            let at: usize = HEADER_SIZE + i * FUNCTION_SIZE;
+            function.encode(&mut page[at..at + FUNCTION_SIZE]);
+        }
+
+        page
+    }
+
+    /// Parse an encoded page, e.g. the copy sanitized by the firmware.
+    pub fn from_bytes(page: &[u8]) -> Result<Self, CpuidError> {
+        if page.len() != PAGE_SIZE {
+            return Err(CpuidError::PageSize { len: page.len() });
+        }
+
+        let count: usize = u32::from_le_bytes(page[0..4].try_into().unwrap()) as usize;
+        if count > CPUID_COUNT_MAX {
+            return Err(CpuidError::TooManyFunctions { count });
+        }
+
+        let functions: Vec<CpuidFunction> = page[HEADER_SIZE..]
+            .chunks_exact(FUNCTION_SIZE)
+            .take(count)
+            .map(CpuidFunction::decode)
+            .collect();
+
+        Ok(Self { functions })
+    }
+
+    /// The requested functions of this page which `accepted` does not
+    /// hold as requested. Functions are matched by their inputs.
+    pub fn diff(&self, accepted: &CpuidPage) -> Vec<CpuidMismatch> {
+        self.functions
+            .iter()
+            .filter_map(|requested| {
+                let accepted: Option<CpuidFunction> = accepted
+                    .functions
+                    .iter()
+                    .find(|f| f.inputs() == requested.inputs())
+                    .copied();
+
+                match accepted {
+                    Some(function) if function == *requested => None,
+                    _ => Some(CpuidMismatch {
+                        requested: *requested,
+                        accepted,
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+impl std::convert::TryFrom<Vec<CpuidFunction>> for CpuidPage {
+    type Error = CpuidError;
+
+    fn try_from(functions: Vec<CpuidFunction>) -> Result<Self, Self::Error> {
+        if functions.len() > CPUID_COUNT_MAX {
+            return Err(CpuidError::TooManyFunctions {
+                count: functions.len(),
+            });
+        }
+
+        Ok(Self { functions })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_round_trip() {
+        let mut page: CpuidPage = CpuidPage::new();
+        page.push(CpuidFunction::new(
+            0x1,
+            0,
+            [0x00a0_0f11, 0x0, 0x7ed8_320b, 0x178b_fbff],
+        ))
+        .unwrap();
+        page.push(CpuidFunction {
+            xcr0_in: 0x7,
+            ..CpuidFunction::new(0xd, 1, [0x340, 0x0, 0x0, 0x0])
+        })
+        .unwrap();
+
+        let bytes: [u8; PAGE_SIZE] = page.to_bytes();
+
+        assert_eq!(&bytes[0..4], &[2, 0, 0, 0]);
+        assert_eq!(&bytes[16..20], &[1, 0, 0, 0]);
+        assert_eq!(&bytes[64..68], &[0xd, 0, 0, 0]);
+        assert_eq!(&bytes[72..80], &[7, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(CpuidPage::from_bytes(&bytes), Ok(page));
+        assert_eq!(
+            CpuidPage::from_bytes(&bytes[..100]),
+            Err(CpuidError::PageSize { len: 100 })
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let functions: Vec<CpuidFunction> = vec![CpuidFunction::default(); CPUID_COUNT_MAX];
+        let mut page: CpuidPage = CpuidPage::try_from(functions.clone()).unwrap();
+
+        assert_eq!(
+            page.push(CpuidFunction::default()),
+            Err(CpuidError::TooManyFunctions { count: 65 })
+        );
+        assert!(CpuidPage::try_from([functions, vec![CpuidFunction::default()]].concat()).is_err());
+
+        let mut bytes: [u8; PAGE_SIZE] = page.to_bytes();
+        bytes[0] = 65;
+        assert_eq!(
+            CpuidPage::from_bytes(&bytes),
+            Err(CpuidError::TooManyFunctions { count: 65 })
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let leaf1: CpuidFunction = CpuidFunction::new(0x1, 0, [0x1, 0x2, 0x3, 0x4]);
+        let leaf7: CpuidFunction = CpuidFunction::new(0x7, 0, [0x0, 0xffff_ffff, 0x0, 0x0]);
+        let leaf8: CpuidFunction = CpuidFunction::new(0x8000_0008, 0, [0x3030, 0x0, 0x0, 0x0]);
+
+        let requested: CpuidPage = CpuidPage::try_from(vec![leaf1, leaf7, leaf8]).unwrap();
+        let sanitized7: CpuidFunction = CpuidFunction {
+            ebx: 0x219c_97a9,
+            ..leaf7
+        };
+        let accepted: CpuidPage = CpuidPage::try_from(vec![leaf1, sanitized7]).unwrap();
+
+        assert_eq!(
+            requested.diff(&accepted),
+            vec![
+                CpuidMismatch {
+                    requested: leaf7,
+                    accepted: Some(sanitized7),
+                },
+                CpuidMismatch {
+                    requested: leaf8,
+                    accepted: None,
+                },
+            ]
+        );
+        assert!(requested.diff(&requested).is_empty());
+    }
+}
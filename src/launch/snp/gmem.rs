@@ -0,0 +1,379 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The SEV-SNP launch process for guests backed by guest_memfd.
+//!
+//! Newer kernels back the private memory of SNP guests with guest_memfd,
+//! which the host cannot map. Such a VM is created with the
+//! [KVM_X86_SNP_VM] type and initialized with `KVM_SEV_INIT2`, its memory
+//! is marked private with `KVM_SET_MEMORY_ATTRIBUTES`, and each launch
+//! update names a range of guest frames to populate, optionally copying
+//! source data from shared memory into it. KVM creates the VMSAs itself
+//! when the launch finishes.
+//!
+//! # Example:
+//! ```ignore
+//! let vm_fd = kvm.create_vm_with_type(KVM_X86_SNP_VM)?;
+//! // ... create a guest_memfd and KVM_SET_USER_MEMORY_REGION2 ...
+//!
+//! let mut launcher = GmemLauncher::new(vm_fd, sev, GmemInit::default())?;
+//! launcher.set_private(0, memory_size)?;
+//!
+//! let mut launcher = launcher.start(Start::new(None, policy, false, [0; 16]))?;
+//! launcher.update(GmemUpdate::new(0xffc00, firmware.len(), PageType::Normal).source(&firmware))?;
+//! launcher.update(GmemUpdate::new(0x800, PAGE_SIZE, PageType::Secrets).source(&scratch))?;
+//!
+//! let (vm_fd, sev) = launcher.finish(Finish::builder().build()?)?;
+//! ```
+
+use super::{Finish, New, PageType, Start, Started, PAGE_SIZE};
+
+use crate::{
+    error::PageUpdateError,
+    firmware::linux::trace::IoctlCall,
+    launch::{
+        linux::{ioctl::*, snp::*},
+        observer::{LaunchEvent, LaunchObserver, Observer},
+        vmm::{SevDevice, VmHandle, KVM_MEMORY_ATTRIBUTE_PRIVATE},
+    },
+};
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    marker::PhantomData,
+};
+
+/// The KVM VM type of SEV-SNP guests backed by guest_memfd.
+pub const KVM_X86_SNP_VM: u64 = 4;
+
+/// Parameters of `KVM_SEV_INIT2`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GmemInit {
+    /// The SEV features (e.g. DebugSwap) enabled in the VMSA of each vCPU.
+    pub vmsa_features: u64,
+
+    /// The GHCB protocol version presented to the guest, or 0 for KVM's
+    /// default.
+    pub ghcb_version: u16,
+}
+
+/// A range of guest frames to populate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GmemUpdate<'a> {
+    pub(crate) start_gfn: u64,
+    pub(crate) len: usize,
+    pub(crate) page_type: PageType,
+    pub(crate) source: Option<&'a [u8]>,
+}
+
+impl<'a> GmemUpdate<'a> {
+    /// Populate `len` bytes of guest memory from frame `start_gfn` with
+    /// pages of `page_type`.
+    pub fn new(start_gfn: u64, len: usize, page_type: PageType) -> Self {
+        Self {
+            start_gfn,
+            len,
+            page_type,
+            source: None,
+        }
+    }
+
+    /// Copy the contents of the pages from `source`, which must be the
+    /// length of the update. Every page type but ZERO requires a source.
+    pub fn source(mut self, source: &'a [u8]) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Check that the update covers whole pages of a type KVM populates,
+    /// with source data where the type requires it.
+    pub fn validate(&self) -> std::result::Result<(), PageUpdateError> {
+        if self.len == 0 {
+            return Err(PageUpdateError::Empty);
+        }
+
+        if self.len % PAGE_SIZE != 0 {
+            return Err(PageUpdateError::PartialPage { len: self.len });
+        }
+
+        if self.page_type == PageType::Vmsa {
+            return Err(PageUpdateError::UnsupportedPageType);
+        }
+
+        if self.page_type.single_page() && self.len != PAGE_SIZE {
+            return Err(PageUpdateError::SinglePage {
+                pages: self.len / PAGE_SIZE,
+            });
+        }
+
+        match self.source {
+            _ if self.page_type == PageType::Zero => Ok(()),
+            None => Err(PageUpdateError::MissingSource),
+            Some(source) if source.len() != self.len => Err(PageUpdateError::SourceSize {
+                len: source.len(),
+                expected: self.len,
+            }),
+            Some(source) if source.as_ptr() as u64 % PAGE_SIZE as u64 != 0 => {
+                Err(PageUpdateError::Misaligned {
+                    addr: source.as_ptr() as u64,
+                })
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// Facilitates the correct execution of the SEV-SNP launch process for
+/// guests backed by guest_memfd.
+pub struct GmemLauncher<T, U: VmHandle, V: SevDevice> {
+    vm_fd: U,
+    sev: V,
+    state: PhantomData<T>,
+    observer: Observer,
+}
+
+impl<T, U: VmHandle, V: SevDevice> AsRef<U> for GmemLauncher<T, U, V> {
+    /// Give access to the vm fd to create vCPUs or such.
+    fn as_ref(&self) -> &U {
+        &self.vm_fd
+    }
+}
+
+impl<T, U: VmHandle, V: SevDevice> AsMut<U> for GmemLauncher<T, U, V> {
+    /// Give access to the vm fd to create vCPUs or such.
+    fn as_mut(&mut self) -> &mut U {
+        &mut self.vm_fd
+    }
+}
+
+impl<T, U: VmHandle, V: SevDevice> GmemLauncher<T, U, V> {
+    /// Mark `size` bytes of guest memory at `gpa` private, so that launch
+    /// updates populate them from guest_memfd.
+    pub fn set_private(&mut self, gpa: u64, size: u64) -> Result<()> {
+        let call = IoctlCall::launch::<KvmMemoryAttributes>();
+        let result = self
+            .vm_fd
+            .set_memory_attributes(gpa, size, KVM_MEMORY_ATTRIBUTE_PRIVATE);
+        call.finish(&result, 0);
+        result
+    }
+}
+
+impl<U: VmHandle, V: SevDevice> GmemLauncher<New, U, V> {
+    /// Begin the SEV-SNP launch process by creating a launcher and issuing
+    /// the KVM_SEV_INIT2 ioctl. `vm_fd` must be a [KVM_X86_SNP_VM].
+    pub fn new(vm_fd: U, sev: V, init: GmemInit) -> Result<Self> {
+        let mut launcher = GmemLauncher {
+            vm_fd,
+            sev,
+            state: PhantomData,
+            observer: Observer::default(),
+        };
+
+        let init2 = Init2::from(init);
+
+        let mut cmd = Command::from(&launcher.sev, &init2);
+        cmd.issue(&mut launcher.vm_fd)?;
+
+        Ok(launcher)
+    }
+
+    /// Notify `observer` as each phase of the launch completes.
+    pub fn observe(mut self, observer: impl LaunchObserver + 'static) -> Self {
+        self.observer = Observer::new(observer);
+        self
+    }
+
+    /// Initialize the flow to launch a guest. Migration agents and IMIs
+    /// are not supported for guest_memfd launches.
+    pub fn start(mut self, start: Start) -> Result<GmemLauncher<Started, U, V>> {
+        if start.ma_uaddr.is_some() || start.imi_en {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "guest_memfd launches do not support migration agents or IMIs",
+            ));
+        }
+
+        let launch_start = GmemLaunchStart::from(start);
+        let mut cmd = Command::from(&self.sev, &launch_start);
+
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Started);
+
+        Ok(GmemLauncher {
+            vm_fd: self.vm_fd,
+            sev: self.sev,
+            state: PhantomData,
+            observer: self.observer,
+        })
+    }
+}
+
+impl<U: VmHandle, V: SevDevice> GmemLauncher<Started, U, V> {
+    /// Populate and encrypt the private guest memory of `update`, issuing
+    /// the command until KVM has processed the whole range.
+    pub fn update(&mut self, update: GmemUpdate) -> Result<()> {
+        update
+            .validate()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+
+        let mut launch_update = GmemLaunchUpdate::from(update);
+
+        while launch_update.len > 0 {
+            let remaining: u64 = launch_update.len;
+
+            let mut cmd = Command::from_mut(&self.sev, &mut launch_update);
+            cmd.issue(&mut self.vm_fd)?;
+
+            if launch_update.len >= remaining {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    "KVM did not advance the launch update",
+                ));
+            }
+
+            self.observer.notify(LaunchEvent::Updated {
+                bytes: remaining - launch_update.len,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Complete the SNP launch process.
+    pub fn finish(mut self, finish: Finish) -> Result<(U, V)> {
+        let launch_finish = GmemLaunchFinish::from(finish);
+        let mut cmd = Command::from(&self.sev, &launch_finish);
+
+        cmd.issue(&mut self.vm_fd)?;
+        self.observer.notify(LaunchEvent::Finished);
+
+        Ok((self.vm_fd, self.sev))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::launch::vmm::{EncryptOp, Loopback, VmCommand};
+
+    use std::mem::size_of;
+
+    #[repr(C, align(4096))]
+    struct Pages([u8; 2 * PAGE_SIZE]);
+
+    /// A VM which processes at most one page of each launch update per
+    /// command, as KVM may.
+    #[derive(Default)]
+    struct PageAtATime(Loopback);
+
+    impl VmHandle for PageAtATime {
+        fn encrypt_op(&mut self, op: &mut EncryptOp) -> Result<()> {
+            if op.id == 101 {
+                let update = unsafe { &mut *(op.data as *mut GmemLaunchUpdate) };
+                update.gfn_start += 1;
+                update.len -= PAGE_SIZE as u64;
+                if update.uaddr != 0 {
+                    update.uaddr += PAGE_SIZE as u64;
+                }
+            }
+
+            self.0.encrypt_op(op)
+        }
+
+        fn register_region(&mut self, addr: u64, size: u64) -> Result<()> {
+            self.0.register_region(addr, size)
+        }
+
+        fn set_memory_attributes(&mut self, gpa: u64, size: u64, attributes: u64) -> Result<()> {
+            self.0.set_memory_attributes(gpa, size, attributes)
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(size_of::<Init2>(), 48);
+        assert_eq!(size_of::<GmemLaunchStart>(), 64);
+        assert_eq!(size_of::<GmemLaunchUpdate>(), 64);
+        assert_eq!(size_of::<GmemLaunchFinish>(), 88);
+    }
+
+    #[test]
+    fn test_validate() {
+        let pages = Pages([0; 2 * PAGE_SIZE]);
+
+        assert_eq!(GmemUpdate::new(0, 8192, PageType::Zero).validate(), Ok(()));
+        assert_eq!(
+            GmemUpdate::new(0, 8192, PageType::Normal)
+                .source(&pages.0)
+                .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            GmemUpdate::new(0, 4096, PageType::Vmsa)
+                .source(&pages.0[..PAGE_SIZE])
+                .validate(),
+            Err(PageUpdateError::UnsupportedPageType)
+        );
+        assert_eq!(
+            GmemUpdate::new(0, 4096, PageType::Secrets).validate(),
+            Err(PageUpdateError::MissingSource)
+        );
+        assert_eq!(
+            GmemUpdate::new(0, 4096, PageType::Cpuid)
+                .source(&pages.0)
+                .validate(),
+            Err(PageUpdateError::SourceSize {
+                len: 8192,
+                expected: 4096,
+            })
+        );
+    }
+
+    #[test]
+    fn test_gmem_launch() {
+        let pages = Pages([0; 2 * PAGE_SIZE]);
+
+        let mut launcher =
+            GmemLauncher::new(PageAtATime::default(), -1, GmemInit::default()).unwrap();
+        launcher.set_private(0, 0x10000).unwrap();
+
+        let mut launcher = launcher.start(Start::default()).unwrap();
+        launcher
+            .update(GmemUpdate::new(0, 8192, PageType::Normal).source(&pages.0))
+            .unwrap();
+        launcher
+            .update(GmemUpdate::new(2, 4096, PageType::Zero))
+            .unwrap();
+
+        let (vm, _) = launcher.finish(Finish::builder().build().unwrap()).unwrap();
+
+        assert_eq!(vm.0.command_ids(), vec![22, 100, 101, 101, 101, 102]);
+        assert_eq!(
+            vm.0.commands()[1],
+            VmCommand::SetMemoryAttributes {
+                gpa: 0,
+                size: 0x10000,
+                attributes: KVM_MEMORY_ATTRIBUTE_PRIVATE,
+            }
+        );
+    }
+
+    #[test]
+    fn test_gmem_launch_rejected() {
+        let pages = Pages([0; 2 * PAGE_SIZE]);
+
+        let launcher = GmemLauncher::new(Loopback::new(), -1, GmemInit::default()).unwrap();
+        let start = Start::default().with_migration_agent(&pages.0);
+        assert_eq!(
+            launcher.start(start).err().unwrap().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        let launcher = GmemLauncher::new(Loopback::new(), -1, GmemInit::default()).unwrap();
+        let mut launcher = launcher.start(Start::default()).unwrap();
+        assert!(launcher
+            .update(GmemUpdate::new(0, 4096, PageType::Zero))
+            .is_err());
+    }
+}
@@ -0,0 +1,919 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! ID blocks for SNP_LAUNCH_FINISH.
+//!
+//! An ID block fixes the expected launch digest and policy of a guest and
+//! names it (family and image IDs, SVN). It is signed with the guest
+//! owner's ID key, which is in turn signed with an author key; the firmware
+//! checks both signatures when the launch finishes and reports the digests
+//! of the keys in attestation reports.
+//!
+//! An [IdBlockBuilder] encodes the ID block and the ID authentication
+//! structure, as modeled by [crate::measurement::idblock_types], signing
+//! with any [IdSigner]. ECDSA P-384 keys of OpenSSL (`openssl` feature) and
+//! of the `p384` crate (`crypto_nossl` feature) are signers.
+//!
+//! On the relying party's side, an [IdBlockVerifier] checks that a report
+//! comes from a guest launched with an ID block of the tenant: that the
+//! reported key digests are those of the tenant's ID and author keys, and
+//! that the signed ID block matches the launch digest, IDs, SVN and policy
+//! of the report.
+
+use crate::{
+    error::{IdBlockError, IdVerificationError},
+    firmware::guest::AttestationReport,
+    measurement::idblock_types::{
+        FamilyId, IdAuth, IdBlock, IdBlockLaunchDigest, ImageId, SevEcdsaKeyData, SevEcdsaPubKey,
+        SevEcdsaSig, CURVE_P384, DEFAULT_KEY_ALGO,
+    },
+    util::digest::sha384,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::convert::{TryFrom, TryInto};
+
+#[cfg(target_os = "linux")]
+use super::Finish;
+#[cfg(target_os = "linux")]
+use crate::launch::linux::snp::KVM_SEV_SNP_FINISH_DATA_SIZE;
+
+pub use super::{ID_AUTH_SIZE, ID_BLOCK_SIZE, ID_KEY_SIZE};
+
+use super::AUTHOR_KEY_OFFSET;
+
+/// An ECDSA P-384 public key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdPublicKey {
+    /// The big-endian x coordinate.
+    pub x: [u8; 48],
+
+    /// The big-endian y coordinate.
+    pub y: [u8; 48],
+}
+
+impl From<&IdPublicKey> for SevEcdsaPubKey {
+    fn from(key: &IdPublicKey) -> Self {
+        let mut data: SevEcdsaKeyData = SevEcdsaKeyData::default();
+        put_component(data.qx.as_mut_slice(), &key.x);
+        put_component(data.qy.as_mut_slice(), &key.y);
+
+        Self {
+            curve: CURVE_P384,
+            data,
+        }
+    }
+}
+
+impl TryFrom<&SevEcdsaPubKey> for IdPublicKey {
+    type Error = IdBlockError;
+
+    fn try_from(key: &SevEcdsaPubKey) -> Result<Self, Self::Error> {
+        if key.curve != CURVE_P384 {
+            return Err(IdBlockError::SevCurveError());
+        }
+
+        Ok(Self {
+            x: get_component(key.data.qx.as_slice()),
+            y: get_component(key.data.qy.as_slice()),
+        })
+    }
+}
+
+impl IdPublicKey {
+    /// Encode the key in the layout of the SEV-SNP Firmware ABI.
+    pub fn encode(&self) -> Result<[u8; ID_KEY_SIZE], IdBlockError> {
+        encode(&SevEcdsaPubKey::from(self))?
+            .try_into()
+            .map_err(|_| IdBlockError::SevCurveError())
+    }
+
+    /// Decode a key encoded in the layout of the SEV-SNP Firmware ABI.
+    pub fn decode(key: &[u8]) -> Result<Self, IdBlockError> {
+        Self::try_from(&decode::<SevEcdsaPubKey>(key)?)
+    }
+
+    /// The digest of the key, as reported in attestation reports.
+    pub fn digest(&self) -> Result<[u8; 48], IdBlockError> {
+        Ok(sha384(&[&self.encode()?]))
+    }
+
+    /// Whether `signature` is a signature of the SHA-384 digest of
+    /// `message` with this key.
+    #[cfg(feature = "openssl")]
+    pub fn verify(&self, message: &[u8], signature: &IdSignature) -> Result<bool, IdBlockError> {
+        use openssl::{
+            bn::BigNum,
+            ec::{EcGroup, EcKey},
+            ecdsa::EcdsaSig,
+            nid::Nid,
+        };
+
+        let group: EcGroup = EcGroup::from_curve_name(Nid::SECP384R1)?;
+        let (x, y): (BigNum, BigNum) = (BigNum::from_slice(&self.x)?, BigNum::from_slice(&self.y)?);
+        let key = EcKey::from_public_key_affine_coordinates(&group, &x, &y)?;
+        let signature: EcdsaSig = EcdsaSig::from_private_components(
+            BigNum::from_slice(&signature.r)?,
+            BigNum::from_slice(&signature.s)?,
+        )?;
+
+        Ok(signature.verify(&sha384(&[message]), &key)?)
+    }
+
+    /// Whether `signature` is a signature of the SHA-384 digest of
+    /// `message` with this key.
+    #[cfg(all(feature = "crypto_nossl", not(feature = "openssl")))]
+    pub fn verify(&self, message: &[u8], signature: &IdSignature) -> Result<bool, IdBlockError> {
+        use p384::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+        let point =
+            p384::EncodedPoint::from_affine_coordinates(&self.x.into(), &self.y.into(), false);
+        let key: VerifyingKey = VerifyingKey::from_encoded_point(&point)
+            .map_err(|e| IdBlockError::SevEcsdsaSigError(e.to_string()))?;
+        let signature: Signature = Signature::from_scalars(signature.r, signature.s)
+            .map_err(|e| IdBlockError::SevEcsdsaSigError(e.to_string()))?;
+
+        Ok(key.verify(message, &signature).is_ok())
+    }
+}
+
+/// An ECDSA P-384 signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdSignature {
+    /// The big-endian r component.
+    pub r: [u8; 48],
+
+    /// The big-endian s component.
+    pub s: [u8; 48],
+}
+
+impl From<&IdSignature> for SevEcdsaSig {
+    fn from(signature: &IdSignature) -> Self {
+        let mut sig: SevEcdsaSig = SevEcdsaSig::default();
+        put_component(sig.r.as_mut_slice(), &signature.r);
+        put_component(sig.s.as_mut_slice(), &signature.s);
+
+        sig
+    }
+}
+
+impl From<&SevEcdsaSig> for IdSignature {
+    fn from(sig: &SevEcdsaSig) -> Self {
+        Self {
+            r: get_component(sig.r.as_slice()),
+            s: get_component(sig.s.as_slice()),
+        }
+    }
+}
+
+impl IdSignature {
+    /// Decode a signature encoded in the layout of the SEV-SNP Firmware ABI.
+    pub fn decode(signature: &[u8]) -> Result<Self, IdBlockError> {
+        Ok(Self::from(&decode::<SevEcdsaSig>(signature)?))
+    }
+}
+
+/// Signs ID blocks and keys with an ECDSA P-384 key.
+pub trait IdSigner {
+    /// The public part of the key.
+    fn public_key(&self) -> Result<IdPublicKey, IdBlockError>;
+
+    /// Sign the SHA-384 digest of `message`.
+    fn sign(&self, message: &[u8]) -> Result<IdSignature, IdBlockError>;
+}
+
+/// Builds and signs an ID block.
+///
+/// # Example:
+/// ```ignore
+/// let id_key: EcKey<Private> = EcKey::private_key_from_pem(&id_pem)?;
+/// let author_key: EcKey<Private> = EcKey::private_key_from_pem(&author_pem)?;
+///
+/// let signed: SignedIdBlock = IdBlockBuilder::new(launch_digest, policy)
+///     .image_id(image_id)
+///     .guest_svn(2)
+///     .sign(&id_key, &author_key)?;
+///
+/// let (vm_fd, sev) = launcher.finish(signed.finish(host_data))?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdBlockBuilder {
+    launch_digest: [u8; 48],
+    family_id: [u8; 16],
+    image_id: [u8; 16],
+    guest_svn: u32,
+    policy: u64,
+}
+
+impl IdBlockBuilder {
+    /// Start an ID block for a guest which measures to `launch_digest` and
+    /// launches with the guest policy `policy`.
+    pub fn new(launch_digest: [u8; 48], policy: u64) -> Self {
+        Self {
+            launch_digest,
+            family_id: [0; 16],
+            image_id: [0; 16],
+            guest_svn: 0,
+            policy,
+        }
+    }
+
+    /// Set the family ID, which the firmware does not interpret.
+    pub fn family_id(mut self, family_id: [u8; 16]) -> Self {
+        self.family_id = family_id;
+        self
+    }
+
+    /// Set the image ID, which the firmware does not interpret.
+    pub fn image_id(mut self, image_id: [u8; 16]) -> Self {
+        self.image_id = image_id;
+        self
+    }
+
+    /// Set the security version number of the guest.
+    pub fn guest_svn(mut self, guest_svn: u32) -> Self {
+        self.guest_svn = guest_svn;
+        self
+    }
+
+    /// The ID block.
+    pub fn id_block(&self) -> Result<IdBlock, IdBlockError> {
+        IdBlock::new(
+            Some(IdBlockLaunchDigest::try_from(&self.launch_digest[..])?),
+            Some(FamilyId::new(self.family_id)),
+            Some(ImageId::new(self.image_id)),
+            Some(self.guest_svn),
+            Some(self.policy),
+        )
+    }
+
+    /// Sign the ID block with `id_key`, and the ID key with `author_key`.
+    pub fn sign(
+        &self,
+        id_key: &impl IdSigner,
+        author_key: &impl IdSigner,
+    ) -> Result<SignedIdBlock, IdBlockError> {
+        let id_block: Vec<u8> = encode(&self.id_block()?)?;
+        let id_pubkey: SevEcdsaPubKey = SevEcdsaPubKey::from(&id_key.public_key()?);
+        let author_pubkey: SevEcdsaPubKey = SevEcdsaPubKey::from(&author_key.public_key()?);
+
+        let id_block_sig: IdSignature = id_key.sign(&id_block)?;
+        let id_key_sig: IdSignature = author_key.sign(&encode(&id_pubkey)?)?;
+
+        let id_auth: IdAuth = IdAuth::new(
+            None,
+            None,
+            SevEcdsaSig::from(&id_block_sig),
+            id_pubkey,
+            SevEcdsaSig::from(&id_key_sig),
+            author_pubkey,
+        );
+
+        Ok(SignedIdBlock {
+            id_block,
+            id_auth: encode(&id_auth)?,
+        })
+    }
+}
+
+/// A signed ID block and its ID authentication structure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedIdBlock {
+    /// The encoded ID block.
+    pub id_block: Vec<u8>,
+
+    /// The encoded ID authentication structure.
+    pub id_auth: Vec<u8>,
+}
+
+impl SignedIdBlock {
+    /// Decode the ID block.
+    pub fn block(&self) -> Result<IdBlock, IdBlockError> {
+        decode(&self.id_block)
+    }
+
+    /// Decode the ID authentication structure.
+    pub fn auth(&self) -> Result<IdAuth, IdBlockError> {
+        decode(&self.id_auth)
+    }
+
+    /// The public ID key.
+    pub fn id_key(&self) -> Result<IdPublicKey, IdBlockError> {
+        IdPublicKey::try_from(&self.auth()?.id_pubkey)
+    }
+
+    /// The public author key.
+    pub fn author_key(&self) -> Result<IdPublicKey, IdBlockError> {
+        IdPublicKey::try_from(&self.auth()?.author_pub_key)
+    }
+
+    /// The digest of the ID key, as reported in attestation reports.
+    pub fn id_key_digest(&self) -> Result<[u8; 48], IdBlockError> {
+        Ok(sha384(&[&encode(&self.auth()?.id_pubkey)?]))
+    }
+
+    /// The digest of the author key, as reported in attestation reports.
+    pub fn author_key_digest(&self) -> Result<[u8; 48], IdBlockError> {
+        Ok(sha384(&[&encode(&self.auth()?.author_pub_key)?]))
+    }
+
+    /// The parameters to finish the launch with this ID block.
+    #[cfg(target_os = "linux")]
+    pub fn finish(&self, host_data: [u8; KVM_SEV_SNP_FINISH_DATA_SIZE]) -> Finish<'_, '_> {
+        Finish::new(Some(&self.id_block), Some(&self.id_auth), host_data)
+    }
+
+    /// The launch digest the guest is expected to measure to.
+    pub fn launch_digest(&self) -> Result<[u8; 48], IdBlockError> {
+        Ok(self.block()?.launch_digest.as_array())
+    }
+
+    /// The family ID of the guest.
+    pub fn family_id(&self) -> Result<[u8; 16], IdBlockError> {
+        Ok(self.block()?.family_id.as_array())
+    }
+
+    /// The image ID of the guest.
+    pub fn image_id(&self) -> Result<[u8; 16], IdBlockError> {
+        Ok(self.block()?.image_id.as_array())
+    }
+
+    /// The security version number of the guest.
+    pub fn guest_svn(&self) -> Result<u32, IdBlockError> {
+        Ok(self.block()?.guest_svn)
+    }
+
+    /// The guest policy the guest must launch with.
+    pub fn policy(&self) -> Result<u64, IdBlockError> {
+        Ok(self.block()?.policy)
+    }
+
+    /// Check that the ID block is signed with the ID key of the ID
+    /// authentication structure.
+    pub fn verify_id_block(&self) -> Result<(), IdVerificationError> {
+        let auth: IdAuth = self.checked_auth(false)?;
+
+        let id_key: IdPublicKey = IdPublicKey::try_from(&auth.id_pubkey)?;
+        let signature: IdSignature = IdSignature::from(&auth.id_block_sig);
+
+        match id_key.verify(&self.id_block, &signature)? {
+            true => Ok(()),
+            false => Err(IdVerificationError::IdBlockSignature),
+        }
+    }
+
+    /// Check that the ID key is signed with the author key of the ID
+    /// authentication structure.
+    pub fn verify_id_key(&self) -> Result<(), IdVerificationError> {
+        let auth: IdAuth = self.checked_auth(true)?;
+
+        let author_key: IdPublicKey = IdPublicKey::try_from(&auth.author_pub_key)?;
+        let signature: IdSignature = IdSignature::from(&auth.id_key_sig);
+
+        match author_key.verify(&encode(&auth.id_pubkey)?, &signature)? {
+            true => Ok(()),
+            false => Err(IdVerificationError::IdKeySignature),
+        }
+    }
+
+    /// Whether the ID authentication structure holds an author key.
+    pub fn has_author_key(&self) -> bool {
+        self.id_auth
+            .get(AUTHOR_KEY_OFFSET..AUTHOR_KEY_OFFSET + ID_KEY_SIZE)
+            .is_some_and(|key| key.iter().any(|b| *b != 0))
+    }
+
+    /// Decode the ID authentication structure, after checking the sizes of
+    /// the ID block and the structure, and the algorithms of its keys. The
+    /// author key's algorithm is only checked if the structure holds an
+    /// author key or `author` requires one.
+    fn checked_auth(&self, author: bool) -> Result<IdAuth, IdVerificationError> {
+        if self.id_block.len() != ID_BLOCK_SIZE {
+            return Err(IdVerificationError::IdBlockSize {
+                len: self.id_block.len(),
+            });
+        }
+
+        if self.id_auth.len() != ID_AUTH_SIZE {
+            return Err(IdVerificationError::IdAuthSize {
+                len: self.id_auth.len(),
+            });
+        }
+
+        let auth: IdAuth = self.auth()?;
+        if auth.id_key_algo != DEFAULT_KEY_ALGO {
+            return Err(IdVerificationError::UnsupportedAlgorithm(auth.id_key_algo));
+        }
+
+        if (author || self.has_author_key()) && auth.author_key_algo != DEFAULT_KEY_ALGO {
+            return Err(IdVerificationError::UnsupportedAlgorithm(
+                auth.author_key_algo,
+            ));
+        }
+
+        Ok(auth)
+    }
+}
+
+/// Checks that attestation reports come from guests launched with an ID
+/// block of the tenant.
+///
+/// # Example:
+/// ```ignore
+/// let verifier = IdBlockVerifier::new(id_key)
+///     .author_key(author_key)
+///     .id_block(signed)
+///     .min_guest_svn(2);
+///
+/// // After verifying the report's signature:
+/// verifier.verify(&report)?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdBlockVerifier {
+    id_key: IdPublicKey,
+    author_key: Option<IdPublicKey>,
+    id_block: Option<SignedIdBlock>,
+    min_guest_svn: u32,
+}
+
+impl IdBlockVerifier {
+    /// Accept reports of guests launched with an ID block signed with
+    /// `id_key`.
+    pub fn new(id_key: IdPublicKey) -> Self {
+        Self {
+            id_key,
+            author_key: None,
+            id_block: None,
+            min_guest_svn: 0,
+        }
+    }
+
+    /// Also require the ID key to be signed with `author_key`.
+    pub fn author_key(mut self, author_key: IdPublicKey) -> Self {
+        self.author_key = Some(author_key);
+        self
+    }
+
+    /// Also require the guest to be launched with `id_block`, which must be
+    /// signed with the ID key (and the author key, if any), and match the
+    /// launch digest, family and image IDs, SVN and policy of the report.
+    pub fn id_block(mut self, id_block: SignedIdBlock) -> Self {
+        self.id_block = Some(id_block);
+        self
+    }
+
+    /// Also require the guest SVN to be at least `min_guest_svn`.
+    pub fn min_guest_svn(mut self, min_guest_svn: u32) -> Self {
+        self.min_guest_svn = min_guest_svn;
+        self
+    }
+
+    /// Check `report` against the keys and ID block. The report's own
+    /// signature must be verified beforehand.
+    pub fn verify(&self, report: &AttestationReport) -> Result<(), IdVerificationError> {
+        if report.id_key_digest.as_bytes()[..] != self.id_key.digest()?[..] {
+            return Err(IdVerificationError::IdKeyDigest);
+        }
+
+        if let Some(author_key) = &self.author_key {
+            if report.key_info.author_key_en() == 0 {
+                return Err(IdVerificationError::AuthorKeyDisabled);
+            }
+
+            if report.author_key_digest.as_bytes()[..] != author_key.digest()?[..] {
+                return Err(IdVerificationError::AuthorKeyDigest);
+            }
+        }
+
+        if report.guest_svn < self.min_guest_svn {
+            return Err(IdVerificationError::GuestSvn {
+                reported: report.guest_svn,
+                minimum: self.min_guest_svn,
+            });
+        }
+
+        match &self.id_block {
+            Some(id_block) => self.verify_id_block(id_block, report),
+            None => Ok(()),
+        }
+    }
+
+    fn verify_id_block(
+        &self,
+        id_block: &SignedIdBlock,
+        report: &AttestationReport,
+    ) -> Result<(), IdVerificationError> {
+        id_block.verify_id_block()?;
+        if id_block.id_key()? != self.id_key {
+            return Err(IdVerificationError::IdKeyMismatch);
+        }
+
+        if let Some(author_key) = &self.author_key {
+            id_block.verify_id_key()?;
+            if id_block.author_key()? != *author_key {
+                return Err(IdVerificationError::AuthorKeyMismatch);
+            }
+        }
+
+        let block: IdBlock = id_block.block()?;
+
+        let mismatch: Option<&'static str> =
+            if *report.measurement.as_bytes() != block.launch_digest.as_array() {
+                Some("launch digest")
+            } else if report.family_id != block.family_id.as_array() {
+                Some("family ID")
+            } else if report.image_id != block.image_id.as_array() {
+                Some("image ID")
+            } else if report.guest_svn != block.guest_svn {
+                Some("guest SVN")
+            } else if u64::from(report.policy) != block.policy {
+                Some("guest policy")
+            } else {
+                None
+            };
+
+        match mismatch {
+            Some(field) => Err(IdVerificationError::Mismatch(field)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Encode a structure of the SEV-SNP Firmware ABI.
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, IdBlockError> {
+    bincode::serialize(value).map_err(|e| IdBlockError::BincodeError(*e))
+}
+
+/// Decode a structure of the SEV-SNP Firmware ABI from the start of
+/// `bytes`.
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, IdBlockError> {
+    bincode::deserialize(bytes).map_err(|e| IdBlockError::BincodeError(*e))
+}
+
+/// Write the big-endian `component` as a little-endian, zero-padded
+/// component at the start of `dest`.
+fn put_component(dest: &mut [u8], component: &[u8; 48]) {
+    for (dest, byte) in dest.iter_mut().zip(component.iter().rev()) {
+        *dest = *byte;
+    }
+}
+
+/// Read the little-endian component at the start of `src` as big-endian.
+fn get_component(src: &[u8]) -> [u8; 48] {
+    let mut component: [u8; 48] = [0; 48];
+    for (dest, byte) in component.iter_mut().zip(src[..48].iter().rev()) {
+        *dest = *byte;
+    }
+
+    component
+}
+
+#[cfg(feature = "openssl")]
+fn padded<const N: usize>(num: &openssl::bn::BigNumRef) -> Result<[u8; N], IdBlockError> {
+    let bytes: Vec<u8> = num.to_vec_padded(N as i32)?;
+    let mut padded: [u8; N] = [0; N];
+    padded.copy_from_slice(&bytes);
+
+    Ok(padded)
+}
+
+#[cfg(feature = "openssl")]
+impl IdSigner for openssl::ec::EcKey<openssl::pkey::Private> {
+    fn public_key(&self) -> Result<IdPublicKey, IdBlockError> {
+        use openssl::{bn::BigNum, bn::BigNumContext, ec::EcKeyRef, nid::Nid};
+
+        if self.group().curve_name() != Some(Nid::SECP384R1) {
+            return Err(IdBlockError::SevCurveError());
+        }
+
+        let mut ctx: BigNumContext = BigNumContext::new()?;
+        let (mut x, mut y): (BigNum, BigNum) = (BigNum::new()?, BigNum::new()?);
+        EcKeyRef::public_key(self).affine_coordinates(self.group(), &mut x, &mut y, &mut ctx)?;
+
+        Ok(IdPublicKey {
+            x: padded(&x)?,
+            y: padded(&y)?,
+        })
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<IdSignature, IdBlockError> {
+        let signature = openssl::ecdsa::EcdsaSig::sign(&sha384(&[message]), self)?;
+
+        Ok(IdSignature {
+            r: padded(signature.r())?,
+            s: padded(signature.s())?,
+        })
+    }
+}
+
+#[cfg(feature = "crypto_nossl")]
+impl IdSigner for p384::ecdsa::SigningKey {
+    fn public_key(&self) -> Result<IdPublicKey, IdBlockError> {
+        let point = self.verifying_key().to_encoded_point(false);
+
+        match (point.x(), point.y()) {
+            (Some(x), Some(y)) => Ok(IdPublicKey {
+                x: (*x).into(),
+                y: (*y).into(),
+            }),
+            _ => Err(IdBlockError::SevCurveError()),
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<IdSignature, IdBlockError> {
+        use p384::ecdsa::{signature::Signer, Signature};
+
+        let signature: Signature = self
+            .try_sign(message)
+            .map_err(|e| IdBlockError::SevEcsdsaSigError(e.to_string()))?;
+        let (r, s) = signature.split_bytes();
+
+        Ok(IdSignature {
+            r: r.into(),
+            s: s.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "openssl")]
+mod test {
+    use super::*;
+
+    use openssl::{
+        bn::BigNum,
+        ec::{EcGroup, EcKey},
+        ecdsa::EcdsaSig,
+        nid::Nid,
+        pkey::Private,
+    };
+
+    fn key() -> EcKey<Private> {
+        let group: EcGroup = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        EcKey::generate(&group).unwrap()
+    }
+
+    fn component(bytes: &[u8]) -> BigNum {
+        let mut big_endian: Vec<u8> = bytes[..48].to_vec();
+        big_endian.reverse();
+        BigNum::from_slice(&big_endian).unwrap()
+    }
+
+    #[test]
+    fn test_id_block_layout() {
+        let builder: IdBlockBuilder = IdBlockBuilder::new([0xaa; 48], 0x30000)
+            .family_id([1; 16])
+            .image_id([2; 16])
+            .guest_svn(3);
+
+        let block: Vec<u8> = encode(&builder.id_block().unwrap()).unwrap();
+
+        assert_eq!(block.len(), ID_BLOCK_SIZE);
+        assert_eq!(block[0x00..0x30], [0xaa; 48]);
+        assert_eq!(block[0x30..0x40], [1; 16]);
+        assert_eq!(block[0x40..0x50], [2; 16]);
+        assert_eq!(block[0x50..0x54], 1u32.to_le_bytes());
+        assert_eq!(block[0x54..0x58], 3u32.to_le_bytes());
+        assert_eq!(block[0x58..0x60], 0x30000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_sign() {
+        let (id_key, author_key) = (key(), key());
+
+        let signed: SignedIdBlock = IdBlockBuilder::new([0; 48], 0x30000)
+            .sign(&id_key, &author_key)
+            .unwrap();
+
+        assert_eq!(signed.id_block.len(), ID_BLOCK_SIZE);
+        assert_eq!(signed.id_auth.len(), ID_AUTH_SIZE);
+        assert_eq!(signed.id_auth[0..8], [1, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(signed.id_auth[0x240..0x244], CURVE_P384.to_le_bytes());
+        assert_eq!(signed.id_key().unwrap(), id_key.public_key().unwrap());
+        assert_eq!(
+            signed.author_key().unwrap(),
+            author_key.public_key().unwrap()
+        );
+
+        let sig: &[u8] = &signed.id_auth[0x40..];
+        let id_block_sig: EcdsaSig =
+            EcdsaSig::from_private_components(component(sig), component(&sig[0x48..])).unwrap();
+        assert!(id_block_sig
+            .verify(&sha384(&[&signed.id_block]), &id_key)
+            .unwrap());
+
+        let sig: &[u8] = &signed.id_auth[0x680..];
+        let id_key_sig: EcdsaSig =
+            EcdsaSig::from_private_components(component(sig), component(&sig[0x48..])).unwrap();
+        assert!(id_key_sig
+            .verify(&sha384(&[&signed.id_auth[0x240..0x644]]), &author_key)
+            .unwrap());
+    }
+
+    fn report(
+        signed: &SignedIdBlock,
+        id_key: &EcKey<Private>,
+        author_key: &EcKey<Private>,
+    ) -> AttestationReport {
+        let mut report: AttestationReport = AttestationReport::default();
+        report
+            .measurement
+            .0
+            .copy_from_slice(&signed.launch_digest().unwrap());
+        report.family_id = signed.family_id().unwrap();
+        report.image_id = signed.image_id().unwrap();
+        report.guest_svn = signed.guest_svn().unwrap();
+        report.policy = signed.policy().unwrap().into();
+        report.key_info = 1.into();
+        report
+            .id_key_digest
+            .0
+            .copy_from_slice(&id_key.public_key().unwrap().digest().unwrap());
+        report
+            .author_key_digest
+            .0
+            .copy_from_slice(&author_key.public_key().unwrap().digest().unwrap());
+
+        report
+    }
+
+    #[test]
+    fn test_decode() {
+        let key: EcKey<Private> = key();
+        let public: IdPublicKey = key.public_key().unwrap();
+
+        assert_eq!(
+            IdPublicKey::decode(&public.encode().unwrap()).unwrap(),
+            public
+        );
+        assert!(IdPublicKey::decode(&[0; ID_KEY_SIZE]).is_err());
+        assert!(IdPublicKey::decode(&[2, 0, 0, 0]).is_err());
+
+        let signature: IdSignature = key.sign(b"message").unwrap();
+        let encoded: Vec<u8> = encode(&SevEcdsaSig::from(&signature)).unwrap();
+        assert_eq!(encoded.len(), 0x200);
+        assert_eq!(IdSignature::decode(&encoded).unwrap(), signature);
+        assert!(matches!(
+            IdSignature::decode(&encoded[..0x8f]),
+            Err(IdBlockError::BincodeError(_))
+        ));
+
+        let signed: SignedIdBlock = IdBlockBuilder::new([0xaa; 48], 0x30000)
+            .family_id([1; 16])
+            .image_id([2; 16])
+            .guest_svn(3)
+            .sign(&key, &key)
+            .unwrap();
+
+        assert_eq!(signed.launch_digest().unwrap(), [0xaa; 48]);
+        assert_eq!(signed.family_id().unwrap(), [1; 16]);
+        assert_eq!(signed.image_id().unwrap(), [2; 16]);
+        assert_eq!(signed.guest_svn().unwrap(), 3);
+        assert_eq!(signed.policy().unwrap(), 0x30000);
+        assert_eq!(public.digest().unwrap(), signed.id_key_digest().unwrap());
+
+        let mut truncated: SignedIdBlock = signed;
+        truncated.id_block.truncate(0x50);
+        assert!(truncated.policy().is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let (id_key, author_key) = (key(), key());
+        let (id_public, author_public) = (
+            id_key.public_key().unwrap(),
+            author_key.public_key().unwrap(),
+        );
+
+        let signed: SignedIdBlock = IdBlockBuilder::new([0xaa; 48], 0x30000)
+            .image_id([2; 16])
+            .guest_svn(3)
+            .sign(&id_key, &author_key)
+            .unwrap();
+        signed.verify_id_block().unwrap();
+        signed.verify_id_key().unwrap();
+
+        let report: AttestationReport = report(&signed, &id_key, &author_key);
+
+        let verifier: IdBlockVerifier = IdBlockVerifier::new(id_public)
+            .author_key(author_public)
+            .id_block(signed.clone())
+            .min_guest_svn(3);
+        verifier.verify(&report).unwrap();
+
+        let mut unauthored: AttestationReport = report;
+        unauthored.key_info = 0.into();
+        IdBlockVerifier::new(id_public).verify(&unauthored).unwrap();
+        assert!(matches!(
+            verifier.verify(&unauthored),
+            Err(IdVerificationError::AuthorKeyDisabled)
+        ));
+
+        assert!(matches!(
+            IdBlockVerifier::new(author_public).verify(&report),
+            Err(IdVerificationError::IdKeyDigest)
+        ));
+        assert!(matches!(
+            IdBlockVerifier::new(id_public)
+                .author_key(id_public)
+                .verify(&report),
+            Err(IdVerificationError::AuthorKeyDigest)
+        ));
+        assert!(matches!(
+            verifier.clone().min_guest_svn(4).verify(&report),
+            Err(IdVerificationError::GuestSvn {
+                reported: 3,
+                minimum: 4
+            })
+        ));
+
+        let mut relaunched: AttestationReport = report;
+        relaunched.policy = 0x30001.into();
+        assert!(matches!(
+            verifier.verify(&relaunched),
+            Err(IdVerificationError::Mismatch("guest policy"))
+        ));
+
+        let mut remeasured: AttestationReport = report;
+        remeasured.measurement.0[0] = 0;
+        assert!(matches!(
+            verifier.verify(&remeasured),
+            Err(IdVerificationError::Mismatch("launch digest"))
+        ));
+    }
+
+    #[test]
+    fn test_verify_without_author_key() {
+        let id_key: EcKey<Private> = key();
+
+        // An ID authentication structure without an author key, as signed
+        // for SNP_LAUNCH_FINISH with AUTHOR_KEY_EN cleared.
+        let mut signed: SignedIdBlock = IdBlockBuilder::new([0xaa; 48], 0x30000)
+            .sign(&id_key, &key())
+            .unwrap();
+        signed.id_auth[0x04..0x08].fill(0);
+        signed.id_auth[0x680..0xc84].fill(0);
+        assert!(!signed.has_author_key());
+
+        signed.verify_id_block().unwrap();
+        assert!(matches!(
+            signed.verify_id_key(),
+            Err(IdVerificationError::UnsupportedAlgorithm(0))
+        ));
+
+        let mut report: AttestationReport = report(&signed, &id_key, &id_key);
+        report.key_info = 0.into();
+        report.author_key_digest = Default::default();
+
+        let id_public: IdPublicKey = id_key.public_key().unwrap();
+        IdBlockVerifier::new(id_public)
+            .id_block(signed.clone())
+            .verify(&report)
+            .unwrap();
+        assert!(matches!(
+            IdBlockVerifier::new(id_public)
+                .author_key(id_public)
+                .id_block(signed.clone())
+                .verify(&report),
+            Err(IdVerificationError::AuthorKeyDisabled)
+        ));
+
+        // An author key without a supported algorithm is still rejected.
+        signed.id_auth[0x880] = 2;
+        assert!(matches!(
+            signed.verify_id_block(),
+            Err(IdVerificationError::UnsupportedAlgorithm(0))
+        ));
+    }
+
+    #[test]
+    fn test_verify_signatures() {
+        let (id_key, author_key) = (key(), key());
+
+        let signed: SignedIdBlock = IdBlockBuilder::new([0; 48], 0x30000)
+            .sign(&id_key, &author_key)
+            .unwrap();
+
+        let mut forged: SignedIdBlock = signed.clone();
+        forged.id_block[0x54] = 1;
+        assert!(matches!(
+            forged.verify_id_block(),
+            Err(IdVerificationError::IdBlockSignature)
+        ));
+
+        let mut forged: SignedIdBlock = signed.clone();
+        let other: [u8; ID_KEY_SIZE] = key().public_key().unwrap().encode().unwrap();
+        forged.id_auth[AUTHOR_KEY_OFFSET..AUTHOR_KEY_OFFSET + ID_KEY_SIZE].copy_from_slice(&other);
+        assert!(matches!(
+            forged.verify_id_key(),
+            Err(IdVerificationError::IdKeySignature)
+        ));
+
+        let mut truncated: SignedIdBlock = signed;
+        truncated.id_auth.truncate(0x800);
+        assert!(matches!(
+            truncated.verify_id_block(),
+            Err(IdVerificationError::IdAuthSize { len: 0x800 })
+        ));
+    }
+}
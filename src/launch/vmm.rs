@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The interface launchers issue their commands through.
+//!
+//! On Linux, launch commands are `KVM_MEMORY_ENCRYPT_OP` ioctls on the VM's
+//! file descriptor, which name the SEV device's file descriptor to prove the
+//! caller may use the platform. Any pair of [AsRawFd] types (e.g. a
+//! `kvm_ioctls::VmFd` and a `std::fs::File` of `/dev/sev`) implements
+//! [VmHandle] and [SevDevice] this way. VMMs which do not hand out a KVM
+//! file descriptor, and tests, implement [VmHandle] themselves, e.g. with a
+//! [Loopback].
+
+use crate::launch::linux::ioctl::{max_vcpus, KvmEncRegion, KvmMemoryAttributes, ENCRYPT_OP};
+
+use std::{
+    collections::HashMap,
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+};
+
+/// A command for the SEV firmware, laid out as the kernel's
+/// `struct kvm_sev_cmd`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EncryptOp {
+    /// The command ID, as numbered by the kernel's `enum sev_cmd_id`.
+    pub id: u32,
+
+    /// The address of the command's parameters.
+    pub data: u64,
+
+    /// The firmware's status code, set when the command fails.
+    pub error: u32,
+
+    /// The file descriptor of the SEV device.
+    pub sev_fd: u32,
+}
+
+/// The SEV device which authorizes launch commands.
+pub trait SevDevice {
+    /// The file descriptor named by each command.
+    fn sev_fd(&self) -> RawFd;
+}
+
+impl<T: AsRawFd> SevDevice for T {
+    fn sev_fd(&self) -> RawFd {
+        self.as_raw_fd()
+    }
+}
+
+/// The virtual machine launch commands are issued against.
+pub trait VmHandle {
+    /// Issue `op` to the SEV firmware. When the firmware rejects the
+    /// command, its status code is left in [EncryptOp::error].
+    fn encrypt_op(&mut self, op: &mut EncryptOp) -> io::Result<()>;
+
+    /// Register `size` bytes of guest memory at `addr` as encrypted.
+    fn register_region(&mut self, addr: u64, size: u64) -> io::Result<()>;
+
+    /// Set the attributes (e.g. [KVM_MEMORY_ATTRIBUTE_PRIVATE]) of `size`
+    /// bytes of guest physical memory at `gpa`. Only VMs backed by
+    /// guest_memfd support memory attributes.
+    fn set_memory_attributes(&mut self, gpa: u64, size: u64, attributes: u64) -> io::Result<()> {
+        let _ = (gpa, size, attributes);
+
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory attributes are not supported",
+        ))
+    }
+
+    /// The largest number of vCPUs the VM supports, which bounds the number
+    /// of VMSAs an SEV-ES launch encrypts. Defaults to [KVM_MAX_VCPUS].
+    fn max_vcpus(&mut self) -> io::Result<u32> {
+        Ok(KVM_MAX_VCPUS)
+    }
+}
+
+/// The most vCPUs x86 KVM supports in any VM.
+pub const KVM_MAX_VCPUS: u32 = 4096;
+
+/// The memory attribute marking guest memory private, i.e. backed by
+/// guest_memfd and inaccessible to the host.
+pub const KVM_MEMORY_ATTRIBUTE_PRIVATE: u64 = 1 << 3;
+
+impl<T: AsRawFd> VmHandle for T {
+    fn encrypt_op(&mut self, op: &mut EncryptOp) -> io::Result<()> {
+        ENCRYPT_OP.ioctl(self, op)?;
+        Ok(())
+    }
+
+    fn register_region(&mut self, addr: u64, size: u64) -> io::Result<()> {
+        KvmEncRegion::from_raw(addr, size).register(self)
+    }
+
+    fn set_memory_attributes(&mut self, gpa: u64, size: u64, attributes: u64) -> io::Result<()> {
+        KvmMemoryAttributes::new(gpa, size, attributes).set(self)
+    }
+
+    fn max_vcpus(&mut self) -> io::Result<u32> {
+        max_vcpus(self)
+    }
+}
+
+/// A command received by a [Loopback].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VmCommand {
+    /// A command for the SEV firmware.
+    EncryptOp {
+        /// The command ID.
+        id: u32,
+
+        /// The file descriptor of the SEV device.
+        sev_fd: u32,
+    },
+
+    /// The registration of an encrypted memory region.
+    RegisterRegion {
+        /// The address of the region.
+        addr: u64,
+
+        /// The size of the region in bytes.
+        size: u64,
+    },
+
+    /// A change of the attributes of guest memory.
+    SetMemoryAttributes {
+        /// The guest physical address of the memory.
+        gpa: u64,
+
+        /// The size of the memory in bytes.
+        size: u64,
+
+        /// The attributes.
+        attributes: u64,
+    },
+}
+
+/// A [VmHandle] which records commands instead of issuing them, for
+/// asserting on the commands a launcher issues in tests.
+///
+/// Commands succeed without touching their parameters, unless scripted to
+/// fail with [fail](Self::fail).
+///
+/// # Example:
+/// ```ignore
+/// let mut launcher = Launcher::new(Loopback::new(), -1).unwrap();
+///
+/// assert_eq!(launcher.as_mut_vmfd().commands().len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Loopback {
+    commands: Vec<VmCommand>,
+    failures: HashMap<u32, u32>,
+}
+
+impl Loopback {
+    /// Create a loopback which has received no commands.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail every command with ID `id`, reporting the firmware status
+    /// code `error`.
+    pub fn fail(&mut self, id: u32, error: u32) -> &mut Self {
+        self.failures.insert(id, error);
+        self
+    }
+
+    /// The commands received so far, in order.
+    pub fn commands(&self) -> &[VmCommand] {
+        &self.commands
+    }
+
+    /// The IDs of the firmware commands received so far, in order.
+    pub fn command_ids(&self) -> Vec<u32> {
+        self.commands
+            .iter()
+            .filter_map(|command| match command {
+                VmCommand::EncryptOp { id, .. } => Some(*id),
+                VmCommand::RegisterRegion { .. } | VmCommand::SetMemoryAttributes { .. } => None,
+            })
+            .collect()
+    }
+}
+
+impl VmHandle for Loopback {
+    fn encrypt_op(&mut self, op: &mut EncryptOp) -> io::Result<()> {
+        self.commands.push(VmCommand::EncryptOp {
+            id: op.id,
+            sev_fd: op.sev_fd,
+        });
+
+        match self.failures.get(&op.id) {
+            Some(error) => {
+                op.error = *error;
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "loopback command failed",
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn register_region(&mut self, addr: u64, size: u64) -> io::Result<()> {
+        self.commands.push(VmCommand::RegisterRegion { addr, size });
+        Ok(())
+    }
+
+    fn set_memory_attributes(&mut self, gpa: u64, size: u64, attributes: u64) -> io::Result<()> {
+        self.commands.push(VmCommand::SetMemoryAttributes {
+            gpa,
+            size,
+            attributes,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "sev")]
+mod test {
+    use super::*;
+
+    use crate::launch::sev::{Launcher, Start};
+
+    use codicon::Decoder;
+
+    const SEV_FD: RawFd = 7;
+
+    #[test]
+    fn test_loopback_launch() {
+        let zeroes = [0u8; std::mem::size_of::<Start>()];
+        let start: Start = Start::decode(&mut &zeroes[..], ()).unwrap();
+        let data = [0u8; 4096];
+
+        let mut launcher = Launcher::new(Loopback::new(), SEV_FD)
+            .unwrap()
+            .start(start)
+            .unwrap();
+        launcher.update_data(&data).unwrap();
+        let mut launcher = launcher.measure().unwrap();
+
+        assert_eq!(
+            launcher.as_mut_vmfd().commands(),
+            &[
+                VmCommand::EncryptOp { id: 0, sev_fd: 7 },
+                VmCommand::EncryptOp { id: 2, sev_fd: 7 },
+                VmCommand::RegisterRegion {
+                    addr: data.as_ptr() as u64,
+                    size: 4096,
+                },
+                VmCommand::EncryptOp { id: 3, sev_fd: 7 },
+                VmCommand::EncryptOp { id: 6, sev_fd: 7 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loopback_failure() {
+        let mut vm: Loopback = Loopback::new();
+        vm.fail(0, 1);
+
+        assert!(Launcher::new(vm, SEV_FD).is_err());
+    }
+}
@@ -58,6 +58,23 @@
 //! etc.) are used. `openssl` and `crypto_nossl` are mutually exclusive,
 //! and enabling both at the same time leads to a compiler error.
 //!
+//! ## Optional Features
+//!
+//! - `snp-types`: the SEV-SNP report types only, `no_std` with `alloc`.
+//! - `kvm`: launch guests from a `kvm_ioctls::VmFd` (`launch::kvm`).
+//! - `tracing`: a `tracing` span around every firmware and KVM ioctl.
+//! - `metrics`: per-command metrics of firmware handles (`firmware::metrics`).
+//! - `otel`: export those metrics as OpenTelemetry instruments.
+//! - `proto`: Protocol Buffers messages of SEV-SNP evidence (`proto`).
+//! - `cbor`: CBOR and CMW encodings of SEV-SNP evidence (`cbor`).
+//! - `cose`: sign encoded evidence into `COSE_Sign1` messages.
+//! - `arbitrary`: `Arbitrary` instances of the core types (`arbitrary`).
+//! - `proptest`: proptest strategies for the same types.
+//! - `simulation`: sign synthetic reports for tests; not for production.
+//! - `igvm`: measure and launch IGVM files (`igvm`).
+//! - `parallel`: hash guest pages in parallel when measuring.
+//! - `capi`: the C API described below.
+//!
 //! ## Remarks
 //!
 //! Note that the linux kernel provides access to these APIs through a set
@@ -79,6 +96,7 @@
 //! [firmware]: ./src/firmware/
 //! [launch]: ./src/launch/
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(clippy::all)]
 #![deny(missing_docs)]
 #![allow(unknown_lints)]
@@ -90,27 +108,56 @@ compile_error!(
     "feature \"openssl\" and feature \"crypto_nossl\" cannot be enabled at the same time"
 );
 
+extern crate alloc;
+
 /// SEV and SEV-SNP certificates interface.
 pub mod certs;
 
+pub mod encoding;
+
+#[cfg(all(feature = "std", feature = "snp"))]
+pub mod appraisal;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+#[cfg(all(
+    feature = "capi",
+    feature = "snp",
+    any(feature = "openssl", feature = "crypto_nossl")
+))]
+pub mod capi;
 pub mod firmware;
-#[cfg(target_os = "linux")]
+#[cfg(all(feature = "std", feature = "snp"))]
+pub mod fleet;
+#[cfg(feature = "igvm")]
+pub mod igvm;
+#[cfg(all(target_os = "linux", feature = "std"))]
 pub mod launch;
 #[cfg(all(
-    any(feature = "sev", feature = "snp"),
-    feature = "openssl",
+    any(feature = "sev", feature = "snp-types"),
+    any(feature = "openssl", feature = "crypto_nossl"),
     target_os = "linux"
 ))]
 pub mod measurement;
+#[cfg(feature = "proto")]
+pub mod proto;
+
+#[cfg(feature = "cbor")]
+pub mod cbor;
 #[cfg(all(target_os = "linux", feature = "openssl", feature = "sev"))]
 pub mod session;
+#[cfg(feature = "std")]
+pub mod snapshot;
 mod util;
+#[cfg(feature = "std")]
 pub mod vmsa;
 
 /// Error module.
+#[cfg(feature = "std")]
 pub mod error;
 
+#[cfg(feature = "std")]
 pub use util::cached_chain;
+#[cfg(feature = "std")]
 use util::{TypeLoad, TypeSave};
 
 #[cfg(all(feature = "openssl", feature = "sev"))]
@@ -131,9 +178,13 @@ use certs::snp::builtin as SnpBuiltin;
 #[cfg(all(feature = "sev", target_os = "linux"))]
 use crate::{certs::sev::sev::Certificate as SevCertificate, error::Indeterminate, launch::sev::*};
 
-#[cfg(any(feature = "sev", feature = "snp"))]
-use std::convert::TryFrom;
+#[cfg(any(feature = "sev", feature = "snp-types"))]
+use core::convert::TryFrom;
+
+#[cfg(any(feature = "sev", feature = "snp-types"))]
+use alloc::string::{String, ToString};
 
+#[cfg(feature = "std")]
 use std::io::{Read, Write};
 
 #[cfg(all(feature = "sev", target_os = "linux"))]
@@ -165,8 +216,8 @@ pub struct Version {
     pub minor: u8,
 }
 
-impl std::fmt::Display for Version {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Version {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{}.{}", self.major, self.minor)
     }
 }
@@ -191,12 +242,19 @@ pub struct Build {
     pub build: u8,
 }
 
-impl std::fmt::Display for Build {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl From<Build> for Version {
+    fn from(build: Build) -> Self {
+        build.version
+    }
+}
+
+impl core::fmt::Display for Build {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{}.{}", self.version, self.build)
     }
 }
 
+#[cfg(feature = "std")]
 impl codicon::Decoder<()> for Build {
     type Error = std::io::Error;
 
@@ -205,6 +263,7 @@ impl codicon::Decoder<()> for Build {
     }
 }
 
+#[cfg(feature = "std")]
 impl codicon::Encoder<()> for Build {
     type Error = std::io::Error;
 
@@ -213,6 +272,41 @@ impl codicon::Encoder<()> for Build {
     }
 }
 
+/// How strictly the binary decoders of this crate validate their input.
+///
+/// The default options reject versions this crate does not know but accept
+/// nonzero reserved bytes, as the firmware has never been required to clear
+/// them. Verifiers which must not accept anything outside the specification
+/// use [ParseOptions::strict], while forensic tools which want to load
+/// malformed blobs anyway use [ParseOptions::lenient].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Reject input with a nonzero reserved byte.
+    pub strict_reserved: bool,
+
+    /// Decode versions this crate does not know with the layout of the
+    /// latest version it knows, rather than rejecting them.
+    pub allow_unknown_versions: bool,
+}
+
+impl ParseOptions {
+    /// Reject unknown versions and nonzero reserved bytes.
+    pub const fn strict() -> Self {
+        Self {
+            strict_reserved: true,
+            allow_unknown_versions: false,
+        }
+    }
+
+    /// Accept unknown versions and nonzero reserved bytes.
+    pub const fn lenient() -> Self {
+        Self {
+            strict_reserved: false,
+            allow_unknown_versions: true,
+        }
+    }
+}
+
 /// A representation for EPYC generational product lines.
 ///
 /// Implements type conversion traits to determine which generation
@@ -253,7 +347,7 @@ impl codicon::Encoder<()> for Build {
 /// }
 /// # }
 /// ```
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Generation {
     /// First generation EPYC (SEV).
     #[cfg(feature = "sev")]
@@ -264,11 +358,11 @@ pub enum Generation {
     Rome,
 
     /// Third generation EPYC (SEV, SEV-ES, SEV-SNP).
-    #[cfg(any(feature = "sev", feature = "snp"))]
+    #[cfg(any(feature = "sev", feature = "snp-types"))]
     Milan,
 
     /// Fourth generation EPYC (SEV, SEV-ES, SEV-SNP).
-    #[cfg(any(feature = "sev", feature = "snp"))]
+    #[cfg(any(feature = "sev", feature = "snp-types"))]
     Genoa,
 }
 
@@ -282,9 +376,9 @@ impl From<Generation> for CertSevCaChain {
             Generation::Naples => (SevBuiltin::naples::ARK, SevBuiltin::naples::ASK),
             #[cfg(feature = "sev")]
             Generation::Rome => (SevBuiltin::rome::ARK, SevBuiltin::rome::ASK),
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             Generation::Milan => (SevBuiltin::milan::ARK, SevBuiltin::milan::ASK),
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             Generation::Genoa => (SevBuiltin::genoa::ARK, SevBuiltin::genoa::ASK),
         };
 
@@ -339,7 +433,7 @@ impl TryFrom<&sev::Chain> for Generation {
     }
 }
 
-#[cfg(any(feature = "sev", feature = "snp"))]
+#[cfg(any(feature = "sev", feature = "snp-types"))]
 impl TryFrom<String> for Generation {
     type Error = ();
 
@@ -351,16 +445,16 @@ impl TryFrom<String> for Generation {
             #[cfg(feature = "sev")]
             "rome" => Ok(Self::Rome),
 
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             "milan" => Ok(Self::Milan),
 
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             "genoa" => Ok(Self::Genoa),
 
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             "bergamo" => Ok(Self::Genoa),
 
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             "siena" => Ok(Self::Genoa),
 
             _ => Err(()),
@@ -368,7 +462,7 @@ impl TryFrom<String> for Generation {
     }
 }
 
-#[cfg(any(feature = "sev", feature = "snp"))]
+#[cfg(any(feature = "sev", feature = "snp-types"))]
 impl Generation {
     /// Create a title-cased string identifying the SEV generation.
     pub fn titlecase(&self) -> String {
@@ -379,10 +473,10 @@ impl Generation {
             #[cfg(feature = "sev")]
             Self::Rome => "Rome".to_string(),
 
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             Self::Milan => "Milan".to_string(),
 
-            #[cfg(any(feature = "sev", feature = "snp"))]
+            #[cfg(any(feature = "sev", feature = "snp-types"))]
             Self::Genoa => "Genoa".to_string(),
         }
     }
@@ -0,0 +1,260 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Operations to calculate the expected launch digest of an OVMF guest in
+//! any SEV mode.
+//!
+//! Tenants booting guests the way QEMU does, with OVMF and optionally a
+//! kernel, initrd and command line measured through the SEV hashes table,
+//! can derive the launch digest they expect from the same inputs without
+//! booting a reference guest.
+use crate::{
+    error::MeasurementError,
+    firmware::guest::AttestationReport,
+    measurement::{
+        gctx::LD_SIZE,
+        sev::{
+            sev_calc_launch_digest, seves_calc_launch_digest, SevEsMeasurementArgs,
+            SevMeasurementArgs,
+        },
+        snp::{calc_snp_ovmf_hash, snp_calc_launch_digest, SnpMeasurementArgs},
+        vcpu_types::CpuType,
+        vmsa::{GuestFeatures, SevMode, VMMType},
+    },
+};
+
+use std::{convert::TryFrom, path::PathBuf};
+
+/// Arguments required to calculate the measurement of a guest in any SEV
+/// mode
+#[derive(Clone)]
+pub struct MeasurementArgs<'a> {
+    /// SEV mode of the guest
+    pub mode: SevMode,
+    /// Number of vcpus (SEV-ES and SEV-SNP only)
+    pub vcpus: u32,
+    /// vcpu type (SEV-ES and SEV-SNP only)
+    pub vcpu_type: CpuType,
+    /// Path to OVMF file
+    pub ovmf_file: PathBuf,
+    /// Active kernel guest features (SEV-SNP only)
+    pub guest_features: GuestFeatures,
+    /// Path to kernel file
+    pub kernel_file: Option<PathBuf>,
+    /// Path to initrd file
+    pub initrd_file: Option<PathBuf>,
+    /// Append arguments for kernel
+    pub append: Option<&'a str>,
+    /// Already calculated ovmf hash (SEV-SNP only)
+    pub ovmf_hash_str: Option<&'a str>,
+    /// vmm type (SEV-ES and SEV-SNP only)
+    pub vmm_type: Option<VMMType>,
+}
+
+/// Calculate the launch digest of a guest in the SEV mode of `args`: 32
+/// bytes for SEV and SEV-ES guests, 48 bytes for SEV-SNP guests.
+pub fn calc_launch_digest(args: MeasurementArgs) -> Result<Vec<u8>, MeasurementError> {
+    let ld: Vec<u8> = match args.mode {
+        SevMode::Sev => sev_calc_launch_digest(SevMeasurementArgs {
+            ovmf_file: args.ovmf_file,
+            kernel_file: args.kernel_file,
+            initrd_file: args.initrd_file,
+            append: args.append,
+        })?
+        .to_vec(),
+        SevMode::SevEs => seves_calc_launch_digest(SevEsMeasurementArgs {
+            vcpus: args.vcpus,
+            vcpu_type: args.vcpu_type,
+            ovmf_file: args.ovmf_file,
+            kernel_file: args.kernel_file,
+            initrd_file: args.initrd_file,
+            append: args.append,
+            vmm_type: args.vmm_type,
+        })?
+        .to_vec(),
+        SevMode::SevSnp => snp_calc_launch_digest(SnpMeasurementArgs {
+            vcpus: args.vcpus,
+            vcpu_type: args.vcpu_type,
+            ovmf_file: args.ovmf_file,
+            guest_features: args.guest_features,
+            kernel_file: args.kernel_file,
+            initrd_file: args.initrd_file,
+            append: args.append,
+            ovmf_hash_str: args.ovmf_hash_str,
+            vmm_type: args.vmm_type,
+        })?
+        .to_vec(),
+    };
+
+    Ok(ld)
+}
+
+/// The largest vCPU count tried when looking for the cause of a mismatch
+const MAX_PROBED_VCPUS: u32 = 64;
+
+/// Guest features which the host may enable by default, e.g. through KVM or
+/// the guest policy, tried when looking for the cause of a mismatch
+const PROBED_GUEST_FEATURES: [u64; 6] = [1 << 2, 1 << 3, 1 << 4, 1 << 5, 1 << 6, 1 << 7];
+
+/// The input which most likely explains a launch digest mismatch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MismatchCause {
+    /// The guest was launched from a different OVMF image than the given
+    /// precomputed OVMF hash
+    Ovmf,
+    /// The guest was launched without the kernel hashes table
+    KernelHashes,
+    /// The guest was launched with this many vCPUs
+    VcpuCount(u32),
+    /// The guest was launched with this vCPU type
+    VcpuType(CpuType),
+    /// The guest's VMSAs were launched with these guest features, e.g.
+    /// because the host enabled a feature by default
+    GuestFeatures(GuestFeatures),
+    /// The guest was launched by this VMM
+    VmmType(VMMType),
+    /// No other input explains the mismatch, so the contents of the OVMF
+    /// image, kernel, initrd or command line most likely differ
+    Unknown,
+}
+
+/// The outcome of verifying an attestation report against the expected
+/// launch digest
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verification {
+    /// The report's measurement is the expected launch digest
+    Match,
+    /// The report's measurement is not the expected launch digest
+    Mismatch {
+        /// Expected launch digest
+        expected: [u8; LD_SIZE],
+        /// Most likely cause of the mismatch
+        cause: MismatchCause,
+    },
+}
+
+impl Verification {
+    /// Check whether the measurement matched
+    pub fn is_match(&self) -> bool {
+        *self == Verification::Match
+    }
+}
+
+/// Calculate the SEV-SNP launch digest of `args` on top of the OVMF hash `ovmf_hash`
+fn snp_digest(args: &MeasurementArgs, ovmf_hash: &str) -> Result<[u8; LD_SIZE], MeasurementError> {
+    snp_calc_launch_digest(SnpMeasurementArgs {
+        vcpus: args.vcpus,
+        vcpu_type: args.vcpu_type,
+        ovmf_file: args.ovmf_file.clone(),
+        guest_features: args.guest_features,
+        kernel_file: args.kernel_file.clone(),
+        initrd_file: args.initrd_file.clone(),
+        append: args.append,
+        ovmf_hash_str: Some(ovmf_hash),
+        vmm_type: args.vmm_type,
+    })
+}
+
+/// Verify the measurement of an SEV-SNP attestation report against the
+/// launch digest expected from `args`.
+///
+/// On a mismatch, the launch digest is recalculated with each input varied
+/// in turn (vCPU count and type, guest features, VMM and kernel hashes) to
+/// find the one the report was most likely launched with.
+pub fn verify_snp(
+    report: &AttestationReport,
+    args: MeasurementArgs,
+) -> Result<Verification, MeasurementError> {
+    if args.mode != SevMode::SevSnp {
+        return Err(MeasurementError::InvalidSevModeError(format!(
+            "{:?}",
+            args.mode
+        )));
+    }
+
+    let ovmf_hash: String = hex::encode(calc_snp_ovmf_hash(args.ovmf_file.clone())?);
+    let seed: String = match args.ovmf_hash_str {
+        Some(hash) => hash.to_lowercase(),
+        None => ovmf_hash.clone(),
+    };
+
+    let expected = snp_digest(&args, &seed)?;
+    if expected == *report.measurement.as_bytes() {
+        return Ok(Verification::Match);
+    }
+
+    let matches = |probe: &MeasurementArgs, hash: &str| -> Result<bool, MeasurementError> {
+        Ok(snp_digest(probe, hash)? == *report.measurement.as_bytes())
+    };
+    let mismatch = |cause: MismatchCause| Ok(Verification::Mismatch { expected, cause });
+
+    if seed != ovmf_hash && matches(&args, &ovmf_hash)? {
+        return mismatch(MismatchCause::Ovmf);
+    }
+
+    for vcpus in (1..=MAX_PROBED_VCPUS).filter(|vcpus| *vcpus != args.vcpus) {
+        if matches(
+            &MeasurementArgs {
+                vcpus,
+                ..args.clone()
+            },
+            &seed,
+        )? {
+            return mismatch(MismatchCause::VcpuCount(vcpus));
+        }
+    }
+
+    for vcpu_type in (0..=u8::MAX).filter_map(|value| CpuType::try_from(value).ok()) {
+        if vcpu_type.sig() != args.vcpu_type.sig()
+            && matches(
+                &MeasurementArgs {
+                    vcpu_type,
+                    ..args.clone()
+                },
+                &seed,
+            )?
+        {
+            return mismatch(MismatchCause::VcpuType(vcpu_type));
+        }
+    }
+
+    for feature in PROBED_GUEST_FEATURES {
+        let guest_features = GuestFeatures(args.guest_features.0 ^ feature);
+        if matches(
+            &MeasurementArgs {
+                guest_features,
+                ..args.clone()
+            },
+            &seed,
+        )? {
+            return mismatch(MismatchCause::GuestFeatures(guest_features));
+        }
+    }
+
+    for vmm_type in [VMMType::QEMU, VMMType::EC2, VMMType::KRUN] {
+        if Some(vmm_type) != args.vmm_type.or(Some(VMMType::QEMU))
+            && matches(
+                &MeasurementArgs {
+                    vmm_type: Some(vmm_type),
+                    ..args.clone()
+                },
+                &seed,
+            )?
+        {
+            return mismatch(MismatchCause::VmmType(vmm_type));
+        }
+    }
+
+    if args.kernel_file.is_some() {
+        let probe = MeasurementArgs {
+            kernel_file: None,
+            initrd_file: None,
+            append: None,
+            ..args.clone()
+        };
+        if matches(&probe, &seed)? {
+            return mismatch(MismatchCause::KernelHashes);
+        }
+    }
+
+    mismatch(MismatchCause::Unknown)
+}
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hash algorithms the launch measurements are calculated with.
+//!
+//! The hashes are computed with OpenSSL under the `openssl` feature and with
+//! the pure-Rust `sha2` crate under `crypto_nossl`, so that precomputing a
+//! measurement does not depend on which cryptographic library the crate was
+//! built with. Callers with a faster implementation, e.g. one offloading
+//! SHA-384 to a hardware accelerator, can pass it to the measurements which
+//! take a [DigestBackend]; everything else hashes with [default_backend].
+//!
+//! # Example:
+//! ```ignore
+//! static ACCELERATED: Accelerated = Accelerated;
+//!
+//! let ld = snp_calc_launch_digest_with(args, &ACCELERATED)?;
+//! ```
+
+/// A hash being computed incrementally, producing an `N` bytes digest
+pub trait Hasher<const N: usize>: Send {
+    /// Hash `data` on top of the data hashed so far.
+    fn update(&mut self, data: &[u8]);
+
+    /// The digest of all the data hashed.
+    fn finish(self: Box<Self>) -> [u8; N];
+}
+
+/// An implementation of the hash algorithms used by the measurements
+pub trait DigestBackend: Send + Sync {
+    /// Start a SHA-256 hash.
+    fn sha256(&self) -> Box<dyn Hasher<32>>;
+
+    /// Start a SHA-384 hash.
+    fn sha384(&self) -> Box<dyn Hasher<48>>;
+}
+
+/// The backend of the cryptographic library the crate was built with.
+pub fn default_backend() -> &'static dyn DigestBackend {
+    #[cfg(feature = "openssl")]
+    {
+        &OpenSsl
+    }
+
+    #[cfg(not(feature = "openssl"))]
+    {
+        &RustCrypto
+    }
+}
+
+/// Hashes computed with OpenSSL
+#[cfg(feature = "openssl")]
+struct OpenSsl;
+
+#[cfg(feature = "openssl")]
+impl Hasher<32> for openssl::sha::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        openssl::sha::Sha256::update(self, data)
+    }
+
+    fn finish(self: Box<Self>) -> [u8; 32] {
+        openssl::sha::Sha256::finish(*self)
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl Hasher<48> for openssl::sha::Sha384 {
+    fn update(&mut self, data: &[u8]) {
+        openssl::sha::Sha384::update(self, data)
+    }
+
+    fn finish(self: Box<Self>) -> [u8; 48] {
+        openssl::sha::Sha384::finish(*self)
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl DigestBackend for OpenSsl {
+    fn sha256(&self) -> Box<dyn Hasher<32>> {
+        Box::new(openssl::sha::Sha256::new())
+    }
+
+    fn sha384(&self) -> Box<dyn Hasher<48>> {
+        Box::new(openssl::sha::Sha384::new())
+    }
+}
+
+/// Hashes computed with the RustCrypto `sha2` crate
+#[cfg(not(feature = "openssl"))]
+struct RustCrypto;
+
+#[cfg(not(feature = "openssl"))]
+impl Hasher<32> for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data)
+    }
+
+    fn finish(self: Box<Self>) -> [u8; 32] {
+        sha2::Digest::finalize(*self).into()
+    }
+}
+
+#[cfg(not(feature = "openssl"))]
+impl Hasher<48> for sha2::Sha384 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data)
+    }
+
+    fn finish(self: Box<Self>) -> [u8; 48] {
+        sha2::Digest::finalize(*self).into()
+    }
+}
+
+#[cfg(not(feature = "openssl"))]
+impl DigestBackend for RustCrypto {
+    fn sha256(&self) -> Box<dyn Hasher<32>> {
+        Box::new(<sha2::Sha256 as sha2::Digest>::new())
+    }
+
+    fn sha384(&self) -> Box<dyn Hasher<48>> {
+        Box::new(<sha2::Sha384 as sha2::Digest>::new())
+    }
+}
+
+/// A SHA-256 hash computed with a [DigestBackend]
+pub struct Sha256(Box<dyn Hasher<32>>);
+
+impl Sha256 {
+    /// Start a hash with the default backend.
+    pub fn new() -> Self {
+        Self::with_backend(default_backend())
+    }
+
+    /// Start a hash with `backend`.
+    pub fn with_backend(backend: &dyn DigestBackend) -> Self {
+        Self(backend.sha256())
+    }
+
+    /// Hash `data` on top of the data hashed so far.
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    /// The digest of all the data hashed.
+    pub fn finish(self) -> [u8; 32] {
+        self.0.finish()
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A SHA-384 hash computed with a [DigestBackend]
+pub struct Sha384(Box<dyn Hasher<48>>);
+
+impl Sha384 {
+    /// Start a hash with the default backend.
+    pub fn new() -> Self {
+        Self::with_backend(default_backend())
+    }
+
+    /// Start a hash with `backend`.
+    pub fn with_backend(backend: &dyn DigestBackend) -> Self {
+        Self(backend.sha384())
+    }
+
+    /// Hash `data` on top of the data hashed so far.
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    /// The digest of all the data hashed.
+    pub fn finish(self) -> [u8; 48] {
+        self.0.finish()
+    }
+}
+
+impl Default for Sha384 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The SHA-256 digest of `data`, computed with the default backend.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    hash.update(data);
+    hash.finish()
+}
+
+/// The SHA-384 digest of `data`, computed with the default backend.
+pub fn sha384(data: &[u8]) -> [u8; 48] {
+    let mut hash = Sha384::new();
+    hash.update(data);
+    hash.finish()
+}
@@ -2,15 +2,15 @@
 //! Operations to handle and create a Guest Context
 use std::convert::TryInto;
 
-use openssl::sha::sha384;
+use crate::measurement::digest::{default_backend, DigestBackend, Sha384};
 
 use crate::error::*;
 
 #[cfg(target_os = "linux")]
-use crate::launch::snp::PageType;
+use crate::{launch::snp::PageType, measurement::vmsa::VMSA};
 
-// Launch digest size in bytes
-pub(crate) const LD_SIZE: usize = 384 / 8;
+/// Launch digest size in bytes
+pub const LD_SIZE: usize = 384 / 8;
 
 // VMSA page is recorded in the RMP table with GPA (u64)(-1).
 // However, the address is page-aligned, and also all the bits above
@@ -20,6 +20,33 @@ pub(crate) const VMSA_GPA: u64 = 0xFFFFFFFFF000;
 // Launch digest intialized in all zeros
 const ZEROS: [u8; LD_SIZE] = [0; LD_SIZE];
 
+/// SHA-384 digest of `data`, computed with `backend`.
+fn sha384(backend: &dyn DigestBackend, data: &[u8]) -> [u8; LD_SIZE] {
+    let mut hash = Sha384::with_backend(backend);
+    hash.update(data);
+    hash.finish()
+}
+
+/// SHA-384 digest of each 4K page of `data`, hashed in parallel with the
+/// `parallel` feature.
+fn page_digests(backend: &dyn DigestBackend, data: &[u8]) -> Vec<[u8; LD_SIZE]> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+
+        data.par_chunks(4096)
+            .map(|page| sha384(backend, page))
+            .collect()
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        data.chunks(4096)
+            .map(|page| sha384(backend, page))
+            .collect()
+    }
+}
+
 fn validate_block_size(length: usize) -> Result<(), GCTXError> {
     if (length % 4096) != 0 {
         Err(GCTXError::InvalidBlockSize)
@@ -35,6 +62,8 @@ pub(crate) struct Completed;
 pub struct Gctx<T> {
     /// Launch Digest, 48 bytes long
     ld: [u8; LD_SIZE],
+    /// Backend the pages are hashed with
+    backend: &'static dyn DigestBackend,
     _state: T,
 }
 
@@ -43,6 +72,7 @@ impl Default for Gctx<Updating> {
     fn default() -> Self {
         Self {
             ld: ZEROS,
+            backend: default_backend(),
             _state: Updating,
         }
     }
@@ -53,10 +83,16 @@ impl Gctx<Updating> {
     pub fn new(seed: &[u8]) -> Result<Self, MeasurementError> {
         Ok(Self {
             ld: seed.try_into()?,
+            backend: default_backend(),
             _state: Updating,
         })
     }
 
+    /// Hash the pages with `backend` instead of the default backend.
+    pub fn with_backend(self, backend: &'static dyn DigestBackend) -> Self {
+        Self { backend, ..self }
+    }
+
     /// Will update guest context launch digest with provided data from page
     fn update(&mut self, page_type: u8, gpa: u64, contents: &[u8]) -> Result<(), GCTXError> {
         let page_info_len: u16 = 0x70;
@@ -85,7 +121,7 @@ impl Gctx<Updating> {
                 page_info_len as usize,
             ));
         }
-        self.ld = sha384(&page_info);
+        self.ld = sha384(self.backend, &page_info);
 
         Ok(())
     }
@@ -104,15 +140,12 @@ impl Gctx<Updating> {
             PageType::Normal => {
                 if let Some(data) = contents {
                     validate_block_size(data.len())?;
-                    let mut offset = 0;
-                    while offset < data.len() {
-                        let page_data = &data[offset..offset + 4096];
+                    for (index, digest) in page_digests(self.backend, data).iter().enumerate() {
                         self.update(
                             page_type as u8,
-                            gpa + offset as u64,
-                            sha384(page_data).as_slice(),
+                            gpa + (index * 4096) as u64,
+                            digest.as_slice(),
                         )?;
-                        offset += 4096;
                     }
                     Ok(())
                 } else {
@@ -123,7 +156,11 @@ impl Gctx<Updating> {
             PageType::Vmsa => {
                 if let Some(data) = contents {
                     validate_block_size(data.len())?;
-                    self.update(page_type as u8, VMSA_GPA, sha384(data).as_slice())?;
+                    self.update(
+                        page_type as u8,
+                        VMSA_GPA,
+                        sha384(self.backend, data).as_slice(),
+                    )?;
                     Ok(())
                 } else {
                     Err(GCTXError::MissingData)
@@ -161,10 +198,28 @@ impl Gctx<Updating> {
         }
     }
 
+    /// Update launch digest with the VMSA pages of `vcpus` vCPUs, whose
+    /// digests were computed ahead of time.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn update_vmsas(
+        &mut self,
+        digests: &VmsaDigests,
+        vcpus: usize,
+    ) -> Result<(), GCTXError> {
+        for index in 0..vcpus {
+            if let Some(digest) = digests.get(index) {
+                self.update(PageType::Vmsa as u8, VMSA_GPA, digest.as_slice())?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Update is done and now we switch to a completed state
     pub(crate) fn finished(&self) -> Gctx<Completed> {
         Gctx {
             ld: self.ld,
+            backend: self.backend,
             _state: Completed,
         }
     }
@@ -176,3 +231,210 @@ impl Gctx<Completed> {
         &self.ld
     }
 }
+
+/// A page update replayed by a [LaunchDigest], as issued to
+/// SNP_LAUNCH_UPDATE without IMI pages or VMPL permissions.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageUpdate<'a> {
+    /// Pages of data, measured by their contents.
+    Normal {
+        /// The guest physical address of the first page.
+        gpa: u64,
+
+        /// The contents of the pages.
+        data: &'a [u8],
+    },
+
+    /// Pages of zeroes.
+    Zero {
+        /// The guest physical address of the first page.
+        gpa: u64,
+
+        /// The length of the range in bytes.
+        len: usize,
+    },
+
+    /// Pages which are encrypted but not measured.
+    Unmeasured {
+        /// The guest physical address of the first page.
+        gpa: u64,
+
+        /// The length of the range in bytes.
+        len: usize,
+    },
+
+    /// The secrets page.
+    Secrets {
+        /// The guest physical address of the page.
+        gpa: u64,
+    },
+
+    /// The CPUID page.
+    Cpuid {
+        /// The guest physical address of the page.
+        gpa: u64,
+    },
+
+    /// The VMSA page of a vCPU.
+    Vmsa {
+        /// The contents of the page.
+        page: &'a [u8],
+    },
+}
+
+/// Predicts the launch digest of an SNP guest by replaying the firmware's
+/// measurement of each page update, in the order a VMM issues them.
+///
+/// # Example:
+/// ```ignore
+/// let mut digest: LaunchDigest = LaunchDigest::new();
+///
+/// digest.update(&PageUpdate::Normal { gpa: 0xffc0_0000, data: &firmware })?;
+/// digest.update(&PageUpdate::Secrets { gpa: 0x80_0000 })?;
+/// digest.vmsas(&vmsa, 4)?;
+///
+/// assert_eq!(digest.digest(), report.measurement);
+/// ```
+#[cfg(target_os = "linux")]
+pub struct LaunchDigest {
+    gctx: Gctx<Updating>,
+}
+
+#[cfg(target_os = "linux")]
+impl Default for LaunchDigest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl LaunchDigest {
+    /// Begin a launch, with a launch digest of all zeroes.
+    pub fn new() -> Self {
+        Self {
+            gctx: Gctx::default(),
+        }
+    }
+
+    /// Continue from the launch digest `seed`, e.g. one computed for a
+    /// firmware image ahead of time.
+    pub fn from_seed(seed: [u8; LD_SIZE]) -> Self {
+        Self {
+            gctx: Gctx {
+                ld: seed,
+                backend: default_backend(),
+                _state: Updating,
+            },
+        }
+    }
+
+    /// Hash the pages with `backend` instead of the default backend.
+    pub fn with_backend(self, backend: &'static dyn DigestBackend) -> Self {
+        Self {
+            gctx: self.gctx.with_backend(backend),
+        }
+    }
+
+    /// Measure `update`.
+    pub fn update(&mut self, update: &PageUpdate) -> Result<(), GCTXError> {
+        match *update {
+            PageUpdate::Normal { gpa, data } => {
+                self.gctx
+                    .update_page(PageType::Normal, gpa, Some(data), None)
+            }
+            PageUpdate::Zero { gpa, len } => {
+                self.gctx.update_page(PageType::Zero, gpa, None, Some(len))
+            }
+            PageUpdate::Unmeasured { gpa, len } => {
+                validate_block_size(len)?;
+                for offset in (0..len as u64).step_by(4096) {
+                    self.gctx
+                        .update_page(PageType::Unmeasured, gpa + offset, None, None)?;
+                }
+                Ok(())
+            }
+            PageUpdate::Secrets { gpa } => {
+                self.gctx.update_page(PageType::Secrets, gpa, None, None)
+            }
+            PageUpdate::Cpuid { gpa } => self.gctx.update_page(PageType::Cpuid, gpa, None, None),
+            PageUpdate::Vmsa { page } => {
+                self.gctx
+                    .update_page(PageType::Vmsa, VMSA_GPA, Some(page), None)
+            }
+        }
+    }
+
+    /// Measure each of `updates` in order.
+    pub fn replay<'a>(
+        &mut self,
+        updates: impl IntoIterator<Item = &'a PageUpdate<'a>>,
+    ) -> Result<(), GCTXError> {
+        for update in updates {
+            self.update(update)?;
+        }
+
+        Ok(())
+    }
+
+    /// Measure the VMSA page of each of `vcpus` vCPUs.
+    pub fn vmsas(&mut self, vmsa: &VMSA, vcpus: usize) -> Result<(), MeasurementError> {
+        self.vmsa_digests(&VmsaDigests::with_backend(vmsa, self.gctx.backend)?, vcpus)
+    }
+
+    /// Measure the VMSA page of each of `vcpus` vCPUs from digests computed
+    /// ahead of time, e.g. when measuring the same guest with varying vCPU
+    /// counts.
+    pub fn vmsa_digests(
+        &mut self,
+        digests: &VmsaDigests,
+        vcpus: usize,
+    ) -> Result<(), MeasurementError> {
+        self.gctx.update_vmsas(digests, vcpus)?;
+
+        Ok(())
+    }
+
+    /// The launch digest of the updates measured so far.
+    pub fn digest(&self) -> [u8; LD_SIZE] {
+        self.gctx.ld
+    }
+}
+
+/// SHA-384 digests of the VMSA pages of a guest's vCPUs.
+///
+/// Every AP starts from the same VMSA page, so its digest is computed once
+/// however many vCPUs the guest has.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VmsaDigests {
+    bsp: [u8; LD_SIZE],
+    ap: Option<[u8; LD_SIZE]>,
+}
+
+#[cfg(target_os = "linux")]
+impl VmsaDigests {
+    /// Serialize and hash the BSP and AP pages of `vmsa`.
+    pub fn new(vmsa: &VMSA) -> Result<Self, MeasurementError> {
+        Self::with_backend(vmsa, default_backend())
+    }
+
+    /// Serialize the BSP and AP pages of `vmsa` and hash them with `backend`.
+    pub fn with_backend(
+        vmsa: &VMSA,
+        backend: &dyn DigestBackend,
+    ) -> Result<Self, MeasurementError> {
+        Ok(Self {
+            bsp: sha384(backend, &vmsa.bsp_page()?),
+            ap: vmsa.ap_page()?.map(|page| sha384(backend, &page)),
+        })
+    }
+
+    /// The digest of the VMSA page of vCPU `index`, if it has one.
+    pub fn get(&self, index: usize) -> Option<&[u8; LD_SIZE]> {
+        match index {
+            0 => Some(&self.bsp),
+            _ => self.ap.as_ref(),
+        }
+    }
+}
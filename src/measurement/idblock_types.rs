@@ -2,6 +2,7 @@
 
 //! Different structures needed to calculate the different pieces needed for ID calculation for pre-attestation
 
+#[cfg(feature = "openssl")]
 use openssl::{
     bn::{BigNum, BigNumContext},
     ec::{EcGroup, EcKey},
@@ -19,6 +20,7 @@ use crate::{error::IdBlockError, measurement::large_array::LargeArray};
 pub(crate) const DEFAULT_ID_VERSION: u32 = 1;
 pub(crate) const DEFAULT_ID_POLICY: u64 = 0x300000;
 
+#[cfg(feature = "openssl")]
 pub(crate) const CURVE_P384_NID: Nid = openssl::nid::Nid::SECP384R1;
 pub(crate) const DEFAULT_KEY_ALGO: u32 = 1;
 pub(crate) const CURVE_P384: u32 = 2;
@@ -67,6 +69,11 @@ impl IdBlockLaunchDigest {
     pub fn new(data: LargeArray<u8, ID_BLK_DIGEST_BYTES>) -> Self {
         Self(data)
     }
+
+    /// Get the Launch Digest as a regular array
+    pub fn as_array(&self) -> [u8; ID_BLK_DIGEST_BYTES] {
+        self.0.as_array()
+    }
 }
 
 /// Family ID of the guest, provided by the guest owner and uninterpreted by the firmware.
@@ -74,21 +81,46 @@ impl IdBlockLaunchDigest {
 #[derive(Default, Serialize, Deserialize, Clone, Copy)]
 pub struct FamilyId([u8; ID_BLK_ID_BYTES]);
 
+impl FamilyId {
+    /// Create Family ID from an array
+    pub fn new(data: [u8; ID_BLK_ID_BYTES]) -> Self {
+        Self(data)
+    }
+
+    /// Get the Family ID as a regular array
+    pub fn as_array(&self) -> [u8; ID_BLK_ID_BYTES] {
+        self.0
+    }
+}
+
 /// Image ID to be provided to the ID-BLOCK
 #[repr(C)]
 #[derive(Default, Serialize, Deserialize, Clone, Copy)]
 pub struct ImageId([u8; ID_BLK_ID_BYTES]);
 
+impl ImageId {
+    /// Create Image ID from an array
+    pub fn new(data: [u8; ID_BLK_ID_BYTES]) -> Self {
+        Self(data)
+    }
+
+    /// Get the Image ID as a regular array
+    pub fn as_array(&self) -> [u8; ID_BLK_ID_BYTES] {
+        self.0
+    }
+}
+
 /// The way the ECDSA SEV signature is strucutred. Need it in this format to calculate the AUTH-ID.
 #[repr(C)]
 #[derive(Default, Serialize, Deserialize, Clone, Copy)]
 pub struct SevEcdsaSig {
-    r: LargeArray<u8, ECDSA_POINT_SIZE_BYTES>,
-    s: LargeArray<u8, ECDSA_POINT_SIZE_BYTES>,
+    pub(crate) r: LargeArray<u8, ECDSA_POINT_SIZE_BYTES>,
+    pub(crate) s: LargeArray<u8, ECDSA_POINT_SIZE_BYTES>,
     reserved: LargeArray<u8, ECDSA_SIG_RESERVED>,
 }
 
 // Derive SEV ECDSA signature from a private EC KEY
+#[cfg(feature = "openssl")]
 impl TryFrom<(EcKey<Private>, &[u8])> for SevEcdsaSig {
     type Error = IdBlockError;
 
@@ -163,6 +195,7 @@ pub struct SevEcdsaPubKey {
 }
 
 // Create SEV ECDSA public key from EC private key
+#[cfg(feature = "openssl")]
 impl TryFrom<&EcKey<Private>> for SevEcdsaPubKey {
     type Error = IdBlockError;
 
@@ -3,7 +3,9 @@
 //! Everything one needs to calculate a launch measurement for a SEV encrypted confidential guest.
 //! This includes, GCTX, SEV-HASHES, VMSA and OVMF pages.
 
-#[cfg(all(target_os = "linux", feature = "snp", feature = "openssl"))]
+pub mod digest;
+
+#[cfg(all(target_os = "linux", feature = "snp"))]
 pub mod gctx;
 
 #[cfg(any(feature = "sev", feature = "snp"))]
@@ -12,22 +14,37 @@ pub mod ovmf;
 #[cfg(any(feature = "sev", feature = "snp"))]
 pub mod vmsa;
 
-#[cfg(all(any(feature = "sev", feature = "snp"), feature = "openssl"))]
+#[cfg(any(feature = "sev", feature = "snp"))]
 pub mod sev_hashes;
 
+#[cfg(all(target_os = "linux", feature = "snp"))]
+pub mod ovmf_hash;
+
 #[cfg(any(feature = "sev", feature = "snp"))]
 pub mod vcpu_types;
 
-#[cfg(all(feature = "snp", feature = "openssl"))]
+#[cfg(feature = "snp")]
 pub mod snp;
 
-#[cfg(all(feature = "sev", feature = "openssl"))]
+#[cfg(all(target_os = "linux", feature = "snp"))]
+pub mod svsm;
+
+#[cfg(feature = "sev")]
 pub mod sev;
 
+#[cfg(all(feature = "sev", feature = "snp"))]
+pub mod calc;
+
+#[cfg(all(feature = "sev", feature = "snp"))]
+pub use calc::verify_snp;
+
+#[cfg(all(feature = "sev", feature = "snp"))]
+pub mod recipe;
+
 #[cfg(all(feature = "snp", feature = "openssl"))]
 pub mod idblock;
 
-#[cfg(all(feature = "snp", feature = "openssl"))]
+#[cfg(feature = "snp")]
 pub mod idblock_types;
 
 pub mod large_array;
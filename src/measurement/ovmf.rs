@@ -6,7 +6,6 @@ use bincode;
 use byteorder::{ByteOrder, LittleEndian};
 use serde::Deserialize;
 use std::{
-    collections::HashMap,
     convert::{TryFrom, TryInto},
     fs::File,
     io::Read,
@@ -148,9 +147,111 @@ impl TryFrom<&[u8]> for OvmfFooterTableEntry {
     }
 }
 
+/// A typed entry of the OVMF footer (GUID) table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OvmfTableEntry {
+    /// Location of the SEV hashes table, where the kernel, initrd and cmdline hashes are placed
+    SevHashTable {
+        /// Guest Physical Address
+        gpa: u32,
+        /// Size
+        size: u32,
+    },
+    /// Location of the area the guest owner's launch secret is injected into
+    SevSecretBlock {
+        /// Guest Physical Address
+        gpa: u32,
+        /// Size
+        size: u32,
+    },
+    /// Reset vector of the SEV-ES application processors
+    SevEsResetBlock {
+        /// Instruction pointer of the reset vector
+        eip: u32,
+    },
+    /// Location of the SEV metadata, which describes the SNP pages
+    SevMetadata {
+        /// Offset from the end of the image
+        offset_from_end: u32,
+    },
+    /// Entry which is not interpreted
+    Unknown {
+        /// GUID
+        guid: Uuid,
+        /// Entry data
+        data: Vec<u8>,
+    },
+}
+
+impl OvmfTableEntry {
+    fn parse(guid: Uuid, data: &[u8]) -> Result<Self, OVMFError> {
+        let u32_at = |at: usize| {
+            data.get(at..at + 4)
+                .map(LittleEndian::read_u32)
+                .ok_or(OVMFError::GetTableItemError)
+        };
+
+        Ok(match guid {
+            SEV_HASH_TABLE_RV_GUID => OvmfTableEntry::SevHashTable {
+                gpa: u32_at(0)?,
+                size: u32_at(4)?,
+            },
+            SEV_SECRET_BLOCK_GUID => OvmfTableEntry::SevSecretBlock {
+                gpa: u32_at(0)?,
+                size: u32_at(4)?,
+            },
+            SEV_ES_RESET_BLOCK_GUID => OvmfTableEntry::SevEsResetBlock { eip: u32_at(0)? },
+            OVMF_SEV_META_DATA_GUID => OvmfTableEntry::SevMetadata {
+                offset_from_end: u32_at(0)?,
+            },
+            guid => OvmfTableEntry::Unknown {
+                guid,
+                data: data.to_vec(),
+            },
+        })
+    }
+}
+
+/// The reset vector the SEV-ES application processors of a guest start at,
+/// as found in the SEV-ES reset block of its OVMF image.
+///
+/// The processors start in real mode, so the VMM splits the vector into
+/// the base of their code segment and their instruction pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApResetVector(u32);
+
+impl ApResetVector {
+    /// Reset vector at `eip`
+    pub fn new(eip: u32) -> Self {
+        Self(eip)
+    }
+
+    /// Instruction pointer of the reset vector
+    pub fn eip(&self) -> u32 {
+        self.0
+    }
+
+    /// Code segment selector the processors start with
+    pub fn cs_selector(&self) -> u16 {
+        0xf000
+    }
+
+    /// Code segment base the processors start with
+    pub fn cs_base(&self) -> u64 {
+        (self.0 & 0xffff_0000).into()
+    }
+
+    /// Instruction pointer, relative to the code segment base, the
+    /// processors start with
+    pub fn rip(&self) -> u64 {
+        (self.0 & 0xffff).into()
+    }
+}
+
 const FOUR_GB: u64 = 0x100000000;
 const OVMF_TABLE_FOOTER_GUID: Uuid = uuid!("96b582de-1fb2-45f7-baea-a366c55a082d");
 const SEV_HASH_TABLE_RV_GUID: Uuid = uuid!("7255371f-3a3b-4b04-927b-1da6efa8d454");
+const SEV_SECRET_BLOCK_GUID: Uuid = uuid!("4c2eb361-7d9b-4cc3-8081-127c90d3d294");
 const SEV_ES_RESET_BLOCK_GUID: Uuid = uuid!("00f771de-1a7e-4fcb-890e-68c77e2fb44e");
 const OVMF_SEV_META_DATA_GUID: Uuid = uuid!("dc886566-984a-4798-a75e-5585a7bf67cc");
 
@@ -158,8 +259,8 @@ const OVMF_SEV_META_DATA_GUID: Uuid = uuid!("dc886566-984a-4798-a75e-5585a7bf67c
 pub struct OVMF {
     /// OVMF data
     data: Vec<u8>,
-    /// Table matching GUID to its data
-    table: HashMap<Uuid, Vec<u8>>,
+    /// Table matching GUID to its data, in the order of the image
+    table: Vec<(Uuid, Vec<u8>)>,
     /// Metadata item description
     metadata_items: Vec<OvmfSevMetadataSectionDesc>,
 }
@@ -175,9 +276,14 @@ impl OVMF {
 
         file.read_to_end(&mut data)?;
 
+        Self::from_bytes(data)
+    }
+
+    /// Generate new OVMF structure from a firmware image already in memory
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, MeasurementError> {
         let mut ovmf = OVMF {
             data,
-            table: HashMap::new(),
+            table: Vec::new(),
             metadata_items: Vec::new(),
         };
 
@@ -199,7 +305,18 @@ impl OVMF {
 
     /// Get an item from the OVMF table
     fn table_item(&self, guid: &Uuid) -> Option<&Vec<u8>> {
-        self.table.get(guid)
+        self.table
+            .iter()
+            .find(|(entry_guid, _)| entry_guid == guid)
+            .map(|(_, data)| data)
+    }
+
+    /// Get the typed entries of the OVMF table, in the order of the image
+    pub fn table_entries(&self) -> Result<Vec<OvmfTableEntry>, OVMFError> {
+        self.table
+            .iter()
+            .map(|(guid, data)| OvmfTableEntry::parse(*guid, data))
+            .collect()
     }
 
     /// Get the OVMF metadata items
@@ -216,13 +333,13 @@ impl OVMF {
 
     /// Check that the table supports SEV hashes
     pub fn is_sev_hashes_table_supported(&self) -> bool {
-        self.table.contains_key(&SEV_HASH_TABLE_RV_GUID)
+        self.table_item(&SEV_HASH_TABLE_RV_GUID).is_some()
             && self.sev_hashes_table_gpa().unwrap_or(0) != 0
     }
 
     /// Get the SEV HASHES GPA
     pub fn sev_hashes_table_gpa(&self) -> Result<u64, OVMFError> {
-        if !self.table.contains_key(&SEV_HASH_TABLE_RV_GUID) {
+        if self.table_item(&SEV_HASH_TABLE_RV_GUID).is_none() {
             return Err(OVMFError::EntryMissingInTable(
                 "SEV_HASH_TABLE_RV_GUID".to_string(),
             ));
@@ -241,7 +358,7 @@ impl OVMF {
 
     /// Get the SEV-ES EIP
     pub fn sev_es_reset_eip(&self) -> Result<u32, OVMFError> {
-        if !self.table.contains_key(&SEV_ES_RESET_BLOCK_GUID) {
+        if self.table_item(&SEV_ES_RESET_BLOCK_GUID).is_none() {
             return Err(OVMFError::EntryMissingInTable(
                 "SEV_ES_RESET_BLOCK_GUID".to_string(),
             ));
@@ -258,11 +375,25 @@ impl OVMF {
         }
     }
 
+    /// Get the reset vector of the SEV-ES application processors, which
+    /// the VMSAs of every vCPU but the first are built with
+    pub fn ap_reset_vector(&self) -> Result<ApResetVector, OVMFError> {
+        self.sev_es_reset_eip().map(ApResetVector::new)
+    }
+
     /// Parse footer table data
     fn parse_footer_table(&mut self) -> Result<(), MeasurementError> {
         self.table.clear();
         let size = self.data.len();
         const ENTRY_HEADER_SIZE: usize = std::mem::size_of::<OvmfFooterTableEntry>();
+        if size < 32 + ENTRY_HEADER_SIZE {
+            return Err(OVMFError::InvalidSize(
+                "OVMF image".to_string(),
+                size,
+                32 + ENTRY_HEADER_SIZE,
+            )
+            .into());
+        }
         //The OVMF table ends 32 bytes before the end of the firmware binary
         let start_of_footer_table = size - 32 - ENTRY_HEADER_SIZE;
         let footer =
@@ -284,6 +415,14 @@ impl OVMF {
         }
 
         let table_size = footer.size as usize - ENTRY_HEADER_SIZE;
+        if table_size > start_of_footer_table {
+            return Err(OVMFError::InvalidSize(
+                "OVMF image".to_string(),
+                start_of_footer_table,
+                table_size,
+            )
+            .into());
+        }
 
         let table_start = start_of_footer_table - table_size;
         let table_bytes = &self.data[table_start..start_of_footer_table];
@@ -305,7 +444,7 @@ impl OVMF {
                 break;
             }
             let entry_data = &table_bytes[offset - entry.size as usize..offset - ENTRY_HEADER_SIZE];
-            self.table.insert(entry_guid, entry_data.to_vec());
+            self.table.push((entry_guid, entry_data.to_vec()));
 
             offset -= entry.size as usize;
         }
@@ -315,27 +454,51 @@ impl OVMF {
 
     /// parse SEV metadata
     fn parse_sev_metadata(&mut self) -> Result<(), MeasurementError> {
-        match self.table.get(&OVMF_SEV_META_DATA_GUID) {
-            Some(entry) => {
-                let offset_from_end = i32::from_le_bytes(entry[..4].try_into()?);
-                let header_start = self.data.len() - (offset_from_end as usize);
-                let header =
-                    OvmfSevMetadataHeader::try_from_bytes(self.data.as_slice(), header_start)?;
-                header.verify()?;
-                let items = &self.data[header_start + std::mem::size_of::<OvmfSevMetadataHeader>()
-                    ..header_start + header.size as usize];
-                for i in 0..header.num_items {
-                    let offset = (i as usize) * std::mem::size_of::<OvmfSevMetadataSectionDesc>();
-                    let item = OvmfSevMetadataSectionDesc::try_from_bytes(items, offset)?;
-                    self.metadata_items.push(item.to_owned());
-                }
-            }
-
+        const HEADER_SIZE: usize = std::mem::size_of::<OvmfSevMetadataHeader>();
+        const ITEM_SIZE: usize = std::mem::size_of::<OvmfSevMetadataSectionDesc>();
+
+        let offset_from_end = match self.table_item(&OVMF_SEV_META_DATA_GUID) {
+            Some(entry) => entry
+                .get(..4)
+                .map(LittleEndian::read_u32)
+                .ok_or(OVMFError::GetTableItemError)? as usize,
             None => {
                 return Err(
                     OVMFError::EntryMissingInTable("OVMF_SEV_METADATA_GUID".to_string()).into(),
                 );
             }
+        };
+
+        if offset_from_end < HEADER_SIZE || offset_from_end > self.data.len() {
+            return Err(OVMFError::InvalidSize(
+                "SEV metadata offset".to_string(),
+                offset_from_end,
+                HEADER_SIZE,
+            )
+            .into());
+        }
+
+        let header_start = self.data.len() - offset_from_end;
+        let header = OvmfSevMetadataHeader::try_from_bytes(self.data.as_slice(), header_start)?;
+        header.verify()?;
+
+        let items_size = header.num_items as usize * ITEM_SIZE;
+        if (header.size as usize) < HEADER_SIZE + items_size
+            || header.size as usize > offset_from_end
+        {
+            return Err(OVMFError::InvalidSize(
+                "SEV metadata".to_string(),
+                header.size as usize,
+                HEADER_SIZE + items_size,
+            )
+            .into());
+        }
+
+        let items = &self.data[header_start + HEADER_SIZE..header_start + header.size as usize];
+        for i in 0..header.num_items {
+            let offset = (i as usize) * ITEM_SIZE;
+            let item = OvmfSevMetadataSectionDesc::try_from_bytes(items, offset)?;
+            self.metadata_items.push(item.to_owned());
         }
 
         Ok(())
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Operations to hash OVMF images the way their launch measures them.
+//!
+//! A plain SHA-384 hash of the image file never matches the OVMF hash an
+//! SEV-SNP launch digest starts from: the launch measures the image page by
+//! page, chaining the digest of each page with its guest physical address.
+//! The image is mapped so that it ends at 4GB and its first page is padded
+//! with zeroes if it is not page-aligned. The whole image is measured
+//! unless regions the VMM populates differently (e.g. the sections of the
+//! SEV metadata) are excluded.
+//!
+//! # Example:
+//! ```ignore
+//! let ovmf = OVMF::new("OVMF.fd".into())?;
+//! let hash = OvmfHasher::new(&ovmf)
+//!     .exclude_metadata()
+//!     .unmeasured(0xffc8_4000..0xffc8_5000)
+//!     .snp()?;
+//! ```
+use crate::{
+    error::MeasurementError,
+    measurement::{
+        gctx::{LaunchDigest, PageUpdate, LD_SIZE},
+        ovmf::OVMF,
+    },
+};
+
+use crate::measurement::digest::Sha256;
+use std::ops::Range;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// How a page of the image is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Measured,
+    Unmeasured,
+    Excluded,
+}
+
+/// Hasher of an OVMF image, honoring the regions of the image which are
+/// not measured by their contents
+pub struct OvmfHasher {
+    /// Guest physical address of the first page of the image
+    gpa: u64,
+    /// The image, padded to whole pages
+    data: Vec<u8>,
+    /// Length of the padding before the image
+    padding: usize,
+    /// Guest physical address ranges which are encrypted but not measured
+    unmeasured: Vec<Range<u64>>,
+    /// Guest physical address ranges which are not populated from the image
+    excluded: Vec<Range<u64>>,
+    /// Guest physical address ranges of the sections of the SEV metadata
+    metadata: Vec<Range<u64>>,
+}
+
+impl OvmfHasher {
+    /// Hash the whole of `ovmf`.
+    pub fn new(ovmf: &OVMF) -> Self {
+        let gpa = ovmf.gpa() & !(PAGE_SIZE - 1);
+
+        let padding = (ovmf.gpa() - gpa) as usize;
+        let mut data = vec![0; padding];
+        data.extend_from_slice(ovmf.data());
+
+        let metadata = ovmf
+            .metadata_items()
+            .iter()
+            .map(|desc| desc.gpa as u64..desc.gpa as u64 + desc.size as u64)
+            .collect();
+
+        Self {
+            gpa,
+            data,
+            padding,
+            unmeasured: Vec::new(),
+            excluded: Vec::new(),
+            metadata,
+        }
+    }
+
+    /// Leave the sections of the image's SEV metadata out of its
+    /// measurement, for VMMs which populate them with their own page types.
+    pub fn exclude_metadata(mut self) -> Self {
+        self.excluded.extend(self.metadata.iter().cloned());
+        self
+    }
+
+    /// Encrypt the pages of `range` without measuring their contents, e.g.
+    /// for variable stores.
+    pub fn unmeasured(mut self, range: Range<u64>) -> Self {
+        self.unmeasured.push(range);
+        self
+    }
+
+    /// Leave the pages of `range` out of the image's measurement.
+    pub fn exclude(mut self, range: Range<u64>) -> Self {
+        self.excluded.push(range);
+        self
+    }
+
+    /// Classify each page of the image, checking that the regions are
+    /// page-aligned.
+    fn regions(&self) -> Result<Vec<Region>, MeasurementError> {
+        for range in self.unmeasured.iter().chain(self.excluded.iter()) {
+            if range.start % PAGE_SIZE != 0 || range.end % PAGE_SIZE != 0 {
+                return Err(MeasurementError::InvalidFirmwareRegion(
+                    range.start,
+                    range.end,
+                ));
+            }
+        }
+
+        let within = |ranges: &[Range<u64>], gpa: u64| ranges.iter().any(|r| r.contains(&gpa));
+
+        Ok((0..self.data.len() as u64 / PAGE_SIZE)
+            .map(|page| self.gpa + page * PAGE_SIZE)
+            .map(|gpa| {
+                if within(&self.excluded, gpa) {
+                    Region::Excluded
+                } else if within(&self.unmeasured, gpa) {
+                    Region::Unmeasured
+                } else {
+                    Region::Measured
+                }
+            })
+            .collect())
+    }
+
+    /// The page updates measuring the image, coalescing consecutive pages
+    /// measured the same way.
+    pub fn page_updates(&self) -> Result<Vec<PageUpdate<'_>>, MeasurementError> {
+        let regions = self.regions()?;
+        let mut updates = Vec::new();
+
+        let mut first = 0;
+        while first < regions.len() {
+            let region = regions[first];
+            let count = regions[first..]
+                .iter()
+                .take_while(|other| **other == region)
+                .count();
+
+            let gpa = self.gpa + (first as u64) * PAGE_SIZE;
+            let offset = first * PAGE_SIZE as usize;
+            let len = count * PAGE_SIZE as usize;
+
+            match region {
+                Region::Measured => updates.push(PageUpdate::Normal {
+                    gpa,
+                    data: &self.data[offset..offset + len],
+                }),
+                Region::Unmeasured => updates.push(PageUpdate::Unmeasured { gpa, len }),
+                Region::Excluded => (),
+            }
+
+            first += count;
+        }
+
+        Ok(updates)
+    }
+
+    /// The OVMF hash an SEV-SNP launch digest starts from, e.g. to pass as
+    /// an `ovmf_hash_str`.
+    pub fn snp(&self) -> Result<[u8; LD_SIZE], MeasurementError> {
+        let mut digest = LaunchDigest::new();
+        digest.replay(&self.page_updates()?)?;
+
+        Ok(digest.digest())
+    }
+
+    /// The SHA-256 hash of the image as the launch digest of an SEV or
+    /// SEV-ES guest covers it, which has no padding. Both unmeasured and
+    /// excluded regions are left out.
+    pub fn sev(&self) -> Result<[u8; 32], MeasurementError> {
+        let mut hash = Sha256::new();
+        for update in self.page_updates()? {
+            if let PageUpdate::Normal { gpa, data } = update {
+                if gpa == self.gpa {
+                    hash.update(&data[self.padding..]);
+                } else {
+                    hash.update(data);
+                }
+            }
+        }
+
+        Ok(hash.finish())
+    }
+}
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A versioned, serializable record of every input to the launch digest of
+//! a guest.
+//!
+//! Pipelines building guest images can publish a recipe next to the images
+//! they build, so that verifiers know which measurement to expect and can
+//! reproduce it from the same firmware, kernel, initrd and command line.
+//! Recipes are stored as JSON documents with a
+//! [versioned envelope](crate::snapshot).
+//!
+//! # Example:
+//! ```ignore
+//! let recipe = MeasurementRecipe::new(&args)?;
+//! recipe.save("guest.recipe.json")?;
+//!
+//! let recipe = MeasurementRecipe::load("guest.recipe.json")?;
+//! assert!(recipe.reproduces(&args)?);
+//! ```
+use crate::{
+    error::{MeasurementError, SnapshotError},
+    measurement::{
+        calc::{calc_launch_digest, MeasurementArgs},
+        ovmf::OVMF,
+        sev_hashes::SevHashes,
+        snp::calc_snp_ovmf_hash,
+        vcpu_types::CpuType,
+        vmsa::{GuestFeatures, SevMode, VMMType},
+    },
+    snapshot::Versioned,
+};
+
+use crate::measurement::digest::sha256;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
+
+/// SHA-256 hashes of the kernel, initrd and command line measured through
+/// the SEV hashes table, hex encoded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KernelHashes {
+    /// Kernel hash
+    pub kernel: String,
+    /// Initrd hash (of empty data if the guest has no initrd)
+    pub initrd: String,
+    /// NUL-terminated command line hash
+    pub cmdline: String,
+}
+
+impl From<&SevHashes> for KernelHashes {
+    fn from(hashes: &SevHashes) -> Self {
+        Self {
+            kernel: hex::encode(hashes.kernel_hash()),
+            initrd: hex::encode(hashes.initrd_hash()),
+            cmdline: hex::encode(hashes.cmdline_hash()),
+        }
+    }
+}
+
+/// Every input to the launch digest of a guest, and the digest itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeasurementRecipe {
+    /// SEV mode of the guest
+    pub mode: SevMode,
+    /// Number of vcpus (SEV-ES and SEV-SNP only)
+    pub vcpus: u32,
+    /// vcpu type (SEV-ES and SEV-SNP only)
+    pub vcpu_type: CpuType,
+    /// vmm type (SEV-ES and SEV-SNP only)
+    #[serde(default)]
+    pub vmm_type: Option<VMMType>,
+    /// Active kernel guest features (SEV-SNP only)
+    pub guest_features: GuestFeatures,
+    /// Guest policy the guest is launched with, which the launch digest
+    /// does not cover but the attestation report does
+    #[serde(default)]
+    pub policy: Option<u64>,
+    /// Hash of the firmware, hex encoded: the OVMF hash a SEV-SNP launch
+    /// digest is seeded with, or the SHA-256 hash of the OVMF image for SEV
+    /// and SEV-ES guests
+    pub firmware_hash: String,
+    /// Hashes of the kernel, initrd and command line, if the guest boots a
+    /// kernel measured through the SEV hashes table
+    #[serde(default)]
+    pub kernel_hashes: Option<KernelHashes>,
+    /// Expected launch digest, hex encoded
+    pub launch_digest: String,
+}
+
+impl Versioned for MeasurementRecipe {
+    const KIND: [u8; 4] = *b"RCPE";
+    const VERSION: u16 = 1;
+}
+
+impl MeasurementRecipe {
+    /// Record the inputs of `args` and the launch digest they produce.
+    pub fn new(args: &MeasurementArgs) -> Result<Self, MeasurementError> {
+        let firmware_hash = match (args.mode, args.ovmf_hash_str) {
+            (SevMode::SevSnp, Some(hash)) => hash.to_lowercase(),
+            (SevMode::SevSnp, None) => hex::encode(calc_snp_ovmf_hash(args.ovmf_file.clone())?),
+            _ => hex::encode(sha256(OVMF::new(args.ovmf_file.clone())?.data())),
+        };
+
+        let kernel_hashes = match &args.kernel_file {
+            Some(kernel) => Some(KernelHashes::from(&SevHashes::new(
+                kernel.clone(),
+                args.initrd_file.clone(),
+                args.append,
+            )?)),
+            None => None,
+        };
+
+        Ok(Self {
+            mode: args.mode,
+            vcpus: args.vcpus,
+            vcpu_type: args.vcpu_type,
+            vmm_type: args.vmm_type,
+            guest_features: args.guest_features,
+            policy: None,
+            firmware_hash,
+            kernel_hashes,
+            launch_digest: hex::encode(calc_launch_digest(args.clone())?),
+        })
+    }
+
+    /// Record the guest `policy` the guest is launched with, e.g. to
+    /// publish it alongside the expected launch digest.
+    pub fn with_policy(self, policy: u64) -> Self {
+        Self {
+            policy: Some(policy),
+            ..self
+        }
+    }
+
+    /// The expected launch digest.
+    pub fn launch_digest(&self) -> Result<Vec<u8>, MeasurementError> {
+        Ok(hex::decode(&self.launch_digest)?)
+    }
+
+    /// Check whether `args` reproduces every input of the recipe and its
+    /// launch digest. The policy is not derived from `args` and not
+    /// compared.
+    pub fn reproduces(&self, args: &MeasurementArgs) -> Result<bool, MeasurementError> {
+        let reproduced = Self {
+            policy: self.policy,
+            ..Self::new(args)?
+        };
+
+        Ok(reproduced == *self)
+    }
+
+    /// Read a recipe from JSON.
+    pub fn from_reader(reader: impl Read) -> Result<Self, SnapshotError> {
+        Self::from_json_reader(reader)
+    }
+
+    /// Write the recipe as JSON.
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), SnapshotError> {
+        self.to_json_writer(writer)
+    }
+
+    /// Read a recipe from the JSON file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::load_json(path)
+    }
+
+    /// Write the recipe to the JSON file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        self.save_json(path)
+    }
+}
@@ -12,7 +12,7 @@ use std::path::PathBuf;
 
 use crate::error::*;
 
-use openssl::sha::Sha256;
+use crate::measurement::digest::Sha256;
 
 const _PAGE_MASK: u64 = 0xfff;
 
@@ -73,8 +73,12 @@ pub fn seves_calc_launch_digest(
         GuestFeatures(0x0),
     );
 
-    for vmsa_page in vmsa.pages(sev_es_measurement.vcpus as usize)?.iter() {
-        launch_hash.update(vmsa_page.as_slice())
+    launch_hash.update(vmsa.bsp_page()?.as_slice());
+
+    if let Some(ap_page) = vmsa.ap_page()? {
+        for _ in 1..sev_es_measurement.vcpus {
+            launch_hash.update(ap_page.as_slice())
+        }
     }
 
     Ok(launch_hash.finish())
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Operations to handle OVMF SEV-HASHES
-use openssl::sha::sha256;
+
 use serde::Serialize;
 use std::fs::File;
 use std::{
@@ -16,6 +16,11 @@ use uuid::{uuid, Uuid};
 
 use crate::error::*;
 
+#[cfg(all(target_os = "linux", feature = "snp"))]
+use crate::measurement::gctx::{LaunchDigest, PageUpdate};
+
+use crate::measurement::digest::{sha256, Sha256};
+
 type Sha256Hash = [u8; 32];
 
 /// GUID stored as little endian
@@ -150,34 +155,35 @@ impl SevHashes {
         let mut kernel_data = Vec::new();
         kernel_file.read_to_end(&mut kernel_data)?;
 
-        let kernel_hash = sha256(&kernel_data);
-        let initrd_data = match initrd {
-            Some(path) => {
-                let mut initrd_file = File::open(path)?;
-                let mut data = Vec::new();
-                initrd_file.read_to_end(&mut data)?;
-                data
-            }
-            None => Vec::new(),
-        };
-
-        let initrd_hash = sha256(&initrd_data);
-
-        let cmdline_hash = match append {
-            Some(append_str) => {
-                let mut append_bytes = append_str.trim().as_bytes().to_vec();
-                append_bytes.extend_from_slice(b"\x00");
-                sha256(&append_bytes)
-            }
-
-            None => sha256(b"\x00"),
-        };
+        let mut builder = HashTableBuilder::new().kernel(&kernel_data);
 
-        Ok(SevHashes {
-            kernel_hash,
-            initrd_hash,
-            cmdline_hash,
-        })
+        if let Some(path) = initrd {
+            let mut initrd_file = File::open(path)?;
+            let mut initrd_data = Vec::new();
+            initrd_file.read_to_end(&mut initrd_data)?;
+            builder = builder.initrd(&initrd_data);
+        }
+
+        if let Some(append_str) = append {
+            builder = builder.cmdline(append_str);
+        }
+
+        builder.hashes()
+    }
+
+    /// SHA-256 hash of the kernel
+    pub fn kernel_hash(&self) -> &[u8; 32] {
+        &self.kernel_hash
+    }
+
+    /// SHA-256 hash of the initrd
+    pub fn initrd_hash(&self) -> &[u8; 32] {
+        &self.initrd_hash
+    }
+
+    /// SHA-256 hash of the NUL-terminated command line
+    pub fn cmdline_hash(&self) -> &[u8; 32] {
+        &self.cmdline_hash
     }
 
     /// Generate the SEV hashes area - this must be *identical* to the way QEMU
@@ -212,3 +218,110 @@ impl SevHashes {
         Ok(page)
     }
 }
+
+/// Builder of the SEV hashes table QEMU places in guest memory when booting
+/// a guest with `-kernel`, which lets OVMF verify the kernel, initrd and
+/// command line it loads.
+///
+/// The initrd and command line are optional, as in QEMU: a missing initrd
+/// is hashed as empty data and a missing command line as a single NUL.
+///
+/// # Example:
+/// ```ignore
+/// let table = HashTableBuilder::new()
+///     .kernel(&kernel)
+///     .initrd(&initrd)
+///     .cmdline("console=ttyS0")
+///     .build()?;
+///
+/// guest_memory[offset..offset + table.as_bytes().len()].copy_from_slice(table.as_bytes());
+/// table.measure_sev(&mut launch_hash);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HashTableBuilder {
+    kernel_hash: Option<Sha256Hash>,
+    initrd_hash: Option<Sha256Hash>,
+    cmdline_hash: Option<Sha256Hash>,
+}
+
+impl HashTableBuilder {
+    /// Begin a table with no kernel, initrd or command line.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash the contents of the kernel image.
+    pub fn kernel(mut self, data: &[u8]) -> Self {
+        self.kernel_hash = Some(sha256(data));
+        self
+    }
+
+    /// Hash the contents of the initrd.
+    pub fn initrd(mut self, data: &[u8]) -> Self {
+        self.initrd_hash = Some(sha256(data));
+        self
+    }
+
+    /// Hash the kernel command line, trimmed and NUL-terminated the way
+    /// QEMU passes it to the guest.
+    pub fn cmdline(mut self, append: &str) -> Self {
+        let mut append_bytes = append.trim().as_bytes().to_vec();
+        append_bytes.extend_from_slice(b"\x00");
+        self.cmdline_hash = Some(sha256(&append_bytes));
+        self
+    }
+
+    fn hashes(self) -> Result<SevHashes, MeasurementError> {
+        Ok(SevHashes {
+            kernel_hash: self.kernel_hash.ok_or(SevHashError::MissingKernel)?,
+            initrd_hash: self.initrd_hash.unwrap_or_else(|| sha256(&[])),
+            cmdline_hash: self.cmdline_hash.unwrap_or_else(|| sha256(b"\x00")),
+        })
+    }
+
+    /// Lay out the hashes in the padded SEV hashes table.
+    pub fn build(self) -> Result<HashTable, MeasurementError> {
+        let hashes = self.hashes()?;
+        let table = hashes.construct_table()?;
+
+        Ok(HashTable { hashes, table })
+    }
+}
+
+/// A built SEV hashes table
+pub struct HashTable {
+    hashes: SevHashes,
+    table: Vec<u8>,
+}
+
+impl HashTable {
+    /// The padded table, as injected into guest memory at the GPA the OVMF
+    /// image reserves for it.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.table
+    }
+
+    /// The page holding the table at `offset`, zeroed elsewhere.
+    pub fn page(&self, offset: usize) -> Result<Vec<u8>, MeasurementError> {
+        self.hashes.construct_page(offset)
+    }
+
+    /// Add the table to the launch digest of an SEV or SEV-ES guest, which
+    /// measures it right after the OVMF image.
+    pub fn measure_sev(&self, launch_hash: &mut Sha256) {
+        launch_hash.update(&self.table);
+    }
+
+    /// Add the table to the launch digest of an SEV-SNP guest, which
+    /// measures the whole page holding the table at `gpa`.
+    #[cfg(all(target_os = "linux", feature = "snp"))]
+    pub fn measure_snp(&self, digest: &mut LaunchDigest, gpa: u64) -> Result<(), MeasurementError> {
+        let page = self.page((gpa & 0xfff) as usize)?;
+        digest.update(&PageUpdate::Normal {
+            gpa: gpa & !0xfff,
+            data: &page,
+        })?;
+
+        Ok(())
+    }
+}
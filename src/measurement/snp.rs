@@ -4,7 +4,8 @@
 use crate::{
     launch::snp::PageType,
     measurement::{
-        gctx::{Gctx, Updating, VMSA_GPA},
+        digest::{default_backend, DigestBackend},
+        gctx::{Gctx, Updating, VmsaDigests},
         ovmf::{OvmfSevMetadataSectionDesc, SectionType, OVMF},
         sev_hashes::SevHashes,
         vcpu_types::CpuType,
@@ -148,15 +149,25 @@ pub struct SnpMeasurementArgs<'a> {
 pub fn snp_calc_launch_digest(
     snp_measurement: SnpMeasurementArgs,
 ) -> Result<[u8; LD_SIZE], MeasurementError> {
+    snp_calc_launch_digest_with(snp_measurement, default_backend())
+}
+
+/// Calulate an SEV-SNP launch digest, hashing the pages with `backend`
+pub fn snp_calc_launch_digest_with(
+    snp_measurement: SnpMeasurementArgs,
+    backend: &'static dyn DigestBackend,
+) -> Result<[u8; LD_SIZE], MeasurementError> {
+    snp_measurement.guest_features.validate()?;
+
     let ovmf = OVMF::new(snp_measurement.ovmf_file)?;
 
     let mut gctx: Gctx<Updating> = match snp_measurement.ovmf_hash_str {
         Some(hash) => {
             let ovmf_hash = Vec::from_hex(hash)?;
-            Gctx::new(ovmf_hash.as_slice())?
+            Gctx::new(ovmf_hash.as_slice())?.with_backend(backend)
         }
         None => {
-            let mut gctx = Gctx::default();
+            let mut gctx = Gctx::default().with_backend(backend);
 
             gctx.update_page(PageType::Normal, ovmf.gpa(), Some(ovmf.data()), None)?;
 
@@ -188,9 +199,10 @@ pub fn snp_calc_launch_digest(
         snp_measurement.guest_features,
     );
 
-    for vmsa_page in vmsa.pages(snp_measurement.vcpus as usize)?.iter() {
-        gctx.update_page(PageType::Vmsa, VMSA_GPA, Some(vmsa_page.as_slice()), None)?
-    }
+    gctx.update_vmsas(
+        &VmsaDigests::with_backend(&vmsa, backend)?,
+        snp_measurement.vcpus as usize,
+    )?;
 
     let gctx = gctx.finished();
 
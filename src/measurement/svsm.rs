@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Operations to calculate the measurement of SEV-SNP guests launched under
+//! an SVSM (Secure VM Service Module), e.g. COCONUT-SVSM.
+//!
+//! Such guests are described by an IGVM file, which lists the pages of the
+//! SVSM image, its loader and the guest firmware, and the register state
+//! the boot vCPU starts the SVSM with at VMPL0. The launch digest covers
+//! those pages in the order of the file's directives, followed by the VMSA
+//! pages of the vCPUs; the VMSAs of the guest's own (lower VMPL) vCPUs are
+//! created by the SVSM after launch and are not measured.
+//!
+//! # Example:
+//! ```ignore
+//! let args = SvsmMeasurementArgs {
+//!     pages: vec![
+//!         PageUpdate::Normal { gpa: 0x80_0000, data: &svsm },
+//!         PageUpdate::Secrets { gpa: 0x80_6000 },
+//!         PageUpdate::Cpuid { gpa: 0x80_7000 },
+//!         PageUpdate::Normal { gpa: 0xffc0_0000, data: &firmware },
+//!     ],
+//!     vmsas: vec![&svsm_vmsa],
+//!     pages_hash_str: None,
+//! };
+//!
+//! let ld = svsm_calc_launch_digest(args)?;
+//! ```
+use crate::{
+    error::MeasurementError,
+    measurement::{
+        gctx::{LaunchDigest, PageUpdate, LD_SIZE},
+        vmsa::VMSA_PAGE_SIZE,
+    },
+};
+
+use hex::FromHex;
+use std::convert::TryInto;
+
+/// Offset of the VMPL a VMSA runs at in its page
+const VMSA_VMPL_OFFSET: usize = 0xca;
+
+/// Arguments required to calculate the measurement of a guest launched
+/// under an SVSM
+pub struct SvsmMeasurementArgs<'a> {
+    /// Page updates described by the IGVM file's page data directives, in
+    /// the order of the file
+    pub pages: Vec<PageUpdate<'a>>,
+    /// VMSA pages described by the IGVM file's VP context directives,
+    /// starting with the boot vCPU running the SVSM at VMPL0
+    pub vmsas: Vec<&'a [u8]>,
+    /// Already calculated launch digest of the pages, which are then not
+    /// measured again (the VMSAs are measured on top of it)
+    pub pages_hash_str: Option<&'a str>,
+}
+
+impl SvsmMeasurementArgs<'_> {
+    /// Check that the boot vCPU starts the SVSM at VMPL0
+    fn validate(&self) -> Result<(), MeasurementError> {
+        let bsp = self.vmsas.first().ok_or_else(|| {
+            MeasurementError::InvalidSvsmLayout("no VMSA for the boot vCPU".to_string())
+        })?;
+
+        if bsp.len() != VMSA_PAGE_SIZE {
+            return Err(MeasurementError::InvalidSvsmLayout(format!(
+                "boot vCPU VMSA is {} bytes",
+                bsp.len()
+            )));
+        }
+
+        if bsp[VMSA_VMPL_OFFSET] != 0 {
+            return Err(MeasurementError::InvalidSvsmLayout(format!(
+                "boot vCPU VMSA runs at VMPL{} instead of VMPL0",
+                bsp[VMSA_VMPL_OFFSET]
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Calculate the launch digest of an SEV-SNP guest launched under an SVSM
+pub fn svsm_calc_launch_digest(
+    svsm_measurement: SvsmMeasurementArgs,
+) -> Result<[u8; LD_SIZE], MeasurementError> {
+    svsm_measurement.validate()?;
+
+    let mut digest = match svsm_measurement.pages_hash_str {
+        Some(hash) => {
+            let seed = Vec::from_hex(hash)?;
+            LaunchDigest::from_seed(seed.as_slice().try_into()?)
+        }
+        None => {
+            let mut digest = LaunchDigest::new();
+            digest.replay(&svsm_measurement.pages)?;
+            digest
+        }
+    };
+
+    for page in svsm_measurement.vmsas {
+        digest.update(&PageUpdate::Vmsa { page })?;
+    }
+
+    Ok(digest.digest())
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
-//! Exisiting AMD EPYC vCPUs
+//! Exisiting AMD EPYC vCPUs and SEV-SNP capable products
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::{convert::TryFrom, fmt};
 
 use crate::error::MeasurementError;
@@ -38,6 +39,10 @@ pub enum CpuType {
     EpycGenoa,
     /// EPYC GENOA V1
     EpycGenoaV1,
+    /// EPYC TURIN
+    EpycTurin,
+    /// EPYC TURIN V1
+    EpycTurinV1,
 }
 
 impl TryFrom<u8> for CpuType {
@@ -59,6 +64,8 @@ impl TryFrom<u8> for CpuType {
             12 => Ok(CpuType::EpycMilanV2),
             13 => Ok(CpuType::EpycGenoa),
             14 => Ok(CpuType::EpycGenoaV1),
+            15 => Ok(CpuType::EpycTurin),
+            16 => Ok(CpuType::EpycTurinV1),
             _ => Err(MeasurementError::InvalidVcpuTypeError(value.to_string())),
         }
     }
@@ -83,6 +90,8 @@ impl CpuType {
             CpuType::EpycMilanV2 => cpu_sig(25, 1, 1),
             CpuType::EpycGenoa => cpu_sig(25, 17, 0),
             CpuType::EpycGenoaV1 => cpu_sig(25, 17, 0),
+            CpuType::EpycTurin => cpu_sig(26, 0, 0),
+            CpuType::EpycTurinV1 => cpu_sig(26, 0, 0),
         }
     }
 }
@@ -105,6 +114,8 @@ impl fmt::Display for CpuType {
             CpuType::EpycMilanV2 => write!(f, "EPYC-Milan-v2"),
             CpuType::EpycGenoa => write!(f, "EPYC-Genoa"),
             CpuType::EpycGenoaV1 => write!(f, "EPYC-Genoa-v1"),
+            CpuType::EpycTurin => write!(f, "EPYC-Turin"),
+            CpuType::EpycTurinV1 => write!(f, "EPYC-Turin-v1"),
         }
     }
 }
@@ -129,6 +140,105 @@ impl TryFrom<&str> for CpuType {
             "epyc-milan-v2" => Ok(CpuType::EpycMilanV2),
             "epyc-genoa" => Ok(CpuType::EpycGenoa),
             "epyc-genoa-v1" => Ok(CpuType::EpycGenoaV1),
+            "epyc-turin" => Ok(CpuType::EpycTurin),
+            "epyc-turin-v1" => Ok(CpuType::EpycTurinV1),
+            _ => Err(MeasurementError::InvalidVcpuTypeError(value.to_string())),
+        }
+    }
+}
+
+impl Serialize for CpuType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CpuType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        CpuType::try_from(name.as_str()).map_err(de::Error::custom)
+    }
+}
+
+/// AMD products supporting SEV-SNP.
+///
+/// When the VMM passes the host CPU model through to the guest (e.g. QEMU's
+/// `-cpu host`), the VMSAs carry the signature of the host's processor
+/// rather than that of a [CpuType], so the expected measurement depends on
+/// the product the guest runs on. Embedded parts share the signature of
+/// the server processors they are derived from; client Ryzen processors do
+/// not support SEV-SNP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProductName {
+    /// 3rd generation EPYC (7003 series)
+    Milan,
+    /// 4th generation EPYC (9004 series)
+    Genoa,
+    /// 4th generation EPYC with Zen 4c cores (97x4 series)
+    Bergamo,
+    /// 4th generation EPYC for the edge (8004 series)
+    Siena,
+    /// 5th generation EPYC (9005 series)
+    Turin,
+    /// 5th generation EPYC with Zen 5c cores (9005 series)
+    TurinDense,
+    /// EPYC Embedded 7003 series
+    EmbeddedMilan,
+    /// EPYC Embedded 9004 series
+    EmbeddedGenoa,
+    /// EPYC Embedded 8004 series
+    EmbeddedSiena,
+}
+
+impl ProductName {
+    /// Family, model and stepping of the processor
+    pub fn family_model_stepping(&self) -> (i32, i32, i32) {
+        match self {
+            ProductName::Milan | ProductName::EmbeddedMilan => (25, 1, 1),
+            ProductName::Genoa | ProductName::EmbeddedGenoa => (25, 17, 1),
+            ProductName::Bergamo | ProductName::Siena | ProductName::EmbeddedSiena => (25, 160, 2),
+            ProductName::Turin => (26, 2, 1),
+            ProductName::TurinDense => (26, 17, 0),
+        }
+    }
+
+    /// Matching the product with its CPU signature
+    pub fn sig(&self) -> i32 {
+        let (family, model, stepping) = self.family_model_stepping();
+        cpu_sig(family, model, stepping)
+    }
+}
+
+impl fmt::Display for ProductName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProductName::Milan => write!(f, "Milan"),
+            ProductName::Genoa => write!(f, "Genoa"),
+            ProductName::Bergamo => write!(f, "Bergamo"),
+            ProductName::Siena => write!(f, "Siena"),
+            ProductName::Turin => write!(f, "Turin"),
+            ProductName::TurinDense => write!(f, "Turin-Dense"),
+            ProductName::EmbeddedMilan => write!(f, "Embedded-Milan"),
+            ProductName::EmbeddedGenoa => write!(f, "Embedded-Genoa"),
+            ProductName::EmbeddedSiena => write!(f, "Embedded-Siena"),
+        }
+    }
+}
+
+impl TryFrom<&str> for ProductName {
+    type Error = MeasurementError;
+
+    fn try_from(value: &str) -> Result<Self, MeasurementError> {
+        match value.to_lowercase().as_str() {
+            "milan" => Ok(ProductName::Milan),
+            "genoa" => Ok(ProductName::Genoa),
+            "bergamo" => Ok(ProductName::Bergamo),
+            "siena" => Ok(ProductName::Siena),
+            "turin" => Ok(ProductName::Turin),
+            "turin-dense" => Ok(ProductName::TurinDense),
+            "embedded-milan" => Ok(ProductName::EmbeddedMilan),
+            "embedded-genoa" => Ok(ProductName::EmbeddedGenoa),
+            "embedded-siena" => Ok(ProductName::EmbeddedSiena),
             _ => Err(MeasurementError::InvalidVcpuTypeError(value.to_string())),
         }
     }
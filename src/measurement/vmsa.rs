@@ -1,16 +1,23 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Operations to build and interact with an SEV-ES VMSA
+#[cfg(feature = "snp")]
+use crate::launch::snp::gmem::GmemInit;
 use crate::{
     error::MeasurementError,
-    measurement::{large_array::LargeArray, vcpu_types::CpuType},
+    measurement::{
+        large_array::LargeArray,
+        ovmf::ApResetVector,
+        vcpu_types::{cpu_sig, CpuType, ProductName},
+    },
 };
 use bitfield::bitfield;
 use serde::{Deserialize, Serialize};
 use std::{convert::TryFrom, fmt, str::FromStr};
 
 /// Different Possible SEV modes
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum SevMode {
     /// SEV
     Sev,
@@ -34,7 +41,8 @@ impl FromStr for SevMode {
 }
 
 /// Supported Virtual Machine Monitors
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum VMMType {
     /// QEMU
     QEMU = 1,
@@ -126,41 +134,101 @@ bitfield! {
     pub struct GuestFeatures(u64);
     impl Debug;
     /// SNPActive
-    pub snp_active, _: 0, 0;
+    pub snp_active, set_snp_active: 0, 0;
     /// vTom
-    pub v_tom, _: 1, 1;
+    pub v_tom, set_v_tom: 1, 1;
     /// ReflectVC
-    pub reflect_vc, _: 2, 2;
+    pub reflect_vc, set_reflect_vc: 2, 2;
     /// RestrictedInjection
-    pub restricted_injection, _: 3,3;
+    pub restricted_injection, set_restricted_injection: 3,3;
     /// AlternateInjection
-    pub alternate_injection, _: 4,4;
+    pub alternate_injection, set_alternate_injection: 4,4;
     /// DebugSwap
-    pub debug_swap, _: 5,5;
+    pub debug_swap, set_debug_swap: 5,5;
     /// PreventHostIbs
-    pub prevent_host_ibs, _: 6,6;
+    pub prevent_host_ibs, set_prevent_host_ibs: 6,6;
     /// BTBIsolation
-    pub btb_isolation, _: 7,7;
+    pub btb_isolation, set_btb_isolation: 7,7;
     /// VmplSSS
-    pub vmpl_sss, _: 8,8;
+    pub vmpl_sss, set_vmpl_sss: 8,8;
     /// SecureTSC
-    pub secure_tsc, _: 9,9;
+    pub secure_tsc, set_secure_tsc: 9,9;
     /// VmgExitParameter
-    pub vmg_exit_parameter, _: 10,10;
+    pub vmg_exit_parameter, set_vmg_exit_parameter: 10,10;
     /// Reserved, SBZ
     reserved_1, _: 11,11;
     /// IbsVirtualization
-    pub ibs_virtualization, _: 12,12;
+    pub ibs_virtualization, set_ibs_virtualization: 12,12;
     /// Reserved, SBZ
     reserved_2, _: 13,13;
     /// VmsaRegProt
-    pub vmsa_reg_prot, _: 14,14;
+    pub vmsa_reg_prot, set_vmsa_reg_prot: 14,14;
     ///SmtProtection
-    pub smt_protection, _: 15,15;
+    pub smt_protection, set_smt_protection: 15,15;
     /// Reserved, SBZ
     reserved_3, sbz: 16, 63;
 }
 
+impl GuestFeatures {
+    /// Bits which are reserved and must be zero
+    const RESERVED: u64 = !0xd7ff;
+
+    /// The features of an SEV-SNP guest launched with `vmsa_features`
+    /// (e.g. the `vmsa_features` of KVM_SEV_INIT2). SNPActive is always
+    /// set in the VMSAs of SEV-SNP guests.
+    pub fn snp(vmsa_features: u64) -> Result<Self, MeasurementError> {
+        let mut features = GuestFeatures(vmsa_features);
+        features.set_snp_active(1);
+        features.validate()?;
+
+        Ok(features)
+    }
+
+    /// Check that no reserved bit is set
+    pub fn validate(&self) -> Result<(), MeasurementError> {
+        if self.0 & Self::RESERVED != 0 {
+            return Err(MeasurementError::InvalidGuestFeatures(self.0));
+        }
+
+        Ok(())
+    }
+}
+
+impl From<u64> for GuestFeatures {
+    fn from(value: u64) -> Self {
+        GuestFeatures(value)
+    }
+}
+
+impl From<GuestFeatures> for u64 {
+    fn from(value: GuestFeatures) -> Self {
+        value.0
+    }
+}
+
+/// The features the VMSAs of a guest launched with `init` are measured with
+#[cfg(feature = "snp")]
+impl TryFrom<&GmemInit> for GuestFeatures {
+    type Error = MeasurementError;
+
+    fn try_from(init: &GmemInit) -> Result<Self, MeasurementError> {
+        GuestFeatures::snp(init.vmsa_features)
+    }
+}
+
+/// Launch a guest whose VMSAs have `features`, leaving SNPActive to KVM
+#[cfg(feature = "snp")]
+impl From<GuestFeatures> for GmemInit {
+    fn from(mut features: GuestFeatures) -> Self {
+        features.set_snp_active(0);
+
+        GmemInit {
+            vmsa_features: features.0,
+            ..Default::default()
+        }
+    }
+}
+
 /// SEV-ES VMSA page
 /// The names of the fields are taken from struct sev_es_work_area in the linux kernel:
 /// https://github.com/AMDESE/linux/blob/sev-snp-v12/arch/x86/include/asm/svm.h#L318
@@ -279,12 +347,22 @@ struct SevEsSaveArea {
 
 const BSP_EIP: u64 = 0xffff_fff0;
 
+/// Size of a VMSA page
+pub const VMSA_PAGE_SIZE: usize = 4096;
+
+/// Raw bytes written over a serialized save area, at an offset into its page
+type Patch = (usize, Vec<u8>);
+
 /// VMSA Structure
 pub struct VMSA {
     /// Bootstrap Processor
     bsp_save_area: SevEsSaveArea,
     /// Auxiliary Processor
     ap_save_area: Option<SevEsSaveArea>,
+    /// Raw overrides of the bootstrap processor page
+    bsp_patches: Vec<Patch>,
+    /// Raw overrides of the auxiliary processor pages
+    ap_patches: Vec<Patch>,
 }
 
 impl VMSA {
@@ -298,13 +376,13 @@ impl VMSA {
         guest_features: GuestFeatures,
     ) -> Self {
         let bsp_save_area =
-            Self::build_save_area(BSP_EIP, guest_features, vcpu_type, vmm_type, cpu_num);
+            Self::build_save_area(BSP_EIP, guest_features, vcpu_type.sig(), vmm_type, cpu_num);
 
         let ap_save_area = if ap_eip > 0 {
             Some(Self::build_save_area(
                 ap_eip,
                 guest_features,
-                vcpu_type,
+                vcpu_type.sig(),
                 vmm_type,
                 cpu_num,
             ))
@@ -315,21 +393,28 @@ impl VMSA {
         VMSA {
             bsp_save_area,
             ap_save_area,
+            bsp_patches: Vec::new(),
+            ap_patches: Vec::new(),
         }
     }
 
+    /// Start building a VMSA from the preset of `vmm_type`
+    pub fn builder(vmm_type: VMMType) -> VmsaBuilder {
+        VmsaBuilder::new(vmm_type)
+    }
+
     /// Generate a save area
     fn build_save_area(
         eip: u64,
         guest_features: GuestFeatures,
-        vcpu_type: CpuType,
+        cpu_sig: i32,
         vmm_type: VMMType,
         cpu_num: Option<u64>,
     ) -> SevEsSaveArea {
         let mut area = SevEsSaveArea::default();
 
         let (cs_flags, ss_flags, tr_flags, rdx, mxcsr, fcw) = match vmm_type {
-            VMMType::QEMU => (0x9b, 0x93, 0x8b, vcpu_type.sig() as u64, 0x1f80, 0x37f),
+            VMMType::QEMU => (0x9b, 0x93, 0x8b, cpu_sig as u64, 0x1f80, 0x37f),
             VMMType::EC2 => {
                 if eip == 0xfffffff0 {
                     (0x9a, 0x92, 0x83, 0, 0, 0)
@@ -364,8 +449,10 @@ impl VMSA {
             }
         };
 
+        let vector = ApResetVector::new(eip as u32);
+
         area.es = VmcbSeg::new(0, 0x93, 0xffff, 0);
-        area.cs = VmcbSeg::new(0xf000, cs_flags, 0xffff, eip & 0xffff0000);
+        area.cs = VmcbSeg::new(vector.cs_selector(), cs_flags, 0xffff, vector.cs_base());
         area.ss = VmcbSeg::new(0, ss_flags, 0xffff, 0);
         area.ds = VmcbSeg::new(0, 0x93, 0xffff, 0);
         area.fs = VmcbSeg::new(0, 0x93, 0xffff, 0);
@@ -380,7 +467,7 @@ impl VMSA {
         area.dr7 = 0x400;
         area.dr6 = 0xffff0ff0;
         area.rflags = 0x2;
-        area.rip = eip & 0xffff;
+        area.rip = vector.rip();
         area.g_pat = 0x7040600070406;
         area.rdx = rdx;
         area.sev_features = guest_features.0;
@@ -391,15 +478,34 @@ impl VMSA {
         area
     }
 
+    /// Serialize a save area to its page, applying the raw overrides
+    fn page(area: &SevEsSaveArea, patches: &[Patch]) -> Result<Vec<u8>, MeasurementError> {
+        let mut page = bincode::serialize(area).map_err(|e| MeasurementError::BincodeError(*e))?;
+
+        for (offset, bytes) in patches {
+            page[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        Ok(page)
+    }
+
+    /// The page of the bootstrap processor
+    pub fn bsp_page(&self) -> Result<Vec<u8>, MeasurementError> {
+        Self::page(&self.bsp_save_area, &self.bsp_patches)
+    }
+
+    /// The page of each auxiliary processor, if they have their own save area
+    pub fn ap_page(&self) -> Result<Option<Vec<u8>>, MeasurementError> {
+        self.ap_save_area
+            .as_ref()
+            .map(|area| Self::page(area, &self.ap_patches))
+            .transpose()
+    }
+
     /// Return a vector containing the save area pages
     pub fn pages(&self, vcpus: usize) -> Result<Vec<Vec<u8>>, MeasurementError> {
-        let bsp_page = bincode::serialize(&self.bsp_save_area)
-            .map_err(|e| MeasurementError::BincodeError(*e))?;
-        let ap_save_area_bytes: Option<Vec<u8>> =
-            match self.ap_save_area.map(|v| bincode::serialize(&v)) {
-                Some(value) => Some(value.map_err(|e| MeasurementError::BincodeError(*e))?),
-                None => None,
-            };
+        let bsp_page = self.bsp_page()?;
+        let ap_save_area_bytes: Option<Vec<u8>> = self.ap_page()?;
 
         let mut pages = Vec::new();
 
@@ -413,3 +519,130 @@ impl VMSA {
         Ok(pages)
     }
 }
+
+/// Builder of the VMSA pages of an SEV-ES or SEV-SNP guest.
+///
+/// Each VMM sets up the initial vCPU state a little differently, so the
+/// builder starts from the preset of a [VMMType] and lets the vCPU type,
+/// guest features and AP reset vector be changed. VMMs without a preset
+/// (e.g. cloud-hypervisor, whose VMSAs are described by its IGVM file) can
+/// be reproduced with raw overrides of the serialized pages.
+#[derive(Debug, Clone)]
+pub struct VmsaBuilder {
+    vmm_type: VMMType,
+    cpu_sig: i32,
+    guest_features: GuestFeatures,
+    ap_eip: u64,
+    cpu_num: Option<u64>,
+    bsp_patches: Vec<Patch>,
+    ap_patches: Vec<Patch>,
+}
+
+impl VmsaBuilder {
+    /// Start from the preset of `vmm_type`, for an EPYC-v4 vCPU, no guest
+    /// features and no auxiliary processors
+    pub fn new(vmm_type: VMMType) -> Self {
+        Self {
+            vmm_type,
+            cpu_sig: CpuType::EpycV4.sig(),
+            guest_features: GuestFeatures::default(),
+            ap_eip: 0,
+            cpu_num: None,
+            bsp_patches: Vec::new(),
+            ap_patches: Vec::new(),
+        }
+    }
+
+    /// Set the vCPU type, whose signature is reported in RDX
+    pub fn vcpu_type(mut self, vcpu_type: CpuType) -> Self {
+        self.cpu_sig = vcpu_type.sig();
+        self
+    }
+
+    /// Set the signature of `product`, for vCPUs passing the host CPU model
+    /// through
+    pub fn product(mut self, product: ProductName) -> Self {
+        self.cpu_sig = product.sig();
+        self
+    }
+
+    /// Set the vCPU family, model and stepping, for vCPUs without a [CpuType]
+    pub fn vcpu_family(mut self, family: i32, model: i32, stepping: i32) -> Self {
+        self.cpu_sig = cpu_sig(family, model, stepping);
+        self
+    }
+
+    /// Set the guest features (SEV_FEATURES) of the vCPUs
+    pub fn guest_features(mut self, guest_features: GuestFeatures) -> Self {
+        self.guest_features = guest_features;
+        self
+    }
+
+    /// Set the reset vector of the auxiliary processors, e.g. from
+    /// [OVMF::sev_es_reset_eip](crate::measurement::ovmf::OVMF::sev_es_reset_eip)
+    pub fn ap_eip(mut self, ap_eip: u64) -> Self {
+        self.ap_eip = ap_eip;
+        self
+    }
+
+    /// Set the reset vector of the auxiliary processors from the SEV-ES
+    /// reset block of the OVMF image, e.g.
+    /// [OVMF::ap_reset_vector](crate::measurement::ovmf::OVMF::ap_reset_vector)
+    pub fn ap_reset_vector(self, vector: ApResetVector) -> Self {
+        self.ap_eip(vector.eip().into())
+    }
+
+    /// Set the number of vCPUs, which some presets depend on
+    pub fn vcpus(mut self, vcpus: u64) -> Self {
+        self.cpu_num = Some(vcpus);
+        self
+    }
+
+    /// Write `bytes` at `offset` of the bootstrap processor page
+    pub fn bsp_override(mut self, offset: usize, bytes: &[u8]) -> Self {
+        self.bsp_patches.push((offset, bytes.to_vec()));
+        self
+    }
+
+    /// Write `bytes` at `offset` of the auxiliary processor pages
+    pub fn ap_override(mut self, offset: usize, bytes: &[u8]) -> Self {
+        self.ap_patches.push((offset, bytes.to_vec()));
+        self
+    }
+
+    /// Build the VMSA, checking that every override fits in the page, that
+    /// no reserved guest feature is set and that the auxiliary processors of
+    /// guests with several vCPUs have a reset vector
+    pub fn build(self) -> Result<VMSA, MeasurementError> {
+        self.guest_features.validate()?;
+
+        if let Some(vcpus) = self.cpu_num.filter(|vcpus| *vcpus > 1) {
+            if self.ap_eip == 0 {
+                return Err(MeasurementError::MissingApResetVector(vcpus));
+            }
+        }
+
+        for (offset, bytes) in self.bsp_patches.iter().chain(self.ap_patches.iter()) {
+            if offset + bytes.len() > VMSA_PAGE_SIZE {
+                return Err(MeasurementError::InvalidVmsaPatch(*offset, bytes.len()));
+            }
+        }
+
+        let save_area = |eip: u64| {
+            VMSA::build_save_area(
+                eip,
+                self.guest_features,
+                self.cpu_sig,
+                self.vmm_type,
+                self.cpu_num,
+            )
+        };
+
+        Ok(VMSA {
+            bsp_save_area: save_area(BSP_EIP),
+            ap_save_area: (self.ap_eip > 0).then(|| save_area(self.ap_eip)),
+            bsp_patches: self.bsp_patches,
+            ap_patches: self.ap_patches,
+        })
+    }
+}
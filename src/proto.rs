@@ -0,0 +1,520 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Protocol Buffers messages to exchange SEV-SNP evidence, e.g. over gRPC.
+//!
+//! The messages are defined by hand with [prost] and mirror the `sev.snp.v1`
+//! package of `proto/sev.proto`, which services in other languages can
+//! generate their own bindings from. Each message converts from and into
+//! the type of this crate it mirrors; conversions from a message fail with
+//! a [ProtoError] if a field has the wrong length or is missing.
+//!
+//! Reports are converted field by field, reserved fields included, so that
+//! a report received as a message still verifies against its signature.
+//!
+//! # Example:
+//! ```ignore
+//! use prost::Message;
+//!
+//! let evidence = proto::Evidence::from((&report, &certs[..]));
+//! let bytes = evidence.encode_to_vec();
+//!
+//! let (report, certs) = proto::Evidence::decode(&bytes[..])?.try_into()?;
+//! ```
+
+use crate::{
+    error::ProtoError,
+    firmware::{guest::AttestationReport as Report, host as types},
+};
+
+use std::{
+    convert::{TryFrom, TryInto},
+    io,
+};
+
+pub use prost::Message;
+
+/// The SVNs of a TCB version.
+#[derive(Clone, Copy, PartialEq, Eq, Message)]
+pub struct TcbVersion {
+    /// SVN of the PSP bootloader.
+    #[prost(uint32, tag = "1")]
+    pub bootloader: u32,
+    /// SVN of the PSP operating system.
+    #[prost(uint32, tag = "2")]
+    pub tee: u32,
+    /// SVN of the SNP firmware.
+    #[prost(uint32, tag = "3")]
+    pub snp: u32,
+    /// Lowest patch level of all the cores.
+    #[prost(uint32, tag = "4")]
+    pub microcode: u32,
+    /// The reserved bytes 2 to 5 of the TCB version, little-endian.
+    #[prost(fixed32, tag = "5")]
+    pub reserved: u32,
+}
+
+/// An ECDSA signature, its components little-endian.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct Signature {
+    /// The `r` component (72 bytes).
+    #[prost(bytes = "vec", tag = "1")]
+    pub r: Vec<u8>,
+    /// The `s` component (72 bytes).
+    #[prost(bytes = "vec", tag = "2")]
+    pub s: Vec<u8>,
+}
+
+/// An SEV-SNP attestation report.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct AttestationReport {
+    /// Version number of the report format.
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    /// The guest SVN.
+    #[prost(uint32, tag = "2")]
+    pub guest_svn: u32,
+    /// The guest policy.
+    #[prost(uint64, tag = "3")]
+    pub policy: u64,
+    /// The family ID provided at launch (16 bytes).
+    #[prost(bytes = "vec", tag = "4")]
+    pub family_id: Vec<u8>,
+    /// The image ID provided at launch (16 bytes).
+    #[prost(bytes = "vec", tag = "5")]
+    pub image_id: Vec<u8>,
+    /// The request VMPL for the attestation report.
+    #[prost(uint32, tag = "6")]
+    pub vmpl: u32,
+    /// The signature algorithm used to sign the report.
+    #[prost(uint32, tag = "7")]
+    pub sig_algo: u32,
+    /// Current TCB.
+    #[prost(message, optional, tag = "8")]
+    pub current_tcb: Option<TcbVersion>,
+    /// Information about the platform.
+    #[prost(uint64, tag = "9")]
+    pub plat_info: u64,
+    /// Information about the key used to sign the report.
+    #[prost(uint32, tag = "10")]
+    pub key_info: u32,
+    /// Guest-provided data (64 bytes).
+    #[prost(bytes = "vec", tag = "11")]
+    pub report_data: Vec<u8>,
+    /// The measurement calculated at launch (48 bytes).
+    #[prost(bytes = "vec", tag = "12")]
+    pub measurement: Vec<u8>,
+    /// Data provided by the hypervisor at launch (32 bytes).
+    #[prost(bytes = "vec", tag = "13")]
+    pub host_data: Vec<u8>,
+    /// SHA-384 digest of the ID public key (48 bytes).
+    #[prost(bytes = "vec", tag = "14")]
+    pub id_key_digest: Vec<u8>,
+    /// SHA-384 digest of the author public key (48 bytes).
+    #[prost(bytes = "vec", tag = "15")]
+    pub author_key_digest: Vec<u8>,
+    /// Report ID of the guest (32 bytes).
+    #[prost(bytes = "vec", tag = "16")]
+    pub report_id: Vec<u8>,
+    /// Report ID of the guest's migration agent (32 bytes).
+    #[prost(bytes = "vec", tag = "17")]
+    pub report_id_ma: Vec<u8>,
+    /// Reported TCB version used to derive the VCEK that signed the report.
+    #[prost(message, optional, tag = "18")]
+    pub reported_tcb: Option<TcbVersion>,
+    /// Identifier unique to the chip, zeroes if masked (64 bytes).
+    #[prost(bytes = "vec", tag = "19")]
+    pub chip_id: Vec<u8>,
+    /// Committed TCB.
+    #[prost(message, optional, tag = "20")]
+    pub committed_tcb: Option<TcbVersion>,
+    /// The build number of CurrentVersion.
+    #[prost(uint32, tag = "21")]
+    pub current_build: u32,
+    /// The minor number of CurrentVersion.
+    #[prost(uint32, tag = "22")]
+    pub current_minor: u32,
+    /// The major number of CurrentVersion.
+    #[prost(uint32, tag = "23")]
+    pub current_major: u32,
+    /// The build number of CommittedVersion.
+    #[prost(uint32, tag = "24")]
+    pub committed_build: u32,
+    /// The minor number of CommittedVersion.
+    #[prost(uint32, tag = "25")]
+    pub committed_minor: u32,
+    /// The major number of CommittedVersion.
+    #[prost(uint32, tag = "26")]
+    pub committed_major: u32,
+    /// The current TCB at the time the guest was launched or imported.
+    #[prost(message, optional, tag = "27")]
+    pub launch_tcb: Option<TcbVersion>,
+    /// Signature of the first 0x2A0 bytes of the report.
+    #[prost(message, optional, tag = "28")]
+    pub signature: Option<Signature>,
+    /// The reserved bytes of the report before the launch TCB, in report
+    /// order (30 bytes).
+    #[prost(bytes = "vec", tag = "29")]
+    pub reserved: Vec<u8>,
+    /// The reserved bytes of the report after the launch TCB (168 bytes).
+    #[prost(bytes = "vec", tag = "30")]
+    pub reserved_tail: Vec<u8>,
+}
+
+/// A certificate, CRL or other entry of an extended report's certificate
+/// table.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct CertTableEntry {
+    /// The GUID identifying the entry's type.
+    #[prost(string, tag = "1")]
+    pub guid: String,
+    /// The raw data of the entry.
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: Vec<u8>,
+}
+
+/// An attestation report and the certificates endorsing it.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct Evidence {
+    /// The attestation report.
+    #[prost(message, optional, tag = "1")]
+    pub report: Option<AttestationReport>,
+    /// The certificates returned with the report, if any.
+    #[prost(message, repeated, tag = "2")]
+    pub certificates: Vec<CertTableEntry>,
+}
+
+/// The outcome of verifying evidence.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct VerificationResult {
+    /// Whether the evidence verified.
+    #[prost(bool, tag = "1")]
+    pub verified: bool,
+    /// Why the evidence did not verify, empty if it did.
+    #[prost(string, tag = "2")]
+    pub error: String,
+}
+
+/// Copy a bytes field into the array it mirrors.
+pub(crate) fn array<const N: usize>(
+    field: &'static str,
+    bytes: &[u8],
+) -> Result<[u8; N], ProtoError> {
+    bytes.try_into().map_err(|_| ProtoError::InvalidLength {
+        field,
+        expected: N,
+        actual: bytes.len(),
+    })
+}
+
+/// Narrow an integer field to the integer type it mirrors.
+pub(crate) fn narrow<T: TryFrom<u32>>(field: &'static str, value: u32) -> Result<T, ProtoError> {
+    T::try_from(value).map_err(|_| ProtoError::OutOfRange(field))
+}
+
+/// Unwrap a message field which must be present.
+pub(crate) fn required<T>(field: &'static str, value: Option<T>) -> Result<T, ProtoError> {
+    value.ok_or(ProtoError::MissingField(field))
+}
+
+impl From<types::TcbVersion> for TcbVersion {
+    fn from(tcb: types::TcbVersion) -> Self {
+        let bytes = u64::from(tcb).to_le_bytes();
+
+        Self {
+            bootloader: tcb.bootloader.into(),
+            tee: tcb.tee.into(),
+            snp: tcb.snp.into(),
+            microcode: tcb.microcode.into(),
+            reserved: u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]),
+        }
+    }
+}
+
+impl TryFrom<TcbVersion> for types::TcbVersion {
+    type Error = ProtoError;
+
+    fn try_from(tcb: TcbVersion) -> Result<Self, Self::Error> {
+        let [r0, r1, r2, r3] = tcb.reserved.to_le_bytes();
+
+        Ok(Self::from(u64::from_le_bytes([
+            narrow("bootloader", tcb.bootloader)?,
+            narrow("tee", tcb.tee)?,
+            r0,
+            r1,
+            r2,
+            r3,
+            narrow("snp", tcb.snp)?,
+            narrow("microcode", tcb.microcode)?,
+        ])))
+    }
+}
+
+impl From<&types::CertTableEntry> for CertTableEntry {
+    fn from(entry: &types::CertTableEntry) -> Self {
+        Self {
+            guid: entry.guid_string(),
+            data: entry.data.clone(),
+        }
+    }
+}
+
+impl TryFrom<CertTableEntry> for types::CertTableEntry {
+    type Error = ProtoError;
+
+    fn try_from(entry: CertTableEntry) -> Result<Self, Self::Error> {
+        let CertTableEntry { guid, data } = entry;
+
+        uuid::Uuid::parse_str(&guid)
+            .and_then(|uuid| Self::from_guid(&uuid, data))
+            .map_err(|_| ProtoError::InvalidGuid(guid))
+    }
+}
+
+impl From<(&Report, &[types::CertTableEntry])> for Evidence {
+    fn from((report, certs): (&Report, &[types::CertTableEntry])) -> Self {
+        Self {
+            report: Some(report.into()),
+            certificates: certs.iter().map(CertTableEntry::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<Evidence> for (Report, Vec<types::CertTableEntry>) {
+    type Error = ProtoError;
+
+    fn try_from(evidence: Evidence) -> Result<Self, Self::Error> {
+        Ok((
+            required("report", evidence.report)?.try_into()?,
+            evidence
+                .certificates
+                .into_iter()
+                .map(types::CertTableEntry::try_from)
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+impl<T, E: core::fmt::Display> From<&Result<T, E>> for VerificationResult {
+    fn from(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self {
+                verified: true,
+                error: String::new(),
+            },
+            Err(e) => Self {
+                verified: false,
+                error: e.to_string(),
+            },
+        }
+    }
+}
+
+impl From<VerificationResult> for io::Result<()> {
+    fn from(result: VerificationResult) -> Self {
+        if result.verified {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, result.error))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use types::CertType;
+
+    const TEST_MILAN_VCEK_DER: &[u8] = include_bytes!("../tests/certs_data/vcek_milan.der");
+
+    const SCHEMA: &str = include_str!("../proto/sev.proto");
+
+    #[test]
+    fn report_round_trip() {
+        let report = Report::milan();
+
+        let message = AttestationReport::from(&report);
+        assert_eq!(message.measurement, report.measurement.as_bytes());
+        assert_eq!(
+            message.reported_tcb.unwrap().snp,
+            u32::from(report.reported_tcb.snp)
+        );
+
+        let decoded = AttestationReport::decode(&message.encode_to_vec()[..]).unwrap();
+        let converted = Report::try_from(decoded).unwrap();
+
+        assert_eq!(converted.to_bytes()[..], report.to_bytes()[..]);
+    }
+
+    #[test]
+    fn report_reserved_preserved() {
+        let mut bytes = Report::milan().to_bytes();
+        bytes[0x18a] = 0x11;
+        bytes[0x1f8] = 0x22;
+        let report = Report::from_bytes(&bytes);
+
+        let converted = Report::try_from(AttestationReport::from(&report)).unwrap();
+
+        assert_eq!(converted.to_bytes()[..], bytes[..]);
+    }
+
+    #[cfg(all(feature = "simulation", feature = "crypto_nossl"))]
+    #[test]
+    fn report_reserved_tcb_verifies() {
+        use p384::ecdsa::{signature::DigestVerifier, SigningKey, VerifyingKey};
+        use sha2::Digest;
+
+        let key = SigningKey::from_slice(&[0x42; 48]).unwrap();
+
+        // Turin and later lay out TCB versions with nonzero bytes 2 to 5.
+        let mut report = Report::milan();
+        report.current_tcb = types::TcbVersion::from(0x1800_0000_0a0b_0304);
+        report.reported_tcb = types::TcbVersion::from(0x1700_0001_0000_0304);
+        report.sign_with(&key).unwrap();
+
+        let message = AttestationReport::from(&report);
+        let decoded = AttestationReport::decode(&message.encode_to_vec()[..]).unwrap();
+        let converted = Report::try_from(decoded).unwrap();
+
+        assert_eq!(converted.to_bytes()[..], report.to_bytes()[..]);
+
+        let sig = p384::ecdsa::Signature::try_from(&converted.signature).unwrap();
+        let digest = sha2::Sha384::new_with_prefix(&converted.to_bytes()[..0x2a0]);
+        VerifyingKey::from(&key)
+            .verify_digest(digest, &sig)
+            .unwrap();
+    }
+
+    #[test]
+    fn report_invalid() {
+        let mut message = AttestationReport::from(&Report::milan());
+        message.measurement.pop();
+
+        assert_eq!(
+            Report::try_from(message.clone()).unwrap_err(),
+            ProtoError::InvalidLength {
+                field: "measurement",
+                expected: 48,
+                actual: 47
+            }
+        );
+
+        message = AttestationReport::from(&Report::milan());
+        message.signature = None;
+        assert_eq!(
+            Report::try_from(message.clone()).unwrap_err(),
+            ProtoError::MissingField("signature")
+        );
+
+        message = AttestationReport::from(&Report::milan());
+        message.current_major = 0x100;
+        assert_eq!(
+            Report::try_from(message).unwrap_err(),
+            ProtoError::OutOfRange("current_major")
+        );
+    }
+
+    #[test]
+    fn evidence_round_trip() {
+        let certs = vec![
+            types::CertTableEntry::new(CertType::VCEK, TEST_MILAN_VCEK_DER.to_vec()),
+            types::CertTableEntry::new(CertType::OTHER(uuid::Uuid::from_u128(1)), vec![1, 2, 3]),
+        ];
+
+        let evidence = Evidence::from((&Report::milan(), &certs[..]));
+        assert_eq!(
+            evidence.certificates[0].guid,
+            "63da758d-e664-4564-adc5-f4b93be8accd"
+        );
+
+        let (decoded, decoded_certs): (Report, Vec<types::CertTableEntry>) =
+            Evidence::decode(&evidence.encode_to_vec()[..])
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        assert_eq!(decoded.to_bytes()[..], Report::milan().to_bytes()[..]);
+        assert_eq!(decoded_certs, certs);
+
+        let invalid = Evidence {
+            certificates: vec![CertTableEntry {
+                guid: "vcek".to_string(),
+                data: vec![],
+            }],
+            ..evidence
+        };
+        assert_eq!(
+            <(Report, Vec<types::CertTableEntry>)>::try_from(invalid).unwrap_err(),
+            ProtoError::InvalidGuid("vcek".to_string())
+        );
+    }
+
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    #[test]
+    fn verification_result() {
+        use crate::certs::snp::{builtin::milan, ca, Certificate, Chain, Verifiable};
+
+        let chain = Chain {
+            ca: ca::Chain {
+                ark: milan::ark().unwrap(),
+                ask: milan::ask().unwrap(),
+            },
+            vek: Certificate::from_der(TEST_MILAN_VCEK_DER).unwrap(),
+        };
+
+        let message = AttestationReport::from(&Report::milan());
+        let report = Report::try_from(message).unwrap();
+
+        let result = VerificationResult::from(&(&chain, &report).verify());
+        assert!(result.verified);
+        assert!(result.error.is_empty());
+        assert!(io::Result::from(result).is_ok());
+
+        let mut tampered = report;
+        tampered.measurement.0[0] ^= 0xff;
+
+        let result = VerificationResult::from(&(&chain, &tampered).verify());
+        assert!(!result.verified);
+        assert!(!result.error.is_empty());
+        assert!(io::Result::from(result).is_err());
+    }
+
+    #[test]
+    fn schema_matches_messages() {
+        for message in [
+            "TcbVersion",
+            "Signature",
+            "AttestationReport",
+            "CertTableEntry",
+            "Evidence",
+            "VerificationResult",
+        ] {
+            assert!(
+                SCHEMA.contains(&format!("message {message} {{")),
+                "{} is missing from proto/sev.proto",
+                message
+            );
+        }
+
+        let fields = AttestationReport::from(&Report::milan());
+        let fields = format!("{fields:?}");
+        for line in SCHEMA
+            .split("message AttestationReport {")
+            .nth(1)
+            .unwrap()
+            .split('}')
+            .next()
+            .unwrap()
+            .lines()
+            .filter(|line| !line.trim().starts_with("//"))
+            .filter_map(|line| line.trim().split(" = ").next())
+            .filter_map(|decl| decl.split_whitespace().nth(1))
+        {
+            assert!(
+                fields.contains(&format!("{}: ", line)),
+                "{} is not a field of AttestationReport",
+                line
+            );
+        }
+    }
+}
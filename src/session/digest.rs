@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! The launch digest of SEV and SEV-ES guests.
+
+use std::io::{Error, ErrorKind, Result};
+
+use crate::measurement::digest::{DigestBackend, Sha256};
+
+/// The SHA-256 digest of the plaintext an SEV or SEV-ES guest is launched
+/// with, in the order the AMD SP encrypts it.
+///
+/// The AMD SP encrypts and measures guest data in blocks of
+/// [BLOCK_SIZE](Self::BLOCK_SIZE) bytes and rejects updates which are not
+/// made of whole blocks. Feeding the digest exactly what is encrypted, with
+/// the same checks, gives the same digest however the VMM splits the data
+/// into updates, so the VMM and the tenant can compute it identically.
+pub struct LaunchDigest(Sha256);
+
+impl Default for LaunchDigest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LaunchDigest {
+    /// The size of the blocks the AMD SP measures.
+    pub const BLOCK_SIZE: usize = 16;
+
+    /// Begin an empty digest.
+    pub fn new() -> Self {
+        Self(Sha256::new())
+    }
+
+    /// Begin an empty digest, hashed with `backend`.
+    pub fn with_backend(backend: &dyn DigestBackend) -> Self {
+        Self(Sha256::with_backend(backend))
+    }
+
+    /// Check that `data` is made of whole blocks.
+    pub fn check(data: &[u8]) -> Result<()> {
+        if data.len() % Self::BLOCK_SIZE != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "launch updates must be a multiple of {} bytes, not {}",
+                    Self::BLOCK_SIZE,
+                    data.len()
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Pad `data` with zeroes to whole blocks, the way guest data which is
+    /// not block-aligned is copied into the guest before it is encrypted.
+    pub fn pad(data: &[u8]) -> Vec<u8> {
+        let mut padded = data.to_vec();
+        padded.resize(
+            (data.len() + Self::BLOCK_SIZE - 1) / Self::BLOCK_SIZE * Self::BLOCK_SIZE,
+            0,
+        );
+        padded
+    }
+
+    /// Measure guest data (LAUNCH_UPDATE_DATA) or a VMSA
+    /// (LAUNCH_UPDATE_VMSA), which must be made of whole blocks.
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        Self::check(data)?;
+        self.0.update(data);
+
+        Ok(())
+    }
+
+    /// The digest of the data measured so far.
+    pub fn finish(self) -> [u8; 32] {
+        self.0.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunking() {
+        let data: Vec<u8> = (0..8192).map(|i| i as u8).collect();
+
+        let mut whole = LaunchDigest::new();
+        whole.update(&data).unwrap();
+
+        let mut chunked = LaunchDigest::new();
+        for chunk in data.chunks(48) {
+            chunked.update(chunk).unwrap();
+        }
+
+        assert_eq!(whole.finish(), chunked.finish());
+        assert_eq!(LaunchDigest::new().finish(), openssl::sha::sha256(&[]),);
+    }
+
+    #[test]
+    fn alignment() {
+        let mut digest = LaunchDigest::new();
+        assert_eq!(
+            digest.update(&[0u8; 20]).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        let padded = LaunchDigest::pad(&[0xffu8; 20]);
+        assert_eq!(padded.len(), 32);
+        assert_eq!(&padded[20..], &[0u8; 12]);
+        assert_eq!(LaunchDigest::pad(&[0u8; 32]).len(), 32);
+        digest.update(&padded).unwrap();
+    }
+}
@@ -92,7 +92,7 @@ impl Key {
         Ok(out)
     }
 
-    pub fn mac(&self, data: &[u8]) -> Result<[u8; 32]> {
+    pub fn mac(&self, data: &[u8]) -> std::result::Result<[u8; 32], openssl::error::ErrorStack> {
         let mut mac = [0u8; 32];
         let key = pkey::PKey::hmac(self)?;
         let mut sig = sign::Signer::new(hash::MessageDigest::sha256(), &key)?;
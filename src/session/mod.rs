@@ -3,10 +3,15 @@
 //! Utilities for creating a secure channel and facilitating the
 //! attestation process between the tenant and the AMD SP.
 
+mod digest;
 mod key;
 
+pub use digest::LaunchDigest;
+
 use super::*;
 
+use crate::error::SessionError;
+
 use std::io::{Error, ErrorKind, Result};
 
 use openssl::*;
@@ -16,7 +21,7 @@ pub struct Initialized;
 
 /// Indicates the Session is currently accepting data to include
 /// in its measurement for comparison against the AMD SP's measurement.
-pub struct Measuring(hash::Hasher);
+pub struct Measuring(LaunchDigest);
 
 /// Denotes an agreeable measurement with the AMD SP.
 pub struct Verified(launch::sev::Measurement);
@@ -36,12 +41,6 @@ pub struct Session<T> {
     data: T,
 }
 
-impl launch::sev::Policy {
-    fn bytes(self) -> [u8; 4] {
-        unsafe { std::mem::transmute(self) }
-    }
-}
-
 impl std::convert::TryFrom<launch::sev::Policy> for Session<Initialized> {
     type Error = std::io::Error;
 
@@ -76,7 +75,7 @@ impl Session<Initialized> {
         assert_eq!(off, wrap.len());
 
         let wmac = kik.mac(&wrap)?;
-        let pmac = self.tik.mac(&self.policy.bytes())?;
+        let pmac = self.tik.mac(&self.policy.to_bytes())?;
 
         Ok(launch::sev::Session {
             policy_mac: pmac,
@@ -134,7 +133,7 @@ impl Session<Initialized> {
             policy: self.policy,
             tek: self.tek,
             tik: self.tik,
-            data: Measuring(hash::Hasher::new(hash::MessageDigest::sha256())?),
+            data: Measuring(LaunchDigest::new()),
         })
     }
 
@@ -144,18 +143,18 @@ impl Session<Initialized> {
         digest: &[u8],
         build: Build,
         msr: launch::sev::Measurement,
-    ) -> Result<Session<Verified>> {
+    ) -> std::result::Result<Session<Verified>, SessionError> {
         let key = pkey::PKey::hmac(&self.tik)?;
         let mut sig = sign::Signer::new(hash::MessageDigest::sha256(), &key)?;
 
         sig.update(&[0x04u8])?;
         sig.update(&[build.version.major, build.version.minor, build.build])?;
-        sig.update(&self.policy.bytes())?;
+        sig.update(&self.policy.to_bytes())?;
         sig.update(digest)?;
         sig.update(&msr.mnonce)?;
 
         if sig.sign_to_vec()? != msr.measure {
-            return Err(ErrorKind::InvalidInput.into());
+            return Err(SessionError::MeasurementMismatch);
         }
 
         Ok(Session {
@@ -185,18 +184,19 @@ impl Session<Measuring> {
     /// Adds additional data to the digest.
     ///
     /// Everything measured by the AMD SP should also be measured by
-    /// the `Session` to ensure both measurements are the same.
+    /// the `Session` to ensure both measurements are the same. Like the
+    /// AMD SP, the `Session` only measures whole blocks; see [LaunchDigest].
     pub fn update_data(&mut self, data: &[u8]) -> std::io::Result<()> {
-        Ok(self.data.0.update(data)?)
+        self.data.0.update(data)
     }
 
     /// Verifies the session's measurement against the AMD SP's measurement.
     pub fn verify(
-        mut self,
+        self,
         build: Build,
         msr: launch::sev::Measurement,
-    ) -> Result<Session<Verified>> {
-        let digest = self.data.0.finish()?;
+    ) -> std::result::Result<Session<Verified>, SessionError> {
+        let digest = self.data.0.finish();
         let session = Session {
             policy: self.policy,
             tek: self.tek,
@@ -214,7 +214,7 @@ impl Session<Measuring> {
         build: Build,
         msr: launch::sev::Measurement,
         digest: &[u8],
-    ) -> Result<Session<Verified>> {
+    ) -> std::result::Result<Session<Verified>, SessionError> {
         let session = Session {
             policy: self.policy,
             tek: self.tek,
@@ -224,6 +224,21 @@ impl Session<Measuring> {
 
         session.verify(digest, build, msr)
     }
+
+    /// Verifies the AMD SP's measurement, after checking that it was taken
+    /// in the launch this session started. Prefer this over
+    /// [verify](Self::verify) when several launches are in flight.
+    pub fn verify_bound(
+        self,
+        build: Build,
+        msr: launch::sev::BoundMeasurement,
+    ) -> std::result::Result<Session<Verified>, SessionError> {
+        if self.tik.mac(&self.policy.to_bytes())? != msr.session.policy_mac {
+            return Err(SessionError::WrongSession);
+        }
+
+        self.verify(build, msr.measurement)
+    }
 }
 
 impl Session<Verified> {
@@ -242,7 +257,7 @@ impl Session<Verified> {
         let mut sig = sign::Signer::new(hash::MessageDigest::sha256(), &key)?;
 
         sig.update(&[0x01u8])?;
-        sig.update(&unsafe { std::mem::transmute::<_, [u8; 4]>(flags) })?;
+        sig.update(&flags.to_bytes())?;
         sig.update(&iv)?;
         sig.update(&(data.len() as u32).to_le_bytes())?;
         sig.update(&(ciphertext.len() as u32).to_le_bytes())?;
@@ -309,6 +324,36 @@ mod initialized {
         );
     }
 
+    #[test]
+    fn policy_mac() {
+        let session = Session {
+            policy: launch::sev::Policy {
+                flags: launch::sev::PolicyFlags::NO_DEBUG
+                    | launch::sev::PolicyFlags::ENCRYPTED_STATE,
+                minfw: Version {
+                    major: 0,
+                    minor: 24,
+                },
+            },
+            tek: key::Key::new(vec![0u8; 16]),
+            tik: key::Key::new(vec![0u8; 16]),
+            data: Initialized,
+        };
+
+        let launch = session
+            .session([0u8; 16], [0u8; 16], key::Key::zeroed(16))
+            .unwrap();
+
+        assert_eq!(
+            launch.policy_mac,
+            [
+                0xf7, 0x8f, 0x3f, 0x72, 0xc5, 0xc3, 0xf9, 0x55, 0x31, 0x21, 0xfa, 0xf0, 0x51, 0x94,
+                0xfb, 0xa5, 0x40, 0x52, 0xc9, 0xb1, 0x0d, 0x82, 0x8b, 0x33, 0x7b, 0x69, 0x6f, 0x4f,
+                0x12, 0x43, 0xe4, 0x25,
+            ]
+        );
+    }
+
     #[test]
     fn verify() {
         let digest = [
@@ -356,4 +401,68 @@ mod initialized {
 
         session.verify(&digest, build, measurement).unwrap();
     }
+
+    #[test]
+    fn verify_bound() {
+        let measurement = launch::sev::Measurement {
+            measure: [
+                0x6f, 0xaa, 0xb2, 0xda, 0xae, 0x38, 0x9b, 0xcd, 0x34, 0x05, 0xa0, 0x5d, 0x6c, 0xaf,
+                0xe3, 0x3c, 0x04, 0x14, 0xf7, 0xbe, 0xdd, 0x0b, 0xae, 0x19, 0xba, 0x5f, 0x38, 0xb7,
+                0xfd, 0x16, 0x64, 0xea,
+            ],
+            mnonce: [
+                0x4f, 0xbe, 0x0b, 0xed, 0xba, 0xd6, 0xc8, 0x6a, 0xe8, 0xf6, 0x89, 0x71, 0xd1, 0x03,
+                0xe5, 0x54,
+            ],
+        };
+
+        let tik = vec![
+            0x66, 0x32, 0x0d, 0xb7, 0x31, 0x58, 0xa3, 0x5a, 0x25, 0x5d, 0x05, 0x17, 0x58, 0xe9,
+            0x5e, 0xd4,
+        ];
+
+        let session = || Session {
+            policy: launch::sev::Policy::default(),
+            tek: key::Key::new(vec![0u8; 16]),
+            tik: key::Key::new(tik.clone()),
+            data: Initialized,
+        };
+        let build = Build {
+            version: Version {
+                major: 0x00,
+                minor: 0x12,
+            },
+            build: 0x0f,
+        };
+
+        let ours = session()
+            .session([0u8; 16], [0u8; 16], key::Key::zeroed(16))
+            .unwrap();
+        let mut other = ours;
+        other.policy_mac = [0u8; 32];
+
+        assert!(session()
+            .measure()
+            .unwrap()
+            .verify_bound(
+                build,
+                launch::sev::BoundMeasurement {
+                    measurement,
+                    session: other,
+                },
+            )
+            .is_err());
+
+        session()
+            .measure()
+            .unwrap()
+            .verify_bound(
+                build,
+                launch::sev::BoundMeasurement {
+                    measurement,
+                    session: ours,
+                },
+            )
+            .unwrap();
+    }
 }
@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A stable, versioned format to store serialized types of this crate.
+//!
+//! A snapshot starts with a 10-byte header: the [MAGIC] bytes, the 4-byte
+//! [Versioned::KIND] tag of the type it holds and the little-endian
+//! [Versioned::VERSION] of the layout it was written with, followed by the
+//! bincode encoding of the type. Whenever the layout of a type changes, its
+//! `VERSION` is bumped and [Versioned::migrate] learns to decode the
+//! previous layouts, so that evidence stored long ago remains readable after
+//! upgrading the crate.
+//!
+//! Types meant to be reviewed or edited, such as measurement recipes and
+//! allow-lists, are stored as JSON documents with the same envelope:
+//!
+//! ```text
+//! { "kind": "RCPE", "version": 1, "payload": { ... } }
+//! ```
+//!
+//! # Example:
+//! ```ignore
+//! use sev::snapshot::Versioned;
+//!
+//! let bytes = report.to_versioned_bytes()?;
+//! std::fs::write("report.snapshot", &bytes)?;
+//!
+//! let report = AttestationReport::from_versioned_bytes(&std::fs::read("report.snapshot")?)?;
+//! ```
+
+use crate::error::SnapshotError;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use std::{
+    convert::TryInto,
+    fs::{self, File},
+    io::{BufReader, Read, Write},
+    path::Path,
+};
+
+/// The magic bytes every snapshot starts with.
+pub const MAGIC: [u8; 4] = *b"SEVS";
+
+/// The size of the snapshot header.
+pub const HEADER_SIZE: usize = 10;
+
+/// A type which is stored in versioned snapshots
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Tag identifying the type, so that a snapshot of one type is never
+    /// read as another.
+    const KIND: [u8; 4];
+
+    /// Version of the layout this crate writes.
+    const VERSION: u16;
+
+    /// Decode the `payload` of a snapshot written with the layout of an
+    /// earlier `version`.
+    ///
+    /// Types implement this once they bump their [Versioned::VERSION],
+    /// typically by decoding a frozen copy of the previous layout and
+    /// converting it. By default no earlier layout can be read.
+    fn migrate(version: u16, payload: &[u8]) -> Result<Self, SnapshotError> {
+        let _ = payload;
+
+        Err(SnapshotError::UnsupportedVersion {
+            kind: Self::KIND,
+            version,
+        })
+    }
+
+    /// Decode the `payload` of a JSON document written with the layout of an
+    /// earlier `version`, like [Versioned::migrate]. By default no earlier
+    /// layout can be read.
+    fn migrate_json(version: u16, payload: serde_json::Value) -> Result<Self, SnapshotError> {
+        let _ = payload;
+
+        Err(SnapshotError::UnsupportedVersion {
+            kind: Self::KIND,
+            version,
+        })
+    }
+
+    /// Serialize into a snapshot of the current layout.
+    fn to_versioned_bytes(&self) -> Result<Vec<u8>, SnapshotError> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&Self::KIND);
+        bytes.extend_from_slice(&Self::VERSION.to_le_bytes());
+
+        bincode::serialize_into(&mut bytes, self).map_err(|e| SnapshotError::Bincode(*e))?;
+
+        Ok(bytes)
+    }
+
+    /// Deserialize a snapshot of any layout this crate still reads.
+    fn from_versioned_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let (kind, version, payload) = header(bytes)?;
+
+        if kind != Self::KIND {
+            return Err(SnapshotError::Kind {
+                expected: Self::KIND,
+                actual: kind,
+            });
+        }
+
+        if version == Self::VERSION {
+            bincode::deserialize(payload).map_err(|e| SnapshotError::Bincode(*e))
+        } else if version < Self::VERSION {
+            Self::migrate(version, payload)
+        } else {
+            Err(SnapshotError::UnsupportedVersion {
+                kind: Self::KIND,
+                version,
+            })
+        }
+    }
+
+    /// Write a JSON document of the current layout.
+    fn to_json_writer(&self, writer: impl Write) -> Result<(), SnapshotError> {
+        let document = Document {
+            kind: String::from_utf8_lossy(&Self::KIND).into_owned(),
+            version: Self::VERSION,
+            payload: self,
+        };
+
+        Ok(serde_json::to_writer_pretty(writer, &document)?)
+    }
+
+    /// Read a JSON document of any layout this crate still reads.
+    fn from_json_reader(reader: impl Read) -> Result<Self, SnapshotError> {
+        let document: Document<serde_json::Value> = serde_json::from_reader(reader)?;
+
+        let kind: [u8; 4] = document
+            .kind
+            .as_bytes()
+            .try_into()
+            .map_err(|_| SnapshotError::Magic)?;
+
+        if kind != Self::KIND {
+            return Err(SnapshotError::Kind {
+                expected: Self::KIND,
+                actual: kind,
+            });
+        }
+
+        if document.version == Self::VERSION {
+            Ok(serde_json::from_value(document.payload)?)
+        } else if document.version < Self::VERSION {
+            Self::migrate_json(document.version, document.payload)
+        } else {
+            Err(SnapshotError::UnsupportedVersion {
+                kind: Self::KIND,
+                version: document.version,
+            })
+        }
+    }
+
+    /// Read a JSON document from the file at `path`.
+    fn load_json(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::from_json_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Write a JSON document to the file at `path`.
+    ///
+    /// The document is written next to `path` first and then renamed over
+    /// it, so that readers of the file never see a partial document.
+    fn save_json(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut file = File::create(&tmp)?;
+        self.to_json_writer(&mut file)?;
+        file.sync_all()?;
+
+        Ok(fs::rename(tmp, path)?)
+    }
+}
+
+/// The envelope of a JSON document.
+#[derive(Serialize, Deserialize)]
+struct Document<T> {
+    kind: String,
+    version: u16,
+    payload: T,
+}
+
+/// Split a snapshot into the kind and version of its header and its
+/// payload.
+pub fn header(bytes: &[u8]) -> Result<([u8; 4], u16, &[u8]), SnapshotError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(SnapshotError::Truncated);
+    }
+
+    let (header, payload) = bytes.split_at(HEADER_SIZE);
+
+    if header[..4] != MAGIC {
+        return Err(SnapshotError::Magic);
+    }
+
+    // The slices have the lengths of the arrays, so these cannot fail.
+    let kind = header[4..8].try_into().unwrap();
+    let version = u16::from_le_bytes(header[8..].try_into().unwrap());
+
+    Ok((kind, version, payload))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde::Deserialize;
+
+    /// The first layout of `Record`, frozen once the layout changed.
+    #[derive(Serialize, Deserialize)]
+    struct RecordV1 {
+        value: u32,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Record {
+        value: u64,
+        label: String,
+    }
+
+    impl Versioned for Record {
+        const KIND: [u8; 4] = *b"TEST";
+        const VERSION: u16 = 2;
+
+        fn migrate(version: u16, payload: &[u8]) -> Result<Self, SnapshotError> {
+            match version {
+                1 => {
+                    let v1: RecordV1 =
+                        bincode::deserialize(payload).map_err(|e| SnapshotError::Bincode(*e))?;
+
+                    Ok(Self {
+                        value: v1.value.into(),
+                        label: String::new(),
+                    })
+                }
+                _ => Err(SnapshotError::UnsupportedVersion {
+                    kind: Self::KIND,
+                    version,
+                }),
+            }
+        }
+    }
+
+    fn snapshot(version: u16, payload: &[u8]) -> Vec<u8> {
+        [&MAGIC[..], b"TEST", &version.to_le_bytes(), payload].concat()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = Record {
+            value: 42,
+            label: "guest".into(),
+        };
+
+        let bytes = record.to_versioned_bytes().unwrap();
+        assert_eq!(&bytes[..HEADER_SIZE], &snapshot(2, &[])[..]);
+        assert_eq!(Record::from_versioned_bytes(&bytes).unwrap(), record);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let v1 = bincode::serialize(&RecordV1 { value: 7 }).unwrap();
+
+        assert_eq!(
+            Record::from_versioned_bytes(&snapshot(1, &v1)).unwrap(),
+            Record {
+                value: 7,
+                label: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            Record::from_versioned_bytes(&MAGIC),
+            Err(SnapshotError::Truncated)
+        ));
+        assert!(matches!(
+            Record::from_versioned_bytes(b"SEVXTEST\x02\x00"),
+            Err(SnapshotError::Magic)
+        ));
+        assert!(matches!(
+            Record::from_versioned_bytes(&[&MAGIC[..], b"SNPR\x02\x00"].concat()),
+            Err(SnapshotError::Kind { actual, .. }) if &actual == b"SNPR"
+        ));
+        assert!(matches!(
+            Record::from_versioned_bytes(&snapshot(3, &[])),
+            Err(SnapshotError::UnsupportedVersion { version: 3, .. })
+        ));
+        assert!(matches!(
+            Record::from_versioned_bytes(&snapshot(0, &[])),
+            Err(SnapshotError::UnsupportedVersion { version: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_json() {
+        let record = Record {
+            value: 42,
+            label: "guest".into(),
+        };
+
+        let mut json = vec![];
+        record.to_json_writer(&mut json).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["kind"], "TEST");
+        assert_eq!(value["version"], 2);
+        assert_eq!(value["payload"]["label"], "guest");
+        assert_eq!(Record::from_json_reader(&json[..]).unwrap(), record);
+
+        let json = r#"{ "kind": "SNPR", "version": 2, "payload": {} }"#;
+        assert!(matches!(
+            Record::from_json_reader(json.as_bytes()),
+            Err(SnapshotError::Kind { actual, .. }) if &actual == b"SNPR"
+        ));
+
+        let json = r#"{ "kind": "TEST", "version": 1, "payload": { "value": 7 } }"#;
+        assert!(matches!(
+            Record::from_json_reader(json.as_bytes()),
+            Err(SnapshotError::UnsupportedVersion { version: 1, .. })
+        ));
+
+        let json = r#"{ "kind": "TEST", "version": 3, "payload": {} }"#;
+        assert!(matches!(
+            Record::from_json_reader(json.as_bytes()),
+            Err(SnapshotError::UnsupportedVersion { version: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Hashes computed with the cryptographic library the crate was built with.
+
+/// The SHA-384 digest of `parts`, hashed one after the other.
+#[cfg(feature = "openssl")]
+pub(crate) fn sha384(parts: &[&[u8]]) -> [u8; 48] {
+    let mut hasher = openssl::sha::Sha384::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finish()
+}
+
+/// The SHA-384 digest of `parts`, hashed one after the other.
+#[cfg(feature = "crypto_nossl")]
+pub(crate) fn sha384(parts: &[&[u8]]) -> [u8; 48] {
+    use sha2::Digest;
+
+    let mut hasher = sha2::Sha384::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// The SHA-512 digest of `parts`, hashed one after the other.
+#[cfg(feature = "openssl")]
+pub(crate) fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = openssl::sha::Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finish()
+}
+
+/// The SHA-512 digest of `parts`, hashed one after the other.
+#[cfg(feature = "crypto_nossl")]
+pub(crate) fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    use sha2::Digest;
+
+    let mut hasher = sha2::Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
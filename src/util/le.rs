@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Explicitly little-endian codecs for the binary layouts of the AMD SP.
+//!
+//! The AMD SP lays out its structures little-endian with fixed-size fields.
+//! Decoding them field by field, rather than copying their bytes over a
+//! `#[repr(C)]` struct, keeps them correct on big-endian hosts and
+//! independent of the host's pointer width.
+
+use core::convert::TryInto;
+
+/// Reads little-endian fields from the front of a byte string
+pub(crate) struct LeReader<'a>(&'a [u8]);
+
+impl<'a> LeReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+
+    /// Read the next `N` bytes.
+    ///
+    /// # Panics
+    ///
+    /// If fewer than `N` bytes remain.
+    pub fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let (field, rest) = self.0.split_at(N);
+        self.0 = rest;
+
+        field.try_into().unwrap()
+    }
+
+    pub fn u8(&mut self) -> u8 {
+        let [byte] = self.bytes();
+        byte
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes())
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes())
+    }
+}
+
+/// Writes little-endian fields to the front of a byte string
+pub(crate) struct LeWriter<'a>(&'a mut [u8]);
+
+impl<'a> LeWriter<'a> {
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        Self(bytes)
+    }
+
+    /// Write `field` to the next bytes.
+    ///
+    /// # Panics
+    ///
+    /// If fewer than `field.len()` bytes remain.
+    pub fn bytes(&mut self, field: &[u8]) {
+        let (next, rest) = core::mem::take(&mut self.0).split_at_mut(field.len());
+        next.copy_from_slice(field);
+        self.0 = rest;
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut bytes = [0u8; 15];
+
+        let mut writer = LeWriter::new(&mut bytes);
+        writer.u8(0xaa);
+        writer.u32(0x0403_0201);
+        writer.u64(0x0c0b_0a09_0807_0605);
+        writer.bytes(&[0xbb, 0xcc]);
+
+        assert_eq!(
+            bytes,
+            [0xaa, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 0xbb, 0xcc]
+        );
+
+        let mut reader = LeReader::new(&bytes);
+        assert_eq!(reader.u8(), 0xaa);
+        assert_eq!(reader.u32(), 0x0403_0201);
+        assert_eq!(reader.u64(), 0x0c0b_0a09_0807_0605);
+        assert_eq!(reader.bytes(), [0xbb, 0xcc]);
+    }
+}
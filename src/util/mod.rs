@@ -2,28 +2,29 @@
 
 //! Helpful primitives for developing the crate.
 
+#[cfg(feature = "std")]
 pub mod cached_chain;
+#[cfg(all(
+    feature = "snp",
+    feature = "std",
+    any(feature = "openssl", feature = "crypto_nossl")
+))]
+pub(crate) mod digest;
 mod impl_const_id;
+#[cfg(feature = "snp-types")]
+mod le;
 
+#[cfg(feature = "snp-types")]
+pub(crate) use le::{LeReader, LeWriter};
+
+#[cfg(feature = "std")]
 use std::{
     io::{Read, Result, Write},
     mem::{size_of, MaybeUninit},
     slice::{from_raw_parts, from_raw_parts_mut},
 };
 
-#[cfg(any(feature = "sev", feature = "snp"))]
-pub fn hexdump(bytes: &[u8]) -> String {
-    let mut retval: String = String::new();
-    for (i, byte) in bytes.iter().enumerate() {
-        if (i % 16) == 0 {
-            retval.push('\n');
-        }
-        retval.push_str(&format!("{byte:02x} "));
-    }
-    retval.push('\n');
-    retval
-}
-
+#[cfg(feature = "std")]
 pub trait TypeLoad: Read {
     fn load<T: Sized + Copy>(&mut self) -> Result<T> {
         #[allow(clippy::uninit_assumed_init)]
@@ -35,6 +36,7 @@ pub trait TypeLoad: Read {
     }
 }
 
+#[cfg(feature = "std")]
 pub trait TypeSave: Write {
     fn save<T: Sized + Copy>(&mut self, value: &T) -> Result<()> {
         let p = value as *const T as *const u8;
@@ -43,5 +45,7 @@ pub trait TypeSave: Write {
     }
 }
 
+#[cfg(feature = "std")]
 impl<T: Read> TypeLoad for T {}
+#[cfg(feature = "std")]
 impl<T: Write> TypeSave for T {}
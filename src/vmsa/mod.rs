@@ -45,7 +45,7 @@ const ATTR_W_MASK: u16 = 1 << ATTR_W_SHIFT;
 /// The layout of a VMCB struct is documented in Table B-1 of the
 /// AMD64 Architecture Programmer’s Manual, Volume 2: System Programming
 #[repr(C, packed)]
-#[derive(Default, Serialize, Deserialize, Clone, Copy)]
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct VmcbSegment {
     /// Segment selector: documented in Figure 4-3 of the
     /// AMD64 Architecture Programmer’s Manual, Volume 2: System Programming
@@ -66,7 +66,7 @@ pub struct VmcbSegment {
 /// The layout of a VMCB struct is documented in Table B-4 of the
 /// AMD64 Architecture Programmer’s Manual, Volume 2: System Programming
 #[repr(C, packed)]
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
 pub struct Vmsa {
     /// Extra segment.
     es: VmcbSegment,
@@ -473,6 +473,126 @@ impl Vmsa {
     }
 }
 
+impl VmcbSegment {
+    /// Segment selector.
+    pub fn selector(&self) -> u16 {
+        self.selector
+    }
+
+    /// Segment attributes.
+    pub fn attrib(&self) -> u16 {
+        self.attrib
+    }
+
+    /// Segment limit.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Segment base address.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+}
+
+/// Read accessors of the VMSA fields, e.g. to inspect the state of a
+/// crashed guest.
+macro_rules! accessors {
+    ($($(#[$doc:meta])* $field:ident: $type:ty,)*) => {
+        impl Vmsa {
+            $(
+                $(#[$doc])*
+                pub fn $field(&self) -> $type {
+                    self.$field
+                }
+            )*
+        }
+    };
+}
+
+accessors! {
+    /// Extra segment.
+    es: VmcbSegment,
+    /// Code segment.
+    cs: VmcbSegment,
+    /// Stack segment.
+    ss: VmcbSegment,
+    /// Data segment.
+    ds: VmcbSegment,
+    /// FS segment.
+    fs: VmcbSegment,
+    /// GS segment.
+    gs: VmcbSegment,
+    /// Global Descriptor Table.
+    gdtr: VmcbSegment,
+    /// Local Descriptor Table.
+    ldtr: VmcbSegment,
+    /// Interrupt Descriptor Table.
+    idtr: VmcbSegment,
+    /// Task register.
+    tr: VmcbSegment,
+    /// Current privilege level.
+    cpl: u8,
+    /// Extended features enable register.
+    efer: u64,
+    /// Control register 0.
+    cr0: u64,
+    /// Control register 2, the address of the last page fault.
+    cr2: u64,
+    /// Control register 3.
+    cr3: u64,
+    /// Control register 4.
+    cr4: u64,
+    /// Debug register 6.
+    dr6: u64,
+    /// Debug register 7.
+    dr7: u64,
+    /// RFLAGS register.
+    rflags: u64,
+    /// Instruction pointer.
+    rip: u64,
+    /// Stack pointer.
+    rsp: u64,
+    /// RAX register.
+    rax: u64,
+    /// RBX register.
+    rbx: u64,
+    /// RCX register.
+    rcx: u64,
+    /// RDX register.
+    rdx: u64,
+    /// RBP register.
+    rbp: u64,
+    /// RSI register.
+    rsi: u64,
+    /// RDI register.
+    rdi: u64,
+    /// R8 register.
+    r8: u64,
+    /// R9 register.
+    r9: u64,
+    /// R10 register.
+    r10: u64,
+    /// R11 register.
+    r11: u64,
+    /// R12 register.
+    r12: u64,
+    /// R13 register.
+    r13: u64,
+    /// R14 register.
+    r14: u64,
+    /// R15 register.
+    r15: u64,
+    /// Exit code of the last VMGEXIT.
+    sw_exit_code: u64,
+    /// First exit information of the last VMGEXIT.
+    sw_exit_info_1: u64,
+    /// Second exit information of the last VMGEXIT.
+    sw_exit_info_2: u64,
+    /// XCR0 register.
+    xcr0: u64,
+}
+
 impl Default for Vmsa {
     fn default() -> Self {
         unsafe { std::mem::zeroed() }
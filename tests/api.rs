@@ -1,9 +1,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
-#[cfg(all(feature = "snp", target_os = "linux"))]
+#[cfg(all(feature = "sev", target_os = "linux"))]
 mod sev {
     use sev::cached_chain;
-    use sev::{certs::sev::sev::Usage, firmware::host::Firmware, Build, Version};
+    use sev::{
+        certs::sev::sev::Usage,
+        firmware::host::{DangerousOps, Firmware, HostCommand, SharedFirmware},
+        Build, Version,
+    };
 
     use serial_test::serial;
 
@@ -22,7 +26,7 @@ mod sev {
     #[serial]
     fn platform_reset() {
         let mut fw = Firmware::open().unwrap();
-        fw.platform_reset().unwrap();
+        fw.platform_reset(&DangerousOps::acknowledge()).unwrap();
         rm_cached_chain();
     }
 
@@ -48,7 +52,7 @@ mod sev {
     #[serial]
     fn pek_generate() {
         let mut fw = Firmware::open().unwrap();
-        fw.pek_generate().unwrap();
+        fw.pek_generate(&DangerousOps::acknowledge()).unwrap();
         rm_cached_chain();
     }
 
@@ -65,7 +69,7 @@ mod sev {
     #[serial]
     fn pdh_generate() {
         let mut fw = Firmware::open().unwrap();
-        fw.pdh_generate().unwrap();
+        fw.pdh_generate(&DangerousOps::acknowledge()).unwrap();
         rm_cached_chain();
     }
 
@@ -86,6 +90,21 @@ mod sev {
         chain.verify().unwrap();
     }
 
+    #[cfg_attr(not(has_sev), ignore)]
+    #[cfg(all(feature = "sev", feature = "openssl"))]
+    #[test]
+    #[serial]
+    fn export_cert_chain() {
+        // The ASK-signed CEK of the cached chain, e.g. downloaded by sevctl.
+        let cek = cached_chain::get().unwrap().sev.cek;
+
+        let mut fw = Firmware::open().unwrap();
+        let chain = fw.export_cert_chain(cek).unwrap();
+
+        assert_eq!(chain.sev.pdh, Usage::PDH);
+        assert_eq!(chain.sev.cek, Usage::CEK);
+    }
+
     #[cfg(feature = "openssl")]
     #[cfg_attr(not(all(has_sev, feature = "dangerous_hw_tests")), ignore)]
     #[test]
@@ -107,7 +126,7 @@ mod sev {
         assert_eq!(oca, chain.oca);
         chain.verify().unwrap();
 
-        fw.platform_reset().unwrap();
+        fw.platform_reset(&DangerousOps::acknowledge()).unwrap();
     }
 
     #[cfg_attr(not(has_sev), ignore)]
@@ -117,6 +136,26 @@ mod sev {
         let id = fw.get_identifier().unwrap();
         assert_ne!(Vec::from(id), vec![0u8; 64]);
     }
+
+    #[cfg_attr(not(has_sev), ignore)]
+    #[test]
+    fn capabilities() {
+        let mut fw = Firmware::open().unwrap();
+        let caps = fw.capabilities();
+        assert!(caps.processor.sev);
+        assert!(caps.supports(HostCommand::PlatformStatus));
+    }
+
+    #[cfg_attr(not(has_sev), ignore)]
+    #[test]
+    fn shared_firmware() {
+        let fw = SharedFirmware::open().unwrap();
+        let worker = fw.clone();
+
+        std::thread::spawn(move || worker.run(|fw| fw.platform_status().unwrap()).unwrap())
+            .join()
+            .unwrap();
+    }
 }
 
 #[cfg(all(feature = "snp", target_os = "linux"))]
@@ -182,7 +221,7 @@ mod snp {
     #[serial]
     fn set_config() {
         let mut fw: Firmware = Firmware::open().unwrap();
-        let new_config = Config::new(TcbVersion::new(1, 0, 1, 1), MaskId(31));
+        let new_config = Config::new(TcbVersion::new(1, 0, 1, 1), MaskId(0b11));
         fw.snp_set_config(new_config).unwrap();
     }
 }
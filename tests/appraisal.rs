@@ -0,0 +1,847 @@
+// SPDX-License-Identifier: Apache-2.0
+
+#![cfg(all(feature = "std", feature = "snp"))]
+
+mod common;
+
+use common::report;
+
+use sev::{appraisal::*, firmware::guest::SigningKey};
+
+use std::time::{Duration, SystemTime};
+
+const MEASUREMENT: &str = "7a1e5c266c0108dbc9bb94fa926951320940915d0aafb42464bd88b579ea158d3e1a0dc39b2c60bd95b9c480cd81841f";
+
+const NONCE: &str = "d447b55d197491bfe15cf298f9de9986";
+
+fn context(age: Duration) -> AppraisalContext {
+    let now = SystemTime::now();
+
+    AppraisalContext {
+        now,
+        ..AppraisalContext::new().nonce(hex::decode(NONCE).unwrap(), now - age)
+    }
+}
+
+fn policy() -> AppraisalPolicy {
+    AppraisalPolicy {
+        min_tcb: Some(MinTcb {
+            bootloader: 3,
+            tee: 0,
+            snp: 8,
+            microcode: 115,
+        }),
+        policy: Some(PolicyRequirements {
+            debug: Some(false),
+            smt: Some(true),
+            ..Default::default()
+        }),
+        measurements: vec![MEASUREMENT.parse().unwrap()],
+        signers: vec![SigningKey::Vcek],
+        max_age_secs: Some(60),
+    }
+}
+
+#[test]
+fn passes() {
+    let appraisal = policy().appraise(&report(), &context(Duration::from_secs(10)));
+
+    assert!(appraisal.passed(), "{:?}", appraisal);
+    assert_eq!(
+        appraisal
+            .results
+            .iter()
+            .map(|result| result.rule)
+            .collect::<Vec<_>>(),
+        [
+            Rule::MinTcb,
+            Rule::Policy,
+            Rule::Measurement,
+            Rule::Signer,
+            Rule::Freshness
+        ]
+    );
+}
+
+#[test]
+fn empty_policy_passes() {
+    let appraisal = AppraisalPolicy::default().appraise(&report(), &AppraisalContext::new());
+
+    assert!(appraisal.passed());
+    assert!(appraisal.results.is_empty());
+}
+
+#[test]
+fn failures() {
+    let mut policy = policy();
+    policy.min_tcb.as_mut().unwrap().snp = 10;
+    policy.policy.as_mut().unwrap().single_socket = Some(true);
+    policy.measurements = vec![Default::default()];
+    policy.signers = vec![SigningKey::Vlek];
+
+    let appraisal = policy.appraise(&report(), &context(Duration::from_secs(61)));
+    assert!(!appraisal.passed());
+
+    let reasons: Vec<_> = appraisal.failures().map(ToString::to_string).collect();
+    assert_eq!(
+        reasons,
+        [
+            "[fail] min_tcb: snp SVN 8 is below 10".to_string(),
+            "[fail] policy: single_socket is false, true required".to_string(),
+            format!("[fail] measurement: measurement {MEASUREMENT} is not allowed"),
+            "[fail] signer: signed by the VCEK, which is not allowed".to_string(),
+            "[fail] freshness: the nonce is 61s old, more than 60s".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn freshness() {
+    let policy = AppraisalPolicy {
+        max_age_secs: Some(60),
+        ..Default::default()
+    };
+    let report = report();
+
+    assert!(!policy.appraise(&report, &AppraisalContext::new()).passed());
+
+    let other = AppraisalContext::new().nonce(vec![0; 16], SystemTime::now());
+    let appraisal = policy.appraise(&report, &other);
+    assert_eq!(
+        appraisal.results[0].reason,
+        "the report data does not contain the nonce"
+    );
+
+    let empty = AppraisalContext::new().nonce(vec![], SystemTime::now());
+    let appraisal = policy.appraise(&report, &empty);
+    assert!(!appraisal.passed());
+    assert_eq!(appraisal.results[0].reason, "the nonce is empty");
+
+    let mut future = context(Duration::ZERO);
+    future.nonce.as_mut().unwrap().issued_at = future.now + Duration::from_secs(5);
+    assert!(!policy.appraise(&report, &future).passed());
+}
+
+#[test]
+fn json_round_trip() {
+    let policy = policy();
+
+    let mut json = vec![];
+    policy.to_writer(&mut json).unwrap();
+
+    let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(value["measurements"][0], MEASUREMENT);
+    assert_eq!(value["signers"][0], "Vcek");
+
+    assert_eq!(AppraisalPolicy::from_reader(&json[..]).unwrap(), policy);
+}
+
+#[test]
+fn json_unknown_rule() {
+    let json = r#"{ "max_age": 60 }"#;
+
+    assert!(AppraisalPolicy::from_reader(json.as_bytes()).is_err());
+}
+
+mod allow_list {
+    use super::*;
+
+    use sev::{encoding::Measurement48, error::SnapshotError, snapshot::Versioned};
+
+    use std::sync::Arc;
+
+    fn key(product: &str) -> MeasurementKey {
+        MeasurementKey::new(product, "edk2-stable202402", "6.8.0-31")
+    }
+
+    fn measurement() -> Measurement48 {
+        MEASUREMENT.parse().unwrap()
+    }
+
+    fn appraise(list: AllowList, context: AppraisalContext) -> Appraisal {
+        let policy = AppraisalPolicy::default();
+
+        policy.appraise(&report(), &context.allow_list(Arc::new(list)))
+    }
+
+    #[test]
+    fn update() {
+        let mut list = AllowList::new();
+
+        assert!(list.allow(key("Milan"), measurement()));
+        assert!(!list.allow(key("Milan"), measurement()));
+        assert!(list.allow(key("Milan"), [0; 48]));
+        assert!(list.allow(key("Genoa"), measurement()));
+
+        assert_eq!(list.get(&key("Milan")).unwrap().measurements.len(), 2);
+        assert_eq!(list.product("Genoa").count(), 1);
+
+        let replaced = list
+            .insert(AllowListEntry {
+                key: key("Milan"),
+                measurements: vec![measurement()],
+                min_tcb: None,
+            })
+            .unwrap();
+        assert_eq!(replaced.measurements.len(), 2);
+
+        assert!(list.remove(&key("Genoa")).is_some());
+        assert!(list.remove(&key("Genoa")).is_none());
+        assert_eq!(list.entries.len(), 1);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut list = AllowList::new();
+        list.allow(key("Milan"), measurement());
+
+        let mut json = vec![];
+        list.to_writer(&mut json).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["kind"], "ALST");
+        assert_eq!(value["version"], 1);
+        let entry = &value["payload"]["entries"][0];
+        assert_eq!(entry["key"]["product"], "Milan");
+        assert_eq!(entry["measurements"][0], MEASUREMENT);
+
+        assert_eq!(AllowList::from_reader(&json[..]).unwrap(), list);
+
+        let json = r#"{ "kind": "ALST", "version": 2, "payload": { "entries": [] } }"#;
+        assert!(matches!(
+            AllowList::from_reader(json.as_bytes()),
+            Err(SnapshotError::UnsupportedVersion { version: 2, .. })
+        ));
+
+        let json = r#"{ "kind": "RCPE", "version": 1, "payload": { "entries": [] } }"#;
+        assert!(matches!(
+            AllowList::from_reader(json.as_bytes()),
+            Err(SnapshotError::Kind { .. })
+        ));
+
+        // Allow-lists are stored in binary snapshots just as well.
+        let bytes = list.to_versioned_bytes().unwrap();
+        assert_eq!(AllowList::from_versioned_bytes(&bytes).unwrap(), list);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("sev-allowlist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("allowlist.json");
+
+        let mut list = AllowList::new();
+        list.allow(key("Milan"), measurement());
+        list.save(&path).unwrap();
+
+        assert_eq!(AllowList::load(&path).unwrap(), list);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn appraisal() {
+        let mut list = AllowList::new();
+        list.allow(key("Milan"), measurement());
+
+        let appraisal = appraise(list.clone(), AppraisalContext::new().product("Milan"));
+        assert!(appraisal.passed());
+        assert_eq!(
+            appraisal.results[0].reason,
+            format!(
+                "measurement {MEASUREMENT} is allowed for OVMF edk2-stable202402 and kernel 6.8.0-31 on Milan"
+            )
+        );
+
+        assert!(appraise(list.clone(), AppraisalContext::new()).passed());
+        assert!(!appraise(list, AppraisalContext::new().product("Genoa")).passed());
+    }
+
+    #[test]
+    fn tcb_scoping() {
+        let mut list = AllowList::new();
+        list.insert(AllowListEntry {
+            key: key("Milan"),
+            measurements: vec![measurement()],
+            min_tcb: Some(MinTcb {
+                snp: 9,
+                ..Default::default()
+            }),
+        });
+
+        let appraisal = appraise(list, AppraisalContext::new());
+        assert!(!appraisal.passed());
+        assert!(appraisal.results[0]
+            .reason
+            .ends_with("only with a newer TCB"));
+    }
+}
+
+mod drift {
+    use super::*;
+
+    use sev::firmware::host::TcbVersion;
+
+    fn drifted(
+        current: TcbVersion,
+        reported: TcbVersion,
+        committed: TcbVersion,
+        launch: TcbVersion,
+    ) -> TcbAnalysis {
+        let mut report = report();
+        report.current_tcb = current;
+        report.reported_tcb = reported;
+        report.committed_tcb = committed;
+        report.launch_tcb = launch;
+
+        TcbAnalysis::of(&report)
+    }
+
+    #[test]
+    fn up_to_date() {
+        let tcb = TcbVersion::new(3, 0, 8, 115);
+        let analysis = drifted(tcb, tcb, tcb, tcb);
+
+        assert!(analysis.is_up_to_date());
+        assert!(analysis.is_consistent());
+        assert_eq!(analysis.tcb(TcbSource::Launch), &tcb);
+    }
+
+    #[test]
+    fn updated() {
+        let old = TcbVersion::new(3, 0, 8, 115);
+        let new = TcbVersion::new(4, 0, 9, 115);
+        let analysis = drifted(new, old, old, old);
+
+        assert!(!analysis.is_up_to_date());
+        assert!(analysis.is_consistent());
+        assert_eq!(
+            analysis.findings,
+            [
+                TcbFinding {
+                    drift: TcbDrift::NewerThanReported,
+                    older: TcbSource::Reported,
+                    newer: TcbSource::Current,
+                    components: vec![TcbComponent::Bootloader, TcbComponent::Snp],
+                },
+                TcbFinding {
+                    drift: TcbDrift::NewerThanCommitted,
+                    older: TcbSource::Committed,
+                    newer: TcbSource::Current,
+                    components: vec![TcbComponent::Bootloader, TcbComponent::Snp],
+                },
+                TcbFinding {
+                    drift: TcbDrift::LaunchedBeforeUpdate,
+                    older: TcbSource::Launch,
+                    newer: TcbSource::Current,
+                    components: vec![TcbComponent::Bootloader, TcbComponent::Snp],
+                },
+            ]
+        );
+        assert_eq!(
+            analysis.findings[0].to_string(),
+            "running newer-than-reported firmware (bootloader, snp)"
+        );
+    }
+
+    #[test]
+    fn reported_below_committed() {
+        let old = TcbVersion::new(3, 0, 8, 115);
+        let new = TcbVersion::new(3, 0, 8, 213);
+        let analysis = drifted(new, old, new, new);
+
+        assert_eq!(
+            analysis
+                .findings
+                .iter()
+                .map(|finding| finding.drift)
+                .collect::<Vec<_>>(),
+            [
+                TcbDrift::NewerThanReported,
+                TcbDrift::ReportedBelowCommitted
+            ]
+        );
+        assert_eq!(analysis.findings[1].components, [TcbComponent::Microcode]);
+    }
+
+    #[test]
+    fn inconsistent() {
+        let current = TcbVersion::new(3, 0, 8, 115);
+        let launch = TcbVersion::new(2, 0, 9, 115);
+        let analysis = drifted(current, current, current, launch);
+
+        assert!(!analysis.is_consistent());
+        assert_eq!(
+            analysis.findings,
+            [TcbFinding {
+                drift: TcbDrift::Inconsistent,
+                older: TcbSource::Launch,
+                newer: TcbSource::Current,
+                components: vec![TcbComponent::Snp],
+            }]
+        );
+        assert_eq!(
+            analysis.findings[0].to_string(),
+            "the launch TCB is newer than the current TCB (snp)"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let old = TcbVersion::new(3, 0, 8, 115);
+        let new = TcbVersion::new(3, 0, 9, 115);
+        let analysis = drifted(new, old, new, new);
+
+        let json = serde_json::to_value(&analysis.findings).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "drift": "newer_than_reported",
+                "older": "reported",
+                "newer": "current",
+                "components": ["snp"],
+            }, {
+                "drift": "reported_below_committed",
+                "older": "reported",
+                "newer": "committed",
+                "components": ["snp"],
+            }])
+        );
+    }
+}
+
+mod freshness {
+    use super::*;
+
+    use sev::error::FreshnessError;
+
+    fn manager() -> NonceManager {
+        NonceManager::new(Duration::from_secs(60)).length(16)
+    }
+
+    #[test]
+    fn validate_once() {
+        let nonces = manager();
+        let issued_at = SystemTime::now();
+
+        let nonce = nonces
+            .register(hex::decode(NONCE).unwrap(), issued_at)
+            .unwrap();
+        assert_eq!(nonces.outstanding(), 1);
+
+        let report = report();
+        assert_eq!(
+            nonces.validate_at(report.report_data, issued_at).unwrap(),
+            nonce
+        );
+        assert_eq!(nonces.outstanding(), 0);
+
+        assert!(matches!(
+            nonces.validate_at(report.report_data, issued_at),
+            Err(FreshnessError::Replayed)
+        ));
+        assert!(matches!(
+            nonces.register(nonce.value, issued_at),
+            Err(FreshnessError::Duplicate)
+        ));
+    }
+
+    #[test]
+    fn expired() {
+        let nonces = manager();
+        let issued_at = SystemTime::now();
+        nonces
+            .register(hex::decode(NONCE).unwrap(), issued_at)
+            .unwrap();
+
+        assert!(matches!(
+            nonces.validate_at(report().report_data, issued_at + Duration::from_secs(61)),
+            Err(FreshnessError::Expired)
+        ));
+    }
+
+    #[test]
+    fn sliding_window() {
+        let nonces = manager();
+        let issued_at = SystemTime::now();
+        nonces
+            .register(hex::decode(NONCE).unwrap(), issued_at)
+            .unwrap();
+
+        // Issuing a nonce after the window forgets the first one.
+        nonces
+            .register(vec![1; 16], issued_at + Duration::from_secs(121))
+            .unwrap();
+        assert_eq!(nonces.outstanding(), 1);
+
+        assert!(matches!(
+            nonces.validate_at(report().report_data, issued_at + Duration::from_secs(121)),
+            Err(FreshnessError::Unknown)
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        let nonces = manager();
+
+        assert!(matches!(
+            nonces.validate(report().report_data),
+            Err(FreshnessError::Unknown)
+        ));
+        assert!(matches!(
+            nonces.validate([0; 8]),
+            Err(FreshnessError::InvalidLength {
+                expected: 16,
+                actual: 8
+            })
+        ));
+        assert!(matches!(
+            nonces.register(vec![0; 32], SystemTime::now()),
+            Err(FreshnessError::InvalidLength {
+                expected: 16,
+                actual: 32
+            })
+        ));
+    }
+
+    #[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+    #[test]
+    fn issue() {
+        let nonces = NonceManager::new(Duration::from_secs(60));
+
+        let first = nonces.issue().unwrap();
+        let second = nonces.issue().unwrap();
+        assert_eq!(first.value.len(), DEFAULT_NONCE_LENGTH);
+        assert_ne!(first.value, second.value);
+        assert_eq!(nonces.outstanding(), 2);
+
+        let mut report_data = [0u8; 64];
+        report_data[..32].copy_from_slice(&second.value);
+        assert_eq!(nonces.validate(report_data).unwrap(), second);
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+mod binding {
+    use super::*;
+
+    use sev::error::BindingError;
+
+    /// The digest of the exporter binding of `[0x11; 32]`, as specified.
+    const EXPORTER_DIGEST: &str =
+        "e447d64e64483acb8fd24891130dd8f9509951f7458d65aa7d425272884651cca9141602a782881a425ccdeb1c96280d";
+
+    fn exporter() -> TlsBinding {
+        TlsBinding::exporter([0x11; TLS_EXPORTER_LENGTH])
+    }
+
+    #[test]
+    fn report_data() {
+        let nonce = hex::decode(NONCE).unwrap();
+        let digest = hex::decode(EXPORTER_DIGEST).unwrap();
+
+        let report_data = exporter().report_data(&nonce).unwrap();
+        assert_eq!(report_data.as_bytes()[..16], nonce[..]);
+        assert_eq!(report_data.as_bytes()[16..], digest[..48]);
+
+        let report_data = exporter().report_data(&[]).unwrap();
+        assert_eq!(report_data.as_bytes()[..48], digest[..]);
+
+        // The kind of binding is part of the digest.
+        let certificate = TlsBinding::certificate([0x11; TLS_EXPORTER_LENGTH]);
+        assert_ne!(certificate.report_data(&[]).unwrap(), report_data);
+    }
+
+    #[test]
+    fn check() {
+        let nonce = hex::decode(NONCE).unwrap();
+
+        let mut report = report();
+        report.report_data = exporter().report_data(&nonce).unwrap();
+        exporter().check(&report, &nonce).unwrap();
+
+        // The nonce is checked as any other.
+        let nonces = NonceManager::new(Duration::from_secs(60)).length(16);
+        nonces.register(nonce.clone(), SystemTime::now()).unwrap();
+        nonces.validate(report.report_data).unwrap();
+
+        assert_eq!(
+            TlsBinding::exporter([0x22; TLS_EXPORTER_LENGTH]).check(&report, &nonce),
+            Err(BindingError::Mismatch)
+        );
+        assert_eq!(
+            exporter().check(&report, &[0; 16]),
+            Err(BindingError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn nonce_too_long() {
+        assert_eq!(
+            exporter().report_data(&[0; 33]),
+            Err(BindingError::NonceTooLong {
+                max: MAX_BOUND_NONCE_LENGTH,
+                actual: 33
+            })
+        );
+        assert!(exporter().report_data(&[0; 32]).is_ok());
+    }
+}
+
+#[cfg(any(feature = "openssl", feature = "crypto_nossl"))]
+mod cache {
+    use super::*;
+
+    use sev::certs::snp::{builtin::milan, ca, Certificate, Chain};
+
+    const TEST_MILAN_VCEK_DER: &[u8] = include_bytes!("certs_data/vcek_milan.der");
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn chain() -> Chain {
+        Chain {
+            ca: ca::Chain {
+                ark: milan::ark().unwrap(),
+                ask: milan::ask().unwrap(),
+            },
+            vek: Certificate::from_der(TEST_MILAN_VCEK_DER).unwrap(),
+        }
+    }
+
+    #[test]
+    fn hit() {
+        let cache = VerificationCache::new(TTL);
+        let (chain, report) = (chain(), report());
+        let now = SystemTime::now();
+
+        cache.verify_at(&chain, &report, now).unwrap();
+        cache.verify_at(&chain, &report, now + TTL).unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn expired() {
+        let cache = VerificationCache::new(TTL);
+        let (chain, report) = (chain(), report());
+        let now = SystemTime::now();
+
+        cache.verify_at(&chain, &report, now).unwrap();
+        cache
+            .verify_at(&chain, &report, now + TTL + Duration::from_secs(1))
+            .unwrap();
+
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 2 });
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn failure_not_cached() {
+        let cache = VerificationCache::new(TTL).capacity(1);
+        let chain = chain();
+
+        cache.verify(&chain, &report()).unwrap();
+
+        let mut tampered = report();
+        tampered.report_data.0[0] ^= 1;
+        for _ in 0..2 {
+            assert!(cache.verify(&chain, &tampered).is_err());
+        }
+
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 3 });
+        assert_eq!(cache.len(), 1);
+    }
+}
+
+#[cfg(all(feature = "simulation", feature = "openssl"))]
+mod release {
+    use super::*;
+
+    use sev::{
+        certs::snp::Chain, encoding::ReportData, error::ReleaseError,
+        firmware::guest::AttestationReport,
+    };
+
+    use openssl::{
+        asn1::Asn1Time,
+        ec::{EcGroup, EcKey},
+        hash::MessageDigest,
+        nid::Nid,
+        pkey::{PKey, Private},
+        x509::{X509Builder, X509Name, X509NameBuilder},
+    };
+
+    const SECRET: &[u8] = b"disk encryption key";
+
+    fn key(curve: Nid) -> EcKey<Private> {
+        EcKey::generate(&EcGroup::from_curve_name(curve).unwrap()).unwrap()
+    }
+
+    fn name(cn: &str) -> X509Name {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", cn).unwrap();
+        name.build()
+    }
+
+    /// A chain of certificates for `keys`, each signed by the one before.
+    fn chain(keys: &[EcKey<Private>; 3]) -> Chain {
+        let names = ["ARK", "ASK", "VCEK"];
+        let mut certs = vec![];
+
+        for (i, key) in keys.iter().enumerate() {
+            let issuer = i.saturating_sub(1);
+
+            let mut builder = X509Builder::new().unwrap();
+            builder.set_version(2).unwrap();
+            builder.set_subject_name(&name(names[i])).unwrap();
+            builder.set_issuer_name(&name(names[issuer])).unwrap();
+            builder
+                .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+                .unwrap();
+            builder
+                .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+                .unwrap();
+            builder
+                .set_pubkey(&PKey::from_ec_key(key.clone()).unwrap())
+                .unwrap();
+            builder
+                .sign(
+                    &PKey::from_ec_key(keys[issuer].clone()).unwrap(),
+                    MessageDigest::sha384(),
+                )
+                .unwrap();
+
+            certs.push(builder.build().to_der().unwrap());
+        }
+
+        Chain::from_der(&certs[0], &certs[1], &certs[2]).unwrap()
+    }
+
+    /// A chain, and a report signed by its VCEK with `report_data`.
+    fn evidence(report_data: ReportData) -> (Chain, AttestationReport) {
+        let keys = [
+            key(Nid::SECP384R1),
+            key(Nid::SECP384R1),
+            key(Nid::SECP384R1),
+        ];
+
+        let mut report = report();
+        report.report_data = report_data;
+        report.sign_with(&keys[2]).unwrap();
+
+        (chain(&keys), report)
+    }
+
+    fn round_trip(key: &ReleaseKey) -> SealedKey {
+        let nonce = hex::decode(NONCE).unwrap();
+        let (chain, report) = evidence(key.report_data(&nonce).unwrap());
+
+        let sealed = KeyRelease::new(policy())
+            .release(
+                &chain,
+                &report,
+                &context(Duration::from_secs(1)),
+                &key.public_key_der().unwrap(),
+                SECRET,
+            )
+            .unwrap();
+
+        assert_eq!(*key.unseal(&sealed).unwrap(), SECRET);
+        sealed
+    }
+
+    #[test]
+    fn ec() {
+        let sealed = round_trip(&ReleaseKey::generate_ec().unwrap());
+        assert!(matches!(sealed, SealedKey::Ecdh { .. }));
+    }
+
+    #[test]
+    fn rsa() {
+        let sealed = round_trip(&ReleaseKey::generate_rsa().unwrap());
+        assert!(matches!(sealed, SealedKey::Rsa { .. }));
+    }
+
+    #[test]
+    fn refused() {
+        let key = ReleaseKey::generate_ec().unwrap();
+        let public_key = key.public_key_der().unwrap();
+        let nonce = hex::decode(NONCE).unwrap();
+        let release = KeyRelease::new(policy());
+
+        // The report binds another key.
+        let other = ReleaseKey::generate_ec().unwrap();
+        let (chain, report) = evidence(other.report_data(&nonce).unwrap());
+        assert!(matches!(
+            release.release(
+                &chain,
+                &report,
+                &context(Duration::from_secs(1)),
+                &public_key,
+                SECRET
+            ),
+            Err(ReleaseError::Binding(_))
+        ));
+
+        // The report was altered after signing.
+        let (chain, mut report) = evidence(key.report_data(&nonce).unwrap());
+        report.guest_svn ^= 1;
+        assert!(matches!(
+            release.release(
+                &chain,
+                &report,
+                &context(Duration::from_secs(1)),
+                &public_key,
+                SECRET
+            ),
+            Err(ReleaseError::Verification(_))
+        ));
+
+        // The report does not satisfy the policy.
+        let (chain, report) = evidence(key.report_data(&nonce).unwrap());
+        let strict = KeyRelease::new(AppraisalPolicy {
+            measurements: vec![Default::default()],
+            ..policy()
+        });
+        match strict.release(
+            &chain,
+            &report,
+            &context(Duration::from_secs(1)),
+            &public_key,
+            SECRET,
+        ) {
+            Err(ReleaseError::Appraisal(appraisal)) => {
+                assert_eq!(appraisal.failures().count(), 1)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsupported() {
+        let p256 = PKey::from_ec_key(key(Nid::X9_62_PRIME256V1)).unwrap();
+        assert!(matches!(
+            ReleaseKey::from_pkey(p256),
+            Err(ReleaseError::UnsupportedKey)
+        ));
+
+        let sealed = round_trip(&ReleaseKey::generate_rsa().unwrap());
+        assert!(matches!(
+            ReleaseKey::generate_ec().unwrap().unseal(&sealed),
+            Err(ReleaseError::UnsupportedKey)
+        ));
+
+        // Tampered secrets do not unseal.
+        let key = ReleaseKey::generate_ec().unwrap();
+        let mut sealed = round_trip(&key);
+        if let SealedKey::Ecdh { tag, .. } = &mut sealed {
+            tag[0] ^= 1;
+        }
+        assert!(matches!(key.unseal(&sealed), Err(ReleaseError::Crypto(_))));
+    }
+}
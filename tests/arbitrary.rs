@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: Apache-2.0
+
+#![cfg(feature = "proptest")]
+
+use sev::arbitrary::strategy;
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+
+#[cfg(feature = "snp")]
+mod snp {
+    use super::*;
+
+    use sev::{
+        firmware::{
+            guest::{AttestationReport, GuestPolicy},
+            host::{CertTableEntry, CertType, TcbVersion},
+        },
+        ParseOptions,
+    };
+
+    use std::convert::TryFrom;
+
+    /// Lay out `entries` as the cert table the AMD SP returns.
+    fn cert_table(entries: &[CertTableEntry]) -> Vec<u8> {
+        let mut offset = (entries.len() + 1) * 24;
+        let mut table = vec![];
+        let mut data = vec![];
+
+        for entry in entries {
+            let guid = uuid::Uuid::parse_str(&entry.guid_string()).unwrap();
+            table.extend_from_slice(guid.as_bytes());
+            table.extend_from_slice(&(offset as u32).to_le_bytes());
+            table.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+
+            offset += entry.data.len();
+            data.extend_from_slice(&entry.data);
+        }
+
+        table.extend_from_slice(&[0; 24]);
+        table.extend_from_slice(&data);
+        table
+    }
+
+    proptest! {
+        #[test]
+        fn report_round_trip(report in strategy::attestation_report()) {
+            let bytes = report.to_bytes();
+            prop_assert_eq!(AttestationReport::from_bytes(&bytes).to_bytes(), bytes);
+
+            let encoded = bincode::serialize(&report).unwrap();
+            prop_assert_eq!(&encoded[..], &bytes[..]);
+
+            let json = serde_json::to_string(&report).unwrap();
+            let decoded: AttestationReport = serde_json::from_str(&json).unwrap();
+            prop_assert_eq!(decoded.to_bytes(), bytes);
+        }
+
+        #[test]
+        fn report_lenient_parse(report in strategy::attestation_report()) {
+            let bytes = report.to_bytes();
+            let parsed = AttestationReport::from_bytes_with(&bytes, ParseOptions::lenient()).unwrap();
+            prop_assert_eq!(parsed.to_bytes(), bytes);
+        }
+
+        #[test]
+        fn tcb_round_trip(tcb in strategy::tcb_version()) {
+            prop_assert_eq!(TcbVersion::from(u64::from(tcb)), tcb);
+        }
+
+        #[test]
+        fn policy_round_trip(policy in strategy::guest_policy()) {
+            prop_assert_eq!(GuestPolicy::from(u64::from(policy)), policy);
+        }
+
+        #[test]
+        fn cert_table_round_trip(entries in prop::collection::vec(strategy::cert_table_entry(64), 0..6)) {
+            let table = CertTableEntry::parse_cert_table(&cert_table(&entries), ParseOptions::strict()).unwrap();
+            prop_assert_eq!(table, entries);
+        }
+
+        #[test]
+        fn cert_type_guid(cert_type in strategy::cert_type()) {
+            let guid = uuid::Uuid::parse_str(&cert_type.to_string()).unwrap();
+            prop_assert_eq!(CertType::try_from(&guid).unwrap(), cert_type);
+        }
+    }
+
+    #[test]
+    fn unstructured() {
+        let data = [0xa5; 4096];
+        let mut u = Unstructured::new(&data);
+
+        let report = AttestationReport::arbitrary(&mut u).unwrap();
+        assert_eq!(report.version, 0xa5a5_a5a5);
+        assert_eq!(report.to_bytes(), [0xa5; AttestationReport::SIZE]);
+
+        let entry = CertTableEntry::arbitrary(&mut u).unwrap();
+        assert_ne!(entry.cert_type, CertType::Empty);
+
+        // Exhausted data yields zeroed instances rather than errors.
+        let mut u = Unstructured::new(&[]);
+        assert_eq!(
+            AttestationReport::arbitrary(&mut u).unwrap().to_bytes(),
+            [0; AttestationReport::SIZE]
+        );
+        assert_eq!(
+            TcbVersion::arbitrary(&mut u).unwrap(),
+            TcbVersion::default()
+        );
+    }
+}
+
+#[cfg(feature = "sev")]
+mod sev_certs {
+    use super::*;
+
+    use ::sev::certs::sev::{ca, sev};
+    use codicon::{Decoder, Encoder};
+
+    proptest! {
+        #[test]
+        fn sev_round_trip(cert in strategy::sev_certificate()) {
+            let mut bytes = vec![];
+            cert.encode(&mut bytes, ()).unwrap();
+
+            prop_assert_eq!(bytes.len(), std::mem::size_of::<sev::Certificate>());
+            prop_assert_eq!(sev::Certificate::decode(&bytes[..], ()).unwrap(), cert);
+        }
+
+        #[test]
+        fn ca_round_trip(cert in strategy::ca_certificate()) {
+            let mut bytes = vec![];
+            cert.encode(&mut bytes, ()).unwrap();
+
+            prop_assert_eq!(ca::Certificate::decode(&bytes[..], ()).unwrap(), cert);
+        }
+    }
+
+    #[test]
+    fn unstructured() {
+        let data = [0x5a; 8192];
+        let mut u = Unstructured::new(&data);
+
+        let mut bytes = vec![];
+        let cert = sev::Certificate::arbitrary(&mut u).unwrap();
+        cert.encode(&mut bytes, ()).unwrap();
+        assert_eq!(bytes[..4], 1u32.to_le_bytes());
+        assert_eq!(bytes[4..8], [0x5a; 4]);
+
+        let mut bytes = vec![];
+        let cert = ca::Certificate::arbitrary(&mut u).unwrap();
+        cert.encode(&mut bytes, ()).unwrap();
+        assert_eq!(bytes[..4], 1u32.to_le_bytes());
+    }
+}
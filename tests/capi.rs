@@ -0,0 +1,168 @@
+// SPDX-License-Identifier: Apache-2.0
+
+#![cfg(all(
+    feature = "capi",
+    feature = "snp",
+    any(feature = "openssl", feature = "crypto_nossl")
+))]
+
+use sev::{capi::*, certs::snp::builtin::milan};
+
+use std::ptr::{null, null_mut};
+
+const TEST_MILAN_VCEK_DER: &[u8] = include_bytes!("certs_data/vcek_milan.der");
+
+const TEST_MILAN_ATTESTATION_REPORT: &[u8] = include_bytes!("certs_data/report_milan.hex");
+
+const HEADER: &str = include_str!("../include/sev_capi.h");
+
+const SOURCE: &str = include_str!("../src/capi.rs");
+
+fn parse(bytes: &[u8]) -> *mut SevSnpReport {
+    let mut report = null_mut();
+    assert_eq!(
+        unsafe { sev_snp_report_parse(bytes.as_ptr(), bytes.len(), &mut report) },
+        0
+    );
+
+    report
+}
+
+fn verify(report: *const SevSnpReport, vek: &[u8]) -> i32 {
+    let ark = milan::ark().unwrap().to_der().unwrap();
+    let ask = milan::ask().unwrap().to_der().unwrap();
+
+    unsafe {
+        sev_snp_report_verify(
+            report,
+            SEV_CAPI_CERT_DER,
+            ark.as_ptr(),
+            ark.len(),
+            ask.as_ptr(),
+            ask.len(),
+            vek.as_ptr(),
+            vek.len(),
+        )
+    }
+}
+
+// Test that the header declares exactly the functions the library exports
+#[test]
+fn header_matches_exports() {
+    let exported: Vec<&str> = SOURCE
+        .split("pub unsafe extern \"C\" fn ")
+        .skip(1)
+        .map(|rest| &rest[..rest.find('(').unwrap()])
+        .collect();
+
+    let declared: Vec<&str> = HEADER
+        .lines()
+        .filter_map(|line| line.split_once(" sev_"))
+        .filter(|(ret, _)| !ret.starts_with('#'))
+        .map(|(_, rest)| &rest[..rest.find('(').unwrap()])
+        .collect();
+
+    assert_eq!(
+        exported,
+        declared
+            .iter()
+            .map(|name| format!("sev_{}", name))
+            .collect::<Vec<_>>()
+    );
+
+    for constant in SOURCE
+        .lines()
+        .filter_map(|line| line.strip_prefix("pub const "))
+    {
+        let (name, value) = constant.split_once(": c_int = ").unwrap();
+        let define = format!("#define {} ", name);
+        let line = HEADER
+            .lines()
+            .find(|line| line.starts_with(&define))
+            .unwrap();
+        assert_eq!(line[define.len()..].trim(), value.trim_end_matches(';'));
+    }
+}
+
+#[test]
+fn parse_and_fields() {
+    let bytes = hex::decode(TEST_MILAN_ATTESTATION_REPORT).unwrap();
+    let report = parse(&bytes);
+
+    unsafe {
+        assert_eq!(sev_snp_report_version(report), 2);
+        assert_eq!(sev_snp_report_policy(report), 0x30000);
+        assert_eq!(sev_snp_report_vmpl(report), 0);
+
+        let mut measurement = [0u8; 48];
+        assert_eq!(
+            sev_snp_report_field(
+                report,
+                SEV_SNP_FIELD_MEASUREMENT,
+                measurement.as_mut_ptr(),
+                measurement.len()
+            ),
+            48
+        );
+        assert_eq!(measurement[..], bytes[0x90..0xc0]);
+
+        assert_eq!(
+            sev_snp_report_field(report, SEV_SNP_FIELD_CHIP_ID, measurement.as_mut_ptr(), 48),
+            SEV_CAPI_ERR_LENGTH
+        );
+        assert_eq!(
+            sev_snp_report_field(report, 10, measurement.as_mut_ptr(), 48),
+            SEV_CAPI_ERR_ARGUMENT
+        );
+
+        let mut tcb = SevSnpTcb::default();
+        assert_eq!(
+            sev_snp_report_tcb(report, SEV_SNP_TCB_REPORTED, &mut tcb),
+            0
+        );
+        assert_eq!(tcb.bootloader, bytes[0x180]);
+        assert_eq!(tcb.microcode, bytes[0x187]);
+        assert_eq!(
+            sev_snp_report_tcb(report, 4, &mut tcb),
+            SEV_CAPI_ERR_ARGUMENT
+        );
+
+        sev_snp_report_free(report);
+    }
+
+    let mut report = null_mut();
+    unsafe {
+        assert_eq!(
+            sev_snp_report_parse(bytes.as_ptr(), bytes.len() - 1, &mut report),
+            SEV_CAPI_ERR_LENGTH
+        );
+        assert_eq!(
+            sev_snp_report_parse(null(), bytes.len(), &mut report),
+            SEV_CAPI_ERR_NULL
+        );
+        assert_eq!(sev_snp_report_version(null()), 0);
+        sev_snp_report_free(null_mut());
+    }
+}
+
+#[test]
+fn verify_report() {
+    let mut bytes = hex::decode(TEST_MILAN_ATTESTATION_REPORT).unwrap();
+
+    let report = parse(&bytes);
+    assert_eq!(verify(report, TEST_MILAN_VCEK_DER), 0);
+    assert_eq!(
+        verify(report, &TEST_MILAN_VCEK_DER[..64]),
+        SEV_CAPI_ERR_PARSE
+    );
+    assert_eq!(
+        unsafe { sev_snp_report_verify(report, 2, null(), 0, null(), 0, null(), 0) },
+        SEV_CAPI_ERR_ARGUMENT
+    );
+    unsafe { sev_snp_report_free(report) };
+
+    bytes[0] ^= 0x80;
+    let report = parse(&bytes);
+    assert_eq!(verify(report, TEST_MILAN_VCEK_DER), SEV_CAPI_ERR_VERIFY);
+    unsafe { sev_snp_report_free(report) };
+}
@@ -22,6 +22,9 @@ mod sev {
     }
 }
 
+#[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
+mod common;
+
 #[cfg(all(feature = "snp", any(feature = "openssl", feature = "crypto_nossl")))]
 mod snp {
     use sev::certs::snp::{builtin::milan, ca, Certificate, Chain, Verifiable};
@@ -82,6 +85,19 @@ mod snp {
         assert_eq!((&chain, &report).verify().ok(), Some(()));
     }
 
+    #[test]
+    fn milan_report_bytes() {
+        use sev::firmware::guest::AttestationReport;
+        use std::convert::TryFrom;
+
+        let report_bytes = hex::decode(TEST_MILAN_ATTESTATION_REPORT).unwrap();
+        let report = AttestationReport::try_from(&report_bytes[..]).unwrap();
+
+        assert_eq!(report.to_bytes()[..], report_bytes[..]);
+        assert_eq!(bincode::serialize(&report).unwrap(), report_bytes);
+        assert!(AttestationReport::try_from(&report_bytes[1..]).is_err());
+    }
+
     #[test]
     fn milan_report_invalid() {
         use sev::firmware::guest::AttestationReport;
@@ -101,4 +117,462 @@ mod snp {
 
         assert_eq!((&chain, &report).verify().ok(), None);
     }
+
+    mod errors {
+        use super::*;
+
+        use crate::common::report;
+
+        use sev::{
+            error::VerificationError,
+            firmware::host::{CertTableEntry, CertType},
+        };
+
+        use std::{error::Error, io};
+
+        fn chain() -> Chain {
+            Chain {
+                ca: ca::Chain {
+                    ark: milan::ark().unwrap(),
+                    ask: milan::ask().unwrap(),
+                },
+                vek: Certificate::from_der(TEST_MILAN_VCEK_DER).unwrap(),
+            }
+        }
+
+        fn entries() -> Vec<CertTableEntry> {
+            let chain = chain();
+
+            vec![
+                CertTableEntry::new(CertType::ARK, chain.ca.ark.to_der().unwrap()),
+                CertTableEntry::new(CertType::ASK, chain.ca.ask.to_der().unwrap()),
+                CertTableEntry::new(CertType::VCEK, chain.vek.to_der().unwrap()),
+            ]
+        }
+
+        #[test]
+        fn report_signature() {
+            let mut report = report();
+            report.measurement.0[0] ^= 0xff;
+
+            let error = (&chain(), &report).verify().unwrap_err();
+            assert!(matches!(error, VerificationError::ReportSignature));
+
+            let error = io::Error::from(error);
+            assert_eq!(error.kind(), io::ErrorKind::Other);
+        }
+
+        #[test]
+        fn certificate_signature() {
+            let mut chain = chain();
+            std::mem::swap(&mut chain.ca.ark, &mut chain.ca.ask);
+
+            assert!(matches!(
+                chain.verify(),
+                Err(VerificationError::CertificateSignature)
+            ));
+        }
+
+        #[test]
+        fn missing_certificate() {
+            let mut entries = entries();
+            entries.remove(1);
+
+            assert!(matches!(
+                Chain::from_cert_table_der(entries),
+                Err(VerificationError::MissingCertificate(CertType::ASK))
+            ));
+
+            let mut entries = self::entries();
+            entries.truncate(2);
+
+            let error = Chain::from_cert_table_der(entries).err().unwrap();
+            assert!(matches!(
+                error,
+                VerificationError::MissingCertificate(CertType::VCEK)
+            ));
+            assert_eq!(
+                error.to_string(),
+                "Neither a VCEK nor a VLEK certificate was found."
+            );
+        }
+
+        #[test]
+        fn duplicate_certificate() {
+            let mut entries = entries();
+            entries.push(entries[0].clone());
+
+            assert!(matches!(
+                Chain::from_cert_table_der(entries),
+                Err(VerificationError::DuplicateCertificate(CertType::ARK))
+            ));
+        }
+
+        #[test]
+        fn parse() {
+            let mut entries = entries();
+            entries[2].data.truncate(16);
+
+            let error = Chain::from_cert_table_der(entries).err().unwrap();
+            assert!(matches!(error, VerificationError::Parse(_)));
+            assert!(error.source().is_some());
+
+            let error = io::Error::from(error);
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+
+        #[test]
+        fn valid_table() {
+            let chain = Chain::from_cert_table_der(entries()).unwrap();
+
+            (&chain, &report()).verify().unwrap();
+        }
+
+        #[test]
+        fn signing_key() {
+            use sev::firmware::guest::KeyInfo;
+
+            let mut entries = entries();
+            entries[0].data = chain().ca.ark.to_pem().unwrap();
+            entries.push(CertTableEntry::new(CertType::VLEK, vec![0; 16]));
+
+            let report = report();
+            assert_eq!(report.vek_type().unwrap(), CertType::VCEK);
+
+            let chain = Chain::from_cert_table_for(entries.clone(), &report).unwrap();
+            (&chain, &report).verify().unwrap();
+
+            let mut vlek = report;
+            vlek.key_info = KeyInfo::from(1 << 2);
+            assert_eq!(vlek.vek_type().unwrap(), CertType::VLEK);
+            entries.pop();
+            assert!(matches!(
+                Chain::from_cert_table_for(entries.clone(), &vlek),
+                Err(VerificationError::MissingCertificate(CertType::VLEK))
+            ));
+
+            let mut unsigned = report;
+            unsigned.key_info = KeyInfo::from(7 << 2);
+            assert!(matches!(
+                unsigned.vek_type(),
+                Err(VerificationError::Unsigned)
+            ));
+            assert!(matches!(
+                (&chain, &unsigned).verify(),
+                Err(VerificationError::Unsigned)
+            ));
+            assert!(matches!(
+                Chain::from_cert_table_for(entries, &unsigned),
+                Err(VerificationError::Unsigned)
+            ));
+
+            let mut reserved = report;
+            reserved.key_info = KeyInfo::from(2 << 2);
+            assert!(matches!(
+                (&chain, &reserved).verify(),
+                Err(VerificationError::ReservedSigningKey(2))
+            ));
+        }
+    }
+
+    #[cfg(feature = "simulation")]
+    mod signing {
+        #[cfg(feature = "openssl")]
+        use super::*;
+
+        use sev::firmware::guest::AttestationReport;
+
+        #[cfg(feature = "openssl")]
+        mod test_chain {
+            use openssl::{
+                asn1::Asn1Time,
+                ec::{EcGroup, EcKey},
+                hash::MessageDigest,
+                nid::Nid,
+                pkey::{PKey, Private},
+                x509::{X509Builder, X509NameBuilder, X509},
+            };
+
+            pub fn key() -> EcKey<Private> {
+                EcKey::generate(&EcGroup::from_curve_name(Nid::SECP384R1).unwrap()).unwrap()
+            }
+
+            /// A certificate for `key` named `name`, signed by `issuer` or self-signed.
+            pub fn cert(
+                name: &str,
+                key: &EcKey<Private>,
+                issuer: Option<(&X509, &EcKey<Private>)>,
+            ) -> Vec<u8> {
+                let mut subject = X509NameBuilder::new().unwrap();
+                subject.append_entry_by_text("CN", name).unwrap();
+                let subject = subject.build();
+
+                let mut builder = X509Builder::new().unwrap();
+                builder.set_version(2).unwrap();
+                builder.set_subject_name(&subject).unwrap();
+                builder
+                    .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+                    .unwrap();
+                builder
+                    .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+                    .unwrap();
+                builder
+                    .set_pubkey(&PKey::from_ec_key(key.clone()).unwrap())
+                    .unwrap();
+
+                let (issuer_name, issuer_key) = match issuer {
+                    Some((cert, key)) => (cert.subject_name(), key),
+                    None => (subject.as_ref(), key),
+                };
+                builder.set_issuer_name(issuer_name).unwrap();
+                builder
+                    .sign(
+                        &PKey::from_ec_key(issuer_key.clone()).unwrap(),
+                        MessageDigest::sha384(),
+                    )
+                    .unwrap();
+
+                builder.build().to_der().unwrap()
+            }
+        }
+
+        #[cfg(feature = "openssl")]
+        #[test]
+        fn synthetic_report() {
+            use openssl::x509::X509;
+
+            let (ark_key, ask_key, vcek_key) =
+                (test_chain::key(), test_chain::key(), test_chain::key());
+
+            let ark = test_chain::cert("ARK", &ark_key, None);
+            let ark_x509 = X509::from_der(&ark).unwrap();
+            let ask = test_chain::cert("ASK", &ask_key, Some((&ark_x509, &ark_key)));
+            let ask_x509 = X509::from_der(&ask).unwrap();
+            let vcek = test_chain::cert("VCEK", &vcek_key, Some((&ask_x509, &ask_key)));
+
+            let chain = Chain::from_der(&ark, &ask, &vcek).unwrap();
+
+            let mut report = AttestationReport::default();
+            report.version = 2;
+            report.sign_with(&vcek_key).unwrap();
+            (&chain, &report).verify().unwrap();
+
+            // The signature covers the body of the report.
+            report.report_data.0[0] ^= 0xff;
+            assert!((&chain, &report).verify().is_err());
+
+            // Only P-384 keys sign reports.
+            let p256 = openssl::ec::EcKey::generate(
+                &openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1)
+                    .unwrap(),
+            )
+            .unwrap();
+            let error = report.sign_with(&p256).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+
+        #[cfg(feature = "crypto_nossl")]
+        #[test]
+        fn synthetic_report() {
+            use p384::ecdsa::{signature::DigestVerifier, SigningKey, VerifyingKey};
+            use sha2::Digest;
+            use std::convert::TryFrom;
+
+            let key = SigningKey::from_slice(&[0x42; 48]).unwrap();
+
+            let mut report = AttestationReport::default();
+            report.version = 2;
+            report.sign_with(&key).unwrap();
+
+            let sig = p384::ecdsa::Signature::try_from(&report.signature).unwrap();
+            let digest = sha2::Sha384::new_with_prefix(&report.to_bytes()[..0x2a0]);
+            VerifyingKey::from(&key)
+                .verify_digest(digest, &sig)
+                .unwrap();
+        }
+    }
+
+    mod offline {
+        use super::*;
+
+        use crate::common::report;
+
+        use sev::{
+            certs::snp::{builtin::genoa, verify_offline, EmbeddedRoots, EvidenceBundle},
+            error::{OfflineError, VerificationError},
+            firmware::host::{CertTableEntry, CertType},
+        };
+
+        fn bundle(cert_types: &[CertType]) -> EvidenceBundle {
+            let certificates = cert_types
+                .iter()
+                .map(|cert_type| {
+                    let data = match cert_type {
+                        CertType::ARK => milan::ark().unwrap().to_der().unwrap(),
+                        CertType::ASK => milan::ask().unwrap().to_der().unwrap(),
+                        _ => TEST_MILAN_VCEK_DER.to_vec(),
+                    };
+
+                    CertTableEntry::new(cert_type.clone(), data)
+                })
+                .collect();
+
+            EvidenceBundle::from((report(), certificates))
+        }
+
+        fn missing(result: Result<Chain, OfflineError>) -> Vec<CertType> {
+            match result {
+                Err(OfflineError::Missing(cert_types)) => cert_types,
+                other => panic!("expected missing certificates, got {:?}", other.err()),
+            }
+        }
+
+        #[test]
+        fn builtin_roots() {
+            let roots = EmbeddedRoots::builtin().unwrap();
+
+            let chain = verify_offline(&bundle(&[CertType::VCEK]), &roots).unwrap();
+            assert_eq!(
+                chain.vek,
+                Certificate::from_der(TEST_MILAN_VCEK_DER).unwrap()
+            );
+
+            verify_offline(&bundle(&[CertType::ASK, CertType::VCEK]), &roots).unwrap();
+        }
+
+        #[test]
+        fn missing_artifacts() {
+            assert_eq!(
+                missing(verify_offline(&bundle(&[]), &EmbeddedRoots::new())),
+                [CertType::ARK, CertType::ASK, CertType::VCEK]
+            );
+            assert_eq!(
+                missing(verify_offline(
+                    &bundle(&[]),
+                    &EmbeddedRoots::builtin().unwrap()
+                )),
+                [CertType::VCEK]
+            );
+
+            // The Genoa ASK does not sign a Milan VCEK.
+            let genoa = EmbeddedRoots::new()
+                .ark(genoa::ark().unwrap())
+                .ask(genoa::ask().unwrap());
+            assert_eq!(
+                missing(verify_offline(&bundle(&[CertType::VCEK]), &genoa)),
+                [CertType::ASK]
+            );
+
+            // The ARK of the bundle is not trusted.
+            assert_eq!(
+                missing(verify_offline(
+                    &bundle(&[CertType::ARK, CertType::ASK, CertType::VCEK]),
+                    &genoa
+                )),
+                [CertType::ARK]
+            );
+        }
+
+        #[test]
+        fn invalid() {
+            let roots = EmbeddedRoots::builtin().unwrap();
+
+            let mut tampered = bundle(&[CertType::VCEK]);
+            tampered.report.measurement.0[0] ^= 0xff;
+            assert!(matches!(
+                verify_offline(&tampered, &roots),
+                Err(OfflineError::Verification(
+                    VerificationError::ReportSignature
+                ))
+            ));
+
+            let mut unsigned = bundle(&[CertType::VCEK]);
+            unsigned.report.key_info = 0x1c.into();
+            assert!(matches!(
+                verify_offline(&unsigned, &roots),
+                Err(OfflineError::Unsigned)
+            ));
+
+            let genoa_ask = bundle(&[CertType::VCEK]);
+            let genoa_ask = EvidenceBundle {
+                certificates: vec![
+                    CertTableEntry::new(CertType::ASK, genoa::ask().unwrap().to_der().unwrap()),
+                    genoa_ask.certificates[0].clone(),
+                ],
+                ..genoa_ask
+            };
+            assert!(matches!(
+                verify_offline(&genoa_ask, &roots),
+                Err(OfflineError::Verification(
+                    VerificationError::CertificateSignature
+                ))
+            ));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    mod guest_certs {
+        use super::*;
+
+        use sev::{
+            error::CertError,
+            firmware::host::{CertTableEntry, CertType, GuestCerts},
+            ParseOptions,
+        };
+
+        fn chain() -> Chain {
+            Chain {
+                ca: ca::Chain {
+                    ark: milan::ark().unwrap(),
+                    ask: milan::ask().unwrap(),
+                },
+                vek: Certificate::from_der(TEST_MILAN_VCEK_DER).unwrap(),
+            }
+        }
+
+        #[test]
+        fn from_chain() {
+            let certs = GuestCerts::from_chain(&chain(), CertType::VCEK).unwrap();
+            assert_eq!(certs.as_bytes().len(), certs.pages() * 0x1000);
+
+            let table =
+                CertTableEntry::parse_cert_table(certs.as_bytes(), ParseOptions::strict()).unwrap();
+            assert_eq!(table, certs.entries());
+            for cert_type in [CertType::ARK, CertType::ASK, CertType::VCEK] {
+                assert!(table.iter().any(|e| e.cert_type == cert_type));
+            }
+
+            let parsed = Chain::from_cert_table_der(table).unwrap();
+            assert_eq!(parsed.vek, chain().vek);
+            parsed.verify().unwrap();
+
+            assert!(GuestCerts::from_chain(&chain(), CertType::ARK).is_err());
+        }
+
+        #[test]
+        fn serve() {
+            let certs = GuestCerts::new(vec![
+                CertTableEntry::new(CertType::VLEK, vec![3; 0x1000]),
+                CertTableEntry::new(CertType::ARK, vec![1; 16]),
+            ])
+            .unwrap();
+            assert_eq!(certs.entries().len(), 2);
+            assert_eq!(certs.pages(), 2);
+
+            let mut pages = vec![0xff; 0x1000];
+            assert!(matches!(
+                certs.serve(&mut pages),
+                Err(CertError::TableTooLarge {
+                    len: 0x2000,
+                    max: 0x1000
+                })
+            ));
+            assert_eq!(pages, [0xff; 0x1000]);
+
+            let mut pages = vec![0xff; 0x3000];
+            certs.serve(&mut pages).unwrap();
+            assert_eq!(&pages[..0x2000], certs.as_bytes());
+            assert!(pages[0x2000..].iter().all(|b| *b == 0));
+        }
+    }
 }
@@ -0,0 +1,12 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use sev::firmware::guest::AttestationReport;
+
+use std::convert::TryFrom;
+
+const TEST_MILAN_ATTESTATION_REPORT: &[u8] = include_bytes!("../certs_data/report_milan.hex");
+
+/// The report of a Milan guest, signed by `certs_data/vcek_milan.der`.
+pub fn report() -> AttestationReport {
+    AttestationReport::try_from(&hex::decode(TEST_MILAN_ATTESTATION_REPORT).unwrap()[..]).unwrap()
+}
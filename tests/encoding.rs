@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: Apache-2.0
+
+#![cfg(feature = "std")]
+
+use sev::{
+    encoding::{to_hex, HexBytes},
+    error::EncodingError,
+};
+
+#[test]
+fn hex_round_trip() {
+    let digest = HexBytes([0xab, 0x01, 0xff, 0x00]);
+
+    assert_eq!(digest.to_string(), "ab01ff00");
+    assert_eq!("ab01ff00".parse::<HexBytes<4>>().unwrap(), digest);
+    assert_eq!("AB01FF00".parse::<HexBytes<4>>().unwrap(), digest);
+    assert_eq!(to_hex(&digest[..]), "ab01ff00");
+}
+
+#[test]
+fn hex_invalid() {
+    assert_eq!(
+        "ab01ff".parse::<HexBytes<4>>().unwrap_err(),
+        EncodingError::InvalidLength {
+            expected: 4,
+            actual: 3
+        }
+    );
+    assert_eq!(
+        "ab01fg00".parse::<HexBytes<4>>().unwrap_err(),
+        EncodingError::InvalidCharacter(5)
+    );
+    assert_eq!(
+        "ab 01ff0".parse::<HexBytes<4>>().unwrap_err(),
+        EncodingError::InvalidCharacter(2)
+    );
+}
+
+#[test]
+fn base64_round_trip() {
+    let digest = HexBytes([0xab, 0x01, 0xff, 0x00]);
+
+    assert_eq!(digest.to_base64(), "qwH/AA==");
+    assert_eq!(HexBytes::<4>::from_base64("qwH/AA==").unwrap(), digest);
+    assert_eq!(
+        HexBytes::<3>::from_base64("qwH/AA==").unwrap_err(),
+        EncodingError::InvalidLength {
+            expected: 3,
+            actual: 4
+        }
+    );
+    assert_eq!(
+        HexBytes::<4>::from_base64("qwH/AA").unwrap_err(),
+        EncodingError::InvalidBase64
+    );
+}
+
+#[test]
+fn serde_human_readable() {
+    let digest = HexBytes([0xab, 0x01, 0xff, 0x00]);
+
+    let json = serde_json::to_string(&digest).unwrap();
+    assert_eq!(json, r#""ab01ff00""#);
+    assert_eq!(serde_json::from_str::<HexBytes<4>>(&json).unwrap(), digest);
+
+    // Arrays of numbers remain readable.
+    assert_eq!(
+        serde_json::from_str::<HexBytes<4>>("[171, 1, 255, 0]").unwrap(),
+        digest
+    );
+    assert!(serde_json::from_str::<HexBytes<4>>("[171, 1, 255]").is_err());
+}
+
+#[test]
+fn serde_binary() {
+    let digest = HexBytes([0xab, 0x01, 0xff, 0x00]);
+
+    let bytes = bincode::serialize(&digest).unwrap();
+    assert_eq!(bytes, digest.0);
+    assert_eq!(bincode::deserialize::<HexBytes<4>>(&bytes).unwrap(), digest);
+}
+
+#[test]
+fn typed_fields() {
+    use sev::encoding::{ChipId, IdKeyDigest, Measurement48};
+
+    use std::convert::TryFrom;
+
+    let hex = "ab".repeat(48);
+    let measurement: Measurement48 = hex.parse().unwrap();
+
+    assert_eq!(measurement.as_bytes(), &[0xab; 48]);
+    assert_eq!(measurement.to_string(), hex);
+    assert_eq!(format!("{measurement:?}"), format!("Measurement48({hex})"));
+    assert_eq!(Measurement48::from(HexBytes([0xab; 48])), measurement);
+    assert_eq!(
+        IdKeyDigest::from(measurement.into_inner()),
+        IdKeyDigest([0xab; 48])
+    );
+
+    assert_eq!(
+        serde_json::to_string(&measurement).unwrap(),
+        format!(r#""{hex}""#)
+    );
+    assert_eq!(bincode::serialize(&measurement).unwrap(), [0xab; 48]);
+
+    assert_eq!(
+        ChipId::try_from(&[0; 48][..]).unwrap_err(),
+        EncodingError::InvalidLength {
+            expected: 64,
+            actual: 48
+        }
+    );
+    assert_eq!(ChipId::try_from(&[1; 64][..]).unwrap(), ChipId([1; 64]));
+}
+
+#[cfg(feature = "snp")]
+mod common;
+
+#[cfg(feature = "snp")]
+mod report {
+    use crate::common::report;
+
+    use sev::firmware::guest::AttestationReport;
+
+    #[test]
+    fn json() {
+        let report = report();
+
+        let json = serde_json::to_value(report).unwrap();
+        assert_eq!(json["measurement"], hex::encode(report.measurement));
+        assert_eq!(json["chip_id"], hex::encode(report.chip_id));
+        assert_eq!(json["report_data"], hex::encode(report.report_data));
+
+        let decoded: AttestationReport = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.to_bytes()[..], report.to_bytes()[..]);
+    }
+
+    #[test]
+    fn bincode_unchanged() {
+        let report = report();
+
+        assert_eq!(
+            bincode::serialize(&report).unwrap()[..],
+            report.to_bytes()[..]
+        );
+    }
+
+    #[test]
+    fn display() {
+        let report = report();
+        let display = report.to_string();
+
+        assert!(display.contains(&format!(
+            "Measurement:                  {}\n",
+            hex::encode(report.measurement)
+        )));
+        assert!(display.contains(&format!(
+            "Chip ID:                      {}\n",
+            hex::encode(report.chip_id)
+        )));
+    }
+
+    #[test]
+    fn text_v1() {
+        // The layout is frozen: never update the expected text.
+        assert_eq!(
+            report().to_text_v1(),
+            include_str!("certs_data/report_milan_v1.txt")
+        );
+    }
+
+    #[test]
+    fn parsable() {
+        let parsable = report().to_parsable();
+
+        // Keys may be added, but never renamed or removed.
+        for line in include_str!("certs_data/report_milan_parsable.txt").lines() {
+            assert!(parsable.lines().any(|l| l == line), "{} is missing", line);
+        }
+
+        let keys: Vec<&str> = parsable
+            .lines()
+            .map(|line| line.split_once('=').unwrap().0)
+            .collect();
+        let mut unique = keys.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), keys.len());
+        assert!(keys.iter().all(|key| !key.contains(char::is_whitespace)));
+    }
+}
@@ -35,3 +35,37 @@ fn get_derived_key() {
 
     fw.get_derived_key(None, derived_key).unwrap();
 }
+
+#[cfg(all(feature = "snp", target_os = "linux"))]
+#[cfg_attr(not(has_sev_guest), ignore)]
+#[test]
+fn get_report_with_key() {
+    let unique_data = [0u8; 64];
+
+    let mut fw = Firmware::open().unwrap();
+
+    let report = fw
+        .get_report_with_key(None, Some(unique_data), None, KeySelect::Vcek)
+        .unwrap();
+
+    assert_eq!(report.key_info.signing_key(), Ok(SigningKey::Vcek));
+}
+
+#[cfg(all(feature = "snp", target_os = "linux"))]
+#[cfg_attr(not(has_sev_guest), ignore)]
+#[test]
+fn get_ext_report_max_certs_len() {
+    use sev::error::{CertError, UserApiError};
+
+    let unique_data = [0u8; 64];
+
+    let mut fw = Firmware::open().unwrap().max_certs_len(0);
+
+    match fw.get_ext_report(None, Some(unique_data), None) {
+        Ok((_, certs)) => assert!(certs.is_none()),
+        Err(e) => assert!(matches!(
+            e,
+            UserApiError::ApiError(CertError::TableTooLarge { max: 0, .. })
+        )),
+    }
+}
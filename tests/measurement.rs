@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: Apache-2.0
 
-#![cfg(feature = "openssl")]
+#![cfg(any(feature = "openssl", feature = "crypto_nossl"))]
 
 #[cfg(all(target_os = "linux", feature = "snp"))]
 mod snp_tests {
@@ -389,6 +389,110 @@ mod snp_tests {
             snp_calc_launch_digest(arguments).unwrap_err().to_string()
         );
     }
+
+    // Test that the launch digest simulator replays the firmware's page measurement
+    #[test]
+    fn test_snp_launch_digest_replay() {
+        use sev::measurement::digest::sha384;
+        use sev::measurement::{
+            gctx::{LaunchDigest, PageUpdate},
+            vmsa::VMSA,
+        };
+
+        let data = [0xf4u8; 4096];
+
+        let mut page_info: Vec<u8> = vec![0; 48];
+        page_info.extend_from_slice(&sha384(&data));
+        page_info.extend_from_slice(&[0x70, 0x00, 0x01, 0, 0, 0, 0, 0]);
+        page_info.extend_from_slice(&0x1000u64.to_le_bytes());
+
+        let mut digest = LaunchDigest::new();
+        digest
+            .update(&PageUpdate::Normal {
+                gpa: 0x1000,
+                data: &data,
+            })
+            .unwrap();
+        assert_eq!(digest.digest(), sha384(&page_info));
+
+        let vmsa = VMSA::new(
+            0xffff_fff0,
+            CpuType::EpycV4,
+            VMMType::QEMU,
+            Some(2),
+            GuestFeatures(0x1),
+        );
+        let pages = vmsa.pages(2).unwrap();
+
+        let mut expected = LaunchDigest::from_seed(digest.digest());
+        expected
+            .replay(&[
+                PageUpdate::Zero {
+                    gpa: 0x2000,
+                    len: 4096,
+                },
+                PageUpdate::Zero {
+                    gpa: 0x3000,
+                    len: 4096,
+                },
+                PageUpdate::Cpuid { gpa: 0x4000 },
+                PageUpdate::Vmsa { page: &pages[0] },
+                PageUpdate::Vmsa { page: &pages[1] },
+            ])
+            .unwrap();
+
+        digest
+            .replay(&[
+                PageUpdate::Zero {
+                    gpa: 0x2000,
+                    len: 8192,
+                },
+                PageUpdate::Cpuid { gpa: 0x4000 },
+            ])
+            .unwrap();
+        digest.vmsas(&vmsa, 2).unwrap();
+
+        assert_eq!(digest.digest(), expected.digest());
+        assert!(digest
+            .update(&PageUpdate::Unmeasured {
+                gpa: 0x5000,
+                len: 100,
+            })
+            .is_err());
+    }
+
+    // Test that cached VMSA digests measure the same as every vCPU's page
+    #[test]
+    fn test_snp_vmsa_digests() {
+        use sev::measurement::{
+            gctx::{LaunchDigest, PageUpdate, VmsaDigests},
+            vmsa::VMSA,
+        };
+
+        let vmsa = VMSA::new(
+            0x0080_0000,
+            CpuType::EpycV4,
+            VMMType::QEMU,
+            Some(64),
+            GuestFeatures(0x1),
+        );
+        let digests = VmsaDigests::new(&vmsa).unwrap();
+
+        for vcpus in [1, 2, 64] {
+            let mut expected = LaunchDigest::new();
+            for page in vmsa.pages(vcpus).unwrap().iter() {
+                expected.update(&PageUpdate::Vmsa { page }).unwrap();
+            }
+
+            let mut digest = LaunchDigest::new();
+            digest.vmsa_digests(&digests, vcpus).unwrap();
+
+            assert_eq!(digest.digest(), expected.digest());
+        }
+
+        assert_eq!(digests.get(1), digests.get(63));
+        assert_ne!(digests.get(0), digests.get(1));
+    }
 }
 
 #[cfg(all(target_os = "linux", feature = "sev"))]
@@ -536,3 +640,781 @@ mod sev_tests {
         );
     }
 }
+
+#[cfg(all(target_os = "linux", feature = "sev", feature = "snp"))]
+mod calc_tests {
+    use sev::measurement::{
+        calc::*,
+        vcpu_types::CpuType,
+        vmsa::{GuestFeatures, SevMode},
+    };
+
+    fn arguments(mode: SevMode, append: Option<&str>) -> MeasurementArgs<'_> {
+        MeasurementArgs {
+            mode,
+            vcpus: 1,
+            vcpu_type: CpuType::EpycV4,
+            ovmf_file: "./tests/measurement/ovmf_AmdSev_suffix.bin".into(),
+            guest_features: GuestFeatures(0x1),
+            kernel_file: Some("/dev/null".into()),
+            initrd_file: Some("/dev/null".into()),
+            append,
+            ovmf_hash_str: None,
+            vmm_type: None,
+        }
+    }
+
+    // Test that each mode matches its own launch digest calculation
+    #[test]
+    fn test_calc_launch_digest() {
+        let ld = calc_launch_digest(arguments(SevMode::Sev, Some("console=ttyS0 loglevel=7")));
+        assert_eq!(
+            hex::encode(ld.unwrap()),
+            "f0d92a1fda00249e008820bd40def6abbed2ee65fea8a8bc47e532863ca0cc6a"
+        );
+
+        let ld = calc_launch_digest(arguments(SevMode::SevEs, None));
+        assert_eq!(
+            hex::encode(ld.unwrap()),
+            "c9c378be09902e3d5927a93b73ed383620eea5387e1d16416807cfc949b7f834"
+        );
+
+        let ld = calc_launch_digest(arguments(SevMode::SevSnp, Some("console=ttyS0 loglevel=7")));
+        assert_eq!(
+            hex::encode(ld.unwrap()),
+            "72b3f3c1ed0df9e5279eb2317a9861be3b878537e8513b318b49c1e184f6228e3ff367d133a8688f430e412ba66f558f"
+        );
+    }
+
+    // Test that mismatches are traced back to the input which differs
+    #[test]
+    fn test_verify_snp() {
+        use sev::{firmware::guest::AttestationReport, measurement::verify_snp};
+        use std::convert::TryInto;
+
+        let report = |args: MeasurementArgs| {
+            let mut report = AttestationReport::default();
+            report.measurement = calc_launch_digest(args).unwrap()[..].try_into().unwrap();
+            report
+        };
+        let expected = || arguments(SevMode::SevSnp, Some("console=ttyS0 loglevel=7"));
+
+        let verification = verify_snp(&report(expected()), expected()).unwrap();
+        assert!(verification.is_match());
+
+        let launched = MeasurementArgs {
+            vcpus: 4,
+            ..expected()
+        };
+        let verification = verify_snp(&report(launched), expected()).unwrap();
+        assert_eq!(
+            verification,
+            Verification::Mismatch {
+                expected: hex::decode("72b3f3c1ed0df9e5279eb2317a9861be3b878537e8513b318b49c1e184f6228e3ff367d133a8688f430e412ba66f558f").unwrap().try_into().unwrap(),
+                cause: MismatchCause::VcpuCount(4),
+            }
+        );
+
+        let launched = MeasurementArgs {
+            guest_features: GuestFeatures(0x21),
+            ..expected()
+        };
+        let verification = verify_snp(&report(launched), expected()).unwrap();
+        assert!(matches!(
+            verification,
+            Verification::Mismatch {
+                cause: MismatchCause::GuestFeatures(GuestFeatures(0x21)),
+                ..
+            }
+        ));
+
+        let launched = MeasurementArgs {
+            kernel_file: None,
+            initrd_file: None,
+            append: None,
+            ..expected()
+        };
+        let verification = verify_snp(&report(launched), expected()).unwrap();
+        assert!(matches!(
+            verification,
+            Verification::Mismatch {
+                cause: MismatchCause::KernelHashes,
+                ..
+            }
+        ));
+
+        assert!(verify_snp(&AttestationReport::default(), arguments(SevMode::Sev, None)).is_err());
+    }
+}
+
+#[cfg(any(feature = "sev", feature = "snp"))]
+mod ovmf_tests {
+    use sev::measurement::ovmf::*;
+
+    // Test that the typed table entries agree with the OVMF accessors
+    #[test]
+    fn test_ovmf_table_entries() {
+        let ovmf = OVMF::new("./tests/measurement/ovmf_AmdSev_suffix.bin".into()).unwrap();
+        let entries = ovmf.table_entries().unwrap();
+
+        assert_eq!(entries.len(), 5);
+        assert_eq!(
+            entries[0],
+            OvmfTableEntry::SevEsResetBlock {
+                eip: ovmf.sev_es_reset_eip().unwrap()
+            }
+        );
+        assert_eq!(
+            entries[1],
+            OvmfTableEntry::SevSecretBlock {
+                gpa: 0x80f000,
+                size: 0xc00
+            }
+        );
+        assert_eq!(
+            entries[2],
+            OvmfTableEntry::SevHashTable {
+                gpa: ovmf.sev_hashes_table_gpa().unwrap() as u32,
+                size: 0x400
+            }
+        );
+        assert!(matches!(entries[3], OvmfTableEntry::SevMetadata { .. }));
+        assert!(matches!(entries[4], OvmfTableEntry::Unknown { .. }));
+
+        assert!(ovmf.has_metadata_section(SectionType::SnpKernelHashes));
+        assert_eq!(ovmf.metadata_items().len(), 6);
+    }
+
+    // Test that truncated images are rejected rather than panicking
+    #[test]
+    fn test_ovmf_truncated() {
+        let data = std::fs::read("./tests/measurement/ovmf_AmdSev_suffix.bin").unwrap();
+
+        assert!(OVMF::from_bytes(data[..16].to_vec()).is_err());
+        assert!(OVMF::from_bytes(data[data.len() - 0x100..].to_vec()).is_err());
+    }
+
+    // Test that the AP reset vector is split the way the VMM loads it
+    #[test]
+    fn test_ap_reset_vector() {
+        let ovmf = OVMF::new("./tests/measurement/ovmf_AmdSev_suffix.bin".into()).unwrap();
+        let vector = ovmf.ap_reset_vector().unwrap();
+
+        assert_eq!(vector.eip(), ovmf.sev_es_reset_eip().unwrap());
+        assert_eq!(vector.cs_base() + vector.rip(), vector.eip() as u64);
+
+        let vector = ApResetVector::new(0x80b004);
+        assert_eq!(vector.cs_selector(), 0xf000);
+        assert_eq!(vector.cs_base(), 0x800000);
+        assert_eq!(vector.rip(), 0xb004);
+    }
+}
+
+#[cfg(any(feature = "sev", feature = "snp"))]
+mod vmsa_tests {
+    use sev::{
+        error::MeasurementError,
+        measurement::{
+            vcpu_types::CpuType,
+            vmsa::{GuestFeatures, VMMType, VMSA, VMSA_PAGE_SIZE},
+        },
+    };
+
+    // Test that the builder reproduces the VMSA presets
+    #[test]
+    fn test_vmsa_builder_presets() {
+        for vmm_type in [VMMType::QEMU, VMMType::EC2, VMMType::KRUN] {
+            let vmsa = VMSA::new(
+                0x80b004,
+                CpuType::EpycMilan,
+                vmm_type,
+                Some(2),
+                GuestFeatures(0x1),
+            );
+            let built = VMSA::builder(vmm_type)
+                .vcpu_type(CpuType::EpycMilan)
+                .guest_features(GuestFeatures(0x1))
+                .ap_eip(0x80b004)
+                .vcpus(2)
+                .build()
+                .unwrap();
+
+            let pages = built.pages(2).unwrap();
+            assert_eq!(pages, vmsa.pages(2).unwrap());
+            assert!(pages.iter().all(|page| page.len() == VMSA_PAGE_SIZE));
+        }
+
+        let milan = VMSA::builder(VMMType::QEMU).vcpu_family(25, 1, 1).build();
+        assert_eq!(
+            milan.unwrap().bsp_page().unwrap(),
+            VMSA::builder(VMMType::QEMU)
+                .vcpu_type(CpuType::EpycMilan)
+                .build()
+                .unwrap()
+                .bsp_page()
+                .unwrap()
+        );
+        assert_eq!(
+            VMSA::builder(VMMType::QEMU)
+                .build()
+                .unwrap()
+                .ap_page()
+                .unwrap(),
+            None
+        );
+    }
+
+    // Test raw overrides of the serialized pages
+    #[test]
+    fn test_vmsa_builder_overrides() {
+        let vmsa = VMSA::builder(VMMType::QEMU)
+            .ap_eip(0x80b004)
+            .bsp_override(0x100, &[0xaa, 0xbb])
+            .ap_override(VMSA_PAGE_SIZE - 1, &[0xcc])
+            .build()
+            .unwrap();
+
+        let bsp = vmsa.bsp_page().unwrap();
+        let ap = vmsa.ap_page().unwrap().unwrap();
+        assert_eq!(&bsp[0x100..0x102], &[0xaa, 0xbb]);
+        assert_ne!(&ap[0x100..0x102], &[0xaa, 0xbb]);
+        assert_eq!(ap[VMSA_PAGE_SIZE - 1], 0xcc);
+
+        assert!(matches!(
+            VMSA::builder(VMMType::QEMU)
+                .bsp_override(VMSA_PAGE_SIZE - 1, &[0, 0])
+                .build(),
+            Err(MeasurementError::InvalidVmsaPatch(4095, 2))
+        ));
+    }
+
+    // Test that guest features are checked and reach the VMSA pages
+    #[test]
+    fn test_guest_features() {
+        let mut features = GuestFeatures::snp(0).unwrap();
+        features.set_debug_swap(1);
+        features.set_secure_tsc(1);
+        assert_eq!(u64::from(features), 0x221);
+        assert_eq!(GuestFeatures::snp(0x220).unwrap(), features);
+
+        let vmsa = VMSA::builder(VMMType::QEMU)
+            .guest_features(features)
+            .build()
+            .unwrap();
+        let page = vmsa.bsp_page().unwrap();
+        assert_eq!(page[0x3b0..0x3b8], 0x221u64.to_le_bytes());
+
+        for reserved in [1 << 11, 1 << 13, 1 << 16, 1 << 63] {
+            assert!(matches!(
+                GuestFeatures::snp(reserved),
+                Err(MeasurementError::InvalidGuestFeatures(_))
+            ));
+            assert!(VMSA::builder(VMMType::QEMU)
+                .guest_features(GuestFeatures::from(reserved))
+                .build()
+                .is_err());
+        }
+    }
+
+    // Test that guest features flow from the KVM_SEV_INIT2 parameters
+    #[cfg(feature = "snp")]
+    #[test]
+    fn test_guest_features_gmem_init() {
+        use sev::launch::snp::gmem::GmemInit;
+        use std::convert::TryFrom;
+
+        let init = GmemInit {
+            vmsa_features: 0x20,
+            ghcb_version: 2,
+        };
+        let features = GuestFeatures::try_from(&init).unwrap();
+        assert_eq!(features, GuestFeatures::from(0x21));
+        assert_eq!(GmemInit::from(features).vmsa_features, 0x20);
+    }
+
+    // Test the signatures of the vCPU types and products
+    #[test]
+    fn test_product_signatures() {
+        use sev::measurement::vcpu_types::ProductName;
+        use std::convert::TryFrom;
+
+        assert_eq!(ProductName::Milan.sig(), 0xa00f11);
+        assert_eq!(ProductName::Genoa.sig(), 0xa10f11);
+        assert_eq!(ProductName::Bergamo.sig(), 0xaa0f02);
+        assert_eq!(ProductName::Turin.sig(), 0xb00f21);
+        assert_eq!(ProductName::EmbeddedGenoa.sig(), ProductName::Genoa.sig());
+        assert_eq!(CpuType::EpycTurin.sig(), 0xb00f00);
+
+        let product = ProductName::try_from("Turin-Dense").unwrap();
+        assert_eq!(product, ProductName::TurinDense);
+        assert_eq!(product.to_string(), "Turin-Dense");
+        assert_eq!(
+            CpuType::try_from(CpuType::EpycTurinV1.to_string().as_str()).unwrap(),
+            CpuType::EpycTurinV1
+        );
+
+        let (family, model, stepping) = product.family_model_stepping();
+        assert_eq!(
+            VMSA::builder(VMMType::QEMU)
+                .product(product)
+                .build()
+                .unwrap()
+                .bsp_page()
+                .unwrap(),
+            VMSA::builder(VMMType::QEMU)
+                .vcpu_family(family, model, stepping)
+                .build()
+                .unwrap()
+                .bsp_page()
+                .unwrap()
+        );
+    }
+
+    // Test that guests with several vCPUs need an AP reset vector
+    #[test]
+    fn test_vmsa_builder_ap_reset_vector() {
+        use sev::measurement::ovmf::ApResetVector;
+
+        assert!(matches!(
+            VMSA::builder(VMMType::QEMU).vcpus(4).build(),
+            Err(MeasurementError::MissingApResetVector(4))
+        ));
+        assert!(VMSA::builder(VMMType::QEMU).vcpus(1).build().is_ok());
+
+        let vmsa = VMSA::builder(VMMType::QEMU)
+            .ap_reset_vector(ApResetVector::new(0x80b004))
+            .vcpus(4)
+            .build()
+            .unwrap();
+        let pages = vmsa.pages(4).unwrap();
+        assert_eq!(pages.len(), 4);
+
+        let expected = VMSA::new(
+            0x80b004,
+            CpuType::EpycV4,
+            VMMType::QEMU,
+            Some(4),
+            GuestFeatures::default(),
+        );
+        assert_eq!(pages, expected.pages(4).unwrap());
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "snp"))]
+mod svsm_tests {
+    use sev::{
+        error::MeasurementError,
+        measurement::{
+            gctx::{LaunchDigest, PageUpdate},
+            svsm::*,
+            vmsa::{VMMType, VMSA},
+        },
+    };
+
+    // Test that the SVSM VMSA is measured after the IGVM pages
+    #[test]
+    fn test_svsm_calc_launch_digest() {
+        let svsm = [0x90u8; 2 * 4096];
+        let vmsa = VMSA::builder(VMMType::QEMU).build().unwrap();
+        let bsp = vmsa.bsp_page().unwrap();
+
+        let pages = vec![
+            PageUpdate::Normal {
+                gpa: 0x80_0000,
+                data: &svsm,
+            },
+            PageUpdate::Secrets { gpa: 0x80_2000 },
+            PageUpdate::Cpuid { gpa: 0x80_3000 },
+        ];
+
+        let mut expected = LaunchDigest::new();
+        expected.replay(&pages).unwrap();
+        let seed = hex::encode(expected.digest());
+        expected.update(&PageUpdate::Vmsa { page: &bsp }).unwrap();
+
+        let ld = svsm_calc_launch_digest(SvsmMeasurementArgs {
+            pages: pages.clone(),
+            vmsas: vec![&bsp],
+            pages_hash_str: None,
+        })
+        .unwrap();
+        assert_eq!(ld, expected.digest());
+
+        let ld = svsm_calc_launch_digest(SvsmMeasurementArgs {
+            pages: vec![],
+            vmsas: vec![&bsp],
+            pages_hash_str: Some(&seed),
+        })
+        .unwrap();
+        assert_eq!(ld, expected.digest());
+    }
+
+    // Test that the boot vCPU must start the SVSM at VMPL0
+    #[test]
+    fn test_svsm_layout_rejected() {
+        let vmsa = VMSA::builder(VMMType::QEMU)
+            .bsp_override(0xca, &[2])
+            .build()
+            .unwrap();
+        let bsp = vmsa.bsp_page().unwrap();
+
+        assert!(matches!(
+            svsm_calc_launch_digest(SvsmMeasurementArgs {
+                pages: vec![],
+                vmsas: vec![&bsp],
+                pages_hash_str: None,
+            }),
+            Err(MeasurementError::InvalidSvsmLayout(_))
+        ));
+        assert!(svsm_calc_launch_digest(SvsmMeasurementArgs {
+            pages: vec![],
+            vmsas: vec![],
+            pages_hash_str: None,
+        })
+        .is_err());
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "snp"))]
+mod sev_hashes_tests {
+    use sev::measurement::digest::{sha256, Sha256};
+    use sev::{
+        error::{MeasurementError, SevHashError},
+        measurement::{
+            gctx::{LaunchDigest, PageUpdate},
+            sev_hashes::*,
+        },
+    };
+
+    // Test that the builder lays out the same table as the kernel files
+    #[test]
+    fn test_builder_matches_files() {
+        let table = HashTableBuilder::new()
+            .kernel(&[])
+            .initrd(&[])
+            .cmdline(" console=ttyS0 ")
+            .build()
+            .unwrap();
+
+        let hashes = SevHashes::new(
+            "/dev/null".into(),
+            Some("/dev/null".into()),
+            Some("console=ttyS0"),
+        )
+        .unwrap();
+
+        assert_eq!(table.as_bytes(), hashes.construct_table().unwrap());
+        assert_eq!(table.as_bytes().len(), 176);
+        assert_eq!(
+            table.page(0x400).unwrap(),
+            hashes.construct_page(0x400).unwrap()
+        );
+    }
+
+    // Test that a missing initrd and cmdline are hashed the way QEMU does
+    #[test]
+    fn test_builder_defaults() {
+        let table = HashTableBuilder::new().kernel(&[]).build().unwrap();
+        let hashes = SevHashes::new("/dev/null".into(), None, None).unwrap();
+
+        assert_eq!(table.as_bytes(), hashes.construct_table().unwrap());
+
+        assert!(matches!(
+            HashTableBuilder::new().build(),
+            Err(MeasurementError::SevHashError(SevHashError::MissingKernel))
+        ));
+    }
+
+    // Test the contribution of the table to the launch digests
+    #[test]
+    fn test_measure() {
+        let table = HashTableBuilder::new().kernel(b"kernel").build().unwrap();
+
+        let mut launch_hash = Sha256::new();
+        table.measure_sev(&mut launch_hash);
+        assert_eq!(launch_hash.finish(), sha256(table.as_bytes()));
+
+        let mut digest = LaunchDigest::new();
+        table.measure_snp(&mut digest, 0x80_1400).unwrap();
+
+        let page = table.page(0x400).unwrap();
+        let mut expected = LaunchDigest::new();
+        expected
+            .update(&PageUpdate::Normal {
+                gpa: 0x80_1000,
+                data: &page,
+            })
+            .unwrap();
+
+        assert_eq!(digest.digest(), expected.digest());
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "sev", feature = "snp"))]
+mod recipe_tests {
+    use sev::{
+        error::SnapshotError,
+        measurement::{
+            calc::*,
+            recipe::*,
+            vcpu_types::CpuType,
+            vmsa::{GuestFeatures, SevMode},
+        },
+        snapshot::Versioned,
+    };
+
+    fn arguments(mode: SevMode) -> MeasurementArgs<'static> {
+        MeasurementArgs {
+            mode,
+            vcpus: 2,
+            vcpu_type: CpuType::EpycMilan,
+            ovmf_file: "./tests/measurement/ovmf_AmdSev_suffix.bin".into(),
+            guest_features: GuestFeatures(0x1),
+            kernel_file: Some("/dev/null".into()),
+            initrd_file: None,
+            append: Some("console=ttyS0"),
+            ovmf_hash_str: None,
+            vmm_type: None,
+        }
+    }
+
+    // Test that a recipe records the launch digest of its inputs
+    #[test]
+    fn test_recipe_new() {
+        for mode in [SevMode::Sev, SevMode::SevEs, SevMode::SevSnp] {
+            let recipe = MeasurementRecipe::new(&arguments(mode)).unwrap();
+
+            assert_eq!(
+                recipe.launch_digest().unwrap(),
+                calc_launch_digest(arguments(mode)).unwrap()
+            );
+            assert_eq!(
+                recipe.kernel_hashes.as_ref().unwrap().initrd,
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            );
+            assert!(recipe.reproduces(&arguments(mode)).unwrap());
+        }
+
+        let recipe = MeasurementRecipe::new(&arguments(SevMode::SevSnp)).unwrap();
+        let args = MeasurementArgs {
+            vcpus: 4,
+            ..arguments(SevMode::SevSnp)
+        };
+        assert!(!recipe.reproduces(&args).unwrap());
+    }
+
+    // Test that a recipe survives a round trip through JSON
+    #[test]
+    fn test_recipe_round_trip() {
+        let recipe = MeasurementRecipe::new(&arguments(SevMode::SevSnp))
+            .unwrap()
+            .with_policy(0x30000);
+        assert_eq!(recipe.policy, Some(0x30000));
+
+        let mut json = Vec::new();
+        recipe.to_writer(&mut json).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["kind"], "RCPE");
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["payload"]["mode"], "sev-snp");
+        assert_eq!(value["payload"]["vcpu_type"], "EPYC-Milan");
+        assert_eq!(value["payload"]["guest_features"], 1);
+
+        let loaded = MeasurementRecipe::from_reader(json.as_slice()).unwrap();
+        assert_eq!(loaded, recipe);
+        assert!(loaded.reproduces(&arguments(SevMode::SevSnp)).unwrap());
+    }
+
+    // Test that recipes of another format version are rejected
+    #[test]
+    fn test_recipe_version() {
+        let recipe = MeasurementRecipe::new(&arguments(SevMode::SevEs)).unwrap();
+
+        let mut json = Vec::new();
+        recipe.to_writer(&mut json).unwrap();
+
+        let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        value["version"] = 2.into();
+        let json = serde_json::to_vec(&value).unwrap();
+
+        assert!(matches!(
+            MeasurementRecipe::from_reader(json.as_slice()),
+            Err(SnapshotError::UnsupportedVersion { version: 2, .. })
+        ));
+
+        // Recipes are stored in binary snapshots just as well.
+        let bytes = recipe.to_versioned_bytes().unwrap();
+        assert_eq!(
+            MeasurementRecipe::from_versioned_bytes(&bytes).unwrap(),
+            recipe
+        );
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "snp"))]
+mod ovmf_hash_tests {
+    use sev::measurement::digest::sha256;
+    use sev::{
+        error::MeasurementError,
+        measurement::{
+            gctx::{LaunchDigest, PageUpdate},
+            ovmf::OVMF,
+            ovmf_hash::*,
+            snp::calc_snp_ovmf_hash,
+        },
+    };
+
+    const OVMF_FILE: &str = "./tests/measurement/ovmf_AmdSev_suffix.bin";
+
+    // Test that a whole image hashes as the launch measures it
+    #[test]
+    fn test_ovmf_hash() {
+        let ovmf = OVMF::new(OVMF_FILE.into()).unwrap();
+        let hasher = OvmfHasher::new(&ovmf);
+
+        assert_eq!(
+            hasher.snp().unwrap(),
+            calc_snp_ovmf_hash(OVMF_FILE.into()).unwrap()
+        );
+        assert_eq!(hasher.sev().unwrap(), sha256(ovmf.data()));
+    }
+
+    // Test that the SEV metadata is only excluded on request
+    #[test]
+    fn test_ovmf_hash_metadata() {
+        let ovmf = OVMF::new(OVMF_FILE.into()).unwrap();
+
+        let excluded = ovmf
+            .metadata_items()
+            .iter()
+            .fold(OvmfHasher::new(&ovmf), |hasher, desc| {
+                hasher.exclude(desc.gpa as u64..desc.gpa as u64 + desc.size as u64)
+            });
+
+        assert_eq!(
+            OvmfHasher::new(&ovmf).exclude_metadata().snp().unwrap(),
+            excluded.snp().unwrap()
+        );
+    }
+
+    // Test that unmeasured and excluded pages are honored
+    #[test]
+    fn test_ovmf_hash_regions() {
+        let mut image: Vec<u8> = (0..0x5000).map(|i| (i / 0x1000) as u8).collect();
+        image.extend_from_slice(&std::fs::read(OVMF_FILE).unwrap());
+
+        let ovmf = OVMF::from_bytes(image).unwrap();
+        let gpa = ovmf.gpa();
+        let data = ovmf.data();
+
+        let hasher = OvmfHasher::new(&ovmf)
+            .unmeasured(gpa + 0x1000..gpa + 0x3000)
+            .exclude(gpa + 0x4000..gpa + 0x5000);
+
+        let mut expected = LaunchDigest::new();
+        expected
+            .replay(&[
+                PageUpdate::Normal {
+                    gpa,
+                    data: &data[..0x1000],
+                },
+                PageUpdate::Unmeasured {
+                    gpa: gpa + 0x1000,
+                    len: 0x2000,
+                },
+                PageUpdate::Normal {
+                    gpa: gpa + 0x3000,
+                    data: &data[0x3000..0x4000],
+                },
+                PageUpdate::Normal {
+                    gpa: gpa + 0x5000,
+                    data: &data[0x5000..],
+                },
+            ])
+            .unwrap();
+        assert_eq!(hasher.snp().unwrap(), expected.digest());
+
+        let mut image = data[..0x1000].to_vec();
+        image.extend_from_slice(&data[0x3000..0x4000]);
+        image.extend_from_slice(&data[0x5000..]);
+        assert_eq!(hasher.sev().unwrap(), sha256(&image));
+
+        assert!(matches!(
+            OvmfHasher::new(&ovmf).exclude(gpa..gpa + 100).snp(),
+            Err(MeasurementError::InvalidFirmwareRegion(_, _))
+        ));
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod digest_tests {
+    use sev::measurement::{
+        digest::*,
+        gctx::{LaunchDigest, PageUpdate},
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Test the hashes of the backend the crate was built with
+    #[test]
+    fn test_default_backend() {
+        assert_eq!(
+            hex::encode(sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex::encode(sha384(b"abc")),
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"
+        );
+
+        let mut hash = Sha384::new();
+        hash.update(b"a");
+        hash.update(b"bc");
+        assert_eq!(hash.finish(), sha384(b"abc"));
+    }
+
+    /// Backend counting the hashes it starts
+    struct Counting(AtomicUsize);
+
+    impl DigestBackend for Counting {
+        fn sha256(&self) -> Box<dyn Hasher<32>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            default_backend().sha256()
+        }
+
+        fn sha384(&self) -> Box<dyn Hasher<48>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            default_backend().sha384()
+        }
+    }
+
+    static COUNTING: Counting = Counting(AtomicUsize::new(0));
+
+    // Test that the measurements hash with the backend the caller passes
+    #[test]
+    fn test_custom_backend() {
+        let mut hash = Sha384::with_backend(&COUNTING);
+        hash.update(b"abc");
+        assert_eq!(hash.finish(), sha384(b"abc"));
+        assert_eq!(COUNTING.0.load(Ordering::SeqCst), 1);
+
+        let mut digest = LaunchDigest::new().with_backend(&COUNTING);
+        digest
+            .update(&PageUpdate::Normal {
+                gpa: 0,
+                data: &[0; 0x2000],
+            })
+            .unwrap();
+        assert_eq!(COUNTING.0.load(Ordering::SeqCst), 5);
+
+        let mut expected = LaunchDigest::new();
+        expected
+            .update(&PageUpdate::Normal {
+                gpa: 0,
+                data: &[0; 0x2000],
+            })
+            .unwrap();
+        assert_eq!(digest.digest(), expected.digest());
+        assert_eq!(COUNTING.0.load(Ordering::SeqCst), 5);
+    }
+}
@@ -0,0 +1,404 @@
+// SPDX-License-Identifier: Apache-2.0
+
+#![cfg(feature = "std")]
+
+use sev::{error::ParseError, ParseOptions};
+
+#[cfg(feature = "snp")]
+mod report {
+    use super::*;
+
+    use sev::{
+        firmware::guest::{layout, AttestationReport, GuestPolicy, SigningKey},
+        Build, Version,
+    };
+
+    const TEST_MILAN_ATTESTATION_REPORT: &[u8] = include_bytes!("certs_data/report_milan.hex");
+
+    fn bytes() -> Vec<u8> {
+        hex::decode(TEST_MILAN_ATTESTATION_REPORT).unwrap()
+    }
+
+    #[test]
+    fn valid() {
+        let bytes = bytes();
+
+        for options in [
+            ParseOptions::default(),
+            ParseOptions::strict(),
+            ParseOptions::lenient(),
+        ] {
+            let report = AttestationReport::from_bytes_with(&bytes, options).unwrap();
+            assert_eq!(report.to_bytes()[..], bytes[..]);
+        }
+    }
+
+    #[test]
+    fn invalid_length() {
+        assert_eq!(
+            AttestationReport::from_bytes_with(&bytes()[1..], ParseOptions::lenient()).unwrap_err(),
+            ParseError::InvalidLength {
+                expected: AttestationReport::SIZE,
+                actual: AttestationReport::SIZE - 1,
+            }
+        );
+    }
+
+    #[test]
+    fn reserved() {
+        let mut bytes = bytes();
+        bytes[0x189] = 1;
+
+        assert!(AttestationReport::from_bytes_with(&bytes, ParseOptions::default()).is_ok());
+        assert_eq!(
+            AttestationReport::from_bytes_with(&bytes, ParseOptions::strict()).unwrap_err(),
+            ParseError::Reserved(0x189)
+        );
+
+        // Version 3 reports the CPUID model there.
+        bytes[0] = 3;
+        assert!(AttestationReport::from_bytes_with(&bytes, ParseOptions::strict()).is_ok());
+
+        bytes[0x400] = 1;
+        assert_eq!(
+            AttestationReport::from_bytes_with(&bytes, ParseOptions::strict()).unwrap_err(),
+            ParseError::Reserved(0x400)
+        );
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut bytes = bytes();
+        bytes[0] = 0x42;
+
+        assert_eq!(
+            AttestationReport::from_bytes_with(&bytes, ParseOptions::strict()).unwrap_err(),
+            ParseError::UnknownVersion(0x42)
+        );
+
+        let report = AttestationReport::from_bytes_with(&bytes, ParseOptions::lenient()).unwrap();
+        assert_eq!(report.version, 0x42);
+    }
+
+    // Integers are decoded little-endian at their offsets whatever the byte
+    // order of the host, so these hold on big-endian targets as well.
+    #[test]
+    fn little_endian() {
+        let mut bytes = [0u8; AttestationReport::SIZE];
+        let mut put = |offset: usize, field: &[u8]| {
+            bytes[offset..offset + field.len()].copy_from_slice(field);
+        };
+
+        put(0x00, &[0x02, 0x01, 0x00, 0x00]);
+        put(0x04, &[0x04, 0x03, 0x02, 0x01]);
+        put(0x08, &[0x00, 0x01, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        put(0x30, &[0x01, 0x00, 0x00, 0x00]);
+        put(0x38, &[3, 0, 0, 0, 0, 0, 8, 115]);
+        put(0x40, &[0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        put(0x48, &[0x04, 0x00, 0x00, 0x00]);
+        put(0x90, &[0xab; 48]);
+        put(0x1e8, &[24, 55, 1, 0, 21, 54, 1, 0]);
+        put(0x2a0, &[0xcd; 72]);
+
+        let report = AttestationReport::from_bytes(&bytes);
+        assert_eq!(report.version, 0x102);
+        assert_eq!(report.guest_svn, 0x0102_0304);
+        assert_eq!(report.policy.abi_major(), 1);
+        assert_eq!(report.policy.smt_allowed(), 1);
+        assert_eq!(report.policy.migrate_ma_allowed(), 1);
+        assert_eq!(report.vmpl, 1);
+        assert_eq!(report.current_tcb.bootloader, 3);
+        assert_eq!(report.current_tcb.snp, 8);
+        assert_eq!(report.current_tcb.microcode, 115);
+        assert_eq!(report.plat_info.smt_enabled(), 1);
+        assert_eq!(report.plat_info.tsme_enabled(), 1);
+        assert_eq!(report.key_info.signing_key(), Ok(SigningKey::Vlek));
+        assert_eq!(report.measurement.into_inner(), [0xab; 48]);
+        assert_eq!(
+            (
+                report.current_build,
+                report.current_minor,
+                report.current_major
+            ),
+            (24, 55, 1)
+        );
+        assert_eq!(
+            (
+                report.committed_build,
+                report.committed_minor,
+                report.committed_major
+            ),
+            (21, 54, 1)
+        );
+        assert_eq!(report.signature.r(), &[0xcd; 72]);
+        assert_eq!(
+            report.current_version(),
+            Build {
+                version: Version {
+                    major: 1,
+                    minor: 55
+                },
+                build: 24
+            }
+        );
+        assert_eq!(report.committed_version().to_string(), "1.54.21");
+
+        assert_eq!(report.to_bytes()[..], bytes[..]);
+        assert_eq!(bincode::serialize(&report).unwrap()[..], bytes[..]);
+    }
+
+    #[test]
+    fn abi_compatibility() {
+        let version = |major, minor| Version { major, minor };
+        let build = |major, minor| Build {
+            version: version(major, minor),
+            build: 4,
+        };
+
+        let mut policy = GuestPolicy(0x30000);
+        policy.set_abi_major(1);
+        policy.set_abi_minor(51);
+        assert_eq!(policy.abi_version(), version(1, 51));
+
+        assert!(policy.is_compatible_with(2, build(1, 51)));
+        assert!(policy.is_compatible_with(2, build(1, 55)));
+        assert!(policy.is_compatible_with(2, build(2, 0)));
+        assert!(!policy.is_compatible_with(2, build(1, 50)));
+        assert!(!policy.is_compatible_with(2, build(0, 99)));
+        assert!(GuestPolicy(0x30000).is_compatible_with(2, Build::default()));
+
+        // The bits added by ABI 1.55 need that ABI and a version 3 report.
+        policy.set_ciphertext_hiding(1);
+        assert!(policy.is_compatible_with(3, build(1, 55)));
+        assert!(!policy.is_compatible_with(3, build(1, 54)));
+        assert!(!policy.is_compatible_with(2, build(1, 55)));
+
+        // PAGE_SWAP_DISABLE needs ABI 1.57.
+        let swap = GuestPolicy(0x30000 | 1 << 25);
+        assert!(swap.is_compatible_with(3, build(1, 57)));
+        assert!(swap.is_compatible_with(5, build(1, 58)));
+        assert!(!swap.is_compatible_with(3, build(1, 56)));
+
+        // Reserved bits are never compatible.
+        let reserved = GuestPolicy(0x30000 | 1 << 26);
+        assert!(!reserved.is_compatible_with(5, build(1, 58)));
+    }
+
+    #[test]
+    fn layout_covers_report() {
+        for version in AttestationReport::MIN_VERSION..=AttestationReport::MAX_VERSION {
+            let mut offset = 0;
+            for field in layout::fields(version) {
+                assert_eq!(
+                    field.offset, offset,
+                    "{} of version {}",
+                    field.name, version
+                );
+                offset = field.range().end;
+            }
+
+            assert_eq!(offset, AttestationReport::SIZE);
+        }
+
+        assert_eq!(layout::fields(1), layout::V2);
+        assert_eq!(layout::fields(4), layout::V3);
+        assert_eq!(layout::fields(6), layout::V5);
+
+        assert_eq!(layout::field(2, "CPUID_FAM_ID"), None);
+        assert_eq!(layout::field(3, "CPUID_FAM_ID"), Some(layout::CPUID_FAM_ID));
+        assert_eq!(layout::field(5, "RESERVED"), None);
+    }
+
+    #[test]
+    fn layout_offsets() {
+        let mut bytes = [0u8; AttestationReport::SIZE];
+        bytes[layout::VMPL.range()].copy_from_slice(&3u32.to_le_bytes());
+        bytes[layout::MEASUREMENT.range()].fill(0xab);
+        bytes[layout::CHIP_ID.range()].fill(0xcd);
+        bytes[layout::COMMITTED_MAJOR.range()].fill(7);
+        bytes[layout::SIGNATURE_S.range()].fill(0xef);
+
+        let report = AttestationReport::from_bytes(&bytes);
+        assert_eq!(report.vmpl, 3);
+        assert_eq!(report.measurement.into_inner(), [0xab; 48]);
+        assert_eq!(report.chip_id.into_inner(), [0xcd; 64]);
+        assert_eq!(report.committed_major, 7);
+        assert_eq!(report.signature.s(), &[0xef; 72]);
+
+        // Strict parsing rejects a non-zero byte in every reserved field.
+        for version in AttestationReport::MIN_VERSION..=AttestationReport::MAX_VERSION {
+            for field in layout::fields(version).iter().filter(|f| f.is_reserved()) {
+                let mut bytes = [0u8; AttestationReport::SIZE];
+                bytes[layout::VERSION.range()].copy_from_slice(&version.to_le_bytes());
+                bytes[field.range().end - 1] = 1;
+
+                assert_eq!(
+                    AttestationReport::from_bytes_with(&bytes, ParseOptions::strict()).unwrap_err(),
+                    ParseError::Reserved(field.range().end - 1)
+                );
+            }
+        }
+    }
+}
+
+#[cfg(feature = "snp")]
+mod cert_table {
+    use super::*;
+
+    use sev::firmware::host::{CertTableEntry, CertType};
+
+    const VCEK_GUID: [u8; 16] = *uuid::uuid!("63da758d-e664-4564-adc5-f4b93be8accd").as_bytes();
+
+    fn entry(guid: [u8; 16], offset: u32, length: u32) -> Vec<u8> {
+        [&guid[..], &offset.to_le_bytes(), &length.to_le_bytes()].concat()
+    }
+
+    #[test]
+    fn valid() {
+        let bytes = [
+            entry(VCEK_GUID, 48, 4),
+            entry([0; 16], 0, 0),
+            vec![1, 2, 3, 4],
+        ]
+        .concat();
+
+        let table = CertTableEntry::parse_cert_table(&bytes, ParseOptions::strict()).unwrap();
+        assert_eq!(
+            table,
+            vec![CertTableEntry::new(CertType::VCEK, vec![1, 2, 3, 4])]
+        );
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let bytes = [
+            entry(VCEK_GUID, 48, 5),
+            entry([0; 16], 0, 0),
+            vec![1, 2, 3, 4],
+        ]
+        .concat();
+
+        assert_eq!(
+            CertTableEntry::parse_cert_table(&bytes, ParseOptions::lenient()).unwrap_err(),
+            ParseError::OutOfBounds {
+                offset: 48,
+                length: 5
+            }
+        );
+
+        let bytes = entry(VCEK_GUID, u32::MAX, u32::MAX);
+        assert!(CertTableEntry::parse_cert_table(&bytes, ParseOptions::lenient()).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = [entry(VCEK_GUID, 0, 24), entry([0; 16], 0, 0)].concat();
+
+        assert_eq!(
+            CertTableEntry::parse_cert_table(&bytes[..30], ParseOptions::lenient()).unwrap_err(),
+            ParseError::Truncated
+        );
+    }
+
+    #[test]
+    fn reserved_terminator() {
+        let bytes = entry([0; 16], 0, 1);
+
+        assert!(
+            CertTableEntry::parse_cert_table(&bytes, ParseOptions::default())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            CertTableEntry::parse_cert_table(&bytes, ParseOptions::strict()).unwrap_err(),
+            ParseError::Reserved(20)
+        );
+    }
+
+    #[test]
+    fn save_and_load_dir() {
+        let dir = std::env::temp_dir().join(format!("sev-certs-{}", std::process::id()));
+        let other = uuid::uuid!("0b7c2a9f-3f55-4b4e-9d4c-2a7f0e6d1c3b");
+
+        let table = vec![
+            CertTableEntry::new(CertType::VCEK, vec![1]),
+            CertTableEntry::new(CertType::OTHER(other), vec![4]),
+            CertTableEntry::new(CertType::ARK, vec![3]),
+            CertTableEntry::new(CertType::ASK, vec![2]),
+        ];
+        CertTableEntry::save_dir(&table, &dir).unwrap();
+
+        assert_eq!(std::fs::read(dir.join("vcek.der")).unwrap(), [1]);
+        assert_eq!(std::fs::read(dir.join("ask.der")).unwrap(), [2]);
+        assert_eq!(std::fs::read(dir.join("ark.der")).unwrap(), [3]);
+        assert_eq!(
+            std::fs::read(dir.join(format!("{}.bin", other))).unwrap(),
+            [4]
+        );
+
+        // Unrelated files are left alone.
+        std::fs::write(dir.join("report.bin"), [5]).unwrap();
+
+        let mut sorted = table;
+        sorted.sort();
+        assert_eq!(CertTableEntry::load_dir(&dir).unwrap(), sorted);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[cfg(feature = "sev")]
+mod sev_cert {
+    use super::*;
+
+    use ::sev::certs::sev::{builtin::naples::ARK, ca, sev};
+
+    use codicon::Decoder;
+
+    use std::io::ErrorKind;
+
+    const CEK: &[u8] = include_bytes!("naples/cek.cert");
+
+    fn parse_error(error: std::io::Error) -> ParseError {
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        *error.into_inner().unwrap().downcast().unwrap()
+    }
+
+    #[test]
+    fn valid() {
+        let cek = sev::Certificate::decode(&mut &CEK[..], ()).unwrap();
+        let strict = sev::Certificate::decode(&mut &CEK[..], ParseOptions::strict()).unwrap();
+        assert_eq!(cek, strict);
+
+        ca::Certificate::decode(&mut &ARK[..], ParseOptions::strict()).unwrap();
+    }
+
+    #[test]
+    fn reserved() {
+        let mut cek = CEK.to_vec();
+        cek[7] = 1;
+
+        sev::Certificate::decode(&mut &cek[..], ()).unwrap();
+        let error = sev::Certificate::decode(&mut &cek[..], ParseOptions::strict()).unwrap_err();
+        assert_eq!(parse_error(error), ParseError::Reserved(7));
+
+        let mut ark = ARK.to_vec();
+        ark[40] = 1;
+
+        ca::Certificate::decode(&mut &ark[..], ()).unwrap();
+        let error = ca::Certificate::decode(&mut &ark[..], ParseOptions::strict()).unwrap_err();
+        assert_eq!(parse_error(error), ParseError::Reserved(40));
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut cek = CEK.to_vec();
+        cek[0] = 2;
+
+        let error = sev::Certificate::decode(&mut &cek[..], ()).unwrap_err();
+        assert_eq!(parse_error(error), ParseError::UnknownVersion(2));
+
+        let cert = sev::Certificate::decode(&mut &cek[..], ParseOptions::lenient()).unwrap();
+        assert_eq!(format!("{:?}", cert), "Certificate { version: 2 }");
+    }
+}
@@ -36,4 +36,28 @@ mod initialized {
             })
             .unwrap();
     }
+
+    #[test]
+    fn verify_mismatch() {
+        use ::sev::{error::SessionError, Build, Version};
+
+        let session = Session::try_from(launch::sev::Policy::default()).unwrap();
+        let build = Build {
+            version: Version {
+                major: 0,
+                minor: 24,
+            },
+            build: 15,
+        };
+        let measurement = launch::sev::Measurement {
+            measure: [0; 32],
+            mnonce: [0; 16],
+        };
+
+        match session.verify(&[0; 32], build, measurement) {
+            Err(SessionError::MeasurementMismatch) => (),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("a forged measurement verified"),
+        }
+    }
 }
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: Apache-2.0
+
+#![cfg(feature = "std")]
+
+use sev::snapshot::Versioned;
+
+#[cfg(feature = "snp")]
+mod common;
+
+#[cfg(feature = "snp")]
+mod snp {
+    use super::*;
+
+    use crate::common::report;
+
+    use sev::firmware::guest::AttestationReport;
+
+    #[test]
+    fn report_round_trip() {
+        let report = report();
+
+        let bytes = report.to_versioned_bytes().unwrap();
+        assert_eq!(&bytes[..10], b"SEVSSNPR\x01\x00");
+
+        let decoded = AttestationReport::from_versioned_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes()[..], report.to_bytes()[..]);
+    }
+}
+
+#[cfg(feature = "sev")]
+mod sev_chain {
+    use super::*;
+
+    use ::sev::{
+        certs::sev::{builtin::naples::*, ca, sev, Chain},
+        error::SnapshotError,
+    };
+
+    use codicon::Decoder;
+
+    const CEK: &[u8] = include_bytes!("naples/cek.cert");
+    const OCA: &[u8] = include_bytes!("naples/oca.cert");
+    const PEK: &[u8] = include_bytes!("naples/pek.cert");
+    const PDH: &[u8] = include_bytes!("naples/pdh.cert");
+
+    fn chain() -> Chain {
+        Chain {
+            ca: ca::Chain {
+                ark: ca::Certificate::decode(&mut &ARK[..], ()).unwrap(),
+                ask: ca::Certificate::decode(&mut &ASK[..], ()).unwrap(),
+            },
+            sev: sev::Chain {
+                cek: sev::Certificate::decode(&mut &CEK[..], ()).unwrap(),
+                oca: sev::Certificate::decode(&mut &OCA[..], ()).unwrap(),
+                pek: sev::Certificate::decode(&mut &PEK[..], ()).unwrap(),
+                pdh: sev::Certificate::decode(&mut &PDH[..], ()).unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn chain_round_trip() {
+        let chain = chain();
+
+        let bytes = chain.to_versioned_bytes().unwrap();
+        assert_eq!(Chain::from_versioned_bytes(&bytes).unwrap(), chain);
+
+        let bytes = chain.ca.to_versioned_bytes().unwrap();
+        assert_eq!(ca::Chain::from_versioned_bytes(&bytes).unwrap(), chain.ca);
+    }
+
+    #[test]
+    fn chain_kind_mismatch() {
+        let bytes = chain().ca.to_versioned_bytes().unwrap();
+
+        match Chain::from_versioned_bytes(&bytes) {
+            Err(SnapshotError::Kind { expected, actual }) => {
+                assert_eq!(&expected, b"SEVC");
+                assert_eq!(&actual, b"OCAC");
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "sev"))]
+#[test]
+fn session_newer_version() {
+    use sev::{error::SnapshotError, launch::sev::Session};
+
+    let session = Session {
+        nonce: [1; 16],
+        wrap_tk: [2; 32],
+        wrap_iv: [3; 16],
+        wrap_mac: [4; 32],
+        policy_mac: [5; 32],
+    };
+
+    let mut bytes = session.to_versioned_bytes().unwrap();
+    assert_eq!(Session::from_versioned_bytes(&bytes).unwrap(), session);
+
+    bytes[8] = 2;
+    assert!(matches!(
+        Session::from_versioned_bytes(&bytes),
+        Err(SnapshotError::UnsupportedVersion { version: 2, .. })
+    ));
+}